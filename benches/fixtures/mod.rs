@@ -0,0 +1,136 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Fixtures shared by the benchmarks.
+
+use routing::{
+    bls_key_set_from_elders_info, elders_info_for_test, mock::Network,
+    section_proof_chain_from_elders_info, Authority, EventStream, FullId, MessageContent,
+    NetworkConfig, Node, Prefix, RoutingMessage, SignedRoutingMessage, XorName,
+};
+use std::collections::BTreeSet;
+use unwrap::unwrap;
+
+// Maximum number of polls before `poll_until_idle` gives up.
+const MAX_POLL_CALLS: usize = 1000;
+
+/// Generates the ids of `count` elders.
+pub fn gen_elder_ids(count: usize) -> Vec<FullId> {
+    (0..count).map(|_| FullId::new()).collect()
+}
+
+/// Generates `size` bytes of random user content.
+pub fn gen_user_content(size: usize) -> Vec<u8> {
+    (0..size).map(|_| rand::random()).collect()
+}
+
+/// Generates a message from a random section to another one, carrying `size` bytes of user
+/// content.
+pub fn gen_section_message(size: usize) -> RoutingMessage {
+    RoutingMessage {
+        src: Authority::Section(rand::random()),
+        dst: Authority::Section(rand::random()),
+        content: MessageContent::UserMessage(gen_user_content(size)),
+    }
+}
+
+/// Returns `msg` as signed by each of the elders, i.e. the signature shares an accumulating elder
+/// would receive.
+pub fn sign_by_each(msg: &RoutingMessage, elder_ids: &[FullId]) -> Vec<SignedRoutingMessage> {
+    let members: BTreeSet<_> = elder_ids.iter().map(|id| *id.public_id()).collect();
+    let elders_info = unwrap!(elders_info_for_test(members, Prefix::default(), 0));
+    let pk_set = bls_key_set_from_elders_info(elders_info.clone());
+    let proof = section_proof_chain_from_elders_info(&elders_info);
+
+    elder_ids
+        .iter()
+        .map(|id| {
+            unwrap!(SignedRoutingMessage::new(
+                msg.clone(),
+                id,
+                pk_set.clone(),
+                proof.clone()
+            ))
+        })
+        .collect()
+}
+
+/// Returns `msg` signed by all the elders, with the signatures combined.
+pub fn sign_and_combine(msg: &RoutingMessage, elder_ids: &[FullId]) -> SignedRoutingMessage {
+    let mut shares = sign_by_each(msg, elder_ids).into_iter();
+    let mut signed_msg = unwrap!(shares.next());
+    for share in shares {
+        signed_msg.add_signature_shares(share);
+    }
+    signed_msg.combine_signatures();
+    signed_msg
+}
+
+/// Generates `count` names, each within a random one of `prefixes`.
+pub fn gen_names_in(prefixes: &[Prefix<XorName>], count: usize) -> Vec<XorName> {
+    (0..count)
+        .map(|index| prefixes[index % prefixes.len()].substituted_in(rand::random()))
+        .collect()
+}
+
+/// Generates all the prefixes of the given bit count.
+pub fn gen_prefixes(bit_count: usize) -> Vec<Prefix<XorName>> {
+    let mut prefixes = vec![Prefix::default()];
+    for _ in 0..bit_count {
+        prefixes = prefixes
+            .into_iter()
+            .flat_map(|prefix| vec![prefix.pushed(false), prefix.pushed(true)])
+            .collect();
+    }
+    prefixes
+}
+
+/// Creates a single section of `size` nodes on the given mock network and polls it until idle.
+pub fn create_section(network: &Network, size: usize) -> Vec<Node> {
+    let mut nodes = vec![unwrap!(Node::builder()
+        .first(true)
+        .min_section_size(network.min_section_size())
+        .create())];
+    let _ = nodes[0].poll();
+    let endpoint = unwrap!(nodes[0].our_connection_info()).peer_addr;
+
+    for _ in 1..size {
+        let config = NetworkConfig::node().with_hard_coded_contact(endpoint);
+        nodes.push(unwrap!(Node::builder()
+            .network_config(config)
+            .min_section_size(network.min_section_size())
+            .create()));
+        let _ = poll_until_idle(network, &mut nodes);
+    }
+
+    // Discard the events produced while forming the section.
+    for node in &mut nodes {
+        while node.try_next_ev().is_ok() {}
+    }
+
+    nodes
+}
+
+/// Polls the network and the nodes until no more messages are exchanged. Returns the number of
+/// rounds it took.
+pub fn poll_until_idle(network: &Network, nodes: &mut [Node]) -> usize {
+    for round in 0..MAX_POLL_CALLS {
+        network.poll();
+
+        let mut handled_message = false;
+        for node in nodes.iter_mut() {
+            handled_message = node.poll() || handled_message;
+        }
+
+        if !handled_message && !network.reset_message_sent() {
+            return round;
+        }
+    }
+
+    panic!("poll_until_idle has been called {} times.", MAX_POLL_CALLS);
+}
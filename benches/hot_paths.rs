@@ -0,0 +1,152 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Benchmarks of the hot paths of routing. Run with `cargo bench --features=mock_base`.
+//! Enabling `mock_crypto` too makes the signature benchmarks meaningless, as the mock signatures
+//! are nearly free.
+
+mod fixtures;
+
+use self::fixtures::{
+    create_section, gen_elder_ids, gen_names_in, gen_prefixes, gen_section_message,
+    gen_user_content, poll_until_idle, sign_and_combine, sign_by_each,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use routing::{
+    mock::Network, Authority, Prefix, SignatureAccumulator, SignedRoutingMessage, XorName, Xorable,
+    MIN_SECTION_SIZE,
+};
+use unwrap::unwrap;
+
+// Elder counts to run the section message benchmarks with.
+const ELDER_COUNTS: &[usize] = &[3, 7, 15];
+// Size of the user content of the benchmarked messages.
+const MSG_SIZE: usize = 1024;
+
+fn signed_message(c: &mut Criterion) {
+    let full_id = unwrap!(gen_elder_ids(1).pop());
+    let msg = gen_section_message(MSG_SIZE);
+    let _ = c.bench_function("signed_message/single_source", move |b| {
+        b.iter(|| unwrap!(SignedRoutingMessage::single_source(msg.clone(), &full_id)))
+    });
+
+    let _ = c.bench_function_over_inputs(
+        "signed_message/sign_and_combine",
+        |b, &&count| {
+            let elder_ids = gen_elder_ids(count);
+            let msg = gen_section_message(MSG_SIZE);
+            b.iter(|| sign_and_combine(&msg, &elder_ids))
+        },
+        ELDER_COUNTS,
+    );
+
+    let _ = c.bench_function_over_inputs(
+        "signed_message/check_integrity",
+        |b, &&count| {
+            let signed_msg =
+                sign_and_combine(&gen_section_message(MSG_SIZE), &gen_elder_ids(count));
+            b.iter(|| unwrap!(signed_msg.check_integrity()))
+        },
+        ELDER_COUNTS,
+    );
+}
+
+fn accumulator(c: &mut Criterion) {
+    let _ = c.bench_function_over_inputs(
+        "accumulator/add_proof",
+        |b, &&count| {
+            let elder_ids = gen_elder_ids(count);
+            let shares = sign_by_each(&gen_section_message(MSG_SIZE), &elder_ids);
+            b.iter_with_setup(
+                || (SignatureAccumulator::default(), shares.clone()),
+                |(mut accumulator, shares)| {
+                    let accumulated = shares
+                        .into_iter()
+                        .filter_map(|share| accumulator.add_proof(share))
+                        .count();
+                    assert_eq!(accumulated, 1);
+                },
+            )
+        },
+        ELDER_COUNTS,
+    );
+}
+
+fn prefix_and_name(c: &mut Criterion) {
+    let prefixes = gen_prefixes(6);
+    let names = gen_names_in(&prefixes, 1000);
+
+    let bench_prefixes = prefixes.clone();
+    let bench_names = names.clone();
+    let _ = c.bench_function("prefix/matches", move |b| {
+        b.iter(|| {
+            bench_names
+                .iter()
+                .filter_map(|name| bench_prefixes.iter().find(|prefix| prefix.matches(name)))
+                .count()
+        })
+    });
+
+    let bench_prefixes = prefixes.clone();
+    let _ = c.bench_function("prefix/is_compatible", move |b| {
+        b.iter(|| {
+            bench_prefixes
+                .iter()
+                .map(|prefix| {
+                    bench_prefixes
+                        .iter()
+                        .filter(|other| prefix.is_compatible(other))
+                        .count()
+                })
+                .sum::<usize>()
+        })
+    });
+
+    let _ = c.bench_function("prefix/is_covered_by", move |b| {
+        b.iter(|| Prefix::default().is_covered_by(&prefixes))
+    });
+
+    let _ = c.bench_function("xor_name/sort_by_distance", move |b| {
+        b.iter_with_setup(
+            || (rand::random::<XorName>(), names.clone()),
+            |(target, mut names)| names.sort_by(|lhs, rhs| target.cmp_distance(lhs, rhs)),
+        )
+    });
+}
+
+fn network_poll(c: &mut Criterion) {
+    let _ = c.bench_function("network_poll/form_section", |b| {
+        b.iter(|| {
+            let network = Network::new(MIN_SECTION_SIZE, None);
+            create_section(&network, MIN_SECTION_SIZE)
+        })
+    });
+
+    let _ = c.bench_function("network_poll/user_messages", |b| {
+        let network = Network::new(MIN_SECTION_SIZE, None);
+        let mut nodes = create_section(&network, MIN_SECTION_SIZE);
+        let names: Vec<_> = nodes.iter().map(|node| *unwrap!(node.our_name())).collect();
+
+        b.iter(|| {
+            for (node, dst) in nodes.iter_mut().zip(names.iter().rev()) {
+                let src = Authority::Node(*unwrap!(node.our_name()));
+                let _ = node.send_message(src, Authority::Node(*dst), gen_user_content(MSG_SIZE));
+            }
+            poll_until_idle(&network, &mut nodes)
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    signed_message,
+    accumulator,
+    prefix_and_name,
+    network_poll
+);
+criterion_main!(benches);
@@ -7,10 +7,13 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::error::InterfaceError;
+use crate::event_stream::EventWaker;
 use crate::id::PublicId;
 use crate::routing_table::Authority;
+use crate::types::MessageId;
 use crate::xor_name::XorName;
 use crate::NetworkBytes;
+use crossbeam_channel as mpmc;
 use hex_fmt::HexFmt;
 use quic_p2p::Token;
 use std::fmt::{self, Debug, Formatter};
@@ -30,12 +33,57 @@ pub enum Action {
         src: Authority<XorName>,
         dst: Authority<XorName>,
         content: Vec<u8>,
+        trace_id: Option<MessageId>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendMessageToMany {
+        src: Authority<XorName>,
+        dsts: Vec<Authority<XorName>>,
+        content: Vec<u8>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendMessageWithAck {
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        msg_id: MessageId,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendStream {
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        stream_id: MessageId,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendUserRequest {
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        token: MessageId,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendUserResponse {
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        token: MessageId,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    RelayClientMessage {
+        client_id: PublicId,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
     GetId {
         result_tx: Sender<PublicId>,
     },
     HandleTimeout(u64),
+    RequestProofChain {
+        from_version: u64,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
     DisconnectClient {
         peer_addr: SocketAddr,
         result_tx: Sender<Result<(), InterfaceError>>,
@@ -46,19 +94,130 @@ pub enum Action {
         token: Token,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
+    PruneParsec {
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendRequest {
+        msg: NetworkBytes,
+        token: Token,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    SendClientRequest {
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    InitiateLeave {
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    StepDown {
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    ReconnectToNeighbours {
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
     Terminate,
 }
 
+/// Sends actions to the state machine, waking the task polling the node, if any, on each send.
+#[derive(Clone)]
+pub struct ActionSender {
+    tx: mpmc::Sender<Action>,
+    waker: EventWaker,
+}
+
+impl ActionSender {
+    pub fn new(tx: mpmc::Sender<Action>, waker: EventWaker) -> Self {
+        Self { tx, waker }
+    }
+
+    // Only the timer sends actions from outside the machine, and the mock one never does.
+    #[allow(unused)]
+    pub fn send(&self, action: Action) -> Result<(), mpmc::SendError<Action>> {
+        let result = self.tx.send(action);
+        self.waker.wake();
+        result
+    }
+}
+
 impl Debug for Action {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match *self {
-            Action::SendMessage { ref content, .. } => write!(
+            Action::SendMessage {
+                ref content,
+                ref trace_id,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendMessage {{ \"{:<8}\", {:?}, result_tx }}",
+                HexFmt(content),
+                trace_id
+            ),
+            Action::SendMessageToMany {
+                ref dsts,
+                ref content,
+                ..
+            } => write!(
                 formatter,
-                "Action::SendMessage {{ \"{:<8}\", result_tx }}",
+                "Action::SendMessageToMany {{ {:?}, \"{:<8}\", result_tx }}",
+                dsts,
+                HexFmt(content)
+            ),
+            Action::SendMessageWithAck {
+                ref content,
+                ref msg_id,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendMessageWithAck {{ \"{:<8}\", {:?}, result_tx }}",
+                HexFmt(content),
+                msg_id
+            ),
+            Action::SendStream {
+                ref content,
+                ref stream_id,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendStream {{ \"{:<8}\", {:?}, result_tx }}",
+                HexFmt(content),
+                stream_id
+            ),
+            Action::SendUserRequest {
+                ref content,
+                ref token,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendUserRequest {{ \"{:<8}\", {:?}, result_tx }}",
+                HexFmt(content),
+                token
+            ),
+            Action::SendUserResponse {
+                ref content,
+                ref token,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendUserResponse {{ \"{:<8}\", {:?}, result_tx }}",
+                HexFmt(content),
+                token
+            ),
+            Action::RelayClientMessage {
+                ref client_id,
+                ref content,
+                ..
+            } => write!(
+                formatter,
+                "Action::RelayClientMessage {{ {}, \"{:<8}\", result_tx }}",
+                client_id,
                 HexFmt(content)
             ),
             Action::GetId { .. } => write!(formatter, "Action::GetId"),
             Action::HandleTimeout(token) => write!(formatter, "Action::HandleTimeout({})", token),
+            Action::RequestProofChain { from_version, .. } => {
+                write!(formatter, "Action::RequestProofChain({})", from_version)
+            }
             Action::DisconnectClient { peer_addr, .. } => {
                 write!(formatter, "Action::DisconnectClient: {}", peer_addr)
             }
@@ -69,6 +228,20 @@ impl Debug for Action {
                 "Action::SendMessageToClient: {}, token: {}",
                 peer_addr, token
             ),
+            Action::PruneParsec { .. } => write!(formatter, "Action::PruneParsec"),
+            Action::SendRequest { token, .. } => {
+                write!(formatter, "Action::SendRequest, token: {}", token)
+            }
+            Action::SendClientRequest { ref content, .. } => write!(
+                formatter,
+                "Action::SendClientRequest {{ \"{:<8}\", result_tx }}",
+                HexFmt(content)
+            ),
+            Action::InitiateLeave { .. } => write!(formatter, "Action::InitiateLeave"),
+            Action::StepDown { .. } => write!(formatter, "Action::StepDown"),
+            Action::ReconnectToNeighbours { .. } => {
+                write!(formatter, "Action::ReconnectToNeighbours")
+            }
             Action::Terminate => write!(formatter, "Action::Terminate"),
         }
     }
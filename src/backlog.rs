@@ -0,0 +1,101 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    network_params::BacklogLimits,
+    time::{Duration, Instant},
+};
+use std::collections::VecDeque;
+
+/// Items put aside until we can handle them, e.g. the messages received while joining. Bounded
+/// both in length and in the age of its items so that a flood of them can't exhaust our memory:
+/// expired items are dropped and, once full, the oldest item is evicted to make room for a new
+/// one.
+pub struct Backlog<T> {
+    items: VecDeque<(T, Instant)>,
+    max_len: usize,
+    max_age: Duration,
+    dropped: u64,
+}
+
+impl<T> Backlog<T> {
+    pub fn new(limits: BacklogLimits) -> Self {
+        Self {
+            items: VecDeque::new(),
+            max_len: limits.max_len,
+            max_age: limits.max_age,
+            dropped: 0,
+        }
+    }
+
+    /// Adds `item`, evicting the oldest item if we are full.
+    pub fn push(&mut self, item: T) {
+        self.evict_expired();
+
+        if self.max_len == 0 {
+            self.dropped += 1;
+            return;
+        }
+
+        if self.items.len() >= self.max_len {
+            let _ = self.items.pop_front();
+            self.dropped += 1;
+        }
+
+        self.items.push_back((item, Instant::now()));
+    }
+
+    /// Removes and returns the items which haven't expired yet, oldest first.
+    pub fn take_all(&mut self) -> Vec<T> {
+        self.evict_expired();
+        self.items.drain(..).map(|(item, _)| item).collect()
+    }
+
+    /// Returns the items which haven't expired yet, oldest first.
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.take_all()
+    }
+
+    /// Returns the number of items we dropped because they expired or to make room for new ones.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn evict_expired(&mut self) {
+        let max_age = self.max_age;
+        let len = self.items.len();
+        self.items.retain(|(_, added)| added.elapsed() < max_age);
+        self.dropped += (len - self.items.len()) as u64;
+    }
+}
+
+#[cfg(all(test, feature = "mock_base"))]
+mod tests {
+    use super::*;
+    use fake_clock::FakeClock;
+
+    #[test]
+    fn evicts_oldest_and_expired_items() {
+        let mut backlog = Backlog::new(BacklogLimits {
+            max_len: 2,
+            max_age: Duration::from_secs(10),
+        });
+
+        backlog.push(0);
+        backlog.push(1);
+        backlog.push(2);
+        assert_eq!(backlog.dropped(), 1);
+        assert_eq!(backlog.take_all(), vec![1, 2]);
+
+        backlog.push(3);
+        FakeClock::advance_time(6000);
+        backlog.push(4);
+        FakeClock::advance_time(6000);
+        assert_eq!(backlog.into_vec(), vec![4]);
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{AccumulatingEvent, ProofSet};
+use crate::id::PublicId;
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt::Write,
+};
+
+/// Number of entries the `AuditLog` keeps. Recording more drops the oldest ones.
+pub const AUDIT_LOG_LEN: usize = 1024;
+
+/// An event which reached consensus in our section, as recorded in the `AuditLog`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditEntry {
+    /// Version of our section's elders info when the event accumulated.
+    pub chain_version: u64,
+    /// Name of the `AccumulatingEvent` variant.
+    pub kind: &'static str,
+    /// Debug representation of the event.
+    pub description: String,
+    /// The elders whose votes made the event accumulate.
+    pub voters: BTreeSet<PublicId>,
+    /// Number of elders of our section when the event accumulated.
+    pub elder_count: usize,
+}
+
+/// Log of the last `AUDIT_LOG_LEN` events polled out of the chain accumulator, oldest first.
+/// Obtained through `ChainView::audit_log`, it tells which elders agreed on what and when, after
+/// the fact. Entries which should outlive the log are to be taken out with `Node::drain_audit_log`.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    // Number of entries dropped to make room for newer ones.
+    dropped: u64,
+}
+
+impl AuditLog {
+    pub(super) fn record(
+        &mut self,
+        chain_version: u64,
+        event: &AccumulatingEvent,
+        proofs: &ProofSet,
+        elder_count: usize,
+    ) {
+        if self.entries.len() == AUDIT_LOG_LEN {
+            let _ = self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(AuditEntry {
+            chain_version,
+            kind: event.kind(),
+            description: format!("{:?}", event),
+            voters: proofs.ids().copied().collect(),
+            elder_count,
+        })
+    }
+
+    /// Returns the entries still in the log, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &AuditEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of entries still in the log.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the log is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of entries dropped so far because the log was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the entries of the given kind.
+    pub fn entries_of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a AuditEntry> {
+        self.entries.iter().filter(move |entry| entry.kind == kind)
+    }
+
+    /// Returns the entries which accumulated at the given chain version or later.
+    pub fn entries_since(&self, chain_version: u64) -> impl Iterator<Item = &AuditEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.chain_version >= chain_version)
+    }
+
+    /// Removes all the entries from the log and returns them, oldest first.
+    pub fn drain(&mut self) -> Vec<AuditEntry> {
+        self.entries.drain(..).collect()
+    }
+
+    /// Returns the log as text, one entry per line.
+    pub fn export(&self) -> String {
+        let mut output = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(
+                output,
+                "v{} {} ({}/{} votes) {}",
+                entry.chain_version,
+                entry.kind,
+                entry.voters.len(),
+                entry.elder_count,
+                entry.description
+            );
+        }
+        output
+    }
+}
@@ -11,10 +11,12 @@ use super::EldersInfo;
 #[cfg(feature = "mock_base")]
 use super::ProofSet;
 use crate::{
-    crypto,
+    crypto::{self, encryption},
     id::{FullId, PublicId},
     QUORUM_DENOMINATOR, QUORUM_NUMERATOR,
 };
+use maidsafe_utilities::serialisation::{deserialise, serialise, SerialisationError};
+use rand_crypto::{rngs::StdRng, CryptoRng, Rng, RngCore, SeedableRng};
 use std::{collections::BTreeMap, fmt};
 
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
@@ -38,6 +40,36 @@ pub struct Signature {
     sigs: BTreeMap<PublicId, SignatureShare>,
 }
 
+/// A message encrypted to the public key of a section. Emulated by encrypting the message with a
+/// one-off key, and the seed of that key to each of the elders, so that any of them can decrypt it
+/// on its own rather than combining decryption shares.
+#[derive(Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
+pub struct Ciphertext {
+    content: Vec<u8>,
+    seeds: BTreeMap<PublicId, Vec<u8>>,
+}
+
+impl Ciphertext {
+    /// Decrypts the message, if `full_id` is one of the elders it was encrypted to.
+    pub fn decrypt(&self, full_id: &FullId) -> Option<Vec<u8>> {
+        let seed = full_id.decrypt(self.seeds.get(full_id.public_id())?)?;
+        if seed.len() != KEY_SEED_LEN {
+            return None;
+        }
+
+        let mut key_seed = [0; KEY_SEED_LEN];
+        key_seed.copy_from_slice(&seed);
+        let content: encryption::Ciphertext = deserialise(&self.content).ok()?;
+        one_off_key(key_seed).decrypt(&content)
+    }
+}
+
+const KEY_SEED_LEN: usize = 32;
+
+fn one_off_key(seed: [u8; KEY_SEED_LEN]) -> encryption::SecretKey {
+    StdRng::from_seed(seed).gen()
+}
+
 impl Signature {
     #[cfg(feature = "mock_base")]
     pub fn from_proof_set(proofs: ProofSet) -> Self {
@@ -117,6 +149,41 @@ impl PublicKey {
     }
 }
 
+impl PublicKey {
+    /// Encrypts `msg` to the elders holding this key. The ciphertext only depends on `msg` and the
+    /// state of `rng`.
+    pub fn encrypt_with_rng<R, M>(
+        &self,
+        rng: &mut R,
+        msg: M,
+    ) -> Result<Ciphertext, SerialisationError>
+    where
+        R: RngCore + CryptoRng,
+        M: AsRef<[u8]>,
+    {
+        let key_seed: [u8; KEY_SEED_LEN] = rng.gen();
+        let content = serialise(
+            &one_off_key(key_seed)
+                .public_key()
+                .encrypt_with_rng(rng, msg),
+        )?;
+        let seeds = self
+            .0
+            .elders_info
+            .members()
+            .iter()
+            .map(|pub_id| {
+                let seed = pub_id
+                    .public_encryption_key()
+                    .encrypt_with_rng(rng, &key_seed);
+                serialise(&seed).map(|seed| (*pub_id, seed))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Ciphertext { content, seeds })
+    }
+}
+
 impl fmt::Debug for PublicKey {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(formatter, "BLS-PublicKey({:?})", self.0.elders_info)
@@ -187,4 +254,21 @@ mod test {
 
         assert!(pk_set.public_key().verify(&sig, &data));
     }
+
+    #[test]
+    fn test_encryption() {
+        let (pk_set, sk_shares) = gen_section(4);
+        let data = [1u8, 2, 3, 4, 5, 6];
+        let mut rng = StdRng::from_seed([7; 32]);
+
+        let ciphertext = unwrap!(pk_set.public_key().encrypt_with_rng(&mut rng, &data));
+        for sk_share in &sk_shares {
+            assert_eq!(ciphertext.decrypt(&sk_share.0), Some(data.to_vec()));
+        }
+        assert_eq!(ciphertext.decrypt(&FullId::new()), None);
+
+        // The same seed yields the same ciphertext.
+        let mut rng = StdRng::from_seed([7; 32]);
+        assert!(unwrap!(pk_set.public_key().encrypt_with_rng(&mut rng, &data)) == ciphertext);
+    }
 }
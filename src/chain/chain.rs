@@ -7,33 +7,49 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    chain_accumulator::{AccumulatingProof, ChainAccumulator, InsertError},
+    chain_accumulator::{
+        AccumulatingProof, AccumulatorMetrics, AccumulatorState, ChainAccumulator, InsertError,
+    },
     shared_state::{PrefixChange, SectionKeyInfo, SharedState},
-    AccumulatingEvent, AgeCounter, EldersInfo, GenesisPfxInfo, MemberPersona, MemberState,
-    NetworkEvent, Proof, ProofSet, SectionProofChain,
+    AccumulatingEvent, AgeCounter, AuditEntry, AuditLog, ConflictingVotesPayload, EldersInfo,
+    GenesisPfxInfo, MemberInfo, MemberPersona, MemberState, NetworkEvent, Proof, ProofSet,
+    SectionProofChain,
 };
 use crate::{
     error::RoutingError,
     id::PublicId,
-    routing_table::{Authority, Error},
+    messages::RelocateDetails,
+    network_params::NetworkParams,
+    routing_table::{Authority, Error, MAX_CLOSE_GROUP_SIZE},
+    time::Duration,
     utils::LogIdent,
-    BlsPublicKeySet, Prefix, XorName, Xorable, SAFE_SECTION_SIZE,
+    BlsPublicKey, BlsPublicKeySet, Prefix, XorName, Xorable, SAFE_SECTION_SIZE,
 };
 use itertools::Itertools;
 use log::LogLevel;
+use maidsafe_utilities::serialisation;
 use std::cmp::Ordering;
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::{self, Debug, Display, Formatter},
     iter, mem,
 };
 
 #[cfg(feature = "mock_base")]
-use {super::MemberInfo, crate::crypto::Digest256};
+use crate::crypto::Digest256;
 
-/// Amount added to `min_section_size` when deciding whether a bucket split can happen. This helps
-/// protect against rapid splitting and merging in the face of moderate churn.
-const SPLIT_BUFFER: usize = 1;
+/// Default amount added to `min_section_size` when deciding whether a bucket split can happen.
+/// This helps protect against rapid splitting and merging in the face of moderate churn. See
+/// `NetworkParams::split_buffer`.
+pub const SPLIT_BUFFER: usize = 1;
+
+// Number of our latest section infos kept in the chain. Older ones, and the keys only they need,
+// are dropped so the chain doesn't grow for as long as the section lives.
+const MAX_OUR_INFOS: usize = 10;
+
+// Number of versions the key a section signed a message with can lag behind the latest key we know
+// for that section before we reject the message as obsolete, e.g. replayed after elder churn.
+const MAX_KEY_VERSION_LAG: u64 = 8;
 
 /// Returns the delivery group size based on the section size `n`
 pub fn delivery_group_size(n: usize) -> usize {
@@ -43,8 +59,8 @@ pub fn delivery_group_size(n: usize) -> usize {
 
 /// Data chain.
 pub struct Chain {
-    /// Minimum number of nodes we consider acceptable in a section
-    min_sec_size: usize,
+    /// The parameters of the network, including the minimum section size.
+    network_params: NetworkParams,
     /// This node's public ID.
     our_id: PublicId,
     /// The shared state of the section.
@@ -59,19 +75,30 @@ pub struct Chain {
     /// Temporary. Counting the accumulated prune events. Only used in tests until tests that
     /// actually tests pruning is in place.
     parsec_prune_accumulated: usize,
+    /// Members whose relocation out of our section started but didn't complete yet.
+    relocations_in_flight: BTreeSet<PublicId>,
+    /// Relocations waiting for one of the ones in flight to complete.
+    relocation_queue: VecDeque<RelocateDetails>,
+    /// The events polled out of the accumulator so far.
+    audit_log: AuditLog,
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl Chain {
     /// Returns the minimum section size.
     pub fn min_sec_size(&self) -> usize {
-        self.min_sec_size
+        self.network_params.min_section_size
+    }
+
+    /// Returns the parameters of the network.
+    pub fn network_params(&self) -> &NetworkParams {
+        &self.network_params
     }
 
     /// Returns the number of nodes which need to exist in each subsection of a given section to
     /// allow it to be split.
     pub fn min_split_size(&self) -> usize {
-        self.min_sec_size + SPLIT_BUFFER
+        self.min_sec_size() + self.network_params.split_buffer
     }
 
     /// Collects prefixes of all sections known by the routing table into a `BTreeSet`.
@@ -84,17 +111,20 @@ impl Chain {
     }
 
     /// Create a new chain given genesis information
-    pub fn new(min_sec_size: usize, our_id: PublicId, gen_info: GenesisPfxInfo) -> Self {
+    pub fn new(network_params: NetworkParams, our_id: PublicId, gen_info: GenesisPfxInfo) -> Self {
         // TODO validate `gen_info` to contain adequate proofs
         let is_elder = gen_info.first_info.members().contains(&our_id);
         Self {
-            min_sec_size,
+            network_params,
             our_id,
             state: SharedState::new(gen_info.first_info, gen_info.first_ages),
             is_elder,
             chain_accumulator: Default::default(),
             event_cache: Default::default(),
             parsec_prune_accumulated: 0,
+            relocations_in_flight: Default::default(),
+            relocation_queue: Default::default(),
+            audit_log: Default::default(),
         }
     }
 
@@ -107,14 +137,25 @@ impl Chain {
         _group: &BTreeSet<PublicId>,
         related_info: &[u8],
     ) -> Result<(), RoutingError> {
-        self.state
-            .update_with_genesis_related_info(related_info, &LogIdent::new(self))
+        if related_info.is_empty() {
+            return Ok(());
+        }
+        self.import_snapshot(related_info)
+    }
+
+    /// Get the serialized shared state, i.e. our section infos and key history, our members and
+    /// what we know of other sections. It is used as the starting point when processing parsec
+    /// data, and can be imported again with `import_snapshot`.
+    pub fn export_snapshot(&self) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialisation::serialise(&self.state.snapshot())?)
     }
 
-    /// Get the serialized shared state that will be the starting point when processing
-    /// parsec data
-    pub fn get_genesis_related_info(&self) -> Result<Vec<u8>, RoutingError> {
-        self.state.get_genesis_related_info()
+    /// Restores the shared state from a snapshot created by `export_snapshot`. Fails if the key
+    /// history in the snapshot is invalid.
+    pub fn import_snapshot(&mut self, snapshot: &[u8]) -> Result<(), RoutingError> {
+        let snapshot = serialisation::deserialise(snapshot)?;
+        let log_ident = LogIdent::new(self);
+        self.state.restore_snapshot(snapshot, &log_ident)
     }
 
     fn get_age_counters(&self) -> BTreeMap<PublicId, AgeCounter> {
@@ -179,12 +220,31 @@ impl Chain {
         Ok(())
     }
 
-    /// Handles an opaque parsec Observation as a NetworkEvent.
+    /// Handles an opaque parsec Observation as a NetworkEvent. The events of a `Batch` are handled
+    /// one by one, each with the `proof` of the whole batch: in the proof sets they accumulate
+    /// with, the signatures of the voters who batched them are over the batch, not over the events.
     pub fn handle_opaque_event(
         &mut self,
         event: &NetworkEvent,
         proof: Proof,
     ) -> Result<(), RoutingError> {
+        if let AccumulatingEvent::Batch(ref events) = event.payload {
+            // The voter only signed the batch, so that proof stands for each of its events.
+            let batch_proof = proof;
+            for event in events {
+                if let AccumulatingEvent::Batch(_) = event.payload {
+                    warn!(
+                        "{} Ignoring nested batch from {}.",
+                        self,
+                        batch_proof.pub_id()
+                    );
+                    continue;
+                }
+                self.handle_opaque_event(event, batch_proof)?;
+            }
+            return Ok(());
+        }
+
         if self.should_skip_accumulator(event) {
             return Ok(());
         }
@@ -219,6 +279,61 @@ impl Chain {
         Ok(())
     }
 
+    /// Returns the voters detected voting for mutually exclusive events since the last call.
+    pub fn take_conflicting_votes(&mut self) -> Vec<ConflictingVotesPayload> {
+        let conflicting_votes = self.chain_accumulator.take_conflicting_votes();
+        for payload in &conflicting_votes {
+            warn!("{} - Detected conflicting votes: {:?}", self, payload);
+        }
+        conflicting_votes
+    }
+
+    /// Returns the metrics of the votes which haven't accumulated yet.
+    pub fn accumulator_metrics(&self) -> AccumulatorMetrics {
+        self.chain_accumulator.metrics()
+    }
+
+    /// Returns the log of the events which accumulated recently.
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    /// Removes the entries of the audit log and returns them, oldest first.
+    pub fn drain_audit_log(&mut self) -> Vec<AuditEntry> {
+        self.audit_log.drain()
+    }
+
+    /// Takes the state of the vote accumulator out of the chain, to be carried over a pause of the
+    /// node and then passed to `restore_accumulator_state`.
+    pub fn take_accumulator_state(&mut self) -> AccumulatorState {
+        self.chain_accumulator.take_state()
+    }
+
+    /// Restores the state of the vote accumulator taken by `take_accumulator_state`, so the
+    /// partially accumulated votes don't need to be cast again.
+    pub fn restore_accumulator_state(&mut self, state: AccumulatorState) {
+        self.chain_accumulator.restore_state(state)
+    }
+
+    /// Purges the incomplete votes which haven't received any new proof within `ttl`, so that
+    /// stalled consensus doesn't keep them in memory forever. Returns the expired events.
+    pub fn purge_expired_votes(&mut self, ttl: Duration) -> Vec<AccumulatingEvent> {
+        self.chain_accumulator
+            .purge_expired(ttl)
+            .into_iter()
+            .map(|(event, proofs)| {
+                warn!(
+                    "{} Vote for {:?} expired with {} of {} proofs.",
+                    self,
+                    event,
+                    proofs.parsec_proof_set().len(),
+                    self.state.our_info().members().len()
+                );
+                event
+            })
+            .collect()
+    }
+
     /// Returns the next accumulated event.
     ///
     /// If the event is a `EldersInfo` or `NeighbourInfo`, it also updates the corresponding
@@ -240,11 +355,17 @@ impl Chain {
             }
         };
 
+        self.audit_log.record(
+            *self.state.our_info().version(),
+            &event,
+            proofs.parsec_proof_set(),
+            self.state.our_info().members().len(),
+        );
+
         match event {
             AccumulatingEvent::SectionInfo(ref info) => {
                 let old_neighbours: BTreeSet<_> = self.neighbour_elders().copied().collect();
                 self.add_elders_info(info.clone(), proofs)?;
-                let new_neighbours: BTreeSet<_> = self.neighbour_elders().copied().collect();
 
                 if let Some((ref cached_info, _)) = self.state.split_cache {
                     if cached_info == info {
@@ -252,17 +373,19 @@ impl Chain {
                     }
                 }
 
-                let neighbour_change = EldersChange {
-                    added: new_neighbours
-                        .difference(&old_neighbours)
-                        .copied()
-                        .collect(),
-                    removed: old_neighbours
-                        .difference(&new_neighbours)
-                        .copied()
-                        .collect(),
-                };
+                let neighbour_change = self.neighbour_change_since(&old_neighbours);
+                return Ok(Some((event, neighbour_change)));
+            }
+            AccumulatingEvent::EldersChanged {
+                ref added,
+                ref removed,
+                ..
+            } => {
+                let old_neighbours: BTreeSet<_> = self.neighbour_elders().copied().collect();
+                let info = self.apply_elders_change(added, removed)?;
+                self.do_add_elders_info(info, proofs)?;
 
+                let neighbour_change = self.neighbour_change_since(&old_neighbours);
                 return Ok(Some((event, neighbour_change)));
             }
             AccumulatingEvent::TheirKeyInfo(ref key_info) => {
@@ -300,13 +423,85 @@ impl Chain {
             | AccumulatingEvent::Online(_)
             | AccumulatingEvent::Offline(_)
             | AccumulatingEvent::User(_)
-            | AccumulatingEvent::SendAckMessage(_)
-            | AccumulatingEvent::Relocate(_) => (),
+            | AccumulatingEvent::NamespacedUser(_)
+            | AccumulatingEvent::ConflictingVotes(_)
+            | AccumulatingEvent::Batch(_)
+            | AccumulatingEvent::SendAckMessages(_)
+            | AccumulatingEvent::Relocate(_)
+            | AccumulatingEvent::RelocationComplete(_)
+            | AccumulatingEvent::StepDown(_) => (),
         }
 
         Ok(Some((event, EldersChange::default())))
     }
 
+    // Returns how the neighbour elders changed since they were `old_neighbours`.
+    fn neighbour_change_since(&self, old_neighbours: &BTreeSet<PublicId>) -> EldersChange {
+        let new_neighbours: BTreeSet<_> = self.neighbour_elders().copied().collect();
+        EldersChange {
+            added: new_neighbours.difference(old_neighbours).copied().collect(),
+            removed: old_neighbours
+                .difference(&new_neighbours)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Returns the members of our section the elder selection policy picks to be our elders.
+    pub fn selected_elders(&self) -> BTreeSet<PublicId> {
+        self.select_elders(None)
+    }
+
+    /// Returns the members the elder selection policy picks to be our elders which aren't yet,
+    /// leaving out `leaving`, e.g. an elder being removed which is still a member.
+    pub fn elders_to_promote(&self, leaving: Option<&PublicId>) -> Vec<PublicId> {
+        let elders = self.state.new_info.members();
+        self.select_elders(leaving)
+            .into_iter()
+            .filter(|pub_id| !elders.contains(pub_id))
+            .collect()
+    }
+
+    /// Returns our elders which are still members but which the elder selection policy no longer
+    /// picks, e.g. as older members joined.
+    pub fn elders_to_demote(&self) -> Vec<PublicId> {
+        let selected = self.selected_elders();
+        self.state
+            .new_info
+            .members()
+            .iter()
+            .filter(|pub_id| self.is_peer_our_member(pub_id) && !selected.contains(*pub_id))
+            .copied()
+            .collect()
+    }
+
+    fn select_elders(&self, excluded: Option<&PublicId>) -> BTreeSet<PublicId> {
+        let members = self
+            .state
+            .our_joined_members()
+            .filter(|(pub_id, _)| Some(*pub_id) != excluded)
+            .map(|(pub_id, info)| (*pub_id, info.age()))
+            .collect();
+        self.network_params.elder_selection.select(&members)
+    }
+
+    /// Returns the info of our section with the given elders added to and removed from our
+    /// current one.
+    pub fn apply_elders_change(
+        &self,
+        added: &BTreeSet<PublicId>,
+        removed: &BTreeSet<PublicId>,
+    ) -> Result<EldersInfo, RoutingError> {
+        let elders = self
+            .our_info()
+            .members()
+            .difference(removed)
+            .chain(added)
+            .copied()
+            .collect();
+        EldersInfo::new(elders, *self.our_prefix(), iter::once(self.our_info()))
+    }
+
     fn increase_members_age(&mut self, trigger_node: &PublicId) {
         if self.state.our_joined_members().count() >= SAFE_SECTION_SIZE
             && self
@@ -367,6 +562,50 @@ impl Chain {
         }
     }
 
+    /// Starts the relocation of a member if fewer than `max_concurrent_relocations` are in flight,
+    /// otherwise queues it until one of them completes. Returns the details if the relocation can
+    /// start now.
+    pub fn start_relocation(&mut self, details: RelocateDetails) -> Option<RelocateDetails> {
+        if self.relocations_in_flight.contains(&details.pub_id)
+            || self
+                .relocation_queue
+                .iter()
+                .any(|queued| queued.pub_id == details.pub_id)
+        {
+            return None;
+        }
+
+        if self.relocations_in_flight.len() < self.network_params.max_concurrent_relocations {
+            let _ = self.relocations_in_flight.insert(details.pub_id);
+            Some(details)
+        } else {
+            self.relocation_queue.push_back(details);
+            None
+        }
+    }
+
+    /// Returns whether the relocation of the given member started and isn't complete yet.
+    pub fn is_relocating(&self, pub_id: &PublicId) -> bool {
+        self.relocations_in_flight.contains(pub_id)
+    }
+
+    /// Marks the relocation of the given member as complete. Returns the next queued relocation,
+    /// which can start now, if any. Queued members which left our section meanwhile are skipped.
+    pub fn complete_relocation(&mut self, pub_id: &PublicId) -> Option<RelocateDetails> {
+        if !self.relocations_in_flight.remove(pub_id) {
+            return None;
+        }
+
+        while let Some(details) = self.relocation_queue.pop_front() {
+            if self.is_peer_our_member(&details.pub_id) {
+                let _ = self.relocations_in_flight.insert(details.pub_id);
+                return Some(details);
+            }
+        }
+
+        None
+    }
+
     /// Adds an elder to our section, creating a new `EldersInfo` in the process.
     /// If we need to split also returns an additional sibling `EldersInfo`.
     /// Should not be called while a pfx change is in progress.
@@ -419,7 +658,7 @@ impl Chain {
             Some(&self.state.new_info),
         )?;
 
-        if self.state.new_info.members().len() < self.min_sec_size {
+        if self.state.new_info.members().len() < self.min_sec_size() {
             // set to merge state to prevent extending chain any further.
             // We'd still not Vote for OurMerge until we've updated our_infos
             self.state.change = PrefixChange::Merging;
@@ -446,7 +685,7 @@ impl Chain {
     /// Returns `true` if we should merge.
     pub fn should_vote_for_merge(&self) -> bool {
         self.state
-            .should_vote_for_merge(self.min_sec_size, self.neighbour_infos())
+            .should_vote_for_merge(self.min_sec_size(), self.neighbour_infos())
     }
 
     /// Finalises a split or merge - creates a `GenesisPfxInfo` for the new graph and returns the
@@ -467,8 +706,9 @@ impl Chain {
 
         Ok(PrefixChangeOutcome {
             gen_pfx_info: GenesisPfxInfo {
+                format_version: GenesisPfxInfo::FORMAT_VERSION,
                 first_info: self.our_info().clone(),
-                first_state_serialized: self.get_genesis_related_info()?,
+                first_state_serialized: self.export_snapshot()?,
                 first_ages: self.get_age_counters(),
                 latest_info: Default::default(),
             },
@@ -492,6 +732,11 @@ impl Chain {
         self.state.our_info()
     }
 
+    /// Returns the infos of our section we still keep, from the oldest to the current one.
+    pub fn our_infos(&self) -> impl Iterator<Item = &EldersInfo> + DoubleEndedIterator {
+        self.state.our_infos()
+    }
+
     /// Returns our own current section's prefix.
     pub fn our_prefix(&self) -> &Prefix<XorName> {
         self.state.our_prefix()
@@ -528,7 +773,6 @@ impl Chain {
     }
 
     /// Get info about a member of our section.
-    #[cfg(feature = "mock_base")]
     pub fn get_member(&self, pub_id: &PublicId) -> Option<&MemberInfo> {
         self.state
             .our_members
@@ -536,6 +780,19 @@ impl Chain {
             .filter(|info| info.state == MemberState::Joined)
     }
 
+    /// Returns our own age, if we are a member of our section.
+    pub fn our_age(&self) -> Option<u8> {
+        self.get_member(&self.our_id).map(|info| info.age())
+    }
+
+    /// Returns the ages of all the current members of our section.
+    pub fn member_ages(&self) -> BTreeMap<PublicId, u8> {
+        self.state
+            .our_joined_members()
+            .map(|(pub_id, info)| (*pub_id, info.age()))
+            .collect()
+    }
+
     /// Returns a set of elders we should be connected to.
     pub fn elders(&self) -> impl Iterator<Item = &PublicId> {
         self.neighbour_infos()
@@ -601,6 +858,26 @@ impl Chain {
         self.state.get_their_keys_info()
     }
 
+    /// Returns whether `name` belongs to the section responsible for `dst`, as far as we know:
+    /// ours, one of our neighbours or one of the other sections we know the key of.
+    pub fn is_in_section_of(&self, name: &XorName, dst: &Authority<XorName>) -> bool {
+        let dst_name = dst.name();
+        self.prefixes()
+            .into_iter()
+            .chain(self.state.their_keys.keys().cloned())
+            .find(|pfx| pfx.matches(&dst_name))
+            .map_or(false, |pfx| pfx.matches(name))
+    }
+
+    /// Returns the current key of the section `name` belongs to, if we know it.
+    pub fn their_key(&self, name: &XorName) -> Option<&BlsPublicKey> {
+        self.state
+            .their_keys
+            .iter()
+            .find(|(prefix, _)| prefix.matches(name))
+            .map(|(_, info)| info.key())
+    }
+
     /// Returns `true` if the `proof_chain` contains a key we have in `their_keys` and that key is
     /// for a prefix compatible with proof_chain prefix.
     pub fn check_trust(&self, proof_chain: &SectionProofChain) -> bool {
@@ -616,6 +893,20 @@ impl Chain {
             .any(|key_info| filtered_keys.contains(key_info))
     }
 
+    /// Returns `true` if the last key of `proof_chain` is long obsolete: we know a key of its
+    /// section more than `MAX_KEY_VERSION_LAG` versions newer.
+    pub fn is_obsolete(&self, proof_chain: &SectionProofChain) -> bool {
+        let last_key_info = proof_chain.last_public_key_info();
+        self.state
+            .get_their_keys_info()
+            .filter(|&(pfx, _)| last_key_info.prefix().is_compatible(pfx))
+            .map(|(_, info)| *info.version())
+            .max()
+            .map_or(false, |latest| {
+                latest > last_key_info.version() + MAX_KEY_VERSION_LAG
+            })
+    }
+
     /// Returns `true` if the `EldersInfo` isn't known to us yet.
     pub fn is_new(&self, elders_info: &EldersInfo) -> bool {
         let is_newer = |si: &EldersInfo| {
@@ -653,8 +944,12 @@ impl Chain {
     /// Provide a SectionProofChain that proves the given signature to the section with a given
     /// prefix
     pub fn prove(&self, target: &Authority<XorName>) -> SectionProofChain {
-        let first_index = self.proving_index(target);
-        self.state.our_history.slice_from(first_index as usize)
+        self.proof_chain_from(self.proving_index(target))
+    }
+
+    /// Provide the chain of our section keys from the given version up to the current one.
+    pub fn proof_chain_from(&self, version: u64) -> SectionProofChain {
+        self.state.our_history.slice_from(version as usize)
     }
 
     /// Returns `true` if the given `NetworkEvent` is already accumulated and can be skipped.
@@ -662,6 +957,10 @@ impl Chain {
         // FIXME: may also need to handle non SI votes to not get handled multiple times
         let si = match event.payload {
             AccumulatingEvent::SectionInfo(ref si) => si,
+            // We can ignore changes to infos we already have.
+            AccumulatingEvent::EldersChanged { version, .. } => {
+                return version <= *self.our_info().version()
+            }
             _ => return false,
         };
 
@@ -707,7 +1006,20 @@ impl Chain {
                 let is_sequence_ok = !info.prefix().matches(self.our_id.name())
                     || info.is_successor_of(self.our_info());
 
-                is_sequence_ok && self.our_info().is_quorum(proofs)
+                is_sequence_ok && self.is_quorum(network_event, proofs)
+            }
+            AccumulatingEvent::EldersChanged {
+                ref added,
+                ref removed,
+                version,
+            } => {
+                // Ensure the change applies to our current info.
+                let members = self.our_info().members();
+                self.state.change == PrefixChange::None
+                    && version == self.our_info().version() + 1
+                    && added.is_disjoint(members)
+                    && removed.is_subset(members)
+                    && self.is_quorum(network_event, proofs)
             }
 
             AccumulatingEvent::AddElder(_)
@@ -718,21 +1030,33 @@ impl Chain {
             | AccumulatingEvent::ParsecPrune
             | AccumulatingEvent::AckMessage(_)
             | AccumulatingEvent::User(_)
-            | AccumulatingEvent::Relocate(_) => {
-                self.state.change == PrefixChange::None && self.our_info().is_quorum(proofs)
-            }
-            AccumulatingEvent::SendAckMessage(_) => {
-                // We may not reach consensus if malicious peer, but when we do we know all our
-                // nodes have updated `their_keys`.
-                self.state.change == PrefixChange::None
-                    && self.our_info().is_total_consensus(proofs)
+            | AccumulatingEvent::NamespacedUser(_)
+            | AccumulatingEvent::ConflictingVotes(_)
+            | AccumulatingEvent::Relocate(_)
+            | AccumulatingEvent::RelocationComplete(_)
+            | AccumulatingEvent::StepDown(_)
+            | AccumulatingEvent::SendAckMessages(_) => {
+                self.state.change == PrefixChange::None && self.is_quorum(network_event, proofs)
             }
+            // Batches are unpacked before they reach the accumulator.
+            AccumulatingEvent::Batch(_) => false,
             AccumulatingEvent::OurMerge | AccumulatingEvent::NeighbourMerge(_) => {
-                self.our_info().is_quorum(proofs)
+                self.is_quorum(network_event, proofs)
             }
         }
     }
 
+    /// Returns whether the proofs from our elders reach the quorum our policy requires for the
+    /// event.
+    fn is_quorum(&self, network_event: &AccumulatingEvent, proofs: &ProofSet) -> bool {
+        let members = self.our_info().members();
+        let votes = proofs.ids().filter(|id| members.contains(id)).count();
+        self.network_params
+            .quorum_policy
+            .quorum(network_event.kind())
+            .is_reached(votes, members.len())
+    }
+
     fn compatible_neighbour_info<'a>(&'a self, si: &'a EldersInfo) -> Option<&'a EldersInfo> {
         self.state
             .neighbour_infos
@@ -825,13 +1149,17 @@ impl Chain {
     ) -> Result<(), RoutingError> {
         let pfx = *elders_info.prefix();
         if pfx.matches(self.our_id.name()) {
-            let is_new_elder = !self.is_elder && elders_info.members().contains(&self.our_id);
-            let pk_set = self.public_key_set();
-            self.state.push_our_new_info(elders_info, proofs, &pk_set)?;
-
-            if is_new_elder {
-                self.is_elder = true;
-            }
+            let is_elder = elders_info.members().contains(&self.our_id);
+            // Aggregate the signature shares of our current elders into a single section
+            // signature of the new info.
+            let sig = proofs
+                .combine_signatures(&self.public_key_set())
+                .ok_or(RoutingError::InvalidNewSectionInfo)?;
+            self.state.push_our_new_info(elders_info, sig)?;
+            self.state.compact(MAX_OUR_INFOS);
+
+            // We can also stop being an elder while staying a member, after stepping down.
+            self.is_elder = is_elder;
             self.check_and_clean_neighbour_infos(None);
         } else {
             let ppfx = elders_info.prefix().popped();
@@ -1035,7 +1363,6 @@ impl Chain {
 
     /// If our section is the closest one to `name`, returns all names in our section *including
     /// ours*, otherwise returns `None`.
-    #[cfg(feature = "mock_base")]
     pub fn close_names(&self, name: &XorName) -> Option<Vec<XorName>> {
         if self.our_prefix().matches(name) {
             Some(
@@ -1079,6 +1406,18 @@ impl Chain {
         }
     }
 
+    /// Returns the `k` elders of the known sections closest to `name`, possibly including us,
+    /// sorted by ascending distance to `name`. `k` is capped at `MAX_CLOSE_GROUP_SIZE`. Adults are
+    /// left out: the elders of other sections don't know them, so every elder computes the same
+    /// group as long as it knows the same sections.
+    pub fn close_group(&self, name: &XorName, k: usize) -> Vec<XorName> {
+        Iterator::flatten(self.all_sections().map(|(_, info)| info.member_names()))
+            .sorted_by(|lhs, rhs| name.cmp_distance(lhs, rhs))
+            .into_iter()
+            .take(k.min(MAX_CLOSE_GROUP_SIZE))
+            .collect()
+    }
+
     /// Returns the prefix of the closest non-empty section to `name`, regardless of whether `name`
     /// belongs in that section or not, and the section itself.
     pub(crate) fn closest_section(&self, name: &XorName) -> (Prefix<XorName>, BTreeSet<XorName>) {
@@ -1120,8 +1459,16 @@ impl Chain {
     ///       RT, returns all members in these prefixes except ourself; otherwise
     ///     - if the prefix is compatible with our prefix and is *not* fully-covered by prefixes in
     ///       our RT, returns `Err(Error::CannotRoute)`; otherwise
-    ///     - returns the `N/3` closest members of the RT to the lower bound of the target
-    ///       prefix
+    ///     - returns the `N/3` closest members of the RT to the lower bound of each section we
+    ///       know of covered by the target prefix, so that each of them gets a copy even if it
+    ///       doesn't know the others, e.g. because the target split while the message was in
+    ///       flight. Their members drop the copies they receive more than once. Returns
+    ///       `Err(Error::CannotRoute)` if any of these sections can't be reached.
+    ///
+    /// * If the destination is an `Authority::CloseGroup`:
+    ///     - if our section is the closest on the network to the target name, returns all other
+    ///       members of the `k` closest known nodes to it; otherwise
+    ///     - returns the `N/3` closest members of the RT to the target
     ///
     /// * If the destination is a group (`ClientManager`, `NaeManager` or `NodeManager`):
     ///     - if our section is the closest on the network (i.e. our section's prefix is a prefix of
@@ -1232,7 +1579,45 @@ impl Chain {
                     let dg_size = targets.len();
                     return Ok((targets, dg_size));
                 }
-                candidates(&prefix.lower_bound())?
+
+                let mut targets = Vec::new();
+                for section_prefix in self.prefixes() {
+                    if !prefix.is_compatible(&section_prefix) {
+                        continue;
+                    }
+                    let target_prefix = if section_prefix.bit_count() > prefix.bit_count() {
+                        section_prefix
+                    } else {
+                        *prefix
+                    };
+                    // Only the delivery group of each section, without the spare targets, so
+                    // that the groups of the other sections aren't treated as spares.
+                    let (dg_size, section_targets) = candidates(&target_prefix.lower_bound())?;
+                    for name in section_targets.into_iter().take(dg_size) {
+                        if !targets.contains(&name) {
+                            targets.push(name);
+                        }
+                    }
+                }
+
+                if targets.is_empty() {
+                    candidates(&prefix.lower_bound())?
+                } else {
+                    (targets.len(), targets)
+                }
+            }
+            Authority::CloseGroup { ref name, k } => {
+                if self.our_prefix().matches(name) {
+                    let group: Vec<_> = self
+                        .close_group(name, k)
+                        .into_iter()
+                        .filter(|target| target != self.our_id().name())
+                        .filter(is_connected)
+                        .collect();
+                    let dg_size = group.len();
+                    return Ok((group, dg_size));
+                }
+                candidates(name)?
             }
         };
 
@@ -1245,6 +1630,30 @@ impl Chain {
             Authority::Node(ref name) => self.our_id().name() == name,
             Authority::Section(ref name) => self.our_prefix().matches(name),
             Authority::PrefixSection(ref prefix) => self.our_prefix().is_compatible(prefix),
+            Authority::CloseGroup { ref name, k } => {
+                self.close_group(name, k).contains(self.our_id().name())
+            }
+        }
+    }
+
+    /// Returns whether a message to `dst` reached the section responsible for it but can't be
+    /// delivered, as it is addressed to a node which isn't a member of our section, nor one being
+    /// relocated out of it.
+    pub fn is_undeliverable(&self, dst: &Authority<XorName>) -> bool {
+        match *dst {
+            Authority::Node(ref name) => {
+                self.our_prefix().matches(name)
+                    && !self
+                        .state
+                        .our_joined_members()
+                        .map(|(pub_id, _)| pub_id)
+                        .chain(&self.relocations_in_flight)
+                        .chain(self.relocation_queue.iter().map(|details| &details.pub_id))
+                        .any(|pub_id| pub_id.name() == name)
+            }
+            Authority::Section(_) | Authority::PrefixSection(_) | Authority::CloseGroup { .. } => {
+                false
+            }
         }
     }
 
@@ -1348,7 +1757,6 @@ impl Display for Chain {
     }
 }
 
-#[cfg(any(test, feature = "mock_base"))]
 impl Chain {
     /// Returns the members of the section with the given prefix (if it exists)
     pub fn get_section(&self, pfx: &Prefix<XorName>) -> Option<&EldersInfo> {
@@ -1358,10 +1766,7 @@ impl Chain {
             self.state.neighbour_infos.get(pfx)
         }
     }
-}
 
-#[cfg(feature = "mock_base")]
-impl Chain {
     /// Returns their_knowledge
     pub fn get_their_knowledge(&self) -> &BTreeMap<Prefix<XorName>, u64> {
         &self.state.get_their_knowledge()
@@ -1387,15 +1792,22 @@ pub struct EldersChange {
 #[cfg(test)]
 mod tests {
     use super::super::{
-        AccumulatingProof, EldersInfo, GenesisPfxInfo, Proof, ProofSet, MIN_AGE_COUNTER,
+        AccumulatingEvent, AccumulatingProof, AuditLog, DefaultQuorumPolicy, EldersInfo,
+        GenesisPfxInfo, Proof, ProofSet, Quorum, QuorumPolicy, SectionKeyInfo, SectionProofChain,
+        AUDIT_LOG_LEN, MIN_AGE, MIN_AGE_COUNTER,
     };
-    use super::Chain;
+    use super::{delivery_group_size, Chain, MAX_KEY_VERSION_LAG};
     use crate::id::{FullId, PublicId};
-    use crate::{Prefix, XorName, MIN_SECTION_SIZE};
+    use crate::messages::RelocateDetails;
+    use crate::routing_table::Error;
+    use crate::{
+        Authority, NetworkParams, Prefix, XorName, MAX_CLOSE_GROUP_SIZE, MIN_SECTION_SIZE,
+    };
     use rand::{thread_rng, Rng};
     use serde::Serialize;
     use std::collections::{BTreeSet, HashMap};
     use std::str::FromStr;
+    use std::sync::Arc;
     use unwrap::unwrap;
 
     enum SecInfoGen<'a> {
@@ -1457,6 +1869,20 @@ mod tests {
     }
 
     fn gen_chain<T>(min_sec_size: usize, sections: T) -> (Chain, HashMap<PublicId, FullId>)
+    where
+        T: IntoIterator<Item = (Prefix<XorName>, usize)>,
+    {
+        let network_params = NetworkParams {
+            min_section_size: min_sec_size,
+            ..Default::default()
+        };
+        gen_chain_with_params(network_params, sections)
+    }
+
+    fn gen_chain_with_params<T>(
+        network_params: NetworkParams,
+        sections: T,
+    ) -> (Chain, HashMap<PublicId, FullId>)
     where
         T: IntoIterator<Item = (Prefix<XorName>, usize)>,
     {
@@ -1483,13 +1909,14 @@ mod tests {
             .map(|pub_id| (*pub_id, MIN_AGE_COUNTER))
             .collect();
         let genesis_info = GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
             first_info,
             first_state_serialized: Vec::new(),
             first_ages,
             latest_info: Default::default(),
         };
 
-        let mut chain = Chain::new(min_sec_size, *our_id.public_id(), genesis_info);
+        let mut chain = Chain::new(network_params, *our_id.public_id(), genesis_info);
 
         for neighbour_info in sections_iter {
             let proofs = gen_proofs(&full_ids, &our_members, &neighbour_info);
@@ -1531,6 +1958,337 @@ mod tests {
         }
     }
 
+    #[derive(Debug)]
+    struct UnanimousUserEvents;
+
+    impl QuorumPolicy for UnanimousUserEvents {
+        fn quorum(&self, event_kind: &str) -> Quorum {
+            match event_kind {
+                "User" => Quorum::All,
+                _ => Quorum::Supermajority,
+            }
+        }
+    }
+
+    fn poll_user_event(quorum_policy: Arc<dyn QuorumPolicy>, voters: usize) -> bool {
+        let network_params = NetworkParams {
+            quorum_policy,
+            ..Default::default()
+        };
+        let (mut chain, full_ids) =
+            gen_chain_with_params(network_params, vec![(Prefix::default(), 7)]);
+
+        let event = AccumulatingEvent::User(vec![1, 2, 3]).into_network_event();
+        let members = chain.our_info().members().clone();
+        for member in members.iter().take(voters) {
+            let proof = unwrap!(Proof::new(&full_ids[member], &event));
+            unwrap!(chain.handle_opaque_event(&event, proof));
+        }
+
+        unwrap!(chain.poll()).is_some()
+    }
+
+    #[test]
+    fn quorum_policy() {
+        // Six votes out of seven are a supermajority, which suffices by default...
+        assert!(poll_user_event(Arc::new(DefaultQuorumPolicy), 6));
+        // ...but not if the policy requires all the elders to vote.
+        assert!(!poll_user_event(Arc::new(UnanimousUserEvents), 6));
+        assert!(poll_user_event(Arc::new(UnanimousUserEvents), 7));
+    }
+
+    #[test]
+    fn batched_votes_accumulate_individually() {
+        let (mut chain, full_ids) = gen_chain(MIN_SECTION_SIZE, vec![(Prefix::default(), 7)]);
+        let first = AccumulatingEvent::User(vec![1]);
+        let second = AccumulatingEvent::User(vec![2]);
+        let batch = AccumulatingEvent::Batch(vec![
+            first.clone().into_network_event(),
+            second.clone().into_network_event(),
+        ])
+        .into_network_event();
+
+        let members = chain.our_info().members().clone();
+        for member in &members {
+            let proof = unwrap!(Proof::new(&full_ids[member], &batch));
+            unwrap!(chain.handle_opaque_event(&batch, proof));
+        }
+
+        let mut polled = BTreeSet::new();
+        while let Some((event, _)) = unwrap!(chain.poll()) {
+            let _ = polled.insert(event);
+        }
+        assert_eq!(polled, vec![first, second].into_iter().collect());
+    }
+
+    #[test]
+    fn batched_vote_accumulates_with_batch_proof() {
+        let (mut chain, full_ids) = gen_chain(MIN_SECTION_SIZE, vec![(Prefix::default(), 7)]);
+        let event = AccumulatingEvent::User(vec![1]);
+        let single = event.clone().into_network_event();
+        let batch = AccumulatingEvent::Batch(vec![single.clone()]).into_network_event();
+
+        // Half the elders vote for the event in a batch, the others for the event itself.
+        let members = chain.our_info().members().clone();
+        let mut batch_voters = BTreeSet::new();
+        for (index, member) in members.iter().enumerate() {
+            let vote = if index % 2 == 0 {
+                let _ = batch_voters.insert(*member);
+                &batch
+            } else {
+                &single
+            };
+            let proof = unwrap!(Proof::new(&full_ids[member], vote));
+            unwrap!(chain.handle_opaque_event(vote, proof));
+        }
+
+        let (polled, proofs) = unwrap!(unwrap!(chain.poll()));
+        assert_eq!(polled, event);
+        let proof_set = proofs.parsec_proof_set();
+        assert_eq!(proof_set.ids().copied().collect::<BTreeSet<_>>(), members);
+        for (pub_id, sig) in &proof_set.sigs {
+            let proof = Proof {
+                pub_id: *pub_id,
+                sig: *sig,
+            };
+            if batch_voters.contains(pub_id) {
+                assert!(proof.validate_signature(&batch));
+                assert!(!proof.validate_signature(&single));
+            } else {
+                assert!(proof.validate_signature(&single));
+            }
+        }
+    }
+
+    #[test]
+    fn audit_log_records_polled_events() {
+        let (mut chain, full_ids) = gen_chain(MIN_SECTION_SIZE, vec![(Prefix::default(), 7)]);
+        let event = AccumulatingEvent::User(vec![1]).into_network_event();
+
+        let members = chain.our_info().members().clone();
+        for member in &members {
+            let proof = unwrap!(Proof::new(&full_ids[member], &event));
+            unwrap!(chain.handle_opaque_event(&event, proof));
+        }
+        assert!(chain.audit_log().is_empty());
+
+        let _ = unwrap!(chain.poll());
+        let entries: Vec<_> = chain.audit_log().entries().cloned().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, "User");
+        assert_eq!(entries[0].chain_version, *chain.our_info().version());
+        assert_eq!(entries[0].voters, members);
+        assert_eq!(entries[0].elder_count, members.len());
+        assert_eq!(chain.audit_log().entries_of_kind("User").count(), 1);
+        assert_eq!(chain.audit_log().export().lines().count(), 1);
+
+        assert_eq!(chain.drain_audit_log(), entries);
+        assert!(chain.audit_log().is_empty());
+    }
+
+    #[test]
+    fn audit_log_keeps_the_latest_entries() {
+        let event = AccumulatingEvent::User(vec![]);
+        let proofs = ProofSet::new();
+        let mut log = AuditLog::default();
+        for version in 0..AUDIT_LOG_LEN as u64 + 2 {
+            log.record(version, &event, &proofs, 0);
+        }
+
+        assert_eq!(log.len(), AUDIT_LOG_LEN);
+        assert_eq!(log.dropped(), 2);
+        assert_eq!(
+            log.entries().next().map(|entry| entry.chain_version),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn relocation_throttling() {
+        let (mut chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(Prefix::default(), 7)]);
+        let members: Vec<_> = chain.our_info().members().iter().cloned().collect();
+        let details = |pub_id| RelocateDetails {
+            pub_id,
+            destination: XorName::default(),
+            age: 5,
+        };
+
+        // By default only one relocation is in flight at a time, the others are queued.
+        assert_eq!(
+            chain.start_relocation(details(members[0])),
+            Some(details(members[0]))
+        );
+        assert_eq!(chain.start_relocation(details(members[1])), None);
+        assert_eq!(chain.start_relocation(details(members[2])), None);
+
+        // Queued members who left our section meanwhile are skipped.
+        chain.remove_member(&members[1]);
+        assert_eq!(
+            chain.complete_relocation(&members[0]),
+            Some(details(members[2]))
+        );
+        assert_eq!(chain.complete_relocation(&members[2]), None);
+    }
+
+    #[test]
+    fn relocating_members_not_undeliverable() {
+        let (mut chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(Prefix::default(), 7)]);
+        let members: Vec<_> = chain.our_info().members().iter().cloned().collect();
+        let dst = Authority::Node(*members[0].name());
+        let details = RelocateDetails {
+            pub_id: members[0],
+            destination: XorName::default(),
+            age: 5,
+        };
+
+        assert!(!chain.is_undeliverable(&dst));
+        assert_eq!(chain.start_relocation(details.clone()), Some(details));
+        chain.remove_member(&members[0]);
+        assert!(!chain.is_undeliverable(&dst));
+        assert_eq!(chain.complete_relocation(&members[0]), None);
+        assert!(chain.is_undeliverable(&dst));
+    }
+
+    #[test]
+    fn names_in_section_of_destination() {
+        let mut rng = thread_rng();
+        let p_0 = Prefix::from_str("0").unwrap();
+        let p_1 = Prefix::from_str("1").unwrap();
+        let (chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(p_0, 8), (p_1, 8)]);
+
+        let dst = Authority::Section(p_1.substituted_in(rng.gen()));
+        assert!(chain.is_in_section_of(&p_1.substituted_in(rng.gen()), &dst));
+        assert!(!chain.is_in_section_of(&p_0.substituted_in(rng.gen()), &dst));
+    }
+
+    #[test]
+    fn obsolete_keys() {
+        let p_0 = Prefix::from_str("0").unwrap();
+        let p_1 = Prefix::from_str("1").unwrap();
+        let (mut chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(p_0, 8)]);
+
+        let mut infos = vec![gen_section_info(SecInfoGen::New(p_1, 8)).0];
+        for _ in 0..=MAX_KEY_VERSION_LAG {
+            let (info, _) = gen_section_info(SecInfoGen::Add(unwrap!(infos.last())));
+            infos.push(info);
+        }
+        chain.update_their_keys(&SectionKeyInfo::from_elders_info(unwrap!(infos.last())));
+
+        let proof_chain = |info: &EldersInfo| {
+            SectionProofChain::from_genesis(SectionKeyInfo::from_elders_info(info))
+        };
+        assert!(chain.is_obsolete(&proof_chain(&infos[0])));
+        assert!(!chain.is_obsolete(&proof_chain(&infos[1])));
+        assert!(!chain.is_obsolete(&proof_chain(unwrap!(infos.last()))));
+    }
+
+    #[test]
+    fn prefix_section_targets_each_covered_section() {
+        let p_0 = Prefix::from_str("0").unwrap();
+        let p_10 = Prefix::from_str("10").unwrap();
+        let p_11 = Prefix::from_str("11").unwrap();
+        let (chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(p_0, 8), (p_10, 8), (p_11, 8)]);
+        let names: Vec<_> = chain
+            .all_sections()
+            .flat_map(|(_, info)| info.member_names())
+            .collect();
+        let connected: Vec<_> = names.iter().collect();
+
+        let dst = Authority::PrefixSection(Prefix::from_str("1").unwrap());
+        let (targets, dg_size) = unwrap!(chain.targets(&dst, &connected));
+        assert_eq!(dg_size, targets.len());
+        for pfx in &[p_10, p_11] {
+            let count = targets.iter().filter(|name| pfx.matches(name)).count();
+            assert_eq!(count, delivery_group_size(8));
+        }
+
+        // Every covered section must be reachable.
+        let connected: Vec<_> = names.iter().filter(|name| !p_11.matches(name)).collect();
+        match chain.targets(&dst, &connected) {
+            Err(Error::CannotRoute) => (),
+            result => panic!("Unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn close_group_targets_span_sections() {
+        let p_0 = Prefix::from_str("0").unwrap();
+        let p_10 = Prefix::from_str("10").unwrap();
+        let p_11 = Prefix::from_str("11").unwrap();
+        let (chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(p_0, 8), (p_10, 8), (p_11, 8)]);
+        let names: Vec<_> = chain
+            .all_sections()
+            .flat_map(|(_, info)| info.member_names())
+            .collect();
+        let connected: Vec<_> = names.iter().collect();
+
+        // All of our section, and the two closest nodes of the other ones.
+        let dst = Authority::CloseGroup {
+            name: p_0.upper_bound(),
+            k: 10,
+        };
+        assert!(chain.in_authority(&dst));
+        let (targets, dg_size) = unwrap!(chain.targets(&dst, &connected));
+        assert_eq!(dg_size, 9);
+        assert_eq!(targets.len(), 9);
+        assert!(!targets.contains(chain.our_id().name()));
+        assert_eq!(targets.iter().filter(|name| !p_0.matches(name)).count(), 2);
+
+        let dst = Authority::CloseGroup {
+            name: p_10.lower_bound(),
+            k: 2,
+        };
+        assert!(!chain.in_authority(&dst));
+    }
+
+    #[test]
+    fn close_group_excludes_adults() {
+        let p_0 = Prefix::from_str("0").unwrap();
+        let p_1 = Prefix::from_str("1").unwrap();
+        let (mut chain, _) = gen_chain(MIN_SECTION_SIZE, vec![(p_0, 8), (p_1, 8)]);
+        let adult = *FullId::within_range(&p_0.range_inclusive()).public_id();
+        chain.add_member(adult, MIN_AGE);
+
+        let dst = Authority::CloseGroup {
+            name: *adult.name(),
+            k: 8,
+        };
+        let group = chain.close_group(adult.name(), 8);
+        assert_eq!(group.len(), 8);
+        assert!(!group.contains(adult.name()));
+        assert!(group.iter().all(|name| p_0.matches(name)));
+        assert_eq!(
+            chain.in_authority(&dst),
+            group.contains(chain.our_id().name())
+        );
+    }
+
+    #[test]
+    fn close_group_size_capped() {
+        let p_0 = Prefix::from_str("0").unwrap();
+        let sections = vec![
+            (p_0, 8),
+            (Prefix::from_str("100").unwrap(), 8),
+            (Prefix::from_str("101").unwrap(), 8),
+            (Prefix::from_str("110").unwrap(), 8),
+            (Prefix::from_str("111").unwrap(), 8),
+        ];
+        let (chain, _) = gen_chain(MIN_SECTION_SIZE, sections);
+        let names: Vec<_> = chain
+            .all_sections()
+            .flat_map(|(_, info)| info.member_names())
+            .collect();
+        assert!(names.len() > MAX_CLOSE_GROUP_SIZE);
+        let connected: Vec<_> = names.iter().collect();
+
+        let dst = Authority::CloseGroup {
+            name: p_0.upper_bound(),
+            k: usize::MAX,
+        };
+        let (targets, _) = unwrap!(chain.targets(&dst, &connected));
+        assert_eq!(targets.len(), MAX_CLOSE_GROUP_SIZE - 1);
+    }
+
     #[test]
     fn neighbour_info_cleaning() {
         let mut rng = thread_rng();
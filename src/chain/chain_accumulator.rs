@@ -6,8 +6,15 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{AccumulatingEvent, NetworkEvent, Proof, ProofSet, SectionInfoSigPayload};
-use crate::id::PublicId;
+use super::{
+    AccumulatingEvent, ConflictingVotesPayload, NetworkEvent, Proof, ProofSet,
+    SectionInfoSigPayload,
+};
+use crate::{
+    id::PublicId,
+    time::{Duration, Instant},
+    BlsPublicKeySet, BlsSignature,
+};
 use log::LogLevel;
 use std::collections::{BTreeMap, BTreeSet};
 use std::mem;
@@ -17,11 +24,12 @@ pub(super) struct ChainAccumulator {
     /// A map containing network events that have not been handled yet, together with their proofs
     /// that have been collected so far. We are still waiting for more proofs, or to reach a state
     /// where we can handle the event.
-    // FIXME: Purge votes that are older than a given period.
-    chain_accumulator: BTreeMap<AccumulatingEvent, AccumulatingProof>,
+    chain_accumulator: BTreeMap<AccumulatingEvent, AccumulatingEntry>,
     /// Events that were handled: Further incoming proofs for these can be ignored.
     /// When an event is completed, it cannot be or inserted in chain_accumulator.
     completed_events: BTreeSet<AccumulatingEvent>,
+    /// Voters detected voting for mutually exclusive events, not yet taken by the chain.
+    conflicting_votes: Vec<ConflictingVotesPayload>,
 }
 
 impl ChainAccumulator {
@@ -35,7 +43,11 @@ impl ChainAccumulator {
         }
 
         let proof = AccumulatingProof::from_proof_set(proof_set);
-        if self.chain_accumulator.insert(event, proof).is_some() {
+        if self
+            .chain_accumulator
+            .insert(event, AccumulatingEntry::new(proof))
+            .is_some()
+        {
             return Err(InsertError::ReplacedAlreadyInserted);
         }
 
@@ -52,23 +64,36 @@ impl ChainAccumulator {
             return Err(InsertError::AlreadyComplete);
         }
 
-        if !self
+        let voter = *proof.pub_id();
+        let conflicts = self
+            .chain_accumulator
+            .iter()
+            .filter(|(_, entry)| entry.proofs.contains_id(&voter))
+            .filter_map(|(other, _)| ConflictingVotesPayload::new(voter, other, &event));
+        self.conflicting_votes.extend(conflicts);
+
+        let entry = self
             .chain_accumulator
             .entry(event)
-            .or_insert_with(AccumulatingProof::default)
-            .add_proof(proof, signature)
-        {
+            .or_insert_with(|| AccumulatingEntry::new(AccumulatingProof::default()));
+        if !entry.proofs.add_proof(proof, signature) {
             return Err(InsertError::ReplacedAlreadyInserted);
         }
+        entry.last_progress = Instant::now();
 
         Ok(())
     }
 
+    /// Returns the conflicting votes detected since the last call.
+    pub fn take_conflicting_votes(&mut self) -> Vec<ConflictingVotesPayload> {
+        mem::replace(&mut self.conflicting_votes, Vec::new())
+    }
+
     pub fn poll_event(
         &mut self,
         event: AccumulatingEvent,
     ) -> Option<(AccumulatingEvent, AccumulatingProof)> {
-        let proofs = self.chain_accumulator.remove(&event)?;
+        let proofs = self.chain_accumulator.remove(&event)?.proofs;
 
         if !self.completed_events.insert(event.clone()) {
             log_or_panic!(LogLevel::Warn, "Duplicate insert in completed events.");
@@ -80,7 +105,84 @@ impl ChainAccumulator {
     pub fn incomplete_events(
         &self,
     ) -> impl Iterator<Item = (&AccumulatingEvent, &AccumulatingProof)> {
-        self.chain_accumulator.iter()
+        self.chain_accumulator
+            .iter()
+            .map(|(event, entry)| (event, &entry.proofs))
+    }
+
+    /// Returns the number of incomplete events.
+    pub fn pending_count(&self) -> usize {
+        self.chain_accumulator.len()
+    }
+
+    /// Returns the incomplete events together with the time since their first proof.
+    pub fn pending_ages(&self) -> impl Iterator<Item = (&AccumulatingEvent, Duration)> {
+        self.chain_accumulator
+            .iter()
+            .map(|(event, entry)| (event, entry.created.elapsed()))
+    }
+
+    /// Returns the number of incomplete events of each kind.
+    pub fn pending_counts_by_kind(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for event in self.chain_accumulator.keys() {
+            *counts.entry(event.kind()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn metrics(&self) -> AccumulatorMetrics {
+        let mut pending_ages: Vec<_> = self.pending_ages().map(|(_, age)| age).collect();
+        pending_ages.sort_by(|lhs, rhs| rhs.cmp(lhs));
+
+        AccumulatorMetrics {
+            pending_events: self.pending_count(),
+            pending_ages,
+            pending_per_kind: self.pending_counts_by_kind(),
+        }
+    }
+
+    /// Removes and returns the incomplete events which haven't received any new proof within
+    /// `ttl`. Unlike polled events, they are not marked completed, so they can start accumulating
+    /// again if voted for anew.
+    pub fn purge_expired(&mut self, ttl: Duration) -> Vec<(AccumulatingEvent, AccumulatingProof)> {
+        let expired: Vec<_> = self
+            .chain_accumulator
+            .iter()
+            .filter(|(_, entry)| entry.last_progress.elapsed() >= ttl)
+            .map(|(event, _)| event.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|event| {
+                self.chain_accumulator
+                    .remove(&event)
+                    .map(|entry| (event, entry.proofs))
+            })
+            .collect()
+    }
+
+    /// Takes the incomplete and completed events out of the accumulator, leaving it empty.
+    pub fn take_state(&mut self) -> AccumulatorState {
+        AccumulatorState {
+            incomplete_events: mem::replace(&mut self.chain_accumulator, Default::default())
+                .into_iter()
+                .map(|(event, entry)| (event, entry.proofs))
+                .collect(),
+            completed_events: mem::replace(&mut self.completed_events, Default::default()),
+        }
+    }
+
+    /// Replaces the content of the accumulator with `state`. The expiry of the restored incomplete
+    /// events starts anew.
+    pub fn restore_state(&mut self, state: AccumulatorState) {
+        self.chain_accumulator = state
+            .incomplete_events
+            .into_iter()
+            .map(|(event, proofs)| (event, AccumulatingEntry::new(proofs)))
+            .collect();
+        self.completed_events = state.completed_events;
     }
 
     pub fn reset_accumulator(&mut self, our_id: &PublicId) -> RemainingEvents {
@@ -90,9 +192,9 @@ impl ChainAccumulator {
         RemainingEvents {
             cached_events: chain_acc
                 .into_iter()
-                .filter(|&(_, ref proofs)| proofs.parsec_proofs.contains_id(our_id))
-                .map(|(event, proofs)| {
-                    event.into_network_event_with(proofs.into_sig_shares().remove(our_id))
+                .filter(|(_, entry)| entry.proofs.parsec_proofs.contains_id(our_id))
+                .map(|(event, entry)| {
+                    event.into_network_event_with(entry.proofs.into_sig_shares().remove(our_id))
                 })
                 .collect(),
             completed_events,
@@ -100,7 +202,25 @@ impl ChainAccumulator {
     }
 }
 
-#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+// An incomplete event's proofs, together with when the first and the latest of them arrived.
+struct AccumulatingEntry {
+    proofs: AccumulatingProof,
+    created: Instant,
+    last_progress: Instant,
+}
+
+impl AccumulatingEntry {
+    fn new(proofs: AccumulatingProof) -> Self {
+        let now = Instant::now();
+        Self {
+            proofs,
+            created: now,
+            last_progress: now,
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize)]
 pub struct AccumulatingProof {
     parsec_proofs: ProofSet,
     sig_shares: BTreeMap<PublicId, SectionInfoSigPayload>,
@@ -138,6 +258,16 @@ impl AccumulatingProof {
         self.parsec_proofs
     }
 
+    /// Aggregates the signature shares into a single section signature. Returns `None` if there
+    /// are not enough valid shares from the members of the section owning `pk_set`.
+    pub fn combine_signatures(&self, pk_set: &BlsPublicKeySet) -> Option<BlsSignature> {
+        pk_set.combine_signatures(
+            self.sig_shares
+                .values()
+                .map(|sig_payload| (sig_payload.pub_key_share, &sig_payload.sig_share)),
+        )
+    }
+
     pub fn into_sig_shares(self) -> BTreeMap<PublicId, SectionInfoSigPayload> {
         self.sig_shares
     }
@@ -149,6 +279,28 @@ pub enum InsertError {
     ReplacedAlreadyInserted,
 }
 
+/// Metrics of the votes of a node which haven't accumulated yet. A growing number of pending
+/// events, or old ones, indicate stalled consensus.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AccumulatorMetrics {
+    /// Number of events still accumulating proofs.
+    pub pending_events: usize,
+    /// Time since the first proof of each pending event, oldest first.
+    pub pending_ages: Vec<Duration>,
+    /// Number of pending events of each kind, keyed by the name of the `AccumulatingEvent`
+    /// variant.
+    pub pending_per_kind: BTreeMap<&'static str, usize>,
+}
+
+/// The content of a `ChainAccumulator`, carried over while the node is paused.
+#[derive(Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct AccumulatorState {
+    /// The events still accumulating proofs, with the proofs collected so far.
+    pub incomplete_events: BTreeMap<AccumulatingEvent, AccumulatingProof>,
+    /// The events already handled.
+    pub completed_events: BTreeSet<AccumulatingEvent>,
+}
+
 /// The outcome of a prefix change.
 #[derive(Default, PartialEq, Eq, Debug)]
 pub struct RemainingEvents {
@@ -163,6 +315,7 @@ mod test {
     use super::super::EldersInfo;
     use super::*;
     use crate::{id::FullId, BlsPublicKeyShare};
+    use maidsafe_utilities::serialisation;
     use parsec::SecretId;
     use std::iter;
     use unwrap::unwrap;
@@ -384,6 +537,121 @@ mod test {
         assert_eq!(incomplete_events(&acc), vec![]);
     }
 
+    #[test]
+    #[cfg(feature = "mock_base")]
+    fn purge_expired_no_sig() {
+        purge_expired(
+            test_data_random_key(EventType::NoSignature),
+            test_data_random_key(EventType::NoSignature),
+        );
+    }
+
+    #[cfg(feature = "mock_base")]
+    fn purge_expired(data: TestData, data2: TestData) {
+        use fake_clock::FakeClock;
+
+        let ttl_ms = 1000;
+        let ttl = Duration::from_millis(ttl_ms);
+
+        let mut acc = ChainAccumulator::default();
+        let _ = acc.add_proof(data.event.clone(), data.first_proof, None);
+
+        // A new proof resets the expiry.
+        FakeClock::advance_time(ttl_ms / 2);
+        let _ = acc.add_proof(data.event.clone(), data2.first_proof, None);
+        FakeClock::advance_time(ttl_ms / 2 + 1);
+        assert!(acc.purge_expired(ttl).is_empty());
+        assert_eq!(incomplete_events(&acc).len(), 1);
+
+        FakeClock::advance_time(ttl_ms);
+        let expired = acc.purge_expired(ttl);
+        assert_eq!(
+            expired
+                .into_iter()
+                .map(|(event, _)| event)
+                .collect::<Vec<_>>(),
+            vec![data.event.clone()]
+        );
+        assert_eq!(incomplete_events(&acc), vec![]);
+        assert_eq!(completed_events(&acc), vec![]);
+
+        // An expired event can accumulate again.
+        let result = acc.add_proof(data.event.clone(), data.first_proof, None);
+        assert_eq!(result, Ok(()));
+        assert_eq!(incomplete_events(&acc), vec![(data.event, data.acc_proofs)]);
+    }
+
+    #[test]
+    #[cfg(feature = "mock_base")]
+    fn metrics() {
+        use fake_clock::FakeClock;
+
+        let data = test_data_random_key(EventType::NoSignature);
+        let data2 = test_data_random_key(EventType::WithSignature);
+
+        let mut acc = ChainAccumulator::default();
+        assert_eq!(acc.metrics(), AccumulatorMetrics::default());
+
+        let _ = acc.add_proof(data.event.clone(), data.first_proof, data.signature.clone());
+        FakeClock::advance_time(1000);
+        let _ = acc.add_proof(data2.event.clone(), data2.first_proof, data2.signature);
+        FakeClock::advance_time(500);
+
+        let metrics = acc.metrics();
+        assert_eq!(metrics.pending_events, 2);
+        assert_eq!(
+            metrics.pending_ages,
+            vec![Duration::from_millis(1500), Duration::from_millis(500)]
+        );
+        assert_eq!(
+            metrics.pending_per_kind,
+            vec![("OurMerge", 1), ("SectionInfo", 1)]
+                .into_iter()
+                .collect()
+        );
+
+        let _ = acc.poll_event(data.event);
+        assert_eq!(acc.metrics().pending_events, 1);
+    }
+
+    #[test]
+    fn take_and_restore_state_no_sig() {
+        take_and_restore_state(
+            test_data_random_key(EventType::NoSignature),
+            test_data_random_key(EventType::WithSignature),
+        );
+    }
+
+    fn take_and_restore_state(data: TestData, data2: TestData) {
+        let mut acc = ChainAccumulator::default();
+        let _ = acc.add_proof(data.event.clone(), data.first_proof, data.signature.clone());
+        let _ = acc.add_proof(
+            data2.event.clone(),
+            data2.first_proof,
+            data2.signature.clone(),
+        );
+        let _ = acc.poll_event(data.event.clone());
+
+        let state = acc.take_state();
+        assert_eq!(incomplete_events(&acc), vec![]);
+        assert_eq!(completed_events(&acc), vec![]);
+
+        let serialised = unwrap!(serialisation::serialise(&state));
+        let state: AccumulatorState = unwrap!(serialisation::deserialise(&serialised));
+
+        let mut acc = ChainAccumulator::default();
+        acc.restore_state(state);
+        assert_eq!(
+            incomplete_events(&acc),
+            vec![(data2.event.clone(), data2.acc_proofs)]
+        );
+        assert_eq!(completed_events(&acc), vec![data.event.clone()]);
+
+        // The restored state keeps rejecting votes for completed events.
+        let result = acc.add_proof(data.event, data.first_proof, data.signature);
+        assert_eq!(result, Err(InsertError::AlreadyComplete));
+    }
+
     #[test]
     fn reset_all_completed_no_sig() {
         reset_all_completed(test_data_random_key(EventType::NoSignature));
@@ -460,4 +728,65 @@ mod test {
         assert_eq!(incomplete_events(&acc), vec![]);
         assert_eq!(completed_events(&acc), vec![]);
     }
+
+    #[test]
+    fn combine_signatures() {
+        let ids: Vec<_> = (0..4).map(|_| FullId::new()).collect();
+        let members = ids.iter().map(|id| *id.public_id()).collect();
+        let elders_info = unwrap!(EldersInfo::new_for_test(members, Default::default(), 0));
+        let pk_set = BlsPublicKeySet::from_elders_info(elders_info.clone());
+        let new_info = unwrap!(EldersInfo::new_for_test(
+            elders_info.members().clone(),
+            Default::default(),
+            1,
+        ));
+
+        let mut acc_proofs = AccumulatingProof::default();
+        for id in &ids[..pk_set.threshold()] {
+            let proof = unwrap!(Proof::new(id, &new_info));
+            let sig_payload = unwrap!(SectionInfoSigPayload::new(&new_info, id));
+            assert!(acc_proofs.add_proof(proof, Some(sig_payload)));
+        }
+        assert!(acc_proofs.combine_signatures(&pk_set).is_none());
+
+        let id = &ids[pk_set.threshold()];
+        let proof = unwrap!(Proof::new(id, &new_info));
+        let sig_payload = unwrap!(SectionInfoSigPayload::new(&new_info, id));
+        assert!(acc_proofs.add_proof(proof, Some(sig_payload)));
+
+        let sig = unwrap!(acc_proofs.combine_signatures(&pk_set));
+        let signed_bytes = unwrap!(serialisation::serialise(&new_info));
+        assert!(pk_set.public_key().verify(&sig, &signed_bytes));
+        assert!(!pk_set.public_key().verify(&sig, &[1]));
+    }
+
+    #[test]
+    fn conflicting_votes() {
+        let members: BTreeSet<_> = (0..3).map(|_| *FullId::new().public_id()).collect();
+        let gen_info = |members| {
+            AccumulatingEvent::SectionInfo(unwrap!(EldersInfo::new_for_test(
+                members,
+                Default::default(),
+                1,
+            )))
+        };
+        let first = gen_info(members.clone());
+        let second = gen_info(members.iter().skip(1).copied().collect());
+
+        let (_, first_proof) = random_ids_and_proof();
+        let (_, other_proof) = random_ids_and_proof();
+        let second_proof = first_proof.clone();
+
+        let mut acc = ChainAccumulator::default();
+        let _ = acc.add_proof(first.clone(), first_proof.clone(), None);
+        let _ = acc.add_proof(second.clone(), other_proof, None);
+        assert_eq!(acc.take_conflicting_votes(), vec![]);
+
+        let _ = acc.add_proof(second, second_proof, None);
+        let conflicting_votes = acc.take_conflicting_votes();
+        assert_eq!(conflicting_votes.len(), 1);
+        assert_eq!(conflicting_votes[0].voter, first_proof.pub_id);
+        assert_eq!(Some(&conflicting_votes[0].first), first.elders_info());
+        assert_eq!(acc.take_conflicting_votes(), vec![]);
+    }
 }
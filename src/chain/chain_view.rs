@@ -0,0 +1,101 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{AuditLog, Chain, EldersInfo, SectionSnapshot};
+use crate::{id::PublicId, routing_table::Prefix, xor_name::XorName};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Read-only view of the state of the section a node is a member of, as returned by
+/// `Node::chain_view`. It borrows the node, so it reflects the state at the time it was obtained.
+#[derive(Clone, Copy)]
+pub struct ChainView<'a> {
+    chain: &'a Chain,
+}
+
+impl<'a> ChainView<'a> {
+    pub(crate) fn new(chain: &'a Chain) -> Self {
+        Self { chain }
+    }
+
+    /// Returns the prefix of our section.
+    pub fn our_prefix(&self) -> &'a Prefix<XorName> {
+        self.chain.our_prefix()
+    }
+
+    /// Returns the current info of our section.
+    pub fn our_info(&self) -> &'a EldersInfo {
+        self.chain.our_info()
+    }
+
+    /// Returns the infos of our section we still keep, from the oldest to the current one.
+    pub fn our_infos(&self) -> impl Iterator<Item = &'a EldersInfo> {
+        self.chain.our_infos()
+    }
+
+    /// Returns the elders of our section, including us if we are one.
+    pub fn our_elders(&self) -> impl Iterator<Item = &'a PublicId> {
+        self.chain.our_elders()
+    }
+
+    /// Returns the infos of our neighbour sections, as signed by our section.
+    pub fn neighbour_infos(&self) -> impl Iterator<Item = &'a EldersInfo> {
+        self.chain.neighbour_infos()
+    }
+
+    /// Returns the prefixes of our neighbour sections.
+    pub fn neighbour_prefixes(&self) -> BTreeSet<Prefix<XorName>> {
+        self.neighbour_infos()
+            .map(EldersInfo::prefix)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the log of the events which reached consensus in our section since we joined it.
+    pub fn audit_log(&self) -> &'a AuditLog {
+        self.chain.audit_log()
+    }
+
+    /// Returns the prefixes of all the sections we know about, including ours.
+    pub fn prefixes(&self) -> BTreeSet<Prefix<XorName>> {
+        self.chain.prefixes()
+    }
+
+    /// Returns the names of the elders of the section with the given prefix. It must be either our
+    /// prefix or the prefix of one of our neighbours, otherwise the set is empty.
+    pub fn section_elders(&self, prefix: &Prefix<XorName>) -> BTreeSet<XorName> {
+        self.chain
+            .get_section(prefix)
+            .map(EldersInfo::member_names)
+            .unwrap_or_default()
+    }
+
+    /// Returns the latest version of our section's info each neighbour section acknowledged.
+    pub fn their_knowledge(&self) -> &'a BTreeMap<Prefix<XorName>, u64> {
+        self.chain.get_their_knowledge()
+    }
+
+    /// If our section is the closest one to `name`, returns all the names in our section
+    /// including ours, otherwise returns `None`.
+    pub fn close_names(&self, name: &XorName) -> Option<Vec<XorName>> {
+        self.chain.close_names(name)
+    }
+
+    /// Returns the elders of all the sections we know about, to be checked with
+    /// `check_invariant`.
+    pub fn snapshot(&self) -> SectionSnapshot {
+        SectionSnapshot {
+            name: *self.chain.our_id().name(),
+            our_prefix: *self.our_prefix(),
+            sections: self
+                .prefixes()
+                .into_iter()
+                .map(|prefix| (prefix, self.section_elders(&prefix)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::id::PublicId;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+};
+
+/// Decides which members of a section are its elders. Consulted when a member joins, to decide
+/// whether to promote it, and when an elder leaves, to pick the members replacing it.
+pub trait ElderSelection: Debug + Send + Sync {
+    /// Returns the members which should be the elders of the section, out of all its joined
+    /// `members` and their ages. Must be deterministic, as all the elders of the section need to
+    /// reach the same decision.
+    fn select(&self, members: &BTreeMap<PublicId, u8>) -> BTreeSet<PublicId>;
+}
+
+/// The default selection: the `elder_size` oldest members, the ones with the lowest names first
+/// among the members of the same age.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OldestElders {
+    /// Maximum number of elders. Unlimited by default, so that all members are elders.
+    pub elder_size: usize,
+}
+
+impl Default for OldestElders {
+    fn default() -> Self {
+        Self {
+            elder_size: usize::max_value(),
+        }
+    }
+}
+
+impl ElderSelection for OldestElders {
+    fn select(&self, members: &BTreeMap<PublicId, u8>) -> BTreeSet<PublicId> {
+        let mut members: Vec<_> = members.iter().collect();
+        members.sort_by_key(|(pub_id, age)| (Reverse(**age), *pub_id.name()));
+        members
+            .into_iter()
+            .take(self.elder_size)
+            .map(|(pub_id, _)| *pub_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::FullId;
+
+    #[test]
+    fn oldest_elders() {
+        let mut ids: Vec<_> = (0..4).map(|_| *FullId::new().public_id()).collect();
+        ids.sort_by_key(|pub_id| *pub_id.name());
+        let members: BTreeMap<_, _> = vec![(ids[0], 4), (ids[1], 6), (ids[2], 5), (ids[3], 6)]
+            .into_iter()
+            .collect();
+
+        let policy = OldestElders { elder_size: 3 };
+        let expected: BTreeSet<_> = vec![ids[1], ids[3], ids[2]].into_iter().collect();
+        assert_eq!(policy.select(&members), expected);
+
+        let policy = OldestElders { elder_size: 2 };
+        let expected: BTreeSet<_> = vec![ids[1], ids[3]].into_iter().collect();
+        assert_eq!(policy.select(&members), expected);
+
+        // Of two members of the same age, the one with the lower name.
+        let policy = OldestElders { elder_size: 1 };
+        let expected: BTreeSet<_> = vec![ids[1]].into_iter().collect();
+        assert_eq!(policy.select(&members), expected);
+
+        let all: BTreeSet<_> = ids.iter().cloned().collect();
+        assert_eq!(OldestElders::default().select(&members), all);
+    }
+}
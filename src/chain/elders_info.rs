@@ -6,13 +6,13 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{AccumulatingEvent, NetworkEvent, ProofSet, SectionInfoSigPayload};
+use super::{AccumulatingEvent, NetworkEvent, SectionInfoSigPayload};
 use crate::{
     crypto::{self, Digest256},
     error::RoutingError,
     id::PublicId,
     routing_table::Prefix,
-    XorName, {QUORUM_DENOMINATOR, QUORUM_NUMERATOR},
+    XorName,
 };
 use maidsafe_utilities::serialisation;
 use serde::{de::Error as SerdeDeError, Deserialize, Deserializer, Serialize, Serializer};
@@ -108,17 +108,6 @@ impl EldersInfo {
         &self.hash
     }
 
-    /// Returns `true` if the proofs are from a quorum of this section.
-    pub fn is_quorum(&self, proofs: &ProofSet) -> bool {
-        proofs.ids().filter(|id| self.members.contains(id)).count() * QUORUM_DENOMINATOR
-            > self.members.len() * QUORUM_NUMERATOR
-    }
-
-    /// Returns `true` if the proofs are from all members of this section.
-    pub fn is_total_consensus(&self, proofs: &ProofSet) -> bool {
-        proofs.ids().filter(|id| self.members.contains(id)).count() == self.members.len()
-    }
-
     /// Returns `true` if `self` is a successor of `other_info`, according to its hash.
     pub fn is_successor_of(&self, other_info: &Self) -> bool {
         self.prev_hash.contains(&other_info.hash)
@@ -0,0 +1,257 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{Prefix, XorName};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The sections a single node knows about, as returned by `ChainView::snapshot`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionSnapshot {
+    /// The name of the node the snapshot was taken from.
+    pub name: XorName,
+    /// The prefix of the node's section.
+    pub our_prefix: Prefix<XorName>,
+    /// The names of the elders of each section the node knows about, including its own.
+    pub sections: BTreeMap<Prefix<XorName>, BTreeSet<XorName>>,
+}
+
+/// A way in which the sections known by the nodes of a network are inconsistent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvariantViolation {
+    /// The name of the node doesn't match the prefix of its section.
+    NameOutsidePrefix {
+        /// The node.
+        node: XorName,
+        /// The prefix of its section.
+        prefix: Prefix<XorName>,
+    },
+    /// A section known by the node has fewer elders than the minimum section size.
+    SectionTooSmall {
+        /// The node.
+        node: XorName,
+        /// The prefix of the section.
+        prefix: Prefix<XorName>,
+        /// The number of elders the node knows in the section.
+        size: usize,
+    },
+    /// A section known by the node has an elder whose name doesn't match its prefix.
+    MemberOutsidePrefix {
+        /// The node.
+        node: XorName,
+        /// The prefix of the section.
+        prefix: Prefix<XorName>,
+        /// The elder.
+        member: XorName,
+    },
+    /// A section known by the node is not a neighbour of its own.
+    NotNeighbour {
+        /// The node.
+        node: XorName,
+        /// The prefix of the section.
+        prefix: Prefix<XorName>,
+    },
+    /// Some of the neighbours of the node's section aren't covered by the sections it knows.
+    NeighboursNotCovered {
+        /// The node.
+        node: XorName,
+    },
+    /// Two nodes know different elders for the same section.
+    Disagreement {
+        /// The prefix of the section.
+        prefix: Prefix<XorName>,
+        /// The two nodes.
+        nodes: (XorName, XorName),
+    },
+    /// The prefixes of two sections known in the network overlap.
+    OverlappingPrefixes(Prefix<XorName>, Prefix<XorName>),
+    /// The sections known in the network don't cover the whole name space.
+    NamespaceNotCovered,
+}
+
+/// Checks that the sections known by the nodes described by `snapshots` form a consistent network:
+/// each node's own section matches its name and its other sections are all and only its
+/// neighbours, every section has at least `min_section_size` elders matching its prefix, the nodes
+/// agree on the elders of each section, and the sections are disjoint and cover the whole name
+/// space. Returns all the violations found, or an empty vector if there are none.
+pub fn check_invariant<'a, I>(snapshots: I, min_section_size: usize) -> Vec<InvariantViolation>
+where
+    I: IntoIterator<Item = &'a SectionSnapshot>,
+{
+    let mut violations = Vec::new();
+    let mut sections: BTreeMap<Prefix<XorName>, (XorName, &BTreeSet<XorName>)> = BTreeMap::new();
+
+    for snapshot in snapshots {
+        check_snapshot(snapshot, min_section_size, &mut violations);
+
+        for (prefix, elders) in &snapshot.sections {
+            if let Some(&(other_name, other_elders)) = sections.get(prefix) {
+                if other_elders != elders {
+                    violations.push(InvariantViolation::Disagreement {
+                        prefix: *prefix,
+                        nodes: (snapshot.name, other_name),
+                    });
+                }
+                continue;
+            }
+            let _ = sections.insert(*prefix, (snapshot.name, elders));
+        }
+    }
+
+    for (index, prefix) in sections.keys().enumerate() {
+        for other_prefix in sections.keys().skip(index + 1) {
+            if prefix.is_compatible(other_prefix) {
+                violations.push(InvariantViolation::OverlappingPrefixes(
+                    *prefix,
+                    *other_prefix,
+                ));
+            }
+        }
+    }
+
+    if !Prefix::default().is_covered_by(sections.keys()) {
+        violations.push(InvariantViolation::NamespaceNotCovered);
+    }
+
+    violations
+}
+
+fn check_snapshot(
+    snapshot: &SectionSnapshot,
+    min_section_size: usize,
+    violations: &mut Vec<InvariantViolation>,
+) {
+    let node = snapshot.name;
+    let our_prefix = snapshot.our_prefix;
+
+    if !our_prefix.matches(&node) {
+        violations.push(InvariantViolation::NameOutsidePrefix {
+            node,
+            prefix: our_prefix,
+        });
+    }
+
+    for (prefix, elders) in &snapshot.sections {
+        // A network of a single section may be smaller than the minimum.
+        if !prefix.is_empty() && elders.len() < min_section_size {
+            violations.push(InvariantViolation::SectionTooSmall {
+                node,
+                prefix: *prefix,
+                size: elders.len(),
+            });
+        }
+
+        if let Some(member) = elders.iter().find(|name| !prefix.matches(name)) {
+            violations.push(InvariantViolation::MemberOutsidePrefix {
+                node,
+                prefix: *prefix,
+                member: *member,
+            });
+        }
+
+        if *prefix != our_prefix && !our_prefix.is_neighbour(prefix) {
+            violations.push(InvariantViolation::NotNeighbour {
+                node,
+                prefix: *prefix,
+            });
+        }
+    }
+
+    let neighbour_prefixes: BTreeSet<_> = snapshot
+        .sections
+        .keys()
+        .filter(|prefix| **prefix != our_prefix)
+        .collect();
+    let all_neighbours_covered = (0..our_prefix.bit_count()).all(|i| {
+        our_prefix
+            .with_flipped_bit(i)
+            .is_covered_by(neighbour_prefixes.iter().cloned())
+    });
+    if !all_neighbours_covered {
+        violations.push(InvariantViolation::NeighboursNotCovered { node });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XOR_NAME_LEN;
+    use std::str::FromStr;
+    use unwrap::unwrap;
+
+    fn prefix(bits: &str) -> Prefix<XorName> {
+        unwrap!(Prefix::from_str(bits))
+    }
+
+    fn elders(prefix: &Prefix<XorName>, count: usize) -> BTreeSet<XorName> {
+        (0..count)
+            .map(|i| {
+                let mut name = prefix.lower_bound();
+                name.0[XOR_NAME_LEN - 1] = i as u8;
+                name
+            })
+            .collect()
+    }
+
+    fn snapshot(our_prefix: &str, others: &[&str]) -> SectionSnapshot {
+        let our_prefix = prefix(our_prefix);
+        let sections = others
+            .iter()
+            .map(|bits| prefix(bits))
+            .chain(Some(our_prefix))
+            .map(|prefix| (prefix, elders(&prefix, 3)))
+            .collect();
+        SectionSnapshot {
+            name: our_prefix.lower_bound(),
+            our_prefix,
+            sections,
+        }
+    }
+
+    #[test]
+    fn consistent_network() {
+        let snapshots = vec![
+            snapshot("0", &["10", "11"]),
+            snapshot("10", &["0", "11"]),
+            snapshot("11", &["0", "10"]),
+        ];
+        assert!(check_invariant(&snapshots, 3).is_empty());
+    }
+
+    #[test]
+    fn inconsistent_network() {
+        let mut snapshots = vec![snapshot("0", &["10"]), snapshot("10", &["0", "11"])];
+        let _ = snapshots[1]
+            .sections
+            .insert(prefix("0"), elders(&prefix("0"), 4));
+        let violations = check_invariant(&snapshots, 3);
+
+        assert!(
+            violations.contains(&InvariantViolation::NeighboursNotCovered {
+                node: prefix("0").lower_bound()
+            })
+        );
+        assert!(violations.contains(&InvariantViolation::Disagreement {
+            prefix: prefix("0"),
+            nodes: (prefix("10").lower_bound(), prefix("0").lower_bound()),
+        }));
+        assert!(!violations.contains(&InvariantViolation::NamespaceNotCovered));
+
+        let violations = check_invariant(&[snapshot("0", &["1", "10"])], 4);
+        assert!(
+            violations.contains(&InvariantViolation::OverlappingPrefixes(
+                prefix("1"),
+                prefix("10")
+            ))
+        );
+        assert!(violations.contains(&InvariantViolation::SectionTooSmall {
+            node: prefix("0").lower_bound(),
+            prefix: prefix("0"),
+            size: 3,
+        }));
+    }
+}
@@ -11,7 +11,6 @@
 pub struct AgeCounter(u32);
 
 impl AgeCounter {
-    #[cfg(any(test, feature = "mock_base"))]
     pub fn age(self) -> u8 {
         f64::from(self.0).log2() as u8
     }
@@ -49,7 +48,6 @@ pub struct MemberInfo {
 }
 
 impl MemberInfo {
-    #[cfg(feature = "mock_base")]
     pub fn age(self) -> u8 {
         self.age_counter.age()
     }
@@ -7,44 +7,100 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 // The `chain` submodule contains the `Chain` implementation, which we reexport here.
+mod audit_log;
 pub(crate) mod bls_emu;
 #[allow(clippy::module_inception)]
 mod chain;
 mod chain_accumulator;
+mod chain_view;
+mod elder_selection;
 mod elders_info;
+mod invariant;
 mod member_info;
 mod network_event;
 mod proof;
+mod quorum;
 mod shared_state;
 
 pub use self::{
-    chain::{delivery_group_size, Chain, EldersChange, PrefixChangeOutcome},
-    chain_accumulator::AccumulatingProof,
+    audit_log::{AuditEntry, AuditLog, AUDIT_LOG_LEN},
+    chain::{delivery_group_size, Chain, EldersChange, PrefixChangeOutcome, SPLIT_BUFFER},
+    chain_accumulator::{AccumulatingProof, AccumulatorMetrics, AccumulatorState},
+    chain_view::ChainView,
+    elder_selection::{ElderSelection, OldestElders},
     elders_info::EldersInfo,
+    invariant::{check_invariant, InvariantViolation, SectionSnapshot},
     member_info::{AgeCounter, MemberInfo, MemberPersona, MemberState, MIN_AGE, MIN_AGE_COUNTER},
     network_event::{
-        AccumulatingEvent, AckMessagePayload, NetworkEvent, OnlinePayload, SectionInfoSigPayload,
-        SendAckMessagePayload,
+        AccumulatingEvent, AckMessagePayload, ConflictingVotesPayload, NamespacedUserPayload,
+        NetworkEvent, OnlinePayload, SectionInfoSigPayload, SendAckMessagesPayload,
     },
     proof::{Proof, ProofSet},
+    quorum::{DefaultQuorumPolicy, Quorum, QuorumPolicy},
     shared_state::{PrefixChange, SectionKeyInfo, SectionProofChain},
 };
-use crate::PublicId;
+use crate::{error::RoutingError, PublicId};
 #[cfg(feature = "mock_base")]
-use crate::{error::RoutingError, BlsPublicKeySet, Prefix, XorName};
+use crate::{BlsPublicKeySet, Prefix, XorName};
+use maidsafe_utilities::serialisation;
 use std::collections::BTreeMap;
 #[cfg(feature = "mock_base")]
 use std::collections::BTreeSet;
 use std::fmt::{self, Debug, Formatter};
 
+/// The state a section's parsec instance starts from.
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct GenesisPfxInfo {
+    /// The version of the format of this info. Serialised first, so that it can be told apart
+    /// even when the rest of the layout changed.
+    pub format_version: u32,
+    /// The section's elders at the start.
     pub first_info: EldersInfo,
+    /// The serialised shared state at the start.
     pub first_state_serialized: Vec<u8>,
+    /// The ages of the section's members at the start.
     pub first_ages: BTreeMap<PublicId, AgeCounter>,
+    /// The latest elders info known when the genesis was created.
     pub latest_info: EldersInfo,
 }
 
+impl GenesisPfxInfo {
+    /// The current version of the format.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Serialises this info, e.g. to persist it across restarts.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialisation::serialise(self)?)
+    }
+
+    /// Deserialises an info serialised by this or an older version of the crate, migrating it to
+    /// the current format. Infos serialised by a newer version are rejected with
+    /// `RoutingError::UnsupportedGenesisVersion`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RoutingError> {
+        let error = match serialisation::deserialise::<Self>(bytes) {
+            Ok(info) => {
+                return if info.format_version == Self::FORMAT_VERSION {
+                    Ok(info)
+                } else {
+                    Err(RoutingError::UnsupportedGenesisVersion(info.format_version))
+                };
+            }
+            Err(error) => error,
+        };
+
+        if let Ok(info) = serialisation::deserialise::<GenesisPfxInfoV0>(bytes) {
+            return Ok(info.into());
+        }
+
+        match bytes.get(..4).map(serialisation::deserialise::<u32>) {
+            Some(Ok(version)) if version > Self::FORMAT_VERSION => {
+                Err(RoutingError::UnsupportedGenesisVersion(version))
+            }
+            _ => Err(error.into()),
+        }
+    }
+}
+
 impl Debug for GenesisPfxInfo {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
@@ -57,6 +113,27 @@ impl Debug for GenesisPfxInfo {
     }
 }
 
+// Layout of `GenesisPfxInfo` before it had a format version.
+#[derive(Deserialize)]
+struct GenesisPfxInfoV0 {
+    first_info: EldersInfo,
+    first_state_serialized: Vec<u8>,
+    first_ages: BTreeMap<PublicId, AgeCounter>,
+    latest_info: EldersInfo,
+}
+
+impl From<GenesisPfxInfoV0> for GenesisPfxInfo {
+    fn from(info: GenesisPfxInfoV0) -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            first_info: info.first_info,
+            first_state_serialized: info.first_state_serialized,
+            first_ages: info.first_ages,
+            latest_info: info.latest_info,
+        }
+    }
+}
+
 #[cfg(feature = "mock_base")]
 /// Test helper to create arbitrary proof.
 pub fn section_proof_chain_from_elders_info(elders_info: &EldersInfo) -> SectionProofChain {
@@ -69,6 +146,24 @@ pub fn bls_key_set_from_elders_info(elders_info: EldersInfo) -> BlsPublicKeySet
     BlsPublicKeySet::from_elders_info(elders_info)
 }
 
+#[cfg(feature = "mock_base")]
+/// Test helper to create the genesis info of a section with the given elders, all of the minimum
+/// age.
+pub fn gen_pfx_info_for_test(elders_info: EldersInfo) -> GenesisPfxInfo {
+    let first_ages = elders_info
+        .members()
+        .iter()
+        .map(|pub_id| (*pub_id, MIN_AGE_COUNTER))
+        .collect();
+    GenesisPfxInfo {
+        format_version: GenesisPfxInfo::FORMAT_VERSION,
+        first_info: elders_info,
+        first_state_serialized: Vec::new(),
+        first_ages,
+        latest_info: EldersInfo::default(),
+    }
+}
+
 #[cfg(feature = "mock_base")]
 /// Test helper to create arbitrary elders nfo.
 pub fn elders_info_for_test(
@@ -78,3 +173,76 @@ pub fn elders_info_for_test(
 ) -> Result<EldersInfo, RoutingError> {
     EldersInfo::new_for_test(members, prefix, version)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::FullId;
+    use std::collections::BTreeSet;
+    use unwrap::unwrap;
+
+    #[derive(Serialize)]
+    struct GenesisPfxInfoV0Ser<'a> {
+        first_info: &'a EldersInfo,
+        first_state_serialized: &'a Vec<u8>,
+        first_ages: &'a BTreeMap<PublicId, AgeCounter>,
+        latest_info: &'a EldersInfo,
+    }
+
+    fn gen_pfx_info() -> GenesisPfxInfo {
+        let members: BTreeSet<_> = (0..3).map(|_| *FullId::new().public_id()).collect();
+        let first_ages = members.iter().map(|id| (*id, MIN_AGE_COUNTER)).collect();
+        GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
+            first_info: unwrap!(EldersInfo::new_for_test(members, Default::default(), 0)),
+            first_state_serialized: vec![1, 2, 3],
+            first_ages,
+            latest_info: EldersInfo::default(),
+        }
+    }
+
+    #[test]
+    fn genesis_roundtrip() {
+        let info = gen_pfx_info();
+        let bytes = unwrap!(info.to_bytes());
+        assert_eq!(unwrap!(GenesisPfxInfo::from_bytes(&bytes)), info);
+    }
+
+    #[test]
+    fn genesis_migration() {
+        let info = gen_pfx_info();
+        let bytes = unwrap!(serialisation::serialise(&GenesisPfxInfoV0Ser {
+            first_info: &info.first_info,
+            first_state_serialized: &info.first_state_serialized,
+            first_ages: &info.first_ages,
+            latest_info: &info.latest_info,
+        }));
+        assert_eq!(unwrap!(GenesisPfxInfo::from_bytes(&bytes)), info);
+    }
+
+    #[test]
+    fn genesis_newer_version() {
+        let newer_version = GenesisPfxInfo::FORMAT_VERSION + 1;
+        let info = GenesisPfxInfo {
+            format_version: newer_version,
+            ..gen_pfx_info()
+        };
+        let bytes = unwrap!(info.to_bytes());
+        match GenesisPfxInfo::from_bytes(&bytes) {
+            Err(RoutingError::UnsupportedGenesisVersion(version)) => {
+                assert_eq!(version, newer_version)
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // The version is reported even if the rest of the layout changed.
+        let mut bytes = unwrap!(serialisation::serialise(&newer_version));
+        bytes.extend_from_slice(&[0; 8]);
+        match GenesisPfxInfo::from_bytes(&bytes) {
+            Err(RoutingError::UnsupportedGenesisVersion(version)) => {
+                assert_eq!(version, newer_version)
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -16,7 +16,10 @@ use crate::{
     BlsPublicKeyShare, BlsSignatureShare, RoutingError, XorName,
 };
 use hex_fmt::HexFmt;
-use std::fmt::{self, Debug, Formatter};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Debug, Formatter},
+};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct AckMessagePayload {
@@ -27,11 +30,10 @@ pub struct AckMessagePayload {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
-pub struct SendAckMessagePayload {
-    /// The prefix acknowledged.
-    pub ack_prefix: Prefix<XorName>,
-    /// The version acknowledged.
-    pub ack_version: u64,
+pub struct SendAckMessagesPayload {
+    /// The version acknowledged for each prefix, the latest one we know of. Built only from
+    /// consensused state, so that every elder votes for identical contents.
+    pub acks: BTreeMap<Prefix<XorName>, u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
@@ -53,10 +55,65 @@ impl SectionInfoSigPayload {
     }
 }
 
+/// Report of a voter who voted for two different infos of the same section at the same version.
+/// An honest elder never does this, so the elders can vote to act on it.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct ConflictingVotesPayload {
+    /// The voter who cast both votes.
+    pub voter: PublicId,
+    /// The info voted for first.
+    pub first: EldersInfo,
+    /// The conflicting info voted for afterwards.
+    pub second: EldersInfo,
+}
+
+impl ConflictingVotesPayload {
+    /// Returns the report if `voter` can't honestly have voted for both `first` and `second`.
+    pub fn new(
+        voter: PublicId,
+        first: &AccumulatingEvent,
+        second: &AccumulatingEvent,
+    ) -> Option<Self> {
+        match (first, second) {
+            (AccumulatingEvent::SectionInfo(first), AccumulatingEvent::SectionInfo(second))
+                if first.prefix() == second.prefix()
+                    && first.version() == second.version()
+                    && first != second =>
+            {
+                Some(Self {
+                    voter,
+                    first: first.clone(),
+                    second: second.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct OnlinePayload {
     pub pub_id: PublicId,
     pub age: u8,
+    /// Whether the node solved the resource proof we challenged it with.
+    pub resource_proof_verified: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct NamespacedUserPayload {
+    pub namespace: String,
+    pub payload: Vec<u8>,
+}
+
+impl Debug for NamespacedUserPayload {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{{ namespace: {:?}, payload: {:<8} }}",
+            self.namespace,
+            HexFmt(&self.payload)
+        )
+    }
 }
 
 /// Routing Network events
@@ -77,6 +134,14 @@ pub enum AccumulatingEvent {
     OurMerge,
     NeighbourMerge(Digest256),
     SectionInfo(EldersInfo),
+    // Routine change of our elders, voted for instead of the whole new `EldersInfo`. Each node
+    // rebuilds the new info by applying the change to our current one. The signature shares
+    // attached to the votes still sign the rebuilt info.
+    EldersChanged {
+        added: BTreeSet<PublicId>,
+        removed: BTreeSet<PublicId>,
+        version: u64,
+    },
 
     // Voted for received message with keys to we can update their_keys
     TheirKeyInfo(SectionKeyInfo),
@@ -84,8 +149,8 @@ pub enum AccumulatingEvent {
     // Voted for received AckMessage to update their_knowledge
     AckMessage(AckMessagePayload),
 
-    // Voted for sending AckMessage (Require 100% consensus)
-    SendAckMessage(SendAckMessagePayload),
+    // Voted for sending AckMessages to several sections at once (Require 100% consensus)
+    SendAckMessages(SendAckMessagesPayload),
 
     // Prune the gossip graph.
     ParsecPrune,
@@ -93,8 +158,24 @@ pub enum AccumulatingEvent {
     // Voted for node to be relocated out of our section.
     Relocate(RelocateDetails),
 
+    // Voted for node whose relocation out of our section completed.
+    RelocationComplete(PublicId),
+
+    // Voted for an elder which asked to step down to adult while staying a member of our section.
+    StepDown(PublicId),
+
     // Opaque user-defined event.
     User(Vec<u8>),
+
+    // User-defined event in a namespace. Events of different namespaces accumulate independently.
+    NamespacedUser(NamespacedUserPayload),
+
+    // Voted for a voter detected voting for mutually exclusive events.
+    ConflictingVotes(ConflictingVotesPayload),
+
+    // Several events voted for in a single observation. Unpacked once polled from parsec, so the
+    // accumulator only ever sees the individual events, each with the voter's proof of the batch.
+    Batch(Vec<NetworkEvent>),
 }
 
 impl AccumulatingEvent {
@@ -118,6 +199,31 @@ impl AccumulatingEvent {
         }
     }
 
+    /// Returns the name of the variant, e.g. for grouping events in metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AccumulatingEvent::AddElder(_) => "AddElder",
+            AccumulatingEvent::RemoveElder(_) => "RemoveElder",
+            AccumulatingEvent::Online(_) => "Online",
+            AccumulatingEvent::Offline(_) => "Offline",
+            AccumulatingEvent::OurMerge => "OurMerge",
+            AccumulatingEvent::NeighbourMerge(_) => "NeighbourMerge",
+            AccumulatingEvent::SectionInfo(_) => "SectionInfo",
+            AccumulatingEvent::EldersChanged { .. } => "EldersChanged",
+            AccumulatingEvent::TheirKeyInfo(_) => "TheirKeyInfo",
+            AccumulatingEvent::AckMessage(_) => "AckMessage",
+            AccumulatingEvent::SendAckMessages(_) => "SendAckMessages",
+            AccumulatingEvent::ParsecPrune => "ParsecPrune",
+            AccumulatingEvent::Relocate(_) => "Relocate",
+            AccumulatingEvent::RelocationComplete(_) => "RelocationComplete",
+            AccumulatingEvent::StepDown(_) => "StepDown",
+            AccumulatingEvent::User(_) => "User",
+            AccumulatingEvent::NamespacedUser(_) => "NamespacedUser",
+            AccumulatingEvent::ConflictingVotes(_) => "ConflictingVotes",
+            AccumulatingEvent::Batch(_) => "Batch",
+        }
+    }
+
     pub fn elders_info(&self) -> Option<&EldersInfo> {
         match self {
             AccumulatingEvent::SectionInfo(info) => Some(info),
@@ -138,18 +244,38 @@ impl Debug for AccumulatingEvent {
                 write!(formatter, "NeighbourMerge({:.14?})", HexFmt(digest))
             }
             AccumulatingEvent::SectionInfo(info) => write!(formatter, "SectionInfo({:?})", info),
+            AccumulatingEvent::EldersChanged {
+                added,
+                removed,
+                version,
+            } => write!(
+                formatter,
+                "EldersChanged {{ added: {:?}, removed: {:?}, version: {} }}",
+                added, removed, version
+            ),
             AccumulatingEvent::TheirKeyInfo(payload) => {
                 write!(formatter, "TheirKeyInfo({:?})", payload)
             }
             AccumulatingEvent::AckMessage(payload) => {
                 write!(formatter, "AckMessage({:?})", payload)
             }
-            AccumulatingEvent::SendAckMessage(payload) => {
-                write!(formatter, "SendAckMessage({:?})", payload)
+            AccumulatingEvent::SendAckMessages(payload) => {
+                write!(formatter, "SendAckMessages({:?})", payload)
             }
             AccumulatingEvent::ParsecPrune => write!(formatter, "ParsecPrune"),
             AccumulatingEvent::Relocate(payload) => write!(formatter, "Relocate({:?})", payload),
+            AccumulatingEvent::RelocationComplete(id) => {
+                write!(formatter, "RelocationComplete({})", id)
+            }
+            AccumulatingEvent::StepDown(id) => write!(formatter, "StepDown({})", id),
             AccumulatingEvent::User(payload) => write!(formatter, "User({:<8})", HexFmt(payload)),
+            AccumulatingEvent::NamespacedUser(payload) => {
+                write!(formatter, "NamespacedUser({:?})", payload)
+            }
+            AccumulatingEvent::ConflictingVotes(payload) => {
+                write!(formatter, "ConflictingVotes({:?})", payload)
+            }
+            AccumulatingEvent::Batch(events) => write!(formatter, "Batch({:?})", events),
         }
     }
 }
@@ -0,0 +1,80 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{QUORUM_DENOMINATOR, QUORUM_NUMERATOR};
+use std::fmt::Debug;
+
+/// The share of the elders of a section whose votes are needed for an event to accumulate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quorum {
+    /// Strictly more than half of the elders.
+    SimpleMajority,
+    /// Strictly more than `QUORUM_NUMERATOR / QUORUM_DENOMINATOR` of the elders.
+    Supermajority,
+    /// All the elders.
+    All,
+}
+
+impl Quorum {
+    /// Returns whether `votes` out of `voters` reach this quorum.
+    pub fn is_reached(self, votes: usize, voters: usize) -> bool {
+        match self {
+            Quorum::SimpleMajority => votes * 2 > voters,
+            Quorum::Supermajority => votes * QUORUM_DENOMINATOR > voters * QUORUM_NUMERATOR,
+            Quorum::All => votes >= voters,
+        }
+    }
+}
+
+/// Decides which quorum the votes for each kind of event need to reach before the event
+/// accumulates.
+pub trait QuorumPolicy: Debug + Send + Sync {
+    /// Returns the quorum required for events of the given kind. The kinds are the names of the
+    /// event variants, as reported in `AccumulatorMetrics::pending_per_kind`.
+    fn quorum(&self, event_kind: &str) -> Quorum;
+}
+
+/// The default policy: a supermajority for all events except `SendAckMessages`, which needs all the
+/// elders. We may then not reach consensus if some elder is malicious, but when we do, we know all
+/// of them have updated their keys.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultQuorumPolicy;
+
+impl QuorumPolicy for DefaultQuorumPolicy {
+    fn quorum(&self, event_kind: &str) -> Quorum {
+        match event_kind {
+            "SendAckMessages" => Quorum::All,
+            _ => Quorum::Supermajority,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quorum_thresholds() {
+        assert!(!Quorum::SimpleMajority.is_reached(3, 6));
+        assert!(Quorum::SimpleMajority.is_reached(4, 7));
+
+        assert!(!Quorum::Supermajority.is_reached(4, 6));
+        assert!(Quorum::Supermajority.is_reached(5, 7));
+
+        assert!(!Quorum::All.is_reached(6, 7));
+        assert!(Quorum::All.is_reached(7, 7));
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy = DefaultQuorumPolicy;
+        assert_eq!(policy.quorum("SendAckMessages"), Quorum::All);
+        assert_eq!(policy.quorum("SectionInfo"), Quorum::Supermajority);
+        assert_eq!(policy.quorum("User"), Quorum::Supermajority);
+    }
+}
@@ -11,8 +11,8 @@ use super::{
     MemberPersona, MemberState, MIN_AGE_COUNTER,
 };
 use crate::{
-    crypto::Digest256, error::RoutingError, id::PublicId, utils::LogIdent, BlsPublicKey,
-    BlsPublicKeySet, BlsSignature, Prefix, XorName,
+    crypto::Digest256, error::RoutingError, id::PublicId, messages::SignedRoutingMessage,
+    utils::LogIdent, BlsPublicKey, BlsSignature, Prefix, XorName,
 };
 use itertools::Itertools;
 use log::LogLevel;
@@ -94,16 +94,27 @@ impl SharedState {
         }
     }
 
-    pub fn update_with_genesis_related_info(
+    /// Returns a snapshot of the state needed to prove and route messages for our section.
+    pub fn snapshot(&self) -> ChainSnapshot {
+        ChainSnapshot {
+            our_infos: self.our_infos.clone(),
+            our_history: self.our_history.clone(),
+            our_members: self.our_members.clone(),
+            neighbour_infos: self.neighbour_infos.clone(),
+            their_keys: self.their_keys.clone(),
+            their_knowledge: self.their_knowledge.clone(),
+            their_recent_keys: self.their_recent_keys.clone(),
+        }
+    }
+
+    /// Replaces our state with the one from `snapshot`. If we already have a history, it is
+    /// expected to match the snapshot and any difference is reported as an error.
+    pub fn restore_snapshot(
         &mut self,
-        related_info: &[u8],
+        snapshot: ChainSnapshot,
         log_ident: &LogIdent,
     ) -> Result<(), RoutingError> {
-        if related_info.is_empty() {
-            return Ok(());
-        }
-
-        let (
+        let ChainSnapshot {
             our_infos,
             our_history,
             our_members,
@@ -111,7 +122,11 @@ impl SharedState {
             their_keys,
             their_knowledge,
             their_recent_keys,
-        ) = serialisation::deserialise(related_info)?;
+        } = snapshot;
+        if !our_history.validate() {
+            return Err(RoutingError::InvalidProvingSection);
+        }
+
         if self.our_infos.len() != 1 {
             // Check nodes with a history before genesis match the genesis block:
             if self.our_infos != our_infos {
@@ -189,16 +204,20 @@ impl SharedState {
         Ok(())
     }
 
-    pub fn get_genesis_related_info(&self) -> Result<Vec<u8>, RoutingError> {
-        Ok(serialisation::serialise(&(
-            &self.our_infos,
-            &self.our_history,
-            &self.our_members,
-            &self.neighbour_infos,
-            &self.their_keys,
-            &self.their_knowledge,
-            &self.their_recent_keys,
-        ))?)
+    /// Drops all but the latest `max_our_infos` of our section infos, and the part of our key
+    /// history that neither these infos nor any section in `their_knowledge` still need.
+    pub fn compact(&mut self, max_our_infos: usize) {
+        self.our_infos.truncate_front(max_our_infos);
+
+        let oldest_info_version = *self.our_infos.first().version();
+        let first_version = self
+            .their_knowledge
+            .values()
+            .copied()
+            .chain(iter::once(oldest_info_version))
+            .min()
+            .unwrap_or(oldest_info_version);
+        self.our_history = self.our_history.slice_from(first_version as usize);
     }
 
     pub fn our_infos(&self) -> impl Iterator<Item = &EldersInfo> + DoubleEndedIterator {
@@ -312,19 +331,17 @@ impl SharedState {
         neighbour_infos.into_iter().any(needs_merge)
     }
 
+    /// Appends our new elders info, signed by the section signature `sig` of the previous elders.
     pub fn push_our_new_info(
         &mut self,
         elders_info: EldersInfo,
-        proofs: AccumulatingProof,
-        pk_set: &BlsPublicKeySet,
+        sig: BlsSignature,
     ) -> Result<(), RoutingError> {
-        let proof_block = if let Some(proof_block) =
-            SectionProofBlock::from_elders_info_with_proofs(&elders_info, proofs, pk_set)
-        {
-            proof_block
-        } else {
+        let proof_block =
+            SectionProofBlock::new(SectionKeyInfo::from_elders_info(&elders_info), sig);
+        if !proof_block.verify_with_pk(self.our_history.last_public_key()) {
             return Err(RoutingError::InvalidNewSectionInfo);
-        };
+        }
 
         self.our_history.push(proof_block);
         self.our_infos.push(elders_info);
@@ -412,7 +429,6 @@ impl SharedState {
             .chain(self.their_recent_keys.iter().map(|(p, k)| (p, k)))
     }
 
-    #[cfg(feature = "mock_base")]
     /// Returns their_knowledge
     pub fn get_their_knowledge(&self) -> &BTreeMap<Prefix<XorName>, u64> {
         &self.their_knowledge
@@ -427,8 +443,21 @@ pub enum PrefixChange {
     Merging,
 }
 
+/// The part of `SharedState` which is exchanged between elders at parsec genesis, and which can
+/// be exported to restore a compacted chain later.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSnapshot {
+    our_infos: NonEmptyList<EldersInfo>,
+    our_history: SectionProofChain,
+    our_members: BTreeMap<PublicId, MemberInfo>,
+    neighbour_infos: BTreeMap<Prefix<XorName>, EldersInfo>,
+    their_keys: BTreeMap<Prefix<XorName>, SectionKeyInfo>,
+    their_knowledge: BTreeMap<Prefix<XorName>, u64>,
+    their_recent_keys: VecDeque<(Prefix<XorName>, SectionKeyInfo)>,
+}
+
 /// Vec-like container that is guaranteed to contain at least one element.
-#[derive(PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NonEmptyList<T> {
     head: Vec<T>,
     tail: T,
@@ -450,6 +479,17 @@ impl<T> NonEmptyList<T> {
         self.head.len() + 1
     }
 
+    /// Removes the oldest elements so that at most `max_len` remain. The last element is always
+    /// kept.
+    pub fn truncate_front(&mut self, max_len: usize) {
+        let excess = self.len().saturating_sub(std::cmp::max(max_len, 1));
+        let _ = self.head.drain(..excess);
+    }
+
+    pub fn first(&self) -> &T {
+        self.head.first().unwrap_or(&self.tail)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &T> + DoubleEndedIterator {
         self.head.iter().chain(iter::once(&self.tail))
     }
@@ -485,21 +525,8 @@ impl Debug for SectionProofBlock {
 }
 
 impl SectionProofBlock {
-    pub fn from_elders_info_with_proofs(
-        elders_info: &EldersInfo,
-        proofs: AccumulatingProof,
-        pk_set: &BlsPublicKeySet,
-    ) -> Option<Self> {
-        let key_info = SectionKeyInfo::from_elders_info(elders_info);
-
-        let sig_shares = proofs.into_sig_shares();
-        let sig = pk_set.combine_signatures(
-            sig_shares
-                .values()
-                .map(|sig_payload| (sig_payload.pub_key_share, &sig_payload.sig_share)),
-        );
-
-        sig.map(|sig| SectionProofBlock { key_info, sig })
+    pub fn new(key_info: SectionKeyInfo, sig: BlsSignature) -> Self {
+        SectionProofBlock { key_info, sig }
     }
 
     pub fn key_info(&self) -> &SectionKeyInfo {
@@ -538,6 +565,8 @@ impl hash::Hash for SectionProofBlock {
 
 impl Eq for SectionProofBlock {}
 
+/// Chain of the keys of a section, each one signed by the previous one. Knowing and trusting any
+/// key of the chain, one can trust all the later ones.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SectionProofChain {
     genesis_key_info: SectionKeyInfo,
@@ -545,6 +574,7 @@ pub struct SectionProofChain {
 }
 
 impl SectionProofChain {
+    /// Creates a chain containing only the given key.
     pub fn from_genesis(key_info: SectionKeyInfo) -> Self {
         Self {
             genesis_key_info: key_info,
@@ -552,14 +582,17 @@ impl SectionProofChain {
         }
     }
 
+    /// Returns the number of keys in the chain after the first one.
     pub fn blocks_len(&self) -> usize {
         self.blocks.len()
     }
 
+    /// Appends a key to the chain, without validating it.
     pub fn push(&mut self, block: SectionProofBlock) {
         self.blocks.push(block);
     }
 
+    /// Returns whether each key of the chain is signed by the previous one.
     pub fn validate(&self) -> bool {
         let mut current_pk = self.genesis_key_info.key();
         for block in &self.blocks {
@@ -571,6 +604,7 @@ impl SectionProofChain {
         true
     }
 
+    /// Returns the latest key of the chain.
     pub fn last_public_key_info(&self) -> &SectionKeyInfo {
         self.blocks
             .last()
@@ -578,15 +612,40 @@ impl SectionProofChain {
             .unwrap_or(&self.genesis_key_info)
     }
 
+    /// Returns the public key of the latest key of the chain.
     pub fn last_public_key(&self) -> &BlsPublicKey {
         self.last_public_key_info().key()
     }
 
+    /// Returns all the keys of the chain, oldest first.
     pub fn all_key_infos(&self) -> impl DoubleEndedIterator<Item = &SectionKeyInfo> {
         iter::once(&self.genesis_key_info).chain(self.blocks.iter().map(|block| block.key_info()))
     }
 
+    /// Checks that this chain is valid and that `msg` is fully signed by its source section with
+    /// one of the keys of this chain.
+    pub fn verify_message(&self, msg: &SignedRoutingMessage) -> Result<(), RoutingError> {
+        if !self.validate() {
+            return Err(RoutingError::InvalidProvingSection);
+        }
+
+        msg.check_integrity()?;
+
+        let key_info = msg
+            .source_section_key_info()
+            .ok_or(RoutingError::UntrustedMessage)?;
+        if self.all_key_infos().any(|info| info == key_info) {
+            Ok(())
+        } else {
+            Err(RoutingError::UntrustedMessage)
+        }
+    }
+
+    /// Returns the part of the chain starting at the key with index `first_index`, i.e. the one
+    /// of version `first_index`. The chain may have been compacted, so the first key it holds is
+    /// not necessarily the one of version 0.
     pub fn slice_from(&self, first_index: usize) -> SectionProofChain {
+        let first_index = first_index.saturating_sub(*self.genesis_key_info.version() as usize);
         if first_index == 0 || self.blocks.is_empty() {
             return self.clone();
         }
@@ -674,7 +733,10 @@ impl Debug for SectionKeyInfo {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{chain::EldersInfo, FullId, Prefix, XorName};
+    use crate::{
+        chain::{EldersInfo, SectionInfoSigPayload},
+        BlsPublicKeySet, FullId, Prefix, XorName,
+    };
     use std::collections::BTreeSet;
     use std::str::FromStr;
     use unwrap::unwrap;
@@ -814,4 +876,85 @@ mod test {
             ],
         );
     }
+
+    fn gen_ids_and_elders_info(pfx: Prefix<XorName>, version: u64) -> (Vec<FullId>, EldersInfo) {
+        let ids: Vec<_> = (0..5)
+            .map(|_| FullId::within_range(&pfx.range_inclusive()))
+            .collect();
+        let members = ids.iter().map(|id| *id.public_id()).collect();
+        let elders_info = unwrap!(EldersInfo::new_for_test(members, pfx, version));
+        (ids, elders_info)
+    }
+
+    // Creates a state whose own section went through the versions `0..count`.
+    fn gen_state_with_history(count: u64) -> SharedState {
+        let (mut ids, mut prev_info) = gen_ids_and_elders_info(Prefix::default(), 0);
+        let mut state = SharedState::new(prev_info.clone(), Default::default());
+
+        for version in 1..count {
+            let (new_ids, info) = gen_ids_and_elders_info(Prefix::default(), version);
+            let shares: Vec<_> = ids
+                .iter()
+                .map(|id| unwrap!(SectionInfoSigPayload::new(&info, id)))
+                .collect();
+            let sig = unwrap!(
+                BlsPublicKeySet::from_elders_info(prev_info).combine_signatures(
+                    shares
+                        .iter()
+                        .map(|share| (share.pub_key_share, &share.sig_share))
+                )
+            );
+            unwrap!(state.push_our_new_info(info.clone(), sig));
+
+            ids = new_ids;
+            prev_info = info;
+        }
+
+        state
+    }
+
+    fn history_versions(state: &SharedState) -> Vec<u64> {
+        state
+            .our_history
+            .all_key_infos()
+            .map(|info| *info.version())
+            .collect()
+    }
+
+    #[test]
+    fn compact() {
+        let mut state = gen_state_with_history(6);
+        let their_pfx = unwrap!(Prefix::from_str("1"));
+        state.update_their_knowledge(their_pfx, 2);
+
+        state.compact(2);
+
+        let info_versions: Vec<_> = state.our_infos().map(|info| *info.version()).collect();
+        assert_eq!(info_versions, vec![4, 5]);
+        // The keys known by the other section are still needed to prove our messages to it.
+        assert_eq!(history_versions(&state), vec![2, 3, 4, 5]);
+        assert!(state.our_history.validate());
+
+        let proof = state.our_history.slice_from(3);
+        assert_eq!(*unwrap!(proof.all_key_infos().next()).version(), 3);
+        assert!(proof.validate());
+
+        // Once they know a newer key, the older ones can go too.
+        state.update_their_knowledge(their_pfx, 5);
+        state.compact(2);
+        assert_eq!(history_versions(&state), vec![4, 5]);
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let mut state = gen_state_with_history(4);
+        state.compact(2);
+
+        let serialised = unwrap!(serialisation::serialise(&state.snapshot()));
+        let snapshot = unwrap!(serialisation::deserialise(&serialised));
+
+        let mut restored = SharedState::new(state.our_info().clone(), Default::default());
+        unwrap!(restored.restore_snapshot(snapshot, &LogIdent::new("test")));
+        assert_eq!(restored.snapshot(), state.snapshot());
+    }
 }
@@ -0,0 +1,399 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Splitting of the user messages too large for the transport into parts, and their reassembly at
+//! the destination.
+
+use crate::{
+    crypto::{self, Digest256},
+    error::RoutingError,
+    messages::MessageContent,
+    routing_table::Authority,
+    time::{Duration, Instant},
+    types::MessageId,
+    xor_name::XorName,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Maximum size in bytes of the content of a user message sent in one piece. Larger contents are
+/// split into parts of at most this size, leaving room below the quic-p2p message limit for the
+/// rest of the message and its signatures.
+#[cfg(not(feature = "mock_base"))]
+pub const MAX_PART_LEN: usize = 512 * 1024;
+#[cfg(feature = "mock_base")]
+pub const MAX_PART_LEN: usize = 1024;
+
+/// Maximum number of parts a user message may be split into.
+const MAX_PART_COUNT: u32 = 128;
+
+/// Maximum size in bytes of the content of a user message.
+pub const MAX_MESSAGE_LEN: usize = MAX_PART_COUNT as usize * MAX_PART_LEN;
+
+/// Maximum number of messages from the same source we reassemble at the same time. Parts of
+/// further messages are rejected until some of them complete or expire.
+const MAX_TRANSFERS_PER_SRC: usize = 16;
+
+/// Maximum number of bytes of parts we hold at the same time, whatever their sources. Further parts
+/// are rejected until some messages complete or expire.
+const MAX_BUFFERED_LEN: usize = 4 * MAX_MESSAGE_LEN;
+
+/// Time after which we give up reassembling a message whose parts stopped arriving.
+pub const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// Returns the id of a user message for which the user didn't choose one: a random one if `src`
+/// is a single node, otherwise one derived from the content, as all the elders sending the message
+/// on behalf of their section need to choose the same one for their signatures to accumulate.
+pub fn message_id(src: &Authority<XorName>, content: &[u8]) -> MessageId {
+    if src.is_single() {
+        MessageId::new()
+    } else {
+        MessageId::from_digest(crypto::sha3_256(content))
+    }
+}
+
+/// Returns the contents of the routing messages carrying the user message `content` with the id
+/// `msg_id`: the message itself if it is small enough, otherwise its parts. A message whose
+/// delivery is to be acknowledged is always sent as parts, as only they ask for the
+/// acknowledgement. Fails if the content is larger than `MAX_MESSAGE_LEN`.
+pub fn split(
+    content: Vec<u8>,
+    msg_id: MessageId,
+    ack: bool,
+) -> Result<Vec<MessageContent>, RoutingError> {
+    if content.len() > MAX_MESSAGE_LEN {
+        return Err(RoutingError::InvalidMessage);
+    }
+
+    if content.len() <= MAX_PART_LEN && !ack {
+        return Ok(vec![MessageContent::UserMessage { content, msg_id }]);
+    }
+
+    // The parts only depend on the content and the id, so that the elders sending the same message
+    // on behalf of their section send the same parts, and their signatures accumulate.
+    let digest = crypto::sha3_256(&content);
+    // An empty message still needs a part to ask for the acknowledgement.
+    if content.is_empty() {
+        return Ok(vec![MessageContent::UserMessagePart {
+            digest,
+            index: 0,
+            part_count: 1,
+            payload: content,
+            msg_id,
+            ack,
+        }]);
+    }
+
+    let part_count = ((content.len() + MAX_PART_LEN - 1) / MAX_PART_LEN) as u32;
+
+    Ok(content
+        .chunks(MAX_PART_LEN)
+        .enumerate()
+        .map(|(index, payload)| MessageContent::UserMessagePart {
+            digest,
+            index: index as u32,
+            part_count,
+            payload: payload.to_vec(),
+            msg_id,
+            ack,
+        })
+        .collect())
+}
+
+/// A message which expired before all its parts were received.
+#[derive(Debug, Eq, PartialEq)]
+pub struct IncompleteMessage {
+    pub src: Authority<XorName>,
+    pub dst: Authority<XorName>,
+    pub received_parts: u32,
+    pub part_count: u32,
+}
+
+type TransferKey = (Authority<XorName>, Authority<XorName>, Digest256);
+
+struct Transfer {
+    // Only the parts received so far, so that a message announcing many parts costs nothing until
+    // they actually arrive.
+    parts: BTreeMap<u32, Vec<u8>>,
+    part_count: u32,
+    expires_at: Instant,
+}
+
+/// The parts of the messages we received some but not all parts of yet.
+#[derive(Default)]
+pub struct Reassembler {
+    transfers: HashMap<TransferKey, Transfer>,
+    // Total size of the parts in `transfers`.
+    buffered_len: usize,
+}
+
+impl Reassembler {
+    /// Adds a received part, returning the content of the message if it was the last missing one.
+    /// Parts larger than `MAX_PART_LEN`, of messages larger than `MAX_MESSAGE_LEN`, of more than
+    /// `MAX_TRANSFERS_PER_SRC` messages of the same source at a time, or which would take the parts
+    /// we hold over `MAX_BUFFERED_LEN` bytes are rejected.
+    pub fn add(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        digest: Digest256,
+        index: u32,
+        part_count: u32,
+        payload: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, RoutingError> {
+        if index >= part_count || part_count > MAX_PART_COUNT || payload.len() > MAX_PART_LEN {
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        let key = (src, dst, digest);
+        if !self.transfers.contains_key(&key)
+            && self
+                .transfers
+                .keys()
+                .filter(|(transfer_src, _, _)| *transfer_src == src)
+                .count()
+                >= MAX_TRANSFERS_PER_SRC
+        {
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        let transfer = self.transfers.entry(key).or_insert_with(|| Transfer {
+            parts: BTreeMap::new(),
+            part_count,
+            expires_at: Instant::now() + REASSEMBLY_TIMEOUT,
+        });
+
+        if transfer.part_count != part_count {
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        let replaced_len = transfer.parts.get(&index).map_or(0, Vec::len);
+        if self.buffered_len - replaced_len + payload.len() > MAX_BUFFERED_LEN {
+            if transfer.parts.is_empty() {
+                let _ = self.transfers.remove(&key);
+            }
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        self.buffered_len = self.buffered_len - replaced_len + payload.len();
+        let _ = transfer.parts.insert(index, payload);
+        if transfer.parts.len() < part_count as usize {
+            return Ok(None);
+        }
+
+        let parts = self
+            .remove(&key)
+            .map(|transfer| transfer.parts)
+            .unwrap_or_default();
+        let content: Vec<u8> = parts.into_iter().flat_map(|(_, part)| part).collect();
+
+        if crypto::sha3_256(&content) == digest {
+            Ok(Some(content))
+        } else {
+            Err(RoutingError::InvalidMessage)
+        }
+    }
+
+    /// Drops the messages we didn't receive all parts of in time, and returns them.
+    pub fn remove_expired(&mut self) -> Vec<IncompleteMessage> {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .transfers
+            .iter()
+            .filter(|(_, transfer)| transfer.expires_at <= now)
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| {
+                let transfer = self.remove(&key)?;
+                Some(IncompleteMessage {
+                    src: key.0,
+                    dst: key.1,
+                    received_parts: transfer.parts.len() as u32,
+                    part_count: transfer.part_count,
+                })
+            })
+            .collect()
+    }
+
+    fn remove(&mut self, key: &TransferKey) -> Option<Transfer> {
+        let transfer = self.transfers.remove(key)?;
+        self.buffered_len -= transfer.parts.values().map(Vec::len).sum::<usize>();
+        Some(transfer)
+    }
+}
+
+#[cfg(all(test, feature = "mock_base"))]
+mod tests {
+    use super::*;
+    use fake_clock::FakeClock;
+    use unwrap::unwrap;
+
+    fn parts(content: Vec<u8>) -> Vec<(Digest256, u32, u32, Vec<u8>)> {
+        unwrap!(split(content, MessageId::new(), false))
+            .into_iter()
+            .map(|content| match content {
+                MessageContent::UserMessagePart {
+                    digest,
+                    index,
+                    part_count,
+                    payload,
+                    ..
+                } => (digest, index, part_count, payload),
+                content => panic!("Unexpected {:?}", content),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_message_is_not_split() {
+        let msg_id = MessageId::new();
+        let content = vec![7; MAX_PART_LEN];
+        assert_eq!(
+            unwrap!(split(content.clone(), msg_id, false)),
+            vec![MessageContent::UserMessage { content, msg_id }]
+        );
+    }
+
+    #[test]
+    fn acknowledged_message_is_sent_as_parts() {
+        let msg_id = MessageId::new();
+        let content = vec![7; MAX_PART_LEN];
+        assert_eq!(
+            unwrap!(split(content.clone(), msg_id, true)),
+            vec![MessageContent::UserMessagePart {
+                digest: crypto::sha3_256(&content),
+                index: 0,
+                part_count: 1,
+                payload: content,
+                msg_id,
+                ack: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn reassemble_parts_in_any_order() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Section(rand::random());
+        let content: Vec<u8> = (0..(2 * MAX_PART_LEN + 1)).map(|i| i as u8).collect();
+
+        let mut parts = parts(content.clone());
+        assert_eq!(parts.len(), 3);
+        parts.reverse();
+
+        let mut reassembler = Reassembler::default();
+        let mut results: Vec<_> = parts
+            .into_iter()
+            .map(|(digest, index, part_count, payload)| {
+                unwrap!(reassembler.add(src, dst, digest, index, part_count, payload))
+            })
+            .collect();
+
+        assert_eq!(results.pop(), Some(Some(content)));
+        assert!(results.into_iter().all(|result| result.is_none()));
+        assert!(reassembler.remove_expired().is_empty());
+    }
+
+    #[test]
+    fn report_incomplete_messages_once_expired() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let parts = parts(vec![1; 3 * MAX_PART_LEN]);
+
+        let mut reassembler = Reassembler::default();
+        for (digest, index, part_count, payload) in parts.into_iter().skip(1) {
+            assert_eq!(
+                unwrap!(reassembler.add(src, dst, digest, index, part_count, payload)),
+                None
+            );
+        }
+
+        assert!(reassembler.remove_expired().is_empty());
+        FakeClock::advance_time(REASSEMBLY_TIMEOUT.as_secs() * 1000 + 1);
+        assert_eq!(
+            reassembler.remove_expired(),
+            vec![IncompleteMessage {
+                src,
+                dst,
+                received_parts: 2,
+                part_count: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn reject_oversized_messages() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let digest = crypto::sha3_256(&[]);
+        let mut reassembler = Reassembler::default();
+
+        assert!(split(vec![0; MAX_MESSAGE_LEN + 1], MessageId::new(), false).is_err());
+        assert!(reassembler
+            .add(src, dst, digest, 0, 2, vec![0; MAX_PART_LEN + 1])
+            .is_err());
+        assert!(reassembler
+            .add(
+                src,
+                dst,
+                digest,
+                0,
+                MAX_PART_COUNT + 1,
+                vec![0; MAX_PART_LEN]
+            )
+            .is_err());
+        assert!(reassembler.remove_expired().is_empty());
+    }
+
+    #[test]
+    fn limit_concurrent_transfers_per_source() {
+        let src = Authority::Node(rand::random());
+        let other_src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let mut reassembler = Reassembler::default();
+
+        let mut add = |src, seed: u8| {
+            let digest = crypto::sha3_256(&[seed]);
+            reassembler.add(src, dst, digest, 0, 2, vec![seed])
+        };
+
+        for seed in 0..MAX_TRANSFERS_PER_SRC as u8 {
+            assert_eq!(unwrap!(add(src, seed)), None);
+        }
+        assert!(add(src, MAX_TRANSFERS_PER_SRC as u8).is_err());
+        // Parts of the messages already being reassembled, and of other sources, are still
+        // accepted.
+        assert_eq!(unwrap!(add(src, 0)), None);
+        assert_eq!(unwrap!(add(other_src, 0)), None);
+    }
+
+    #[test]
+    fn limit_buffered_parts_of_all_sources() {
+        let dst = Authority::Node(rand::random());
+        let mut reassembler = Reassembler::default();
+
+        let add = |reassembler: &mut Reassembler, seed: usize| {
+            let src = Authority::Node(rand::random());
+            let digest = crypto::sha3_256(&seed.to_le_bytes());
+            reassembler.add(src, dst, digest, 0, 2, vec![0; MAX_PART_LEN])
+        };
+
+        let part_count = MAX_BUFFERED_LEN / MAX_PART_LEN;
+        for seed in 0..part_count {
+            assert_eq!(unwrap!(add(&mut reassembler, seed)), None);
+        }
+        assert!(add(&mut reassembler, part_count).is_err());
+
+        // Expired messages free their share of the budget.
+        FakeClock::advance_time(REASSEMBLY_TIMEOUT.as_secs() * 1000 + 1);
+        assert_eq!(reassembler.remove_expired().len(), part_count);
+        assert_eq!(unwrap!(add(&mut reassembler, part_count)), None);
+    }
+}
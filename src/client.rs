@@ -0,0 +1,171 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    action::{Action, ActionSender},
+    error::{InterfaceError, RoutingError},
+    event::Event,
+    event_stream::{EventStepper, EventStream},
+    id::{FullId, PublicId},
+    outbox::{EventBox, EventBuf},
+    quic_p2p::OurType,
+    routing_table::Authority,
+    state_machine::{State, StateMachine},
+    states,
+    xor_name::XorName,
+    NetworkBytes, NetworkConfig,
+};
+use crossbeam_channel as mpmc;
+use quic_p2p::Token;
+use std::sync::mpsc;
+
+/// A builder to configure and create a new `Client`.
+pub struct ClientBuilder {
+    network_config: Option<NetworkConfig>,
+    full_id: Option<FullId>,
+}
+
+impl ClientBuilder {
+    /// The client will use the given network config rather than default.
+    pub fn network_config(self, config: NetworkConfig) -> Self {
+        Self {
+            network_config: Some(config),
+            ..self
+        }
+    }
+
+    /// The client will use the given full id rather than default, randomly generated one.
+    pub fn full_id(self, full_id: FullId) -> Self {
+        Self {
+            full_id: Some(full_id),
+            ..self
+        }
+    }
+
+    /// Creates new `Client`.
+    ///
+    /// It bootstraps to a proxy node and raises `Event::Connected` once connected. Unlike a
+    /// `Node`, it never joins the network.
+    pub fn create(self) -> Result<Client, RoutingError> {
+        let mut event_buffer = EventBuf::new();
+        let (_, machine) = self.make_state_machine(&mut event_buffer);
+        let (interface_result_tx, interface_result_rx) = mpsc::channel();
+
+        Ok(Client {
+            interface_result_tx,
+            interface_result_rx,
+            machine,
+            event_buffer,
+        })
+    }
+
+    fn make_state_machine(self, outbox: &mut dyn EventBox) -> (ActionSender, StateMachine) {
+        StateMachine::start(
+            OurType::Client,
+            self.full_id,
+            self.network_config,
+            |network_service, full_id, timer, _outbox| {
+                State::Client(states::Client::new(network_service, full_id, timer))
+            },
+            outbox,
+        )
+    }
+}
+
+/// Interface for exchanging messages with the network in the role of a client.
+///
+/// A client connects to the network through a single proxy node. It sends requests to the proxy
+/// with `send_request` and receives the proxy's messages as `Event::ResponseReceived`. With
+/// `send_message`, it has the proxy relay a signed message into the network instead. Once
+/// connected, the client identifies itself to the proxy with its public id, so that the proxy can
+/// authorise it. If the connection to the proxy is lost, the client bootstraps again shortly after
+/// and raises `Event::Connected` again.
+pub struct Client {
+    interface_result_tx: mpsc::Sender<Result<(), InterfaceError>>,
+    interface_result_rx: mpsc::Receiver<Result<(), InterfaceError>>,
+    machine: StateMachine,
+    event_buffer: EventBuf,
+}
+
+impl Client {
+    /// Creates a new builder to configure and create a `Client`.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder {
+            network_config: None,
+            full_id: None,
+        }
+    }
+
+    /// Returns the `PublicId` of this client.
+    pub fn id(&self) -> Result<PublicId, RoutingError> {
+        self.machine.current().id().ok_or(RoutingError::Terminated)
+    }
+
+    /// Sends a request to our proxy node. Fails with `InterfaceError::NotConnected` if we are not
+    /// connected to one.
+    pub fn send_request(&mut self, msg: NetworkBytes, token: Token) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendRequest {
+            msg,
+            token,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        let transition = self
+            .machine
+            .current_mut()
+            .handle_action(action, &mut self.event_buffer);
+        self.machine
+            .apply_transition(transition, &mut self.event_buffer);
+        self.interface_result_rx.recv()?
+    }
+
+    /// Sends a message to `dst`, signed by us and relayed into the network by our proxy node. The
+    /// recipients receive it as `Event::MessageReceived` carrying our `PublicId`. Fails with
+    /// `InterfaceError::NotConnected` if we are not connected to a proxy.
+    pub fn send_message(
+        &mut self,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendClientRequest {
+            dst,
+            content,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        let transition = self
+            .machine
+            .current_mut()
+            .handle_action(action, &mut self.event_buffer);
+        self.machine
+            .apply_transition(transition, &mut self.event_buffer);
+        self.interface_result_rx.recv()?
+    }
+}
+
+impl EventStepper for Client {
+    type Item = Event;
+
+    fn produce_events(&mut self) -> Result<(), mpmc::RecvError> {
+        self.machine.step(&mut self.event_buffer)
+    }
+
+    fn try_produce_events(&mut self) -> Result<(), mpmc::TryRecvError> {
+        self.machine.try_step(&mut self.event_buffer)
+    }
+
+    fn pop_item(&mut self) -> Option<Event> {
+        self.event_buffer.take_first()
+    }
+}
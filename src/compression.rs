@@ -0,0 +1,119 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Compression of the serialised messages we send to the peers which support it, as announced in
+//! their `BootstrapRequest`.
+
+use crate::error::RoutingError;
+use std::borrow::Cow;
+
+/// Size in bytes from which a serialised message is compressed.
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+/// Maximum size in bytes of a decompressed message. Compressed messages which would be larger are
+/// rejected before anything is allocated for them.
+#[cfg(not(feature = "mock_base"))]
+pub const MAX_DECOMPRESSED_LEN: usize = 32 * 1024 * 1024;
+#[cfg(feature = "mock_base")]
+pub const MAX_DECOMPRESSED_LEN: usize = 1024 * 1024;
+
+// Prefix of the compressed messages. A serialised `Message` never starts with it, as it starts with
+// the tag of its envelope, or with the index of its variant for the older versions.
+const COMPRESSED_PREFIX: &[u8] = &[0xff, 0xff, 0xff, 0xff];
+
+/// Compresses the serialised message if it is large enough and compression actually makes it
+/// smaller, otherwise returns it unchanged.
+pub fn compress(bytes: Vec<u8>) -> Vec<u8> {
+    if bytes.len() < COMPRESSION_THRESHOLD {
+        return bytes;
+    }
+
+    match snap::Encoder::new().compress_vec(&bytes) {
+        Ok(compressed) if compressed.len() + COMPRESSED_PREFIX.len() < bytes.len() => {
+            let mut output = Vec::with_capacity(COMPRESSED_PREFIX.len() + compressed.len());
+            output.extend_from_slice(COMPRESSED_PREFIX);
+            output.extend_from_slice(&compressed);
+            output
+        }
+        _ => bytes,
+    }
+}
+
+/// Returns the size in bytes the received message has once decompressed, without decompressing
+/// it. Fails if it is compressed but doesn't say how large it is.
+pub fn decompressed_len(bytes: &[u8]) -> Result<usize, RoutingError> {
+    if !bytes.starts_with(COMPRESSED_PREFIX) {
+        return Ok(bytes.len());
+    }
+
+    snap::decompress_len(&bytes[COMPRESSED_PREFIX.len()..])
+        .map_err(|_| RoutingError::InvalidMessage)
+}
+
+/// Decompresses the received message if it was compressed, otherwise returns it unchanged. A
+/// compressed message is rejected if `accept_compressed` is false, as its sender isn't one we
+/// agreed to exchange compressed messages with, or if it is larger than `MAX_DECOMPRESSED_LEN` once
+/// decompressed.
+pub fn decompress(bytes: &[u8], accept_compressed: bool) -> Result<Cow<[u8]>, RoutingError> {
+    if !bytes.starts_with(COMPRESSED_PREFIX) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+
+    if !accept_compressed {
+        return Err(RoutingError::InvalidMessage);
+    }
+
+    let compressed = &bytes[COMPRESSED_PREFIX.len()..];
+    let len = snap::decompress_len(compressed).map_err(|_| RoutingError::InvalidMessage)?;
+    if len > MAX_DECOMPRESSED_LEN {
+        return Err(RoutingError::InvalidMessage);
+    }
+
+    snap::Decoder::new()
+        .decompress_vec(compressed)
+        .map(Cow::Owned)
+        .map_err(|_| RoutingError::InvalidMessage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    #[test]
+    fn compress_only_large_messages() {
+        let small = vec![0; COMPRESSION_THRESHOLD - 1];
+        assert_eq!(compress(small.clone()), small);
+        assert_eq!(unwrap!(decompress(&small, false)), &small[..]);
+
+        let large = vec![0; 2 * COMPRESSION_THRESHOLD];
+        let compressed = compress(large.clone());
+        assert!(compressed.len() < large.len());
+        assert_eq!(unwrap!(decompressed_len(&compressed)), large.len());
+        assert_eq!(unwrap!(decompress(&compressed, true)), &large[..]);
+    }
+
+    #[test]
+    fn reject_compressed_messages_not_agreed_on() {
+        let compressed = compress(vec![0; 2 * COMPRESSION_THRESHOLD]);
+        assert!(decompress(&compressed, false).is_err());
+    }
+
+    #[test]
+    fn reject_messages_too_large_once_decompressed() {
+        let compressed = compress(vec![0; MAX_DECOMPRESSED_LEN + 1]);
+        assert!(decompress(&compressed, true).is_err());
+    }
+
+    #[test]
+    fn reject_corrupt_compressed_messages() {
+        let mut corrupt = COMPRESSED_PREFIX.to_vec();
+        corrupt.extend_from_slice(&[0xff; 16]);
+        assert!(decompress(&corrupt, true).is_err());
+    }
+}
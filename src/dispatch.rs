@@ -0,0 +1,573 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::RoutingError,
+    id::PublicId,
+    local_params::InboundLimits,
+    messages::{DirectMessage, MessageContent},
+    routing_table::Authority,
+    xor_name::XorName,
+};
+use maidsafe_utilities::serialisation::serialise;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+/// Kind of a routing message, i.e. of a message sent from one authority to another and possibly
+/// relayed by other nodes on the way.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RoutingMessageKind {
+    /// Request of a node to connect to us.
+    ConnectionRequest,
+    /// The elders of a neighbour section.
+    NeighbourInfo,
+    /// Request of a neighbour section to merge with ours.
+    Merge,
+    /// Message of the user of a node.
+    UserMessage,
+    /// Part of a message of the user of a node, too large to be sent in one piece.
+    UserMessagePart,
+    /// Message of the user of a node, or part of it, encrypted to the destination section. Never
+    /// seen by a `MessageHandler`, which sees the decrypted message instead.
+    EncryptedUserMessage,
+    /// Acknowledgement of a user message by its destination.
+    DeliveryAck,
+    /// Part of a stream of the user of a node.
+    StreamPart,
+    /// Credit granted to the sender of a stream by its destination.
+    StreamCredit,
+    /// Request of the user of a node, expecting a response.
+    UserRequest,
+    /// Response of the user of a node to a request.
+    UserResponse,
+    /// Report that a message couldn't be delivered to its destination.
+    Undeliverable,
+    /// Rejection of a user message by the filters of its destination.
+    MessageRejected,
+    /// Message of a client, relayed into the network by its proxy elder.
+    RelayedClientMessage,
+    /// Approval of a joining node by its section.
+    NodeApproval,
+    /// Acknowledgement of our section's info by another section.
+    AckMessage,
+    /// Request of its section to a node to relocate.
+    Relocate,
+}
+
+/// Kind of a direct message, i.e. of a message sent to a peer we are connected to.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DirectMessageKind {
+    /// Signature share of a routing message.
+    MessageSignature,
+    /// Request of a bootstrapping node.
+    BootstrapRequest,
+    /// Response to a `BootstrapRequest`.
+    BootstrapResponse,
+    /// Request of a node to join our section.
+    JoinRequest,
+    /// Response to a `ConnectionRequest`.
+    ConnectionResponse,
+    /// Request to send a parsec gossip request.
+    ParsecPoke,
+    /// Parsec gossip request.
+    ParsecRequest,
+    /// Parsec gossip response.
+    ParsecResponse,
+    /// Request of the chain of our section's keys.
+    ProofChainRequest,
+    /// Response to a `ProofChainRequest`.
+    ProofChainResponse,
+    /// Resource proof a joining node must solve.
+    ResourceChallenge,
+    /// Solution of a `ResourceChallenge`.
+    ResourceProofResponse,
+    /// Announcement of a member leaving the network.
+    Leave,
+    /// Request of an elder to be demoted to an adult.
+    StepDown,
+    /// Announcement of a member which received a `NodeApproval` after having joined already.
+    JoinedAlready,
+    /// Nonce a joining node must sign.
+    JoinChallenge,
+    /// Signature of a `JoinChallenge` nonce.
+    JoinChallengeResponse,
+    /// Request of a client to relay its message into the network.
+    ClientRequest,
+    /// Identification of a client to its proxy.
+    ClientIdentify,
+}
+
+/// A routing message which reached us and accumulated, as seen by a `MessageHandler`.
+pub struct RoutingMessageView<'a> {
+    content: &'a MessageContent,
+    src: &'a Authority<XorName>,
+    dst: &'a Authority<XorName>,
+}
+
+impl<'a> RoutingMessageView<'a> {
+    pub(crate) fn new(
+        content: &'a MessageContent,
+        src: &'a Authority<XorName>,
+        dst: &'a Authority<XorName>,
+    ) -> Self {
+        Self { content, src, dst }
+    }
+
+    /// Returns the kind of the message.
+    pub fn kind(&self) -> RoutingMessageKind {
+        self.content.kind()
+    }
+
+    /// Returns the source authority of the message.
+    pub fn src(&self) -> &Authority<XorName> {
+        self.src
+    }
+
+    /// Returns the destination authority of the message.
+    pub fn dst(&self) -> &Authority<XorName> {
+        self.dst
+    }
+
+    /// Returns the content of the user carried by the message, for the kinds which carry one:
+    /// `UserMessage`, `UserMessagePart`, `StreamPart`, `UserRequest`, `UserResponse`,
+    /// `MessageRejected` and `RelayedClientMessage`.
+    pub fn user_content(&self) -> Option<&[u8]> {
+        use crate::messages::MessageContent::*;
+        match self.content {
+            UserMessage(content)
+            | UserMessagePart {
+                payload: content, ..
+            }
+            | StreamPart {
+                payload: content, ..
+            }
+            | UserRequest { content, .. }
+            | UserResponse { content, .. }
+            | MessageRejected {
+                reason: content, ..
+            }
+            | RelayedClientMessage { content, .. } => Some(&content[..]),
+            _ => None,
+        }
+    }
+
+    /// Returns the whole content of the message, serialised, e.g. to archive it or to hand it to
+    /// another process.
+    pub fn serialised_content(&self) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialise(self.content)?)
+    }
+}
+
+impl<'a> Debug for RoutingMessageView<'a> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{:?} from {:?} to {:?}",
+            self.content, self.src, self.dst
+        )
+    }
+}
+
+/// A direct message a peer sent us, as seen by a `MessageHandler`.
+pub struct DirectMessageView<'a> {
+    content: &'a DirectMessage,
+    sender: &'a PublicId,
+}
+
+impl<'a> DirectMessageView<'a> {
+    pub(crate) fn new(content: &'a DirectMessage, sender: &'a PublicId) -> Self {
+        Self { content, sender }
+    }
+
+    /// Returns the kind of the message.
+    pub fn kind(&self) -> DirectMessageKind {
+        self.content.kind()
+    }
+
+    /// Returns the peer which sent the message.
+    pub fn sender(&self) -> &PublicId {
+        self.sender
+    }
+
+    /// Returns the destination and the content of a `ClientRequest`, i.e. the message the client
+    /// asks us to relay.
+    pub fn client_request(&self) -> Option<(&Authority<XorName>, &[u8])> {
+        match self.content {
+            DirectMessage::ClientRequest { dst, content, .. } => Some((dst, &content[..])),
+            _ => None,
+        }
+    }
+
+    /// Returns the whole content of the message, serialised.
+    pub fn serialised_content(&self) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialise(self.content)?)
+    }
+}
+
+impl<'a> Debug for DirectMessageView<'a> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{:?} from {}", self.content, self.sender)
+    }
+}
+
+/// Whether a message should be handled any further after a `MessageHandler` saw it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Dispatch {
+    /// Pass the message on to the next handler, and eventually to the built-in one.
+    Continue,
+    /// Drop the message: neither the following handlers nor the built-in one see it.
+    Consume,
+}
+
+/// Handler attached to some kinds of messages an elder receives, through a `DispatchTable`. It
+/// sees the whole message, and either handles it itself, consuming it, or passes it on to the
+/// next handler and eventually to the built-in handling, e.g. after collecting statistics. Both
+/// methods default to passing the message on.
+pub trait MessageHandler: Send + Sync {
+    /// Called with each routing message of a kind this handler is attached to, once it reached
+    /// us and accumulated. Encrypted messages are decrypted first.
+    fn handle_routing_message(&self, _msg: &RoutingMessageView) -> Dispatch {
+        Dispatch::Continue
+    }
+
+    /// Called with each direct message of a kind this handler is attached to.
+    fn handle_direct_message(&self, _msg: &DirectMessageView) -> Dispatch {
+        Dispatch::Continue
+    }
+}
+
+/// What to do with a user message after a `UserMessageFilter` saw it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Filtered {
+    /// Pass the message on with the given content, which may differ from the one received.
+    Accept(Vec<u8>),
+    /// Drop the message, and send the given reason back to its source, which raises it as
+    /// `Event::MessageRejected`.
+    Reject(Vec<u8>),
+}
+
+/// Filter of the user messages an elder receives, through a `DispatchTable`, e.g. to enforce
+/// policies on their payload. It is called with each message before `Event::MessageReceived` is
+/// raised for it, once all its parts arrived, and likewise with each request and each part of a
+/// stream.
+pub trait UserMessageFilter: Send + Sync {
+    /// Returns whether to accept the message from `src` to `dst` with the given content.
+    fn filter(
+        &self,
+        src: &Authority<XorName>,
+        dst: &Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Filtered;
+}
+
+/// Decision of a `ClientAuthoriser` on a client connecting to us.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClientAuthorisation {
+    /// Serve the client, within the budgets of `NodeBuilder::inbound_limits`.
+    Accept,
+    /// Serve the client, within the given budgets instead of the default ones.
+    Limit(InboundLimits),
+    /// Disconnect from the client.
+    Reject,
+}
+
+/// Authoriser of the clients an elder serves, through a `DispatchTable`, e.g. to implement
+/// allow-lists or per-client quotas. It is consulted by address when a client connects, then by
+/// public id when the client identifies itself after bootstrapping and with each of its messages.
+/// The messages of a client which hasn't identified itself yet are dropped while an authoriser is
+/// set.
+pub trait ClientAuthoriser: Send + Sync {
+    /// Returns whether to serve the client connecting from `peer_addr`, and within which budgets.
+    fn authorise_connection(&self, peer_addr: &SocketAddr) -> ClientAuthorisation;
+
+    /// Returns whether to serve the client with the given public id, connected from `peer_addr`.
+    /// A client which isn't authorised is disconnected from.
+    fn authorise_client(&self, _peer_addr: &SocketAddr, _client_id: &PublicId) -> bool {
+        true
+    }
+}
+
+/// The handlers attached to each kind of message, called in the order they were attached. The
+/// messages of the kinds no handler is attached to go straight to the built-in handling. It also
+/// holds the filters of the user messages, also called in the order they were added, and the
+/// authoriser of the clients connecting to us.
+#[derive(Clone, Default)]
+pub struct DispatchTable {
+    routing: BTreeMap<RoutingMessageKind, Vec<Arc<dyn MessageHandler>>>,
+    direct: BTreeMap<DirectMessageKind, Vec<Arc<dyn MessageHandler>>>,
+    user_message_filters: Vec<Arc<dyn UserMessageFilter>>,
+    client_authoriser: Option<Arc<dyn ClientAuthoriser>>,
+}
+
+impl DispatchTable {
+    /// Attaches `handler` to the routing messages of the given kind.
+    pub fn on_routing_message(
+        &mut self,
+        kind: RoutingMessageKind,
+        handler: Arc<dyn MessageHandler>,
+    ) {
+        self.routing
+            .entry(kind)
+            .or_insert_with(Vec::new)
+            .push(handler)
+    }
+
+    /// Attaches `handler` to the direct messages of the given kind.
+    pub fn on_direct_message(&mut self, kind: DirectMessageKind, handler: Arc<dyn MessageHandler>) {
+        self.direct
+            .entry(kind)
+            .or_insert_with(Vec::new)
+            .push(handler)
+    }
+
+    /// Adds `filter` to the filters of the user messages. Each filter sees the content accepted
+    /// by the previous one, and a rejection skips the following ones.
+    pub fn add_user_message_filter(&mut self, filter: Arc<dyn UserMessageFilter>) {
+        self.user_message_filters.push(filter)
+    }
+
+    /// Sets the authoriser of the clients connecting to us, replacing the previous one. All
+    /// clients are served, within the default budgets, if none is set.
+    pub fn set_client_authoriser(&mut self, authoriser: Arc<dyn ClientAuthoriser>) {
+        self.client_authoriser = Some(authoriser)
+    }
+
+    pub(crate) fn authorise_client_connection(
+        &self,
+        peer_addr: &SocketAddr,
+    ) -> ClientAuthorisation {
+        self.client_authoriser
+            .as_ref()
+            .map_or(ClientAuthorisation::Accept, |authoriser| {
+                authoriser.authorise_connection(peer_addr)
+            })
+    }
+
+    pub(crate) fn has_client_authoriser(&self) -> bool {
+        self.client_authoriser.is_some()
+    }
+
+    pub(crate) fn authorise_client(&self, peer_addr: &SocketAddr, client_id: &PublicId) -> bool {
+        self.client_authoriser.as_ref().map_or(true, |authoriser| {
+            authoriser.authorise_client(peer_addr, client_id)
+        })
+    }
+
+    pub(crate) fn filter_user_message(
+        &self,
+        src: &Authority<XorName>,
+        dst: &Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Filtered {
+        let mut content = content;
+        for filter in &self.user_message_filters {
+            match filter.filter(src, dst, content) {
+                Filtered::Accept(accepted) => content = accepted,
+                rejected @ Filtered::Reject(_) => return rejected,
+            }
+        }
+        Filtered::Accept(content)
+    }
+
+    pub(crate) fn dispatch_routing_message(&self, msg: &RoutingMessageView) -> Dispatch {
+        dispatch(self.routing.get(&msg.kind()), |handler| {
+            handler.handle_routing_message(msg)
+        })
+    }
+
+    pub(crate) fn dispatch_direct_message(&self, msg: &DirectMessageView) -> Dispatch {
+        dispatch(self.direct.get(&msg.kind()), |handler| {
+            handler.handle_direct_message(msg)
+        })
+    }
+}
+
+impl Debug for DispatchTable {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("DispatchTable")
+            .field("routing", &self.routing.keys().collect::<Vec<_>>())
+            .field("direct", &self.direct.keys().collect::<Vec<_>>())
+            .field("user_message_filters", &self.user_message_filters.len())
+            .field("client_authoriser", &self.client_authoriser.is_some())
+            .finish()
+    }
+}
+
+fn dispatch<F>(handlers: Option<&Vec<Arc<dyn MessageHandler>>>, mut f: F) -> Dispatch
+where
+    F: FnMut(&dyn MessageHandler) -> Dispatch,
+{
+    let consumed = handlers
+        .into_iter()
+        .flatten()
+        .any(|handler| f(handler.as_ref()) == Dispatch::Consume);
+
+    if consumed {
+        Dispatch::Consume
+    } else {
+        Dispatch::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, types::MessageId};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counter {
+        calls: AtomicUsize,
+        result: Dispatch,
+    }
+
+    impl Counter {
+        fn new(result: Dispatch) -> Arc<Self> {
+            Arc::new(Self {
+                calls: AtomicUsize::new(0),
+                result,
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl MessageHandler for Counter {
+        fn handle_direct_message(&self, _: &DirectMessageView) -> Dispatch {
+            let _ = self.calls.fetch_add(1, Ordering::SeqCst);
+            self.result
+        }
+    }
+
+    #[test]
+    fn consuming_handler_stops_dispatch() {
+        let sender = *FullId::new().public_id();
+        let first = Counter::new(Dispatch::Continue);
+        let second = Counter::new(Dispatch::Consume);
+        let third = Counter::new(Dispatch::Continue);
+
+        let mut table = DispatchTable::default();
+        table.on_direct_message(DirectMessageKind::Leave, first.clone());
+        table.on_direct_message(DirectMessageKind::Leave, second.clone());
+        table.on_direct_message(DirectMessageKind::Leave, third.clone());
+
+        let leave = DirectMessage::Leave;
+        assert_eq!(
+            table.dispatch_direct_message(&DirectMessageView::new(&leave, &sender)),
+            Dispatch::Consume
+        );
+        let step_down = DirectMessage::StepDown;
+        assert_eq!(
+            table.dispatch_direct_message(&DirectMessageView::new(&step_down, &sender)),
+            Dispatch::Continue
+        );
+        assert_eq!(first.calls(), 1);
+        assert_eq!(second.calls(), 1);
+        assert_eq!(third.calls(), 0);
+    }
+
+    struct Doubler;
+
+    impl UserMessageFilter for Doubler {
+        fn filter(
+            &self,
+            _: &Authority<XorName>,
+            _: &Authority<XorName>,
+            content: Vec<u8>,
+        ) -> Filtered {
+            Filtered::Accept(content.iter().chain(&content).cloned().collect())
+        }
+    }
+
+    struct MaxLen(usize);
+
+    impl UserMessageFilter for MaxLen {
+        fn filter(
+            &self,
+            _: &Authority<XorName>,
+            _: &Authority<XorName>,
+            content: Vec<u8>,
+        ) -> Filtered {
+            if content.len() > self.0 {
+                Filtered::Reject(b"too long".to_vec())
+            } else {
+                Filtered::Accept(content)
+            }
+        }
+    }
+
+    // Consumes the user messages whose content starts with the given prefix.
+    struct DropPrefixed(&'static [u8]);
+
+    impl MessageHandler for DropPrefixed {
+        fn handle_routing_message(&self, msg: &RoutingMessageView) -> Dispatch {
+            match msg.user_content() {
+                Some(content) if content.starts_with(self.0) => Dispatch::Consume,
+                _ => Dispatch::Continue,
+            }
+        }
+    }
+
+    #[test]
+    fn routing_message_handlers_see_the_content() {
+        let src = Authority::Node(XorName::default());
+        let dst = Authority::Section(XorName::default());
+
+        let mut table = DispatchTable::default();
+        table.on_routing_message(
+            RoutingMessageKind::UserMessage,
+            Arc::new(DropPrefixed(b"spam")),
+        );
+
+        let spam = MessageContent::UserMessage {
+            content: b"spam and eggs".to_vec(),
+            msg_id: MessageId::new(),
+        };
+        assert_eq!(
+            table.dispatch_routing_message(&RoutingMessageView::new(&spam, &src, &dst)),
+            Dispatch::Consume
+        );
+        let eggs = MessageContent::UserMessage {
+            content: b"eggs".to_vec(),
+            msg_id: MessageId::new(),
+        };
+        assert_eq!(
+            table.dispatch_routing_message(&RoutingMessageView::new(&eggs, &src, &dst)),
+            Dispatch::Continue
+        );
+    }
+
+    #[test]
+    fn user_message_filters_see_previous_output() {
+        let src = Authority::Node(XorName::default());
+        let dst = Authority::Section(XorName::default());
+
+        let mut table = DispatchTable::default();
+        assert_eq!(
+            table.filter_user_message(&src, &dst, vec![1, 2]),
+            Filtered::Accept(vec![1, 2])
+        );
+
+        table.add_user_message_filter(Arc::new(Doubler));
+        table.add_user_message_filter(Arc::new(MaxLen(4)));
+        assert_eq!(
+            table.filter_user_message(&src, &dst, vec![1, 2]),
+            Filtered::Accept(vec![1, 2, 1, 2])
+        );
+        assert_eq!(
+            table.filter_user_message(&src, &dst, vec![1, 2, 3]),
+            Filtered::Reject(b"too long".to_vec())
+        );
+    }
+}
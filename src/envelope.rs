@@ -0,0 +1,146 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Versioned envelope of the messages we send over the network: a tag, the version of the format
+//! and the codec the rest of the message is serialised with. Messages without the tag are those of
+//! older versions of the crate, serialised with bincode.
+
+use crate::{error::RoutingError, local_params::Codec};
+use maidsafe_utilities::serialisation;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The current version of the format. Increased whenever the layout of the messages changes, e.g.
+/// when a variant is added. Version 2 added the trace ids, batches of hop messages, requests and
+/// responses, rejected and close group messages, and changed what signatures cover.
+pub const FORMAT_VERSION: u8 = 2;
+
+// Tag starting every message in an envelope. A bare bincode `Message` never starts with it, as its
+// first four bytes are the index of its variant, nor does a compressed one.
+const TAG: &[u8] = b"RT";
+const HEADER_LEN: usize = 4;
+
+impl Codec {
+    fn to_byte(self) -> u8 {
+        match self {
+            Codec::Bincode => 0,
+            Codec::Json => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Codec::Bincode),
+            1 => Some(Codec::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Serialises `value` with `codec` into an envelope of the current version.
+pub fn seal<T: Serialize>(value: &T, codec: Codec) -> Result<Vec<u8>, RoutingError> {
+    let payload = match codec {
+        Codec::Bincode => serialisation::serialise(value)?,
+        Codec::Json => serde_json::to_vec(value)?,
+    };
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(TAG);
+    bytes.push(FORMAT_VERSION);
+    bytes.push(codec.to_byte());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Returns the bincode serialised message in `bytes`, without the header of its envelope if it has
+/// one, or `None` if it was serialised with another codec.
+#[cfg(feature = "mock_base")]
+pub fn bincode_payload(bytes: &[u8]) -> Option<&[u8]> {
+    if !bytes.starts_with(TAG) {
+        return Some(bytes);
+    }
+
+    match bytes.get(TAG.len() + 1).cloned().and_then(Codec::from_byte) {
+        Some(Codec::Bincode) => Some(&bytes[HEADER_LEN..]),
+        Some(Codec::Json) | None => None,
+    }
+}
+
+/// Deserialises a value from an envelope, or from bare bincode if `bytes` don't start with the
+/// tag. A message of a newer version is read if it doesn't use anything this version doesn't know,
+/// and is otherwise rejected with `RoutingError::UnsupportedMessageVersion`, to be skipped.
+pub fn open<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, RoutingError> {
+    if !bytes.starts_with(TAG) {
+        return Ok(serialisation::deserialise(bytes)?);
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(RoutingError::InvalidMessage);
+    }
+
+    let version = bytes[TAG.len()];
+    let codec = Codec::from_byte(bytes[TAG.len() + 1]);
+    let payload = &bytes[HEADER_LEN..];
+
+    let result = match codec {
+        Some(Codec::Bincode) => serialisation::deserialise(payload).map_err(RoutingError::from),
+        Some(Codec::Json) => serde_json::from_slice(payload).map_err(RoutingError::from),
+        None => Err(RoutingError::InvalidMessage),
+    };
+
+    match result {
+        Err(_) if version > FORMAT_VERSION => Err(RoutingError::UnsupportedMessageVersion(version)),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    enum Old {
+        A(u32),
+    }
+
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    enum New {
+        A(u32),
+        B(String),
+    }
+
+    #[test]
+    fn round_trip() {
+        for codec in &[Codec::Bincode, Codec::Json] {
+            let bytes = unwrap!(seal(&New::B("hello".to_string()), *codec));
+            assert_eq!(unwrap!(open::<New>(&bytes)), New::B("hello".to_string()));
+        }
+    }
+
+    #[test]
+    fn read_bare_bincode() {
+        let bytes = unwrap!(serialisation::serialise(&Old::A(7)));
+        assert_eq!(unwrap!(open::<Old>(&bytes)), Old::A(7));
+    }
+
+    #[test]
+    fn skip_unknown_variant_of_newer_version() {
+        let mut bytes = unwrap!(seal(&New::A(7), Codec::Bincode));
+        bytes[TAG.len()] = FORMAT_VERSION + 1;
+        // Variants known to both versions are still read.
+        assert_eq!(unwrap!(open::<Old>(&bytes)), Old::A(7));
+
+        let mut bytes = unwrap!(seal(&New::B("hello".to_string()), Codec::Bincode));
+        bytes[TAG.len()] = FORMAT_VERSION + 1;
+        match open::<Old>(&bytes) {
+            Err(RoutingError::UnsupportedMessageVersion(version)) => {
+                assert_eq!(version, FORMAT_VERSION + 1)
+            }
+            result => panic!("Unexpected {:?}", result),
+        }
+    }
+}
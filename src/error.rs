@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::routing_table::Error as RoutingTableError;
-use crate::{action::Action, event::Event, id::PublicId, quic_p2p};
+use crate::{action::Action, event::Event, id::PublicId, messages::ProtocolVersion, quic_p2p};
 use crossbeam_channel as mpmc;
 use maidsafe_utilities::serialisation;
 use quick_error::quick_error;
@@ -25,6 +25,8 @@ pub enum InterfaceError {
     NotConnected,
     /// We are not in a state to handle the action.
     InvalidState,
+    /// We are not allowed to send messages from the given source authority.
+    InvalidSource,
     /// Error while trying to receive a message from a multiple-producer-single-consumer channel
     MpscRecvError(mpsc::RecvError),
     /// Error while trying to receive a message from a multiple-producer-multiple-consumer channel
@@ -33,6 +35,8 @@ pub enum InterfaceError {
     MpmcSendEventError(mpmc::SendError<Event>),
     /// Error while trying to send an action to a multiple-producer-multiple-consumer channel
     MpmcSendActionError(mpmc::SendError<Action>),
+    /// The message couldn't be sent, e.g. as there is no route to its destination.
+    SendFailed(Box<RoutingError>),
 }
 
 impl From<mpsc::RecvError> for InterfaceError {
@@ -84,6 +88,8 @@ pub enum RoutingError {
     InvalidStateForOperation,
     /// Serialisation Error
     SerialisationError(serialisation::SerialisationError),
+    /// Self-describing (JSON) serialisation error
+    JsonSerialisationError(serde_json::Error),
     /// Unknown Connection
     UnknownConnection(PublicId),
     /// Invalid Destination
@@ -98,6 +104,19 @@ pub enum RoutingError {
     UntrustedMessage,
     /// A new SectionInfo is invalid.
     InvalidNewSectionInfo,
+    /// Genesis info serialised by a newer version of the crate, with the given format version.
+    UnsupportedGenesisVersion(u32),
+    /// A peer speaks the given version of the protocol, incompatible with ours.
+    IncompatibleProtocol(ProtocolVersion),
+    /// A message reached the maximum number of hops before its destination.
+    HopLimitExceeded,
+    /// A message was signed with a long obsolete key of its source section.
+    ObsoleteMessage,
+    /// A message serialised by a newer version of the crate, with the given format version, using
+    /// something this version doesn't know.
+    UnsupportedMessageVersion(u8),
+    /// The configuration of a node being created is invalid, for the given reason.
+    InvalidConfig(&'static str),
 }
 
 impl From<RoutingTableError> for RoutingError {
@@ -130,6 +149,12 @@ impl From<serialisation::SerialisationError> for RoutingError {
     }
 }
 
+impl From<serde_json::Error> for RoutingError {
+    fn from(error: serde_json::Error) -> RoutingError {
+        RoutingError::JsonSerialisationError(error)
+    }
+}
+
 quick_error! {
     #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
     pub enum BootstrapResponseError {
@@ -141,5 +166,10 @@ quick_error! {
             description("Bootstrap node has too few peers")
             display("The chosen bootstrap node has too few connections to peers.")
         }
+        IncompatibleVersion(version: ProtocolVersion) {
+            description("Bootstrap node speaks an incompatible protocol version")
+            display("The chosen bootstrap node speaks the incompatible protocol version {}.{}.",
+                    version.major, version.minor)
+        }
     }
 }
@@ -6,12 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::chain::SectionProofChain;
+use crate::messages::ProtocolVersion;
 use crate::routing_table::Authority;
 use crate::routing_table::Prefix;
+use crate::types::{MessageId, RelayedClientInfo};
 use crate::xor_name::XorName;
 use crate::NetworkBytes;
 use hex_fmt::HexFmt;
 use quic_p2p::Token;
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug, Formatter};
 use std::net::SocketAddr;
 
@@ -78,27 +82,193 @@ pub enum Event {
         src: Authority<XorName>,
         /// The destination authority that receives the message.
         dst: Authority<XorName>,
+        /// If the message was sent by a client and relayed into the network by its proxy elder,
+        /// the client and the relay path. `None` for messages originated by nodes.
+        client: Option<RelayedClientInfo>,
+        /// The id the message was traced with, if its sender sent it with
+        /// `Node::send_traced_message`.
+        trace_id: Option<MessageId>,
+        /// The id the message was sent with, identical for all its recipients. It is the id given
+        /// to `Node::send_message_with_ack`, if the message was sent with it, and otherwise a new
+        /// one for each message from a single node, or one derived from the content for messages
+        /// from a section or group.
+        msg_id: MessageId,
+        /// Number of hops the message took to reach us, or the last of its parts if it was split.
+        hop_count: u8,
+        /// Whether the message was signed by its source section and its key proven to us, as in
+        /// Secure Message Delivery, rather than signed by a single node.
+        secure: bool,
+    },
+    /// Gave up receiving a message which was split into parts, as some of them didn't arrive in
+    /// time. The parts received are dropped.
+    MessageIncomplete {
+        /// The source authority that sent the message.
+        src: Authority<XorName>,
+        /// The destination authority that was to receive the message.
+        dst: Authority<XorName>,
+        /// Number of parts received.
+        received_parts: u32,
+        /// Number of parts of the message.
+        part_count: u32,
+    },
+    /// The destination of the message with the given id, sent with `Node::send_message_with_ack`,
+    /// acknowledged receiving it.
+    MessageDelivered(MessageId),
+    /// The destination of the message with the given id, sent with `Node::send_message_with_ack`,
+    /// didn't acknowledge receiving it in time. The message may still have been delivered.
+    MessageDeliveryFailed(MessageId),
+    /// Received the next part of a stream sent with `Node::send_stream`. The parts of a stream are
+    /// raised in order.
+    StreamPart {
+        /// The source authority that sent the stream.
+        src: Authority<XorName>,
+        /// The destination authority that receives the stream.
+        dst: Authority<XorName>,
+        /// The id of the stream.
+        stream_id: MessageId,
+        /// The index of this part, starting at zero.
+        index: u32,
+        /// The content of this part.
+        content: Vec<u8>,
+    },
+    /// Received the last part of a stream sent with `Node::send_stream`.
+    StreamComplete {
+        /// The source authority that sent the stream.
+        src: Authority<XorName>,
+        /// The destination authority that received the stream.
+        dst: Authority<XorName>,
+        /// The id of the stream.
+        stream_id: MessageId,
+    },
+    /// Gave up sending a stream sent with `Node::send_stream`, as its destination didn't grant us
+    /// credit for its remaining parts in time. The parts sent may have been delivered.
+    StreamFailed {
+        /// The source authority that sent the stream.
+        src: Authority<XorName>,
+        /// The destination authority that was to receive the stream.
+        dst: Authority<XorName>,
+        /// The id of the stream.
+        stream_id: MessageId,
+    },
+    /// A user message we sent was rejected by the filters of its destination, with the given
+    /// reason.
+    MessageRejected {
+        /// The source authority that sent the message.
+        src: Authority<XorName>,
+        /// The destination authority that rejected the message.
+        dst: Authority<XorName>,
+        /// The id of the message as reported by `Event::MessageReceived`, or the token of the
+        /// request, or the id of the stream the rejected part belongs to.
+        msg_id: MessageId,
+        /// The reason given by the filters.
+        reason: Vec<u8>,
+    },
+    /// Received a request sent with `Node::send_request`. Answer it with `Node::send_response`,
+    /// passing back its `token`.
+    RequestReceived {
+        /// The content of the request.
+        content: Vec<u8>,
+        /// The source authority that sent the request, to send the response to.
+        src: Authority<XorName>,
+        /// The destination authority that receives the request, to send the response from.
+        dst: Authority<XorName>,
+        /// The token correlating the response with the request.
+        token: MessageId,
+    },
+    /// Received the response to a request sent with `Node::send_request` from its destination.
+    NodeResponseReceived {
+        /// The token returned by `Node::send_request` for the request this responds to.
+        token: MessageId,
+        /// The content of the response.
+        content: Vec<u8>,
+        /// The source authority that sent the response, i.e. the destination of the request.
+        src: Authority<XorName>,
+    },
+    /// The request with the given token, sent with `Node::send_request`, wasn't responded to in
+    /// time. A late response is dropped.
+    RequestTimedOut(MessageId),
+    /// A message we sent couldn't be delivered, as its destination is a node which isn't a member
+    /// of the section responsible for it, or as it was encrypted to a key some elders of its
+    /// destination section can't decrypt with. Raised once per message, for user messages only.
+    MessageUndeliverable {
+        /// The source authority that sent the message.
+        src: Authority<XorName>,
+        /// The destination authority that was to receive the message.
+        dst: Authority<XorName>,
     },
     /// A node has connected to us.
     NodeAdded(XorName),
     /// A node has disconnected from us.
     NodeLost(XorName),
+    /// The elders of our section changed without our section splitting or merging. Raised once
+    /// the change is agreed by the section.
+    EldersChanged {
+        /// The names of the new elders.
+        added: BTreeSet<XorName>,
+        /// The names of the elders which are no longer elders.
+        removed: BTreeSet<XorName>,
+    },
     /// Our own section has been split, resulting in the included `Prefix` for our new section.
     SectionSplit(Prefix<XorName>),
     /// Our own section requires merged with others, resulting in the included `Prefix` for our new
     /// section.
     SectionMerged(Prefix<XorName>),
+    /// Our section started relocating one of its members to another section. The member no
+    /// longer counts as one of ours.
+    RelocationStarted {
+        /// The name of the member.
+        name: XorName,
+        /// The member joins the section whose prefix matches this name.
+        destination: XorName,
+    },
+    /// A member relocated by our section had its chance to receive the relocation and was
+    /// disconnected from, so the section moves on to the next relocation, if any.
+    RelocationCompleted(XorName),
     /// The client has successfully connected to a proxy node on the network.
     Connected,
+    /// The client received a message from its proxy node, typically the response to a request
+    /// sent with `Client::send_request`.
+    ResponseReceived {
+        /// The message.
+        msg: NetworkBytes,
+    },
     /// Disconnected or failed to connect - restart required.
     RestartRequired,
     /// Startup failed - terminate.
     Terminated,
+    /// Our bootstrap node rejected us as it speaks an incompatible version of the protocol -
+    /// upgrade required. Followed by `Terminated`.
+    IncompatibleProtocol {
+        /// The version we speak.
+        ours: ProtocolVersion,
+        /// The version the bootstrap node speaks.
+        theirs: ProtocolVersion,
+    },
+    /// An attempt to join a section timed out without our being approved. Once `max_attempts`
+    /// attempts failed, we rebootstrap.
+    JoinAttemptFailed {
+        /// Number of the attempt which failed, starting at one.
+        attempt: u8,
+        /// Number of attempts before rebootstrapping.
+        max_attempts: u8,
+    },
     // TODO: Find a better solution for periodic tasks.
     /// This event is sent periodically every time Routing sends the `Heartbeat` messages.
     TimerTicked,
     /// Consensus on a custom event.
     Consensus(Vec<u8>),
+    /// Consensus on a custom event voted for with `Node::vote_for_user_event`.
+    UserEventConsensused {
+        /// The namespace the event was voted for in.
+        namespace: String,
+        /// The content of the event.
+        payload: Vec<u8>,
+    },
+    /// Our age has increased to the included value.
+    AgeIncreased(u8),
+    /// Received the chain of our section's keys requested with `Node::request_proof_chain`. The
+    /// chain is valid and starts at a key we trust.
+    ProofChainReceived(SectionProofChain),
 }
 
 impl From<ClientEvent> for Event {
@@ -117,30 +287,184 @@ impl Debug for Event {
                 ref content,
                 ref src,
                 ref dst,
+                ref client,
+                ref trace_id,
+                ref msg_id,
+                hop_count,
+                secure,
+            } => write!(
+                formatter,
+                "Event::MessageReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?}, client: {:?}, \
+                 trace_id: {:?}, msg_id: {:?}, hop_count: {}, secure: {} }}",
+                HexFmt(content),
+                src,
+                dst,
+                client,
+                trace_id,
+                msg_id,
+                hop_count,
+                secure
+            ),
+            Event::MessageIncomplete {
+                ref src,
+                ref dst,
+                received_parts,
+                part_count,
+            } => write!(
+                formatter,
+                "Event::MessageIncomplete {{ src: {:?}, dst: {:?}, parts: {}/{} }}",
+                src, dst, received_parts, part_count
+            ),
+            Event::MessageDelivered(ref msg_id) => {
+                write!(formatter, "Event::MessageDelivered({:?})", msg_id)
+            }
+            Event::MessageDeliveryFailed(ref msg_id) => {
+                write!(formatter, "Event::MessageDeliveryFailed({:?})", msg_id)
+            }
+            Event::StreamPart {
+                ref src,
+                ref dst,
+                ref stream_id,
+                index,
+                ref content,
+            } => write!(
+                formatter,
+                "Event::StreamPart {{ src: {:?}, dst: {:?}, stream_id: {:?}, index: {}, content: \"{:<8}\" }}",
+                src,
+                dst,
+                stream_id,
+                index,
+                HexFmt(content)
+            ),
+            Event::StreamComplete {
+                ref src,
+                ref dst,
+                ref stream_id,
             } => write!(
                 formatter,
-                "Event::MessageReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?} }}",
+                "Event::StreamComplete {{ src: {:?}, dst: {:?}, stream_id: {:?} }}",
+                src, dst, stream_id
+            ),
+            Event::StreamFailed {
+                ref src,
+                ref dst,
+                ref stream_id,
+            } => write!(
+                formatter,
+                "Event::StreamFailed {{ src: {:?}, dst: {:?}, stream_id: {:?} }}",
+                src, dst, stream_id
+            ),
+            Event::MessageRejected {
+                ref src,
+                ref dst,
+                ref msg_id,
+                ref reason,
+            } => write!(
+                formatter,
+                "Event::MessageRejected {{ src: {:?}, dst: {:?}, msg_id: {:?}, \
+                 reason: \"{:<8}\" }}",
+                src,
+                dst,
+                msg_id,
+                HexFmt(reason)
+            ),
+            Event::RequestReceived {
+                ref content,
+                ref src,
+                ref dst,
+                ref token,
+            } => write!(
+                formatter,
+                "Event::RequestReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?}, token: {:?} }}",
                 HexFmt(content),
                 src,
-                dst
+                dst,
+                token
+            ),
+            Event::NodeResponseReceived {
+                ref token,
+                ref content,
+                ref src,
+            } => write!(
+                formatter,
+                "Event::NodeResponseReceived {{ token: {:?}, content: \"{:<8}\", src: {:?} }}",
+                token,
+                HexFmt(content),
+                src
+            ),
+            Event::RequestTimedOut(ref token) => {
+                write!(formatter, "Event::RequestTimedOut({:?})", token)
+            }
+            Event::MessageUndeliverable { ref src, ref dst } => write!(
+                formatter,
+                "Event::MessageUndeliverable {{ src: {:?}, dst: {:?} }}",
+                src, dst
             ),
             Event::NodeAdded(ref node_name) => {
                 write!(formatter, "Event::NodeAdded({:?})", node_name)
             }
             Event::NodeLost(ref node_name) => write!(formatter, "Event::NodeLost({:?})", node_name),
+            Event::EldersChanged {
+                ref added,
+                ref removed,
+            } => write!(
+                formatter,
+                "Event::EldersChanged {{ added: {:?}, removed: {:?} }}",
+                added, removed
+            ),
             Event::SectionSplit(ref prefix) => {
                 write!(formatter, "Event::SectionSplit({:?})", prefix)
             }
             Event::SectionMerged(ref prefix) => {
                 write!(formatter, "Event::SectionMerged({:?})", prefix)
             }
+            Event::RelocationStarted {
+                ref name,
+                ref destination,
+            } => write!(
+                formatter,
+                "Event::RelocationStarted {{ name: {:?}, destination: {:?} }}",
+                name, destination
+            ),
+            Event::RelocationCompleted(ref name) => {
+                write!(formatter, "Event::RelocationCompleted({:?})", name)
+            }
             Event::Connected => write!(formatter, "Event::Connected"),
+            Event::ResponseReceived { .. } => write!(formatter, "Event::ResponseReceived"),
             Event::RestartRequired => write!(formatter, "Event::RestartRequired"),
             Event::Terminated => write!(formatter, "Event::Terminated"),
+            Event::IncompatibleProtocol { ours, theirs } => write!(
+                formatter,
+                "Event::IncompatibleProtocol {{ ours: {:?}, theirs: {:?} }}",
+                ours, theirs
+            ),
+            Event::JoinAttemptFailed {
+                attempt,
+                max_attempts,
+            } => write!(
+                formatter,
+                "Event::JoinAttemptFailed({}/{})",
+                attempt, max_attempts
+            ),
             Event::TimerTicked => write!(formatter, "Event::TimerTicked"),
             Event::Consensus(ref payload) => {
                 write!(formatter, "Event::Consensus({:<8})", HexFmt(payload))
             }
+            Event::UserEventConsensused {
+                ref namespace,
+                ref payload,
+            } => write!(
+                formatter,
+                "Event::UserEventConsensused {{ namespace: {:?}, payload: {:<8} }}",
+                namespace,
+                HexFmt(payload)
+            ),
+            Event::AgeIncreased(age) => write!(formatter, "Event::AgeIncreased({})", age),
+            Event::ProofChainReceived(ref chain) => write!(
+                formatter,
+                "Event::ProofChainReceived({:?})",
+                chain.last_public_key_info()
+            ),
         }
     }
 }
@@ -7,6 +7,12 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crossbeam_channel::{RecvError, TryRecvError};
+#[cfg(feature = "async")]
+use futures::task::Waker;
+#[cfg(feature = "async")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use unwrap::unwrap;
 
 /// Trait to fake a channel.
 pub trait EventStream {
@@ -82,3 +88,34 @@ where
         result
     }
 }
+
+/// Wakes the task polling a `Node` as a `Stream` when something is sent to its state machine. The
+/// clones share the task to wake. Without the `async` feature there is no task, and waking does
+/// nothing.
+#[derive(Clone, Default)]
+pub struct EventWaker {
+    #[cfg(feature = "async")]
+    task: Arc<Mutex<Option<Waker>>>,
+}
+
+impl EventWaker {
+    /// Sets the task the next `wake` wakes.
+    #[cfg(feature = "async")]
+    pub fn register(&self, waker: &Waker) {
+        let mut task = unwrap!(self.task.lock());
+        match *task {
+            Some(ref registered) if registered.will_wake(waker) => (),
+            _ => *task = Some(waker.clone()),
+        }
+    }
+
+    /// Wakes the registered task, if any. It has to register again to be woken by the next call.
+    pub fn wake(&self) {
+        #[cfg(feature = "async")]
+        {
+            if let Some(waker) = unwrap!(self.task.lock()).take() {
+                waker.wake();
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Entry points for fuzz targets (cargo-fuzz, AFL, ...), enabled by the `fuzzing` feature.
+//!
+//! A `FuzzTarget` sets up a node over the mock network in the requested state and then feeds it
+//! arbitrary bytes as if they were messages received from one of its peers. The bytes go through
+//! the same deserialisation and dispatch as real incoming messages do.
+//!
+//! Not available with `mock_serialise`, as then messages are not serialised at all.
+
+use crate::{
+    error::RoutingError, event_stream::EventStream, mock::Network, node::Node, NetworkConfig,
+    MIN_SECTION_SIZE,
+};
+use bytes::Bytes;
+use std::net::SocketAddr;
+
+// The network is seeded so that a given input always produces the same run.
+const SEED: [u32; 4] = [1, 2, 3, 4];
+
+/// The state of the node receiving the fuzzed messages.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FuzzState {
+    /// A node which sent its bootstrap request and awaits the response. The messages come from
+    /// the node it is bootstrapping off.
+    Bootstrapping,
+    /// The first node of the network, elder of the single section. The messages come from a
+    /// peer bootstrapping off it.
+    Elder,
+}
+
+/// A node set up in a chosen state, ready to receive fuzzed messages.
+pub struct FuzzTarget {
+    _network: Network,
+    nodes: Vec<Node>,
+    target: usize,
+    peer_addr: SocketAddr,
+}
+
+impl FuzzTarget {
+    /// Creates a new network with a node in the given state.
+    pub fn new(state: FuzzState) -> Result<Self, RoutingError> {
+        let network = Network::new(MIN_SECTION_SIZE, Some(SEED));
+
+        let mut first = Node::builder().first(true).create()?;
+        let _ = first.poll();
+        let first_addr = first.our_connection_info()?.peer_addr;
+
+        let config = NetworkConfig::node().with_hard_coded_contact(first_addr);
+        let mut second = Node::builder().network_config(config).create()?;
+        let second_addr = second.our_connection_info()?.peer_addr;
+        network.poll();
+
+        let (target, peer_addr) = match state {
+            FuzzState::Bootstrapping => {
+                let _ = second.poll();
+                (1, first_addr)
+            }
+            FuzzState::Elder => {
+                let _ = first.poll();
+                (0, second_addr)
+            }
+        };
+
+        Ok(Self {
+            _network: network,
+            nodes: vec![first, second],
+            target,
+            peer_addr,
+        })
+    }
+
+    /// Delivers `data` to the node as a message from its peer and lets the node process it.
+    /// Any events the node raises are discarded.
+    pub fn feed(&mut self, data: &[u8]) {
+        let node = &mut self.nodes[self.target];
+        node.inject_message(self.peer_addr, Bytes::from(data));
+        let _ = node.poll();
+        while node.try_next_ev().is_ok() {}
+    }
+}
+
+/// Convenience for fuzz targets: creates a node in the given state and feeds it `data`.
+pub fn fuzz_incoming_message(state: FuzzState, data: &[u8]) -> Result<(), RoutingError> {
+    FuzzTarget::new(state)?.feed(data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{self, Rng};
+    use unwrap::unwrap;
+
+    fn feed_random_bytes(state: FuzzState) {
+        let mut rng = rand::thread_rng();
+        let mut target = unwrap!(FuzzTarget::new(state));
+
+        for _ in 0..100 {
+            let len = rng.gen_range(0, 512);
+            let data: Vec<u8> = rng.gen_iter().take(len).collect();
+            target.feed(&data);
+        }
+    }
+
+    #[test]
+    fn bootstrapping_survives_random_bytes() {
+        feed_random_bytes(FuzzState::Bootstrapping)
+    }
+
+    #[test]
+    fn elder_survives_random_bytes() {
+        feed_random_bytes(FuzzState::Elder)
+    }
+}
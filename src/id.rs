@@ -93,6 +93,12 @@ impl FullId {
             &self.secret_keys.signing,
         )
     }
+
+    /// Decrypts a serialised ciphertext encrypted to our public encryption key.
+    pub(crate) fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        let ciphertext: encryption::Ciphertext = deserialise(ciphertext).ok()?;
+        self.secret_keys.encryption.decrypt(&ciphertext)
+    }
 }
 
 impl parsec::SecretId for FullId {
@@ -115,8 +121,35 @@ impl parsec::SecretId for FullId {
     }
 
     fn decrypt(&self, _from: &Self::PublicId, ciphertext: &[u8]) -> Option<Vec<u8>> {
-        let ciphertext: encryption::Ciphertext = deserialise(ciphertext).ok()?;
-        self.secret_keys.encryption.decrypt(&ciphertext)
+        FullId::decrypt(self, ciphertext)
+    }
+}
+
+impl Serialize for FullId {
+    fn serialize<S: Serializer>(&self, serialiser: S) -> Result<S::Ok, S::Error> {
+        (
+            &self.public_id,
+            &self.secret_keys.signing,
+            encryption::SerdeSecret(&self.secret_keys.encryption),
+        )
+            .serialize(serialiser)
+    }
+}
+
+impl<'de> Deserialize<'de> for FullId {
+    fn deserialize<D: Deserializer<'de>>(deserialiser: D) -> Result<Self, D::Error> {
+        let (public_id, signing, encryption): (
+            PublicId,
+            signing::SecretKey,
+            encryption::SerdeSecret<encryption::SecretKey>,
+        ) = Deserialize::deserialize(deserialiser)?;
+        Ok(FullId {
+            public_id,
+            secret_keys: Rc::new(SecretKeys {
+                signing,
+                encryption: encryption.0,
+            }),
+        })
     }
 }
 
@@ -230,4 +263,15 @@ mod tests {
         let parsed = unwrap!(deserialise(&serialised));
         assert_eq!(*full_id.public_id(), parsed);
     }
+
+    #[test]
+    fn full_id_serialisation() {
+        let full_id = FullId::new();
+        let serialised = unwrap!(serialise(&full_id));
+        let parsed: FullId = unwrap!(deserialise(&serialised));
+        assert_eq!(full_id.public_id(), parsed.public_id());
+
+        let message = b"message";
+        assert!(full_id.public_id().verify(message, &parsed.sign(message)));
+    }
 }
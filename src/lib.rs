@@ -38,6 +38,9 @@
 //! `Node` is used to handle and send requests within that network, and to implement its
 //! functionality, e.g. storing and retrieving data, validating permissions, managing metadata, etc.
 //!
+//! With the `async` feature enabled, `Node` also implements `futures::Stream<Item = Event>`, so it
+//! can be driven from an async executor instead of polling it through `EventStream`.
+//!
 //! # Sequence diagrams
 //!
 //! - [Bootstrapping](bootstrap.png)
@@ -111,15 +114,26 @@ extern crate serde_derive;
 mod macros;
 
 mod action;
+mod backlog;
 mod chain;
+mod chunking;
+mod client;
+#[cfg(not(feature = "mock_serialise"))]
+mod compression;
 #[cfg(not(feature = "mock_crypto"))]
 mod crypto;
+mod dispatch;
+#[cfg(not(feature = "mock_serialise"))]
+mod envelope;
 mod error;
 mod event;
 mod event_stream;
 mod id;
+mod local_params;
 mod message_filter;
+mod message_lanes;
 mod messages;
+mod network_params;
 mod network_service;
 mod node;
 mod outbox;
@@ -128,14 +142,19 @@ mod peer_map;
 mod routing_message_filter;
 mod routing_table;
 mod signature_accumulator;
+mod simulation;
 mod state_machine;
 mod states;
+mod streaming;
 mod time;
 mod timer;
 mod types;
 mod utils;
 mod xor_name;
 
+/// Entry points for fuzzing.
+#[cfg(all(feature = "fuzzing", not(feature = "mock_serialise")))]
+pub mod fuzz;
 /// Mocking utilities.
 #[cfg(feature = "mock_base")]
 pub mod mock;
@@ -166,20 +185,42 @@ use crate::mock::quic_p2p;
 pub use crate::{
     chain::{
         bls_key_set_from_elders_info, delivery_group_size, elders_info_for_test,
-        section_proof_chain_from_elders_info,
+        gen_pfx_info_for_test, section_proof_chain_from_elders_info,
     },
     messages::{HopMessage, Message, MessageContent, RoutingMessage, SignedRoutingMessage},
+    parsec::ParsecMetrics,
+    signature_accumulator::SignatureAccumulator,
 };
 pub use crate::{
+    chain::{
+        check_invariant, AccumulatorMetrics, AuditEntry, AuditLog, ChainView, DefaultQuorumPolicy,
+        ElderSelection, EldersInfo, GenesisPfxInfo, InvariantViolation, OldestElders, Quorum,
+        QuorumPolicy, SectionProofChain, SectionSnapshot, AUDIT_LOG_LEN,
+    },
+    client::{Client, ClientBuilder},
+    dispatch::{
+        ClientAuthorisation, ClientAuthoriser, DirectMessageKind, DirectMessageView, Dispatch,
+        DispatchTable, Filtered, MessageHandler, RoutingMessageKind, RoutingMessageView,
+        UserMessageFilter,
+    },
     error::{InterfaceError, RoutingError},
     event::{ClientEvent, Event},
     event_stream::EventStream,
     id::{FullId, PublicId},
+    local_params::{Codec, InboundLimits, MessageFilterLimits},
+    messages::ProtocolVersion,
+    network_params::{BacklogLimits, JoinBackoff, NetworkParams, Timeouts},
     node::{Node, NodeBuilder},
-    pause::PausedState,
+    parsec::PrunePolicy,
+    pause::{PausedState, RelocationState},
+    routing_message_filter::DuplicateCounts,
     routing_table::Error as RoutingTableError,
-    routing_table::{Authority, Prefix, RoutingTable, VersionedPrefix, Xorable},
-    types::MessageId,
+    routing_table::{
+        Authority, Prefix, RoutingTable, VersionedPrefix, Xorable, MAX_CLOSE_GROUP_SIZE,
+    },
+    simulation::{simulate_join, JoinOutcome, Topology},
+    state_machine::{TransitionObserver, TransitionRecord},
+    types::{MessageId, NeighbourConnectivity, RelayedClientInfo},
     utils::XorTargetInterval,
     xor_name::{XorName, XorNameFromHexError, XOR_NAME_BITS, XOR_NAME_LEN},
 };
@@ -197,7 +238,7 @@ pub(crate) type NetworkBytes = std::rc::Rc<Message>;
 pub use self::quic_p2p::Config as NetworkConfig;
 pub(crate) use self::{
     chain::bls_emu::{
-        PublicKey as BlsPublicKey, PublicKeySet as BlsPublicKeySet,
+        Ciphertext as BlsCiphertext, PublicKey as BlsPublicKey, PublicKeySet as BlsPublicKeySet,
         PublicKeyShare as BlsPublicKeyShare, Signature as BlsSignature,
         SignatureShare as BlsSignatureShare,
     },
@@ -211,7 +252,10 @@ pub(crate) use self::mock::crypto;
 #[cfg(feature = "mock_base")]
 #[doc(hidden)]
 pub mod test_consts {
-    pub use crate::states::{ADD_TIMEOUT, BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT};
+    pub use crate::{
+        local_params::{DELIVERY_ACK_TIMEOUT, REQUEST_TIMEOUT},
+        states::{ADD_TIMEOUT, BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT},
+    };
 }
 
 #[cfg(test)]
@@ -0,0 +1,148 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{dispatch::DispatchTable, time::Duration};
+use std::path::PathBuf;
+
+// Default time a bootstrapping node skips a proxy which timed out or rejected it.
+const PROXY_BLACKLIST_DURATION: Duration = Duration::from_secs(2 * 60);
+// Default time after which a node gives up waiting for the acknowledgement of a message.
+pub const DELIVERY_ACK_TIMEOUT: Duration = Duration::from_secs(60);
+// Default time after which a node gives up waiting for the response to a request.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// Default number of received routing messages a node remembers, to drop them if received again.
+const INCOMING_FILTER_CAPACITY: usize = 100_000;
+// Default time after which a node forgets a received routing message.
+const INCOMING_FILTER_EXPIRY: Duration = Duration::from_secs(20 * 60);
+// Default number of routing messages sent to a peer a node remembers, not to send them again.
+const OUTGOING_FILTER_CAPACITY: usize = 100_000;
+// Default time after which a node forgets a routing message sent to a peer.
+const OUTGOING_FILTER_EXPIRY: Duration = Duration::from_secs(10 * 60);
+// Default window over which the messages received from a peer are counted.
+const INBOUND_WINDOW: Duration = Duration::from_secs(10);
+// Default number of messages a peer may send us per window. Unlimited in tests, where the clock
+// doesn't move unless they advance it.
+#[cfg(not(feature = "mock_base"))]
+const MAX_INBOUND_MESSAGES: usize = 10_000;
+#[cfg(feature = "mock_base")]
+const MAX_INBOUND_MESSAGES: usize = usize::max_value();
+// Default number of bytes a peer may send us per window.
+#[cfg(not(feature = "mock_base"))]
+const MAX_INBOUND_BYTES: usize = 256 * 1024 * 1024;
+#[cfg(feature = "mock_base")]
+const MAX_INBOUND_BYTES: usize = usize::max_value();
+
+/// Codec the node serialises the messages it sends with. Whichever it uses, the node reads the
+/// messages of its peers in either codec.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Codec {
+    /// Compact, but only readable knowing the layout of the message.
+    Bincode,
+    /// Self-describing, readable without knowing the layout of the message, but larger.
+    Json,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::Bincode
+    }
+}
+
+/// Settings local to our node, which other nodes don't need to agree on. Unlike `NetworkParams`,
+/// they are set through `NodeBuilder`, and carried along the network parameters only so that every
+/// state of the node sees them.
+#[derive(Clone, Debug)]
+pub(crate) struct LocalParams {
+    /// How long a bootstrapping node skips a proxy which timed out or rejected its
+    /// `BootstrapRequest`, so it doesn't keep retrying the same dead contact.
+    pub proxy_blacklist_duration: Duration,
+    /// How long the node waits for the acknowledgement of a message sent with
+    /// `Node::send_message_with_ack` before raising `Event::MessageDeliveryFailed`.
+    pub delivery_ack_timeout: Duration,
+    /// How long the node waits for the response to a request sent with `Node::send_request`
+    /// before raising `Event::RequestTimedOut`.
+    pub request_timeout: Duration,
+    /// Limits of the filters suppressing the duplicate routing messages the node receives or
+    /// sends.
+    pub message_filter_limits: MessageFilterLimits,
+    /// Budgets of the messages the node accepts from each peer before disconnecting from it.
+    pub inbound_limits: InboundLimits,
+    /// Handlers attached to the messages the node receives while it is an elder, filters of the
+    /// user messages it receives and authoriser of the clients it serves.
+    pub dispatch_table: DispatchTable,
+    /// Codec of the messages the node sends.
+    pub codec: Codec,
+    /// File the node logs the votes it casts to, so that it doesn't contradict them after a
+    /// restart.
+    pub vote_log_path: Option<PathBuf>,
+}
+
+impl Default for LocalParams {
+    fn default() -> Self {
+        Self {
+            proxy_blacklist_duration: PROXY_BLACKLIST_DURATION,
+            delivery_ack_timeout: DELIVERY_ACK_TIMEOUT,
+            request_timeout: REQUEST_TIMEOUT,
+            message_filter_limits: MessageFilterLimits::default(),
+            inbound_limits: InboundLimits::default(),
+            dispatch_table: DispatchTable::default(),
+            codec: Codec::default(),
+            vote_log_path: None,
+        }
+    }
+}
+
+/// Limits of the filters suppressing duplicate routing messages. Larger ones suppress duplicates
+/// for longer, at the cost of memory. Once full, the least recently seen message is forgotten.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MessageFilterLimits {
+    /// Maximum number of received messages remembered, to drop them if received again.
+    pub incoming_capacity: usize,
+    /// Time after which a received message is forgotten.
+    pub incoming_expiry: Duration,
+    /// Maximum number of messages sent to a peer remembered, not to send them to it again.
+    pub outgoing_capacity: usize,
+    /// Time after which a message sent to a peer is forgotten.
+    pub outgoing_expiry: Duration,
+}
+
+impl Default for MessageFilterLimits {
+    fn default() -> Self {
+        Self {
+            incoming_capacity: INCOMING_FILTER_CAPACITY,
+            incoming_expiry: INCOMING_FILTER_EXPIRY,
+            outgoing_capacity: OUTGOING_FILTER_CAPACITY,
+            outgoing_expiry: OUTGOING_FILTER_EXPIRY,
+        }
+    }
+}
+
+/// Budgets of the messages a node accepts from a single peer, protecting it from floods. A peer
+/// exceeding them within a window is disconnected from, and optionally blacklisted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InboundLimits {
+    /// Window over which the messages of a peer are counted.
+    pub window: Duration,
+    /// Maximum number of messages a peer may send per window.
+    pub max_messages: usize,
+    /// Maximum number of bytes a peer may send per window.
+    pub max_bytes: usize,
+    /// How long connections from a peer exceeding its budget are refused, if at all.
+    pub blacklist_duration: Option<Duration>,
+}
+
+impl Default for InboundLimits {
+    fn default() -> Self {
+        Self {
+            window: INBOUND_WINDOW,
+            max_messages: MAX_INBOUND_MESSAGES,
+            max_bytes: MAX_INBOUND_BYTES,
+            blacklist_duration: None,
+        }
+    }
+}
@@ -22,12 +22,16 @@ fn hash<T: Hash>(t: &T) -> u64 {
 /// A time based message filter that takes any generic type as a key and will drop keys after a
 /// time period (LRU Cache pattern).
 pub struct MessageFilter<Message> {
-    /// The number of times each message has been received so far, and the expiry timestamp.
-    count: HashMap<u64, (usize, Instant)>,
+    /// The number of times each message has been received so far, the expiry timestamp and the
+    /// sequence number of the latest insertion.
+    count: HashMap<u64, (usize, Instant, u64)>,
     /// A record of message hashes and the expiry timestamps of all insertions, ordered
     /// chronologically. The timestamps are out of date if the same hash has been inserted again.
-    timeout_queue: VecDeque<(u64, Instant)>,
+    timeout_queue: VecDeque<(u64, Instant, u64)>,
     time_to_live: Duration,
+    capacity: Option<usize>,
+    /// Sequence number of the next insertion.
+    next_seq: u64,
     phantom: PhantomData<Message>,
 }
 
@@ -38,10 +42,24 @@ impl<Message: Hash> MessageFilter<Message> {
             count: HashMap::new(),
             timeout_queue: VecDeque::new(),
             time_to_live: time_to_live,
+            capacity: None,
+            next_seq: 0,
             phantom: PhantomData,
         }
     }
 
+    /// Constructor for a `MessageFilter` which also holds at most `capacity` messages, dropping
+    /// the least recently inserted ones first.
+    pub fn with_expiry_duration_and_capacity(
+        time_to_live: Duration,
+        capacity: usize,
+    ) -> MessageFilter<Message> {
+        MessageFilter {
+            capacity: Some(capacity),
+            ..Self::with_expiry_duration(time_to_live)
+        }
+    }
+
     /// Adds a message to the filter.
     ///
     /// Removes any expired messages, then adds `message`, then removes enough older messages until
@@ -55,15 +73,22 @@ impl<Message: Hash> MessageFilter<Message> {
         self.remove_expired();
         let hash_code = hash(message);
         let expiry = Instant::now() + self.time_to_live;
-        self.timeout_queue.push_back((hash_code, expiry));
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.timeout_queue.push_back((hash_code, expiry, seq));
         match self.count.entry(hash_code) {
             Entry::Occupied(entry) => {
-                let &mut (ref mut c, ref mut t) = entry.into_mut();
+                let &mut (ref mut c, ref mut t, ref mut s) = entry.into_mut();
                 *t = expiry;
+                *s = seq;
                 *c += 1;
                 *c
             }
-            Entry::Vacant(entry) => entry.insert((1, expiry)).0,
+            Entry::Vacant(entry) => {
+                let _ = entry.insert((1, expiry, seq));
+                self.remove_excess();
+                1
+            }
         }
     }
 
@@ -71,7 +96,7 @@ impl<Message: Hash> MessageFilter<Message> {
     #[cfg(test)]
     pub fn count(&self, message: &Message) -> usize {
         let hash_code = hash(message);
-        self.count.get(&hash_code).map_or(0, |&(count, _)| count)
+        self.count.get(&hash_code).map_or(0, |&(count, _, _)| count)
     }
 
     /// Removes any expired messages, then returns whether `message` exists in the filter or not.
@@ -86,9 +111,9 @@ impl<Message: Hash> MessageFilter<Message> {
         while self
             .timeout_queue
             .front()
-            .map_or(false, |&(_, ref t)| *t <= now)
+            .map_or(false, |&(_, ref t, _)| *t <= now)
         {
-            let (hash_code, _) = unwrap!(self.timeout_queue.pop_front());
+            let (hash_code, _, _) = unwrap!(self.timeout_queue.pop_front());
             if let Entry::Occupied(entry) = self.count.entry(hash_code) {
                 if entry.get().1 <= now {
                     let _removed_pair = entry.remove_entry();
@@ -96,6 +121,23 @@ impl<Message: Hash> MessageFilter<Message> {
             }
         }
     }
+
+    fn remove_excess(&mut self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+
+        while self.count.len() > capacity {
+            let (hash_code, _, seq) = unwrap!(self.timeout_queue.pop_front());
+            // Only the latest insertion of a message removes it.
+            if let Entry::Occupied(entry) = self.count.entry(hash_code) {
+                if entry.get().2 == seq {
+                    let _removed_pair = entry.remove_entry();
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -225,4 +267,23 @@ mod tests {
         sleep(sleep_duration);
         assert!(!msg_filter.contains(&0));
     }
+
+    #[test]
+    fn capacity() {
+        let time_to_live = Duration::from_secs(99);
+        let mut msg_filter =
+            MessageFilter::<usize>::with_expiry_duration_and_capacity(time_to_live, 3);
+
+        for i in 0..3 {
+            assert_eq!(1, msg_filter.insert(&i));
+        }
+        // Re-inserting "0" makes "1" the least recently inserted message.
+        assert_eq!(2, msg_filter.insert(&0));
+        assert_eq!(1, msg_filter.insert(&3));
+
+        assert!(msg_filter.contains(&0));
+        assert!(!msg_filter.contains(&1));
+        assert!(msg_filter.contains(&2));
+        assert!(msg_filter.contains(&3));
+    }
 }
@@ -0,0 +1,76 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::messages::Priority;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Queue with a lane per message priority. Messages are taken from the highest priority lane
+/// first, and in the order they were added within a lane, so that a burst of user messages can't
+/// hold up the ones managing our section.
+pub struct MessageLanes<T> {
+    lanes: BTreeMap<Priority, VecDeque<T>>,
+}
+
+impl<T> Default for MessageLanes<T> {
+    fn default() -> Self {
+        Self {
+            lanes: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> MessageLanes<T> {
+    pub fn push(&mut self, priority: Priority, msg: T) {
+        self.lanes
+            .entry(priority)
+            .or_insert_with(VecDeque::new)
+            .push_back(msg)
+    }
+
+    /// Returns whether there are no messages in any lane.
+    pub fn is_empty(&self) -> bool {
+        self.lanes.values().all(VecDeque::is_empty)
+    }
+
+    /// Removes and returns the oldest message of the highest priority.
+    pub fn pop(&mut self) -> Option<T> {
+        self.lanes
+            .values_mut()
+            .rev()
+            .filter_map(VecDeque::pop_front)
+            .next()
+    }
+
+    /// Returns the messages in the order `pop` would return them.
+    pub fn into_vec(self) -> Vec<T> {
+        self.lanes
+            .into_iter()
+            .rev()
+            .flat_map(|(_, lane)| lane)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_by_priority_then_in_order() {
+        let mut lanes = MessageLanes::default();
+        lanes.push(Priority::Bulk, 0);
+        lanes.push(Priority::Critical, 1);
+        lanes.push(Priority::Bulk, 2);
+        lanes.push(Priority::Normal, 3);
+        lanes.push(Priority::Critical, 4);
+
+        assert_eq!(lanes.pop(), Some(1));
+        assert_eq!(lanes.pop(), Some(4));
+        assert_eq!(lanes.into_vec(), vec![3, 0, 2]);
+    }
+}
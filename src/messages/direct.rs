@@ -7,17 +7,23 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
+    chain::SectionProofChain,
     crypto::signing::Signature,
+    dispatch::DirectMessageKind,
     error::{BootstrapResponseError, RoutingError},
     id::{FullId, PublicId},
-    messages::{SignedRelocateDetails, SignedRoutingMessage},
+    messages::{Priority, SignedRelocateDetails, SignedRoutingMessage},
     parsec,
-    routing_table::Prefix,
+    routing_table::{Authority, Prefix},
+    time::Duration,
+    utils,
     xor_name::XorName,
     ConnectionInfo,
 };
 use maidsafe_utilities::serialisation::serialise;
 use std::{
+    cmp,
+    collections::VecDeque,
     fmt::{self, Debug, Formatter},
     hash::{Hash, Hasher},
     mem,
@@ -33,8 +39,8 @@ pub enum DirectMessage {
     /// message will only be relayed once enough signatures have been accumulated.
     MessageSignature(SignedRoutingMessage),
     /// Sent from a newly connected peer to the bootstrap node to request connection infos of
-    /// members of the section matching the given name.
-    BootstrapRequest(XorName),
+    /// members of the section matching the given name. Also announces what the peer supports.
+    BootstrapRequest(XorName, Capabilities),
     /// Sent from the bootstrap node to a peer in response to `BootstrapRequest`. It can either
     /// accept the peer into the section, or redirect it to another set of bootstrap peers
     BootstrapResponse(BootstrapResponse),
@@ -51,6 +57,129 @@ pub enum DirectMessage {
     ParsecRequest(u64, parsec::Request),
     /// Parsec response message
     ParsecResponse(u64, parsec::Response),
+    /// Sent to an elder to request the chain of its section's keys from the given version up to
+    /// the current one.
+    ProofChainRequest(u64),
+    /// Sent from an elder in response to `ProofChainRequest`.
+    ProofChainResponse(SectionProofChain),
+    /// Sent from an elder to a joining node in response to its `JoinRequest`. The node must solve
+    /// the resource proof before the elder votes it online.
+    ResourceChallenge {
+        target_size: usize,
+        difficulty: u8,
+        seed: Vec<u8>,
+    },
+    /// Sent from a joining node to an elder with the solution to its `ResourceChallenge`.
+    ResourceProofResponse { data: VecDeque<u8>, solution: u64 },
+    /// Sent from a member to the elders of its section to announce it is leaving the network, so
+    /// they can vote it offline right away.
+    Leave,
+    /// Sent from an elder to the other elders of its section to ask them to demote it to an adult,
+    /// e.g. because its host is going under maintenance.
+    StepDown,
+    /// Sent from a member to the elders of its section when it receives a `NodeApproval` after
+    /// having joined already, so they stop approving it.
+    JoinedAlready,
+    /// Sent from an elder to a joining node in response to its `JoinRequest`. The node must sign
+    /// the nonce before the elder challenges it with a resource proof, proving it is reachable at
+    /// its address and holds the key of its identity now rather than replaying an old request.
+    JoinChallenge(Vec<u8>),
+    /// Sent from a joining node to an elder with its signature of the `JoinChallenge` nonce. See
+    /// `join_challenge_bytes`.
+    JoinChallengeResponse(Signature),
+    /// Sent from a client to its proxy elder, which relays `content` to `dst` on the client's
+    /// behalf. The signature of the enclosing `SignedDirectMessage` identifies the client. The
+    /// proxy's connection info stops the request from being replayed to another elder, and the
+    /// nonce, which must grow with each request, from being replayed to the same one. See
+    /// `client_nonce_clock`.
+    ClientRequest {
+        dst: Authority<XorName>,
+        proxy: ConnectionInfo,
+        nonce: u64,
+        content: Vec<u8>,
+    },
+    /// Sent from a client to its proxy elder once bootstrapped, so that the proxy can authorise
+    /// it by its public id before passing on its messages. It is protected from replays like a
+    /// `ClientRequest`.
+    ClientIdentify { proxy: ConnectionInfo, nonce: u64 },
+}
+
+/// Returns the bytes a joining node signs to answer the `JoinChallenge` with the given nonce. They
+/// are prefixed so that an elder can't make the node sign arbitrary data.
+pub fn join_challenge_bytes(nonce: &[u8]) -> Vec<u8> {
+    let mut bytes = b"JoinChallenge".to_vec();
+    bytes.extend_from_slice(nonce);
+    bytes
+}
+
+/// Returns the current time in milliseconds since the Unix epoch. Clients use it as the nonce of
+/// their `ClientRequest`s and `ClientIdentify`s unless it isn't greater than the previous one, so
+/// that the nonces keep growing when a client restarts with the same identity, and proxies only
+/// need to remember the last nonce of the recent clients.
+pub fn client_nonce_clock() -> u64 {
+    utils::unix_time_ms()
+}
+
+/// Returns the current time in milliseconds since the Unix epoch. Peers use it as the nonce of a
+/// new connection, which they sign their `SignedDirectMessage`s over it with, unless it isn't
+/// greater than the previous one. See `PeerMap::our_connection_nonce`.
+pub fn connection_nonce_clock() -> u64 {
+    utils::unix_time_ms()
+}
+
+/// Version of the wire protocol. Peers with different major versions can't talk to each other,
+/// while peers with different minor versions use the features of the older one.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ProtocolVersion {
+    /// Incremented on incompatible changes.
+    pub major: u16,
+    /// Incremented on backwards-compatible changes, e.g. new optional features.
+    pub minor: u16,
+}
+
+impl ProtocolVersion {
+    /// The version this version of the crate speaks.
+    pub const CURRENT: Self = Self { major: 1, minor: 1 };
+
+    /// Returns the version two peers speaking `self` and `other` use to talk to each other, or
+    /// `None` if they can't.
+    pub fn negotiate(self, other: Self) -> Option<Self> {
+        if self.major == other.major {
+            Some(cmp::min(self, other))
+        } else {
+            None
+        }
+    }
+}
+
+/// The version of the protocol and the optional features a peer supports, announced when
+/// connecting to another peer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// The version of the protocol the peer speaks.
+    pub version: ProtocolVersion,
+    /// Whether the peer can decompress the large messages we compress. Since version 1.1.
+    pub compression: bool,
+}
+
+impl Capabilities {
+    /// Returns the capabilities of this version of the crate.
+    pub fn ours() -> Self {
+        Self {
+            version: ProtocolVersion::CURRENT,
+            compression: true,
+        }
+    }
+
+    /// Returns the capabilities both `self` and `other` support, or `None` if their versions are
+    /// incompatible.
+    pub fn common(&self, other: &Self) -> Option<Self> {
+        let version = self.version.negotiate(other.version)?;
+        Some(Self {
+            version,
+            compression: self.compression && other.compression && version.minor >= 1,
+        })
+    }
 }
 
 /// Response to a BootstrapRequest
@@ -58,14 +187,19 @@ pub enum DirectMessage {
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug, Hash)]
 pub enum BootstrapResponse {
     /// This response means that the new peer is clear to join the section. The connection infos of
-    /// the section elders and the section prefix are provided.
+    /// the section elders and the section prefix are provided, along with the capabilities of the
+    /// responding elder.
     Join {
         prefix: Prefix<XorName>,
         conn_infos: Vec<ConnectionInfo>,
+        capabilities: Capabilities,
     },
     /// The new peer should retry bootstrapping with another section. The set of connection infos
     /// of the members of that section is provided.
     Rebootstrap(Vec<ConnectionInfo>),
+    /// The section is already processing as many joining nodes as it admits at the same time. The
+    /// new peer should retry bootstrapping after the given backoff.
+    TryAgainLater { backoff: Duration },
     /// An error has occurred
     Error(BootstrapResponseError),
 }
@@ -105,12 +239,69 @@ impl RelocatePayload {
     }
 }
 
+impl DirectMessage {
+    /// Returns the kind of this message, i.e. its variant.
+    pub fn kind(&self) -> DirectMessageKind {
+        use self::DirectMessage::*;
+        match self {
+            MessageSignature(_) => DirectMessageKind::MessageSignature,
+            BootstrapRequest(..) => DirectMessageKind::BootstrapRequest,
+            BootstrapResponse(_) => DirectMessageKind::BootstrapResponse,
+            JoinRequest(_) => DirectMessageKind::JoinRequest,
+            ConnectionResponse => DirectMessageKind::ConnectionResponse,
+            ParsecPoke(_) => DirectMessageKind::ParsecPoke,
+            ParsecRequest(..) => DirectMessageKind::ParsecRequest,
+            ParsecResponse(..) => DirectMessageKind::ParsecResponse,
+            ProofChainRequest(_) => DirectMessageKind::ProofChainRequest,
+            ProofChainResponse(_) => DirectMessageKind::ProofChainResponse,
+            ResourceChallenge { .. } => DirectMessageKind::ResourceChallenge,
+            ResourceProofResponse { .. } => DirectMessageKind::ResourceProofResponse,
+            Leave => DirectMessageKind::Leave,
+            StepDown => DirectMessageKind::StepDown,
+            JoinedAlready => DirectMessageKind::JoinedAlready,
+            JoinChallenge(_) => DirectMessageKind::JoinChallenge,
+            JoinChallengeResponse(_) => DirectMessageKind::JoinChallengeResponse,
+            ClientRequest { .. } => DirectMessageKind::ClientRequest,
+            ClientIdentify { .. } => DirectMessageKind::ClientIdentify,
+        }
+    }
+
+    /// Returns the priority of this message. Signatures of routing messages take the priority of
+    /// the message they sign.
+    pub fn priority(&self) -> Priority {
+        use self::DirectMessage::*;
+        match self {
+            MessageSignature(msg) => msg.priority(),
+            ClientRequest { .. } => Priority::Bulk,
+            BootstrapRequest(..)
+            | BootstrapResponse(_)
+            | JoinRequest(_)
+            | ConnectionResponse
+            | ResourceChallenge { .. }
+            | ResourceProofResponse { .. }
+            | JoinChallenge(_)
+            | JoinChallengeResponse(_)
+            | ClientIdentify { .. } => Priority::Normal,
+            ParsecPoke(_)
+            | ParsecRequest(..)
+            | ParsecResponse(..)
+            | ProofChainRequest(_)
+            | ProofChainResponse(_)
+            | Leave
+            | StepDown
+            | JoinedAlready => Priority::Critical,
+        }
+    }
+}
+
 impl Debug for DirectMessage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         use self::DirectMessage::*;
         match self {
             MessageSignature(msg) => write!(formatter, "MessageSignature ({:?})", msg),
-            BootstrapRequest(name) => write!(formatter, "BootstrapRequest({})", name),
+            BootstrapRequest(name, capabilities) => {
+                write!(formatter, "BootstrapRequest({}, {:?})", name, capabilities)
+            }
             BootstrapResponse(response) => write!(formatter, "BootstrapResponse({:?})", response),
             JoinRequest(relocate_details) => write!(
                 formatter,
@@ -123,6 +314,40 @@ impl Debug for DirectMessage {
             ParsecRequest(v, _) => write!(formatter, "ParsecRequest({}, _)", v),
             ParsecResponse(v, _) => write!(formatter, "ParsecResponse({}, _)", v),
             ParsecPoke(v) => write!(formatter, "ParsecPoke({})", v),
+            ProofChainRequest(v) => write!(formatter, "ProofChainRequest({})", v),
+            ProofChainResponse(chain) => write!(
+                formatter,
+                "ProofChainResponse({:?})",
+                chain.last_public_key_info()
+            ),
+            ResourceChallenge {
+                target_size,
+                difficulty,
+                ..
+            } => write!(
+                formatter,
+                "ResourceChallenge {{ target_size: {}, difficulty: {} }}",
+                target_size, difficulty
+            ),
+            ResourceProofResponse { data, solution } => write!(
+                formatter,
+                "ResourceProofResponse {{ data: {} bytes, solution: {} }}",
+                data.len(),
+                solution
+            ),
+            Leave => write!(formatter, "Leave"),
+            StepDown => write!(formatter, "StepDown"),
+            JoinedAlready => write!(formatter, "JoinedAlready"),
+            JoinChallenge(_) => write!(formatter, "JoinChallenge"),
+            JoinChallengeResponse(_) => write!(formatter, "JoinChallengeResponse"),
+            ClientRequest { dst, nonce, .. } => {
+                write!(
+                    formatter,
+                    "ClientRequest {{ dst: {:?}, nonce: {} }}",
+                    dst, nonce
+                )
+            }
+            ClientIdentify { nonce, .. } => write!(formatter, "ClientIdentify({})", nonce),
         }
     }
 }
@@ -140,7 +365,10 @@ impl Hash for DirectMessage {
 
         match self {
             MessageSignature(msg) => msg.hash(state),
-            BootstrapRequest(name) => name.hash(state),
+            BootstrapRequest(name, capabilities) => {
+                name.hash(state);
+                capabilities.hash(state);
+            }
             BootstrapResponse(response) => response.hash(state),
             JoinRequest(payload) => payload.hash(state),
             ConnectionResponse => (),
@@ -155,40 +383,97 @@ impl Hash for DirectMessage {
                 // Fake hash via serialisation
                 serialise(&response).ok().hash(state)
             }
+            ProofChainRequest(version) => version.hash(state),
+            ProofChainResponse(chain) => chain.hash(state),
+            ResourceChallenge {
+                target_size,
+                difficulty,
+                seed,
+            } => {
+                target_size.hash(state);
+                difficulty.hash(state);
+                seed.hash(state);
+            }
+            ResourceProofResponse { data, solution } => {
+                data.hash(state);
+                solution.hash(state);
+            }
+            JoinChallenge(nonce) => nonce.hash(state),
+            JoinChallengeResponse(signature) => signature.hash(state),
+            ClientRequest {
+                dst,
+                proxy,
+                nonce,
+                content,
+            } => {
+                dst.hash(state);
+                proxy.hash(state);
+                nonce.hash(state);
+                content.hash(state);
+            }
+            ClientIdentify { proxy, nonce } => {
+                proxy.hash(state);
+                nonce.hash(state);
+            }
+            Leave | StepDown | JoinedAlready => (),
         }
     }
 }
 
+/// A `DirectMessage` signed by its sender, together with the sender's id. The signature also
+/// covers the id of the recipient, if the sender knows it, and the nonce of the sender's connection
+/// to the recipient, so that the message can't be replayed to another peer or over another
+/// connection to impersonate the sender.
 #[cfg_attr(feature = "mock_serialise", derive(Clone))]
 #[derive(Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct SignedDirectMessage {
     content: DirectMessage,
     src_id: PublicId,
+    dst_id: Option<PublicId>,
+    conn_nonce: u64,
     signature: Signature,
 }
 
 impl SignedDirectMessage {
-    /// Create new `DirectMessage` with `content` and signed by `src_full_id`.
-    pub fn new(content: DirectMessage, src_full_id: &FullId) -> Result<Self, RoutingError> {
-        let signature = self::implementation::sign(src_full_id, &content)?;
+    /// Create new `DirectMessage` with `content`, sent to `dst_id` over the connection with the
+    /// nonce `conn_nonce` and signed by `src_full_id`.
+    pub fn new(
+        content: DirectMessage,
+        src_full_id: &FullId,
+        dst_id: Option<PublicId>,
+        conn_nonce: u64,
+    ) -> Result<Self, RoutingError> {
+        let signature = self::implementation::sign(src_full_id, dst_id, conn_nonce, &content)?;
 
         Ok(Self {
             content,
             src_id: *src_full_id.public_id(),
+            dst_id,
+            conn_nonce,
             signature,
         })
     }
 
     /// Verify the message signature.
     pub fn verify(&self) -> Result<(), RoutingError> {
-        self::implementation::verify(&self.src_id, &self.signature, &self.content)
+        self::implementation::verify(
+            &self.src_id,
+            self.dst_id,
+            self.conn_nonce,
+            &self.signature,
+            &self.content,
+        )
     }
 
-    /// Verify the message signature and return its content and the sender id.
+    /// Verify the message signature and that it was sent to `our_id`, and return its content, the
+    /// sender id and the nonce of the connection it was sent over.
     /// Consume the message in the process.
-    pub fn open(self) -> Result<(DirectMessage, PublicId), RoutingError> {
+    pub fn open(self, our_id: &PublicId) -> Result<(DirectMessage, PublicId, u64), RoutingError> {
         self.verify()?;
-        Ok((self.content, self.src_id))
+        if self.dst_id.map_or(false, |dst_id| dst_id != *our_id) {
+            return Err(RoutingError::InvalidDestination);
+        }
+        Ok((self.content, self.src_id, self.conn_nonce))
     }
 
     /// Content of the message.
@@ -202,8 +487,9 @@ impl Debug for SignedDirectMessage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
             formatter,
-            "SignedDirectMessage {{ content: {:?}, src_id: {:?}, signature: {:?} }}",
-            self.content, self.src_id, self.signature
+            "SignedDirectMessage {{ content: {:?}, src_id: {:?}, dst_id: {:?}, conn_nonce: {}, \
+             signature: {:?} }}",
+            self.content, self.src_id, self.dst_id, self.conn_nonce, self.signature
         )
     }
 }
@@ -212,18 +498,25 @@ impl Debug for SignedDirectMessage {
 mod implementation {
     use super::*;
 
-    pub fn sign(src_full_id: &FullId, content: &DirectMessage) -> Result<Signature, RoutingError> {
-        let serialised = serialise(content)?;
+    pub fn sign(
+        src_full_id: &FullId,
+        dst_id: Option<PublicId>,
+        conn_nonce: u64,
+        content: &DirectMessage,
+    ) -> Result<Signature, RoutingError> {
+        let serialised = serialise(&(src_full_id.public_id(), dst_id, conn_nonce, content))?;
         let signature = src_full_id.sign(&serialised);
         Ok(signature)
     }
 
     pub fn verify(
         src_id: &PublicId,
+        dst_id: Option<PublicId>,
+        conn_nonce: u64,
         signature: &Signature,
         content: &DirectMessage,
     ) -> Result<(), RoutingError> {
-        let serialised = serialise(content)?;
+        let serialised = serialise(&(src_id, dst_id, conn_nonce, content))?;
 
         if src_id.verify(&serialised, signature) {
             Ok(())
@@ -239,11 +532,22 @@ mod implementation {
     use crate::crypto::signing::SIGNATURE_LENGTH;
     use unwrap::unwrap;
 
-    pub fn sign(_: &FullId, _: &DirectMessage) -> Result<Signature, RoutingError> {
+    pub fn sign(
+        _: &FullId,
+        _: Option<PublicId>,
+        _: u64,
+        _: &DirectMessage,
+    ) -> Result<Signature, RoutingError> {
         Ok(unwrap!(Signature::from_bytes(&[0; SIGNATURE_LENGTH])))
     }
 
-    pub fn verify(_: &PublicId, _: &Signature, _: &DirectMessage) -> Result<(), RoutingError> {
+    pub fn verify(
+        _: &PublicId,
+        _: Option<PublicId>,
+        _: u64,
+        _: &Signature,
+        _: &DirectMessage,
+    ) -> Result<(), RoutingError> {
         Ok(())
     }
 }
@@ -10,28 +10,40 @@ mod direct;
 mod relocate;
 
 pub use self::{
-    direct::{BootstrapResponse, DirectMessage, RelocatePayload, SignedDirectMessage},
+    direct::{
+        client_nonce_clock, connection_nonce_clock, join_challenge_bytes, BootstrapResponse,
+        Capabilities, DirectMessage, ProtocolVersion, RelocatePayload, SignedDirectMessage,
+    },
     relocate::{RelocateDetails, SignedRelocateDetails},
 };
 use crate::{
     chain::{Chain, EldersInfo, GenesisPfxInfo, SectionKeyInfo, SectionProofChain},
     crypto::{self, signing::Signature, Digest256},
+    dispatch::RoutingMessageKind,
     error::{Result, RoutingError},
     id::{FullId, PublicId},
     routing_table::{Authority, Prefix},
-    types::MessageId,
+    time::Duration,
+    types::{MessageId, RelayedClientInfo},
+    utils::{self, RngCompat},
     xor_name::XorName,
-    BlsPublicKeySet, BlsPublicKeyShare, BlsSignature, BlsSignatureShare, ConnectionInfo,
+    BlsCiphertext, BlsPublicKey, BlsPublicKeySet, BlsPublicKeyShare, BlsSignature,
+    BlsSignatureShare, ConnectionInfo,
 };
 use hex_fmt::HexFmt;
 use log::LogLevel;
-use maidsafe_utilities::serialisation::serialise;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
 use std::{
     collections::BTreeMap,
     fmt::{self, Debug, Formatter},
     mem,
 };
 
+/// Maximum number of times a message can be relayed on its way to its destination. Messages
+/// caught in a routing loop, e.g. while sections disagree about their prefixes, are dropped once
+/// they reach it.
+pub const MAX_HOPS: u8 = 32;
+
 /// Wrapper of all messages.
 ///
 /// This is the only type allowed to be sent / received on the network.
@@ -44,6 +56,24 @@ pub enum Message {
     Direct(SignedDirectMessage),
     /// A message sent across the network (in transit)
     Hop(HopMessage),
+    /// Several messages in transit with the same next hop, sent together. The recipient handles
+    /// each of them as if it came in its own `Hop`.
+    Hops(Vec<HopMessage>),
+}
+
+impl Message {
+    /// Returns the priority of the message, which depends on the messages it carries.
+    pub fn priority(&self) -> Priority {
+        match self {
+            Message::Direct(msg) => msg.content().priority(),
+            Message::Hop(msg) => msg.content.priority(),
+            Message::Hops(msgs) => msgs
+                .iter()
+                .map(|msg| msg.content.priority())
+                .max()
+                .unwrap_or(Priority::Bulk),
+        }
+    }
 }
 
 /// An individual hop message that represents a part of the route of a message in transit.
@@ -56,12 +86,20 @@ pub enum Message {
 pub struct HopMessage {
     /// Wrapped signed message.
     pub content: SignedRoutingMessage,
+    /// Number of times the message may still be relayed. Each relay decrements it, and a message
+    /// received with a TTL of zero is only handled if it is for us.
+    pub ttl: u8,
 }
 
 impl HopMessage {
     /// Wrap `content` for transmission to the next hop and sign it.
     pub fn new(content: SignedRoutingMessage) -> Result<HopMessage> {
-        Ok(HopMessage { content: content })
+        Self::with_ttl(content, MAX_HOPS)
+    }
+
+    /// Wrap `content`, which may be relayed `ttl` more times, for transmission to the next hop.
+    pub fn with_ttl(content: SignedRoutingMessage, ttl: u8) -> Result<HopMessage> {
+        Ok(HopMessage { content, ttl })
     }
 }
 
@@ -127,12 +165,41 @@ impl Debug for FullSecurityMetadata {
 pub struct SingleSrcSecurityMetadata {
     public_id: PublicId,
     signature: Signature,
+    /// All the destinations of a message sent to several of them at once. The signature then
+    /// covers the message with these in place of its own destination, so that it is computed only
+    /// once for all of them.
+    dsts: Option<Vec<Authority<XorName>>>,
+    /// When the message was signed, in milliseconds since the Unix epoch by the sender's clock.
+    /// Covered by the signature, so a replayed message is dropped once it is too old to still be
+    /// remembered by the recipient's filter.
+    sent_at: u64,
 }
 
 impl SingleSrcSecurityMetadata {
     pub fn verify_sig(&self, bytes: &[u8]) -> bool {
         self.public_id.verify(bytes, &self.signature)
     }
+
+    // Returns the bytes the signature of `content` is of.
+    fn signed_bytes(&self, content: &RoutingMessage) -> Result<Vec<u8>> {
+        match self.dsts {
+            None => Ok(serialise(&(content, self.sent_at))?),
+            Some(ref dsts) if dsts.contains(&content.dst) => Ok(serialise(&(
+                &content.src,
+                dsts,
+                &content.content,
+                self.sent_at,
+            ))?),
+            Some(_) => Err(RoutingError::InvalidDestination),
+        }
+    }
+
+    // Returns whether the message was sent less than `max_skew` before or after the current time.
+    fn is_fresh(&self, max_skew: Duration) -> bool {
+        let now = utils::unix_time_ms();
+        let max_skew = utils::duration_ms(max_skew);
+        self.sent_at.saturating_add(max_skew) >= now && self.sent_at <= now.saturating_add(max_skew)
+    }
 }
 
 impl Debug for SingleSrcSecurityMetadata {
@@ -172,6 +239,13 @@ pub struct SignedRoutingMessage {
     content: RoutingMessage,
     /// Optional metadata for verifying the sender
     security_metadata: SecurityMetadata,
+    /// Identifier set by the sender to trace the path of the message through the network in the
+    /// logs. Not signed, as it is only meant for debugging.
+    trace_id: Option<MessageId>,
+    /// Number of hops the message took to reach us. Not sent: the recipient sets it from the TTL
+    /// of the `HopMessage` the message arrived in.
+    #[serde(skip)]
+    hop_count: u8,
 }
 
 impl SignedRoutingMessage {
@@ -194,6 +268,8 @@ impl SignedRoutingMessage {
         Ok(Self {
             content,
             security_metadata: SecurityMetadata::Partial(partial_metadata),
+            trace_id: None,
+            hop_count: 0,
         })
     }
 
@@ -202,23 +278,62 @@ impl SignedRoutingMessage {
         content: RoutingMessage,
         full_id: &FullId,
     ) -> Result<SignedRoutingMessage> {
+        let sent_at = utils::unix_time_ms();
         let single_metadata = SingleSrcSecurityMetadata {
             public_id: *full_id.public_id(),
-            signature: full_id.sign(&serialise(&content)?),
+            signature: full_id.sign(&serialise(&(&content, sent_at))?),
+            dsts: None,
+            sent_at,
         };
 
         Ok(Self {
             content,
             security_metadata: SecurityMetadata::Single(single_metadata),
+            trace_id: None,
+            hop_count: 0,
         })
     }
 
+    /// Creates the messages with `content` from the single source `src` to each of `dsts`, all
+    /// covered by the same signature by the given `full_id`.
+    pub fn single_source_to_many(
+        src: Authority<XorName>,
+        dsts: Vec<Authority<XorName>>,
+        content: MessageContent,
+        full_id: &FullId,
+    ) -> Result<Vec<SignedRoutingMessage>> {
+        let sent_at = utils::unix_time_ms();
+        let signature = full_id.sign(&serialise(&(&src, &dsts, &content, sent_at))?);
+        let single_metadata = SingleSrcSecurityMetadata {
+            public_id: *full_id.public_id(),
+            signature,
+            dsts: Some(dsts.clone()),
+            sent_at,
+        };
+
+        Ok(dsts
+            .into_iter()
+            .map(|dst| Self {
+                content: RoutingMessage {
+                    src,
+                    dst,
+                    content: content.clone(),
+                },
+                security_metadata: SecurityMetadata::Single(single_metadata.clone()),
+                trace_id: None,
+                hop_count: 0,
+            })
+            .collect())
+    }
+
     /// Creates a `SignedRoutingMessage` without security metadata
     #[cfg(all(test, feature = "mock_base"))]
     pub fn insecure(content: RoutingMessage) -> SignedRoutingMessage {
         Self {
             content,
             security_metadata: SecurityMetadata::None,
+            trace_id: None,
+            hop_count: 0,
         }
     }
 
@@ -229,6 +344,39 @@ impl SignedRoutingMessage {
         Self {
             content,
             security_metadata,
+            trace_id: None,
+            hop_count: 0,
+        }
+    }
+
+    /// Sets the identifier tracing this message through the network.
+    pub fn set_trace_id(&mut self, trace_id: Option<MessageId>) {
+        self.trace_id = trace_id;
+    }
+
+    /// Returns the identifier tracing this message through the network, if its sender set one.
+    pub fn trace_id(&self) -> Option<MessageId> {
+        self.trace_id
+    }
+
+    /// Sets the number of hops the message took to reach us.
+    pub fn set_hop_count(&mut self, hop_count: u8) {
+        self.hop_count = hop_count;
+    }
+
+    /// Returns the number of hops the message took to reach us: zero if sent by ourselves.
+    pub fn hop_count(&self) -> u8 {
+        self.hop_count
+    }
+
+    /// Returns whether the message is signed by its source section and carries the proof of the
+    /// section's key, as in Secure Message Delivery, rather than signed by a single node.
+    pub fn is_section_signed(&self) -> bool {
+        match self.security_metadata {
+            SecurityMetadata::Full(_) => true,
+            SecurityMetadata::None | SecurityMetadata::Partial(_) | SecurityMetadata::Single(_) => {
+                false
+            }
         }
     }
 
@@ -246,7 +394,7 @@ impl SignedRoutingMessage {
                     return Err(RoutingError::InvalidMessage);
                 }
 
-                let signed_bytes = serialise(&self.content)?;
+                let signed_bytes = security_metadata.signed_bytes(&self.content)?;
                 if !security_metadata.verify_sig(&signed_bytes) {
                     return Err(RoutingError::FailedSignature);
                 }
@@ -276,6 +424,19 @@ impl SignedRoutingMessage {
         }
     }
 
+    /// Checks that the message can't be a replay: that it isn't signed with a long obsolete key of
+    /// its source section, or, if signed by a single node, that it was sent less than `max_skew`
+    /// from now.
+    pub fn check_fresh(&self, chain: &Chain, max_skew: Duration) -> bool {
+        match self.security_metadata {
+            SecurityMetadata::Full(ref security_metadata) => {
+                !chain.is_obsolete(security_metadata.proof_chain())
+            }
+            SecurityMetadata::Single(ref security_metadata) => security_metadata.is_fresh(max_skew),
+            SecurityMetadata::None | SecurityMetadata::Partial(_) => true,
+        }
+    }
+
     /// Returns the security metadata validating the message.
     pub fn source_section_key_info(&self) -> Option<&SectionKeyInfo> {
         match self.security_metadata {
@@ -311,9 +472,13 @@ impl SignedRoutingMessage {
                 self_partial.shares.append(&mut other_partial.shares);
             }
         }
+        if self.trace_id.is_none() {
+            self.trace_id = msg.trace_id;
+        }
     }
 
-    /// Combines the signatures into a single BLS signature
+    /// Combines the signatures into a single BLS signature and verifies it against the last key
+    /// of the proof chain. If either fails, the message is left without security metadata.
     pub fn combine_signatures(&mut self) {
         match mem::replace(&mut self.security_metadata, SecurityMetadata::None) {
             SecurityMetadata::Partial(partial) => {
@@ -321,10 +486,22 @@ impl SignedRoutingMessage {
                     .pk_set
                     .combine_signatures(partial.shares.iter().map(|(key, sig)| (*key, sig)))
                 {
-                    self.security_metadata = SecurityMetadata::Full(FullSecurityMetadata {
+                    let full = FullSecurityMetadata {
                         proof: partial.proof,
                         signature: full_sig,
-                    });
+                    };
+                    let verified = serialise(&self.content)
+                        .map(|signed_bytes| full.verify_sig(&signed_bytes))
+                        .unwrap_or(false);
+                    if verified {
+                        self.security_metadata = SecurityMetadata::Full(full);
+                    } else {
+                        log_or_panic!(
+                            LogLevel::Error,
+                            "Combined signature failed verification on {:?}!",
+                            self
+                        );
+                    }
                 } else {
                     log_or_panic!(
                         LogLevel::Error,
@@ -349,6 +526,11 @@ impl SignedRoutingMessage {
         (self.content, self.security_metadata)
     }
 
+    /// Returns the priority of the routing message.
+    pub fn priority(&self) -> Priority {
+        self.content.priority()
+    }
+
     /// The routing message that was signed.
     pub fn routing_message(&self) -> &RoutingMessage {
         &self.content
@@ -454,6 +636,23 @@ impl RoutingMessage {
         let serialised_msg = serialise(self)?;
         Ok(crypto::sha3_256(&serialised_msg))
     }
+
+    /// Returns the priority of the message, which depends on its content.
+    pub fn priority(&self) -> Priority {
+        self.content.priority()
+    }
+}
+
+/// Priority of a routing message. Messages of higher priority are handled first, so that bulk
+/// user traffic can't hold up the ones managing the sections.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Priority {
+    /// User and client messages.
+    Bulk,
+    /// Messages establishing connections between nodes.
+    Normal,
+    /// Messages changing the membership or the knowledge of the sections.
+    Critical,
 }
 
 /// The routing message types
@@ -524,6 +723,8 @@ pub enum MessageContent {
         pub_id: PublicId,
         /// Sender's connection info.
         conn_info: ConnectionInfo,
+        /// The version of the protocol and the features the sender supports.
+        capabilities: Capabilities,
         /// The message's unique identifier.
         msg_id: MessageId,
     },
@@ -533,7 +734,95 @@ pub enum MessageContent {
     /// the given hash will be the merged section.
     Merge(Digest256),
     /// User-facing message
-    UserMessage(Vec<u8>),
+    UserMessage {
+        /// The content of the message.
+        content: Vec<u8>,
+        /// The message's unique identifier, raised with it in `Event::MessageReceived`.
+        msg_id: MessageId,
+    },
+    /// Part of a user-facing message too large to be sent in one piece.
+    UserMessagePart {
+        /// Hash of the whole content of the message, identifying it.
+        digest: Digest256,
+        /// Index of this part, starting at zero.
+        index: u32,
+        /// Number of parts of the message.
+        part_count: u32,
+        /// The content of this part.
+        payload: Vec<u8>,
+        /// The message's unique identifier, raised with it in `Event::MessageReceived`.
+        msg_id: MessageId,
+        /// If set, the recipients acknowledge receiving the whole message with a `DeliveryAck`
+        /// carrying its id.
+        ack: bool,
+    },
+    /// `UserMessage`, `UserMessagePart`, `StreamPart`, `UserRequest` or `UserResponse` encrypted
+    /// to the key of the destination section, so that the nodes relaying it can't read it.
+    EncryptedUserMessage(BlsCiphertext),
+    /// Acknowledgement of the user message with the given id, sent back to its source by its
+    /// destination.
+    DeliveryAck(MessageId),
+    /// Part of a user-facing stream, delivered in order as soon as the preceding parts were.
+    StreamPart {
+        /// Id of the stream, chosen by its sender.
+        stream_id: MessageId,
+        /// Index of this part, starting at zero.
+        index: u32,
+        /// Number of parts of the stream.
+        part_count: u32,
+        /// The content of this part.
+        payload: Vec<u8>,
+    },
+    /// Credit granted to the sender of a stream by its destination: the sender may send the
+    /// parts with an index below `credit`.
+    StreamCredit {
+        /// Id of the stream.
+        stream_id: MessageId,
+        /// Number of parts the sender may have sent.
+        credit: u32,
+    },
+    /// User-facing request, to be answered with a `UserResponse` carrying the same token.
+    UserRequest {
+        /// Token chosen by the requester, correlating the response with the request.
+        token: MessageId,
+        /// The content of the request.
+        content: Vec<u8>,
+    },
+    /// User-facing response to the `UserRequest` with the given token.
+    UserResponse {
+        /// Token of the request this responds to.
+        token: MessageId,
+        /// The content of the response.
+        content: Vec<u8>,
+    },
+    /// Report that the message with the given hash, addressed to `dst`, couldn't be delivered, as
+    /// the section responsible for `dst` has no such node, or as it was encrypted to a key of that
+    /// section from before the reporting elder joined it. Sent back to the source of the message
+    /// by the nodes of that section.
+    Undeliverable {
+        /// Hash of the routing message which couldn't be delivered.
+        digest: Digest256,
+        /// Destination of that message.
+        dst: Authority<XorName>,
+    },
+    /// Rejection of a user message by the filters of its destination, with their reason. Sent back
+    /// to the source of the message by its destination.
+    MessageRejected {
+        /// The id of the rejected message, or the token of the rejected request, or the id of the
+        /// stream the rejected part belongs to.
+        msg_id: MessageId,
+        /// The reason given by the filters.
+        reason: Vec<u8>,
+    },
+    /// User-facing message sent by a client and relayed into the network by its proxy elder.
+    RelayedClientMessage {
+        /// The client and the relay path.
+        client: RelayedClientInfo,
+        /// The content of the message.
+        content: Vec<u8>,
+        /// The message's unique identifier, chosen by the proxy.
+        msg_id: MessageId,
+    },
     /// Approves the joining node as a routing node.
     ///
     /// Sent from Group Y to the joining node.
@@ -549,12 +838,102 @@ pub enum MessageContent {
     Relocate(RelocateDetails),
 }
 
+impl MessageContent {
+    /// Returns the kind of this message, i.e. its variant.
+    pub fn kind(&self) -> RoutingMessageKind {
+        use self::MessageContent::*;
+        match self {
+            ConnectionRequest { .. } => RoutingMessageKind::ConnectionRequest,
+            NeighbourInfo(_) => RoutingMessageKind::NeighbourInfo,
+            Merge(_) => RoutingMessageKind::Merge,
+            UserMessage { .. } => RoutingMessageKind::UserMessage,
+            UserMessagePart { .. } => RoutingMessageKind::UserMessagePart,
+            EncryptedUserMessage(_) => RoutingMessageKind::EncryptedUserMessage,
+            DeliveryAck(_) => RoutingMessageKind::DeliveryAck,
+            StreamPart { .. } => RoutingMessageKind::StreamPart,
+            StreamCredit { .. } => RoutingMessageKind::StreamCredit,
+            UserRequest { .. } => RoutingMessageKind::UserRequest,
+            UserResponse { .. } => RoutingMessageKind::UserResponse,
+            Undeliverable { .. } => RoutingMessageKind::Undeliverable,
+            MessageRejected { .. } => RoutingMessageKind::MessageRejected,
+            RelayedClientMessage { .. } => RoutingMessageKind::RelayedClientMessage,
+            NodeApproval(_) => RoutingMessageKind::NodeApproval,
+            AckMessage { .. } => RoutingMessageKind::AckMessage,
+            Relocate(_) => RoutingMessageKind::Relocate,
+        }
+    }
+
+    /// Returns the priority of this message.
+    pub fn priority(&self) -> Priority {
+        use self::MessageContent::*;
+        match self {
+            UserMessage { .. }
+            | UserMessagePart { .. }
+            | EncryptedUserMessage(_)
+            | StreamPart { .. }
+            | UserRequest { .. }
+            | UserResponse { .. }
+            | RelayedClientMessage { .. } => Priority::Bulk,
+            ConnectionRequest { .. }
+            | DeliveryAck(_)
+            | StreamCredit { .. }
+            | Undeliverable { .. }
+            | MessageRejected { .. } => Priority::Normal,
+            NeighbourInfo(_) | Merge(_) | NodeApproval(_) | AckMessage { .. } | Relocate(_) => {
+                Priority::Critical
+            }
+        }
+    }
+
+    /// Returns whether this message carries a message of the user of a node or client, or part of
+    /// it.
+    pub fn is_user_message(&self) -> bool {
+        use self::MessageContent::*;
+        match self {
+            UserMessage { .. }
+            | UserMessagePart { .. }
+            | EncryptedUserMessage(_)
+            | StreamPart { .. }
+            | UserRequest { .. }
+            | UserResponse { .. }
+            | RelayedClientMessage { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Encrypts this user message to the section holding `key`. Encryption is randomised, so the
+    /// same message encrypts differently every time.
+    pub fn encrypt(&self, key: &BlsPublicKey) -> Result<MessageContent> {
+        let bytes = serialise(self)?;
+        let mut rng = RngCompat(utils::new_rng());
+        Ok(MessageContent::EncryptedUserMessage(
+            key.encrypt_with_rng(&mut rng, &bytes)?,
+        ))
+    }
+
+    /// Decrypts the user message of an `EncryptedUserMessage`, if we are one of the elders it was
+    /// encrypted to.
+    pub fn decrypt(ciphertext: &BlsCiphertext, full_id: &FullId) -> Result<MessageContent> {
+        let bytes = ciphertext
+            .decrypt(full_id)
+            .ok_or(RoutingError::InvalidMessage)?;
+        match deserialise(&bytes)? {
+            content @ MessageContent::UserMessage { .. }
+            | content @ MessageContent::UserMessagePart { .. }
+            | content @ MessageContent::StreamPart { .. }
+            | content @ MessageContent::UserRequest { .. }
+            | content @ MessageContent::UserResponse { .. } => Ok(content),
+            _ => Err(RoutingError::InvalidMessage),
+        }
+    }
+}
+
 impl Debug for HopMessage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
             formatter,
-            "HopMessage {{ content: {:?}, signature: .. }}",
-            self.content
+            "HopMessage {{ content: {:?}, ttl: {} }}",
+            self.content, self.ttl
         )
     }
 }
@@ -563,8 +942,8 @@ impl Debug for SignedRoutingMessage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
             formatter,
-            "SignedRoutingMessage {{ content: {:?}, security_metadata: {:?} }}",
-            self.content, self.security_metadata
+            "SignedRoutingMessage {{ content: {:?}, security_metadata: {:?}, trace_id: {:?} }}",
+            self.content, self.security_metadata, self.trace_id
         )
     }
 }
@@ -580,7 +959,62 @@ impl Debug for MessageContent {
             ),
             NeighbourInfo(info) => write!(formatter, "NeighbourInfo({:?})", info),
             Merge(digest) => write!(formatter, "Merge({:.14?})", HexFmt(digest)),
-            UserMessage(content) => write!(formatter, "UserMessage({:?})", content,),
+            UserMessage { content, msg_id } => {
+                write!(formatter, "UserMessage({:?}, {:?})", msg_id, content)
+            }
+            UserMessagePart {
+                digest,
+                index,
+                part_count,
+                ..
+            } => write!(
+                formatter,
+                "UserMessagePart({:.14?}, {}/{})",
+                HexFmt(digest),
+                index + 1,
+                part_count
+            ),
+            EncryptedUserMessage(_) => write!(formatter, "EncryptedUserMessage(..)"),
+            DeliveryAck(msg_id) => write!(formatter, "DeliveryAck({:?})", msg_id),
+            StreamPart {
+                stream_id,
+                index,
+                part_count,
+                ..
+            } => write!(
+                formatter,
+                "StreamPart({:?}, {}/{})",
+                stream_id,
+                index + 1,
+                part_count
+            ),
+            StreamCredit { stream_id, credit } => {
+                write!(formatter, "StreamCredit({:?}, {})", stream_id, credit)
+            }
+            UserRequest { token, content } => {
+                write!(formatter, "UserRequest({:?}, {:?})", token, content)
+            }
+            UserResponse { token, content } => {
+                write!(formatter, "UserResponse({:?}, {:?})", token, content)
+            }
+            MessageRejected { msg_id, reason } => {
+                write!(formatter, "MessageRejected({:?}, {:?})", msg_id, reason)
+            }
+            Undeliverable { digest, dst } => write!(
+                formatter,
+                "Undeliverable({:.14?}, {:?})",
+                HexFmt(digest),
+                dst
+            ),
+            RelayedClientMessage {
+                client,
+                content,
+                msg_id,
+            } => write!(
+                formatter,
+                "RelayedClientMessage({:?}, {:?}, {:?})",
+                msg_id, client, content
+            ),
             NodeApproval(gen_info) => write!(formatter, "NodeApproval({:?})", gen_info),
             AckMessage {
                 src_prefix,
@@ -621,7 +1055,10 @@ mod tests {
         let msg = RoutingMessage {
             src: Authority::Node(rand::random()),
             dst: Authority::Section(rand::random()),
-            content: MessageContent::UserMessage(vec![0, 1, 2, 3, 4]),
+            content: MessageContent::UserMessage {
+                content: vec![0, 1, 2, 3, 4],
+                msg_id: MessageId::new(),
+            },
         };
         let mut signed_msg = unwrap!(SignedRoutingMessage::new(
             msg.clone(),
@@ -644,6 +1081,97 @@ mod tests {
         assert!(signed_msg.check_integrity().is_err());
     }
 
+    #[test]
+    fn single_source_to_many_check_integrity() {
+        let full_id = FullId::new();
+        let src = Authority::Node(*full_id.public_id().name());
+        let dsts = vec![
+            Authority::Section(rand::random()),
+            Authority::Node(rand::random()),
+        ];
+        let content = MessageContent::UserMessage {
+            content: vec![0, 1, 2, 3, 4],
+            msg_id: MessageId::new(),
+        };
+
+        let signed_msgs = unwrap!(SignedRoutingMessage::single_source_to_many(
+            src,
+            dsts.clone(),
+            content.clone(),
+            &full_id,
+        ));
+
+        assert_eq!(dsts.len(), signed_msgs.len());
+        for (signed_msg, dst) in signed_msgs.iter().zip(&dsts) {
+            assert_eq!(*dst, signed_msg.routing_message().dst);
+            assert_eq!(content, signed_msg.routing_message().content);
+            unwrap!(signed_msg.check_integrity());
+        }
+
+        // The signature doesn't cover a destination it wasn't made for.
+        let mut signed_msg = signed_msgs[0].clone();
+        signed_msg.content.dst = Authority::Section(rand::random());
+        assert!(signed_msg.check_integrity().is_err());
+    }
+
+    #[test]
+    fn single_source_freshness() {
+        let full_id = FullId::new();
+        let msg = RoutingMessage {
+            src: Authority::Node(*full_id.public_id().name()),
+            dst: Authority::Section(rand::random()),
+            content: MessageContent::UserMessage {
+                content: vec![0, 1, 2, 3, 4],
+                msg_id: MessageId::new(),
+            },
+        };
+        let mut signed_msg = unwrap!(SignedRoutingMessage::single_source(msg, &full_id));
+        let mut metadata = match signed_msg.security_metadata {
+            SecurityMetadata::Single(ref metadata) => metadata.clone(),
+            ref metadata => panic!("Unexpected {:?}", metadata),
+        };
+        let sent_at = metadata.sent_at;
+        let max_skew = Duration::from_secs(60);
+        let max_skew_ms = utils::duration_ms(max_skew);
+
+        assert!(metadata.is_fresh(max_skew));
+        metadata.sent_at = sent_at - 2 * max_skew_ms;
+        assert!(!metadata.is_fresh(max_skew));
+        metadata.sent_at = sent_at + 2 * max_skew_ms;
+        assert!(!metadata.is_fresh(max_skew));
+
+        // The time it was sent can't be changed without invalidating the signature.
+        metadata.sent_at = sent_at + 1;
+        signed_msg.security_metadata = SecurityMetadata::Single(metadata);
+        assert!(signed_msg.check_integrity().is_err());
+    }
+
+    #[test]
+    fn signed_direct_message_recipient() {
+        let full_id = FullId::new();
+        let dst_id = *FullId::new().public_id();
+        let other_id = *FullId::new().public_id();
+        let new_msg = |dst_id| {
+            unwrap!(SignedDirectMessage::new(
+                DirectMessage::ParsecPoke(0),
+                &full_id,
+                dst_id,
+                1
+            ))
+        };
+
+        let (_, src_id, conn_nonce) = unwrap!(new_msg(Some(dst_id)).open(&dst_id));
+        assert_eq!(src_id, *full_id.public_id());
+        assert_eq!(conn_nonce, 1);
+
+        // It can't be replayed to another peer, unless the sender didn't know the recipient.
+        match new_msg(Some(dst_id)).open(&other_id) {
+            Err(RoutingError::InvalidDestination) => (),
+            result => panic!("Unexpected {:?}", result.map(|(_, src_id, _)| src_id)),
+        }
+        assert!(new_msg(None).open(&other_id).is_ok());
+    }
+
     #[test]
     fn signed_routing_message_signatures() {
         let full_id_0 = FullId::new();
@@ -656,7 +1184,10 @@ mod tests {
         let msg = RoutingMessage {
             src: Authority::Section(name),
             dst: Authority::Section(name),
-            content: MessageContent::UserMessage(content),
+            content: MessageContent::UserMessage {
+                content,
+                msg_id: MessageId::new(),
+            },
         };
 
         let src_section_nodes = vec![
@@ -701,4 +1232,86 @@ mod tests {
             .expect("no signatures")
             .contains_key(&BlsPublicKeyShare(*full_id_3.public_id())));
     }
+
+    #[test]
+    fn verify_message_with_proof_chain() {
+        let full_ids: Vec<_> = (0..4).map(|_| FullId::new()).collect();
+        let prefix = Prefix::new(0, *full_ids[0].public_id().name());
+        let pub_ids = full_ids
+            .iter()
+            .map(|full_id| *full_id.public_id())
+            .collect();
+        let elders_info = unwrap!(EldersInfo::new(pub_ids, prefix, None));
+        let proof_chain =
+            SectionProofChain::from_genesis(SectionKeyInfo::from_elders_info(&elders_info));
+
+        let name: XorName = rand::random();
+        let msg = RoutingMessage {
+            src: Authority::Section(name),
+            dst: Authority::Section(name),
+            content: MessageContent::UserMessage {
+                content: vec![1, 2, 3],
+                msg_id: MessageId::new(),
+            },
+        };
+        let signed_bytes = unwrap!(serialise(&msg));
+        let pk_set = BlsPublicKeySet::from_elders_info(elders_info);
+        let mut signed_msg = unwrap!(SignedRoutingMessage::new(
+            msg,
+            &full_ids[0],
+            pk_set,
+            proof_chain.clone()
+        ));
+        for full_id in &full_ids[1..] {
+            let sig = full_id.sign(&signed_bytes);
+            signed_msg.add_signature_share(BlsPublicKeyShare(*full_id.public_id()), sig);
+        }
+        assert!(signed_msg.check_fully_signed());
+        signed_msg.combine_signatures();
+        unwrap!(proof_chain.verify_message(&signed_msg));
+
+        // A chain of another section's keys doesn't prove the message.
+        let other_ids = (0..4).map(|_| *FullId::new().public_id()).collect();
+        let other_info = unwrap!(EldersInfo::new(other_ids, prefix, None));
+        let other_chain =
+            SectionProofChain::from_genesis(SectionKeyInfo::from_elders_info(&other_info));
+        match other_chain.verify_message(&signed_msg) {
+            Err(RoutingError::UntrustedMessage) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn encrypted_user_message() {
+        let full_ids: Vec<_> = (0..4).map(|_| FullId::new()).collect();
+        let prefix = Prefix::new(0, *full_ids[0].public_id().name());
+        let pub_ids = full_ids
+            .iter()
+            .map(|full_id| *full_id.public_id())
+            .collect();
+        let elders_info = unwrap!(EldersInfo::new(pub_ids, prefix, None));
+        let key_info = SectionKeyInfo::from_elders_info(&elders_info);
+
+        let content = MessageContent::UserMessage {
+            content: vec![1, 2, 3],
+            msg_id: MessageId::new(),
+        };
+        let encrypted = unwrap!(content.encrypt(key_info.key()));
+        assert_ne!(unwrap!(content.encrypt(key_info.key())), encrypted);
+
+        let ciphertext = match encrypted {
+            MessageContent::EncryptedUserMessage(ciphertext) => ciphertext,
+            content => panic!("Unexpected {:?}", content),
+        };
+        for full_id in &full_ids {
+            assert_eq!(
+                unwrap!(MessageContent::decrypt(&ciphertext, full_id)),
+                content
+            );
+        }
+        match MessageContent::decrypt(&ciphertext, &FullId::new()) {
+            Err(RoutingError::InvalidMessage) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
 }
@@ -52,6 +52,7 @@ pub mod signing {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
     pub struct SecretKey([u8; SECRET_KEY_LENGTH]);
 
     impl SecretKey {
@@ -165,6 +166,7 @@ pub mod encryption {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
     pub struct SecretKey([u8; KEY_LENGTH]);
 
     impl SecretKey {
@@ -183,6 +185,10 @@ pub mod encryption {
         }
     }
 
+    /// Mirrors `threshold_crypto::serde_impl::SerdeSecret`.
+    #[derive(Serialize, Deserialize)]
+    pub struct SerdeSecret<T>(pub T);
+
     impl Distribution<SecretKey> for Standard {
         fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> SecretKey {
             SecretKey(rng.gen())
@@ -17,4 +17,4 @@ pub(crate) mod quic_p2p;
 #[cfg(feature = "mock_crypto")]
 pub(crate) mod crypto;
 
-pub use self::quic_p2p::Network;
+pub use self::quic_p2p::{LoggedMessage, MessageLog, Network};
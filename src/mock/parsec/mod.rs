@@ -205,6 +205,12 @@ where
         }) || self.our_unpolled_observations().next().is_some()
     }
 
+    pub fn unpolled_block_count(&self) -> usize {
+        state::with::<T, S::PublicId, _, _>(self.section_hash, |state| {
+            state.block_count().saturating_sub(self.first_unpolled)
+        })
+    }
+
     pub fn our_unpolled_observations(&self) -> impl Iterator<Item = &Observation<T, S::PublicId>> {
         self.observations
             .iter()
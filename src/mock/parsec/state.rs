@@ -89,6 +89,10 @@ impl<T: NetworkEvent, P: PublicId> SectionState<T, P> {
         Some((block, holder))
     }
 
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
     pub fn has_unconsensused_observations(&self, peer_list: &BTreeSet<P>) -> bool {
         self.unconsensused_observations
             .iter()
@@ -0,0 +1,149 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    messages::{DirectMessage, Message, SignedRoutingMessage},
+    states::common::from_network_bytes,
+    NetworkBytes,
+};
+use itertools::Itertools;
+use std::{collections::BTreeMap, fmt::Write, net::SocketAddr};
+
+/// A message delivered by the mock network.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LoggedMessage {
+    /// Address of the sender.
+    pub src: SocketAddr,
+    /// Address of the recipient.
+    pub dst: SocketAddr,
+    /// Short description of the message, e.g. `Hop(UserMessage)` or `Direct(ParsecPoke)`.
+    pub kind: String,
+    /// Version of the last section key in the proof chain of the message, if it has one.
+    pub chain_version: Option<u64>,
+}
+
+impl LoggedMessage {
+    pub(super) fn new(src: SocketAddr, dst: SocketAddr, msg: &NetworkBytes) -> Self {
+        let (kind, chain_version) = match from_network_bytes(msg.clone(), true) {
+            Ok(Message::Direct(msg)) => match msg.content() {
+                DirectMessage::MessageSignature(msg) => (
+                    format!(
+                        "Direct(MessageSignature({:?}))",
+                        msg.routing_message().content.kind()
+                    ),
+                    chain_version(msg),
+                ),
+                content => (format!("Direct({:?})", content.kind()), None),
+            },
+            Ok(Message::Hop(msg)) => (
+                format!("Hop({:?})", msg.content.routing_message().content.kind()),
+                chain_version(&msg.content),
+            ),
+            Ok(Message::Hops(msgs)) => (
+                format!(
+                    "Hops({:?})",
+                    msgs.iter()
+                        .map(|msg| msg.content.routing_message().content.kind())
+                        .format(", ")
+                ),
+                msgs.first().and_then(|msg| chain_version(&msg.content)),
+            ),
+            Err(_) => ("Unknown".to_string(), None),
+        };
+
+        Self {
+            src,
+            dst,
+            kind,
+            chain_version,
+        }
+    }
+
+    fn label(&self) -> String {
+        if let Some(version) = self.chain_version {
+            format!("{} v{}", self.kind, version)
+        } else {
+            self.kind.clone()
+        }
+    }
+}
+
+/// Log of the messages delivered by the mock network, in delivery order. Enable it with
+/// `Network::set_message_log` and export it as a sequence diagram to see which node sent what to
+/// whom when debugging a failed test.
+#[derive(Clone, Debug, Default)]
+pub struct MessageLog(Vec<LoggedMessage>);
+
+impl MessageLog {
+    pub(super) fn push(&mut self, msg: LoggedMessage) {
+        self.0.push(msg)
+    }
+
+    /// Returns the logged messages.
+    pub fn messages(&self) -> &[LoggedMessage] {
+        &self.0
+    }
+
+    /// Returns the log as a mermaid sequence diagram.
+    pub fn to_mermaid(&self) -> String {
+        let participants = self.participants();
+        let mut output = "sequenceDiagram\n".to_string();
+
+        for (addr, index) in &participants {
+            let _ = writeln!(output, "    participant n{} as {}", index, addr);
+        }
+
+        for msg in &self.0 {
+            let _ = writeln!(
+                output,
+                "    n{}->>n{}: {}",
+                participants[&msg.src],
+                participants[&msg.dst],
+                msg.label()
+            );
+        }
+
+        output
+    }
+
+    /// Returns the log as a graphviz digraph. Each edge is a single message, labelled with its
+    /// position in the log.
+    pub fn to_graphviz(&self) -> String {
+        let mut output = "digraph messages {\n".to_string();
+
+        for (index, msg) in self.0.iter().enumerate() {
+            let _ = writeln!(
+                output,
+                "    \"{}\" -> \"{}\" [label=\"{}: {}\"];",
+                msg.src,
+                msg.dst,
+                index + 1,
+                msg.label()
+            );
+        }
+
+        output.push_str("}\n");
+        output
+    }
+
+    // Numbers the participants in the order they first appear in the log.
+    fn participants(&self) -> BTreeMap<SocketAddr, usize> {
+        let mut participants = BTreeMap::new();
+        for msg in &self.0 {
+            for addr in &[msg.src, msg.dst] {
+                let next_index = participants.len();
+                let _ = participants.entry(*addr).or_insert(next_index);
+            }
+        }
+        participants
+    }
+}
+
+fn chain_version(msg: &SignedRoutingMessage) -> Option<u64> {
+    msg.source_section_key_info().map(|info| *info.version())
+}
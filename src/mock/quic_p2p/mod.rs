@@ -6,24 +6,28 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod message_log;
 mod network;
 mod node;
 #[cfg(test)]
 mod tests;
 
-pub use self::network::Network;
+pub use self::{
+    message_log::{LoggedMessage, MessageLog},
+    network::Network,
+};
 pub use quic_p2p::Token;
 
 use self::node::Node;
 use crate::NetworkBytes;
 use crossbeam_channel::Sender;
 use std::{
-    cell::RefCell,
     collections::HashSet,
     iter,
     net::{IpAddr, SocketAddr},
-    rc::Rc,
+    sync::{Arc, Mutex},
 };
+use unwrap::unwrap;
 
 /// Builder for `QuickP2p`.
 pub struct Builder {
@@ -58,7 +62,7 @@ impl Builder {
 
 /// Main QuicP2p interface.
 pub struct QuicP2p {
-    inner: Rc<RefCell<Node>>,
+    inner: Arc<Mutex<Node>>,
 }
 
 impl QuicP2p {
@@ -70,18 +74,18 @@ impl QuicP2p {
     ///
     /// In case of success `Event::BootstrapedTo` will be fired. On error quic-p2p will fire `Event::BootstrapFailure`.
     pub fn bootstrap(&mut self) {
-        self.inner.borrow_mut().bootstrap()
+        unwrap!(self.inner.lock()).bootstrap()
     }
 
     /// Connect to the given peer. This will error out if the peer is already in the process of
     /// being connected to OR for any other connection failure reasons.
     pub fn connect_to(&mut self, peer_info: NodeInfo) {
-        self.inner.borrow().connect(peer_info.peer_addr);
+        unwrap!(self.inner.lock()).connect(peer_info.peer_addr);
     }
 
     /// Disconnect from the given peer
     pub fn disconnect_from(&mut self, peer_addr: SocketAddr) {
-        self.inner.borrow_mut().disconnect(peer_addr)
+        unwrap!(self.inner.lock()).disconnect(peer_addr)
     }
 
     /// Send message to peer.
@@ -90,17 +94,17 @@ impl QuicP2p {
     /// and then send the message. This can be called multiple times while the peer is still being
     /// connected to - all the sends will be buffered until the peer is connected to.
     pub fn send(&mut self, peer: Peer, msg: NetworkBytes, token: Token) {
-        self.inner.borrow_mut().send(peer.peer_addr(), msg, token)
+        unwrap!(self.inner.lock()).send(peer.peer_addr(), msg, token)
     }
 
     /// Get our connection info to give to others for them to connect to us
     pub fn our_connection_info(&mut self) -> Result<NodeInfo, Error> {
-        self.inner.borrow().our_connection_info()
+        unwrap!(self.inner.lock()).our_connection_info()
     }
 
     /// Retrieves current node bootstrap cache.
     pub fn bootstrap_cache(&mut self) -> Result<Vec<NodeInfo>, Error> {
-        Ok(self.inner.borrow().bootstrap_cache())
+        Ok(unwrap!(self.inner.lock()).bootstrap_cache())
     }
 
     /// Check whether the given contact is hard-coded (always `true` in mock).
@@ -110,7 +114,7 @@ impl QuicP2p {
 
     /// Returns the config used to create this instance.
     pub fn config(&self) -> Config {
-        self.inner.borrow().config().clone()
+        unwrap!(self.inner.lock()).config().clone()
     }
 
     fn new(event_tx: Sender<Event>, config: Config) -> Self {
@@ -123,11 +127,11 @@ impl QuicP2p {
 #[cfg(test)]
 impl QuicP2p {
     fn addr(&self) -> SocketAddr {
-        *self.inner.borrow().addr()
+        *unwrap!(self.inner.lock()).addr()
     }
 
     fn our_type(&self) -> OurType {
-        self.inner.borrow().our_type()
+        unwrap!(self.inner.lock()).our_type()
     }
 }
 
@@ -142,6 +146,14 @@ pub struct Config {
     pub ip: Option<IpAddr>,
     /// IP address to listen to.
     pub port: Option<u16>,
+    /// Maximum size (in bytes) of a message we accept from a client. Larger messages are
+    /// rejected. No limit if `None`.
+    pub max_client_msg_size: Option<usize>,
+    /// Maximum number of messages per second we accept from a single client. Messages over the
+    /// limit are rejected. No limit if `None`.
+    pub max_client_msgs_per_sec: Option<u32>,
+    /// Directory to keep the bootstrap cache in. The default directory is used if `None`.
+    pub bootstrap_cache_dir: Option<String>,
 }
 
 impl Config {
@@ -189,6 +201,22 @@ impl Config {
             ..self
         }
     }
+
+    /// Set the `max_client_msg_size`.
+    pub fn with_max_client_msg_size(self, size: usize) -> Self {
+        Self {
+            max_client_msg_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Set the `max_client_msgs_per_sec`.
+    pub fn with_max_client_msgs_per_sec(self, rate: u32) -> Self {
+        Self {
+            max_client_msgs_per_sec: Some(rate),
+            ..self
+        }
+    }
 }
 
 /// The type of our `QuicP2p` instance: client or node.
@@ -6,23 +6,31 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{node::Node, OurType};
+use super::{
+    message_log::{LoggedMessage, MessageLog},
+    node::Node,
+    OurType,
+};
+#[cfg(not(feature = "mock_serialise"))]
+use crate::envelope;
 #[cfg(feature = "mock_parsec")]
 use crate::mock::parsec;
 use crate::NetworkBytes;
 use fxhash::{FxHashMap, FxHashSet};
+use itertools::Itertools;
 use maidsafe_utilities::SeededRng;
 use rand::Rng;
 use std::{
     cell::RefCell,
     cmp,
-    collections::{hash_map::Entry, VecDeque},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, VecDeque},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-    rc::{Rc, Weak},
-    sync::Once,
+    sync::{Arc, Mutex, Once, Weak},
 };
+use unwrap::unwrap;
 
 const IP_BASE: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const IP_V6_BASE: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
 const PORT: u16 = 9999;
 
 static PRINT_SEED: Once = Once::new();
@@ -30,8 +38,10 @@ static PRINT_SEED: Once = Once::new();
 /// Handle to the mock network. Create one before testing with mocks. Call `set_next_node_addr` or
 /// `gen_next_node_addr` before creating a `QuicP2p` instance.
 /// This handle is cheap to clone. Each clone refers to the same underlying mock network instance.
+/// Without the `mock_serialise` feature, the handle can also be sent to other threads (see
+/// `make_current`).
 #[derive(Clone)]
-pub struct Network(Rc<RefCell<Inner>>);
+pub struct Network(Arc<Mutex<Inner>>);
 
 impl Network {
     /// Construct new mock network.
@@ -47,23 +57,57 @@ impl Network {
         #[cfg(feature = "mock_parsec")]
         parsec::init_mock();
 
-        let inner = Rc::new(RefCell::new(Inner {
+        let inner = Arc::new(Mutex::new(Inner {
             min_section_size,
             rng,
             nodes: Default::default(),
             connections: Default::default(),
             used_ips: Default::default(),
             message_sent: false,
+            duplicate_one_in: None,
+            ipv6: false,
+            message_log: None,
         }));
 
-        NETWORK.with(|network| *network.borrow_mut() = Some(inner.clone()));
+        let network = Network(inner);
+        network.make_current();
+        network
+    }
 
-        Network(inner)
+    /// Make this the network the `QuicP2p` instances created on the current thread connect to.
+    /// `Network::new` does this for the thread it is called on, so this is only needed when
+    /// creating instances on other threads.
+    pub fn make_current(&self) {
+        NETWORK.with(|network| *network.borrow_mut() = Some(Arc::clone(&self.0)));
     }
 
     /// Generate new unique socket addrs.
     pub fn gen_addr(&self) -> SocketAddr {
-        self.0.borrow_mut().gen_addr(None, None)
+        unwrap!(self.0.lock()).gen_addr(None, None)
+    }
+
+    /// Switch between generating IPv4 (the default) and IPv6 addresses, for `gen_addr` and for
+    /// the `QuicP2p` instances created without an explicit IP. Both kinds of addresses can be used
+    /// in the same network.
+    pub fn set_ipv6(&self, ipv6: bool) {
+        unwrap!(self.0.lock()).ipv6 = ipv6;
+    }
+
+    /// Start or stop recording the delivered messages. Starting clears any previous log.
+    pub fn set_message_log(&self, enabled: bool) {
+        unwrap!(self.0.lock()).message_log = if enabled {
+            Some(MessageLog::default())
+        } else {
+            None
+        };
+    }
+
+    /// Returns the messages delivered since recording started (empty if it isn't enabled).
+    pub fn message_log(&self) -> MessageLog {
+        unwrap!(self.0.lock())
+            .message_log
+            .clone()
+            .unwrap_or_default()
     }
 
     /// Poll the network by delivering the queued messages.
@@ -75,48 +119,130 @@ impl Network {
 
     /// Disconnect peer at `addr0` from the peer at `addr1`.
     pub fn disconnect(&self, addr0: &SocketAddr, addr1: &SocketAddr) {
-        let node = self.0.borrow().find_node(addr0);
+        let node = unwrap!(self.0.lock()).find_node(addr0);
+        if let Some(node) = node {
+            unwrap!(node.lock()).disconnect(*addr1)
+        }
+    }
+
+    /// Abruptly cut all the connections of the peer at `addr`, as if its network interface went
+    /// down. Both that peer and all the peers it was connected to see the connections fail.
+    pub fn disconnect_all(&self, addr: &SocketAddr) {
+        let node = unwrap!(self.0.lock()).find_node(addr);
         if let Some(node) = node {
-            node.borrow_mut().disconnect(*addr1)
+            unwrap!(node.lock()).disconnect_all()
         }
     }
 
+    /// Switch the duplicate and reorder delivery mode on (`Some(one_in)`) or off (`None`). When on,
+    /// each delivered message is, with the probability of `1 / one_in`, delivered once more at a
+    /// random later point, possibly after messages sent over the same connection after it. Use
+    /// this to check that message handling is idempotent and tolerant to reordering.
+    pub fn set_duplicate_delivery(&self, one_in: Option<u32>) {
+        unwrap!(self.0.lock()).duplicate_one_in = one_in;
+    }
+
     /// Is the peer at `addr0` connected to the one at `addr1`?
     pub fn is_connected(&self, addr0: &SocketAddr, addr1: &SocketAddr) -> bool {
-        self.0.borrow().is_connected(addr0, addr1)
+        self.find_node(addr0)
+            .map(|node| unwrap!(node.lock()).is_connected(addr1))
+            .unwrap_or(false)
+    }
+
+    /// Returns all the connections in the network, as pairs `(addr0, addr1)` where the peer at
+    /// `addr0` is connected to the one at `addr1`. Both directions are normally present, unless a
+    /// connection is half-way established or closed.
+    pub fn connections(&self) -> BTreeSet<(SocketAddr, SocketAddr)> {
+        self.nodes()
+            .into_iter()
+            .flat_map(|(addr, node)| {
+                unwrap!(node.lock())
+                    .peer_addrs()
+                    .map(|peer_addr| (addr, peer_addr))
+                    .collect_vec()
+            })
+            .collect()
+    }
+
+    /// Returns the number of peers the peer at `addr` is connected to.
+    pub fn degree(&self, addr: &SocketAddr) -> usize {
+        self.find_node(addr)
+            .map(|node| unwrap!(node.lock()).peer_addrs().count())
+            .unwrap_or(0)
+    }
+
+    /// Returns the addresses of all the peers reachable from the peer at `addr` over one or more
+    /// connections, in either direction, including `addr` itself.
+    pub fn connected_component(&self, addr: &SocketAddr) -> BTreeSet<SocketAddr> {
+        let mut neighbours: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (addr0, addr1) in self.connections() {
+            neighbours.entry(addr0).or_default().push(addr1);
+            neighbours.entry(addr1).or_default().push(addr0);
+        }
+
+        let mut component = BTreeSet::new();
+        let mut pending = vec![*addr];
+        while let Some(addr) = pending.pop() {
+            if component.insert(addr) {
+                pending.extend(neighbours.remove(&addr).into_iter().flatten());
+            }
+        }
+        component
+    }
+
+    /// Are there any messages sent but not yet delivered?
+    pub fn has_messages_in_flight(&self) -> bool {
+        unwrap!(self.0.lock())
+            .connections
+            .values()
+            .any(|queue| !queue.is_empty())
     }
 
     /// Get min section size.
     pub fn min_section_size(&self) -> usize {
-        self.0.borrow().min_section_size
+        unwrap!(self.0.lock()).min_section_size
     }
 
     /// Construct a new random number generator using a seed generated from random data provided by `self`.
     pub fn new_rng(&self) -> SeededRng {
-        self.0.borrow_mut().rng.new_rng()
+        unwrap!(self.0.lock()).rng.new_rng()
     }
 
     /// Return whether sent any message since previous query and reset the flag.
     pub fn reset_message_sent(&self) -> bool {
-        let mut inner = self.0.borrow_mut();
+        let mut inner = unwrap!(self.0.lock());
         let message_sent = inner.message_sent;
         inner.message_sent = false;
         message_sent
     }
 
     fn pop_random_packet(&self) -> Option<(Connection, Packet)> {
-        self.0.borrow_mut().pop_random_packet()
+        unwrap!(self.0.lock()).pop_random_packet()
     }
 
     fn process_packet(&self, connection: &Connection, packet: Packet) {
         let response = if let Some(dst) = self.find_node(&connection.dst) {
-            let msg = if let Packet::Message(ref msg, msg_id) = packet {
-                Some(Packet::MessageSent(msg.clone(), msg_id))
-            } else {
-                None
-            };
-            dst.borrow_mut().receive_packet(connection.src, packet);
-            msg
+            match packet {
+                Packet::Message(msg, msg_id) => {
+                    if unwrap!(dst.lock()).check_client_limits(connection.src, &msg) {
+                        unwrap!(dst.lock())
+                            .receive_packet(connection.src, Packet::Message(msg.clone(), msg_id));
+                        let mut inner = unwrap!(self.0.lock());
+                        inner.log_message(connection, &msg);
+                        inner.maybe_duplicate(*connection, &msg);
+                        Some(Packet::MessageSent(msg, msg_id))
+                    } else {
+                        Some(Packet::MessageFailure(msg, msg_id))
+                    }
+                }
+                packet => {
+                    if let Packet::Duplicate(ref msg) = packet {
+                        unwrap!(self.0.lock()).log_message(connection, msg);
+                    }
+                    unwrap!(dst.lock()).receive_packet(connection.src, packet);
+                    None
+                }
+            }
         } else {
             match packet {
                 Packet::BootstrapRequest(_) => Some(Packet::BootstrapFailure),
@@ -131,34 +257,43 @@ impl Network {
         }
     }
 
-    fn find_node(&self, addr: &SocketAddr) -> Option<Rc<RefCell<Node>>> {
-        self.0.borrow().find_node(addr)
+    fn find_node(&self, addr: &SocketAddr) -> Option<Arc<Mutex<Node>>> {
+        unwrap!(self.0.lock()).find_node(addr)
+    }
+
+    fn nodes(&self) -> Vec<(SocketAddr, Arc<Mutex<Node>>)> {
+        unwrap!(self.0.lock()).nodes()
     }
 
     fn send(&self, src: SocketAddr, dst: SocketAddr, packet: Packet) {
-        self.0.borrow_mut().send(src, dst, packet)
+        unwrap!(self.0.lock()).send(src, dst, packet)
     }
 }
 
 pub(super) struct Inner {
     min_section_size: usize,
     rng: SeededRng,
-    nodes: FxHashMap<SocketAddr, Weak<RefCell<Node>>>,
+    nodes: FxHashMap<SocketAddr, Weak<Mutex<Node>>>,
     connections: FxHashMap<Connection, Queue>,
     used_ips: FxHashSet<IpAddr>,
     message_sent: bool,
+    duplicate_one_in: Option<u32>,
+    ipv6: bool,
+    message_log: Option<MessageLog>,
 }
 
 impl Inner {
     pub fn gen_addr(&mut self, ip: Option<IpAddr>, port: Option<u16>) -> SocketAddr {
         let ip = ip.unwrap_or_else(|| {
+            let ipv6 = self.ipv6;
             self.nodes
                 .keys()
                 .map(|addr| addr.ip())
                 .chain(self.used_ips.iter().cloned())
+                .filter(|ip| ip.is_ipv6() == ipv6)
                 .max()
                 .map(next_ip)
-                .unwrap_or(IP_BASE)
+                .unwrap_or(if ipv6 { IP_V6_BASE } else { IP_BASE })
         });
         let port = port.unwrap_or(PORT);
 
@@ -167,11 +302,11 @@ impl Inner {
         SocketAddr::new(ip, port)
     }
 
-    pub fn insert_node(&mut self, addr: SocketAddr, node: Rc<RefCell<Node>>) {
+    pub fn insert_node(&mut self, addr: SocketAddr, node: Arc<Mutex<Node>>) {
         match self.nodes.entry(addr) {
             Entry::Occupied(_) => panic!("Node with {} already exists", addr),
             Entry::Vacant(entry) => {
-                let _ = entry.insert(Rc::downgrade(&node));
+                let _ = entry.insert(Arc::downgrade(&node));
             }
         }
     }
@@ -197,10 +332,46 @@ impl Inner {
         self.send(src, dst, Packet::Disconnect);
     }
 
-    fn find_node(&self, addr: &SocketAddr) -> Option<Rc<RefCell<Node>>> {
+    fn log_message(&mut self, connection: &Connection, msg: &NetworkBytes) {
+        if let Some(ref mut log) = self.message_log {
+            log.push(LoggedMessage::new(connection.src, connection.dst, msg));
+        }
+    }
+
+    // In the duplicate delivery mode, randomly queue the message for another delivery.
+    fn maybe_duplicate(&mut self, connection: Connection, msg: &NetworkBytes) {
+        let one_in = if let Some(one_in) = self.duplicate_one_in {
+            one_in
+        } else {
+            return;
+        };
+
+        if !self.rng.gen_weighted_bool(one_in) {
+            return;
+        }
+
+        let packet = Packet::Duplicate(msg.clone());
+        if !packet.is_parsec_gossip() {
+            self.message_sent = true;
+        }
+
+        self.connections
+            .entry(connection)
+            .or_insert_with(Queue::new)
+            .insert_random_msg(packet, &mut self.rng)
+    }
+
+    fn find_node(&self, addr: &SocketAddr) -> Option<Arc<Mutex<Node>>> {
         self.nodes.get(addr).and_then(Weak::upgrade)
     }
 
+    fn nodes(&self) -> Vec<(SocketAddr, Arc<Mutex<Node>>)> {
+        self.nodes
+            .iter()
+            .filter_map(|(addr, node)| node.upgrade().map(|node| (*addr, node)))
+            .collect()
+    }
+
     fn pop_random_packet(&mut self) -> Option<(Connection, Packet)> {
         let connections: Vec<_> = self
             .connections
@@ -231,20 +402,14 @@ impl Inner {
             Entry::Vacant(_) => None,
         }
     }
-
-    fn is_connected(&self, addr0: &SocketAddr, addr1: &SocketAddr) -> bool {
-        self.find_node(addr0)
-            .map(|node| node.borrow().is_connected(addr1))
-            .unwrap_or(false)
-    }
 }
 
 // The 4-byte tags of `Message::Direct` and `DirectMessage::ParsecRequest`.
-// A serialised Parsec request message starts with these bytes.
+// A Parsec request message serialised with bincode starts with these bytes, after its envelope.
 #[cfg(not(feature = "mock_serialise"))]
 static PARSEC_REQ_MSG_TAGS: &[u8] = &[0, 0, 0, 0, 6, 0, 0, 0];
 // The 4-byte tags of `Message::Direct` and `DirectMessage::ParsecResponse`.
-// A serialised Parsec response message starts with these bytes.
+// A Parsec response message serialised with bincode starts with these bytes, after its envelope.
 #[cfg(not(feature = "mock_serialise"))]
 static PARSEC_RSP_MSG_TAGS: &[u8] = &[0, 0, 0, 0, 7, 0, 0, 0];
 
@@ -257,6 +422,8 @@ pub(super) enum Packet {
     ConnectSuccess,
     ConnectFailure,
     Message(NetworkBytes, u64),
+    // Extra copy of an already delivered message (only in the duplicate delivery mode).
+    Duplicate(NetworkBytes),
     MessageFailure(NetworkBytes, u64),
     MessageSent(NetworkBytes, u64),
     Disconnect,
@@ -267,8 +434,13 @@ impl Packet {
     #[cfg(not(feature = "mock_serialise"))]
     pub fn is_parsec_gossip(&self) -> bool {
         match self {
-            Packet::Message(bytes, _) if bytes.len() >= 8 => {
-                &bytes[..8] == PARSEC_REQ_MSG_TAGS || &bytes[..8] == PARSEC_RSP_MSG_TAGS
+            Packet::Message(bytes, _) | Packet::Duplicate(bytes) => {
+                match envelope::bincode_payload(bytes) {
+                    Some(bytes) if bytes.len() >= 8 => {
+                        &bytes[..8] == PARSEC_REQ_MSG_TAGS || &bytes[..8] == PARSEC_RSP_MSG_TAGS
+                    }
+                    _ => false,
+                }
             }
             _ => false,
         }
@@ -279,7 +451,7 @@ impl Packet {
         use crate::messages::{DirectMessage, Message};
 
         match self {
-            Packet::Message(ref message, _) => match **message {
+            Packet::Message(ref message, _) | Packet::Duplicate(ref message) => match **message {
                 Message::Direct(ref message) => match message.content() {
                     DirectMessage::ParsecRequest(..) | DirectMessage::ParsecResponse(..) => true,
                     _ => false,
@@ -289,6 +461,13 @@ impl Packet {
             _ => false,
         }
     }
+
+    fn is_message(&self) -> bool {
+        match self {
+            Packet::Message(..) | Packet::Duplicate(_) => true,
+            _ => false,
+        }
+    }
 }
 
 struct Queue(VecDeque<Packet>);
@@ -311,18 +490,24 @@ impl Queue {
         let first_non_msg_packet = self
             .0
             .iter()
-            .position(|packet| {
-                if let Packet::Message(_, _) = packet {
-                    false
-                } else {
-                    true
-                }
-            })
+            .position(|packet| !packet.is_message())
             .unwrap_or(0);
 
         let selected = rng.gen_range(0, cmp::max(first_non_msg_packet, 1));
         self.0.remove(selected)
     }
+
+    // Insert the msg at a random position among the msgs at the front of the queue, so it doesn't
+    // overtake any other kind of packet.
+    fn insert_random_msg(&mut self, packet: Packet, rng: &mut SeededRng) {
+        let msgs = self
+            .0
+            .iter()
+            .take_while(|packet| packet.is_message())
+            .count();
+        let index = rng.gen_range(0, msgs + 1);
+        self.0.insert(index, packet)
+    }
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
@@ -338,7 +523,7 @@ impl Connection {
 }
 
 thread_local! {
-    pub(super) static NETWORK: RefCell<Option<Rc<RefCell<Inner>>>> = RefCell::new(None);
+    pub(super) static NETWORK: RefCell<Option<Arc<Mutex<Inner>>>> = RefCell::new(None);
 }
 
 fn next_ip(ip: IpAddr) -> IpAddr {
@@ -10,17 +10,23 @@ use super::{
     network::{Inner, Packet, NETWORK},
     Config, Error, Event, NodeInfo, OurType, Peer,
 };
-use crate::NetworkBytes;
+use crate::{
+    time::{Duration, Instant},
+    NetworkBytes,
+};
 use crossbeam_channel::Sender;
 // Note: using `FxHashMap` / `FxHashSet` because they don't use random state and thus guarantee
 // consistent iteration order (necessary for repeatable tests). Can't use `BTreeMap` / `BTreeSet`
 // because we key by `SocketAddr` which doesn't implement `Ord`.
 use fxhash::{FxHashMap, FxHashSet};
-use std::{cell::RefCell, net::SocketAddr, rc::Rc};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
 use unwrap::unwrap;
 
 pub(super) struct Node {
-    network: Rc<RefCell<Inner>>,
+    network: Arc<Mutex<Inner>>,
     addr: SocketAddr,
     event_tx: Sender<Event>,
     config: Config,
@@ -28,20 +34,21 @@ pub(super) struct Node {
     bootstrap_cache: FxHashSet<NodeInfo>,
     pending_bootstraps: FxHashSet<SocketAddr>,
     pending_messages: FxHashMap<SocketAddr, Vec<(NetworkBytes, u64)>>,
+    clients: FxHashMap<SocketAddr, ClientRate>,
 }
 
 impl Node {
-    pub fn new(event_tx: Sender<Event>, config: Config) -> Rc<RefCell<Self>> {
+    pub fn new(event_tx: Sender<Event>, config: Config) -> Arc<Mutex<Self>> {
         let network = NETWORK.with(|network| {
-            Rc::clone(unwrap!(
+            Arc::clone(unwrap!(
                 network.borrow().as_ref(),
                 "Mock Network must exist before creating instances of QuicP2p."
             ))
         });
 
-        let addr = network.borrow_mut().gen_addr(config.ip, config.port);
-        let node = Rc::new(RefCell::new(Node {
-            network,
+        let addr = unwrap!(network.lock()).gen_addr(config.ip, config.port);
+        let node = Arc::new(Mutex::new(Node {
+            network: Arc::clone(&network),
             addr,
             event_tx,
             config,
@@ -49,11 +56,9 @@ impl Node {
             bootstrap_cache: Default::default(),
             pending_bootstraps: Default::default(),
             pending_messages: Default::default(),
+            clients: Default::default(),
         }));
-        node.borrow()
-            .network
-            .borrow_mut()
-            .insert_node(addr, Rc::clone(&node));
+        unwrap!(network.lock()).insert_node(addr, Arc::clone(&node));
         node
     }
 
@@ -80,7 +85,7 @@ impl Node {
             .chain(&self.bootstrap_cache)
         {
             let _ = self.pending_bootstraps.insert(contact.peer_addr);
-            self.network.borrow_mut().send(
+            unwrap!(self.network.lock()).send(
                 self.addr,
                 contact.peer_addr,
                 Packet::BootstrapRequest(self.config.our_type),
@@ -99,7 +104,20 @@ impl Node {
 
     pub fn disconnect(&mut self, dst: SocketAddr) {
         if self.peers.remove(&dst).is_some() {
-            self.network.borrow_mut().disconnect(self.addr, dst)
+            let _ = self.clients.remove(&dst);
+            unwrap!(self.network.lock()).disconnect(self.addr, dst)
+        }
+    }
+
+    pub fn disconnect_all(&mut self) {
+        let peer_addrs: Vec<_> = self.peers.drain().map(|(addr, _)| addr).collect();
+        self.clients.clear();
+        for peer_addr in peer_addrs {
+            unwrap!(self.network.lock()).disconnect(self.addr, peer_addr);
+            self.fire_event(Event::ConnectionFailure {
+                peer_addr,
+                err: Error,
+            })
         }
     }
 
@@ -116,9 +134,8 @@ impl Node {
         match packet {
             Packet::BootstrapRequest(peer_type) => {
                 if self.peers.insert(src, ConnectionType::Bootstrap).is_none() {
-                    self.network
-                        .borrow_mut()
-                        .send(self.addr, src, Packet::BootstrapSuccess);
+                    self.add_client(src, peer_type);
+                    unwrap!(self.network.lock()).send(self.addr, src, Packet::BootstrapSuccess);
 
                     self.fire_event(Event::ConnectedTo {
                         peer: Peer::new(peer_type, src),
@@ -139,9 +156,7 @@ impl Node {
                         node: NodeInfo::from(src),
                     })
                 } else {
-                    self.network
-                        .borrow_mut()
-                        .send(self.addr, src, Packet::Disconnect)
+                    unwrap!(self.network.lock()).send(self.addr, src, Packet::Disconnect)
                 }
             }
             Packet::BootstrapFailure => {
@@ -160,9 +175,8 @@ impl Node {
             }
             Packet::ConnectRequest(peer_type) => {
                 if self.peers.insert(src, ConnectionType::Normal).is_none() {
-                    self.network
-                        .borrow_mut()
-                        .send(self.addr, src, Packet::ConnectSuccess);
+                    self.add_client(src, peer_type);
+                    unwrap!(self.network.lock()).send(self.addr, src, Packet::ConnectSuccess);
                     self.send_pending_messages(src);
 
                     self.fire_event(Event::ConnectedTo {
@@ -192,13 +206,21 @@ impl Node {
                         msg,
                     })
                 } else {
-                    self.network.borrow_mut().send(
+                    unwrap!(self.network.lock()).send(
                         self.addr,
                         src,
                         Packet::MessageFailure(msg, token),
                     )
                 }
             }
+            Packet::Duplicate(msg) => {
+                if self.peers.contains_key(&src) {
+                    self.fire_event(Event::NewMessage {
+                        peer_addr: src,
+                        msg,
+                    })
+                }
+            }
             Packet::MessageFailure(msg, token) => self.fire_event(Event::UnsentUserMessage {
                 peer_addr: src,
                 msg,
@@ -211,6 +233,7 @@ impl Node {
             }),
             Packet::Disconnect => {
                 if self.peers.remove(&src).is_some() {
+                    let _ = self.clients.remove(&src);
                     self.fire_event(Event::ConnectionFailure {
                         peer_addr: src,
                         err: Error,
@@ -220,6 +243,36 @@ impl Node {
         }
     }
 
+    // Check the message from `src` against our client limits (if `src` is a client). Returns
+    // whether the message should be accepted.
+    pub fn check_client_limits(&mut self, src: SocketAddr, msg: &NetworkBytes) -> bool {
+        let rate = if let Some(rate) = self.clients.get_mut(&src) {
+            rate
+        } else {
+            return true;
+        };
+
+        if let Some(max_size) = self.config.max_client_msg_size {
+            if msg_size(msg) > max_size {
+                return false;
+            }
+        }
+
+        if let Some(max_msgs) = self.config.max_client_msgs_per_sec {
+            if rate.window_start.elapsed() >= Duration::from_secs(1) {
+                rate.window_start = Instant::now();
+                rate.count = 0;
+            }
+
+            if rate.count >= max_msgs {
+                return false;
+            }
+        }
+
+        rate.count += 1;
+        true
+    }
+
     pub fn our_connection_info(&self) -> Result<NodeInfo, Error> {
         match self.config.our_type {
             OurType::Client => Err(Error),
@@ -235,6 +288,10 @@ impl Node {
         self.peers.get(addr).is_some()
     }
 
+    pub fn peer_addrs<'a>(&'a self) -> impl Iterator<Item = SocketAddr> + 'a {
+        self.peers.keys().cloned()
+    }
+
     pub fn config(&self) -> &Config {
         &self.config
     }
@@ -243,16 +300,28 @@ impl Node {
         let _ = self.event_tx.send(event);
     }
 
+    fn add_client(&mut self, addr: SocketAddr, peer_type: OurType) {
+        if peer_type == OurType::Client {
+            let _ = self.clients.insert(
+                addr,
+                ClientRate {
+                    window_start: Instant::now(),
+                    count: 0,
+                },
+            );
+        }
+    }
+
     fn send_connect_request(&self, dst: SocketAddr) {
-        self.network
-            .borrow_mut()
-            .send(self.addr, dst, Packet::ConnectRequest(self.config.our_type))
+        unwrap!(self.network.lock()).send(
+            self.addr,
+            dst,
+            Packet::ConnectRequest(self.config.our_type),
+        )
     }
 
     fn send_message(&self, dst: SocketAddr, msg: NetworkBytes, token: u64) {
-        self.network
-            .borrow_mut()
-            .send(self.addr, dst, Packet::Message(msg, token))
+        unwrap!(self.network.lock()).send(self.addr, dst, Packet::Message(msg, token))
     }
 
     fn add_pending_message(&mut self, addr: SocketAddr, msg: NetworkBytes, token: u64) {
@@ -289,14 +358,30 @@ impl Node {
 impl Drop for Node {
     fn drop(&mut self) {
         for (dst, _) in self.peers.drain() {
-            self.network.borrow_mut().disconnect(self.addr, dst)
+            unwrap!(self.network.lock()).disconnect(self.addr, dst)
         }
 
-        self.network.borrow_mut().remove_node(&self.addr);
+        unwrap!(self.network.lock()).remove_node(&self.addr);
         self.fire_event(Event::Finish)
     }
 }
 
+// Number of messages received from a client within the current one second window.
+struct ClientRate {
+    window_start: Instant,
+    count: u32,
+}
+
+#[cfg(not(feature = "mock_serialise"))]
+fn msg_size(msg: &NetworkBytes) -> usize {
+    msg.len()
+}
+
+#[cfg(feature = "mock_serialise")]
+fn msg_size(msg: &NetworkBytes) -> usize {
+    maidsafe_utilities::serialisation::serialised_size(&**msg) as usize
+}
+
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum ConnectionType {
     // Connection established via `connect_to`.
@@ -10,7 +10,7 @@ use super::{Builder, Config, Event, Network, NodeInfo, OurType, Peer, QuicP2p};
 use crate::NetworkBytes;
 use crossbeam_channel::{self as mpmc, Receiver, TryRecvError};
 use fxhash::FxHashSet;
-use std::{iter, net::SocketAddr};
+use std::{collections::BTreeSet, iter, net::SocketAddr};
 use unwrap::unwrap;
 
 const MIN_SECTION_SIZE: usize = 4;
@@ -344,6 +344,70 @@ fn our_connection_info_of_client() {
     assert!(client.our_connection_info().is_err())
 }
 
+#[test]
+fn gen_ipv6_addr() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let v4_addr = network.gen_addr();
+    assert!(v4_addr.is_ipv4());
+
+    network.set_ipv6(true);
+    let v6_addr0 = network.gen_addr();
+    let v6_addr1 = network.gen_addr();
+    assert!(v6_addr0.is_ipv6());
+    assert!(v6_addr1.is_ipv6());
+    assert_ne!(v6_addr0, v6_addr1);
+
+    network.set_ipv6(false);
+    let v4_addr1 = network.gen_addr();
+    assert!(v4_addr1.is_ipv4());
+    assert_ne!(v4_addr, v4_addr1);
+}
+
+#[test]
+fn our_connection_info_of_ipv6_node() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    network.set_ipv6(true);
+
+    let mut a = Agent::node();
+    let node_info = unwrap!(a.inner.our_connection_info());
+    assert!(node_info.peer_addr.is_ipv6());
+    assert_eq!(node_info.peer_addr, a.addr());
+}
+
+#[test]
+fn connect_and_send_between_address_families() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let mut a = Agent::node();
+    network.set_ipv6(true);
+    let mut b = Agent::node();
+    let mut c = Agent::bootstrapped_client(&network, b.addr());
+
+    assert!(a.addr().is_ipv4());
+    assert!(b.addr().is_ipv6());
+    assert!(c.addr().is_ipv6());
+    b.expect_connected_to_client(&c.addr());
+
+    establish_connection(&network, &mut a, &mut b);
+    assert!(unwrap!(a.inner.bootstrap_cache()).contains(&NodeInfo::from(b.addr())));
+
+    let msg = gen_message();
+    a.send(b.addr(), msg.clone(), 0);
+    network.poll();
+    a.expect_sent_message(&b.addr(), &msg, 0);
+    b.expect_new_message(&a.addr(), &msg);
+
+    b.send(a.addr(), msg.clone(), 1);
+    network.poll();
+    b.expect_sent_message(&a.addr(), &msg, 1);
+    a.expect_new_message(&b.addr(), &msg);
+
+    c.send(b.addr(), msg.clone(), 2);
+    network.poll();
+    c.expect_sent_message(&b.addr(), &msg, 2);
+    b.expect_new_message(&c.addr(), &msg);
+}
+
 #[test]
 fn bootstrap_cache() {
     let network = Network::new(MIN_SECTION_SIZE, None);
@@ -380,6 +444,212 @@ fn drop_disconnects() {
     b.expect_connection_failure(&a_addr);
 }
 
+#[test]
+fn connectivity_graph() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let mut a = Agent::node();
+    let mut b = Agent::node();
+    let mut c = Agent::node();
+    let d = Agent::node();
+
+    establish_connection(&network, &mut a, &mut b);
+    establish_connection(&network, &mut b, &mut c);
+
+    let expected_connections = vec![
+        (a.addr(), b.addr()),
+        (b.addr(), a.addr()),
+        (b.addr(), c.addr()),
+        (c.addr(), b.addr()),
+    ];
+    assert_eq!(
+        network.connections(),
+        expected_connections.into_iter().collect()
+    );
+
+    assert_eq!(network.degree(&a.addr()), 1);
+    assert_eq!(network.degree(&b.addr()), 2);
+    assert_eq!(network.degree(&d.addr()), 0);
+
+    let component: BTreeSet<_> = vec![a.addr(), b.addr(), c.addr()].into_iter().collect();
+    assert_eq!(network.connected_component(&a.addr()), component);
+    assert_eq!(network.connected_component(&c.addr()), component);
+    assert_eq!(
+        network.connected_component(&d.addr()),
+        iter::once(d.addr()).collect()
+    );
+}
+
+#[test]
+fn disconnect_all() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let mut a = Agent::node();
+    let mut b = Agent::node();
+    let mut c = Agent::node();
+
+    establish_connection(&network, &mut a, &mut b);
+    establish_connection(&network, &mut a, &mut c);
+
+    network.disconnect_all(&a.addr());
+    network.poll();
+
+    let failed_addrs: FxHashSet<_> = (0..2)
+        .map(|_| {
+            assert_match!(
+                a.rx.try_recv(),
+                Ok(Event::ConnectionFailure { peer_addr, .. }) => peer_addr
+            )
+        })
+        .collect();
+    assert_eq!(failed_addrs, vec![b.addr(), c.addr()].into_iter().collect());
+
+    b.expect_connection_failure(&a.addr());
+    c.expect_connection_failure(&a.addr());
+}
+
+#[test]
+#[cfg(not(feature = "mock_serialise"))]
+fn client_message_size_limit() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let small_msg = gen_message();
+    let large_msg = NetworkBytes::from(vec![0; 2 * small_msg.len()]);
+
+    let a = Agent::with_config(Config::node().with_max_client_msg_size(small_msg.len()));
+    let mut b = Agent::bootstrapped_client(&network, a.addr());
+    a.expect_connected_to_client(&b.addr());
+
+    b.send(a.addr(), small_msg.clone(), 0);
+    b.send(a.addr(), large_msg.clone(), 1);
+    network.poll();
+
+    b.expect_sent_message(&a.addr(), &small_msg, 0);
+    b.expect_unsent_message(&a.addr(), &large_msg, 1);
+    a.expect_new_message(&b.addr(), &small_msg);
+    a.expect_none();
+}
+
+#[test]
+fn client_message_rate_limit() {
+    use fake_clock::FakeClock;
+
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let a = Agent::with_config(Config::node().with_max_client_msgs_per_sec(2));
+    let mut b = Agent::bootstrapped_client(&network, a.addr());
+    a.expect_connected_to_client(&b.addr());
+
+    let msgs = [gen_message(), gen_message(), gen_message()];
+    for (token, msg) in msgs.iter().enumerate() {
+        b.send(a.addr(), msg.clone(), token as u64);
+    }
+    network.poll();
+
+    b.expect_sent_message(&a.addr(), &msgs[0], 0);
+    b.expect_sent_message(&a.addr(), &msgs[1], 1);
+    b.expect_unsent_message(&a.addr(), &msgs[2], 2);
+    expected_messages_received(msgs[..2].to_vec(), a.received_messages(&b.addr()));
+
+    // Once the rate window passes, the client can send again.
+    FakeClock::advance_time(1001);
+    b.send(a.addr(), msgs[2].clone(), 3);
+    network.poll();
+
+    b.expect_sent_message(&a.addr(), &msgs[2], 3);
+    a.expect_new_message(&b.addr(), &msgs[2]);
+}
+
+#[test]
+fn node_messages_are_not_limited() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let mut a = Agent::with_config(Config::node().with_max_client_msgs_per_sec(1));
+    let mut b = Agent::node();
+    establish_connection(&network, &mut b, &mut a);
+
+    let msgs = [gen_message(), gen_message()];
+    for (token, msg) in msgs.iter().enumerate() {
+        b.send(a.addr(), msg.clone(), token as u64);
+    }
+    network.poll();
+
+    for (token, msg) in msgs.iter().enumerate() {
+        b.expect_sent_message(&a.addr(), msg, token as u64);
+    }
+    expected_messages_received(msgs.to_vec(), a.received_messages(&b.addr()));
+}
+
+#[test]
+fn duplicate_delivery() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    network.set_duplicate_delivery(Some(1));
+
+    let mut a = Agent::node();
+    let mut b = Agent::node();
+    establish_connection(&network, &mut a, &mut b);
+
+    let msg = gen_message();
+    a.send(b.addr(), msg.clone(), 0);
+    network.poll();
+
+    // The message is delivered twice, but its sender is notified only once.
+    a.expect_sent_message(&b.addr(), &msg, 0);
+    a.expect_none();
+    b.expect_new_message(&a.addr(), &msg);
+    b.expect_new_message(&a.addr(), &msg);
+    b.expect_none();
+}
+
+#[test]
+fn message_log() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+
+    let mut a = Agent::node();
+    let mut b = Agent::node();
+    establish_connection(&network, &mut a, &mut b);
+
+    // Nothing is recorded until the log is enabled.
+    a.send(b.addr(), gen_message(), 0);
+    network.poll();
+    assert!(network.message_log().messages().is_empty());
+
+    network.set_message_log(true);
+    a.send(b.addr(), gen_message(), 0);
+    network.poll();
+    b.send(a.addr(), gen_message(), 0);
+    network.poll();
+
+    let log = network.message_log();
+    let endpoints: Vec<_> = log
+        .messages()
+        .iter()
+        .map(|msg| (msg.src, msg.dst))
+        .collect();
+    assert_eq!(endpoints, vec![(a.addr(), b.addr()), (b.addr(), a.addr())]);
+
+    let mermaid = log.to_mermaid();
+    assert!(mermaid.starts_with("sequenceDiagram\n"));
+    assert!(mermaid.contains(&format!("participant n0 as {}", a.addr())));
+    assert!(mermaid.contains("n0->>n1: "));
+    assert!(mermaid.contains("n1->>n0: "));
+
+    let graphviz = log.to_graphviz();
+    assert!(graphviz.contains(&format!(
+        "\"{}\" -> \"{}\" [label=\"1: ",
+        a.addr(),
+        b.addr()
+    )));
+    assert!(graphviz.contains(&format!(
+        "\"{}\" -> \"{}\" [label=\"2: ",
+        b.addr(),
+        a.addr()
+    )));
+
+    network.set_message_log(false);
+    assert!(network.message_log().messages().is_empty());
+}
+
 #[test]
 #[cfg(not(feature = "mock_serialise"))]
 fn packet_is_parsec_gossip() {
@@ -392,6 +662,7 @@ fn packet_is_parsec_gossip() {
         },
         parsec::{Request, Response},
         routing_table::Authority,
+        types::MessageId,
     };
 
     use maidsafe_utilities::serialisation;
@@ -403,8 +674,10 @@ fn packet_is_parsec_gossip() {
         unwrap!(serialisation::serialise(&msg))
     }
 
-    let make_message =
-        |content| Message::Direct(unwrap!(SignedDirectMessage::new(content, &full_id)));
+    let make_message = |content| {
+        let message = unwrap!(SignedDirectMessage::new(content, &full_id, None, 0));
+        Message::Direct(message)
+    };
 
     // Parsec doesn't provide constructors for requests and responses, but they have the same
     // representation as a `Vec`, or a `()` in real or mock Parsec respectively.
@@ -442,7 +715,10 @@ fn packet_is_parsec_gossip() {
     let msg = RoutingMessage {
         src: Authority::Section(rand::random()),
         dst: Authority::Section(rand::random()),
-        content: MessageContent::UserMessage(vec![rand::random(), rand::random(), rand::random()]),
+        content: MessageContent::UserMessage {
+            content: vec![rand::random(), rand::random(), rand::random()],
+            msg_id: MessageId::new(),
+        },
     };
     let msg = SignedRoutingMessage::insecure(msg);
     let msg = unwrap!(HopMessage::new(msg));
@@ -702,7 +978,8 @@ fn gen_message() -> NetworkBytes {
         // that it is unique. Let's use `DirectMessage::ParsecPoke` as it is the simplest message
         // that carries some data.
         let content = DirectMessage::ParsecPoke(num as u64);
-        let message = FULL_ID.with(|full_id| unwrap!(SignedDirectMessage::new(content, full_id)));
+        let message =
+            FULL_ID.with(|full_id| unwrap!(SignedDirectMessage::new(content, full_id, None, 0)));
         NetworkBytes::new(Message::Direct(message))
     }
 
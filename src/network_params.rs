@@ -0,0 +1,279 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    chain::{DefaultQuorumPolicy, ElderSelection, OldestElders, QuorumPolicy, SPLIT_BUFFER},
+    local_params::LocalParams,
+    parsec::PrunePolicy,
+    states::{ADD_TIMEOUT, BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT},
+    time::Duration,
+    MIN_SECTION_SIZE,
+};
+use std::{cmp, sync::Arc};
+
+// Default number of members a section relocates out at the same time.
+const MAX_CONCURRENT_RELOCATIONS: usize = 1;
+// Default size in bytes of the data a joining node must send back to solve a resource proof.
+#[cfg(not(feature = "mock_base"))]
+const RESOURCE_PROOF_TARGET_SIZE: usize = 1024 * 1024;
+#[cfg(feature = "mock_base")]
+const RESOURCE_PROOF_TARGET_SIZE: usize = 64;
+// Default difficulty of the resource proofs.
+#[cfg(not(feature = "mock_base"))]
+const RESOURCE_PROOF_DIFFICULTY: u8 = 2;
+#[cfg(feature = "mock_base")]
+const RESOURCE_PROOF_DIFFICULTY: u8 = 0;
+// Default timeout of the first join attempt.
+const INITIAL_JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+// Default factor the join timeout grows by after each failed attempt.
+const JOIN_TIMEOUT_MULTIPLIER: u32 = 2;
+// Default maximum random delay added to each join timeout.
+const MAX_JOIN_JITTER: Duration = Duration::from_secs(5);
+// Default number of join attempts before rebootstrapping.
+const MAX_JOIN_ATTEMPTS: u8 = 5;
+// Default number of clients and joining peers an elder serves as bootstrap proxy at the same time.
+const MAX_PROXY_LOAD: usize = 100;
+// Default number of joining nodes a section processes at the same time.
+const MAX_JOINING_CANDIDATES: usize = 16;
+// Default time a node waits before bootstrapping again when the section it joins is busy.
+const ADMISSION_BACKOFF: Duration = Duration::from_secs(30);
+// Default number of items a backlog holds.
+const MAX_BACKLOG_LEN: usize = 1000;
+// Default time after which a backlogged item is dropped.
+const MAX_BACKLOG_AGE: Duration = Duration::from_secs(5 * 60);
+// Default interval between the parsec gossip rounds of a node.
+const GOSSIP_TIMEOUT: Duration = Duration::from_secs(2);
+// Default interval between the parsec pokes of an adult which isn't added to its section yet.
+const POKE_TIMEOUT: Duration = Duration::from_secs(60);
+// Default interval between the `Ticked` events of an elder.
+const TICK_TIMEOUT: Duration = Duration::from_secs(15);
+// Default time after which a leaving node stops waiting for its section to vote it offline.
+const LEAVE_TIMEOUT: Duration = Duration::from_secs(60);
+// Default time after which the elders disconnect from a relocated node.
+const RELOCATE_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+// Default time an adult which lost its connection to its elders has to rejoin with its identity.
+const REJOIN_GRACE_PERIOD: Duration = Duration::from_secs(60);
+/// Parameters of the network. All the nodes of a network must use the same ones.
+#[derive(Clone, Debug)]
+pub struct NetworkParams {
+    /// Minimum number of nodes in a section.
+    pub min_section_size: usize,
+    /// Number of members beyond `min_section_size` each half of a section needs before the
+    /// section splits. A larger buffer makes splits rarer, but the sections resulting from them
+    /// less likely to merge back under churn. Must be the same for all the nodes of a network.
+    pub split_buffer: usize,
+    /// Decides whether the votes for an event are sufficient for it to accumulate.
+    pub quorum_policy: Arc<dyn QuorumPolicy>,
+    /// Decides which members of a section are its elders. Must be the same for all the nodes of
+    /// a network.
+    pub elder_selection: Arc<dyn ElderSelection>,
+    /// Maximum number of members a section relocates out at the same time. Further relocations
+    /// are queued until one in flight completes, so that ageing churn can't drain the section.
+    /// Must be at least one.
+    pub max_concurrent_relocations: usize,
+    /// When the elders vote for pruning their parsec instance.
+    pub prune_policy: PrunePolicy,
+    /// Size in bytes of the data a joining node must send back to each elder to solve its
+    /// resource proof, testing the node's bandwidth. Joining nodes cap the size they are
+    /// challenged with to it.
+    pub resource_proof_target_size: usize,
+    /// Difficulty of the resource proofs, testing the joining node's CPU. Joining nodes cap the
+    /// difficulty they are challenged with to it.
+    pub resource_proof_difficulty: u8,
+    /// How long a joining node waits for approval before retrying, and how many times it retries
+    /// before rebootstrapping.
+    pub join_backoff: JoinBackoff,
+    /// Number of clients and joining peers an elder serves as bootstrap proxy at the same time.
+    /// Further `BootstrapRequest`s are redirected to other elders.
+    pub max_proxy_load: usize,
+    /// Number of joining nodes a section processes at the same time. Further nodes are queued and
+    /// told to try again later, rather than all timing out while the section is busy. Must be at
+    /// least one.
+    pub max_joining_candidates: usize,
+    /// How long a node told to try again later waits before bootstrapping again. Nodes further
+    /// back in the queue wait for multiples of it.
+    pub admission_backoff: Duration,
+    /// Timeouts of the protocol, other than the ones of joining which are in `join_backoff`.
+    pub timeouts: Timeouts,
+    /// Limits of the backlogs of the messages and events a node receives while joining, which it
+    /// handles once it joined.
+    pub backlog_limits: BacklogLimits,
+    /// Settings local to our node, set through `NodeBuilder`.
+    pub(crate) local: LocalParams,
+}
+
+impl Default for NetworkParams {
+    fn default() -> Self {
+        Self {
+            min_section_size: MIN_SECTION_SIZE,
+            split_buffer: SPLIT_BUFFER,
+            quorum_policy: Arc::new(DefaultQuorumPolicy),
+            elder_selection: Arc::new(OldestElders::default()),
+            max_concurrent_relocations: MAX_CONCURRENT_RELOCATIONS,
+            prune_policy: PrunePolicy::default(),
+            resource_proof_target_size: RESOURCE_PROOF_TARGET_SIZE,
+            resource_proof_difficulty: RESOURCE_PROOF_DIFFICULTY,
+            join_backoff: JoinBackoff::default(),
+            max_proxy_load: MAX_PROXY_LOAD,
+            max_joining_candidates: MAX_JOINING_CANDIDATES,
+            admission_backoff: ADMISSION_BACKOFF,
+            timeouts: Timeouts::default(),
+            backlog_limits: BacklogLimits::default(),
+            local: LocalParams::default(),
+        }
+    }
+}
+
+/// Limits of a backlog of messages or events. Once full, the oldest item is dropped to make room
+/// for a new one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BacklogLimits {
+    /// Maximum number of items.
+    pub max_len: usize,
+    /// Time after which an item is dropped.
+    pub max_age: Duration,
+}
+
+impl Default for BacklogLimits {
+    fn default() -> Self {
+        Self {
+            max_len: MAX_BACKLOG_LEN,
+            max_age: MAX_BACKLOG_AGE,
+        }
+    }
+}
+
+/// Timeouts of the protocol. Shorter ones make a node react faster to unresponsive peers, at the
+/// cost of more traffic or of giving up on slow peers too early.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Timeouts {
+    /// How long a bootstrapping node waits for the `BootstrapResponse` of a proxy.
+    pub bootstrap: Duration,
+    /// How long an approved node waits to be added to its section before rebootstrapping.
+    pub add: Duration,
+    /// Interval between the parsec gossip rounds of a node.
+    pub gossip: Duration,
+    /// Interval between the parsec pokes of an adult which isn't added to its section yet.
+    pub poke: Duration,
+    /// Interval between the `Ticked` events of an elder.
+    pub tick: Duration,
+    /// How long a leaving node waits for its section to vote it offline before leaving anyway.
+    pub leave: Duration,
+    /// How long the elders stay connected to a relocated node, so it can bootstrap off them.
+    pub relocate_disconnect: Duration,
+    /// How long an adult which lost its connection to the elders has to rejoin with its identity
+    /// before they vote it offline.
+    pub rejoin_grace_period: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            bootstrap: BOOTSTRAP_TIMEOUT,
+            add: ADD_TIMEOUT,
+            gossip: GOSSIP_TIMEOUT,
+            poke: POKE_TIMEOUT,
+            tick: TICK_TIMEOUT,
+            leave: LEAVE_TIMEOUT,
+            relocate_disconnect: RELOCATE_DISCONNECT_TIMEOUT,
+            rejoin_grace_period: REJOIN_GRACE_PERIOD,
+        }
+    }
+}
+
+/// Exponential backoff of the attempts of a node to join a section.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JoinBackoff {
+    /// Timeout of the first attempt.
+    pub initial_timeout: Duration,
+    /// Factor the timeout grows by after each failed attempt.
+    pub multiplier: u32,
+    /// Maximum random delay added to each timeout, so that nodes which failed to join at the same
+    /// time don't all retry at the same time.
+    pub max_jitter: Duration,
+    /// Upper bound of the timeout, before jitter. The jitter is added on top of it, so that nodes
+    /// retrying at the cap don't retry in lockstep.
+    pub max_timeout: Duration,
+    /// Number of attempts after which the node gives up and rebootstraps. Must be at least one.
+    pub max_attempts: u8,
+}
+
+impl JoinBackoff {
+    /// Returns the timeout of the given attempt (counted from zero), before jitter.
+    pub fn timeout(&self, attempt: u8) -> Duration {
+        self.multiplier
+            .checked_pow(u32::from(attempt))
+            .and_then(|factor| self.initial_timeout.checked_mul(factor))
+            .map_or(self.max_timeout, |timeout| {
+                cmp::min(timeout, self.max_timeout)
+            })
+    }
+
+    /// Returns the timeout of the given attempt with the given `jitter`, itself capped to
+    /// `max_jitter`, added after clamping to `max_timeout`.
+    pub(crate) fn timeout_with_jitter(&self, attempt: u8, jitter: Duration) -> Duration {
+        self.timeout(attempt) + cmp::min(jitter, self.max_jitter)
+    }
+}
+
+impl Default for JoinBackoff {
+    fn default() -> Self {
+        Self {
+            initial_timeout: INITIAL_JOIN_TIMEOUT,
+            multiplier: JOIN_TIMEOUT_MULTIPLIER,
+            max_jitter: MAX_JOIN_JITTER,
+            max_timeout: JOIN_TIMEOUT,
+            max_attempts: MAX_JOIN_ATTEMPTS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_backoff_grows_up_to_max_timeout() {
+        let backoff = JoinBackoff {
+            initial_timeout: Duration::from_secs(10),
+            multiplier: 3,
+            max_jitter: Duration::from_secs(0),
+            max_timeout: Duration::from_secs(60),
+            max_attempts: 5,
+        };
+
+        assert_eq!(backoff.timeout(0), Duration::from_secs(10));
+        assert_eq!(backoff.timeout(1), Duration::from_secs(30));
+        assert_eq!(backoff.timeout(2), Duration::from_secs(60));
+        assert_eq!(backoff.timeout(u8::max_value()), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn join_backoff_jitter_applies_at_max_timeout() {
+        let backoff = JoinBackoff {
+            initial_timeout: Duration::from_secs(10),
+            multiplier: 3,
+            max_jitter: Duration::from_secs(5),
+            max_timeout: Duration::from_secs(60),
+            max_attempts: 5,
+        };
+
+        assert_eq!(
+            backoff.timeout_with_jitter(0, Duration::from_secs(2)),
+            Duration::from_secs(12)
+        );
+        assert_eq!(
+            backoff.timeout_with_jitter(4, Duration::from_secs(2)),
+            Duration::from_secs(62)
+        );
+        assert_eq!(
+            backoff.timeout_with_jitter(4, Duration::from_secs(20)),
+            Duration::from_secs(65)
+        );
+    }
+}
@@ -6,16 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod proxy_blacklist;
 mod sending_targets_cache;
 
+#[cfg(not(feature = "mock_serialise"))]
+use crate::compression;
 use crate::{
+    local_params::Codec,
+    message_lanes::MessageLanes,
+    messages::{Capabilities, Priority},
     quic_p2p::{Builder, Error, Peer, Token},
     utils::LogIdent,
     ConnectionInfo, NetworkBytes, NetworkConfig, NetworkEvent, QuicP2p,
 };
 use crossbeam_channel::Sender;
-use std::net::SocketAddr;
+use std::{collections::HashSet, net::SocketAddr};
 
+pub use proxy_blacklist::ProxyBlacklist;
 use sending_targets_cache::SendingTargetsCache;
 
 /// Struct that handles network operations: sending and receiving messages, as well as resending on
@@ -24,6 +31,16 @@ pub struct NetworkService {
     quic_p2p: QuicP2p,
     cache: SendingTargetsCache,
     next_msg_token: Token,
+    // Peers which announced they can decompress our messages.
+    compressing_peers: HashSet<SocketAddr>,
+    // Peers which may send us compressed messages, as they learned we can decompress them.
+    compressed_senders: HashSet<SocketAddr>,
+    proxy_blacklist: ProxyBlacklist,
+    // Codec of the messages we send.
+    codec: Codec,
+    // Messages waiting to be handed to quic-p2p, together with their targets and delivery group
+    // size.
+    outgoing: MessageLanes<(Vec<ConnectionInfo>, usize, NetworkBytes)>,
 }
 
 impl NetworkService {
@@ -40,14 +57,89 @@ impl NetworkService {
         &mut self.cache
     }
 
+    pub fn proxy_blacklist_mut(&mut self) -> &mut ProxyBlacklist {
+        &mut self.proxy_blacklist
+    }
+
+    /// Codec the messages we send are serialised with.
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    /// Records the optional features the peer at `peer_addr` supports.
+    pub fn set_peer_capabilities(&mut self, peer_addr: SocketAddr, capabilities: Capabilities) {
+        if capabilities.compression {
+            let _ = self.compressing_peers.insert(peer_addr);
+            let _ = self.compressed_senders.insert(peer_addr);
+        } else {
+            let _ = self.compressing_peers.remove(&peer_addr);
+            let _ = self.compressed_senders.remove(&peer_addr);
+        }
+    }
+
+    /// Accepts compressed messages from the peer at `peer_addr`, which learned we can decompress
+    /// them from our `ConnectionRequest` without telling us what it supports.
+    pub fn accept_compressed_from(&mut self, peer_addr: SocketAddr) {
+        let _ = self.compressed_senders.insert(peer_addr);
+    }
+
+    /// Returns whether we agreed to exchange compressed messages with the peer at `peer_addr`.
+    /// Compressed messages from any other peer are rejected.
+    pub fn accepts_compressed_from(&self, peer_addr: &SocketAddr) -> bool {
+        self.compressed_senders.contains(peer_addr)
+    }
+
+    /// Forgets the features of the peer at `peer_addr`, as we are no longer connected to it.
+    pub fn forget_peer(&mut self, peer_addr: &SocketAddr) {
+        let _ = self.compressing_peers.remove(peer_addr);
+        let _ = self.compressed_senders.remove(peer_addr);
+    }
+
+    /// Returns whether some messages we sent haven't reached any of their targets yet, nor failed
+    /// to reach all of them.
+    pub fn has_pending_sends(&self) -> bool {
+        !self.cache.is_empty() || !self.outgoing.is_empty()
+    }
+
+    /// Queues the message to be sent by the next `flush`, after any queued message of higher
+    /// priority.
     pub fn send_message_to_initial_targets(
         &mut self,
         conn_infos: Vec<ConnectionInfo>,
         dg_size: usize,
         msg: NetworkBytes,
+        priority: Priority,
     ) {
+        self.outgoing.push(priority, (conn_infos, dg_size, msg));
+    }
+
+    /// Sends all the queued messages, the ones of the highest priority first.
+    pub fn flush(&mut self) {
+        while let Some((conn_infos, dg_size, msg)) = self.outgoing.pop() {
+            self.send_now(conn_infos, dg_size, msg);
+        }
+    }
+
+    fn send_now(&mut self, conn_infos: Vec<ConnectionInfo>, dg_size: usize, msg: NetworkBytes) {
         let token = self.next_msg_token();
 
+        // Compress only if all the targets support it, as the message may be resent to any of
+        // them.
+        #[cfg(not(feature = "mock_serialise"))]
+        let msg = if !conn_infos.is_empty()
+            && conn_infos
+                .iter()
+                .all(|conn_info| self.compressing_peers.contains(&conn_info.peer_addr))
+        {
+            NetworkBytes::from(compression::compress(msg.to_vec()))
+        } else {
+            msg
+        };
+
         // initially only send to dg_size targets
         for conn_info in conn_infos.iter().take(dg_size) {
             // NetworkBytes is refcounted and cheap to clone.
@@ -108,6 +200,63 @@ impl NetworkBuilder {
             quic_p2p: self.quic_p2p.build()?,
             cache: Default::default(),
             next_msg_token: 0,
+            compressing_peers: HashSet::new(),
+            compressed_senders: HashSet::new(),
+            proxy_blacklist: ProxyBlacklist::default(),
+            codec: Codec::default(),
+            outgoing: MessageLanes::default(),
         })
     }
 }
+
+#[cfg(all(test, feature = "mock_base", not(feature = "mock_serialise")))]
+mod tests {
+    use super::*;
+    use crate::{mock::Network, NetworkConfig, NetworkEvent};
+    use crossbeam_channel as mpmc;
+    use unwrap::unwrap;
+
+    #[test]
+    fn flush_sends_higher_priority_messages_first() {
+        let network = Network::new(4, None);
+
+        let (tx, _rx) = mpmc::unbounded();
+        let mut service = unwrap!(NetworkBuilder::new(tx)
+            .with_config(NetworkConfig::node())
+            .build());
+
+        let (peer_tx, peer_rx) = mpmc::unbounded();
+        let mut peer = unwrap!(Builder::new(peer_tx)
+            .with_config(NetworkConfig::node())
+            .build());
+        let peer_info = unwrap!(peer.our_connection_info());
+
+        let bulk = NetworkBytes::from_static(b"user message");
+        let critical = NetworkBytes::from_static(b"parsec gossip");
+        service.send_message_to_initial_targets(
+            vec![peer_info.clone()],
+            1,
+            bulk.clone(),
+            Priority::Bulk,
+        );
+        service.send_message_to_initial_targets(
+            vec![peer_info],
+            1,
+            critical.clone(),
+            Priority::Critical,
+        );
+        assert!(service.has_pending_sends());
+
+        service.flush();
+        network.poll();
+
+        let received: Vec<_> = peer_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                NetworkEvent::NewMessage { msg, .. } => Some(msg),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(received, vec![critical, bulk]);
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::time::{Duration, Instant};
+use std::{collections::HashMap, net::SocketAddr};
+
+/// Proxies that timed out or rejected our `BootstrapRequest`, with the time they were blacklisted.
+/// Kept by the network service so that it outlives the bootstrapping state: a node which gives up
+/// joining and bootstraps again still skips them.
+#[derive(Default)]
+pub struct ProxyBlacklist(HashMap<SocketAddr, Instant>);
+
+impl ProxyBlacklist {
+    pub fn insert(&mut self, peer_addr: SocketAddr) {
+        let _ = self.0.insert(peer_addr, Instant::now());
+    }
+
+    /// Returns how much longer `peer_addr` is blacklisted for, if at all. Forgets the proxies whose
+    /// `cool_down` expired.
+    pub fn remaining(&mut self, peer_addr: &SocketAddr, cool_down: Duration) -> Option<Duration> {
+        self.0.retain(|_, since| since.elapsed() < cool_down);
+        self.0
+            .get(peer_addr)
+            .and_then(|since| cool_down.checked_sub(since.elapsed()))
+    }
+}
+
+#[cfg(all(test, feature = "mock_base"))]
+mod tests {
+    use super::*;
+    use fake_clock::FakeClock;
+    use unwrap::unwrap;
+
+    #[test]
+    fn proxy_blacklist_cools_down() {
+        let cool_down = Duration::from_secs(10);
+        let proxy_a: SocketAddr = unwrap!("127.0.0.1:1".parse());
+        let proxy_b: SocketAddr = unwrap!("127.0.0.1:2".parse());
+
+        let mut blacklist = ProxyBlacklist::default();
+        blacklist.insert(proxy_a);
+        assert_eq!(blacklist.remaining(&proxy_a, cool_down), Some(cool_down));
+        assert_eq!(blacklist.remaining(&proxy_b, cool_down), None);
+
+        FakeClock::advance_time(4_000);
+        assert_eq!(
+            blacklist.remaining(&proxy_a, cool_down),
+            Some(Duration::from_secs(6))
+        );
+
+        FakeClock::advance_time(6_000);
+        assert_eq!(blacklist.remaining(&proxy_a, cool_down), None);
+    }
+}
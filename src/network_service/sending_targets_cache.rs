@@ -141,6 +141,11 @@ impl SendingTargetsCache {
         result
     }
 
+    /// Returns whether no message is being sent.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
     pub fn target_succeeded(&mut self, token: Token, target: SocketAddr) {
         let _ = self
             .target_states_mut(token)
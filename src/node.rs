@@ -7,32 +7,50 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    action::Action,
+    action::{Action, ActionSender},
+    chain::{AccumulatorMetrics, AuditEntry, Chain, ChainView},
+    dispatch::DispatchTable,
     error::{InterfaceError, RoutingError},
     event::Event,
     event_stream::{EventStepper, EventStream},
     id::{FullId, PublicId},
+    local_params::{Codec, InboundLimits, LocalParams, MessageFilterLimits},
     outbox::{EventBox, EventBuf},
-    pause::PausedState,
+    pause::{PausedState, RelocationState},
     quic_p2p::OurType,
+    routing_message_filter::DuplicateCounts,
     routing_table::Authority,
-    state_machine::{State, StateMachine},
+    state_machine::{State, StateMachine, TransitionObserver, TransitionRecord},
     states::{self, BootstrappingPeer},
+    time::Duration,
+    types::{MessageId, NeighbourConnectivity},
     xor_name::XorName,
-    NetworkBytes, NetworkConfig, MIN_SECTION_SIZE,
+    NetworkBytes, NetworkConfig, NetworkParams, Prefix,
 };
 #[cfg(feature = "mock_base")]
-use crate::{chain::SectionProofChain, utils::XorTargetInterval, Chain, ConnectionInfo, Prefix};
+use crate::{
+    chain::{GenesisPfxInfo, SectionProofChain},
+    parsec::ParsecMetrics,
+    time::Instant,
+    utils::XorTargetInterval,
+    ConnectionInfo,
+};
 use crossbeam_channel as mpmc;
+#[cfg(feature = "async")]
+use futures::{
+    stream::{Next, Stream, StreamExt},
+    task::{Context, Poll},
+};
 use quic_p2p::Token;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "mock_base")]
+use std::fmt::{self, Display, Formatter};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+#[cfg(feature = "async")]
+use std::pin::Pin;
 use std::sync::mpsc;
 #[cfg(feature = "mock_base")]
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    fmt::{self, Display, Formatter},
-};
-#[cfg(feature = "mock_base")]
 use unwrap::unwrap;
 
 /// A builder to configure and create a new `Node`.
@@ -40,7 +58,13 @@ pub struct NodeBuilder {
     first: bool,
     network_config: Option<NetworkConfig>,
     full_id: Option<FullId>,
-    min_section_size: usize,
+    network_params: NetworkParams,
+    local_params: LocalParams,
+    relocation: Option<RelocationState>,
+    bootstrap_cache_dir: Option<PathBuf>,
+    event_channel: bool,
+    #[cfg(feature = "mock_base")]
+    gen_pfx_info: Option<GenesisPfxInfo>,
 }
 
 impl NodeBuilder {
@@ -57,6 +81,15 @@ impl NodeBuilder {
         }
     }
 
+    /// The node will keep its bootstrap cache in `dir` rather than in the default directory,
+    /// overriding the one set in the network config, if any.
+    pub fn bootstrap_cache_dir<P: Into<PathBuf>>(self, dir: P) -> Self {
+        Self {
+            bootstrap_cache_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
     /// The node will use the given full id rather than default, randomly generated one.
     pub fn full_id(self, full_id: FullId) -> Self {
         Self {
@@ -68,7 +101,104 @@ impl NodeBuilder {
     /// Override the default min section size.
     pub fn min_section_size(self, min_section_size: usize) -> Self {
         Self {
-            min_section_size,
+            network_params: NetworkParams {
+                min_section_size,
+                ..self.network_params
+            },
+            ..self
+        }
+    }
+
+    /// The node will use the given network parameters rather than the default ones.
+    pub fn network_params(self, network_params: NetworkParams) -> Self {
+        Self {
+            network_params,
+            ..self
+        }
+    }
+
+    /// How long the node skips a bootstrap proxy which timed out or rejected it, so it doesn't
+    /// keep retrying the same dead contact. The blacklist is kept when the node bootstraps again.
+    pub fn proxy_blacklist_duration(mut self, duration: Duration) -> Self {
+        self.local_params.proxy_blacklist_duration = duration;
+        self
+    }
+
+    /// How long the node waits for the acknowledgement of a message sent with
+    /// `Node::send_message_with_ack` before raising `Event::MessageDeliveryFailed`.
+    pub fn delivery_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.local_params.delivery_ack_timeout = timeout;
+        self
+    }
+
+    /// How long the node waits for the response to a request sent with `Node::send_request`
+    /// before raising `Event::RequestTimedOut`.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.local_params.request_timeout = timeout;
+        self
+    }
+
+    /// Limits of the filters suppressing the duplicate routing messages the node receives or
+    /// sends.
+    pub fn message_filter_limits(mut self, limits: MessageFilterLimits) -> Self {
+        self.local_params.message_filter_limits = limits;
+        self
+    }
+
+    /// Budgets of the messages the node accepts from each peer before disconnecting from it.
+    pub fn inbound_limits(mut self, limits: InboundLimits) -> Self {
+        self.local_params.inbound_limits = limits;
+        self
+    }
+
+    /// Handlers attached to the messages the node receives while it is an elder, filters of the
+    /// user messages it receives and authoriser of the clients it serves.
+    pub fn dispatch_table(mut self, dispatch_table: DispatchTable) -> Self {
+        self.local_params.dispatch_table = dispatch_table;
+        self
+    }
+
+    /// The node will log the votes it casts to the file at `path`, and read it back when it
+    /// starts, so that it never casts a vote conflicting with one it cast before an unclean
+    /// restart. Without it the log is only kept in memory.
+    pub fn vote_log_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.local_params.vote_log_path = Some(path.into());
+        self
+    }
+
+    /// Codec of the messages the node sends. `Codec::Json` makes them readable without knowing
+    /// their layout, e.g. by tools inspecting the traffic, at the cost of larger messages.
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.local_params.codec = codec;
+        self
+    }
+
+    /// Resume the relocation interrupted by the restart of the node, rather than joining as a new
+    /// node. The full id, if set, is ignored: the node keeps the id it was relocated with.
+    pub fn resume_relocation(self, relocation: RelocationState) -> Self {
+        Self {
+            relocation: Some(relocation),
+            ..self
+        }
+    }
+
+    /// The node will send its events to a channel as it produces them, rather than buffering them
+    /// until read through `EventStream`. The receiving end is returned by `Node::event_receiver`.
+    /// The node still needs to be polled to make progress. Reading its events through
+    /// `EventStream` then reads from the same channel, so each event is received only once.
+    pub fn event_channel(self) -> Self {
+        Self {
+            event_channel: true,
+            ..self
+        }
+    }
+
+    /// Start the node right away as an elder of the section described by `gen_pfx_info`,
+    /// skipping bootstrapping and joining. The node's full id must be one of the section's elders.
+    #[cfg(feature = "mock_base")]
+    pub fn genesis(self, gen_pfx_info: GenesisPfxInfo) -> Self {
+        Self {
+            gen_pfx_info: Some(gen_pfx_info),
             ..self
         }
     }
@@ -79,46 +209,158 @@ impl NodeBuilder {
     /// request a new name and integrate itself into the network using the new name.
     ///
     /// The initial `Node` object will have newly generated keys.
+    ///
+    /// Fails with `RoutingError::InvalidConfig` if the options set on the builder contradict each
+    /// other or the network parameters are out of range.
     pub fn create(self) -> Result<Node, RoutingError> {
+        self.validate()?;
+
         let mut ev_buffer = EventBuf::new();
+        let event_channel = if self.event_channel {
+            Some(mpmc::unbounded())
+        } else {
+            None
+        };
 
         // start the handler for routing without a restriction to become a full node
         let (_, machine) = self.make_state_machine(&mut ev_buffer);
         let (tx, rx) = mpsc::channel();
 
-        Ok(Node {
+        let mut node = Node {
             interface_result_tx: tx,
             interface_result_rx: rx,
             machine: machine,
             event_buffer: ev_buffer,
-        })
+            event_channel,
+        };
+        // Forward the events produced while starting, e.g. `Connected` for the first node.
+        node.forward_events();
+        Ok(node)
+    }
+
+    fn validate(&self) -> Result<(), RoutingError> {
+        let params = &self.network_params;
+        if params.min_section_size == 0 {
+            return Err(RoutingError::InvalidConfig(
+                "min_section_size must be at least one",
+            ));
+        }
+        if params.max_concurrent_relocations == 0 {
+            return Err(RoutingError::InvalidConfig(
+                "max_concurrent_relocations must be at least one",
+            ));
+        }
+        if params.max_joining_candidates == 0 {
+            return Err(RoutingError::InvalidConfig(
+                "max_joining_candidates must be at least one",
+            ));
+        }
+        if params.join_backoff.max_attempts == 0 {
+            return Err(RoutingError::InvalidConfig(
+                "join_backoff.max_attempts must be at least one",
+            ));
+        }
+        if self
+            .bootstrap_cache_dir
+            .as_ref()
+            .map_or(false, |dir| dir.to_str().is_none())
+        {
+            return Err(RoutingError::InvalidConfig(
+                "bootstrap_cache_dir must be valid unicode",
+            ));
+        }
+        if self.first && self.relocation.is_some() {
+            return Err(RoutingError::InvalidConfig(
+                "the first node can't resume a relocation",
+            ));
+        }
+
+        #[cfg(feature = "mock_base")]
+        {
+            if let Some(ref gen_pfx_info) = self.gen_pfx_info {
+                if self.first || self.relocation.is_some() {
+                    return Err(RoutingError::InvalidConfig(
+                        "a genesis node can't also be the first node or resume a relocation",
+                    ));
+                }
+                let is_elder = self.full_id.as_ref().map_or(false, |full_id| {
+                    gen_pfx_info
+                        .first_info
+                        .members()
+                        .contains(full_id.public_id())
+                });
+                if !is_elder {
+                    return Err(RoutingError::InvalidConfig(
+                        "the full id of a genesis node must be one of the genesis elders",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    fn make_state_machine(self, outbox: &mut dyn EventBox) -> (mpmc::Sender<Action>, StateMachine) {
-        let full_id = self.full_id.unwrap_or_else(FullId::new);
-        let min_section_size = self.min_section_size;
+    fn make_state_machine(self, outbox: &mut dyn EventBox) -> (ActionSender, StateMachine) {
+        let network_params = NetworkParams {
+            local: self.local_params,
+            ..self.network_params
+        };
 
         let first = self.first;
+        let relocation = self.relocation;
+        #[cfg(feature = "mock_base")]
+        let gen_pfx_info = self.gen_pfx_info;
+
+        let mut network_config = self.network_config;
+        if let Some(dir) = self.bootstrap_cache_dir {
+            network_config
+                .get_or_insert_with(NetworkConfig::default)
+                .bootstrap_cache_dir = dir.to_str().map(str::to_string);
+        }
 
-        let mut network_config = self.network_config.unwrap_or_default();
-        network_config.our_type = OurType::Node;
+        StateMachine::start(
+            OurType::Node,
+            self.full_id,
+            network_config,
+            move |mut network_service, full_id, timer, outbox| {
+                network_service.set_codec(network_params.local.codec);
+
+                #[cfg(feature = "mock_base")]
+                {
+                    if let Some(gen_pfx_info) = gen_pfx_info {
+                        return states::Elder::from_genesis(
+                            network_service,
+                            full_id,
+                            gen_pfx_info,
+                            network_params,
+                            timer,
+                            outbox,
+                        )
+                        .map(State::Elder)
+                        .unwrap_or(State::Terminated);
+                    }
+                }
 
-        StateMachine::new(
-            move |network_service, timer, outbox| {
                 if first {
-                    states::Elder::first(network_service, full_id, min_section_size, timer, outbox)
+                    states::Elder::first(network_service, full_id, network_params, timer, outbox)
                         .map(State::Elder)
                         .unwrap_or(State::Terminated)
+                } else if let Some(relocation) = relocation {
+                    State::BootstrappingPeer(BootstrappingPeer::resume_relocation(
+                        network_service,
+                        network_params,
+                        timer,
+                        relocation,
+                    ))
                 } else {
                     State::BootstrappingPeer(BootstrappingPeer::new(
                         network_service,
                         full_id,
-                        min_section_size,
+                        network_params,
                         timer,
                     ))
                 }
             },
-            network_config,
             outbox,
         )
     }
@@ -136,6 +378,7 @@ pub struct Node {
     interface_result_rx: mpsc::Receiver<Result<(), InterfaceError>>,
     machine: StateMachine,
     event_buffer: EventBuf,
+    event_channel: Option<(mpmc::Sender<Event>, mpmc::Receiver<Event>)>,
 }
 
 impl Node {
@@ -145,16 +388,24 @@ impl Node {
             first: false,
             network_config: None,
             full_id: None,
-            min_section_size: MIN_SECTION_SIZE,
+            network_params: NetworkParams::default(),
+            local_params: LocalParams::default(),
+            relocation: None,
+            bootstrap_cache_dir: None,
+            event_channel: false,
+            #[cfg(feature = "mock_base")]
+            gen_pfx_info: None,
         }
     }
 
-    /// Pauses the node in order to be upgraded and/or restarted.
+    /// Pauses the node in order to be upgraded and/or restarted. Fails if the node is still
+    /// bootstrapping, as it has nothing to keep yet.
     pub fn pause(self) -> Result<PausedState, RoutingError> {
         self.machine.pause()
     }
 
-    /// Resume previously paused node.
+    /// Resume previously paused node. The messages sent with acknowledgement and the requests still
+    /// pending when it was paused are sent again.
     pub fn resume(state: PausedState) -> Self {
         let (interface_result_tx, interface_result_rx) = mpsc::channel();
         let event_buffer = EventBuf::new();
@@ -165,9 +416,18 @@ impl Node {
             interface_result_rx,
             machine,
             event_buffer,
+            event_channel: None,
         }
     }
 
+    /// Returns the receiving end of the channel the node sends its events to, if it was created
+    /// with `NodeBuilder::event_channel`.
+    pub fn event_receiver(&self) -> Option<mpmc::Receiver<Event>> {
+        self.event_channel
+            .as_ref()
+            .map(|(_, event_rx)| event_rx.clone())
+    }
+
     /// Returns the first `count` names of the nodes in the routing table which are closest
     /// to the given one.
     pub fn close_group(&self, name: XorName, count: usize) -> Option<Vec<XorName>> {
@@ -188,7 +448,20 @@ impl Node {
             .map(|elder| elder.vote_for_user_event(event));
     }
 
-    /// Send a message.
+    /// Vote for a custom event in the given namespace. Events of different namespaces accumulate
+    /// independently, and are raised as `Event::UserEventConsensused` once they reach consensus.
+    pub fn vote_for_user_event(&mut self, namespace: &str, payload: Vec<u8>) {
+        let _ = self
+            .machine
+            .current_mut()
+            .elder_state_mut()
+            .map(|elder| elder.vote_for_namespaced_user_event(namespace.to_string(), payload));
+    }
+
+    /// Send a message. Until we are promoted to an elder, we can only send messages from
+    /// `Authority::Node` with our own name; they are relayed into the network by our section's
+    /// elders. Contents too large for the transport are sent in parts and reassembled by the
+    /// recipients, which raise `Event::MessageIncomplete` if some parts don't arrive in time.
     pub fn send_message(
         &mut self,
         src: Authority<XorName>,
@@ -202,6 +475,178 @@ impl Node {
             src: src,
             dst: dst,
             content,
+            trace_id: None,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Send a message like `send_message`, tagged with `trace_id`. Each node the message passes
+    /// through logs it with the id, and the recipients raise it in `Event::MessageReceived`, so
+    /// that its path through the network can be followed. Messages on behalf of our section are
+    /// only traced once all its elders tagged their signatures with the same id.
+    pub fn send_traced_message(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        trace_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendMessage {
+            src,
+            dst,
+            content,
+            trace_id: Some(trace_id),
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Send the same message to each of `dsts`, under the same rules as `send_message`. The
+    /// content is split into parts only once for all the destinations, and destinations listed
+    /// more than once are only sent to once.
+    pub fn send_message_to_many(
+        &mut self,
+        src: Authority<XorName>,
+        dsts: &[Authority<XorName>],
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendMessageToMany {
+            src,
+            dsts: dsts.to_vec(),
+            content,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Send a message like `send_message`, and have its destination acknowledge it. We raise
+    /// `Event::MessageDelivered` with `msg_id` once the acknowledgement arrives, or
+    /// `Event::MessageDeliveryFailed` if it doesn't arrive in time. When sending on behalf of our
+    /// section, all its elders must use the same `msg_id` for their messages to accumulate. Only
+    /// elders can send messages with acknowledgement.
+    pub fn send_message_with_ack(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendMessageWithAck {
+            src,
+            dst,
+            content,
+            msg_id,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Send a large message as a stream: its parts are sent in order, no faster than the
+    /// destination delivers them, and raised there as `Event::StreamPart`s followed by
+    /// `Event::StreamComplete`. When sending on behalf of our section, all its elders must use the
+    /// same `stream_id` for their parts to accumulate. Only elders can send and receive streams.
+    /// If the destination stops granting credit for the remaining parts, we raise
+    /// `Event::StreamFailed`.
+    pub fn send_stream(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        stream_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendStream {
+            src,
+            dst,
+            content,
+            stream_id,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Send a request to `dst`, raised there as `Event::RequestReceived`. Returns the token of the
+    /// request: we raise `Event::NodeResponseReceived` with it once the response sent with
+    /// `send_response` arrives from `dst`, or `Event::RequestTimedOut` if it doesn't arrive in
+    /// time. Fails with `InterfaceError::SendFailed` if the request couldn't be sent. Each
+    /// request gets its own token, so requests on behalf of our section don't accumulate; send
+    /// them from our node instead. Only elders can send and receive requests.
+    pub fn send_request(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<MessageId, InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let token = MessageId::new();
+        let action = Action::SendUserRequest {
+            src,
+            dst,
+            content,
+            token,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action).map(|()| token)
+    }
+
+    /// Respond to the request with the given token, received as `Event::RequestReceived`. `src`
+    /// and `dst` are the destination and source of the request respectively.
+    pub fn send_response(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        token: MessageId,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::SendUserResponse {
+            src,
+            dst,
+            content,
+            token,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Relay a message received from the client with the given id into the network. The
+    /// recipients see it as sent by us, together with the client's id and our section version.
+    pub fn relay_client_message(
+        &mut self,
+        client_id: PublicId,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::RelayClientMessage {
+            client_id,
+            dst,
+            content,
             result_tx: self.interface_result_tx.clone(),
         };
 
@@ -228,6 +673,21 @@ impl Node {
         self.perform_action(action)
     }
 
+    /// Request the chain of our section's keys from `from_version` (a version whose key we already
+    /// trust) up to the current one, from one of our section's elders. The chain is delivered as
+    /// `Event::ProofChainReceived` and can be used to verify messages from our section.
+    pub fn request_proof_chain(&mut self, from_version: u64) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::RequestProofChain {
+            from_version,
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
     /// Disconnect form a client peer
     pub fn disconnect_from_client(&mut self, peer_addr: SocketAddr) -> Result<(), InterfaceError> {
         // Make sure the state machine has processed any outstanding network events.
@@ -241,6 +701,202 @@ impl Node {
         self.perform_action(action)
     }
 
+    /// Makes us vote for pruning our section's parsec instance right away, regardless of the
+    /// thresholds of the `PrunePolicy`. Only elders can vote for pruning.
+    pub fn prune_parsec(&mut self) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::PruneParsec {
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Leaves the network gracefully: we announce our departure to our section's elders, which
+    /// vote us offline right away rather than waiting to notice we disconnected. Once the section
+    /// has removed us, or after a timeout, we flush our outstanding messages and raise
+    /// `Event::Terminated`. Only adults and elders can leave this way.
+    pub fn initiate_leave(&mut self) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::InitiateLeave {
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Asks our section to demote us from elder to adult, e.g. before taking our host down for
+    /// maintenance. We stay a member of the section, so we keep our age. Once the other elders
+    /// agree, we transition back to an adult and the section removes us from its elders. Fails
+    /// with `InterfaceError::InvalidState` if we are not an elder or if the section doesn't have
+    /// enough elders to spare one.
+    pub fn step_down(&mut self) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::StepDown {
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Connects again to the elders of our neighbour sections we lost the connection to, as
+    /// reported by `neighbour_connectivity`. Fails with `InterfaceError::InvalidState` if we are
+    /// not an elder.
+    pub fn reconnect_to_neighbours(&mut self) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding network events.
+        let _ = self.poll();
+
+        let action = Action::ReconnectToNeighbours {
+            result_tx: self.interface_result_tx.clone(),
+        };
+
+        self.perform_action(action)
+    }
+
+    /// Returns, for each of our neighbour sections, which of its elders we hold live connections
+    /// to. Empty if we are not a member of a section yet.
+    pub fn neighbour_connectivity(&self) -> BTreeMap<Prefix<XorName>, NeighbourConnectivity> {
+        let view = match self.chain_view() {
+            Some(view) => view,
+            None => return BTreeMap::new(),
+        };
+        view.neighbour_infos()
+            .map(|info| {
+                let (connected, disconnected) = info
+                    .members()
+                    .iter()
+                    .partition(|pub_id| self.machine.current().is_connected(pub_id));
+                (
+                    *info.prefix(),
+                    NeighbourConnectivity {
+                        connected,
+                        disconnected,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Returns our age, or `None` if we are not yet a member of a section.
+    pub fn our_age(&self) -> Option<u8> {
+        self.chain().and_then(Chain::our_age)
+    }
+
+    /// Returns the ages of all the members of our section. Empty if we are not yet a member of a
+    /// section.
+    pub fn member_ages(&self) -> BTreeMap<PublicId, u8> {
+        self.chain().map(Chain::member_ages).unwrap_or_default()
+    }
+
+    /// Returns the metrics of our votes which haven't accumulated yet, to help detect stalled
+    /// consensus. Returns `None` if we are not yet a member of a section.
+    pub fn accumulator_metrics(&self) -> Option<AccumulatorMetrics> {
+        self.chain().map(Chain::accumulator_metrics)
+    }
+
+    /// Returns a read-only view of the state of our section. Returns `None` if we are not yet a
+    /// member of a section.
+    pub fn chain_view(&self) -> Option<ChainView> {
+        self.chain().map(ChainView::new)
+    }
+
+    /// Removes the entries of our section's audit log and returns them, oldest first. The log only
+    /// keeps the last `AUDIT_LOG_LEN` entries, so it has to be drained regularly for none to be
+    /// missed. Returns nothing until we are approved.
+    pub fn drain_audit_log(&mut self) -> Vec<AuditEntry> {
+        self.machine
+            .current_mut()
+            .chain_mut()
+            .map(Chain::drain_audit_log)
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of our current state: `"BootstrappingPeer"`, `"JoiningPeer"`, `"Adult"`,
+    /// `"Elder"` or `"Terminated"`.
+    pub fn state_name(&self) -> &'static str {
+        self.machine.state_name()
+    }
+
+    /// Returns how long we have been in our current state.
+    pub fn time_in_state(&self) -> Duration {
+        self.machine.time_in_state()
+    }
+
+    /// Returns our most recent state transitions, oldest first. Only a bounded number of them is
+    /// kept.
+    pub fn transition_history(&self) -> impl Iterator<Item = &TransitionRecord> {
+        self.machine.transition_history()
+    }
+
+    /// Sets the observer to notify of each of our state transitions, replacing the previous one.
+    /// Unlike `transition_history`, it sees every transition, not only the most recent ones.
+    pub fn set_transition_observer<O: TransitionObserver + 'static>(&mut self, observer: O) {
+        self.machine.set_transition_observer(Box::new(observer))
+    }
+
+    /// Returns the number of messages and events we dropped from our backlogs while joining
+    /// because they expired or the backlogs were full. See `NetworkParams::backlog_limits`.
+    pub fn dropped_backlog_entries(&self) -> u64 {
+        self.machine.current().dropped_backlog_entries()
+    }
+
+    /// Returns the numbers of duplicate routing messages we filtered out, by kind of message.
+    /// Returns `None` if we are not yet joining a section. See
+    /// `NodeBuilder::message_filter_limits`.
+    pub fn duplicate_counts(&self) -> Option<DuplicateCounts> {
+        self.machine.current().duplicate_counts()
+    }
+
+    /// Returns the progress of our relocation while we bootstrap to or join our destination
+    /// section, so that it can be resumed with `NodeBuilder::resume_relocation` should we restart
+    /// before being approved there. Returns `None` if we are not being relocated.
+    pub fn relocation_state(&self) -> Option<RelocationState> {
+        self.machine.current().relocation_state()
+    }
+
+    /// Our `Prefix` once we are a part of the section.
+    pub fn our_prefix(&self) -> Option<&Prefix<XorName>> {
+        self.chain().map(Chain::our_prefix)
+    }
+
+    /// Collects prefixes of all sections known by the routing table into a `BTreeSet`.
+    pub fn prefixes(&self) -> BTreeSet<Prefix<XorName>> {
+        self.chain().map(Chain::prefixes).unwrap_or_default()
+    }
+
+    /// Returns the elder of a section with the given prefix.
+    /// Prefix must be either our prefix or of one of our neighbours. Returns empty set otherwise.
+    pub fn section_elders(&self, prefix: &Prefix<XorName>) -> BTreeSet<XorName> {
+        self.chain_view()
+            .map(|view| view.section_elders(prefix))
+            .unwrap_or_default()
+    }
+
+    /// If our section is the closest one to `name`, returns all names in our section *including
+    /// ours*, otherwise returns `None`. Lets the layers above tell whether our section is
+    /// responsible for the data at `name`.
+    pub fn close_names(&self, name: &XorName) -> Option<Vec<XorName>> {
+        self.chain().and_then(|chain| chain.close_names(name))
+    }
+
+    /// Returns the prefix and the member names of the section we know of which is closest to
+    /// `name`, whether or not `name` belongs in it. Returns `None` if we are not yet a member of a
+    /// section.
+    pub fn closest_section(&self, name: &XorName) -> Option<(Prefix<XorName>, BTreeSet<XorName>)> {
+        self.chain().map(|chain| chain.closest_section(name))
+    }
+
+    /// Returns the chain for this node.
+    fn chain(&self) -> Option<&Chain> {
+        self.machine.current().chain()
+    }
+
     fn perform_action(&mut self, action: Action) -> Result<(), InterfaceError> {
         let transition = self
             .machine
@@ -248,31 +904,156 @@ impl Node {
             .handle_action(action, &mut self.event_buffer);
         self.machine
             .apply_transition(transition, &mut self.event_buffer);
+        self.forward_events();
         self.interface_result_rx.recv()?
     }
+
+    // Sends the buffered events to the channel set up with `NodeBuilder::event_channel`, if any.
+    fn forward_events(&mut self) {
+        if let Some((ref event_tx, _)) = self.event_channel {
+            while let Some(event) = self.event_buffer.take_first() {
+                // The node holds a receiver itself, so the channel can't be disconnected.
+                let _ = event_tx.send(event);
+            }
+        }
+    }
 }
 
 impl EventStepper for Node {
     type Item = Event;
 
     fn produce_events(&mut self) -> Result<(), mpmc::RecvError> {
-        self.machine.step(&mut self.event_buffer)
+        self.machine.step(&mut self.event_buffer)?;
+        self.forward_events();
+        Ok(())
     }
 
     fn try_produce_events(&mut self) -> Result<(), mpmc::TryRecvError> {
-        self.machine.try_step(&mut self.event_buffer)
+        self.machine.try_step(&mut self.event_buffer)?;
+        self.forward_events();
+        Ok(())
     }
 
     fn pop_item(&mut self) -> Option<Event> {
-        self.event_buffer.take_first()
+        if let Some((_, ref event_rx)) = self.event_channel {
+            event_rx.try_recv().ok()
+        } else {
+            self.event_buffer.take_first()
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Node {
+    /// Returns a future resolving to the next event, or to `None` once the node has terminated.
+    pub fn next_event(&mut self) -> Next<'_, Self> {
+        self.next()
+    }
+
+    /// Like `send_message`, but returns a future, for use from an async executor.
+    pub async fn send_message_async(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        self.send_message(src, dst, content)
+    }
+
+    /// Like `send_message_to_many`, but returns a future, for use from an async executor.
+    pub async fn send_message_to_many_async(
+        &mut self,
+        src: Authority<XorName>,
+        dsts: &[Authority<XorName>],
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        self.send_message_to_many(src, dsts, content)
+    }
+
+    /// Like `send_request`, but returns a future, for use from an async executor.
+    pub async fn send_request_async(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<MessageId, InterfaceError> {
+        self.send_request(src, dst, content)
+    }
+
+    /// Like `send_response`, but returns a future, for use from an async executor.
+    pub async fn send_response_async(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        token: MessageId,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        self.send_response(src, dst, token, content)
+    }
+
+    /// Like `send_message_to_client`, but returns a future, for use from an async executor.
+    pub async fn send_message_to_client_async(
+        &mut self,
+        peer_addr: SocketAddr,
+        msg: NetworkBytes,
+        token: Token,
+    ) -> Result<(), InterfaceError> {
+        self.send_message_to_client(peer_addr, msg, token)
+    }
+
+    /// Like `disconnect_from_client`, but returns a future, for use from an async executor.
+    pub async fn disconnect_from_client_async(
+        &mut self,
+        peer_addr: SocketAddr,
+    ) -> Result<(), InterfaceError> {
+        self.disconnect_from_client(peer_addr)
+    }
+}
+
+/// The task polling the node is woken by the sends to its state machine: timeouts and other
+/// actions wake it through the `ActionSender`. The transport hands network events over a plain
+/// channel, so those are handled the next time the task is woken.
+#[cfg(feature = "async")]
+impl Stream for Node {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Event>> {
+        let node = self.get_mut();
+        // Register before draining the channels, so a send racing with this poll still wakes us.
+        node.machine.waker().register(cx.waker());
+        loop {
+            if let Some(event) = node.pop_item() {
+                return Poll::Ready(Some(event));
+            }
+
+            match node.try_produce_events() {
+                Ok(()) => (),
+                Err(mpmc::TryRecvError::Empty) => return Poll::Pending,
+                Err(mpmc::TryRecvError::Disconnected) => return Poll::Ready(None),
+            }
+        }
     }
 }
 
 #[cfg(feature = "mock_base")]
 impl Node {
-    /// Returns the chain for this node.
-    fn chain(&self) -> Option<&Chain> {
-        self.machine.current().chain()
+    /// Simulates a crash of this node: its transport is dropped without a graceful shutdown and
+    /// only its identity and chain state survive. Use `Node::resume` to restart it.
+    pub fn crash(self) -> Result<PausedState, RoutingError> {
+        self.machine.crash()
+    }
+
+    /// Like `poll`, but processes at most `max_events` of the pending incoming messages, actions
+    /// and timeouts. Returns whether any were processed. Used to simulate a slow node.
+    pub fn poll_bounded(&mut self, max_events: usize) -> bool {
+        let mut result = false;
+        for _ in 0..max_events {
+            if self.try_produce_events().is_err() {
+                break;
+            }
+            result = true;
+        }
+        result
     }
 
     /// Returns the underlying Elder state.
@@ -295,11 +1076,6 @@ impl Node {
         self.elder_state().is_some()
     }
 
-    /// Our `Prefix` once we are a part of the section.
-    pub fn our_prefix(&self) -> Option<&Prefix<XorName>> {
-        self.chain().map(Chain::our_prefix)
-    }
-
     /// Our `XorName`.
     pub fn our_name(&self) -> Option<&XorName> {
         self.chain().map(|chain| chain.our_id().name())
@@ -307,28 +1083,8 @@ impl Node {
 
     /// Returns the prefixes of all out neighbours signed by our section
     pub fn neighbour_prefixes(&self) -> BTreeSet<Prefix<XorName>> {
-        if let Some(chain) = self.chain() {
-            chain
-                .neighbour_infos()
-                .map(|info| info.prefix())
-                .cloned()
-                .collect()
-        } else {
-            Default::default()
-        }
-    }
-
-    /// Collects prefixes of all sections known by the routing table into a `BTreeSet`.
-    pub fn prefixes(&self) -> BTreeSet<Prefix<XorName>> {
-        self.chain().map(Chain::prefixes).unwrap_or_default()
-    }
-
-    /// Returns the elder of a section with the given prefix.
-    /// Prefix must be either our prefix or of one of our neighbours. Returns empty set otherwise.
-    pub fn section_elders(&self, prefix: &Prefix<XorName>) -> BTreeSet<XorName> {
-        self.chain()
-            .and_then(|chain| chain.get_section(prefix))
-            .map(|info| info.member_names())
+        self.chain_view()
+            .map(|view| view.neighbour_prefixes())
             .unwrap_or_default()
     }
 
@@ -339,18 +1095,11 @@ impl Node {
 
     /// Returns their knowledge
     pub fn get_their_knowledge(&self) -> BTreeMap<Prefix<XorName>, u64> {
-        self.chain()
-            .map(Chain::get_their_knowledge)
-            .cloned()
+        self.chain_view()
+            .map(|view| view.their_knowledge().clone())
             .unwrap_or_default()
     }
 
-    /// If our section is the closest one to `name`, returns all names in our section *including
-    /// ours*, otherwise returns `None`.
-    pub fn close_names(&self, name: &XorName) -> Option<Vec<XorName>> {
-        self.chain().and_then(|chain| chain.close_names(name))
-    }
-
     /// Returns the minimum section size this vault is using.
     /// Only if we have a chain (meaning we are elders) we will process this API
     pub fn min_sec_size(&self) -> Option<usize> {
@@ -379,6 +1128,11 @@ impl Node {
             .map(|state| state.set_next_relocation_interval(interval));
     }
 
+    /// Returns the earliest deadline of the pending timers of this node, if any.
+    pub fn next_timer_deadline(&self) -> Option<Instant> {
+        self.machine.current().next_timer_deadline()
+    }
+
     /// Indicates if there are any pending observations in the parsec object
     pub fn has_unpolled_observations(&self) -> bool {
         self.machine.current().has_unpolled_observations()
@@ -411,6 +1165,12 @@ impl Node {
         self.chain().map(Chain::parsec_prune_accumulated)
     }
 
+    /// Returns the metrics of our current parsec instance: the gossip graph size, the number of
+    /// unpolled blocks and the backlog of our votes. Returns `None` if we don't run parsec yet.
+    pub fn parsec_metrics(&self) -> Option<ParsecMetrics> {
+        self.machine.current().parsec_metrics()
+    }
+
     /// Trigger relocation of the given node to a section matching the given destination address.
     // TODO: this method exist only so we can test relocation until proper relocation trigger is
     // implemented. It should be removed afterwards.
@@ -421,6 +1181,16 @@ impl Node {
     }
 }
 
+#[cfg(feature = "fuzzing")]
+impl Node {
+    /// Handle `msg` right away, as if it was just received from the peer at `peer_addr`.
+    pub(crate) fn inject_message(&mut self, peer_addr: SocketAddr, msg: NetworkBytes) {
+        let event = crate::NetworkEvent::NewMessage { peer_addr, msg };
+        self.machine
+            .inject_network_event(event, &mut self.event_buffer)
+    }
+}
+
 #[cfg(feature = "mock_base")]
 impl Display for Node {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
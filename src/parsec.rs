@@ -9,7 +9,7 @@
 #[cfg(feature = "mock_parsec")]
 use crate::mock::parsec as inner;
 use crate::{
-    chain::{self, GenesisPfxInfo},
+    chain::{self, AccumulatingEvent, GenesisPfxInfo},
     id::{self, FullId},
     messages::DirectMessage,
     utils::{self, LogIdent},
@@ -20,7 +20,11 @@ use maidsafe_utilities::serialisation;
 use parsec as inner;
 use std::{
     collections::{btree_map::Entry, BTreeMap},
-    fmt, mem,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    path::{Path, PathBuf},
 };
 
 #[cfg(feature = "mock_parsec")]
@@ -48,20 +52,61 @@ const PARSEC_SIZE_LIMIT: u64 = 200_000;
 #[cfg(feature = "mock_parsec")]
 const PARSEC_SIZE_LIMIT: u64 = 100;
 
+/// The thresholds beyond which the elders vote for pruning the current parsec instance. Any of
+/// them being exceeded triggers a `ParsecPrune` vote; `None` disables a threshold.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrunePolicy {
+    /// Estimated memory taken by the gossip graph, in bytes of gossip handled.
+    pub max_gossip_size: Option<u64>,
+    /// Number of gossip messages handled, which grows with the size of the gossip graph.
+    pub max_gossip_messages: Option<u64>,
+    /// Number of blocks consensused.
+    pub max_blocks: Option<u64>,
+}
+
+impl Default for PrunePolicy {
+    fn default() -> Self {
+        Self {
+            max_gossip_size: Some(PARSEC_SIZE_LIMIT),
+            max_gossip_messages: None,
+            max_blocks: None,
+        }
+    }
+}
+
+fn exceeds(value: u64, limit: Option<u64>) -> bool {
+    limit.map_or(false, |limit| value > limit)
+}
+
 // Keep track of size in case we need to prune.
 #[derive(Default, Debug, PartialEq, Eq)]
 struct ParsecSizeCounter {
     size_counter: u64,
+    message_counter: u64,
+    block_counter: u64,
+    pruning_requested: bool,
     pruning_voted_for: bool,
 }
 
 impl ParsecSizeCounter {
     fn increase_size(&mut self, size: u64) {
         self.size_counter += size;
+        self.message_counter += 1;
     }
 
-    fn needs_pruning(&self) -> bool {
-        self.size_counter > PARSEC_SIZE_LIMIT && !self.pruning_voted_for
+    fn increase_blocks(&mut self) {
+        self.block_counter += 1;
+    }
+
+    fn needs_pruning(&self, policy: &PrunePolicy) -> bool {
+        let over_limit = exceeds(self.size_counter, policy.max_gossip_size)
+            || exceeds(self.message_counter, policy.max_gossip_messages)
+            || exceeds(self.block_counter, policy.max_blocks);
+        (over_limit || self.pruning_requested) && !self.pruning_voted_for
+    }
+
+    fn set_pruning_requested(&mut self) {
+        self.pruning_requested = true;
     }
 
     fn set_pruning_voted_for(&mut self) {
@@ -75,13 +120,177 @@ impl fmt::Display for ParsecSizeCounter {
     }
 }
 
+// Write-ahead log of the votes we cast, keyed by the parsec version they were cast for. Every vote
+// is recorded here before it is handed to parsec, so that after an unclean restart we can detect
+// and refuse to cast a vote which would conflict with one we cast before. Without a file, set
+// through `NodeBuilder::vote_log_path`, the log only lives as long as the node.
+//
+// The file is a sequence of records, each the serialised version and vote prefixed with its
+// length as four little-endian bytes. A record is synced to disk before its vote is cast.
+#[derive(Default, Debug)]
+struct VoteLog {
+    entries: BTreeMap<u64, Vec<chain::NetworkEvent>>,
+    file: Option<(PathBuf, File)>,
+}
+
+impl VoteLog {
+    // Opens the log kept in the file at `path`, creating it if needed, and loads the votes it
+    // records.
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        let _ = file.read_to_end(&mut bytes)?;
+
+        let mut entries = BTreeMap::new();
+        let mut valid_len = 0;
+        while let Some((record_len, (version, event))) = read_record(&bytes[valid_len..]) {
+            entries.entry(version).or_insert_with(Vec::new).push(event);
+            valid_len += record_len;
+        }
+
+        // A record cut short by a crash while it was appended is dropped: its vote was never cast.
+        if valid_len < bytes.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            entries,
+            file: Some((path.to_path_buf(), file)),
+        })
+    }
+
+    // Returns the vote we previously cast for `version` which conflicts with `event`, if any.
+    fn find_conflict(
+        &self,
+        version: u64,
+        event: &chain::NetworkEvent,
+    ) -> Option<&chain::NetworkEvent> {
+        self.entries
+            .get(&version)?
+            .iter()
+            .find(|logged| is_conflicting_vote(logged, event))
+    }
+
+    // Records the vote, on disk first if we have a file. The vote must not be cast if this fails.
+    fn record(&mut self, version: u64, event: chain::NetworkEvent) -> io::Result<()> {
+        if let Some((_, ref mut file)) = self.file {
+            file.write_all(&write_record(version, &event)?)?;
+            file.sync_data()?;
+        }
+
+        self.entries
+            .entry(version)
+            .or_insert_with(Vec::new)
+            .push(event);
+        Ok(())
+    }
+
+    // Drop the entries for all versions older than `oldest_version`. The file is replaced by one
+    // with only the remaining entries, so that a crash leaves either the old or the new one.
+    fn prune(&mut self, oldest_version: u64) -> io::Result<()> {
+        let remaining = self.entries.split_off(&oldest_version);
+        let pruned = mem::replace(&mut self.entries, remaining);
+
+        let path = match self.file {
+            Some((ref path, _)) if !pruned.is_empty() => path.clone(),
+            _ => return Ok(()),
+        };
+
+        let tmp_path = path.with_extension("tmp");
+        let mut tmp_file = File::create(&tmp_path)?;
+        for (version, events) in &self.entries {
+            for event in events {
+                tmp_file.write_all(&write_record(*version, event)?)?;
+            }
+        }
+        tmp_file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+
+        let file = OpenOptions::new().read(true).append(true).open(&path)?;
+        self.file = Some((path, file));
+        Ok(())
+    }
+}
+
+fn write_record(version: u64, event: &chain::NetworkEvent) -> io::Result<Vec<u8>> {
+    let payload = serialisation::serialise(&(version, event))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))?;
+    let mut record = Vec::with_capacity(4 + payload.len());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+// Returns the length of the record at the start of `bytes` and its contents, or `None` if there
+// is no complete record there.
+fn read_record(bytes: &[u8]) -> Option<(usize, (u64, chain::NetworkEvent))> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let mut len_bytes = [0; 4];
+    len_bytes.copy_from_slice(&bytes[..4]);
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    let payload = bytes.get(4..4 + len)?;
+    let contents = serialisation::deserialise(payload).ok()?;
+    Some((4 + len, contents))
+}
+
+// Two votes conflict if they are identical (voting twice is pointless at best) or if they are
+// `SectionInfo`s for the same prefix and version, but with different contents (equivocation).
+fn is_conflicting_vote(lhs: &chain::NetworkEvent, rhs: &chain::NetworkEvent) -> bool {
+    if lhs == rhs {
+        return true;
+    }
+
+    match (lhs.payload.elders_info(), rhs.payload.elders_info()) {
+        (Some(lhs), Some(rhs)) => lhs.prefix() == rhs.prefix() && lhs.version() == rhs.version(),
+        _ => false,
+    }
+}
+
+/// Metrics of the current parsec instance of a node.
+#[cfg(feature = "mock_base")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ParsecMetrics {
+    /// Estimated size (in bytes) of the gossip graph, based on the gossip handled since the
+    /// current parsec instance was created.
+    pub gossip_size: u64,
+    /// Number of consensused blocks not polled yet. Only available with mock parsec.
+    pub unpolled_blocks: Option<usize>,
+    /// Number of our own votes which haven't been polled yet.
+    pub observation_backlog: usize,
+}
+
 pub struct ParsecMap {
     map: BTreeMap<u64, Parsec>,
     size_counter: ParsecSizeCounter,
+    prune_policy: PrunePolicy,
+    vote_log: VoteLog,
 }
 
 impl ParsecMap {
-    pub fn new(full_id: FullId, gen_pfx_info: &GenesisPfxInfo) -> Self {
+    /// Creates the map with the parsec of `gen_pfx_info`. The votes we cast are logged to the file
+    /// at `vote_log_path`, if any, and the ones it already records are never contradicted.
+    pub fn new(
+        full_id: FullId,
+        gen_pfx_info: &GenesisPfxInfo,
+        prune_policy: PrunePolicy,
+        vote_log_path: Option<&PathBuf>,
+    ) -> Self {
+        let vote_log = vote_log_path.map_or_else(
+            || Ok(VoteLog::default()),
+            |path| {
+                VoteLog::open(path).map_err(|err| {
+                    error!("Failed to open the vote log {:?}: {:?}", path, err);
+                })
+            },
+        );
+
         let mut map = BTreeMap::new();
         let _ = map.insert(
             *gen_pfx_info.first_info.version(),
@@ -89,12 +298,17 @@ impl ParsecMap {
         );
         let size_counter = ParsecSizeCounter::default();
 
-        Self { map, size_counter }
+        Self {
+            map,
+            size_counter,
+            prune_policy,
+            vote_log: vote_log.unwrap_or_default(),
+        }
     }
 
     pub fn init(&mut self, full_id: FullId, gen_pfx_info: &GenesisPfxInfo, log_ident: &LogIdent) {
         self.add_new(full_id, gen_pfx_info, log_ident);
-        self.remove_old();
+        self.remove_old(log_ident);
     }
 
     pub fn handle_request(
@@ -163,18 +377,67 @@ impl ParsecMap {
     }
 
     pub fn vote_for(&mut self, event: chain::NetworkEvent, log_ident: &LogIdent) {
-        if let Some(ref mut parsec) = self.map.values_mut().last() {
-            let obs = match event.into_obs() {
-                Err(_) => {
-                    warn!(
-                        "{} - Failed to convert NetworkEvent to Parsec Observation.",
-                        log_ident
-                    );
-                    return;
+        self.vote_for_many(vec![event], log_ident)
+    }
+
+    /// Votes for all the `events` in a single observation, to cut the gossip overhead when we have
+    /// many events to vote for at once. Adding and removing elders are still voted for on their
+    /// own, as parsec needs to see them as separate observations.
+    pub fn vote_for_many(&mut self, events: Vec<chain::NetworkEvent>, log_ident: &LogIdent) {
+        let version = if let Some(version) = self.map.keys().last() {
+            *version
+        } else {
+            return;
+        };
+
+        let mut batch = Vec::new();
+        for event in events {
+            if let Some(previous) = self.vote_log.find_conflict(version, &event) {
+                warn!(
+                    "{} - Not voting for {:?} as it conflicts with our previous vote {:?}.",
+                    log_ident, event, previous
+                );
+                continue;
+            }
+            if let Err(err) = self.vote_log.record(version, event.clone()) {
+                error!(
+                    "{} - Not voting for {:?} as it couldn't be logged: {:?}.",
+                    log_ident, event, err
+                );
+                continue;
+            }
+
+            match event.payload {
+                AccumulatingEvent::AddElder(_) | AccumulatingEvent::RemoveElder(_) => {
+                    self.cast_vote(version, event, log_ident)
                 }
-                Ok(obs) => obs,
-            };
+                _ => batch.push(event),
+            }
+        }
+
+        let event = if batch.len() > 1 {
+            AccumulatingEvent::Batch(batch).into_network_event()
+        } else if let Some(event) = batch.pop() {
+            event
+        } else {
+            return;
+        };
+        self.cast_vote(version, event, log_ident)
+    }
 
+    fn cast_vote(&mut self, version: u64, event: chain::NetworkEvent, log_ident: &LogIdent) {
+        let obs = match event.into_obs() {
+            Err(_) => {
+                warn!(
+                    "{} - Failed to convert NetworkEvent to Parsec Observation.",
+                    log_ident
+                );
+                return;
+            }
+            Ok(obs) => obs,
+        };
+
+        if let Some(parsec) = self.map.get_mut(&version) {
             if let Err(err) = parsec.vote_for(obs) {
                 trace!("{} - Parsec vote error: {:?}", log_ident, err);
             }
@@ -199,7 +462,9 @@ impl ParsecMap {
     }
 
     pub fn poll(&mut self) -> Option<Block> {
-        self.map.values_mut().last().and_then(Parsec::poll)
+        let block = self.map.values_mut().last().and_then(Parsec::poll)?;
+        self.size_counter.increase_blocks();
+        Some(block)
     }
 
     pub fn our_unpolled_observations(
@@ -224,8 +489,27 @@ impl ParsecMap {
         parsec.has_unpolled_observations()
     }
 
+    #[cfg(feature = "mock_base")]
+    pub fn metrics(&self) -> ParsecMetrics {
+        #[cfg(feature = "mock_parsec")]
+        let unpolled_blocks = self.map.values().last().map(Parsec::unpolled_block_count);
+        #[cfg(not(feature = "mock_parsec"))]
+        let unpolled_blocks = None;
+
+        ParsecMetrics {
+            gossip_size: self.size_counter.size_counter,
+            unpolled_blocks,
+            observation_backlog: self.our_unpolled_observations().count(),
+        }
+    }
+
     pub fn needs_pruning(&self) -> bool {
-        self.size_counter.needs_pruning()
+        self.size_counter.needs_pruning(&self.prune_policy)
+    }
+
+    /// Makes `needs_pruning` return true regardless of the thresholds, until we vote for pruning.
+    pub fn request_pruning(&mut self) {
+        self.size_counter.set_pruning_requested();
     }
 
     pub fn set_pruning_voted_for(&mut self) {
@@ -236,10 +520,10 @@ impl ParsecMap {
         if self.last_version() == msg_version && self.map.contains_key(&msg_version) {
             self.size_counter.increase_size(size);
             trace!(
-                "{} - Parsec size is now estimated to: {} / {}.",
+                "{} - Parsec size is now estimated to: {} / {:?}.",
                 log_ident,
                 self.size_counter,
-                PARSEC_SIZE_LIMIT,
+                self.prune_policy.max_gossip_size,
             );
         }
     }
@@ -255,7 +539,7 @@ impl ParsecMap {
         }
     }
 
-    fn remove_old(&mut self) {
+    fn remove_old(&mut self, log_ident: &LogIdent) {
         let parsec_map = mem::replace(&mut self.map, Default::default());
         self.map = parsec_map
             .into_iter()
@@ -263,6 +547,12 @@ impl ParsecMap {
             .take(MAX_PARSECS)
             .rev()
             .collect();
+
+        if let Some(&oldest_version) = self.map.keys().next() {
+            if let Err(err) = self.vote_log.prune(oldest_version) {
+                warn!("{} - Failed to prune the vote log: {:?}", log_ident, err);
+            }
+        }
     }
 }
 
@@ -301,23 +591,53 @@ fn create(full_id: FullId, gen_pfx_info: &GenesisPfxInfo) -> Parsec {
 mod tests {
     use super::*;
     use crate::{
-        chain::{EldersInfo, MIN_AGE_COUNTER},
+        chain::{AccumulatingEvent, EldersInfo, MIN_AGE_COUNTER},
         routing_table::Prefix,
         xor_name::XorName,
     };
     use serde::Serialize;
+    use std::{collections::BTreeSet, env};
     use unwrap::unwrap;
 
     const DEFAULT_MIN_SECTION_SIZE: usize = 4;
 
     #[test]
     fn parsec_size_counter() {
+        let policy = PrunePolicy::default();
         let mut counter = ParsecSizeCounter::default();
-        assert!(!counter.needs_pruning());
+        assert!(!counter.needs_pruning(&policy));
         counter.increase_size(PARSEC_SIZE_LIMIT);
-        assert!(!counter.needs_pruning());
+        assert!(!counter.needs_pruning(&policy));
         counter.increase_size(1);
-        assert!(counter.needs_pruning());
+        assert!(counter.needs_pruning(&policy));
+    }
+
+    #[test]
+    fn prune_policy_thresholds() {
+        let policy = PrunePolicy {
+            max_gossip_size: None,
+            max_gossip_messages: Some(2),
+            max_blocks: Some(1),
+        };
+
+        let mut counter = ParsecSizeCounter::default();
+        counter.increase_size(PARSEC_SIZE_LIMIT + 1);
+        counter.increase_size(1);
+        assert!(!counter.needs_pruning(&policy));
+        counter.increase_size(1);
+        assert!(counter.needs_pruning(&policy));
+
+        let mut counter = ParsecSizeCounter::default();
+        counter.increase_blocks();
+        assert!(!counter.needs_pruning(&policy));
+        counter.increase_blocks();
+        assert!(counter.needs_pruning(&policy));
+
+        let mut counter = ParsecSizeCounter::default();
+        counter.set_pruning_requested();
+        assert!(counter.needs_pruning(&policy));
+        counter.set_pruning_voted_for();
+        assert!(!counter.needs_pruning(&policy));
     }
 
     fn create_full_ids() -> Vec<FullId> {
@@ -339,6 +659,7 @@ mod tests {
             .map(|pub_id| (*pub_id, MIN_AGE_COUNTER))
             .collect();
         GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
             first_info: elders_info,
             first_state_serialized: Vec::new(),
             first_ages,
@@ -352,7 +673,7 @@ mod tests {
         let full_id = full_ids[0].clone();
 
         let gen_pfx_info = create_gen_pfx_info(full_ids.clone(), 0);
-        let mut parsec_map = ParsecMap::new(full_id.clone(), &gen_pfx_info);
+        let mut parsec_map = ParsecMap::new(full_id.clone(), &gen_pfx_info, PrunePolicy::default());
 
         for parsec_no in 1..=size {
             let gen_pfx_info = create_gen_pfx_info(full_ids.clone(), parsec_no);
@@ -483,6 +804,81 @@ mod tests {
         assert_eq!(parsec_map.needs_pruning(), false);
     }
 
+    #[test]
+    fn vote_log_detects_conflicting_votes() {
+        let full_ids = create_full_ids();
+        let members: BTreeSet<_> = full_ids.iter().map(|id| *id.public_id()).collect();
+        let info = unwrap!(EldersInfo::new_for_test(
+            members.clone(),
+            Prefix::<XorName>::default(),
+            1
+        ));
+        let other_info = unwrap!(EldersInfo::new_for_test(
+            members.into_iter().skip(1).collect(),
+            Prefix::<XorName>::default(),
+            1
+        ));
+        let next_info = unwrap!(EldersInfo::new_for_test(
+            info.members().clone(),
+            Prefix::<XorName>::default(),
+            2
+        ));
+
+        let mut vote_log = VoteLog::default();
+        unwrap!(vote_log.record(
+            0,
+            AccumulatingEvent::SectionInfo(info.clone()).into_network_event(),
+        ));
+
+        // Same vote again, or a different one for the same prefix and version, conflicts.
+        let same = AccumulatingEvent::SectionInfo(info).into_network_event();
+        assert!(vote_log.find_conflict(0, &same).is_some());
+        let other = AccumulatingEvent::SectionInfo(other_info).into_network_event();
+        assert!(vote_log.find_conflict(0, &other).is_some());
+
+        // Votes for a different version, or cast in a different parsec, do not.
+        let next = AccumulatingEvent::SectionInfo(next_info).into_network_event();
+        assert!(vote_log.find_conflict(0, &next).is_none());
+        assert!(vote_log.find_conflict(1, &other).is_none());
+
+        unwrap!(vote_log.prune(1));
+        assert!(vote_log.find_conflict(0, &same).is_none());
+    }
+
+    #[test]
+    fn vote_log_survives_restart() {
+        let path = env::temp_dir().join(format!("routing_vote_log_{}", rand::random::<u64>()));
+        let first = AccumulatingEvent::User(vec![1]).into_network_event();
+        let second = AccumulatingEvent::User(vec![2]).into_network_event();
+
+        {
+            let mut vote_log = unwrap!(VoteLog::open(&path));
+            unwrap!(vote_log.record(0, first.clone()));
+            unwrap!(vote_log.record(1, second.clone()));
+        }
+
+        // A record cut short by a crash is dropped, the ones before it are kept.
+        let mut bytes = unwrap!(fs::read(&path));
+        bytes.extend_from_slice(&unwrap!(write_record(1, &first))[..5]);
+        unwrap!(fs::write(&path, bytes));
+
+        let mut vote_log = unwrap!(VoteLog::open(&path));
+        assert!(vote_log.find_conflict(0, &first).is_some());
+        assert!(vote_log.find_conflict(1, &second).is_some());
+        assert!(vote_log.find_conflict(1, &first).is_none());
+
+        // Pruned votes are gone from the file too.
+        unwrap!(vote_log.prune(1));
+        unwrap!(vote_log.record(1, first.clone()));
+        drop(vote_log);
+        let vote_log = unwrap!(VoteLog::open(&path));
+        assert!(vote_log.find_conflict(0, &first).is_none());
+        assert!(vote_log.find_conflict(1, &first).is_some());
+        assert!(vote_log.find_conflict(1, &second).is_some());
+
+        unwrap!(fs::remove_file(&path));
+    }
+
     #[test]
     fn prune_required_is_reset_on_voting() {
         let parsec_age = 0;
@@ -492,4 +888,32 @@ mod tests {
         parsec_map.set_pruning_voted_for();
         assert_eq!(parsec_map.needs_pruning(), false);
     }
+
+    #[test]
+    fn vote_for_many_batches_votes() {
+        let log_ident = LogIdent::new("node");
+        let mut parsec_map = create_parsec_map(0);
+        let new_elder = *FullId::new().public_id();
+
+        let first = AccumulatingEvent::User(vec![1]).into_network_event();
+        let second = AccumulatingEvent::User(vec![2]).into_network_event();
+        let events = vec![
+            first.clone(),
+            second.clone(),
+            AccumulatingEvent::AddElder(new_elder).into_network_event(),
+            // Duplicates are not voted for again.
+            first.clone(),
+        ];
+        parsec_map.vote_for_many(events, &log_ident);
+
+        // Adding an elder is still voted for on its own, the rest are batched.
+        let observations: Vec<_> = parsec_map.our_unpolled_observations().cloned().collect();
+        assert_eq!(observations.len(), 2);
+        assert!(observations.iter().any(|obs| match obs {
+            Observation::Add { peer_id, .. } => *peer_id == new_elder,
+            _ => false,
+        }));
+        let batch = AccumulatingEvent::Batch(vec![first, second]).into_network_event();
+        assert!(observations.contains(&Observation::OpaquePayload(batch)));
+    }
 }
@@ -7,14 +7,20 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chain::{Chain, GenesisPfxInfo},
+    chain::{AccumulatorState, Chain, GenesisPfxInfo},
+    chunking::Reassembler,
+    event::Event,
     id::FullId,
-    messages::SignedRoutingMessage,
+    messages::{RelocatePayload, SignedRelocateDetails, SignedRoutingMessage},
     parsec::ParsecMap,
     peer_map::PeerMap,
     routing_message_filter::RoutingMessageFilter,
+    routing_table::Authority,
     signature_accumulator::SignatureAccumulator,
-    NetworkEvent, NetworkService,
+    streaming::{IncomingStreams, OutgoingStreams},
+    types::MessageId,
+    xor_name::XorName,
+    ConnectionInfo, NetworkEvent, NetworkParams, NetworkService,
 };
 use crossbeam_channel as mpmc;
 
@@ -27,15 +33,141 @@ use crossbeam_channel as mpmc;
 // able to create a value of this type in routing version X and use it to resume the node in any
 // version >= X.
 pub struct PausedState {
-    pub(super) chain: Chain,
+    pub(super) common: PausedCommon,
+    pub(super) network_rx: Option<mpmc::Receiver<NetworkEvent>>,
+    pub(super) stage: PausedStage,
+}
+
+// The state shared by all the states a node can be paused in.
+pub(super) struct PausedCommon {
     pub(super) full_id: FullId,
-    pub(super) gen_pfx_info: GenesisPfxInfo,
     pub(super) msg_filter: RoutingMessageFilter,
+    // The routing messages we received but didn't handle yet.
     pub(super) msg_queue: Vec<SignedRoutingMessage>,
     // TODO: instead of storing both network_service and network_rx, store only the network config.
     pub(super) network_service: NetworkService,
-    pub(super) network_rx: Option<mpmc::Receiver<NetworkEvent>>,
-    pub(super) parsec_map: ParsecMap,
     pub(super) peer_map: PeerMap,
+}
+
+// The state specific to the state the node was paused in.
+pub(super) enum PausedStage {
+    JoiningPeer(PausedJoiningPeer),
+    Adult(PausedAdult),
+    Elder(PausedElder),
+}
+
+pub(super) struct PausedJoiningPeer {
+    pub(super) network_params: NetworkParams,
+    pub(super) conn_infos: Vec<ConnectionInfo>,
+    pub(super) relocate_payload: Option<RelocatePayload>,
+    pub(super) relocation: Option<RelocationState>,
+}
+
+pub(super) struct PausedAdult {
+    pub(super) chain: Chain,
+    // Kept apart from the chain as, unlike the rest of it, it is already serialisable.
+    pub(super) chain_accumulator: AccumulatorState,
+    pub(super) event_backlog: Vec<Event>,
+    pub(super) gen_pfx_info: GenesisPfxInfo,
+    pub(super) parsec_map: ParsecMap,
+    // Whether we were still waiting to be added to our section.
+    pub(super) awaiting_add: bool,
+}
+
+pub(super) struct PausedElder {
+    pub(super) chain: Chain,
+    // Kept apart from the chain as, unlike the rest of it, it is already serialisable.
+    pub(super) chain_accumulator: AccumulatorState,
+    pub(super) gen_pfx_info: GenesisPfxInfo,
+    pub(super) parsec_map: ParsecMap,
     pub(super) sig_accumulator: SignatureAccumulator,
+    // The streams we were sending, with the parts waiting for credit from their destinations.
+    pub(super) outgoing_streams: OutgoingStreams,
+    // The parts received so far of the split messages and of the streams addressed to us.
+    pub(super) reassembler: Reassembler,
+    pub(super) incoming_streams: IncomingStreams,
+    // The messages we sent with acknowledgement and the requests we sent, still waiting for their
+    // acknowledgement or response. They are sent again and their timeouts restart when we resume.
+    pub(super) pending_deliveries: Vec<PendingMessage>,
+    pub(super) pending_requests: Vec<PendingMessage>,
+}
+
+/// A user message or request we sent, kept until it is acknowledged or responded to so it can be
+/// sent again if we are paused meanwhile.
+pub(crate) struct PendingMessage {
+    pub src: Authority<XorName>,
+    pub dst: Authority<XorName>,
+    pub content: Vec<u8>,
+    /// The id of the message, or the token of the request.
+    pub id: MessageId,
+}
+
+/// The progress of a node being relocated to another section, obtained with
+/// `Node::relocation_state` while the node bootstraps or joins its destination section. If the
+/// node restarts before it is approved there, passing this to `NodeBuilder::resume_relocation`
+/// resumes the relocation instead of joining as a new node, keeping the age granted to it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RelocationState {
+    // Our id in the source section, which signs our new id in the destination section.
+    pub(crate) full_id: FullId,
+    pub(crate) details: SignedRelocateDetails,
+    // The contacts in the destination section we bootstrap to.
+    pub(crate) conn_infos: Vec<ConnectionInfo>,
+}
+
+impl RelocationState {
+    /// Name the destination section matches.
+    pub fn destination(&self) -> &XorName {
+        &self.details.content().destination
+    }
+
+    /// Age the node will have in the destination section.
+    pub fn age(&self) -> u8 {
+        self.details.content().age
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        messages::{RelocateDetails, SecurityMetadata},
+        routing_table::Authority,
+    };
+    use maidsafe_utilities::serialisation::{deserialise, serialise};
+    use unwrap::unwrap;
+
+    #[test]
+    fn relocation_state_serialisation() {
+        let full_id = FullId::new();
+        let destination = rand::random();
+        let details = SignedRelocateDetails::new(
+            RelocateDetails {
+                pub_id: *full_id.public_id(),
+                destination,
+                age: 5,
+            },
+            Authority::Section(*full_id.public_id().name()),
+            Authority::Node(*full_id.public_id().name()),
+            SecurityMetadata::None,
+        );
+        let conn_info = ConnectionInfo {
+            peer_addr: ([127, 0, 0, 1], 5483).into(),
+            peer_cert_der: vec![],
+        };
+        let state = RelocationState {
+            full_id,
+            details,
+            conn_infos: vec![conn_info.clone()],
+        };
+
+        let serialised = unwrap!(serialise(&state));
+        let parsed: RelocationState = unwrap!(deserialise(&serialised));
+
+        assert_eq!(parsed.destination(), &destination);
+        assert_eq!(parsed.age(), 5);
+        assert_eq!(parsed.full_id.public_id(), state.full_id.public_id());
+        assert!(parsed.details == state.details);
+        assert_eq!(parsed.conn_infos, vec![conn_info]);
+    }
 }
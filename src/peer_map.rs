@@ -6,9 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{id::PublicId, xor_name::XorName, ConnectionInfo};
+use crate::{
+    id::PublicId,
+    local_params::InboundLimits,
+    messages::connection_nonce_clock,
+    time::{Duration, Instant},
+    xor_name::XorName,
+    ConnectionInfo,
+};
 use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
-use std::{collections::hash_map::Entry, net::SocketAddr};
+use std::{
+    cmp,
+    collections::hash_map::Entry,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Maximum difference (in milliseconds) between the nonce of a connection and our clock for a
+/// peer to be accepted over that connection, unless it is the connection we already know the peer
+/// at. We only remember the nonces within it, as older ones are rejected anyway.
+const CONNECTION_NONCE_WINDOW_MS: u64 = 10 * 60 * 1000;
 
 /// This structure holds the bi-directional association between peers public id and their network
 /// connection info. This association can be create in two ways:
@@ -19,12 +35,29 @@ use std::{collections::hash_map::Entry, net::SocketAddr};
 ///    the network layer, call `connect`. Then when their public id is received, call `identify`.
 ///    This happens when the peer connects to us and then sends us a message which contains their
 ///    public id.
+///
+/// It also tracks the messages each IP address sends us, against the budgets of `InboundLimits`,
+/// so a peer can't renew its budget by reconnecting from another port.
+///
+/// Finally, it holds the nonces binding the direct messages we exchange with a peer to our
+/// connection to it: the one we sign our messages to each connection with, and the one of the
+/// connection we last accepted messages signed by each peer from.
 #[derive(Default)]
 pub struct PeerMap {
     forward: HashMap<XorName, ConnectionInfo>,
     reverse: HashMap<SocketAddr, HashSet<PublicId>>,
     pending: HashMap<SocketAddr, PendingConnection>,
     clients: HashSet<SocketAddr>,
+    inbound_limits: InboundLimits,
+    // Limits replacing `inbound_limits` for individual clients.
+    client_limits: HashMap<SocketAddr, InboundLimits>,
+    // Public ids the clients identified themselves with.
+    client_ids: HashMap<SocketAddr, PublicId>,
+    inbound: HashMap<IpAddr, InboundUsage>,
+    blacklist: HashMap<IpAddr, (Instant, Duration)>,
+    our_nonces: HashMap<SocketAddr, u64>,
+    last_our_nonce: u64,
+    their_nonces: HashMap<XorName, (SocketAddr, u64)>,
 }
 
 impl PeerMap {
@@ -32,6 +65,13 @@ impl PeerMap {
         Self::default()
     }
 
+    pub fn with_inbound_limits(inbound_limits: InboundLimits) -> Self {
+        Self {
+            inbound_limits,
+            ..Self::default()
+        }
+    }
+
     // Marks the connection as established at the network layer.
     // TODO: remove this `allow` when https://github.com/rust-lang/rust-clippy/issues/4219
     // is fixed and stabilized.
@@ -49,6 +89,8 @@ impl PeerMap {
     // public ids associated with that connection, if any.
     pub fn disconnect(&mut self, socket_addr: SocketAddr) -> Vec<PublicId> {
         let _ = self.pending.remove(&socket_addr);
+        let _ = self.our_nonces.remove(&socket_addr);
+        self.remove_stale_inbound();
         let removed_pub_ids: Vec<_> = self
             .reverse
             .remove(&socket_addr)
@@ -60,6 +102,7 @@ impl PeerMap {
             let _ = self.forward.remove(pub_id.name());
         }
 
+        self.remove_stale_nonces();
         removed_pub_ids
     }
 
@@ -153,6 +196,16 @@ impl PeerMap {
             .and_then(|pub_ids| pub_ids.iter().find(|pub_id| pub_id.name() == name))
     }
 
+    // Get the PublicId of the peer at `socket_addr`, unless there are several or none.
+    pub fn get_id_at(&self, socket_addr: &SocketAddr) -> Option<&PublicId> {
+        let pub_ids = self.reverse.get(socket_addr)?;
+        if pub_ids.len() == 1 {
+            pub_ids.iter().next()
+        } else {
+            None
+        }
+    }
+
     // Returns an iterator over the public IDs of connected peers
     pub fn connected_ids(&self) -> impl Iterator<Item = &PublicId> {
         self.reverse.values().flatten()
@@ -171,12 +224,167 @@ impl PeerMap {
     // Inserts a new client entry
     pub fn remove_client(&mut self, peer_addr: &SocketAddr) {
         let _ = self.clients.remove(&peer_addr);
+        let _ = self.client_limits.remove(&peer_addr);
+        let _ = self.client_ids.remove(&peer_addr);
+    }
+
+    // Records the public id the client at `peer_addr` identified itself with, until it is removed.
+    pub fn identify_client(&mut self, peer_addr: SocketAddr, client_id: PublicId) {
+        let _ = self.client_ids.insert(peer_addr, client_id);
+    }
+
+    // Returns the public id the client at `peer_addr` identified itself with, if any.
+    pub fn client_id(&self, peer_addr: &SocketAddr) -> Option<&PublicId> {
+        self.client_ids.get(peer_addr)
+    }
+
+    // Sets the limits of the client at `peer_addr`, replacing the default ones until it is
+    // removed.
+    pub fn set_client_limits(&mut self, peer_addr: SocketAddr, limits: InboundLimits) {
+        let _ = self.client_limits.insert(peer_addr, limits);
+    }
+
+    fn inbound_limits(&self, peer_addr: &SocketAddr) -> InboundLimits {
+        self.client_limits
+            .get(peer_addr)
+            .cloned()
+            .unwrap_or(self.inbound_limits)
     }
 
     // Return true if we know of that peer as a client
     pub fn is_known_client(&self, peer_addr: &SocketAddr) -> bool {
         self.clients.contains(peer_addr)
     }
+
+    // Returns the number of clients connected to us
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    // Records a message of `len` bytes received from the peer at `peer_addr`. Returns `false` if
+    // the peers at its IP address exceeded their budget for the current window.
+    pub fn record_inbound(&mut self, peer_addr: SocketAddr, len: usize) -> bool {
+        let limits = self.inbound_limits(&peer_addr);
+        let usage = self
+            .inbound
+            .entry(peer_addr.ip())
+            .or_insert_with(InboundUsage::new);
+        if usage.window_start.elapsed() >= limits.window {
+            *usage = InboundUsage::new();
+        }
+
+        usage.messages = usage.messages.saturating_add(1);
+        usage.bytes = usage.bytes.saturating_add(len);
+        usage.messages <= limits.max_messages && usage.bytes <= limits.max_bytes
+    }
+
+    // Blacklists the IP address of the peer at `peer_addr`, if our limits say to. Returns whether
+    // it was blacklisted.
+    pub fn blacklist(&mut self, peer_addr: SocketAddr) -> bool {
+        if let Some(duration) = self.inbound_limits(&peer_addr).blacklist_duration {
+            let _ = self
+                .blacklist
+                .insert(peer_addr.ip(), (Instant::now(), duration));
+            true
+        } else {
+            false
+        }
+    }
+
+    // Returns `true` if the IP address of the peer at `peer_addr` is blacklisted.
+    pub fn is_blacklisted(&mut self, peer_addr: &SocketAddr) -> bool {
+        let ip = peer_addr.ip();
+        match self.blacklist.get(&ip) {
+            Some((since, duration)) if since.elapsed() < *duration => true,
+            Some(_) => {
+                let _ = self.blacklist.remove(&ip);
+                false
+            }
+            None => false,
+        }
+    }
+
+    // Returns the nonce we sign the direct messages we send over our connection to `peer_addr`
+    // with, picking a new one the first time we use the connection. It is our clock then, unless
+    // that isn't greater than the previous one, so that a peer we reconnect to can tell the new
+    // connection from the old one.
+    pub fn our_connection_nonce(&mut self, peer_addr: SocketAddr) -> u64 {
+        if let Some(nonce) = self.our_nonces.get(&peer_addr) {
+            return *nonce;
+        }
+
+        let nonce = cmp::max(self.last_our_nonce + 1, connection_nonce_clock());
+        self.last_our_nonce = nonce;
+        let _ = self.our_nonces.insert(peer_addr, nonce);
+        nonce
+    }
+
+    // Returns whether to accept a direct message signed by `pub_id` with the connection `nonce`,
+    // received from `peer_addr`. It is accepted if it comes from the connection we last accepted
+    // the peer's messages from, or if it comes from a newer and recent one, in which case the peer
+    // has reconnected, possibly from another address. Messages replayed from another connection
+    // are rejected.
+    pub fn accept_connection_nonce(
+        &mut self,
+        pub_id: &PublicId,
+        peer_addr: SocketAddr,
+        nonce: u64,
+    ) -> bool {
+        let last = self.their_nonces.get(pub_id.name()).cloned();
+        if last == Some((peer_addr, nonce)) {
+            return true;
+        }
+
+        let now = connection_nonce_clock();
+        if last.map_or(false, |(_, last_nonce)| nonce <= last_nonce)
+            || nonce.saturating_add(CONNECTION_NONCE_WINDOW_MS) < now
+            || nonce > now.saturating_add(CONNECTION_NONCE_WINDOW_MS)
+        {
+            return false;
+        }
+
+        let _ = self.their_nonces.insert(*pub_id.name(), (peer_addr, nonce));
+        true
+    }
+
+    // Forgets the nonces of the connections which are gone and too old to be accepted anyway.
+    fn remove_stale_nonces(&mut self) {
+        let now = connection_nonce_clock();
+        let reverse = &self.reverse;
+        let pending = &self.pending;
+        let clients = &self.clients;
+        self.their_nonces.retain(|_, (peer_addr, nonce)| {
+            reverse.contains_key(peer_addr)
+                || pending.contains_key(peer_addr)
+                || clients.contains(peer_addr)
+                || nonce.saturating_add(CONNECTION_NONCE_WINDOW_MS) >= now
+        });
+    }
+
+    // Forgets the usage of the IP addresses whose window is over, as it would be renewed anyway.
+    // It is kept across disconnections until then, so reconnecting doesn't renew it.
+    fn remove_stale_inbound(&mut self) {
+        let window = self.inbound_limits.window;
+        self.inbound
+            .retain(|_, usage| usage.window_start.elapsed() < window);
+    }
+}
+
+// Messages received from a peer in the current window.
+struct InboundUsage {
+    window_start: Instant,
+    messages: usize,
+    bytes: usize,
+}
+
+impl InboundUsage {
+    fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            messages: 0,
+            bytes: 0,
+        }
+    }
 }
 
 struct PendingConnection {
@@ -237,6 +445,103 @@ mod tests {
         assert_eq!(peer_map.get_connection_info(&pub_id), Some(&conn_info));
     }
 
+    #[test]
+    fn our_connection_nonces() {
+        let mut peer_map = PeerMap::new();
+        let addr = connection_info("198.51.100.0:5555").peer_addr;
+        let other_addr = connection_info("198.51.100.1:5555").peer_addr;
+
+        let nonce = peer_map.our_connection_nonce(addr);
+        assert_eq!(peer_map.our_connection_nonce(addr), nonce);
+        assert!(peer_map.our_connection_nonce(other_addr) > nonce);
+
+        // A new connection gets a new nonce.
+        let _ = peer_map.disconnect(addr);
+        assert!(peer_map.our_connection_nonce(addr) > nonce);
+    }
+
+    #[test]
+    fn their_connection_nonces() {
+        let mut peer_map = PeerMap::new();
+        let addr = connection_info("198.51.100.0:5555").peer_addr;
+        let other_addr = connection_info("198.51.100.1:5555").peer_addr;
+        let pub_id = *FullId::new().public_id();
+        let nonce = connection_nonce_clock();
+
+        assert!(peer_map.accept_connection_nonce(&pub_id, addr, nonce));
+        assert!(peer_map.accept_connection_nonce(&pub_id, addr, nonce));
+
+        // Messages replayed from another connection are rejected...
+        assert!(!peer_map.accept_connection_nonce(&pub_id, other_addr, nonce));
+        assert!(!peer_map.accept_connection_nonce(&pub_id, other_addr, nonce - 1));
+
+        // ...but the peer can reconnect from another address.
+        assert!(peer_map.accept_connection_nonce(&pub_id, other_addr, nonce + 1));
+        assert!(!peer_map.accept_connection_nonce(&pub_id, addr, nonce));
+
+        // Nonces too far from our clock are rejected.
+        let other_id = *FullId::new().public_id();
+        let stale_nonce = nonce - CONNECTION_NONCE_WINDOW_MS - 1_000;
+        assert!(!peer_map.accept_connection_nonce(&other_id, addr, stale_nonce));
+        let future_nonce = nonce + CONNECTION_NONCE_WINDOW_MS + 1_000;
+        assert!(!peer_map.accept_connection_nonce(&other_id, addr, future_nonce));
+    }
+
+    #[cfg(feature = "mock_base")]
+    #[test]
+    fn inbound_budget() {
+        use fake_clock::FakeClock;
+
+        let limits = InboundLimits {
+            window: Duration::from_secs(10),
+            max_messages: 3,
+            max_bytes: 100,
+            blacklist_duration: Some(Duration::from_secs(60)),
+        };
+        let mut peer_map = PeerMap::with_inbound_limits(limits);
+        let addr = connection_info("198.51.100.0:5555").peer_addr;
+
+        assert!(peer_map.record_inbound(addr, 10));
+        assert!(peer_map.record_inbound(addr, 10));
+        assert!(peer_map.record_inbound(addr, 10));
+        assert!(!peer_map.record_inbound(addr, 10));
+
+        // The budget is renewed with each window.
+        FakeClock::advance_time(10_000);
+        assert!(peer_map.record_inbound(addr, 100));
+        assert!(!peer_map.record_inbound(addr, 1));
+
+        assert!(peer_map.blacklist(addr));
+        assert!(peer_map.is_blacklisted(&addr));
+        FakeClock::advance_time(60_000);
+        assert!(!peer_map.is_blacklisted(&addr));
+
+        // A client can be given its own limits.
+        let client_addr = connection_info("198.51.100.1:5555").peer_addr;
+        peer_map.insert_client(client_addr);
+        peer_map.set_client_limits(
+            client_addr,
+            InboundLimits {
+                max_messages: 1,
+                ..limits
+            },
+        );
+        assert!(peer_map.record_inbound(client_addr, 10));
+        assert!(!peer_map.record_inbound(client_addr, 10));
+
+        // Once removed, the client has the default limits again, but keeps its usage.
+        peer_map.remove_client(&client_addr);
+        assert!(peer_map.record_inbound(client_addr, 10));
+        assert!(!peer_map.record_inbound(client_addr, 10));
+
+        // All the ports of an IP address share its budget and blacklisting.
+        let other_port_addr = connection_info("198.51.100.1:6666").peer_addr;
+        let _ = peer_map.disconnect(client_addr);
+        assert!(!peer_map.record_inbound(other_port_addr, 10));
+        assert!(peer_map.blacklist(other_port_addr));
+        assert!(peer_map.is_blacklisted(&client_addr));
+    }
+
     fn connection_info(addr: &str) -> ConnectionInfo {
         let peer_addr: SocketAddr = unwrap!(addr.parse());
         ConnectionInfo {
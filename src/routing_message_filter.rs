@@ -6,17 +6,25 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{crypto, id::PublicId, message_filter::MessageFilter, messages::RoutingMessage};
+use crate::{
+    crypto, dispatch::RoutingMessageKind, id::PublicId, local_params::MessageFilterLimits,
+    message_filter::MessageFilter, messages::RoutingMessage,
+};
 use lru_time_cache::LruCache;
 use maidsafe_utilities::serialisation::serialise;
 use serde::Serialize;
-use std::fmt::Debug;
-use std::time::Duration;
+use std::{collections::BTreeMap, fmt::Debug};
 
 type Digest = [u8; 32];
 
-const INCOMING_EXPIRY_DURATION_SECS: u64 = 60 * 20;
-const OUTGOING_EXPIRY_DURATION_SECS: u64 = 60 * 10;
+/// Numbers of duplicate routing messages filtered out by a node, by kind of message.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DuplicateCounts {
+    /// Messages we received again and dropped, as we already handled them.
+    pub incoming: BTreeMap<RoutingMessageKind, u64>,
+    /// Messages we didn't send to a peer, as we recently sent them to it already.
+    pub outgoing: BTreeMap<RoutingMessageKind, u64>,
+}
 
 /// An enum representing a result of message filtering
 #[derive(Eq, PartialEq)]
@@ -40,19 +48,39 @@ impl FilteringResult {
 pub struct RoutingMessageFilter {
     incoming: MessageFilter<Digest>,
     outgoing: LruCache<(Digest, PublicId), ()>,
+    limits: MessageFilterLimits,
+    duplicates: DuplicateCounts,
 }
 
 impl RoutingMessageFilter {
-    pub fn new() -> Self {
-        let incoming_duration = Duration::from_secs(INCOMING_EXPIRY_DURATION_SECS);
-        let outgoing_duration = Duration::from_secs(OUTGOING_EXPIRY_DURATION_SECS);
+    pub fn new(limits: MessageFilterLimits) -> Self {
+        RoutingMessageFilter {
+            incoming: MessageFilter::with_expiry_duration_and_capacity(
+                limits.incoming_expiry,
+                limits.incoming_capacity,
+            ),
+            outgoing: LruCache::with_expiry_duration_and_capacity(
+                limits.outgoing_expiry,
+                limits.outgoing_capacity,
+            ),
+            limits,
+            duplicates: DuplicateCounts::default(),
+        }
+    }
 
+    // Returns an empty filter with the same limits, which keeps counting the duplicates from where
+    // this one is.
+    pub fn reset(self) -> Self {
         RoutingMessageFilter {
-            incoming: MessageFilter::with_expiry_duration(incoming_duration),
-            outgoing: LruCache::with_expiry_duration(outgoing_duration),
+            duplicates: self.duplicates,
+            ..Self::new(self.limits)
         }
     }
 
+    pub fn duplicate_counts(&self) -> &DuplicateCounts {
+        &self.duplicates
+    }
+
     // Filter incoming `RoutingMessage`. Return whether this specific message has already been seen.
     pub fn filter_incoming(&mut self, msg: &RoutingMessage) -> FilteringResult {
         let hash = match hash(msg) {
@@ -60,6 +88,7 @@ impl RoutingMessageFilter {
             None => return FilteringResult::NewMessage,
         };
         if self.incoming.insert(&hash) > 1 {
+            count_duplicate(&mut self.duplicates.incoming, msg);
             FilteringResult::KnownMessage
         } else {
             FilteringResult::NewMessage
@@ -71,22 +100,46 @@ impl RoutingMessageFilter {
     //
     // Return `KnownMessage` also if hashing the message fails - that can be handled elsewhere.
     pub fn filter_outgoing(&mut self, msg: &RoutingMessage, pub_id: &PublicId) -> FilteringResult {
-        hash(msg).map_or(FilteringResult::KnownMessage, |hash| {
-            if self.outgoing.insert((hash, *pub_id), ()).is_some() {
-                FilteringResult::KnownMessage
-            } else {
-                FilteringResult::NewMessage
-            }
-        })
+        let hash = match hash(msg) {
+            Some(hash) => hash,
+            None => return FilteringResult::KnownMessage,
+        };
+        if self.outgoing.insert((hash, *pub_id), ()).is_some() {
+            count_duplicate(&mut self.duplicates.outgoing, msg);
+            FilteringResult::KnownMessage
+        } else {
+            FilteringResult::NewMessage
+        }
+    }
+
+    // Forget the messages we sent, so that they can be sent to the same peers again, e.g. when we
+    // resume without knowing which of them got out.
+    pub fn clear_outgoing(&mut self) {
+        self.outgoing = LruCache::with_expiry_duration_and_capacity(
+            self.limits.outgoing_expiry,
+            self.limits.outgoing_capacity,
+        );
+    }
+
+    // Records that we have seen `msg`, e.g. because we sent it ourselves, so that we don't handle
+    // it should someone send it to us.
+    pub fn mark_incoming(&mut self, msg: &RoutingMessage) {
+        if let Some(hash) = hash(msg) {
+            let _ = self.incoming.insert(&hash);
+        }
     }
 }
 
 impl Default for RoutingMessageFilter {
     fn default() -> Self {
-        Self::new()
+        Self::new(MessageFilterLimits::default())
     }
 }
 
+fn count_duplicate(counts: &mut BTreeMap<RoutingMessageKind, u64>, msg: &RoutingMessage) {
+    *counts.entry(msg.content.kind()).or_insert(0) += 1;
+}
+
 fn hash<T: Serialize + Debug>(msg: &T) -> Option<Digest> {
     if let Ok(msg_bytes) = serialise(msg) {
         Some(crypto::sha3_256(&msg_bytes))
@@ -95,3 +148,63 @@ fn hash<T: Serialize + Debug>(msg: &T) -> Option<Digest> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, messages::MessageContent, routing_table::Authority, types::MessageId};
+
+    fn user_message(content: Vec<u8>) -> RoutingMessage {
+        RoutingMessage {
+            src: Authority::Node(rand::random()),
+            dst: Authority::Section(rand::random()),
+            content: MessageContent::UserMessage {
+                content,
+                msg_id: MessageId::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn count_duplicates_by_kind() {
+        let mut filter = RoutingMessageFilter::default();
+        let msg = user_message(vec![1, 2, 3]);
+        let peer = *FullId::new().public_id();
+
+        assert!(filter.filter_incoming(&msg).is_new());
+        assert!(!filter.filter_incoming(&msg).is_new());
+        assert!(filter.filter_outgoing(&msg, &peer).is_new());
+        assert!(!filter.filter_outgoing(&msg, &peer).is_new());
+        assert!(!filter.filter_outgoing(&msg, &peer).is_new());
+
+        let expected = DuplicateCounts {
+            incoming: vec![(RoutingMessageKind::UserMessage, 1)]
+                .into_iter()
+                .collect(),
+            outgoing: vec![(RoutingMessageKind::UserMessage, 2)]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(*filter.duplicate_counts(), expected);
+
+        // Resetting forgets the messages but keeps counting.
+        let mut filter = filter.reset();
+        assert!(filter.filter_incoming(&msg).is_new());
+        assert_eq!(*filter.duplicate_counts(), expected);
+    }
+
+    #[test]
+    fn forget_least_recent_messages_once_full() {
+        let mut filter = RoutingMessageFilter::new(MessageFilterLimits {
+            incoming_capacity: 2,
+            ..MessageFilterLimits::default()
+        });
+        let msgs: Vec<_> = (0..3).map(|i| user_message(vec![i])).collect();
+
+        for msg in &msgs {
+            assert!(filter.filter_incoming(msg).is_new());
+        }
+        assert!(filter.filter_incoming(&msgs[0]).is_new());
+        assert!(!filter.filter_incoming(&msgs[2]).is_new());
+    }
+}
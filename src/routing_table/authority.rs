@@ -10,6 +10,10 @@ use super::{Prefix, Xorable};
 use crate::xor_name::XorName;
 use std::fmt::{self, Binary, Debug, Display, Formatter};
 
+/// The largest number of nodes an `Authority::CloseGroup` spans: a greater `k` counts as this one,
+/// so that a sender can't have its message sent to every node we know.
+pub const MAX_CLOSE_GROUP_SIZE: usize = 32;
+
 /// An entity that can act as a source or destination of a message.
 ///
 /// `Client` and `ManagedNode` are single-node authorities (i.e. no verification of messages from
@@ -18,6 +22,8 @@ use std::fmt::{self, Binary, Debug, Display, Formatter};
 /// require quorum agreement from the group of nodes closest to the source, while `Section` and
 /// `PrefixSection` use _section_ verification: the set from which a quorum is required is all
 /// members of the section (`Section`) or of all sections matching the prefix (`PrefixSection`).
+/// `CloseGroup` is made of elders only, and as a source it is verified by the section closest to
+/// its name, like `Section`: only that section's elders hold shares of the key signing for it.
 #[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Hash)]
 #[allow(clippy::large_enum_variant)]
 pub enum Authority<N: Xorable + Clone + Copy + Binary + Default> {
@@ -28,13 +34,22 @@ pub enum Authority<N: Xorable + Clone + Copy + Binary + Default> {
     PrefixSection(Prefix<N>),
     /// A single node
     Node(N),
+    /// The `k` elders closest to the given name - may span multiple `Section`s
+    CloseGroup {
+        /// The name the group is closest to
+        name: N,
+        /// The number of nodes in the group, at most `MAX_CLOSE_GROUP_SIZE`
+        k: usize,
+    },
 }
 
 impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
     /// Returns `true` if the authority consists of multiple nodes, otherwise `false`.
     pub fn is_multiple(&self) -> bool {
         match self {
-            Authority::Section(_) | Authority::PrefixSection(_) => true,
+            Authority::Section(_) | Authority::PrefixSection(_) | Authority::CloseGroup { .. } => {
+                true
+            }
             Authority::Node(_) => false,
         }
     }
@@ -42,7 +57,9 @@ impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
     /// Returns `true` if the authority is a single node, and `false` otherwise.
     pub fn is_single(&self) -> bool {
         match self {
-            Authority::Section(_) | Authority::PrefixSection(_) => false,
+            Authority::Section(_) | Authority::PrefixSection(_) | Authority::CloseGroup { .. } => {
+                false
+            }
             Authority::Node(_) => true,
         }
     }
@@ -50,7 +67,9 @@ impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
     /// Returns the name of authority.
     pub fn name(&self) -> N {
         match self {
-            Authority::Section(name) | Authority::Node(name) => *name,
+            Authority::Section(name)
+            | Authority::Node(name)
+            | Authority::CloseGroup { name, .. } => *name,
             Authority::PrefixSection(prefix) => prefix.lower_bound(),
         }
     }
@@ -60,7 +79,9 @@ impl Authority<XorName> {
     /// provide the name mathching a single node's public key
     pub fn single_signing_name(&self) -> Option<&XorName> {
         match *self {
-            Authority::Section(_) | Authority::PrefixSection(_) => None,
+            Authority::Section(_) | Authority::PrefixSection(_) | Authority::CloseGroup { .. } => {
+                None
+            }
             Authority::Node(ref name) => Some(name),
         }
     }
@@ -73,6 +94,9 @@ impl<N: Xorable + Clone + Copy + Binary + Default + Display> Debug for Authority
             Authority::PrefixSection(ref prefix) => {
                 write!(formatter, "PrefixSection(prefix: {:?})", prefix)
             }
+            Authority::CloseGroup { ref name, k } => {
+                write!(formatter, "CloseGroup(name: {}, k: {})", name, k)
+            }
             Authority::Node(ref name) => write!(formatter, "Node(name: {})", name),
         }
     }
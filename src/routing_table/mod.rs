@@ -103,7 +103,7 @@ mod network_tests;
 mod prefix;
 mod xorable;
 
-pub use self::authority::Authority;
+pub use self::authority::{Authority, MAX_CLOSE_GROUP_SIZE};
 pub use self::error::Error;
 #[cfg(any(test, feature = "mock_base"))]
 pub use self::network_tests::verify_network_invariant;
@@ -787,6 +787,11 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
     ///     - returns the `N/3` closest members of the RT to the lower bound of the target
     ///       prefix
     ///
+    /// * If the destination is an `Authority::CloseGroup`:
+    ///     - if we are among the `k` closest entries of the RT to the target, returns the other
+    ///       members of the group; otherwise
+    ///     - returns the `N/3` closest members of the RT to the target
+    ///
     /// * If the destination is a group (`ClientManager`, `NaeManager` or `NodeManager`):
     ///     - if our section is the closest on the network (i.e. our section's prefix is a prefix of
     ///       the destination), returns all other members of our section; otherwise
@@ -856,6 +861,16 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
                 }
                 candidates(&prefix.lower_bound())
             }
+            Authority::CloseGroup { ref name, k } => {
+                if let Some(group) = self.other_closest_names(name, k.min(MAX_CLOSE_GROUP_SIZE)) {
+                    return Ok(group
+                        .into_iter()
+                        .filter(|&x| *x != exclude)
+                        .cloned()
+                        .collect());
+                }
+                candidates(name)
+            }
         };
         let n = closest_section.len();
         Ok(closest_section
@@ -871,6 +886,9 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
             Authority::Node(ref name) => self.our_name == *name,
             Authority::Section(ref name) => self.our_prefix.matches(name),
             Authority::PrefixSection(ref prefix) => self.our_prefix.is_compatible(prefix),
+            Authority::CloseGroup { ref name, k } => {
+                self.is_closest(name, k.min(MAX_CLOSE_GROUP_SIZE))
+            }
         }
     }
 
@@ -12,6 +12,7 @@ use std::{
     fmt::Result as FmtResult,
     fmt::{Binary, Debug, Formatter},
     hash::{Hash, Hasher},
+    iter,
     ops::RangeInclusive,
 };
 #[cfg(test)]
@@ -231,6 +232,48 @@ impl<T: Clone + Copy + Default + Binary + Xorable> Prefix<T> {
             *self
         }
     }
+
+    /// Returns `true` if `self` is a strict prefix of `other`, i.e. if `other` is an extension of
+    /// `self`.
+    pub fn is_ancestor_of(&self, other: &Prefix<T>) -> bool {
+        other.is_extension_of(self)
+    }
+
+    /// Returns an iterator over the strict ancestors of `self`, from the empty prefix down to
+    /// `self.popped()`. Empty if `self` is the empty prefix.
+    pub fn ancestors(&self) -> impl Iterator<Item = Prefix<T>> {
+        let name = self.name;
+        (0..self.bit_count()).map(move |bit_count| Prefix::new(bit_count, name))
+    }
+
+    /// Returns an iterator over all the extensions of `self` with exactly `bit_count` bits, in
+    /// ascending order. Yields only `self` if `bit_count` equals `self.bit_count()` and nothing
+    /// if it is smaller. `bit_count` is capped at the size of `T` in bits.
+    pub fn descendants(&self, bit_count: usize) -> impl Iterator<Item = Prefix<T>> {
+        let bit_count = cmp::min(bit_count, T::bit_len());
+        let own_bit_count = self.bit_count();
+        let first = if bit_count < own_bit_count {
+            None
+        } else {
+            Some(Prefix::new(bit_count, self.name))
+        };
+        // Each next prefix is found by counting up in the bits past ours: setting the last unset
+        // one and clearing the ones after it. They are thus generated one at a time, as needed.
+        iter::successors(first, move |prefix| {
+            let name = prefix.name;
+            let i = (own_bit_count..bit_count).rev().find(|&i| !name.bit(i))?;
+            Some(Prefix::new(
+                bit_count,
+                name.with_bit(i, true).set_remaining(i + 1, false),
+            ))
+        })
+    }
+
+    /// Returns an iterator over all the prefixes with exactly `bit_count` bits, in ascending
+    /// order. Together they cover the whole name space.
+    pub fn all(bit_count: usize) -> impl Iterator<Item = Prefix<T>> {
+        Prefix::default().descendants(bit_count)
+    }
 }
 
 impl<T: Clone + Copy + Default + Binary + Xorable> PartialEq<Prefix<T>> for Prefix<T> {
@@ -367,4 +410,57 @@ mod tests {
         assert_eq!(Prefix::<u64>::new(64, 0).bit_count(), 64);
         assert_eq!(Prefix::<u64>::new(65, 0).bit_count(), 64);
     }
+
+    #[test]
+    fn ancestry() {
+        let prefix = unwrap!(Prefix::<u8>::from_str("101"));
+        let ancestors: Vec<_> = prefix.ancestors().collect();
+        assert_eq!(
+            ancestors,
+            vec![
+                Prefix::default(),
+                unwrap!(Prefix::<u8>::from_str("1")),
+                unwrap!(Prefix::<u8>::from_str("10")),
+            ]
+        );
+        assert!(ancestors
+            .iter()
+            .all(|ancestor| ancestor.is_ancestor_of(&prefix)));
+        assert!(!prefix.is_ancestor_of(&prefix));
+        assert!(!unwrap!(Prefix::<u8>::from_str("11")).is_ancestor_of(&prefix));
+        assert_eq!(Prefix::<u8>::default().ancestors().count(), 0);
+
+        let descendants: Vec<_> = prefix.descendants(5).collect();
+        assert_eq!(
+            descendants,
+            vec![
+                unwrap!(Prefix::<u8>::from_str("10100")),
+                unwrap!(Prefix::<u8>::from_str("10101")),
+                unwrap!(Prefix::<u8>::from_str("10110")),
+                unwrap!(Prefix::<u8>::from_str("10111")),
+            ]
+        );
+        assert!(descendants
+            .iter()
+            .all(|descendant| prefix.is_ancestor_of(descendant)));
+        assert_eq!(prefix.descendants(3).collect::<Vec<_>>(), vec![prefix]);
+        assert_eq!(prefix.descendants(2).count(), 0);
+        assert_eq!(prefix.descendants(9).count(), 32);
+
+        let all: Vec<_> = Prefix::<u8>::all(2).collect();
+        assert_eq!(all.len(), 4);
+        assert!(Prefix::<u8>::default().is_covered_by(&all));
+        assert_eq!(
+            Prefix::<u8>::all(0).collect::<Vec<_>>(),
+            vec![Prefix::default()]
+        );
+
+        // The prefixes are generated lazily, so only the ones taken are computed.
+        let first: Vec<_> = Prefix::<XorName>::all(64).take(2).collect();
+        assert_eq!(first[0], Prefix::new(64, XorName::default()));
+        assert_eq!(
+            first[1],
+            Prefix::new(64, XorName::default().with_bit(63, true))
+        );
+    }
 }
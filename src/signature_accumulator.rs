@@ -18,6 +18,7 @@ use std::collections::HashMap;
 /// accumulate.
 pub const ACCUMULATION_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Accumulates the signature shares of section messages until a quorum of them is collected.
 #[derive(Default)]
 pub struct SignatureAccumulator {
     msgs: HashMap<Digest256, (Option<SignedRoutingMessage>, Instant)>,
@@ -82,6 +83,7 @@ mod tests {
             SignedRoutingMessage,
         },
         routing_table::{Authority, Prefix},
+        types::MessageId,
         BlsPublicKeySet,
     };
     use itertools::Itertools;
@@ -106,11 +108,10 @@ mod tests {
             let routing_msg = RoutingMessage {
                 src: Authority::Section(rand::random()),
                 dst: Authority::Section(rand::random()),
-                content: MessageContent::UserMessage(vec![
-                    rand::random(),
-                    rand::random(),
-                    rand::random(),
-                ]),
+                content: MessageContent::UserMessage {
+                    content: vec![rand::random(), rand::random(), rand::random()],
+                    msg_id: MessageId::new(),
+                },
             };
             let prefix = Prefix::new(0, *unwrap!(all_ids.iter().next()).name());
             let elders_info = unwrap!(EldersInfo::new(all_ids, prefix, None));
@@ -133,6 +134,8 @@ mod tests {
                             proof.clone(),
                         ))),
                         msg_sender_id,
+                        None,
+                        0,
                     ))
                 })
                 .collect();
@@ -0,0 +1,248 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    chain::{MIN_AGE, SPLIT_BUFFER},
+    Prefix, XorName,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A snapshot of the sections of a network and their members, used as input to `simulate_join`.
+#[derive(Clone, Debug)]
+pub struct Topology {
+    min_section_size: usize,
+    split_buffer: usize,
+    sections: BTreeMap<Prefix<XorName>, BTreeSet<XorName>>,
+}
+
+impl Topology {
+    /// Creates an empty topology of a network with the given minimum section size and the default
+    /// split buffer.
+    pub fn new(min_section_size: usize) -> Self {
+        Self {
+            min_section_size,
+            split_buffer: SPLIT_BUFFER,
+            sections: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the section with the given prefix and member names, replacing any section with the
+    /// same prefix.
+    pub fn insert_section<I>(&mut self, prefix: Prefix<XorName>, members: I)
+    where
+        I: IntoIterator<Item = XorName>,
+    {
+        let _ = self.sections.insert(prefix, members.into_iter().collect());
+    }
+
+    /// Adds a single member to the section whose prefix matches its name. Returns `false` if
+    /// there is no such section.
+    pub fn insert_member(&mut self, name: XorName) -> bool {
+        if let Some(members) = self
+            .sections
+            .iter_mut()
+            .find(|(prefix, _)| prefix.matches(&name))
+            .map(|(_, members)| members)
+        {
+            let _ = members.insert(name);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the prefix and members of the section whose prefix matches `name`, if any.
+    pub fn section(&self, name: &XorName) -> Option<(&Prefix<XorName>, &BTreeSet<XorName>)> {
+        self.sections
+            .iter()
+            .find(|(prefix, _)| prefix.matches(name))
+    }
+
+    /// Returns the prefixes of all sections.
+    pub fn prefixes(&self) -> BTreeSet<Prefix<XorName>> {
+        self.sections.keys().cloned().collect()
+    }
+
+    /// Returns the minimum section size.
+    pub fn min_section_size(&self) -> usize {
+        self.min_section_size
+    }
+
+    /// Sets the split buffer of the network, as in `NetworkParams::split_buffer`.
+    pub fn set_split_buffer(&mut self, split_buffer: usize) {
+        self.split_buffer = split_buffer;
+    }
+
+    /// Returns the split buffer of the network.
+    pub fn split_buffer(&self) -> usize {
+        self.split_buffer
+    }
+}
+
+/// The predicted result of a node joining the network.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct JoinOutcome {
+    /// Prefix of the section the node would join.
+    pub section: Prefix<XorName>,
+    /// The prefixes of the two sections resulting from the split the join would trigger, if any.
+    pub split: Option<(Prefix<XorName>, Prefix<XorName>)>,
+    /// Prefix of the section the node would end up in once the join (and split) is complete.
+    /// Joining nodes are currently not relocated, so this is always `section` or the half of it
+    /// matching the node's name.
+    pub final_section: Prefix<XorName>,
+    /// Age the node would start with.
+    pub age: u8,
+}
+
+/// Predicts which section a node with the given `name` would join, whether that would trigger a
+/// split and where the node would end up, without running the network. Returns `None` if no
+/// section in `topology` covers `name`.
+///
+/// The prediction uses the same rules as the elders do: a section splits when each of its halves
+/// would have at least `min_section_size + split_buffer` members. Pending splits and merges are
+/// not taken into account.
+pub fn simulate_join(name: &XorName, topology: &Topology) -> Option<JoinOutcome> {
+    let (prefix, members) = topology.section(name)?;
+
+    let min_split_size = topology.min_section_size + topology.split_buffer;
+    let section_0 = prefix.pushed(false);
+    let section_1 = prefix.pushed(true);
+    let size_0 = members
+        .iter()
+        .chain(Some(name))
+        .filter(|member| section_0.matches(member))
+        .count();
+    let size_1 = members.len() + 1 - size_0;
+
+    let split = if size_0 >= min_split_size && size_1 >= min_split_size {
+        Some((section_0, section_1))
+    } else {
+        None
+    };
+
+    let final_section = if split.is_none() {
+        *prefix
+    } else if section_0.matches(name) {
+        section_0
+    } else {
+        section_1
+    };
+
+    Some(JoinOutcome {
+        section: *prefix,
+        split,
+        final_section,
+        age: MIN_AGE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Xorable;
+    use rand;
+    use unwrap::unwrap;
+
+    const MIN_SECTION_SIZE: usize = 3;
+
+    fn gen_names_in(prefix: &Prefix<XorName>, count: usize) -> Vec<XorName> {
+        (0..count)
+            .map(|_| prefix.substituted_in(rand::random()))
+            .collect()
+    }
+
+    #[test]
+    fn join_without_split() {
+        let prefix = Prefix::default();
+        let mut topology = Topology::new(MIN_SECTION_SIZE);
+        topology.insert_section(prefix, gen_names_in(&prefix, MIN_SECTION_SIZE));
+
+        let name = rand::random();
+        let outcome = unwrap!(simulate_join(&name, &topology));
+
+        assert_eq!(outcome.section, prefix);
+        assert_eq!(outcome.split, None);
+        assert_eq!(outcome.final_section, prefix);
+        assert_eq!(outcome.age, MIN_AGE);
+    }
+
+    #[test]
+    fn join_triggering_split() {
+        let min_split_size = MIN_SECTION_SIZE + SPLIT_BUFFER;
+        let prefix = Prefix::default();
+        let prefix_0 = prefix.pushed(false);
+        let prefix_1 = prefix.pushed(true);
+
+        let mut topology = Topology::new(MIN_SECTION_SIZE);
+        topology.insert_section(
+            prefix,
+            gen_names_in(&prefix_0, min_split_size)
+                .into_iter()
+                .chain(gen_names_in(&prefix_1, min_split_size - 1)),
+        );
+
+        // A node joining the smaller half completes the split.
+        let name = prefix_1.substituted_in(rand::random());
+        let outcome = unwrap!(simulate_join(&name, &topology));
+        assert_eq!(outcome.split, Some((prefix_0, prefix_1)));
+        assert_eq!(outcome.final_section, prefix_1);
+
+        // A node joining the larger half doesn't.
+        let name = prefix_0.substituted_in(rand::random());
+        let outcome = unwrap!(simulate_join(&name, &topology));
+        assert_eq!(outcome.split, None);
+        assert_eq!(outcome.final_section, prefix);
+    }
+
+    #[test]
+    fn join_with_larger_split_buffer() {
+        let min_split_size = MIN_SECTION_SIZE + SPLIT_BUFFER;
+        let prefix = Prefix::default();
+        let prefix_0 = prefix.pushed(false);
+        let prefix_1 = prefix.pushed(true);
+
+        let mut topology = Topology::new(MIN_SECTION_SIZE);
+        topology.set_split_buffer(SPLIT_BUFFER + 1);
+        topology.insert_section(
+            prefix,
+            gen_names_in(&prefix_0, min_split_size)
+                .into_iter()
+                .chain(gen_names_in(&prefix_1, min_split_size - 1)),
+        );
+
+        // Enough to split with the default buffer, but not with the larger one.
+        let name = prefix_1.substituted_in(rand::random());
+        let outcome = unwrap!(simulate_join(&name, &topology));
+        assert_eq!(outcome.split, None);
+        assert_eq!(outcome.final_section, prefix);
+    }
+
+    #[test]
+    fn join_picks_matching_section() {
+        let prefix_0 = Prefix::default().pushed(false);
+        let prefix_1 = Prefix::default().pushed(true);
+
+        let mut topology = Topology::new(MIN_SECTION_SIZE);
+        topology.insert_section(prefix_0, gen_names_in(&prefix_0, MIN_SECTION_SIZE));
+        topology.insert_section(prefix_1, gen_names_in(&prefix_1, MIN_SECTION_SIZE));
+
+        let name: XorName = rand::random();
+        let expected = if name.bit(0) { prefix_1 } else { prefix_0 };
+        assert_eq!(unwrap!(simulate_join(&name, &topology)).section, expected);
+    }
+
+    #[test]
+    fn join_outside_known_sections() {
+        let prefix = Prefix::default().pushed(false);
+        let mut topology = Topology::new(MIN_SECTION_SIZE);
+        topology.insert_section(prefix, gen_names_in(&prefix, MIN_SECTION_SIZE));
+
+        let name = prefix.sibling().substituted_in(rand::random());
+        assert_eq!(simulate_join(&name, &topology), None);
+    }
+}
@@ -7,25 +7,30 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    action::Action,
-    chain::{EldersInfo, GenesisPfxInfo},
+    action::{Action, ActionSender},
+    chain::{Chain, EldersInfo, GenesisPfxInfo},
     error::RoutingError,
-    id::PublicId,
+    event_stream::EventWaker,
+    id::{FullId, PublicId},
     messages::{RelocatePayload, SignedRelocateDetails},
     network_service::NetworkBuilder,
     outbox::EventBox,
-    pause::PausedState,
+    pause::{PausedStage, PausedState, RelocationState},
+    quic_p2p::OurType,
+    routing_message_filter::DuplicateCounts,
     routing_table::Prefix,
-    states::common::Base,
-    states::{Adult, BootstrappingPeer, Elder, JoiningPeer},
+    states::common::{Approved, Base},
+    states::{Adult, BootstrappingPeer, Client, Elder, JoiningPeer},
+    time::{Duration, Instant},
     timer::Timer,
     xor_name::XorName,
     ConnectionInfo, NetworkConfig, NetworkEvent, NetworkService,
 };
 #[cfg(feature = "mock_base")]
-use crate::{routing_table::Authority, Chain};
+use crate::{parsec::ParsecMetrics, routing_table::Authority};
 use crossbeam_channel as mpmc;
 use std::{
+    collections::VecDeque,
     fmt::{self, Debug, Display, Formatter},
     mem,
 };
@@ -36,6 +41,7 @@ use unwrap::unwrap;
 macro_rules! state_dispatch {
     ($self:expr, $state:pat => $expr:expr, Terminated => $term_expr:expr) => {
         match $self {
+            State::Client($state) => $expr,
             State::BootstrappingPeer($state) => $expr,
             State::JoiningPeer($state) => $expr,
             State::Adult($state) => $expr,
@@ -45,12 +51,52 @@ macro_rules! state_dispatch {
     };
 }
 
+// Number of the most recent transitions the state machine remembers.
+const TRANSITION_HISTORY_LEN: usize = 16;
+
+/// A state transition of a node, as recorded in its history.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransitionRecord {
+    /// Name of the state the node left, e.g. `"Adult"`.
+    pub from: &'static str,
+    /// Name of the state the node entered.
+    pub to: &'static str,
+    /// Kind of the transition, e.g. `"IntoElder"` or `"Rebootstrap"`.
+    pub transition: &'static str,
+    /// How long the node had been in the state it left.
+    pub time_in_previous_state: Duration,
+}
+
+/// Observer of the state transitions of a node, e.g. to emit metrics or logs. Implemented by any
+/// `FnMut(&TransitionRecord)` closure.
+pub trait TransitionObserver: Send {
+    /// Called after each state transition.
+    fn on_transition(&mut self, record: &TransitionRecord);
+}
+
+impl<F> TransitionObserver for F
+where
+    F: FnMut(&TransitionRecord) + Send,
+{
+    fn on_transition(&mut self, record: &TransitionRecord) {
+        self(record)
+    }
+}
+
 /// Holds the current state and handles state transitions.
 pub struct StateMachine {
     state: State,
     network_rx: mpmc::Receiver<NetworkEvent>,
     action_rx: mpmc::Receiver<Action>,
     is_running: bool,
+    // When we entered the current state.
+    state_since: Instant,
+    // The most recent transitions, oldest first.
+    history: VecDeque<TransitionRecord>,
+    observer: Option<Box<dyn TransitionObserver>>,
+    // Woken whenever an action is sent to us.
+    #[cfg(feature = "async")]
+    waker: EventWaker,
     #[cfg(feature = "mock_base")]
     events: Vec<EventType>,
 }
@@ -58,6 +104,7 @@ pub struct StateMachine {
 // FIXME - See https://maidsafe.atlassian.net/browse/MAID-2026 for info on removing this exclusion.
 #[allow(clippy::large_enum_variant)]
 pub enum State {
+    Client(Client),
     BootstrappingPeer(BootstrappingPeer),
     JoiningPeer(JoiningPeer),
     Adult(Adult),
@@ -121,6 +168,76 @@ impl State {
         )
     }
 
+    /// Returns the name of the state, e.g. `"Elder"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            State::Client(_) => "Client",
+            State::BootstrappingPeer(_) => "BootstrappingPeer",
+            State::JoiningPeer(_) => "JoiningPeer",
+            State::Adult(_) => "Adult",
+            State::Elder(_) => "Elder",
+            State::Terminated => "Terminated",
+        }
+    }
+
+    pub fn chain(&self) -> Option<&Chain> {
+        match *self {
+            State::Adult(ref state) => Some(state.chain()),
+            State::Elder(ref state) => Some(state.chain()),
+            State::Client(_)
+            | State::BootstrappingPeer(_)
+            | State::JoiningPeer(_)
+            | State::Terminated => None,
+        }
+    }
+
+    pub fn chain_mut(&mut self) -> Option<&mut Chain> {
+        match *self {
+            State::Adult(ref mut state) => Some(state.chain_mut()),
+            State::Elder(ref mut state) => Some(state.chain_mut()),
+            State::Client(_)
+            | State::BootstrappingPeer(_)
+            | State::JoiningPeer(_)
+            | State::Terminated => None,
+        }
+    }
+
+    pub fn dropped_backlog_entries(&self) -> u64 {
+        match *self {
+            State::JoiningPeer(ref state) => state.dropped_backlog_entries(),
+            State::Adult(ref state) => state.dropped_backlog_entries(),
+            State::Client(_)
+            | State::BootstrappingPeer(_)
+            | State::Elder(_)
+            | State::Terminated => 0,
+        }
+    }
+
+    pub fn duplicate_counts(&self) -> Option<DuplicateCounts> {
+        match *self {
+            State::JoiningPeer(ref state) => Some(state.duplicate_counts().clone()),
+            State::Adult(ref state) => Some(state.duplicate_counts().clone()),
+            State::Elder(ref state) => Some(state.duplicate_counts().clone()),
+            State::Client(_) | State::BootstrappingPeer(_) | State::Terminated => None,
+        }
+    }
+
+    pub fn relocation_state(&self) -> Option<RelocationState> {
+        match *self {
+            State::BootstrappingPeer(ref state) => state.relocation_state(),
+            State::JoiningPeer(ref state) => state.relocation_state(),
+            State::Client(_) | State::Adult(_) | State::Elder(_) | State::Terminated => None,
+        }
+    }
+
+    pub fn is_connected<N: AsRef<XorName>>(&self, name: N) -> bool {
+        state_dispatch!(
+            self,
+            state => state.peer_map().has(name),
+            Terminated => false
+        )
+    }
+
     /// Returns this elder mut state.
     pub fn elder_state_mut(&mut self) -> Option<&mut Elder> {
         match *self {
@@ -129,6 +246,15 @@ impl State {
         }
     }
 
+    // Sends the messages queued by the state, the ones of the highest priority first.
+    fn flush_sends(&mut self) {
+        state_dispatch!(
+            self,
+            state => state.network_service_mut().flush(),
+            Terminated => ()
+        )
+    }
+
     fn replace_with<F, E>(&mut self, f: F)
     where
         F: FnOnce(Self) -> Result<Self, E>,
@@ -169,14 +295,6 @@ impl Debug for State {
 
 #[cfg(feature = "mock_base")]
 impl State {
-    pub fn chain(&self) -> Option<&Chain> {
-        match *self {
-            State::Adult(ref state) => Some(state.chain()),
-            State::Elder(ref state) => Some(state.chain()),
-            State::BootstrappingPeer(_) | State::JoiningPeer(_) | State::Terminated => None,
-        }
-    }
-
     /// Returns this elder state.
     pub fn elder_state(&self) -> Option<&Elder> {
         match *self {
@@ -187,21 +305,46 @@ impl State {
 
     pub fn get_timed_out_tokens(&mut self) -> Vec<u64> {
         match *self {
-            State::BootstrappingPeer(_) | State::Terminated => vec![],
+            State::Client(_) | State::BootstrappingPeer(_) | State::Terminated => vec![],
             State::JoiningPeer(ref mut state) => state.get_timed_out_tokens(),
             State::Adult(ref mut state) => state.get_timed_out_tokens(),
             State::Elder(ref mut state) => state.get_timed_out_tokens(),
         }
     }
 
+    /// Returns the earliest deadline of the timers scheduled by this state, if any. Timers of the
+    /// `BootstrappingPeer` state are not fired under the mock network, so they are not included.
+    pub fn next_timer_deadline(&self) -> Option<Instant> {
+        match *self {
+            State::Client(_) | State::BootstrappingPeer(_) | State::Terminated => None,
+            State::JoiningPeer(ref state) => state.next_timer_deadline(),
+            State::Adult(ref state) => state.next_timer_deadline(),
+            State::Elder(ref state) => state.next_timer_deadline(),
+        }
+    }
+
     pub fn has_unpolled_observations(&self) -> bool {
         match *self {
-            State::Terminated | State::BootstrappingPeer(_) | State::JoiningPeer(_) => false,
+            State::Terminated
+            | State::Client(_)
+            | State::BootstrappingPeer(_)
+            | State::JoiningPeer(_) => false,
             State::Adult(ref state) => state.has_unpolled_observations(),
             State::Elder(ref state) => state.has_unpolled_observations(),
         }
     }
 
+    pub fn parsec_metrics(&self) -> Option<ParsecMetrics> {
+        match *self {
+            State::Terminated
+            | State::Client(_)
+            | State::BootstrappingPeer(_)
+            | State::JoiningPeer(_) => None,
+            State::Adult(ref state) => Some(state.parsec_metrics()),
+            State::Elder(ref state) => Some(state.parsec_metrics()),
+        }
+    }
+
     pub fn in_authority(&self, auth: &Authority<XorName>) -> bool {
         state_dispatch!(
             *self,
@@ -217,14 +360,6 @@ impl State {
             Terminated => Err(RoutingError::InvalidStateForOperation)
         )
     }
-
-    pub fn is_connected<N: AsRef<XorName>>(&self, name: N) -> bool {
-        state_dispatch!(
-            self,
-            state => state.peer_map().has(name),
-            Terminated => false
-        )
-    }
 }
 
 /// Enum returned from many message handlers
@@ -253,35 +388,47 @@ pub enum Transition {
         elders_info: EldersInfo,
         old_pfx: Prefix<XorName>,
     },
+    // `Elder` state stepping down to `Adult`.
+    Demote,
     Terminate,
 }
 
-impl Debug for Transition {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+impl Transition {
+    /// Returns the name of the variant.
+    pub fn kind(&self) -> &'static str {
         match self {
-            Self::Stay => write!(f, "Stay"),
-            Self::IntoJoining { .. } => write!(f, "IntoJoining"),
-            Self::Rebootstrap => write!(f, "Rebootstrap"),
-            Self::Relocate { .. } => write!(f, "Relocate"),
-            Self::IntoAdult { .. } => write!(f, "IntoAdult"),
-            Self::IntoElder { .. } => write!(f, "IntoElder"),
-            Self::Terminate => write!(f, "Terminate"),
+            Self::Stay => "Stay",
+            Self::IntoJoining { .. } => "IntoJoining",
+            Self::Rebootstrap => "Rebootstrap",
+            Self::Relocate { .. } => "Relocate",
+            Self::IntoAdult { .. } => "IntoAdult",
+            Self::IntoElder { .. } => "IntoElder",
+            Self::Demote => "Demote",
+            Self::Terminate => "Terminate",
         }
     }
 }
 
+impl Debug for Transition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind())
+    }
+}
+
 impl StateMachine {
     // Construct a new StateMachine by passing a function returning the initial state.
     pub fn new<F>(
         init_state: F,
         network_config: NetworkConfig,
         outbox: &mut dyn EventBox,
-    ) -> (mpmc::Sender<Action>, Self)
+    ) -> (ActionSender, Self)
     where
         F: FnOnce(NetworkService, Timer, &mut dyn EventBox) -> State,
     {
         let (action_tx, action_rx) = mpmc::unbounded();
         let (network_tx, network_rx) = mpmc::unbounded();
+        let waker = EventWaker::default();
+        let action_tx = ActionSender::new(action_tx, waker.clone());
 
         let network_service = unwrap!(
             NetworkBuilder::new(network_tx)
@@ -291,7 +438,8 @@ impl StateMachine {
         );
 
         let timer = Timer::new(action_tx.clone());
-        let state = init_state(network_service, timer, outbox);
+        let mut state = init_state(network_service, timer, outbox);
+        state.flush_sends();
         let is_running = match state {
             State::Terminated => false,
             _ => true,
@@ -301,6 +449,11 @@ impl StateMachine {
             network_rx,
             action_rx,
             is_running,
+            state_since: Instant::now(),
+            history: VecDeque::new(),
+            observer: None,
+            #[cfg(feature = "async")]
+            waker,
             #[cfg(feature = "mock_base")]
             events: Vec::new(),
         };
@@ -308,29 +461,72 @@ impl StateMachine {
         (action_tx, machine)
     }
 
+    // Construct the StateMachine of a node or client created through its builder: fills in the
+    // full id and network config the builder left unset, then starts in the state returned by
+    // `init_state`.
+    pub fn start<F>(
+        our_type: OurType,
+        full_id: Option<FullId>,
+        network_config: Option<NetworkConfig>,
+        init_state: F,
+        outbox: &mut dyn EventBox,
+    ) -> (ActionSender, Self)
+    where
+        F: FnOnce(NetworkService, FullId, Timer, &mut dyn EventBox) -> State,
+    {
+        let full_id = full_id.unwrap_or_else(FullId::new);
+        let mut network_config = network_config.unwrap_or_default();
+        network_config.our_type = our_type;
+
+        Self::new(
+            move |network_service, timer, outbox| {
+                init_state(network_service, full_id, timer, outbox)
+            },
+            network_config,
+            outbox,
+        )
+    }
+
     pub fn pause(self) -> Result<PausedState, RoutingError> {
-        // TODO: should we allow pausing from other states too?
-        match self.state {
-            State::Elder(state) => {
-                let mut state = state.pause()?;
-                state.network_rx = Some(self.network_rx);
-                Ok(state)
+        // A bootstrapping node has nothing worth keeping: it can just bootstrap again.
+        let mut state = match self.state {
+            State::JoiningPeer(state) => state.pause(),
+            State::Adult(state) => state.pause()?,
+            State::Elder(state) => state.pause()?,
+            State::Client(_) | State::BootstrappingPeer(_) | State::Terminated => {
+                return Err(RoutingError::InvalidStateForOperation)
             }
-            _ => Err(RoutingError::InvalidStateForOperation),
-        }
+        };
+        state.network_rx = Some(self.network_rx);
+        Ok(state)
     }
 
-    pub fn resume(mut state: PausedState) -> (mpmc::Sender<Action>, Self) {
+    pub fn resume(state: PausedState) -> (ActionSender, Self) {
         let (action_tx, action_rx) = mpmc::unbounded();
-        let network_rx = state.network_rx.take().expect("PausedState is incomplete");
+        let waker = EventWaker::default();
+        let action_tx = ActionSender::new(action_tx, waker.clone());
+        let network_rx = state.network_rx.expect("PausedState is incomplete");
 
         let timer = Timer::new(action_tx.clone());
-        let state = State::Elder(Elder::resume(state, timer));
+        let common = state.common;
+        let mut state = match state.stage {
+            PausedStage::JoiningPeer(state) => {
+                State::JoiningPeer(JoiningPeer::resume(common, state, timer))
+            }
+            PausedStage::Adult(state) => State::Adult(Adult::resume(common, state, timer)),
+            PausedStage::Elder(state) => State::Elder(Elder::resume(common, state, timer)),
+        };
+        state.flush_sends();
         let machine = StateMachine {
             state,
             network_rx,
             action_rx,
             is_running: true,
+            state_since: Instant::now(),
+            history: VecDeque::new(),
+            observer: None,
+            #[cfg(feature = "async")]
+            waker,
             #[cfg(feature = "mock_base")]
             events: Vec::new(),
         };
@@ -338,6 +534,29 @@ impl StateMachine {
         (action_tx, machine)
     }
 
+    /// Simulates an unclean shutdown: the state is paused, but the network service is dropped
+    /// immediately (without notifying the peers beyond the lost connections) and replaced with a
+    /// fresh, unconnected one.
+    #[cfg(feature = "mock_base")]
+    pub fn crash(self) -> Result<PausedState, RoutingError> {
+        let mut state = self.pause()?;
+
+        let mut network_config = state.common.network_service.service_mut().config();
+        network_config.ip = None;
+        network_config.port = None;
+
+        let (network_tx, network_rx) = mpmc::unbounded();
+        state.common.network_service = unwrap!(
+            NetworkBuilder::new(network_tx)
+                .with_config(network_config)
+                .build(),
+            "Unable to start network service"
+        );
+        state.network_rx = Some(network_rx);
+
+        Ok(state)
+    }
+
     fn handle_network_event(&mut self, event: NetworkEvent, outbox: &mut dyn EventBox) {
         let transition = self.state.handle_network_event(event, outbox);
         self.apply_transition(transition, outbox)
@@ -350,6 +569,17 @@ impl StateMachine {
 
     pub fn apply_transition(&mut self, transition: Transition, outbox: &mut dyn EventBox) {
         use self::Transition::*;
+
+        // Send what the handler queued, the most important messages first.
+        self.state.flush_sends();
+
+        if let Stay = transition {
+            return;
+        }
+
+        let from = self.state_name();
+        let kind = transition.kind();
+
         match transition {
             Stay => (),
             IntoJoining {
@@ -384,8 +614,57 @@ impl StateMachine {
                 State::Adult(src) => src.into_elder(elders_info, old_pfx, outbox),
                 _ => unreachable!(),
             }),
+            Demote => self.state.replace_with(|state| match state {
+                State::Elder(src) => src.demote(outbox),
+                _ => unreachable!(),
+            }),
             Terminate => self.terminate(),
         }
+
+        self.state.flush_sends();
+        self.record_transition(from, kind);
+    }
+
+    fn record_transition(&mut self, from: &'static str, transition: &'static str) {
+        let now = Instant::now();
+        if self.history.len() == TRANSITION_HISTORY_LEN {
+            let _ = self.history.pop_front();
+        }
+        let record = TransitionRecord {
+            from,
+            to: self.state_name(),
+            transition,
+            time_in_previous_state: now - self.state_since,
+        };
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_transition(&record);
+        }
+        self.history.push_back(record);
+        self.state_since = now;
+    }
+
+    /// Sets the observer to notify of each state transition, replacing the previous one.
+    pub fn set_transition_observer(&mut self, observer: Box<dyn TransitionObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Returns the name of the current state, or `"Terminated"` once the machine stopped.
+    pub fn state_name(&self) -> &'static str {
+        if self.is_running {
+            self.state.name()
+        } else {
+            State::Terminated.name()
+        }
+    }
+
+    /// Returns how long we have been in the current state.
+    pub fn time_in_state(&self) -> Duration {
+        Instant::now() - self.state_since
+    }
+
+    /// Returns the most recent transitions, oldest first.
+    pub fn transition_history(&self) -> impl Iterator<Item = &TransitionRecord> {
+        self.history.iter()
     }
 
     fn terminate(&mut self) {
@@ -412,6 +691,12 @@ impl StateMachine {
         }
     }
 
+    /// Returns the waker woken whenever an action is sent to the machine.
+    #[cfg(feature = "async")]
+    pub fn waker(&self) -> &EventWaker {
+        &self.waker
+    }
+
     /// Get reference to the current state.
     pub fn current(&self) -> &State {
         &self.state
@@ -532,6 +817,14 @@ impl StateMachine {
     }
 }
 
+#[cfg(feature = "fuzzing")]
+impl StateMachine {
+    /// Handle the given network event right away, as if it was just received from the transport.
+    pub fn inject_network_event(&mut self, event: NetworkEvent, outbox: &mut dyn EventBox) {
+        self.handle_network_event(event, outbox)
+    }
+}
+
 impl Display for StateMachine {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter, "{:?}", self.state)
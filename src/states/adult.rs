@@ -8,47 +8,51 @@
 
 use super::{
     bootstrapping_peer::BootstrappingPeer,
-    common::{Approved, Base, GOSSIP_TIMEOUT},
+    common::{Approved, Base},
     elder::{Elder, ElderDetails},
 };
+#[cfg(feature = "mock_base")]
+use crate::parsec::ParsecMetrics;
 use crate::{
+    backlog::Backlog,
     chain::{
-        Chain, EldersChange, EldersInfo, GenesisPfxInfo, OnlinePayload, SectionKeyInfo,
-        SendAckMessagePayload,
+        AckMessagePayload, Chain, ConflictingVotesPayload, EldersChange, EldersInfo,
+        GenesisPfxInfo, OnlinePayload, SectionKeyInfo, SendAckMessagesPayload,
     },
-    error::{BootstrapResponseError, RoutingError},
+    error::{BootstrapResponseError, InterfaceError, RoutingError},
     event::Event,
     id::{FullId, PublicId},
     messages::{
-        BootstrapResponse, DirectMessage, HopMessage, RelocateDetails, RoutingMessage,
+        BootstrapResponse, DirectMessage, HopMessage, Message, RelocateDetails, RoutingMessage,
         SignedRoutingMessage,
     },
     outbox::EventBox,
     parsec::ParsecMap,
+    pause::{PausedAdult, PausedCommon, PausedStage, PausedState},
     peer_map::PeerMap,
-    routing_message_filter::RoutingMessageFilter,
+    routing_message_filter::{DuplicateCounts, RoutingMessageFilter},
     routing_table::{Authority, Prefix},
     state_machine::{State, Transition},
     time::Duration,
     timer::Timer,
+    types::MessageId,
     xor_name::XorName,
-    NetworkService,
+    NetworkParams, NetworkService, Timeouts,
 };
 use itertools::Itertools;
 use std::fmt::{self, Display, Formatter};
 
-const POKE_TIMEOUT: Duration = Duration::from_secs(60);
-
-/// Time after which the node reinitiates the bootstrap if it is not added to the section.
+/// Default time after which the node reinitiates the bootstrap if it is not added to the section.
+/// See `Timeouts::add`.
 pub const ADD_TIMEOUT: Duration = Duration::from_secs(120);
 
 pub struct AdultDetails {
     pub network_service: NetworkService,
-    pub event_backlog: Vec<Event>,
+    pub event_backlog: Backlog<Event>,
     pub full_id: FullId,
     pub gen_pfx_info: GenesisPfxInfo,
-    pub min_section_size: usize,
-    pub msg_backlog: Vec<SignedRoutingMessage>,
+    pub network_params: NetworkParams,
+    pub msg_backlog: Backlog<SignedRoutingMessage>,
     pub peer_map: PeerMap,
     pub routing_msg_filter: RoutingMessageFilter,
     pub timer: Timer,
@@ -57,31 +61,57 @@ pub struct AdultDetails {
 pub struct Adult {
     chain: Chain,
     network_service: NetworkService,
-    event_backlog: Vec<Event>,
+    event_backlog: Backlog<Event>,
     full_id: FullId,
     gen_pfx_info: GenesisPfxInfo,
     /// Routing messages addressed to us that we cannot handle until we are established.
-    msg_backlog: Vec<SignedRoutingMessage>,
+    msg_backlog: Backlog<SignedRoutingMessage>,
     parsec_map: ParsecMap,
     peer_map: PeerMap,
-    add_timer_token: u64,
+    /// Set until we are added to our section. Not set if we stepped down from elder.
+    add_timer_token: Option<u64>,
     parsec_timer_token: u64,
     routing_msg_filter: RoutingMessageFilter,
     timer: Timer,
+    /// Set when we are leaving the network, until our section votes us offline or the timeout
+    /// expires.
+    leave_timer_token: Option<u64>,
 }
 
 impl Adult {
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    pub fn dropped_backlog_entries(&self) -> u64 {
+        self.msg_backlog.dropped() + self.event_backlog.dropped()
+    }
+
+    pub fn duplicate_counts(&self) -> &DuplicateCounts {
+        self.routing_msg_filter.duplicate_counts()
+    }
+
+    fn timeouts(&self) -> Timeouts {
+        self.chain.network_params().timeouts
+    }
+
     pub fn from_joining_peer(
         details: AdultDetails,
         outbox: &mut dyn EventBox,
     ) -> Result<Self, RoutingError> {
         let public_id = *details.full_id.public_id();
-        let parsec_timer_token = details.timer.schedule(POKE_TIMEOUT);
-        let add_timer_token = details.timer.schedule(ADD_TIMEOUT);
-
-        let parsec_map = ParsecMap::new(details.full_id.clone(), &details.gen_pfx_info);
+        let timeouts = details.network_params.timeouts;
+        let parsec_timer_token = details.timer.schedule(timeouts.poke);
+        let add_timer_token = details.timer.schedule(timeouts.add);
+
+        let parsec_map = ParsecMap::new(
+            details.full_id.clone(),
+            &details.gen_pfx_info,
+            details.network_params.prune_policy,
+            details.network_params.local.vote_log_path.as_ref(),
+        );
         let chain = Chain::new(
-            details.min_section_size,
+            details.network_params,
             public_id,
             details.gen_pfx_info.clone(),
         );
@@ -98,17 +128,108 @@ impl Adult {
             routing_msg_filter: details.routing_msg_filter,
             timer: details.timer,
             parsec_timer_token,
-            add_timer_token,
+            add_timer_token: Some(add_timer_token),
+            leave_timer_token: None,
         };
 
         node.init(outbox)?;
         Ok(node)
     }
 
+    /// Creates an adult from an elder which stepped down, keeping its view of the section.
+    pub fn from_elder(
+        details: AdultDetails,
+        chain: Chain,
+        parsec_map: ParsecMap,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Self, RoutingError> {
+        let parsec_timer_token = details.timer.schedule(chain.network_params().timeouts.poke);
+
+        let mut node = Self {
+            chain,
+            network_service: details.network_service,
+            event_backlog: details.event_backlog,
+            full_id: details.full_id,
+            gen_pfx_info: details.gen_pfx_info,
+            msg_backlog: details.msg_backlog,
+            parsec_map,
+            peer_map: details.peer_map,
+            routing_msg_filter: details.routing_msg_filter,
+            timer: details.timer,
+            parsec_timer_token,
+            add_timer_token: None,
+            leave_timer_token: None,
+        };
+
+        node.init(outbox)?;
+        Ok(node)
+    }
+
+    pub fn pause(mut self) -> Result<PausedState, RoutingError> {
+        let chain_accumulator = self.chain.take_accumulator_state();
+        Ok(PausedState {
+            common: PausedCommon {
+                full_id: self.full_id,
+                msg_filter: self.routing_msg_filter,
+                msg_queue: self.msg_backlog.into_vec(),
+                network_service: self.network_service,
+                peer_map: self.peer_map,
+            },
+            network_rx: None,
+            stage: PausedStage::Adult(PausedAdult {
+                chain: self.chain,
+                chain_accumulator,
+                event_backlog: self.event_backlog.into_vec(),
+                gen_pfx_info: self.gen_pfx_info,
+                parsec_map: self.parsec_map,
+                awaiting_add: self.add_timer_token.is_some(),
+            }),
+        })
+    }
+
+    pub fn resume(common: PausedCommon, state: PausedAdult, timer: Timer) -> Self {
+        let mut chain = state.chain;
+        chain.restore_accumulator_state(state.chain_accumulator);
+
+        let timeouts = chain.network_params().timeouts;
+        let backlog_limits = chain.network_params().backlog_limits;
+        let parsec_timer_token = timer.schedule(timeouts.poke);
+        let add_timer_token = if state.awaiting_add {
+            Some(timer.schedule(timeouts.add))
+        } else {
+            None
+        };
+
+        let mut event_backlog = Backlog::new(backlog_limits);
+        for event in state.event_backlog {
+            event_backlog.push(event);
+        }
+        let mut msg_backlog = Backlog::new(backlog_limits);
+        for msg in common.msg_queue {
+            msg_backlog.push(msg);
+        }
+
+        Self {
+            chain,
+            network_service: common.network_service,
+            event_backlog,
+            full_id: common.full_id,
+            gen_pfx_info: state.gen_pfx_info,
+            msg_backlog,
+            parsec_map: state.parsec_map,
+            peer_map: common.peer_map,
+            routing_msg_filter: common.msg_filter,
+            timer,
+            parsec_timer_token,
+            add_timer_token,
+            leave_timer_token: None,
+        }
+    }
+
     fn init(&mut self, outbox: &mut dyn EventBox) -> Result<(), RoutingError> {
         debug!("{} - State changed to Adult.", self);
 
-        for msg in self.msg_backlog.drain(..).collect_vec() {
+        for msg in self.msg_backlog.take_all() {
             self.dispatch_routing_message(msg, outbox)?;
         }
 
@@ -116,7 +237,7 @@ impl Adult {
     }
 
     pub fn rebootstrap(self) -> Result<State, RoutingError> {
-        let min_section_size = self.min_section_size();
+        let network_params = self.chain.network_params().clone();
 
         // Try to join the same section, but using new id, otherwise the section won't accept us
         // due to duplicate votes.
@@ -125,7 +246,7 @@ impl Adult {
         Ok(State::BootstrappingPeer(BootstrappingPeer::new(
             self.network_service,
             full_id,
-            min_section_size,
+            network_params,
             self.timer,
         )))
     }
@@ -139,15 +260,15 @@ impl Adult {
         let details = ElderDetails {
             chain: self.chain,
             network_service: self.network_service,
-            event_backlog: self.event_backlog,
+            event_backlog: self.event_backlog.into_vec(),
             full_id: self.full_id,
             gen_pfx_info: self.gen_pfx_info,
-            msg_queue: self.msg_backlog.into_iter().collect(),
+            msg_queue: self.msg_backlog.into_vec(),
             parsec_map: self.parsec_map,
             peer_map: self.peer_map,
             // we reset the message filter so that the node can correctly process some messages as
             // an Elder even if it has already seen them as an Adult
-            routing_msg_filter: RoutingMessageFilter::new(),
+            routing_msg_filter: self.routing_msg_filter.reset(),
             timer: self.timer,
         };
 
@@ -170,19 +291,28 @@ impl Adult {
                     ConnectionRequest {
                         conn_info,
                         pub_id,
+                        capabilities,
                         msg_id,
                     },
                 src: Node(_),
                 dst: Node(_),
             } => {
                 if self.chain.our_prefix().matches(&msg.src.name()) {
-                    self.handle_connection_request(conn_info, pub_id, msg.src, msg.dst, outbox)
+                    self.handle_connection_request(
+                        conn_info,
+                        pub_id,
+                        capabilities,
+                        msg.src,
+                        msg.dst,
+                        outbox,
+                    )
                 } else {
                     self.add_message_to_backlog(SignedRoutingMessage::from_parts(
                         RoutingMessage {
                             content: ConnectionRequest {
                                 conn_info,
                                 pub_id,
+                                capabilities,
                                 msg_id,
                             },
                             ..msg
@@ -192,6 +322,14 @@ impl Adult {
                     Ok(())
                 }
             }
+            RoutingMessage {
+                content: NodeApproval(_),
+                src: PrefixSection(_),
+                dst: Node(_),
+            } => {
+                self.handle_duplicate_node_approval();
+                Ok(())
+            }
             _ => {
                 self.add_message_to_backlog(SignedRoutingMessage::from_parts(msg, metadata));
                 Ok(())
@@ -221,6 +359,13 @@ impl Adult {
         }
     }
 
+    fn finish_leave(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        info!("{} - Leaving the network.", self);
+        self.leave_timer_token = None;
+        outbox.send_event(Event::Terminated);
+        Transition::Terminate
+    }
+
     // Backlog the message to be processed once we are established.
     fn add_message_to_backlog(&mut self, msg: SignedRoutingMessage) {
         trace!(
@@ -246,21 +391,34 @@ impl Adult {
         );
         self.disconnect(&pub_id);
     }
+
+    // The section started relocating a member: it no longer counts as one of ours.
+    fn start_relocation(&mut self, details: RelocateDetails, outbox: &mut dyn EventBox) {
+        self.chain.remove_member(&details.pub_id);
+        outbox.send_event(Event::RelocationStarted {
+            name: *details.pub_id.name(),
+            destination: details.destination,
+        });
+    }
 }
 
 #[cfg(feature = "mock_base")]
 impl Adult {
-    pub fn chain(&self) -> &Chain {
-        &self.chain
-    }
-
     pub fn get_timed_out_tokens(&mut self) -> Vec<u64> {
         self.timer.get_timed_out_tokens()
     }
 
+    pub fn next_timer_deadline(&self) -> Option<crate::time::Instant> {
+        self.timer.next_deadline()
+    }
+
     pub fn has_unpolled_observations(&self) -> bool {
         self.parsec_map.has_unpolled_observations()
     }
+
+    pub fn parsec_metrics(&self) -> ParsecMetrics {
+        self.parsec_map.metrics()
+    }
 }
 
 impl Base for Adult {
@@ -296,16 +454,82 @@ impl Base for Adult {
         &mut self.timer
     }
 
-    fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
+    fn handle_request_proof_chain(&mut self, from_version: u64) -> Result<(), InterfaceError> {
+        self.send_proof_chain_request(from_version)
+    }
+
+    fn handle_send_message(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        trace_id: Option<MessageId>,
+    ) -> Result<(), InterfaceError> {
+        // We can only sign as ourselves: sending on behalf of our section needs the elders.
+        if src != Authority::Node(*self.name()) {
+            warn!(
+                "{} - Cannot send a message from {:?} - not an elder.",
+                self, src
+            );
+            return Err(InterfaceError::InvalidSource);
+        }
+
+        match self.send_user_message(src, dst, content, None, trace_id) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
+        }
+    }
+
+    fn handle_send_message_to_many(
+        &mut self,
+        src: Authority<XorName>,
+        dsts: Vec<Authority<XorName>>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        if src != Authority::Node(*self.name()) {
+            warn!(
+                "{} - Cannot send a message from {:?} - not an elder.",
+                self, src
+            );
+            return Err(InterfaceError::InvalidSource);
+        }
+
+        match self.send_user_message_to_many(src, &dsts, content) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
+        }
+    }
+
+    fn handle_initiate_leave(&mut self) -> Result<(), InterfaceError> {
+        if self.leave_timer_token.is_some() {
+            return Ok(());
+        }
+
+        info!("{} - Initiating leave.", self);
+
+        let elders: Vec<_> = self.chain.our_elders().copied().collect();
+        for pub_id in &elders {
+            self.send_direct_message(pub_id, DirectMessage::Leave);
+        }
+
+        self.leave_timer_token = Some(self.timer.schedule(self.timeouts().leave));
+        Ok(())
+    }
+
+    fn handle_timeout(&mut self, token: u64, outbox: &mut dyn EventBox) -> Transition {
         if self.parsec_timer_token == token {
-            if self.chain.is_peer_our_elder(self.id()) {
+            // After stepping down we are still listed in our elders until the section agrees on
+            // their change, but parsec already removed us, so we need to be poked.
+            if self.chain.is_peer_our_elder(self.id())
+                && !self.parsec_map.gossip_recipients().is_empty()
+            {
                 self.send_parsec_gossip(None);
-                self.parsec_timer_token = self.timer.schedule(GOSSIP_TIMEOUT);
+                self.parsec_timer_token = self.timer.schedule(self.timeouts().gossip);
             } else {
                 self.send_parsec_poke();
-                self.parsec_timer_token = self.timer.schedule(POKE_TIMEOUT);
+                self.parsec_timer_token = self.timer.schedule(self.timeouts().poke);
             }
-        } else if self.add_timer_token == token {
+        } else if self.add_timer_token == Some(token) {
             debug!("{} - Timeout when trying to join a section.", self);
 
             for peer_addr in self
@@ -319,6 +543,9 @@ impl Base for Adult {
             }
 
             return Transition::Rebootstrap;
+        } else if self.leave_timer_token == Some(token) {
+            debug!("{} - Timeout when waiting to be voted offline.", self);
+            return self.finish_leave(outbox);
         }
 
         Transition::Stay
@@ -343,12 +570,20 @@ impl Base for Adult {
             ParsecResponse(version, par_response) => {
                 self.handle_parsec_response(version, par_response, pub_id, outbox)
             }
-            BootstrapRequest(name) => {
+            BootstrapRequest(name, _) => {
                 self.handle_bootstrap_request(pub_id, name);
                 Ok(Transition::Stay)
             }
             ConnectionResponse => {
                 debug!("{} - Received connection response from {}", self, pub_id);
+                if let Some(conn_info) = self.peer_map.get_connection_info(&pub_id) {
+                    self.network_service
+                        .accept_compressed_from(conn_info.peer_addr);
+                }
+                Ok(Transition::Stay)
+            }
+            ProofChainResponse(proof_chain) => {
+                self.handle_proof_chain_response(proof_chain, pub_id, outbox);
                 Ok(Transition::Stay)
             }
             _ => {
@@ -379,11 +614,28 @@ impl Base for Adult {
     }
 
     fn send_routing_message(&mut self, routing_msg: RoutingMessage) -> Result<(), RoutingError> {
+        self.send_traced_routing_message(routing_msg, None)
+    }
+
+    fn send_traced_routing_message(
+        &mut self,
+        routing_msg: RoutingMessage,
+        trace_id: Option<MessageId>,
+    ) -> Result<(), RoutingError> {
         if self.in_authority(&routing_msg.dst) {
             return Ok(()); // Message is for us.
         }
 
-        let signed_msg = SignedRoutingMessage::single_source(routing_msg, self.full_id())?;
+        let mut signed_msg = SignedRoutingMessage::single_source(routing_msg, self.full_id())?;
+        signed_msg.set_trace_id(trace_id);
+        if let Some(trace_id) = trace_id {
+            info!(
+                "{} - Trace {:?}: sending {:?} to our elders.",
+                self,
+                trace_id,
+                signed_msg.routing_message()
+            );
+        }
 
         // We should only be connected to our own Elders - send to all of them
         // Need to collect IDs first so that self is not borrowed via the iterator
@@ -417,6 +669,10 @@ impl Approved for Adult {
         &mut self.parsec_map
     }
 
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
     fn chain_mut(&mut self) -> &mut Chain {
         &mut self.chain
     }
@@ -429,6 +685,42 @@ impl Approved for Adult {
         false
     }
 
+    fn send_signed_messages(
+        &mut self,
+        msgs: Vec<SignedRoutingMessage>,
+    ) -> Result<(), RoutingError> {
+        let msgs: Vec<_> = msgs
+            .into_iter()
+            .filter(|msg| !self.in_authority(&msg.routing_message().dst))
+            .collect();
+
+        // We should only be connected to our own Elders - send each of them the messages it
+        // hasn't seen yet, all together.
+        let target_ids: Vec<_> = self.peer_map.connected_ids().cloned().collect();
+
+        for pub_id in target_ids {
+            let mut hop_msgs = Vec::new();
+            for msg in &msgs {
+                if self
+                    .routing_msg_filter
+                    .filter_outgoing(msg.routing_message(), &pub_id)
+                    .is_new()
+                {
+                    hop_msgs.push(HopMessage::new(msg.clone())?);
+                }
+            }
+
+            let message = match hop_msgs.len() {
+                0 => continue,
+                1 => Message::Hop(hop_msgs.remove(0)),
+                _ => Message::Hops(hop_msgs),
+            };
+            self.send_message(&pub_id, message);
+        }
+
+        Ok(())
+    }
+
     fn handle_add_elder_event(
         &mut self,
         pub_id: PublicId,
@@ -448,7 +740,7 @@ impl Approved for Adult {
 
         // If the elder being added is us, start sending parsec gossips.
         if pub_id == *self.id() {
-            self.parsec_timer_token = self.timer.schedule(GOSSIP_TIMEOUT);
+            self.parsec_timer_token = self.timer.schedule(self.timeouts().gossip);
         }
 
         Ok(())
@@ -458,13 +750,13 @@ impl Approved for Adult {
         &mut self,
         pub_id: PublicId,
         outbox: &mut dyn EventBox,
-    ) -> Result<(), RoutingError> {
+    ) -> Result<Transition, RoutingError> {
         info!("{} - handle RemoveElder: {}.", self, pub_id);
         let _ = self.chain.remove_elder(pub_id)?;
         self.disconnect(&pub_id);
         self.send_event(Event::NodeLost(*pub_id.name()), outbox);
 
-        Ok(())
+        Ok(Transition::Stay)
     }
 
     fn handle_online_event(
@@ -477,10 +769,19 @@ impl Approved for Adult {
         Ok(())
     }
 
-    fn handle_offline_event(&mut self, pub_id: PublicId) -> Result<(), RoutingError> {
+    fn handle_offline_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
         info!("{} - handle Offline: {}.", self, pub_id);
         self.chain.remove_member(&pub_id);
-        Ok(())
+
+        if pub_id == *self.id() && self.leave_timer_token.is_some() {
+            return Ok(self.finish_leave(outbox));
+        }
+
+        Ok(Transition::Stay)
     }
 
     fn handle_section_info_event(
@@ -501,16 +802,42 @@ impl Approved for Adult {
         }
     }
 
-    fn handle_relocate_event(&mut self, details: RelocateDetails) -> Result<(), RoutingError> {
+    fn handle_relocate_event(
+        &mut self,
+        details: RelocateDetails,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
         info!("{} - handle Relocate: {:?}.", self, details);
 
         if !self.chain.our_prefix().matches(&details.destination) {
-            self.chain.remove_member(&details.pub_id);
+            if let Some(details) = self.chain.start_relocation(details) {
+                self.start_relocation(details, outbox);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_relocation_complete_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        if self.chain.is_relocating(&pub_id) {
+            outbox.send_event(Event::RelocationCompleted(*pub_id.name()));
+        }
+
+        if let Some(details) = self.chain.complete_relocation(&pub_id) {
+            self.start_relocation(details, outbox);
         }
 
         Ok(())
     }
 
+    fn handle_step_down_event(&mut self, _pub_id: PublicId) -> Result<(), RoutingError> {
+        Ok(())
+    }
+
     fn handle_their_key_info_event(
         &mut self,
         _key_info: SectionKeyInfo,
@@ -518,9 +845,20 @@ impl Approved for Adult {
         Ok(())
     }
 
-    fn handle_send_ack_message_event(
+    fn handle_ack_message_event(&mut self, _ack_payload: &AckMessagePayload) {}
+
+    fn handle_send_ack_messages_event(
+        &mut self,
+        _ack_payload: SendAckMessagesPayload,
+    ) -> Result<(), RoutingError> {
+        Ok(())
+    }
+
+    fn handle_conflicting_votes(&mut self, _payload: ConflictingVotesPayload) {}
+
+    fn handle_conflicting_votes_event(
         &mut self,
-        _ack_payload: SendAckMessagePayload,
+        _payload: ConflictingVotesPayload,
     ) -> Result<(), RoutingError> {
         Ok(())
     }
@@ -8,43 +8,53 @@
 
 use super::common::Base;
 use crate::{
-    error::{InterfaceError, RoutingError},
+    error::{BootstrapResponseError, InterfaceError, RoutingError},
     event::Event,
     id::{FullId, PublicId},
     messages::{
-        BootstrapResponse, DirectMessage, HopMessage, RelocatePayload, RoutingMessage,
-        SignedRelocateDetails,
+        BootstrapResponse, Capabilities, DirectMessage, HopMessage, ProtocolVersion,
+        RelocatePayload, RoutingMessage, SignedRelocateDetails,
     },
     outbox::EventBox,
+    pause::RelocationState,
     peer_map::PeerMap,
     routing_table::{Authority, Prefix},
     state_machine::{State, Transition},
     states::JoiningPeer,
     timer::Timer,
+    types::MessageId,
     xor_name::XorName,
-    ConnectionInfo, NetworkService,
+    ConnectionInfo, NetworkParams, NetworkService,
 };
 use log::LogLevel;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::{self, Display, Formatter},
     net::SocketAddr,
     time::Duration,
 };
 
-/// Time after which bootstrap is cancelled (and possibly retried).
+/// Default time after which bootstrap is cancelled (and possibly retried). See
+/// `Timeouts::bootstrap`.
 pub const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(20);
+/// Maximum number of proxies we race `BootstrapRequest`s against at the same time.
+const MAX_PARALLEL_BOOTSTRAPS: usize = 3;
 
 // State of Client or Node while bootstrapping.
 pub struct BootstrappingPeer {
     nodes_to_await: HashSet<SocketAddr>,
-    bootstrap_connection: Option<(ConnectionInfo, u64)>,
+    // Proxies we sent a `BootstrapRequest` to and are waiting for a response from, with the token
+    // of the timeout of each request. The first usable response wins.
+    bootstrap_connections: HashMap<SocketAddr, (ConnectionInfo, u64)>,
+    // Token of the timer to retry bootstrapping once a blacklisted proxy cools down.
+    retry_timer_token: Option<u64>,
     network_service: NetworkService,
     full_id: FullId,
-    min_section_size: usize,
+    network_params: NetworkParams,
     peer_map: PeerMap,
     timer: Timer,
-    relocate_details: Option<SignedRelocateDetails>,
+    // Set if we are being relocated, until we join the destination section.
+    relocation: Option<RelocationState>,
 }
 
 impl BootstrappingPeer {
@@ -53,19 +63,21 @@ impl BootstrappingPeer {
     pub fn new(
         mut network_service: NetworkService,
         full_id: FullId,
-        min_section_size: usize,
+        network_params: NetworkParams,
         timer: Timer,
     ) -> Self {
         network_service.service_mut().bootstrap();
+        let peer_map = PeerMap::with_inbound_limits(network_params.local.inbound_limits);
         Self {
             network_service,
             full_id,
-            min_section_size,
+            network_params,
             timer,
-            bootstrap_connection: None,
+            bootstrap_connections: Default::default(),
+            retry_timer_token: None,
             nodes_to_await: Default::default(),
-            peer_map: PeerMap::new(),
-            relocate_details: None,
+            peer_map,
+            relocation: None,
         }
     }
 
@@ -73,20 +85,26 @@ impl BootstrappingPeer {
     pub fn relocate(
         network_service: NetworkService,
         full_id: FullId,
-        min_section_size: usize,
+        network_params: NetworkParams,
         timer: Timer,
         conn_infos: Vec<ConnectionInfo>,
         relocate_details: SignedRelocateDetails,
     ) -> Self {
+        let peer_map = PeerMap::with_inbound_limits(network_params.local.inbound_limits);
         let mut node = Self {
             network_service,
-            full_id,
-            min_section_size,
+            full_id: full_id.clone(),
+            network_params,
             timer,
-            bootstrap_connection: None,
+            bootstrap_connections: Default::default(),
+            retry_timer_token: None,
             nodes_to_await: conn_infos.iter().map(|info| info.peer_addr).collect(),
-            peer_map: PeerMap::new(),
-            relocate_details: Some(relocate_details),
+            peer_map,
+            relocation: Some(RelocationState {
+                full_id,
+                details: relocate_details,
+                conn_infos: conn_infos.clone(),
+            }),
         };
 
         for conn_info in conn_infos {
@@ -96,59 +114,213 @@ impl BootstrappingPeer {
         node
     }
 
+    /// Create `BootstrappingPeer` for a node resuming a relocation interrupted by a restart.
+    pub fn resume_relocation(
+        network_service: NetworkService,
+        network_params: NetworkParams,
+        timer: Timer,
+        relocation: RelocationState,
+    ) -> Self {
+        info!(
+            "Resuming relocation of {} to {}.",
+            relocation.full_id.public_id(),
+            relocation.destination()
+        );
+        Self::relocate(
+            network_service,
+            relocation.full_id,
+            network_params,
+            timer,
+            relocation.conn_infos,
+            relocation.details,
+        )
+    }
+
+    pub fn relocation_state(&self) -> Option<RelocationState> {
+        self.relocation.clone()
+    }
+
     pub fn into_joining(
         self,
         conn_infos: Vec<ConnectionInfo>,
         relocate_payload: Option<RelocatePayload>,
         _outbox: &mut dyn EventBox,
     ) -> Result<State, RoutingError> {
+        // Should we restart while joining, we bootstrap to the section we are joining again.
+        let relocation = self.relocation.map(|relocation| RelocationState {
+            conn_infos: conn_infos.clone(),
+            ..relocation
+        });
+
         Ok(State::JoiningPeer(JoiningPeer::new(
             self.network_service,
             self.full_id,
-            self.min_section_size,
+            self.network_params,
             self.timer,
             self.peer_map,
             conn_infos,
             relocate_payload,
+            relocation,
         )))
     }
 
     fn send_bootstrap_request(&mut self, dst: ConnectionInfo) {
         let _ = self.nodes_to_await.remove(&dst.peer_addr);
 
-        if let Some((bootstrap_dst, _)) = self.bootstrap_connection.as_ref() {
-            if *bootstrap_dst != dst {
-                // we already have an active connection, drop this one
-                self.network_service
-                    .service_mut()
-                    .disconnect_from(dst.peer_addr);
+        if self.bootstrap_connections.contains_key(&dst.peer_addr) {
+            return;
+        }
+
+        let cool_down = self.network_params.local.proxy_blacklist_duration;
+        if let Some(remaining) = self
+            .network_service
+            .proxy_blacklist_mut()
+            .remaining(&dst.peer_addr, cool_down)
+        {
+            debug!("{} Skipping blacklisted proxy {}.", self, dst.peer_addr);
+            let _ = self.peer_map.disconnect(dst.peer_addr);
+            self.network_service
+                .service_mut()
+                .disconnect_from(dst.peer_addr);
+
+            // It might be the only proxy we can reach, so try again once it cools down rather than
+            // straight away.
+            if self.nodes_to_await.is_empty()
+                && self.bootstrap_connections.is_empty()
+                && self.retry_timer_token.is_none()
+            {
+                self.retry_timer_token = Some(self.timer.schedule(remaining));
             }
+            return;
+        }
+
+        if self.bootstrap_connections.len() >= MAX_PARALLEL_BOOTSTRAPS {
+            // we already race enough proxies, drop this one
+            self.network_service
+                .service_mut()
+                .disconnect_from(dst.peer_addr);
+            return;
+        }
+
+        debug!("{} Sending BootstrapRequest to {}.", self, dst.peer_addr);
+
+        let token = self.timer.schedule(self.network_params.timeouts.bootstrap);
+        let _ = self
+            .bootstrap_connections
+            .insert(dst.peer_addr, (dst.clone(), token));
+
+        // If we are relocating, request bootstrap to the section matching the name given to us
+        // by our section. Otherwise request bootstrap to the section matching our current name.
+        let destination = if let Some(relocation) = self.relocation.as_ref() {
+            *relocation.destination()
         } else {
-            debug!("{} Sending BootstrapRequest to {}.", self, dst.peer_addr);
+            *self.name()
+        };
 
-            let token = self.timer.schedule(BOOTSTRAP_TIMEOUT);
-            self.bootstrap_connection = Some((dst.clone(), token));
+        self.send_direct_message(
+            &dst,
+            DirectMessage::BootstrapRequest(destination, Capabilities::ours()),
+        );
+        self.peer_map_mut().connect(dst);
+    }
 
-            // If we are relocating, request bootstrap to the section matching the name given to us
-            // by our section. Otherwise request bootstrap to the section matching our current name.
-            let destination = if let Some(details) = self.relocate_details.as_ref() {
-                details.content().destination
-            } else {
-                *self.name()
-            };
+    // Returns the address of the proxy `pub_id` if we are waiting for its `BootstrapResponse`.
+    fn bootstrap_connection_of(&self, pub_id: &PublicId) -> Option<SocketAddr> {
+        self.peer_map
+            .get_connection_info(pub_id)
+            .map(|conn_info| conn_info.peer_addr)
+            .filter(|peer_addr| self.bootstrap_connections.contains_key(peer_addr))
+    }
 
-            self.send_direct_message(&dst, DirectMessage::BootstrapRequest(destination));
-            self.peer_map_mut().connect(dst);
+    fn handle_bootstrap_response(
+        &mut self,
+        pub_id: PublicId,
+        response: BootstrapResponse,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
+        let proxy_addr = if let Some(peer_addr) = self.bootstrap_connection_of(&pub_id) {
+            peer_addr
+        } else {
+            debug!(
+                "{} - Ignoring BootstrapResponse from {} - not awaited.",
+                self, pub_id
+            );
+            return Ok(Transition::Stay);
+        };
+
+        match response {
+            BootstrapResponse::Join {
+                prefix,
+                conn_infos,
+                capabilities,
+            } => {
+                info!(
+                    "{} - Joining a section {:?}: {:?}",
+                    self, prefix, conn_infos
+                );
+                if let Some(common) = Capabilities::ours().common(&capabilities) {
+                    self.network_service
+                        .set_peer_capabilities(proxy_addr, common);
+                }
+                self.join_section(proxy_addr, prefix, conn_infos)
+            }
+            BootstrapResponse::Rebootstrap(new_conn_infos) => {
+                info!(
+                    "{} - Bootstrapping redirected to another set of peers: {:?}",
+                    self, new_conn_infos
+                );
+                self.reconnect_to_new_section(new_conn_infos);
+                Ok(Transition::Stay)
+            }
+            BootstrapResponse::TryAgainLater { backoff } => {
+                info!(
+                    "{} - Section of {} is busy - trying again in {:?}.",
+                    self, proxy_addr, backoff
+                );
+
+                self.disconnect_from_bootstrap_proxy(proxy_addr);
+                if self.nodes_to_await.is_empty()
+                    && self.bootstrap_connections.is_empty()
+                    && self.retry_timer_token.is_none()
+                {
+                    self.retry_timer_token = Some(self.timer.schedule(backoff));
+                }
+                Ok(Transition::Stay)
+            }
+            BootstrapResponse::Error(BootstrapResponseError::IncompatibleVersion(theirs)) => {
+                error!(
+                    "{} - Bootstrap rejected by {} - incompatible protocol version {:?}. Terminating.",
+                    self, proxy_addr, theirs
+                );
+                outbox.send_event(Event::IncompatibleProtocol {
+                    ours: ProtocolVersion::CURRENT,
+                    theirs,
+                });
+                outbox.send_event(Event::Terminated);
+                Ok(Transition::Terminate)
+            }
+            BootstrapResponse::Error(error) => {
+                debug!(
+                    "{} - Bootstrap rejected by {}: {:?}",
+                    self, proxy_addr, error
+                );
+
+                self.network_service
+                    .proxy_blacklist_mut()
+                    .insert(proxy_addr);
+                self.disconnect_from_bootstrap_proxy(proxy_addr);
+                self.rebootstrap();
+                Ok(Transition::Stay)
+            }
         }
     }
 
     fn join_section(
         &mut self,
+        winner: SocketAddr,
         prefix: Prefix<XorName>,
         conn_infos: Vec<ConnectionInfo>,
     ) -> Result<Transition, RoutingError> {
-        let old_full_id = self.full_id.clone();
-
         if !prefix.matches(self.name()) {
             let new_full_id = FullId::within_range(&prefix.range_inclusive());
             info!(
@@ -159,11 +331,30 @@ impl BootstrappingPeer {
             self.full_id = new_full_id;
         }
 
-        let relocate_payload = if let Some(details) = self.relocate_details.take() {
+        // We are done racing: drop the other proxies, unless we are about to join through them.
+        let losers: Vec<_> = self
+            .bootstrap_connections
+            .drain()
+            .map(|(peer_addr, _)| peer_addr)
+            .filter(|peer_addr| {
+                *peer_addr != winner
+                    && conn_infos
+                        .iter()
+                        .all(|conn_info| conn_info.peer_addr != *peer_addr)
+            })
+            .collect();
+        for peer_addr in losers {
+            let _ = self.peer_map.disconnect(peer_addr);
+            self.network_service
+                .service_mut()
+                .disconnect_from(peer_addr);
+        }
+
+        let relocate_payload = if let Some(relocation) = self.relocation.as_ref() {
             Some(RelocatePayload::new(
-                details,
+                relocation.details.clone(),
                 self.full_id.public_id(),
-                &old_full_id,
+                &relocation.full_id,
             )?)
         } else {
             None
@@ -176,16 +367,11 @@ impl BootstrappingPeer {
     }
 
     fn reconnect_to_new_section(&mut self, new_conn_infos: Vec<ConnectionInfo>) {
-        if let Some((conn_info, _)) = self.bootstrap_connection.take() {
-            debug!(
-                "{} Dropping connected node at {} and retrying.",
-                self, conn_info.peer_addr
-            );
-
-            // drop the current connection
-            self.network_service
-                .service_mut()
-                .disconnect_from(conn_info.peer_addr);
+        // The redirect tells us where our section is, so the responses of the other proxies are
+        // no longer needed.
+        let proxies: Vec<_> = self.bootstrap_connections.keys().copied().collect();
+        for peer_addr in proxies {
+            self.disconnect_from_bootstrap_proxy(peer_addr);
         }
 
         self.nodes_to_await = new_conn_infos
@@ -198,27 +384,24 @@ impl BootstrappingPeer {
         }
     }
 
-    fn disconnect_from_bootstrap_proxy(&mut self) {
-        if let Some((conn_info, _)) = self.bootstrap_connection.take() {
-            debug!(
-                "{} Dropping bootstrap node at {} and retrying.",
-                self, conn_info.peer_addr
-            );
+    fn disconnect_from_bootstrap_proxy(&mut self, peer_addr: SocketAddr) {
+        if self.bootstrap_connections.remove(&peer_addr).is_some() {
+            debug!("{} Dropping bootstrap node at {}.", self, peer_addr);
 
             self.network_service
                 .service_mut()
-                .disconnect_from(conn_info.peer_addr);
+                .disconnect_from(peer_addr);
         }
     }
 
     fn rebootstrap(&mut self) {
-        // only rebootstrap if we're not waiting for connections from anyone else -
+        // only rebootstrap if we're not waiting for connections or responses from anyone else -
         // otherwise we'll just wait and maybe another connection succeeds
-        if !self.nodes_to_await.is_empty() {
+        if !self.nodes_to_await.is_empty() || !self.bootstrap_connections.is_empty() {
             return;
         }
 
-        self.disconnect_from_bootstrap_proxy();
+        debug!("{} Retrying bootstrap.", self);
         self.network_service.service_mut().bootstrap();
     }
 }
@@ -241,7 +424,7 @@ impl Base for BootstrappingPeer {
     }
 
     fn min_section_size(&self) -> usize {
-        self.min_section_size
+        self.network_params.min_section_size
     }
 
     fn peer_map(&self) -> &PeerMap {
@@ -261,6 +444,7 @@ impl Base for BootstrappingPeer {
         _: Authority<XorName>,
         _: Authority<XorName>,
         _: Vec<u8>,
+        _: Option<MessageId>,
     ) -> Result<(), InterfaceError> {
         warn!("{} - Cannot handle SendMessage - not bootstrapped.", self);
         // TODO: return Err here eventually. Returning Ok for now to
@@ -269,16 +453,27 @@ impl Base for BootstrappingPeer {
     }
 
     fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
-        if let Some((conn_info, bootstrap_token)) = self.bootstrap_connection.as_ref() {
-            if *bootstrap_token == token {
-                debug!(
-                    "{} - Timeout when trying to bootstrap against {}.",
-                    self, conn_info.peer_addr
-                );
+        if self.retry_timer_token == Some(token) {
+            self.retry_timer_token = None;
+            self.rebootstrap();
+            return Transition::Stay;
+        }
 
-                self.disconnect_from_bootstrap_proxy();
-                self.rebootstrap();
-            }
+        let timed_out = self
+            .bootstrap_connections
+            .iter()
+            .find(|(_, (_, bootstrap_token))| *bootstrap_token == token)
+            .map(|(peer_addr, _)| *peer_addr);
+
+        if let Some(peer_addr) = timed_out {
+            debug!(
+                "{} - Timeout when trying to bootstrap against {}.",
+                self, peer_addr
+            );
+
+            self.network_service.proxy_blacklist_mut().insert(peer_addr);
+            self.disconnect_from_bootstrap_proxy(peer_addr);
+            self.rebootstrap();
         }
 
         Transition::Stay
@@ -287,17 +482,13 @@ impl Base for BootstrappingPeer {
     fn handle_bootstrapped_to(&mut self, conn_info: ConnectionInfo) -> Transition {
         self.peer_map_mut().connect(conn_info.clone());
 
-        if self.bootstrap_connection.is_none() {
-            debug!(
-                "{} Received BootstrappedTo event from {}.",
-                self, conn_info.peer_addr
-            );
+        debug!(
+            "{} Received BootstrappedTo event from {}.",
+            self, conn_info.peer_addr
+        );
 
-            // Established connection. Pending Validity checks
-            self.send_bootstrap_request(conn_info);
-        } else {
-            warn!("{} Received more than one BootstrappedTo event", self);
-        }
+        // Established connection. Pending Validity checks
+        self.send_bootstrap_request(conn_info);
 
         Transition::Stay
     }
@@ -330,38 +521,25 @@ impl Base for BootstrappingPeer {
         let _ = self.nodes_to_await.remove(&peer_addr);
         let _ = self.peer_map_mut().disconnect(peer_addr);
 
-        if let Some((conn_info, _)) = self.bootstrap_connection.as_ref() {
-            if conn_info.peer_addr == peer_addr {
-                info!("{} Lost connection to proxy {}.", self, peer_addr);
-                self.disconnect_from_bootstrap_proxy();
-                self.rebootstrap();
-            }
+        if self.bootstrap_connections.contains_key(&peer_addr) {
+            info!("{} Lost connection to proxy {}.", self, peer_addr);
+            self.disconnect_from_bootstrap_proxy(peer_addr);
         }
 
+        self.rebootstrap();
+
         Transition::Stay
     }
 
     fn handle_direct_message(
         &mut self,
         msg: DirectMessage,
-        _pub_id: PublicId,
-        _: &mut dyn EventBox,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
         match msg {
-            DirectMessage::BootstrapResponse(BootstrapResponse::Join { prefix, conn_infos }) => {
-                info!(
-                    "{} - Joining a section {:?}: {:?}",
-                    self, prefix, conn_infos
-                );
-                self.join_section(prefix, conn_infos)
-            }
-            DirectMessage::BootstrapResponse(BootstrapResponse::Rebootstrap(new_conn_infos)) => {
-                info!(
-                    "{} - Bootstrapping redirected to another set of peers: {:?}",
-                    self, new_conn_infos
-                );
-                self.reconnect_to_new_section(new_conn_infos);
-                Ok(Transition::Stay)
+            DirectMessage::BootstrapResponse(response) => {
+                self.handle_bootstrap_response(pub_id, response, outbox)
             }
             _ => {
                 debug!("{} - Unhandled direct message: {:?}", self, msg);
@@ -401,13 +579,14 @@ mod tests {
     use super::*;
     use crate::{
         id::FullId,
-        messages::Message,
+        local_params::Codec,
+        messages::{connection_nonce_clock, Message, SignedDirectMessage},
         mock::Network,
         outbox::EventBuf,
         quic_p2p::{Builder, Peer},
         state_machine::StateMachine,
-        states::common::from_network_bytes,
-        NetworkConfig, NetworkEvent,
+        states::common::{from_network_bytes, to_network_bytes},
+        NetworkConfig, NetworkEvent, QuicP2p,
     };
     use crossbeam_channel as mpmc;
     use unwrap::unwrap;
@@ -438,7 +617,10 @@ mod tests {
                 State::BootstrappingPeer(BootstrappingPeer::new(
                     network_service,
                     node_b_full_id,
-                    min_section_size,
+                    NetworkParams {
+                        min_section_size,
+                        ..Default::default()
+                    },
                     timer,
                 ))
             },
@@ -465,9 +647,9 @@ mod tests {
         if let NetworkEvent::NewMessage { peer_addr, msg } = unwrap!(event_rx.try_recv()) {
             assert_eq!(peer_addr, node_b_endpoint);
 
-            let ok = match unwrap!(from_network_bytes(msg)) {
+            let ok = match unwrap!(from_network_bytes(msg, true)) {
                 Message::Direct(msg) => match *msg.content() {
-                    DirectMessage::BootstrapRequest(_) => true,
+                    DirectMessage::BootstrapRequest(..) => true,
                     _ => false,
                 },
                 _ => false,
@@ -498,6 +680,103 @@ mod tests {
         assert_eq!(events[0], Event::Terminated);
     }
 
+    #[test]
+    // Check that when redirected to several proxies, we race our `BootstrapRequest`s against all of
+    // them rather than dropping all but one.
+    fn race_bootstrap_requests() {
+        let min_section_size = 8;
+        let network = Network::new(min_section_size, None);
+
+        let (mut proxy_a, proxy_a_rx, proxy_a_endpoint) = start_network_service(&network);
+        let (_proxy_b, proxy_b_rx, proxy_b_endpoint) = start_network_service(&network);
+        let (_proxy_c, proxy_c_rx, proxy_c_endpoint) = start_network_service(&network);
+
+        let node_endpoint = network.gen_addr();
+        let config = NetworkConfig::node()
+            .with_hard_coded_contact(proxy_a_endpoint)
+            .with_endpoint(node_endpoint);
+        let mut node_outbox = EventBuf::new();
+        let (_node_action_tx, mut node_state_machine) = StateMachine::new(
+            move |network_service, timer, _outbox2| {
+                State::BootstrappingPeer(BootstrappingPeer::new(
+                    network_service,
+                    FullId::new(),
+                    NetworkParams {
+                        min_section_size,
+                        ..Default::default()
+                    },
+                    timer,
+                ))
+            },
+            config,
+            &mut node_outbox,
+        );
+
+        // Bootstrap off proxy A...
+        network.poll();
+        step_at_least_once(&mut node_state_machine, &mut node_outbox);
+        network.poll();
+        assert!(received_bootstrap_request(&proxy_a_rx, node_endpoint));
+
+        // ...which redirects us to proxies B and C.
+        let response = DirectMessage::BootstrapResponse(BootstrapResponse::Rebootstrap(vec![
+            ConnectionInfo::from(proxy_b_endpoint),
+            ConnectionInfo::from(proxy_c_endpoint),
+        ]));
+        let msg = Message::Direct(unwrap!(SignedDirectMessage::new(
+            response,
+            &FullId::new(),
+            None,
+            connection_nonce_clock(),
+        )));
+        proxy_a.send(
+            Peer::Client {
+                peer_addr: node_endpoint,
+            },
+            unwrap!(to_network_bytes(&msg, Codec::Bincode).ok()),
+            0,
+        );
+        network.poll();
+        step_at_least_once(&mut node_state_machine, &mut node_outbox);
+
+        // Once connected, we send our request to both of them.
+        network.poll();
+        step_at_least_once(&mut node_state_machine, &mut node_outbox);
+        network.poll();
+        assert!(received_bootstrap_request(&proxy_b_rx, node_endpoint));
+        assert!(received_bootstrap_request(&proxy_c_rx, node_endpoint));
+    }
+
+    fn start_network_service(
+        network: &Network,
+    ) -> (QuicP2p, mpmc::Receiver<NetworkEvent>, SocketAddr) {
+        let (event_tx, event_rx) = mpmc::unbounded();
+        let endpoint = network.gen_addr();
+        let config = NetworkConfig::node().with_endpoint(endpoint);
+        let network_service = unwrap!(Builder::new(event_tx).with_config(config).build());
+        (network_service, event_rx, endpoint)
+    }
+
+    // Returns whether the network service received a `BootstrapRequest` from `src`, discarding its
+    // other events.
+    fn received_bootstrap_request(
+        event_rx: &mpmc::Receiver<NetworkEvent>,
+        src: SocketAddr,
+    ) -> bool {
+        event_rx.try_iter().any(|event| match event {
+            NetworkEvent::NewMessage { peer_addr, msg } if peer_addr == src => {
+                match unwrap!(from_network_bytes(msg, true)) {
+                    Message::Direct(msg) => match *msg.content() {
+                        DirectMessage::BootstrapRequest(..) => true,
+                        _ => false,
+                    },
+                    _ => false,
+                }
+            }
+            _ => false,
+        })
+    }
+
     fn step_at_least_once(machine: &mut StateMachine, outbox: &mut dyn EventBox) {
         // Blocking step for the first one. Must not err.
         unwrap!(machine.step(outbox));
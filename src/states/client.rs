@@ -0,0 +1,315 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::common::{to_network_bytes, Base};
+use crate::{
+    error::{InterfaceError, RoutingError},
+    event::Event,
+    id::{FullId, PublicId},
+    messages::{client_nonce_clock, DirectMessage, HopMessage, RoutingMessage},
+    outbox::EventBox,
+    peer_map::PeerMap,
+    quic_p2p::{Peer, Token},
+    routing_table::Authority,
+    state_machine::Transition,
+    time::Duration,
+    timer::Timer,
+    xor_name::XorName,
+    ConnectionInfo, NetworkBytes, NetworkService, MIN_SECTION_SIZE,
+};
+use log::LogLevel;
+use std::{
+    cmp,
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+};
+
+/// Time we wait after losing our proxy before bootstrapping again, so that a proxy which refuses
+/// to serve us isn't flooded with reconnections.
+const REBOOTSTRAP_DELAY: Duration = Duration::from_secs(5);
+
+/// State of a client: bootstraps to a proxy node and exchanges requests and responses with it,
+/// without ever joining the network as a node.
+pub struct Client {
+    network_service: NetworkService,
+    full_id: FullId,
+    peer_map: PeerMap,
+    timer: Timer,
+    // The node we are bootstrapped to, once connected.
+    proxy: Option<ConnectionInfo>,
+    // Whether we raised `Event::Connected` for the current proxy.
+    notified_connected: bool,
+    // Whether we were connected to a proxy before, so that failing to bootstrap requires a restart
+    // rather than terminating.
+    was_connected: bool,
+    // Token of the timer to bootstrap again once we lost our proxy.
+    rebootstrap_token: Option<u64>,
+    // Nonce of our last `ClientRequest` or `ClientIdentify`. Proxies reject messages whose nonce
+    // isn't greater than the last one they saw from us.
+    nonce: u64,
+}
+
+impl Client {
+    pub fn new(mut network_service: NetworkService, full_id: FullId, timer: Timer) -> Self {
+        network_service.service_mut().bootstrap();
+        Self {
+            network_service,
+            full_id,
+            peer_map: PeerMap::new(),
+            timer,
+            proxy: None,
+            notified_connected: false,
+            was_connected: false,
+            rebootstrap_token: None,
+            nonce: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> u64 {
+        self.nonce = cmp::max(self.nonce + 1, client_nonce_clock());
+        self.nonce
+    }
+
+    // Signs `msg` and sends it to our proxy at `node_info`.
+    fn send_to_proxy(&mut self, node_info: ConnectionInfo, msg: DirectMessage) {
+        let message = match self.to_signed_direct_message(&node_info, None, msg) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        let bytes = match to_network_bytes(&message, self.network_service.codec()) {
+            Ok(bytes) => bytes,
+            Err((error, message)) => {
+                error!(
+                    "{} Failed to serialise message {:?}: {:?}",
+                    self, message, error
+                );
+                return;
+            }
+        };
+
+        self.network_service
+            .service_mut()
+            .send(Peer::Node { node_info }, bytes, 0);
+    }
+}
+
+impl Base for Client {
+    fn network_service(&self) -> &NetworkService {
+        &self.network_service
+    }
+
+    fn network_service_mut(&mut self) -> &mut NetworkService {
+        &mut self.network_service
+    }
+
+    fn full_id(&self) -> &FullId {
+        &self.full_id
+    }
+
+    fn in_authority(&self, _: &Authority<XorName>) -> bool {
+        false
+    }
+
+    fn min_section_size(&self) -> usize {
+        MIN_SECTION_SIZE
+    }
+
+    fn peer_map(&self) -> &PeerMap {
+        &self.peer_map
+    }
+
+    fn peer_map_mut(&mut self) -> &mut PeerMap {
+        &mut self.peer_map
+    }
+
+    fn timer(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    fn handle_send_request(
+        &mut self,
+        msg: NetworkBytes,
+        token: Token,
+    ) -> Result<(), InterfaceError> {
+        let node_info = self.proxy.clone().ok_or(InterfaceError::NotConnected)?;
+        self.network_service
+            .service_mut()
+            .send(Peer::Node { node_info }, msg, token);
+        Ok(())
+    }
+
+    fn handle_send_client_request(
+        &mut self,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        let node_info = self.proxy.clone().ok_or(InterfaceError::NotConnected)?;
+
+        let request = DirectMessage::ClientRequest {
+            dst,
+            proxy: node_info.clone(),
+            nonce: self.next_nonce(),
+            content,
+        };
+        self.send_to_proxy(node_info, request);
+        Ok(())
+    }
+
+    fn handle_bootstrapped_to(&mut self, conn_info: ConnectionInfo) -> Transition {
+        if self.proxy.is_some() {
+            warn!("{} Received more than one BootstrappedTo event", self);
+            return Transition::Stay;
+        }
+
+        debug!("{} Connected to proxy {}.", self, conn_info.peer_addr);
+        self.proxy = Some(conn_info.clone());
+
+        // Identify ourselves, so the proxy can authorise us before serving us.
+        let identify = DirectMessage::ClientIdentify {
+            proxy: conn_info.clone(),
+            nonce: self.next_nonce(),
+        };
+        self.send_to_proxy(conn_info, identify);
+
+        Transition::Stay
+    }
+
+    fn handle_bootstrap_failure(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        if self.was_connected {
+            info!("{} Failed to reconnect to a proxy. Restart required.", self);
+            outbox.send_event(Event::RestartRequired);
+        } else {
+            info!("{} Failed to bootstrap. Terminating.", self);
+            outbox.send_event(Event::Terminated);
+        }
+
+        Transition::Terminate
+    }
+
+    fn handle_connection_failure(
+        &mut self,
+        peer_addr: SocketAddr,
+        _: &mut dyn EventBox,
+    ) -> Transition {
+        if self
+            .proxy
+            .as_ref()
+            .map_or(false, |proxy| proxy.peer_addr == peer_addr)
+        {
+            info!("{} Lost connection to proxy {}.", self, peer_addr);
+            self.proxy = None;
+            self.notified_connected = false;
+            self.rebootstrap_token = Some(self.timer.schedule(REBOOTSTRAP_DELAY));
+        }
+
+        Transition::Stay
+    }
+
+    fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
+        if self.rebootstrap_token == Some(token) {
+            self.rebootstrap_token = None;
+            self.network_service.service_mut().bootstrap();
+        }
+
+        Transition::Stay
+    }
+
+    fn handle_new_message(
+        &mut self,
+        src_addr: SocketAddr,
+        msg: NetworkBytes,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        if self
+            .proxy
+            .as_ref()
+            .map_or(false, |proxy| proxy.peer_addr == src_addr)
+        {
+            outbox.send_event(Event::ResponseReceived { msg });
+        } else {
+            debug!("{} - Ignoring message from non-proxy {}.", self, src_addr);
+        }
+
+        Transition::Stay
+    }
+
+    fn handle_unsent_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        _msg: NetworkBytes,
+        token: Token,
+        _: &mut dyn EventBox,
+    ) -> Transition {
+        debug!(
+            "{} - Failed to send request with token {} to {}.",
+            self, token, peer_addr
+        );
+        Transition::Stay
+    }
+
+    fn handle_sent_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        _msg: NetworkBytes,
+        token: Token,
+        _: &mut dyn EventBox,
+    ) -> Transition {
+        trace!(
+            "{} - Successfully sent request with token {} to {}.",
+            self,
+            token,
+            peer_addr
+        );
+        Transition::Stay
+    }
+
+    fn finish_handle_network_event(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        if self.proxy.is_some() && !self.notified_connected {
+            self.notified_connected = true;
+            self.was_connected = true;
+            outbox.send_event(Event::Connected);
+        }
+
+        Transition::Stay
+    }
+
+    fn handle_direct_message(
+        &mut self,
+        msg: DirectMessage,
+        _pub_id: PublicId,
+        _: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
+        debug!("{} - Unhandled direct message: {:?}", self, msg);
+        Ok(Transition::Stay)
+    }
+
+    fn handle_hop_message(
+        &mut self,
+        msg: HopMessage,
+        _: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
+        debug!("{} - Unhandled hop message: {:?}", self, msg);
+        Ok(Transition::Stay)
+    }
+
+    fn send_routing_message(&mut self, routing_msg: RoutingMessage) -> Result<(), RoutingError> {
+        log_or_panic!(
+            LogLevel::Error,
+            "{} - Tried to send a routing message: {:?}",
+            self,
+            routing_msg
+        );
+        Ok(())
+    }
+}
+
+impl Display for Client {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Client({})", self.name())
+    }
+}
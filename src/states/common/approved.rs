@@ -9,13 +9,18 @@
 use super::Base;
 use crate::{
     chain::{
-        AccumulatingEvent, Chain, EldersChange, EldersInfo, OnlinePayload, Proof, ProofSet,
-        SectionKeyInfo, SendAckMessagePayload,
+        AccumulatingEvent, AckMessagePayload, Chain, ConflictingVotesPayload, EldersChange,
+        EldersInfo, NamespacedUserPayload, OnlinePayload, Proof, ProofSet, SectionKeyInfo,
+        SectionProofChain, SendAckMessagesPayload,
     },
-    error::RoutingError,
+    chunking,
+    error::{InterfaceError, RoutingError},
     event::Event,
     id::PublicId,
-    messages::{DirectMessage, MessageContent, RelocateDetails, RoutingMessage},
+    messages::{
+        Capabilities, DirectMessage, MessageContent, RelocateDetails, RoutingMessage,
+        SignedRoutingMessage,
+    },
     outbox::EventBox,
     parsec::{self, Block, Observation, ParsecMap},
     routing_table::{Authority, Prefix},
@@ -23,19 +28,25 @@ use crate::{
     types::MessageId,
     utils,
     xor_name::XorName,
-    ConnectionInfo,
+    BlsPublicKey, ConnectionInfo,
 };
 use log::LogLevel;
+use std::collections::BTreeSet;
 
 /// Common functionality for node states post resource proof.
 pub trait Approved: Base {
     fn parsec_map(&self) -> &ParsecMap;
     fn parsec_map_mut(&mut self) -> &mut ParsecMap;
+    fn chain(&self) -> &Chain;
     fn chain_mut(&mut self) -> &mut Chain;
     fn send_event(&mut self, event: Event, outbox: &mut dyn EventBox);
     fn set_pfx_successfully_polled(&mut self, val: bool);
     fn is_pfx_successfully_polled(&self) -> bool;
 
+    /// Sends messages signed by us on their routes, those with the same next hops together.
+    fn send_signed_messages(&mut self, msgs: Vec<SignedRoutingMessage>)
+        -> Result<(), RoutingError>;
+
     /// Handles an accumulated `AddElder` event.
     fn handle_add_elder_event(
         &mut self,
@@ -48,7 +59,7 @@ pub trait Approved: Base {
         &mut self,
         pub_id: PublicId,
         outbox: &mut dyn EventBox,
-    ) -> Result<(), RoutingError>;
+    ) -> Result<Transition, RoutingError>;
 
     /// Handles an accumulated `Online` event.
     fn handle_online_event(
@@ -58,7 +69,11 @@ pub trait Approved: Base {
     ) -> Result<(), RoutingError>;
 
     /// Handles an accumulated `Offline` event.
-    fn handle_offline_event(&mut self, pub_id: PublicId) -> Result<(), RoutingError>;
+    fn handle_offline_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError>;
 
     /// Handles an accumulated `OurMerge` event.
     fn handle_our_merge_event(&mut self) -> Result<(), RoutingError>;
@@ -79,14 +94,40 @@ pub trait Approved: Base {
     fn handle_their_key_info_event(&mut self, key_info: SectionKeyInfo)
         -> Result<(), RoutingError>;
 
-    /// Handle an accumulated `SendAckMessage` event
-    fn handle_send_ack_message_event(
+    /// Handle an accumulated `AckMessage` event
+    fn handle_ack_message_event(&mut self, ack_payload: &AckMessagePayload);
+
+    /// Handle an accumulated `SendAckMessages` event
+    fn handle_send_ack_messages_event(
         &mut self,
-        ack_payload: SendAckMessagePayload,
+        ack_payload: SendAckMessagesPayload,
     ) -> Result<(), RoutingError>;
 
     /// Handle an accumulated `Relocate` event
-    fn handle_relocate_event(&mut self, payload: RelocateDetails) -> Result<(), RoutingError>;
+    fn handle_relocate_event(
+        &mut self,
+        payload: RelocateDetails,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError>;
+
+    /// Handle an accumulated `RelocationComplete` event
+    fn handle_relocation_complete_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError>;
+
+    /// Handle an accumulated `StepDown` event
+    fn handle_step_down_event(&mut self, pub_id: PublicId) -> Result<(), RoutingError>;
+
+    /// Handle conflicting votes newly detected in the chain accumulator
+    fn handle_conflicting_votes(&mut self, payload: ConflictingVotesPayload);
+
+    /// Handle an accumulated `ConflictingVotes` event
+    fn handle_conflicting_votes_event(
+        &mut self,
+        payload: ConflictingVotesPayload,
+    ) -> Result<(), RoutingError>;
 
     /// Handle an accumulated `User` event
     fn handle_user_event(
@@ -98,6 +139,17 @@ pub trait Approved: Base {
         Ok(())
     }
 
+    /// Handle an accumulated `NamespacedUser` event
+    fn handle_namespaced_user_event(
+        &mut self,
+        payload: NamespacedUserPayload,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        let NamespacedUserPayload { namespace, payload } = payload;
+        self.send_event(Event::UserEventConsensused { namespace, payload }, outbox);
+        Ok(())
+    }
+
     fn handle_parsec_request(
         &mut self,
         msg_version: u64,
@@ -229,6 +281,10 @@ pub trait Approved: Base {
                             event
                         );
                         self.chain_mut().handle_opaque_event(event, proof)?;
+
+                        for payload in self.chain_mut().take_conflicting_votes() {
+                            self.handle_conflicting_votes(payload);
+                        }
                     }
                 }
                 Observation::Add { peer_id, .. } => {
@@ -265,6 +321,7 @@ pub trait Approved: Base {
 
     fn chain_poll(&mut self, outbox: &mut dyn EventBox) -> Result<Transition, RoutingError> {
         let mut our_pfx = *self.chain_mut().our_prefix();
+        let mut our_age = self.chain_mut().our_age();
         while let Some((event, neighbour_change)) = self.chain_mut().poll()? {
             trace!("{} Handle accumulated event: {:?}", self, event);
 
@@ -273,13 +330,26 @@ pub trait Approved: Base {
                     self.handle_add_elder_event(pub_id, outbox)?;
                 }
                 AccumulatingEvent::RemoveElder(pub_id) => {
-                    self.handle_remove_elder_event(pub_id, outbox)?;
+                    match self.handle_remove_elder_event(pub_id, outbox)? {
+                        Transition::Stay => (),
+                        transition => return Ok(transition),
+                    }
                 }
                 AccumulatingEvent::Online(payload) => {
-                    self.handle_online_event(payload, outbox)?;
+                    if payload.resource_proof_verified {
+                        self.handle_online_event(payload, outbox)?;
+                    } else {
+                        info!(
+                            "{} - Not adding {}: it failed the resource proof.",
+                            self, payload.pub_id
+                        );
+                    }
                 }
                 AccumulatingEvent::Offline(pub_id) => {
-                    self.handle_offline_event(pub_id)?;
+                    match self.handle_offline_event(pub_id, outbox)? {
+                        Transition::Stay => (),
+                        transition => return Ok(transition),
+                    }
                 }
                 AccumulatingEvent::OurMerge => self.handle_our_merge_event()?,
                 AccumulatingEvent::NeighbourMerge(_) => self.handle_neighbour_merge_event()?,
@@ -294,14 +364,37 @@ pub trait Approved: Base {
                         transition => return Ok(transition),
                     }
                 }
+                AccumulatingEvent::EldersChanged { added, removed, .. } => {
+                    let names = |ids: BTreeSet<PublicId>| ids.iter().map(|id| *id.name()).collect();
+                    self.send_event(
+                        Event::EldersChanged {
+                            added: names(added),
+                            removed: names(removed),
+                        },
+                        outbox,
+                    );
+
+                    // The chain already rebuilt our new info from the change.
+                    let elders_info = self.chain_mut().our_info().clone();
+                    match self.handle_section_info_event(
+                        elders_info,
+                        our_pfx,
+                        neighbour_change,
+                        outbox,
+                    )? {
+                        Transition::Stay => (),
+                        transition => return Ok(transition),
+                    }
+                }
                 AccumulatingEvent::TheirKeyInfo(key_info) => {
                     self.handle_their_key_info_event(key_info)?
                 }
-                AccumulatingEvent::AckMessage(_payload) => {
+                AccumulatingEvent::AckMessage(payload) => {
                     // Update their_knowledge is handled within the chain.
+                    self.handle_ack_message_event(&payload)
                 }
-                AccumulatingEvent::SendAckMessage(payload) => {
-                    self.handle_send_ack_message_event(payload)?
+                AccumulatingEvent::SendAckMessages(payload) => {
+                    self.handle_send_ack_messages_event(payload)?
                 }
                 AccumulatingEvent::ParsecPrune => {
                     info!(
@@ -309,16 +402,159 @@ pub trait Approved: Base {
                         self, event
                     );
                 }
-                AccumulatingEvent::Relocate(payload) => self.handle_relocate_event(payload)?,
+                AccumulatingEvent::Relocate(payload) => {
+                    self.handle_relocate_event(payload, outbox)?
+                }
+                AccumulatingEvent::RelocationComplete(pub_id) => {
+                    self.handle_relocation_complete_event(pub_id, outbox)?
+                }
+                AccumulatingEvent::StepDown(pub_id) => self.handle_step_down_event(pub_id)?,
                 AccumulatingEvent::User(payload) => self.handle_user_event(payload, outbox)?,
+                AccumulatingEvent::NamespacedUser(payload) => {
+                    self.handle_namespaced_user_event(payload, outbox)?
+                }
+                AccumulatingEvent::ConflictingVotes(payload) => {
+                    self.handle_conflicting_votes_event(payload)?
+                }
+                AccumulatingEvent::Batch(_) => {
+                    // Unpacked before reaching the accumulator, so never accumulates.
+                }
             }
 
             our_pfx = *self.chain_mut().our_prefix();
+
+            let new_age = self.chain_mut().our_age();
+            if let (Some(old_age), Some(new_age)) = (our_age, new_age) {
+                if new_age > old_age {
+                    self.send_event(Event::AgeIncreased(new_age), outbox);
+                }
+            }
+            our_age = new_age;
         }
 
         Ok(Transition::Stay)
     }
 
+    /// Sends a user message, split into parts if it is too large for the transport. Messages from
+    /// a node to a section whose key we know are encrypted to it, so only its elders can read them.
+    /// If `ack` is set, the message is sent with it as its id, and the recipients acknowledge it
+    /// with a `DeliveryAck` carrying it. If `trace_id` is set, its parts are traced with it.
+    fn send_user_message(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        ack: Option<MessageId>,
+        trace_id: Option<MessageId>,
+    ) -> Result<(), RoutingError> {
+        let parts = match ack {
+            Some(msg_id) => chunking::split(content, msg_id, true)?,
+            None => {
+                let msg_id = chunking::message_id(&src, &content);
+                chunking::split(content, msg_id, false)?
+            }
+        };
+        self.send_user_message_parts(src, dst, &parts, trace_id)
+    }
+
+    /// Sends the same user message to each of `dsts`. The content is split only once, and each
+    /// destination is only sent to once however often it is listed. From a single node, the parts
+    /// of the message sent in plaintext are signed once for all their destinations, and sent
+    /// together to the destinations with the same next hops. Sending to the remaining destinations
+    /// continues if one fails; the last error is returned.
+    fn send_user_message_to_many(
+        &mut self,
+        src: Authority<XorName>,
+        dsts: &[Authority<XorName>],
+        content: Vec<u8>,
+    ) -> Result<(), RoutingError> {
+        let msg_id = chunking::message_id(&src, &content);
+        let parts = chunking::split(content, msg_id, false)?;
+        let mut sent = BTreeSet::new();
+        let mut shared_dsts = Vec::new();
+        let mut result = Ok(());
+
+        for dst in dsts {
+            if !sent.insert(*dst) {
+                continue;
+            }
+
+            if src.is_single() && self.encryption_key(src, *dst).is_none() {
+                shared_dsts.push(*dst);
+                continue;
+            }
+
+            if let Err(error) = self.send_user_message_parts(src, *dst, &parts, None) {
+                debug!(
+                    "{} - Failed to send message to {:?}: {:?}",
+                    self, dst, error
+                );
+                result = Err(error);
+            }
+        }
+
+        if shared_dsts.is_empty() {
+            return result;
+        }
+        if !self.in_authority(&src) {
+            return Err(RoutingError::InvalidSource);
+        }
+
+        for content in parts {
+            let msgs = SignedRoutingMessage::single_source_to_many(
+                src,
+                shared_dsts.clone(),
+                content,
+                self.full_id(),
+            )?;
+            if let Err(error) = self.send_signed_messages(msgs) {
+                debug!(
+                    "{} - Failed to send message to {:?}: {:?}",
+                    self, shared_dsts, error
+                );
+                result = Err(error);
+            }
+        }
+
+        result
+    }
+
+    // Returns the key to encrypt a user message from `src` to `dst` with, if it is to be encrypted.
+    // Messages from a section are sent in plaintext, as the elders sending them need to send the
+    // same content for their signatures to accumulate.
+    fn encryption_key(
+        &self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+    ) -> Option<BlsPublicKey> {
+        match (src, dst) {
+            (Authority::Node(_), Authority::Section(name)) => {
+                self.chain().their_key(&name).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    fn send_user_message_parts(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        parts: &[MessageContent],
+        trace_id: Option<MessageId>,
+    ) -> Result<(), RoutingError> {
+        let key = self.encryption_key(src, dst);
+
+        for content in parts {
+            let content = match key {
+                Some(ref key) => content.encrypt(key)?,
+                None => content.clone(),
+            };
+            self.send_traced_routing_message(RoutingMessage { src, dst, content }, trace_id)?;
+        }
+
+        Ok(())
+    }
+
     fn send_connection_request(
         &mut self,
         their_pub_id: PublicId,
@@ -343,6 +579,7 @@ pub trait Approved: Base {
         let content = MessageContent::ConnectionRequest {
             conn_info: self.our_connection_info()?,
             pub_id: *self.full_id().public_id(),
+            capabilities: Capabilities::ours(),
             msg_id: MessageId::new(),
         };
 
@@ -362,6 +599,7 @@ pub trait Approved: Base {
         &mut self,
         their_conn_info: ConnectionInfo,
         their_pub_id: PublicId,
+        their_capabilities: Capabilities,
         src: Authority<XorName>,
         dst: Authority<XorName>,
         _: &mut dyn EventBox,
@@ -376,16 +614,87 @@ pub trait Approved: Base {
             return Err(RoutingError::InvalidDestination);
         }
 
+        let capabilities = Capabilities::ours()
+            .common(&their_capabilities)
+            .ok_or_else(|| RoutingError::IncompatibleProtocol(their_capabilities.version))?;
+
         debug!(
             "{} - Received connection request from {:?}.",
             self, their_pub_id
         );
 
+        self.network_service_mut()
+            .set_peer_capabilities(their_conn_info.peer_addr, capabilities);
         self.peer_map_mut().insert(their_pub_id, their_conn_info);
         self.send_direct_message(&their_pub_id, DirectMessage::ConnectionResponse);
 
         Ok(())
     }
+
+    /// Asks one of our connected elders for the chain of our section's keys from `from_version`
+    /// up to the current one.
+    fn send_proof_chain_request(&mut self, from_version: u64) -> Result<(), InterfaceError> {
+        let our_id = *self.id();
+        let elders: Vec<_> = self
+            .chain_mut()
+            .our_elders()
+            .filter(|pub_id| **pub_id != our_id)
+            .copied()
+            .collect();
+        let elder = elders
+            .into_iter()
+            .find(|pub_id| self.peer_map().has(pub_id))
+            .ok_or(InterfaceError::InvalidState)?;
+
+        debug!(
+            "{} - Requesting proof chain from version {} from {}.",
+            self, from_version, elder
+        );
+        self.send_direct_message(&elder, DirectMessage::ProofChainRequest(from_version));
+        Ok(())
+    }
+
+    fn handle_proof_chain_request(&mut self, from_version: u64, pub_id: PublicId) {
+        let proof_chain = self.chain_mut().proof_chain_from(from_version);
+        self.send_direct_message(&pub_id, DirectMessage::ProofChainResponse(proof_chain));
+    }
+
+    fn handle_proof_chain_response(
+        &mut self,
+        proof_chain: SectionProofChain,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) {
+        if !proof_chain.validate() || !self.chain_mut().check_trust(&proof_chain) {
+            debug!(
+                "{} - Ignoring untrusted proof chain {:?} from {}.",
+                self,
+                proof_chain.last_public_key_info(),
+                pub_id
+            );
+            return;
+        }
+
+        self.send_event(Event::ProofChainReceived(proof_chain), outbox);
+    }
+
+    /// Handles a `NodeApproval` received while we are already a member of our section, e.g. a late
+    /// one or one approving a repeated `JoinRequest`. Tells our elders we joined already, so they
+    /// stop approving us.
+    fn handle_duplicate_node_approval(&mut self) {
+        debug!("{} - Ignoring NodeApproval - already joined.", self);
+
+        let our_id = *self.id();
+        let elders: Vec<_> = self
+            .chain_mut()
+            .our_elders()
+            .filter(|pub_id| **pub_id != our_id)
+            .copied()
+            .collect();
+        for pub_id in &elders {
+            self.send_direct_message(pub_id, DirectMessage::JoinedAlready);
+        }
+    }
 }
 
 fn to_proof_set(block: &Block) -> ProofSet {
@@ -8,8 +8,10 @@
 
 use crate::{
     action::Action,
+    dispatch::ClientAuthorisation,
     error::{InterfaceError, RoutingError},
     id::{FullId, PublicId},
+    local_params::Codec,
     messages::{
         DirectMessage, HopMessage, Message, RoutingMessage, SignedDirectMessage,
         SignedRoutingMessage,
@@ -20,13 +22,15 @@ use crate::{
     routing_table::Authority,
     state_machine::Transition,
     timer::Timer,
+    types::MessageId,
     utils::LogIdent,
     xor_name::XorName,
     ClientEvent, ConnectionInfo, NetworkBytes, NetworkEvent, NetworkService,
 };
+#[cfg(not(feature = "mock_serialise"))]
+use crate::{compression, envelope};
 use itertools::Itertools;
 use log::LogLevel;
-use maidsafe_utilities::serialisation;
 use std::{
     fmt::{Debug, Display},
     net::SocketAddr,
@@ -45,6 +49,16 @@ pub trait Base: Display {
     fn timer(&mut self) -> &mut Timer;
     fn send_routing_message(&mut self, routing_msg: RoutingMessage) -> Result<(), RoutingError>;
 
+    /// Sends a routing message like `send_routing_message`, tagged with `trace_id` so that its
+    /// path can be followed in the logs. States which can't trace messages send them untagged.
+    fn send_traced_routing_message(
+        &mut self,
+        routing_msg: RoutingMessage,
+        _trace_id: Option<MessageId>,
+    ) -> Result<(), RoutingError> {
+        self.send_routing_message(routing_msg)
+    }
+
     fn log_ident(&self) -> LogIdent {
         LogIdent::new(self)
     }
@@ -72,9 +86,68 @@ pub trait Base: Display {
                 src,
                 dst,
                 content,
+                trace_id,
+                result_tx,
+            } => {
+                let result = self.handle_send_message(src, dst, content, trace_id);
+                let _ = result_tx.send(result);
+            }
+            Action::SendMessageToMany {
+                src,
+                dsts,
+                content,
+                result_tx,
+            } => {
+                let result = self.handle_send_message_to_many(src, dsts, content);
+                let _ = result_tx.send(result);
+            }
+            Action::SendMessageWithAck {
+                src,
+                dst,
+                content,
+                msg_id,
                 result_tx,
             } => {
-                let result = self.handle_send_message(src, dst, content);
+                let result = self.handle_send_message_with_ack(src, dst, content, msg_id);
+                let _ = result_tx.send(result);
+            }
+            Action::SendStream {
+                src,
+                dst,
+                content,
+                stream_id,
+                result_tx,
+            } => {
+                let result = self.handle_send_stream(src, dst, content, stream_id);
+                let _ = result_tx.send(result);
+            }
+            Action::SendUserRequest {
+                src,
+                dst,
+                content,
+                token,
+                result_tx,
+            } => {
+                let result = self.handle_send_user_request(src, dst, content, token);
+                let _ = result_tx.send(result);
+            }
+            Action::SendUserResponse {
+                src,
+                dst,
+                content,
+                token,
+                result_tx,
+            } => {
+                let result = self.handle_send_user_response(src, dst, content, token);
+                let _ = result_tx.send(result);
+            }
+            Action::RelayClientMessage {
+                client_id,
+                dst,
+                content,
+                result_tx,
+            } => {
+                let result = self.handle_relay_client_message(client_id, dst, content);
                 let _ = result_tx.send(result);
             }
             Action::GetId { result_tx } => {
@@ -86,6 +159,13 @@ pub trait Base: Display {
                     return transition;
                 }
             },
+            Action::RequestProofChain {
+                from_version,
+                result_tx,
+            } => {
+                let result = self.handle_request_proof_chain(from_version);
+                let _ = result_tx.send(result);
+            }
             Action::DisconnectClient {
                 peer_addr,
                 result_tx,
@@ -103,6 +183,38 @@ pub trait Base: Display {
                 self.send_msg_to_client(peer_addr, msg, token);
                 let _ = result_tx.send(Ok(()));
             }
+            Action::PruneParsec { result_tx } => {
+                let result = self.handle_prune_parsec();
+                let _ = result_tx.send(result);
+            }
+            Action::SendRequest {
+                msg,
+                token,
+                result_tx,
+            } => {
+                let result = self.handle_send_request(msg, token);
+                let _ = result_tx.send(result);
+            }
+            Action::SendClientRequest {
+                dst,
+                content,
+                result_tx,
+            } => {
+                let result = self.handle_send_client_request(dst, content);
+                let _ = result_tx.send(result);
+            }
+            Action::InitiateLeave { result_tx } => {
+                let result = self.handle_initiate_leave();
+                let _ = result_tx.send(result);
+            }
+            Action::StepDown { result_tx } => {
+                let result = self.handle_step_down();
+                let _ = result_tx.send(result);
+            }
+            Action::ReconnectToNeighbours { result_tx } => {
+                let result = self.handle_reconnect_to_neighbours(outbox);
+                let _ = result_tx.send(result);
+            }
             Action::Terminate => {
                 return Transition::Terminate;
             }
@@ -116,11 +228,140 @@ pub trait Base: Display {
         _src: Authority<XorName>,
         _dst: Authority<XorName>,
         _content: Vec<u8>,
+        _trace_id: Option<MessageId>,
     ) -> Result<(), InterfaceError> {
         warn!("{} - Cannot handle SendMessage - invalid state.", self);
         Err(InterfaceError::InvalidState)
     }
 
+    fn handle_send_message_to_many(
+        &mut self,
+        _src: Authority<XorName>,
+        _dsts: Vec<Authority<XorName>>,
+        _content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle SendMessageToMany - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_message_with_ack(
+        &mut self,
+        _src: Authority<XorName>,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+        _msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle SendMessageWithAck - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_stream(
+        &mut self,
+        _src: Authority<XorName>,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+        _stream_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle SendStream - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_user_request(
+        &mut self,
+        _src: Authority<XorName>,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+        _token: MessageId,
+    ) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle SendUserRequest - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_user_response(
+        &mut self,
+        _src: Authority<XorName>,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+        _token: MessageId,
+    ) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle SendUserResponse - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_relay_client_message(
+        &mut self,
+        _client_id: PublicId,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle RelayClientMessage - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_request_proof_chain(&mut self, _from_version: u64) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle RequestProofChain - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_prune_parsec(&mut self) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle PruneParsec - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_request(
+        &mut self,
+        _msg: NetworkBytes,
+        _token: Token,
+    ) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle SendRequest - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_send_client_request(
+        &mut self,
+        _dst: Authority<XorName>,
+        _content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle SendClientRequest - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_initiate_leave(&mut self) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle InitiateLeave - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_step_down(&mut self) -> Result<(), InterfaceError> {
+        warn!("{} - Cannot handle StepDown - invalid state.", self);
+        Err(InterfaceError::InvalidState)
+    }
+
+    fn handle_reconnect_to_neighbours(
+        &mut self,
+        _outbox: &mut dyn EventBox,
+    ) -> Result<(), InterfaceError> {
+        warn!(
+            "{} - Cannot handle ReconnectToNeighbours - invalid state.",
+            self
+        );
+        Err(InterfaceError::InvalidState)
+    }
+
     fn handle_timeout(&mut self, _token: u64, _outbox: &mut dyn EventBox) -> Transition {
         Transition::Stay
     }
@@ -139,17 +380,21 @@ pub trait Base: Display {
         let transition = match event {
             BootstrappedTo { node } => self.handle_bootstrapped_to(node),
             BootstrapFailure => self.handle_bootstrap_failure(outbox),
+            ConnectedTo { peer } if self.peer_map_mut().is_blacklisted(&peer.peer_addr()) => {
+                debug!(
+                    "{} - Refusing connection from blacklisted {}",
+                    self,
+                    peer.peer_addr()
+                );
+                self.disconnect_from(peer.peer_addr());
+                Transition::Stay
+            }
             ConnectedTo {
                 peer: Peer::Node { node_info },
             } => self.handle_connected_to(node_info, outbox),
             ConnectedTo {
                 peer: Peer::Client { peer_addr },
-            } => {
-                self.peer_map_mut().insert_client(peer_addr);
-                let client_event = ClientEvent::ConnectedToClient { peer_addr };
-                outbox.send_event(From::from(client_event));
-                Transition::Stay
-            }
+            } => self.handle_connected_to_client(peer_addr, outbox),
             ConnectionFailure { peer_addr, .. } => {
                 if self.peer_map().is_known_client(&peer_addr) {
                     let client_event = ClientEvent::ConnectionFailureToClient { peer_addr };
@@ -160,10 +405,13 @@ pub trait Base: Display {
                 }
             }
             NewMessage { peer_addr, msg } => {
-                if self.peer_map().is_known_client(&peer_addr) {
-                    let client_event = ClientEvent::NewMessageFromClient { peer_addr, msg };
-                    outbox.send_event(client_event.into());
-                    Transition::Stay
+                if !self
+                    .peer_map_mut()
+                    .record_inbound(peer_addr, inbound_len(&msg))
+                {
+                    self.handle_inbound_limit_exceeded(peer_addr, outbox)
+                } else if self.peer_map().is_known_client(&peer_addr) {
+                    self.handle_client_message(peer_addr, msg, outbox)
                 } else {
                     self.handle_new_message(peer_addr, msg, outbox)
                 }
@@ -240,6 +488,7 @@ pub trait Base: Display {
 
         let mut transition = Transition::Stay;
 
+        self.network_service_mut().forget_peer(&peer_addr);
         let pub_ids = self.peer_map_mut().disconnect(peer_addr);
         for pub_id in pub_ids {
             trace!("{} - ConnectionFailure from {}", self, pub_id);
@@ -253,18 +502,100 @@ pub trait Base: Display {
         transition
     }
 
+    fn handle_connected_to_client(
+        &mut self,
+        peer_addr: SocketAddr,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        match self.authorise_client_connection(&peer_addr) {
+            ClientAuthorisation::Accept => (),
+            ClientAuthorisation::Limit(limits) => {
+                self.peer_map_mut().set_client_limits(peer_addr, limits)
+            }
+            ClientAuthorisation::Reject => {
+                debug!("{} - Refusing connection from client {}", self, peer_addr);
+                self.disconnect_from(peer_addr);
+                return Transition::Stay;
+            }
+        }
+
+        self.peer_map_mut().insert_client(peer_addr);
+        let client_event = ClientEvent::ConnectedToClient { peer_addr };
+        outbox.send_event(From::from(client_event));
+        Transition::Stay
+    }
+
+    // Returns whether to serve the client connecting from `peer_addr`. Only elders are configured
+    // with an authoriser.
+    fn authorise_client_connection(&self, _peer_addr: &SocketAddr) -> ClientAuthorisation {
+        ClientAuthorisation::Accept
+    }
+
+    // Disconnects from the client at `peer_addr` and tells the user it is gone.
+    fn disconnect_client(&mut self, peer_addr: SocketAddr, outbox: &mut dyn EventBox) {
+        self.disconnect_from(peer_addr);
+        self.peer_map_mut().remove_client(&peer_addr);
+        let client_event = ClientEvent::ConnectionFailureToClient { peer_addr };
+        outbox.send_event(client_event.into());
+    }
+
+    fn handle_inbound_limit_exceeded(
+        &mut self,
+        peer_addr: SocketAddr,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        let blacklisted = self.peer_map_mut().blacklist(peer_addr);
+        warn!(
+            "{} - Disconnecting from {} which exceeded its inbound budget{}.",
+            self,
+            peer_addr,
+            if blacklisted {
+                " and blacklisting it"
+            } else {
+                ""
+            }
+        );
+        if self.peer_map().is_known_client(&peer_addr) {
+            self.disconnect_client(peer_addr, outbox);
+            Transition::Stay
+        } else {
+            self.disconnect_from(peer_addr);
+            self.handle_connection_failure(peer_addr, outbox)
+        }
+    }
+
+    fn handle_client_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        msg: NetworkBytes,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        let client_event = ClientEvent::NewMessageFromClient { peer_addr, msg };
+        outbox.send_event(client_event.into());
+        Transition::Stay
+    }
+
     fn handle_new_message(
         &mut self,
         src_addr: SocketAddr,
         bytes: NetworkBytes,
         outbox: &mut dyn EventBox,
     ) -> Transition {
-        let result = from_network_bytes(bytes)
+        let accept_compressed = self.network_service().accepts_compressed_from(&src_addr);
+        let result = from_network_bytes(bytes, accept_compressed)
             .and_then(|message| self.handle_new_deserialised_message(src_addr, message, outbox));
 
         match result {
             Ok(transition) => transition,
             Err(RoutingError::FilterCheckFailed) => Transition::Stay,
+            Err(RoutingError::UnsupportedMessageVersion(version)) => {
+                trace!(
+                    "{} - Skipping message of newer format version {}.",
+                    self,
+                    version
+                );
+                Transition::Stay
+            }
             Err(err) => {
                 debug!("{} - {:?}", self, err);
                 Transition::Stay
@@ -279,15 +610,54 @@ pub trait Base: Display {
         outbox: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
         match message {
-            Message::Hop(msg) => self.handle_hop_message(msg, outbox),
+            Message::Hop(msg) => self.handle_received_hop_message(src_addr, msg, outbox),
+            Message::Hops(msgs) => {
+                for msg in msgs {
+                    match self.handle_received_hop_message(src_addr, msg, outbox) {
+                        Ok(Transition::Stay) => (),
+                        Err(error) => debug!("{} - {:?}", self, error),
+                        transition => return transition,
+                    }
+                }
+                Ok(Transition::Stay)
+            }
             Message::Direct(msg) => {
-                let (msg, pub_id) = msg.open()?;
+                let (msg, pub_id, conn_nonce) = msg.open(self.id())?;
+                if !self
+                    .peer_map_mut()
+                    .accept_connection_nonce(&pub_id, src_addr, conn_nonce)
+                {
+                    debug!(
+                        "{} - Dropping {:?} from {} signed by {} for another connection.",
+                        self, msg, src_addr, pub_id
+                    );
+                    return Err(RoutingError::InvalidSource);
+                }
                 self.peer_map_mut().identify(pub_id, src_addr);
                 self.handle_direct_message(msg, pub_id, outbox)
             }
         }
     }
 
+    fn handle_received_hop_message(
+        &mut self,
+        src_addr: SocketAddr,
+        msg: HopMessage,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
+        if let Some(trace_id) = msg.content.trace_id() {
+            info!(
+                "{} - Trace {:?}: received {:?} from {} with TTL {}.",
+                self,
+                trace_id,
+                msg.content.routing_message(),
+                src_addr,
+                msg.ttl
+            );
+        }
+        self.handle_hop_message(msg, outbox)
+    }
+
     fn handle_unsent_message(
         &mut self,
         peer_addr: SocketAddr,
@@ -350,13 +720,25 @@ pub trait Base: Display {
     }
 
     fn send_direct_message<T: MessageRecipient>(&mut self, dst: &T, content: DirectMessage) {
-        let message = if let Ok(message) = self.to_signed_direct_message(content) {
-            message
+        let conn_info = if let Some(conn_info) = dst.resolve(self.peer_map()) {
+            conn_info.clone()
         } else {
+            warn!(
+                "{} - Not sending {:?} to unknown recipient {:?}",
+                self, content, dst
+            );
             return;
         };
+        let dst_id = dst.id(self.peer_map());
+
+        let message =
+            if let Ok(message) = self.to_signed_direct_message(&conn_info, dst_id, content) {
+                message
+            } else {
+                return;
+            };
 
-        self.send_message(dst, message);
+        self.send_message_to_initial_targets(vec![conn_info], 1, message);
     }
 
     fn send_message<T: MessageRecipient>(&mut self, dst: &T, message: Message) {
@@ -397,7 +779,8 @@ pub trait Base: Display {
         dg_size: usize,
         message: Message,
     ) {
-        let bytes = match to_network_bytes(&message) {
+        let priority = message.priority();
+        let bytes = match to_network_bytes(&message, self.network_service().codec()) {
             Ok(bytes) => bytes,
             Err((error, message)) => {
                 error!(
@@ -412,7 +795,7 @@ pub trait Base: Display {
         };
 
         self.network_service_mut()
-            .send_message_to_initial_targets(conn_infos, dg_size, bytes);
+            .send_message_to_initial_targets(conn_infos, dg_size, bytes, priority);
     }
 
     // Create HopMessage containing the given signed message.
@@ -421,8 +804,17 @@ pub trait Base: Display {
         Ok(Message::Hop(hop_msg))
     }
 
-    fn to_signed_direct_message(&self, content: DirectMessage) -> Result<Message, RoutingError> {
-        SignedDirectMessage::new(content, self.full_id())
+    // Signs `content` for the peer `dst_id` at `conn_info`, binding it to our connection to it.
+    fn to_signed_direct_message(
+        &mut self,
+        conn_info: &ConnectionInfo,
+        dst_id: Option<PublicId>,
+        content: DirectMessage,
+    ) -> Result<Message, RoutingError> {
+        let conn_nonce = self
+            .peer_map_mut()
+            .our_connection_nonce(conn_info.peer_addr);
+        SignedDirectMessage::new(content, self.full_id(), dst_id, conn_nonce)
             .map(Message::Direct)
             .map_err(|err| {
                 error!("{} - Failed to create SignedDirectMessage: {:?}", self, err);
@@ -438,6 +830,7 @@ pub trait Base: Display {
     }
 
     fn disconnect_from(&mut self, peer_addr: SocketAddr) {
+        self.network_service_mut().forget_peer(&peer_addr);
         self.network_service_mut()
             .service_mut()
             .disconnect_from(peer_addr);
@@ -469,24 +862,47 @@ pub trait Base: Display {
 
 pub fn to_network_bytes(
     message: &Message,
-) -> Result<NetworkBytes, (serialisation::SerialisationError, &Message)> {
+    codec: Codec,
+) -> Result<NetworkBytes, (RoutingError, &Message)> {
     #[cfg(not(feature = "mock_serialise"))]
     let result = Ok(NetworkBytes::from(
-        serialisation::serialise(message).map_err(|err| (err, message))?,
+        envelope::seal(message, codec).map_err(|err| (err, message))?,
     ));
 
     #[cfg(feature = "mock_serialise")]
-    let result = Ok(NetworkBytes::new(message.clone()));
+    let result = {
+        let _ = codec;
+        Ok(NetworkBytes::new(message.clone()))
+    };
 
     result
 }
 
-pub fn from_network_bytes(data: NetworkBytes) -> Result<Message, RoutingError> {
+// Number of bytes the received message is charged against the inbound budget of its sender: its
+// size once decompressed, so that a small compressed message can't smuggle a large one past the
+// budget. A compressed message which doesn't say its size is charged the whole budget.
+fn inbound_len(data: &NetworkBytes) -> usize {
+    #[cfg(not(feature = "mock_serialise"))]
+    let len = compression::decompressed_len(&data[..]).unwrap_or_else(|_| usize::max_value());
+
+    #[cfg(feature = "mock_serialise")]
+    let len = data.len();
+
+    len
+}
+
+pub fn from_network_bytes(
+    data: NetworkBytes,
+    accept_compressed: bool,
+) -> Result<Message, RoutingError> {
     #[cfg(not(feature = "mock_serialise"))]
-    let result = serialisation::deserialise(&data[..]).map_err(RoutingError::SerialisationError);
+    let result = envelope::open(&compression::decompress(&data[..], accept_compressed)?);
 
     #[cfg(feature = "mock_serialise")]
-    let result = Ok((*data).clone());
+    let result = {
+        let _ = accept_compressed;
+        Ok((*data).clone())
+    };
 
     result
 }
@@ -495,22 +911,37 @@ pub fn from_network_bytes(data: NetworkBytes) -> Result<Message, RoutingError> {
 pub trait MessageRecipient: Debug {
     /// Resolve this recipient to a ConnectionInfo using the given PeerMap.
     fn resolve<'a>(&'a self, peer_map: &'a PeerMap) -> Option<&'a ConnectionInfo>;
+
+    /// Returns the public id of this recipient, if known.
+    fn id(&self, peer_map: &PeerMap) -> Option<PublicId>;
 }
 
 impl MessageRecipient for PublicId {
     fn resolve<'a>(&'a self, peer_map: &'a PeerMap) -> Option<&'a ConnectionInfo> {
         peer_map.get_connection_info(self)
     }
+
+    fn id(&self, _: &PeerMap) -> Option<PublicId> {
+        Some(*self)
+    }
 }
 
 impl MessageRecipient for XorName {
     fn resolve<'a>(&'a self, peer_map: &'a PeerMap) -> Option<&'a ConnectionInfo> {
         peer_map.get_connection_info(self)
     }
+
+    fn id(&self, peer_map: &PeerMap) -> Option<PublicId> {
+        peer_map.get_id(self).cloned()
+    }
 }
 
 impl MessageRecipient for ConnectionInfo {
     fn resolve(&self, _: &PeerMap) -> Option<&ConnectionInfo> {
         Some(self)
     }
+
+    fn id(&self, peer_map: &PeerMap) -> Option<PublicId> {
+        peer_map.get_id_at(&self.peer_addr).cloned()
+    }
 }
@@ -11,8 +11,5 @@ mod base;
 
 pub use self::{
     approved::Approved,
-    base::{from_network_bytes, Base},
+    base::{from_network_bytes, to_network_bytes, Base},
 };
-use crate::time::Duration;
-
-pub(super) const GOSSIP_TIMEOUT: Duration = Duration::from_secs(2);
@@ -0,0 +1,100 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    id::PublicId,
+    time::{Duration, Instant},
+};
+use std::collections::VecDeque;
+
+/// Nodes asking to join our section while it already processes as many joining candidates as it
+/// admits, in the order they first asked. They are told to try again later, and admitted in that
+/// order as candidates are approved or dropped.
+#[derive(Default)]
+pub struct AdmissionQueue {
+    // Each waiting node with the time after which we forget it, should it not come back.
+    waiting: VecDeque<(PublicId, Instant)>,
+}
+
+impl AdmissionQueue {
+    /// Decides whether `pub_id` can join now, given the number of `candidates` we are processing
+    /// and the maximum number of them. Otherwise queues it and returns how long it should wait
+    /// before trying again: `backoff` for the first nodes of the queue, multiples of it further
+    /// back.
+    pub fn admit(
+        &mut self,
+        pub_id: &PublicId,
+        candidates: usize,
+        limit: usize,
+        backoff: Duration,
+    ) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.waiting.retain(|(_, expiry)| *expiry > now);
+
+        let free = limit.saturating_sub(candidates);
+        let position = self.waiting.iter().position(|(id, _)| id == pub_id);
+
+        match position {
+            Some(position) if position < free => {
+                let _ = self.waiting.remove(position);
+                return Ok(());
+            }
+            None if self.waiting.len() < free => return Ok(()),
+            _ => (),
+        }
+
+        let position = position.unwrap_or_else(|| {
+            self.waiting.push_back((*pub_id, now));
+            self.waiting.len() - 1
+        });
+
+        let backoff = backoff * (1 + position / limit.max(1)) as u32;
+        // Give the node twice its backoff to come back before giving its place away.
+        self.waiting[position].1 = now + backoff * 2;
+
+        Err(backoff)
+    }
+
+    /// Returns the number of nodes waiting to be admitted.
+    #[cfg(all(test, feature = "mock_base"))]
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+}
+
+#[cfg(all(test, feature = "mock_base"))]
+mod tests {
+    use super::*;
+    use crate::id::FullId;
+    use fake_clock::FakeClock;
+
+    #[test]
+    fn admits_in_order_once_candidates_are_processed() {
+        let backoff = Duration::from_secs(10);
+        let ids: Vec<_> = (0..4).map(|_| *FullId::new().public_id()).collect();
+
+        let mut queue = AdmissionQueue::default();
+
+        // We are busy: all of them have to wait, the ones further back for longer.
+        assert_eq!(queue.admit(&ids[0], 2, 2, backoff), Err(backoff));
+        assert_eq!(queue.admit(&ids[1], 2, 2, backoff), Err(backoff));
+        assert_eq!(queue.admit(&ids[2], 2, 2, backoff), Err(backoff * 2));
+        assert_eq!(queue.len(), 3);
+
+        // A slot frees up, but it is reserved for the first node of the queue.
+        assert_eq!(queue.admit(&ids[3], 1, 2, backoff), Err(backoff * 2));
+        assert_eq!(queue.admit(&ids[1], 1, 2, backoff), Err(backoff));
+        assert_eq!(queue.admit(&ids[0], 1, 2, backoff), Ok(()));
+        assert_eq!(queue.len(), 3);
+
+        // The nodes which don't come back in time are forgotten.
+        FakeClock::advance_time(backoff.as_secs() * 1000 * 5);
+        assert_eq!(queue.admit(&ids[3], 1, 2, backoff), Ok(()));
+        assert_eq!(queue.len(), 0);
+    }
+}
@@ -6,60 +6,90 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod admission_queue;
+mod pending_acks;
 #[cfg(all(test, feature = "mock_parsec"))]
 mod tests;
 
+use self::{admission_queue::AdmissionQueue, pending_acks::PendingAcks};
+
 use super::{
-    common::{Approved, Base, GOSSIP_TIMEOUT},
+    adult::{Adult, AdultDetails},
+    common::{from_network_bytes, Approved, Base},
     BootstrappingPeer,
 };
-#[cfg(feature = "mock_base")]
-use crate::messages::Message;
 use crate::{
+    backlog::Backlog,
     chain::{
-        delivery_group_size, AccumulatingEvent, AckMessagePayload, Chain, EldersChange, EldersInfo,
-        GenesisPfxInfo, NetworkEvent, OnlinePayload, PrefixChange, PrefixChangeOutcome,
-        SectionInfoSigPayload, SectionKeyInfo, SendAckMessagePayload, MIN_AGE, MIN_AGE_COUNTER,
+        delivery_group_size, AccumulatingEvent, AccumulatorMetrics, AckMessagePayload, Chain,
+        ConflictingVotesPayload, EldersChange, EldersInfo, GenesisPfxInfo, NamespacedUserPayload,
+        NetworkEvent, OnlinePayload, PrefixChange, PrefixChangeOutcome, SectionInfoSigPayload,
+        SectionKeyInfo, SendAckMessagesPayload, MIN_AGE, MIN_AGE_COUNTER,
     },
-    crypto::Digest256,
+    chunking::Reassembler,
+    crypto::{signing::Signature, Digest256},
+    dispatch::{ClientAuthorisation, DirectMessageView, Dispatch, Filtered, RoutingMessageView},
     error::{BootstrapResponseError, InterfaceError, RoutingError},
     event::Event,
     id::{FullId, PublicId},
+    local_params::LocalParams,
+    message_lanes::MessageLanes,
     messages::{
-        BootstrapResponse, DirectMessage, HopMessage, MessageContent, RelocateDetails,
-        RelocatePayload, RoutingMessage, SecurityMetadata, SignedRelocateDetails,
-        SignedRoutingMessage,
+        client_nonce_clock, join_challenge_bytes, BootstrapResponse, Capabilities, DirectMessage,
+        HopMessage, Message, MessageContent, ProtocolVersion, RelocateDetails, RelocatePayload,
+        RoutingMessage, SecurityMetadata, SignedDirectMessage, SignedRelocateDetails,
+        SignedRoutingMessage, MAX_HOPS,
     },
     outbox::EventBox,
     parsec::{self, ParsecMap},
-    pause::PausedState,
+    pause::{PausedCommon, PausedElder, PausedStage, PausedState, PendingMessage},
     peer_map::PeerMap,
-    routing_message_filter::RoutingMessageFilter,
+    routing_message_filter::{DuplicateCounts, RoutingMessageFilter},
     routing_table::{Authority, Prefix, Xorable},
     signature_accumulator::SignatureAccumulator,
     state_machine::State,
     state_machine::Transition,
-    time::Duration,
+    streaming::{IncomingStreams, OutgoingStreams},
+    time::{Duration, Instant},
     timer::Timer,
-    utils::XorTargetInterval,
+    types::{MessageId, RelayedClientInfo},
+    utils::{self, XorTargetInterval},
     xor_name::XorName,
-    BlsPublicKeySet, ConnectionInfo, NetworkService,
+    BlsPublicKeySet, ClientEvent, ConnectionInfo, NetworkBytes, NetworkParams, NetworkService,
+    Timeouts,
 };
 use itertools::Itertools;
 use log::LogLevel;
-#[cfg(feature = "mock_base")]
-use std::net::SocketAddr;
+use lru_time_cache::LruCache;
+use rand::Rng;
+use resource_proof::ResourceProof;
 use std::{
     cmp,
     collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
     fmt::{self, Display, Formatter},
     iter, mem,
+    net::SocketAddr,
 };
 
-/// Time after which a `Ticked` event is sent.
-const TICK_TIMEOUT: Duration = Duration::from_secs(15);
-/// Time after which we disconnect from relocated peer.
-const RELOCATE_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Time after which an incomplete vote which hasn't received any new proof is purged.
+const VOTE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Period within which lost connections are counted towards a mass disconnection.
+const MASS_DISCONNECT_WINDOW: Duration = Duration::from_secs(5);
+/// Upper bound of the random delay (in milliseconds) before reconnecting to a peer lost in a mass
+/// disconnection.
+const RECONNECT_JITTER_MS: u64 = 5_000;
+/// Maximum difference (in milliseconds) between the nonce of a `ClientRequest` or
+/// `ClientIdentify` and our clock for the message to be accepted. We only remember the last nonce
+/// of the clients whose last message is within it, as older messages are rejected anyway.
+const CLIENT_NONCE_WINDOW_MS: u64 = 10 * 60 * 1000;
+// Number of reports of undeliverable messages we remember, to raise only one event per message.
+const UNDELIVERABLE_REPORTS_CAPACITY: usize = 1_000;
+// Number of user messages we sent we remember, to accept reports that they can't be delivered.
+const SENT_USER_MESSAGES_CAPACITY: usize = 10_000;
+/// Length of the seeds of the resource proofs we challenge joining nodes with.
+const RESOURCE_PROOF_SEED_LEN: usize = 32;
+/// Length of the nonces joining nodes must sign.
+const JOIN_CHALLENGE_NONCE_LEN: usize = 32;
 
 pub struct ElderDetails {
     pub chain: Chain,
@@ -78,9 +108,15 @@ pub struct Elder {
     network_service: NetworkService,
     full_id: FullId,
     is_first_node: bool,
-    /// The queue of routing messages addressed to us. These do not themselves need forwarding,
-    /// although they may wrap a message which needs forwarding.
-    msg_queue: VecDeque<SignedRoutingMessage>,
+    /// The queue of routing messages addressed to us, handled by priority. These do not
+    /// themselves need forwarding, although they may wrap a message which needs forwarding.
+    msg_queue: MessageLanes<SignedRoutingMessage>,
+    /// The parts received so far of the user messages addressed to us which were split.
+    reassembler: Reassembler,
+    /// The streams we are sending, waiting for credit from their destinations.
+    outgoing_streams: OutgoingStreams,
+    /// The streams addressed to us, with the parts received ahead of those delivered.
+    incoming_streams: IncomingStreams,
     peer_map: PeerMap,
     routing_msg_filter: RoutingMessageFilter,
     sig_accumulator: SignatureAccumulator,
@@ -98,13 +134,58 @@ pub struct Elder {
     pfx_is_successfully_polled: bool,
     /// Peers we will disconnect from in the future.
     delayed_disconnects: HashMap<u64, PublicId>,
+    /// Peers we will try to reconnect to in the future.
+    delayed_reconnects: HashMap<u64, PublicId>,
+    /// Adults we lost the connection to and will vote offline unless they rejoin in time.
+    delayed_offline_votes: HashMap<u64, PublicId>,
+    /// Messages we sent which are yet to be acknowledged, by the token of the timer after which we
+    /// give up waiting.
+    pending_deliveries: HashMap<u64, PendingMessage>,
+    /// Requests we sent which are yet to be responded to, by the token of the timer after which we
+    /// give up waiting.
+    pending_requests: HashMap<u64, PendingMessage>,
+    /// Times at which we recently lost connections to peers.
+    recent_losses: VecDeque<Instant>,
+    /// Joining nodes we sent a `JoinChallenge`, with its nonce and the age they will join with.
+    join_challenges: HashMap<PublicId, (Vec<u8>, u8)>,
+    /// Joining nodes we challenged with a resource proof, with the seed of the proof and the age
+    /// they will join with.
+    resource_challenges: HashMap<PublicId, (Vec<u8>, u8)>,
+    /// Nodes waiting for the number of joining nodes we challenged to drop so they can join.
+    admission_queue: AdmissionQueue,
+    /// Connection infos of the elders of our neighbour sections, kept even while we are not
+    /// connected to them so that we can send nodes joining a neighbour straight to it.
+    neighbour_conn_infos: BTreeMap<PublicId, ConnectionInfo>,
+    /// Acks received from other sections, waiting to be voted for together.
+    pending_acks: PendingAcks,
+    /// Versions of other sections we will acknowledge once we voted for them.
+    pending_send_acks: PendingAcks,
+    /// Set when we are leaving the network, until our section votes us offline or the timeout
+    /// expires, then again until our last messages are sent or the timeout expires again.
+    leave_timer_token: Option<u64>,
+    /// Set once we stopped waiting to be voted offline and only wait for our last messages to be
+    /// sent and acknowledged before terminating.
+    leave_draining: bool,
+    /// Elders whose request to step down accumulated, until our section removes them from its
+    /// elders. They stay members, so we don't vote them offline.
+    stepping_down: BTreeSet<PublicId>,
+    /// Members which told us they hold their `NodeApproval`, so we don't approve them again until
+    /// we lose the connection to them.
+    joined_already: BTreeSet<PublicId>,
+    /// Nonces of the last `ClientRequest` or `ClientIdentify` we accepted from each recent client,
+    /// so replayed messages are dropped.
+    client_nonces: HashMap<PublicId, u64>,
+    /// Hashes of the messages we sent and were reported undeliverable.
+    undeliverable_reports: LruCache<Digest256, ()>,
+    /// Hashes of the user messages we recently sent, with their destination.
+    sent_user_messages: LruCache<Digest256, Authority<XorName>>,
 }
 
 impl Elder {
     pub fn first(
         network_service: NetworkService,
         full_id: FullId,
-        min_section_size: usize,
+        network_params: NetworkParams,
         timer: Timer,
         outbox: &mut dyn EventBox,
     ) -> Result<Self, RoutingError> {
@@ -112,14 +193,22 @@ impl Elder {
         let mut first_ages = BTreeMap::new();
         let _ = first_ages.insert(public_id, MIN_AGE_COUNTER);
         let gen_pfx_info = GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
             first_info: create_first_elders_info(public_id)?,
             first_state_serialized: Vec::new(),
             first_ages,
             latest_info: EldersInfo::default(),
         };
-        let parsec_map = ParsecMap::new(full_id.clone(), &gen_pfx_info);
-        let chain = Chain::new(min_section_size, public_id, gen_pfx_info.clone());
-        let peer_map = PeerMap::new();
+        let parsec_map = ParsecMap::new(
+            full_id.clone(),
+            &gen_pfx_info,
+            network_params.prune_policy,
+            network_params.local.vote_log_path.as_ref(),
+        );
+        let routing_msg_filter =
+            RoutingMessageFilter::new(network_params.local.message_filter_limits);
+        let peer_map = PeerMap::with_inbound_limits(network_params.local.inbound_limits);
+        let chain = Chain::new(network_params, public_id, gen_pfx_info.clone());
 
         let details = ElderDetails {
             chain,
@@ -130,7 +219,7 @@ impl Elder {
             msg_queue: Vec::new(),
             parsec_map,
             peer_map,
-            routing_msg_filter: RoutingMessageFilter::new(),
+            routing_msg_filter,
             timer,
         };
 
@@ -156,38 +245,133 @@ impl Elder {
         Ok(elder)
     }
 
-    pub fn pause(self) -> Result<PausedState, RoutingError> {
+    /// Creates an elder of the section described by `gen_pfx_info` right away, skipping
+    /// bootstrapping and joining. Lets tests start from a known section state.
+    #[cfg(feature = "mock_base")]
+    pub fn from_genesis(
+        network_service: NetworkService,
+        full_id: FullId,
+        gen_pfx_info: GenesisPfxInfo,
+        network_params: NetworkParams,
+        timer: Timer,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Self, RoutingError> {
+        let public_id = *full_id.public_id();
+        let parsec_map = ParsecMap::new(
+            full_id.clone(),
+            &gen_pfx_info,
+            network_params.prune_policy,
+            network_params.local.vote_log_path.as_ref(),
+        );
+        let routing_msg_filter =
+            RoutingMessageFilter::new(network_params.local.message_filter_limits);
+        let peer_map = PeerMap::with_inbound_limits(network_params.local.inbound_limits);
+        let chain = Chain::new(network_params, public_id, gen_pfx_info.clone());
+        let elders_info = gen_pfx_info.first_info.clone();
+        let prefix = *elders_info.prefix();
+
+        let details = ElderDetails {
+            chain,
+            network_service,
+            event_backlog: Vec::new(),
+            full_id,
+            gen_pfx_info,
+            msg_queue: Vec::new(),
+            parsec_map,
+            peer_map,
+            routing_msg_filter,
+            timer,
+        };
+
+        Self::from_adult(details, elders_info, prefix, outbox)
+    }
+
+    pub fn pause(mut self) -> Result<PausedState, RoutingError> {
+        let chain_accumulator = self.chain.take_accumulator_state();
         Ok(PausedState {
-            chain: self.chain,
-            full_id: self.full_id,
-            gen_pfx_info: self.gen_pfx_info,
-            msg_filter: self.routing_msg_filter,
-            msg_queue: self.msg_queue.into_iter().collect(),
-            network_service: self.network_service,
+            common: PausedCommon {
+                full_id: self.full_id,
+                msg_filter: self.routing_msg_filter,
+                msg_queue: self.msg_queue.into_vec(),
+                network_service: self.network_service,
+                peer_map: self.peer_map,
+            },
             network_rx: None,
-            parsec_map: self.parsec_map,
-            peer_map: self.peer_map,
-            sig_accumulator: self.sig_accumulator,
+            stage: PausedStage::Elder(PausedElder {
+                chain: self.chain,
+                chain_accumulator,
+                gen_pfx_info: self.gen_pfx_info,
+                parsec_map: self.parsec_map,
+                sig_accumulator: self.sig_accumulator,
+                outgoing_streams: self.outgoing_streams,
+                reassembler: self.reassembler,
+                incoming_streams: self.incoming_streams,
+                pending_deliveries: self.pending_deliveries.drain().map(|(_, p)| p).collect(),
+                pending_requests: self.pending_requests.drain().map(|(_, p)| p).collect(),
+            }),
         })
     }
 
-    pub fn resume(state: PausedState, timer: Timer) -> Self {
-        Self::new(
+    pub fn resume(common: PausedCommon, state: PausedElder, timer: Timer) -> Self {
+        let mut chain = state.chain;
+        chain.restore_accumulator_state(state.chain_accumulator);
+
+        let mut elder = Self::new(
             ElderDetails {
-                chain: state.chain,
-                network_service: state.network_service,
+                chain,
+                network_service: common.network_service,
                 event_backlog: Vec::new(),
-                full_id: state.full_id,
+                full_id: common.full_id,
                 gen_pfx_info: state.gen_pfx_info,
-                msg_queue: state.msg_queue,
+                msg_queue: common.msg_queue,
                 parsec_map: state.parsec_map,
-                peer_map: state.peer_map,
-                routing_msg_filter: state.msg_filter,
+                peer_map: common.peer_map,
+                routing_msg_filter: common.msg_filter,
                 timer,
             },
             false,
             state.sig_accumulator,
-        )
+        );
+
+        elder.outgoing_streams = state.outgoing_streams;
+        elder.reassembler = state.reassembler;
+        elder.incoming_streams = state.incoming_streams;
+
+        // We can't tell which of the pending messages got out before we paused, so send them all
+        // again. Destinations that already got them drop the copies as duplicates.
+        elder.routing_msg_filter.clear_outgoing();
+        let local_params = elder.local_params().clone();
+        for delivery in state.pending_deliveries {
+            if let Err(error) = elder.send_user_message(
+                delivery.src,
+                delivery.dst,
+                delivery.content.clone(),
+                Some(delivery.id),
+                None,
+            ) {
+                debug!(
+                    "{} - Failed to resend {:?}: {:?}",
+                    elder, delivery.id, error
+                );
+            }
+            let token = elder.timer.schedule(local_params.delivery_ack_timeout);
+            let _ = elder.pending_deliveries.insert(token, delivery);
+        }
+        for request in state.pending_requests {
+            let content = MessageContent::UserRequest {
+                token: request.id,
+                content: request.content.clone(),
+            };
+            if let Err(error) =
+                elder.send_user_message_parts(request.src, request.dst, &[content], None)
+            {
+                debug!("{} - Failed to resend {:?}: {:?}", elder, request.id, error);
+            }
+            let timer_token = elder.timer.schedule(local_params.request_timeout);
+            let _ = elder.pending_requests.insert(timer_token, request);
+        }
+
+        elder
     }
 
     pub fn relocate(
@@ -198,27 +382,70 @@ impl Elder {
         Ok(State::BootstrappingPeer(BootstrappingPeer::relocate(
             self.network_service,
             self.full_id,
-            self.chain.min_sec_size(),
+            self.chain.network_params().clone(),
             self.timer,
             conn_infos,
             details,
         )))
     }
 
+    pub fn demote(self, outbox: &mut dyn EventBox) -> Result<State, RoutingError> {
+        let backlog_limits = self.chain.network_params().backlog_limits;
+        let mut msg_backlog = Backlog::new(backlog_limits);
+        for msg in self.msg_queue.into_vec() {
+            msg_backlog.push(msg);
+        }
+
+        let details = AdultDetails {
+            network_params: self.chain.network_params().clone(),
+            network_service: self.network_service,
+            event_backlog: Backlog::new(backlog_limits),
+            full_id: self.full_id,
+            gen_pfx_info: self.gen_pfx_info,
+            msg_backlog,
+            peer_map: self.peer_map,
+            routing_msg_filter: self.routing_msg_filter,
+            timer: self.timer,
+        };
+
+        Adult::from_elder(details, self.chain, self.parsec_map, outbox).map(State::Adult)
+    }
+
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn timeouts(&self) -> Timeouts {
+        self.chain.network_params().timeouts
+    }
+
+    fn local_params(&self) -> &LocalParams {
+        &self.chain.network_params().local
+    }
+
     fn new(
         details: ElderDetails,
         is_first_node: bool,
         sig_accumulator: SignatureAccumulator,
     ) -> Self {
         let timer = details.timer;
-        let tick_timer_token = timer.schedule(TICK_TIMEOUT);
-        let gossip_timer_token = timer.schedule(GOSSIP_TIMEOUT);
+        let timeouts = details.chain.network_params().timeouts;
+        let tick_timer_token = timer.schedule(timeouts.tick);
+        let gossip_timer_token = timer.schedule(timeouts.gossip);
+
+        let mut msg_queue = MessageLanes::default();
+        for msg in details.msg_queue {
+            msg_queue.push(msg.priority(), msg);
+        }
 
         Self {
             network_service: details.network_service,
             full_id: details.full_id.clone(),
             is_first_node,
-            msg_queue: details.msg_queue.into_iter().collect(),
+            msg_queue,
+            reassembler: Reassembler::default(),
+            outgoing_streams: OutgoingStreams::default(),
+            incoming_streams: IncomingStreams::default(),
             peer_map: details.peer_map,
             routing_msg_filter: details.routing_msg_filter,
             sig_accumulator,
@@ -232,6 +459,24 @@ impl Elder {
             chain: details.chain,
             pfx_is_successfully_polled: false,
             delayed_disconnects: HashMap::default(),
+            delayed_reconnects: HashMap::default(),
+            delayed_offline_votes: HashMap::default(),
+            pending_deliveries: HashMap::default(),
+            pending_requests: HashMap::default(),
+            recent_losses: VecDeque::new(),
+            join_challenges: HashMap::default(),
+            resource_challenges: HashMap::default(),
+            admission_queue: AdmissionQueue::default(),
+            neighbour_conn_infos: BTreeMap::new(),
+            pending_acks: PendingAcks::default(),
+            pending_send_acks: PendingAcks::default(),
+            leave_timer_token: None,
+            leave_draining: false,
+            stepping_down: BTreeSet::new(),
+            joined_already: BTreeSet::new(),
+            client_nonces: HashMap::default(),
+            undeliverable_reports: LruCache::with_capacity(UNDELIVERABLE_REPORTS_CAPACITY),
+            sent_user_messages: LruCache::with_capacity(SENT_USER_MESSAGES_CAPACITY),
         }
     }
 
@@ -287,7 +532,7 @@ impl Elder {
     }
 
     fn handle_routing_messages(&mut self, outbox: &mut dyn EventBox) -> Transition {
-        while let Some(msg) = self.msg_queue.pop_front() {
+        while let Some(msg) = self.msg_queue.pop() {
             if self.in_authority(&msg.routing_message().dst) {
                 match self.dispatch_routing_message(msg, outbox) {
                     Ok(Transition::Stay) => (),
@@ -375,6 +620,13 @@ impl Elder {
                 parsec::Observation::Remove { peer_id, .. } => {
                     AccumulatingEvent::Offline(peer_id).into_network_event()
                 }
+                parsec::Observation::OpaquePayload(NetworkEvent {
+                    payload: AccumulatingEvent::Batch(events),
+                    ..
+                }) => {
+                    cached_events.extend(events);
+                    continue;
+                }
                 parsec::Observation::OpaquePayload(event) => event,
 
                 parsec::Observation::Genesis { .. }
@@ -388,8 +640,8 @@ impl Elder {
         }
         let our_pfx = *self.chain.our_prefix();
 
-        cached_events
-            .iter()
+        let events = cached_events
+            .into_iter()
             .filter(|event| match event.payload {
                 // Only re-vote not yet accumulated events and still relevant to our new prefix.
                 AccumulatingEvent::Offline(pub_id) => {
@@ -403,7 +655,14 @@ impl Elder {
                 | AccumulatingEvent::RemoveElder(_)
                 | AccumulatingEvent::Online(_)
                 | AccumulatingEvent::ParsecPrune
-                | AccumulatingEvent::Relocate(_) => false,
+                | AccumulatingEvent::Relocate(_)
+                | AccumulatingEvent::StepDown(_) => false,
+
+                // Drop: the change applied to our info before the prefix change.
+                AccumulatingEvent::EldersChanged { .. } => false,
+
+                // Drop: batches were unpacked above.
+                AccumulatingEvent::Batch(_) => false,
 
                 // Keep: Additional signatures for neighbours for sec-msg-relay.
                 AccumulatingEvent::SectionInfo(ref elders_info) => {
@@ -417,12 +676,14 @@ impl Elder {
                 AccumulatingEvent::NeighbourMerge(_)
                 | AccumulatingEvent::TheirKeyInfo(_)
                 | AccumulatingEvent::AckMessage(_)
-                | AccumulatingEvent::SendAckMessage(_)
-                | AccumulatingEvent::User(_) => true,
+                | AccumulatingEvent::SendAckMessages(_)
+                | AccumulatingEvent::User(_)
+                | AccumulatingEvent::NamespacedUser(_)
+                | AccumulatingEvent::ConflictingVotes(_)
+                | AccumulatingEvent::RelocationComplete(_) => true,
             })
-            .for_each(|event| {
-                self.vote_for_network_event(event.clone());
-            });
+            .collect();
+        self.vote_for_many(events);
 
         Ok(())
     }
@@ -455,16 +716,18 @@ impl Elder {
         }
 
         if let Some(signed_msg) = self.sig_accumulator.add_proof(msg.clone()) {
-            self.handle_signed_message(signed_msg)?;
+            self.handle_signed_message(signed_msg, MAX_HOPS)?;
         }
         Ok(())
     }
 
     // If the message is for us, verify it then, handle the enclosed routing message and swarm it
-    // to the rest of our section when destination is targeting multiple; if not, forward it.
+    // to the rest of our section when destination is targeting multiple; if not, forward it. `ttl`
+    // is the number of times the message may still be relayed.
     fn handle_signed_message(
         &mut self,
         mut signed_msg: SignedRoutingMessage,
+        ttl: u8,
     ) -> Result<(), RoutingError> {
         if !self
             .routing_msg_filter
@@ -481,24 +744,76 @@ impl Elder {
 
         if self.in_authority(&signed_msg.routing_message().dst) {
             self.check_signed_message_trust(&signed_msg)?;
+            self.check_signed_message_freshness(&signed_msg)?;
             self.check_signed_message_integrity(&signed_msg)?;
             self.update_our_knowledge(&signed_msg);
 
             if signed_msg.routing_message().dst.is_multiple() {
                 // Broadcast to the rest of the section.
-                if let Err(error) = self.send_signed_message(&mut signed_msg) {
+                if let Err(error) = self.relay_signed_message(&mut signed_msg, ttl) {
                     debug!("{} Failed to send {:?}: {:?}", self, signed_msg, error);
                 }
             }
             // if addressed to us, then we just queue it and return
-            self.msg_queue.push_back(signed_msg);
-        } else if let Err(error) = self.send_signed_message(&mut signed_msg) {
+            signed_msg.set_hop_count(MAX_HOPS.saturating_sub(ttl));
+            self.msg_queue.push(signed_msg.priority(), signed_msg);
+        } else if signed_msg.routing_message().content.is_user_message()
+            && self.is_undeliverable(&signed_msg.routing_message().dst)
+        {
+            self.report_undeliverable(signed_msg.routing_message())?;
+        } else if let Err(error) = self.relay_signed_message(&mut signed_msg, ttl) {
             debug!("{} Failed to send {:?}: {:?}", self, signed_msg, error);
         }
 
         Ok(())
     }
 
+    // Returns whether a message to `dst` can't be delivered, as it is addressed to a node which is
+    // neither a member of our section, nor being relocated out of it or joining it.
+    fn is_undeliverable(&self, dst: &Authority<XorName>) -> bool {
+        self.chain.is_undeliverable(dst)
+            && !self
+                .join_challenges
+                .keys()
+                .chain(self.resource_challenges.keys())
+                .any(|pub_id| Authority::Node(*pub_id.name()) == *dst)
+    }
+
+    // Remembers that we sent `msg`, if a user message, to accept reports that it can't be
+    // delivered.
+    fn record_sent_user_message(&mut self, msg: &RoutingMessage) -> Result<(), RoutingError> {
+        if msg.content.is_user_message() {
+            let _ = self.sent_user_messages.insert(msg.hash()?, msg.dst);
+        }
+        Ok(())
+    }
+
+    // Tells the source of a message addressed to a node of our section which doesn't exist, or
+    // encrypted to a key we can't decrypt with, that it can't be delivered.
+    fn report_undeliverable(&mut self, msg: &RoutingMessage) -> Result<(), RoutingError> {
+        debug!("{} - Cannot deliver {:?}.", self, msg);
+        self.send_routing_message(RoutingMessage {
+            src: Authority::Node(*self.name()),
+            dst: msg.src,
+            content: MessageContent::Undeliverable {
+                digest: msg.hash()?,
+                dst: msg.dst,
+            },
+        })
+    }
+
+    // Relays a message which may still be relayed `ttl` times, unless it reached its hop limit.
+    fn relay_signed_message(
+        &mut self,
+        signed_msg: &mut SignedRoutingMessage,
+        ttl: u8,
+    ) -> Result<(), RoutingError> {
+        match ttl.checked_sub(1) {
+            Some(ttl) => self.send_signed_message(signed_msg, ttl),
+            None => Err(RoutingError::HopLimitExceeded),
+        }
+    }
+
     fn dispatch_routing_message(
         &mut self,
         signed_msg: SignedRoutingMessage,
@@ -506,22 +821,62 @@ impl Elder {
     ) -> Result<Transition, RoutingError> {
         use crate::messages::MessageContent::*;
 
-        let (msg, metadata) = signed_msg.into_parts();
+        let trace_id = signed_msg.trace_id();
+        let delivery = Delivery {
+            trace_id,
+            hop_count: signed_msg.hop_count(),
+            secure: signed_msg.is_section_signed(),
+        };
+        let (mut msg, metadata) = signed_msg.into_parts();
+
+        if let Some(trace_id) = trace_id {
+            info!("{} - Trace {:?}: delivered {:?}.", self, trace_id, msg);
+        }
+
+        // Decrypt first, so the handlers and the built-in handling see the message itself.
+        if let EncryptedUserMessage(ciphertext) = &msg.content {
+            match MessageContent::decrypt(ciphertext, &self.full_id) {
+                Ok(content) => msg.content = content,
+                Err(error) => {
+                    // The source encrypted it to a key of our section from before we joined it:
+                    // let it know rather than dropping the message silently.
+                    self.report_undeliverable(&msg)?;
+                    return Err(error);
+                }
+            }
+        }
 
         match msg.content {
-            UserMessage { .. } => (),
+            UserMessage { .. }
+            | UserMessagePart { .. }
+            | EncryptedUserMessage(_)
+            | StreamPart { .. }
+            | RelayedClientMessage { .. } => (),
             _ => trace!("{} Got routing message {:?}.", self, msg),
         }
 
+        let view = RoutingMessageView::new(&msg.content, &msg.src, &msg.dst);
+        let dispatch = self
+            .local_params()
+            .dispatch_table
+            .dispatch_routing_message(&view);
+        if dispatch == Dispatch::Consume {
+            trace!("{} {:?} consumed by a handler.", self, msg.content.kind());
+            return Ok(Transition::Stay);
+        }
+
         match (msg.content, msg.src, msg.dst) {
             (
                 ConnectionRequest {
-                    conn_info, pub_id, ..
+                    conn_info,
+                    pub_id,
+                    capabilities,
+                    ..
                 },
                 src @ Authority::Node(_),
                 dst @ Authority::Node(_),
             ) => {
-                self.handle_connection_request(conn_info, pub_id, src, dst, outbox)?;
+                self.handle_connection_request(conn_info, pub_id, capabilities, src, dst, outbox)?;
                 Ok(Transition::Stay)
             }
             (NeighbourInfo(elders_info), Authority::Section(_), Authority::PrefixSection(_)) => {
@@ -532,8 +887,64 @@ impl Elder {
                 self.handle_merge(digest)?;
                 Ok(Transition::Stay)
             }
-            (UserMessage(content), src, dst) => {
-                outbox.send_event(Event::MessageReceived { content, src, dst });
+            (content @ UserMessage { .. }, src, dst)
+            | (content @ UserMessagePart { .. }, src, dst) => {
+                self.handle_user_message(content, src, dst, delivery, outbox)?;
+                Ok(Transition::Stay)
+            }
+            (DeliveryAck(msg_id), src, _) => {
+                self.handle_delivery_ack(msg_id, src, outbox);
+                Ok(Transition::Stay)
+            }
+            (UserRequest { token, content }, src, dst) => {
+                self.handle_user_request(token, content, src, dst, outbox)?;
+                Ok(Transition::Stay)
+            }
+            (UserResponse { token, content }, src, _) => {
+                self.handle_user_response(token, content, src, outbox);
+                Ok(Transition::Stay)
+            }
+            (Undeliverable { digest, dst }, Authority::Node(reporter), src) => {
+                self.handle_undeliverable(digest, reporter, src, dst, outbox);
+                Ok(Transition::Stay)
+            }
+            (content @ StreamPart { .. }, src, dst) => {
+                self.handle_stream_part(content, src, dst, outbox)?;
+                Ok(Transition::Stay)
+            }
+            (StreamCredit { stream_id, credit }, src, _) => {
+                self.handle_stream_credit(stream_id, src, credit)?;
+                Ok(Transition::Stay)
+            }
+            (
+                RelayedClientMessage {
+                    client,
+                    content,
+                    msg_id,
+                },
+                src @ Authority::Node(_),
+                dst,
+            ) => {
+                if src.name() != client.proxy {
+                    debug!(
+                        "{} - Ignoring client message relayed by {:?} on behalf of {:?}.",
+                        self, src, client
+                    );
+                    return Ok(Transition::Stay);
+                }
+
+                let client = Some(client);
+                let _ =
+                    self.raise_user_message(msg_id, content, src, dst, client, delivery, outbox)?;
+                Ok(Transition::Stay)
+            }
+            (MessageRejected { msg_id, reason }, src, dst) => {
+                outbox.send_event(Event::MessageRejected {
+                    src: dst,
+                    dst: src,
+                    msg_id,
+                    reason,
+                });
                 Ok(Transition::Stay)
             }
             (
@@ -550,6 +961,10 @@ impl Elder {
             (Relocate(payload), src @ Authority::Section(_), dst @ Authority::Node(_)) => {
                 Ok(self.handle_relocate(src, dst, payload, metadata))
             }
+            (NodeApproval(_), Authority::PrefixSection(_), Authority::Node(_)) => {
+                self.handle_duplicate_node_approval();
+                Ok(Transition::Stay)
+            }
             (content, src, dst) => {
                 debug!(
                     "{} Unhandled routing message {:?} from {:?} to {:?}",
@@ -569,20 +984,55 @@ impl Elder {
     ) -> Result<(), RoutingError> {
         // Prefix doesn't need to match, as we may get an ack for the section where we were before
         // splitting.
-        self.vote_for_event(AccumulatingEvent::AckMessage(AckMessagePayload {
-            src_prefix,
-            ack_version,
-        }));
+        self.pending_acks.insert(src_prefix, ack_version);
         Ok(())
     }
 
-    fn vote_send_section_info_ack(&mut self, ack_payload: SendAckMessagePayload) {
-        let has_their_keys = self.chain.get_their_keys_info().any(|(_, info)| {
-            *info.prefix() == ack_payload.ack_prefix && *info.version() == ack_payload.ack_version
-        });
+    // Called when a consensused event tells us about the `version` of `prefix`.
+    fn vote_send_section_info_ack(&mut self, prefix: Prefix<XorName>, version: u64) {
+        let has_their_keys = self
+            .chain
+            .get_their_keys_info()
+            .any(|(_, info)| *info.prefix() == prefix && *info.version() == version);
 
         if has_their_keys {
-            self.vote_for_event(AccumulatingEvent::SendAckMessage(ack_payload));
+            self.pending_send_acks.insert(prefix, version);
+            self.vote_for_pending_send_acks();
+        }
+    }
+
+    // Votes for sending all the pending acks in a single `SendAckMessages` event, unless the one we
+    // voted for last hasn't accumulated yet: the acks of a burst of section updates then wait for
+    // it and are voted for together. Only consensused events change the pending acks and call
+    // this, so every elder votes for identical contents, as `SendAckMessages` needs all of them.
+    fn vote_for_pending_send_acks(&mut self) {
+        if let Some(acks) = self.pending_send_acks.take_batch() {
+            self.vote_for_event(AccumulatingEvent::SendAckMessages(SendAckMessagesPayload {
+                acks,
+            }));
+        }
+    }
+
+    // Votes for the received acks which changed since we last voted for them, all in a single
+    // observation. The elders receive the `AckMessage`s at different times, so each
+    // (prefix, version) is a separate event, which accumulates as soon as enough elders saw it,
+    // whatever else they have pending.
+    fn vote_for_pending_acks(&mut self) {
+        let events: Vec<_> = self
+            .pending_acks
+            .take_unvoted()
+            .into_iter()
+            .map(|(src_prefix, ack_version)| {
+                let payload = AckMessagePayload {
+                    src_prefix,
+                    ack_version,
+                };
+                AccumulatingEvent::AckMessage(payload).into_network_event()
+            })
+            .collect();
+
+        if !events.is_empty() {
+            self.vote_for_many(events);
         }
     }
 
@@ -612,6 +1062,7 @@ impl Elder {
         };
 
         let trimmed_info = GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
             first_info: self.gen_pfx_info.first_info.clone(),
             first_state_serialized: self.gen_pfx_info.first_state_serialized.clone(),
             first_ages: self.gen_pfx_info.first_ages.clone(),
@@ -634,6 +1085,31 @@ impl Elder {
             .init(self.full_id.clone(), &self.gen_pfx_info, &self.log_ident())
     }
 
+    // Records the capabilities we have in common with the bootstrapping peer. Rejects it and
+    // returns `false` if it speaks an incompatible version of the protocol.
+    fn negotiate_capabilities(&mut self, pub_id: &PublicId, capabilities: Capabilities) -> bool {
+        if let Some(common) = Capabilities::ours().common(&capabilities) {
+            if let Some(conn_info) = self.peer_map.get_connection_info(pub_id) {
+                self.network_service
+                    .set_peer_capabilities(conn_info.peer_addr, common);
+            }
+            return true;
+        }
+
+        debug!(
+            "{} - Rejecting BootstrapRequest from {} - incompatible protocol version {:?}.",
+            self, pub_id, capabilities.version
+        );
+        self.send_direct_message(
+            pub_id,
+            DirectMessage::BootstrapResponse(BootstrapResponse::Error(
+                BootstrapResponseError::IncompatibleVersion(ProtocolVersion::CURRENT),
+            )),
+        );
+        self.disconnect(pub_id);
+        false
+    }
+
     // If this returns an error, the peer will be dropped.
     fn handle_bootstrap_request(
         &mut self,
@@ -653,9 +1129,11 @@ impl Elder {
             return Err(RoutingError::UnknownConnection(pub_id));
         };
 
-        if self.chain.is_peer_our_member(&pub_id) {
+        // An adult restarting with its identity can rejoin right away, but an elder's parsec
+        // state can't be restored, so it has to wait until we removed it.
+        if self.chain.is_peer_elder(&pub_id) {
             debug!(
-                "{} - Ignoring BootstrapRequest from {} - already member of our section",
+                "{} - Ignoring BootstrapRequest from {} - already elder of our section",
                 self, pub_id
             );
             return Ok(());
@@ -680,12 +1158,78 @@ impl Elder {
             return Ok(());
         }
 
+        if self.proxy_load(&pub_id) >= self.chain.network_params().max_proxy_load {
+            self.shed_bootstrap_request(&pub_id);
+            return Ok(());
+        }
+
+        if self.our_prefix().matches(&name) {
+            let params = self.chain.network_params();
+            if let Err(backoff) = self.admission_queue.admit(
+                &pub_id,
+                self.join_challenges.len() + self.resource_challenges.len(),
+                params.max_joining_candidates,
+                params.admission_backoff,
+            ) {
+                debug!(
+                    "{} - Too many joining nodes - sending BootstrapResponse::TryAgainLater to {}",
+                    self, pub_id
+                );
+                self.send_direct_message(
+                    &pub_id,
+                    DirectMessage::BootstrapResponse(BootstrapResponse::TryAgainLater { backoff }),
+                );
+                self.disconnect(&pub_id);
+                return Ok(());
+            }
+        }
+
         self.respond_to_bootstrap_request(&pub_id, &name);
 
         Ok(())
     }
 
+    // Refreshes the cached connection infos of our neighbours' elders with the ones we are
+    // connected to and forgets the ones which are no longer neighbour elders.
+    fn update_neighbour_conn_infos(&mut self) {
+        let neighbour_elders: BTreeSet<_> = self.chain.neighbour_elders().copied().collect();
+        self.neighbour_conn_infos
+            .retain(|pub_id, _| neighbour_elders.contains(pub_id));
+
+        for pub_id in neighbour_elders {
+            if let Some(conn_info) = self.peer_map.get_connection_info(&pub_id) {
+                let _ = self.neighbour_conn_infos.insert(pub_id, conn_info.clone());
+            }
+        }
+    }
+
+    // Returns the prefix and the cached connection infos of the elders of the neighbour section
+    // matching `name`, if we know any.
+    fn neighbour_join_info(
+        &self,
+        name: &XorName,
+    ) -> Option<(Prefix<XorName>, Vec<ConnectionInfo>)> {
+        let info = self
+            .chain
+            .neighbour_infos()
+            .find(|info| info.prefix().matches(name))?;
+        let conn_infos: Vec<_> = info
+            .members()
+            .iter()
+            .filter_map(|pub_id| self.neighbour_conn_infos.get(pub_id))
+            .cloned()
+            .collect();
+
+        if conn_infos.is_empty() {
+            None
+        } else {
+            Some((*info.prefix(), conn_infos))
+        }
+    }
+
     fn respond_to_bootstrap_request(&mut self, pub_id: &PublicId, name: &XorName) {
+        self.update_neighbour_conn_infos();
+
         let response = if self.our_prefix().matches(name) {
             let mut conn_infos: Vec<_> = self
                 .peer_map
@@ -700,6 +1244,18 @@ impl Elder {
             BootstrapResponse::Join {
                 prefix: *self.chain.our_prefix(),
                 conn_infos,
+                capabilities: Capabilities::ours(),
+            }
+        } else if let Some((prefix, conn_infos)) = self.neighbour_join_info(name) {
+            // Spare the peer the round trip of rebootstrapping off our neighbour.
+            debug!(
+                "{} - Sending BootstrapResponse::Join to {} for neighbour {:?}",
+                self, pub_id, prefix
+            );
+            BootstrapResponse::Join {
+                prefix,
+                conn_infos,
+                capabilities: Capabilities::ours(),
             }
         } else {
             let names = self.chain.closest_section(name).1;
@@ -717,11 +1273,61 @@ impl Elder {
         self.send_direct_message(pub_id, DirectMessage::BootstrapResponse(response));
     }
 
+    // Returns the number of clients and joining peers we are the bootstrap proxy of, other than
+    // `pub_id`.
+    fn proxy_load(&self, pub_id: &PublicId) -> usize {
+        let joining_peers = self
+            .peer_map
+            .connected_ids()
+            .filter(|other_id| {
+                *other_id != pub_id
+                    && !self.chain.is_peer_our_member(other_id)
+                    && !self.chain.is_peer_elder(other_id)
+            })
+            .count();
+        joining_peers + self.peer_map.client_count()
+    }
+
+    // Redirects the bootstrapping peer to our neighbours' elders, or to our other elders if we
+    // don't know any, because we are already the proxy of too many peers.
+    fn shed_bootstrap_request(&mut self, pub_id: &PublicId) {
+        self.update_neighbour_conn_infos();
+
+        let mut conn_infos: Vec<_> = self.neighbour_conn_infos.values().cloned().collect();
+        if conn_infos.is_empty() {
+            let our_id = *self.id();
+            conn_infos = self
+                .peer_map
+                .get_connection_infos(self.chain.our_elders().filter(|id| **id != our_id))
+                .cloned()
+                .collect();
+        }
+
+        debug!(
+            "{} - Too many peers to proxy - sending BootstrapResponse::Rebootstrap to {}",
+            self, pub_id
+        );
+        self.send_direct_message(
+            pub_id,
+            DirectMessage::BootstrapResponse(BootstrapResponse::Rebootstrap(conn_infos)),
+        );
+        self.disconnect(pub_id);
+    }
+
     fn handle_connection_response(&mut self, pub_id: PublicId, _: &mut dyn EventBox) {
         debug!("{} - Received connection response from {}", self, pub_id);
+        if let Some(conn_info) = self.peer_map.get_connection_info(&pub_id) {
+            self.network_service
+                .accept_compressed_from(conn_info.peer_addr);
+        }
     }
 
-    fn handle_join_request(&mut self, pub_id: PublicId, relocate_payload: Option<RelocatePayload>) {
+    fn handle_join_request(
+        &mut self,
+        pub_id: PublicId,
+        relocate_payload: Option<RelocatePayload>,
+        outbox: &mut dyn EventBox,
+    ) {
         debug!("{} - Received JoinRequest from {}", self, pub_id);
 
         if !self.chain.our_prefix().matches(pub_id.name()) {
@@ -734,14 +1340,27 @@ impl Elder {
             return;
         }
 
-        if self.chain.is_peer_our_member(&pub_id) {
+        if self.chain.is_peer_elder(&pub_id) {
             debug!(
-                "{} - Ignoring JoinRequest from {} - already member of our section.",
+                "{} - Ignoring JoinRequest from {} - already elder of our section.",
                 self, pub_id
             );
             return;
         }
 
+        if self.joined_already.contains(&pub_id) {
+            debug!(
+                "{} - Ignoring JoinRequest from {} - already joined.",
+                self, pub_id
+            );
+            return;
+        }
+
+        if self.chain.is_peer_our_member(&pub_id) {
+            self.handle_rejoin_request(pub_id, outbox);
+            return;
+        }
+
         // This joining node is being relocated to us.
         let age = if let Some(payload) = relocate_payload {
             if !payload.verify_identity(&pub_id) {
@@ -791,48 +1410,162 @@ impl Elder {
         };
 
         self.send_direct_message(&pub_id, DirectMessage::ConnectionResponse);
-        self.vote_for_event(AccumulatingEvent::Online(OnlinePayload { pub_id, age }))
+        self.send_join_challenge(pub_id, age);
     }
 
-    fn handle_relocate(
-        &mut self,
-        src: Authority<XorName>,
-        dst: Authority<XorName>,
-        payload: RelocateDetails,
-        security_metadata: SecurityMetadata,
-    ) -> Transition {
-        if self.chain.our_prefix().matches(&payload.destination) {
-            debug!(
-                "{} - Ignoring Relocate message - already at the destination.",
-                self
-            );
-            return Transition::Stay;
-        }
-
-        debug!(
-            "{} - Received Relocate message - rebootstrapping to join the new section at {}.",
-            self, payload.destination
-        );
+    // Approves an adult which restarted with the identity it had as a member of our section, without
+    // a resource proof. The request being a signed direct message, the sender proved it holds the
+    // key of that member.
+    fn handle_rejoin_request(&mut self, pub_id: PublicId, outbox: &mut dyn EventBox) {
+        info!("{} - {} is rejoining our section.", self, pub_id);
 
-        let names = self.chain.closest_section(&payload.destination).1;
-        let conn_infos = self
-            .peer_map
-            .get_connection_infos(&names)
-            .cloned()
-            .collect();
+        self.delayed_offline_votes
+            .retain(|_, delayed_id| *delayed_id != pub_id);
+        self.send_direct_message(&pub_id, DirectMessage::ConnectionResponse);
+        self.handle_candidate_approval(pub_id, outbox);
+    }
 
-        for conn_info in self.peer_map.remove_all() {
-            self.network_service
-                .service_mut()
-                .disconnect_from(conn_info.peer_addr);
+    // Challenges the joining node to sign a fresh nonce before we spend any effort on it. A repeated
+    // `JoinRequest` gets the same challenge again, or the resource proof if it answered already.
+    fn send_join_challenge(&mut self, pub_id: PublicId, age: u8) {
+        if self.resource_challenges.contains_key(&pub_id) {
+            self.send_resource_challenge(pub_id, age);
+            return;
         }
 
-        let details = SignedRelocateDetails::new(payload, src, dst, security_metadata);
+        let nonce = self
+            .join_challenges
+            .entry(pub_id)
+            .or_insert_with(|| {
+                let nonce = utils::new_rng()
+                    .gen_iter()
+                    .take(JOIN_CHALLENGE_NONCE_LEN)
+                    .collect();
+                (nonce, age)
+            })
+            .0
+            .clone();
 
-        Transition::Relocate {
-            details,
-            conn_infos,
-        }
+        self.send_direct_message(&pub_id, DirectMessage::JoinChallenge(nonce));
+    }
+
+    fn handle_join_challenge_response(&mut self, pub_id: PublicId, signature: Signature) {
+        let (nonce, age) = if let Some(challenge) = self.join_challenges.remove(&pub_id) {
+            challenge
+        } else {
+            debug!(
+                "{} - Ignoring JoinChallengeResponse from {} - not challenged.",
+                self, pub_id
+            );
+            return;
+        };
+
+        if !pub_id.verify(&join_challenge_bytes(&nonce), &signature) {
+            debug!("{} - Invalid JoinChallengeResponse from {}.", self, pub_id);
+            self.disconnect(&pub_id);
+            return;
+        }
+
+        self.send_resource_challenge(pub_id, age);
+    }
+
+    // Challenges the joining node with a resource proof. A repeated `JoinRequest` gets the same
+    // challenge again, so that the node doesn't have to start over.
+    fn send_resource_challenge(&mut self, pub_id: PublicId, age: u8) {
+        let seed = self
+            .resource_challenges
+            .entry(pub_id)
+            .or_insert_with(|| {
+                let seed = utils::new_rng()
+                    .gen_iter()
+                    .take(RESOURCE_PROOF_SEED_LEN)
+                    .collect();
+                (seed, age)
+            })
+            .0
+            .clone();
+
+        let params = self.chain.network_params();
+        let challenge = DirectMessage::ResourceChallenge {
+            target_size: params.resource_proof_target_size,
+            difficulty: params.resource_proof_difficulty,
+            seed,
+        };
+        self.send_direct_message(&pub_id, challenge);
+    }
+
+    fn handle_resource_proof_response(
+        &mut self,
+        pub_id: PublicId,
+        data: VecDeque<u8>,
+        solution: u64,
+    ) {
+        let (seed, age) = if let Some(challenge) = self.resource_challenges.remove(&pub_id) {
+            challenge
+        } else {
+            debug!(
+                "{} - Ignoring ResourceProofResponse from {} - not challenged.",
+                self, pub_id
+            );
+            return;
+        };
+
+        let params = self.chain.network_params();
+        let resource_proof = ResourceProof::new(
+            params.resource_proof_target_size,
+            params.resource_proof_difficulty,
+        );
+        let resource_proof_verified = resource_proof.validate_all(&seed, &data, solution);
+        if !resource_proof_verified {
+            debug!("{} - Invalid ResourceProofResponse from {}.", self, pub_id);
+        }
+
+        self.vote_for_event(AccumulatingEvent::Online(OnlinePayload {
+            pub_id,
+            age,
+            resource_proof_verified,
+        }))
+    }
+
+    fn handle_relocate(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        payload: RelocateDetails,
+        security_metadata: SecurityMetadata,
+    ) -> Transition {
+        if self.chain.our_prefix().matches(&payload.destination) {
+            debug!(
+                "{} - Ignoring Relocate message - already at the destination.",
+                self
+            );
+            return Transition::Stay;
+        }
+
+        debug!(
+            "{} - Received Relocate message - rebootstrapping to join the new section at {}.",
+            self, payload.destination
+        );
+
+        let names = self.chain.closest_section(&payload.destination).1;
+        let conn_infos = self
+            .peer_map
+            .get_connection_infos(&names)
+            .cloned()
+            .collect();
+
+        for conn_info in self.peer_map.remove_all() {
+            self.network_service
+                .service_mut()
+                .disconnect_from(conn_info.peer_addr);
+        }
+
+        let details = SignedRelocateDetails::new(payload, src, dst, security_metadata);
+
+        Transition::Relocate {
+            details,
+            conn_infos,
+        }
     }
 
     fn update_our_knowledge(&mut self, signed_msg: &SignedRoutingMessage) {
@@ -858,6 +1591,298 @@ impl Elder {
         Ok(())
     }
 
+    // Handles a `UserMessage` or `UserMessagePart`, raising the message once all its parts arrived,
+    // and acknowledging it if the sender asked for it and our filters accepted it.
+    fn handle_user_message(
+        &mut self,
+        content: MessageContent,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        delivery: Delivery,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        let (content, msg_id, ack) = match content {
+            MessageContent::UserMessage { content, msg_id } => (content, msg_id, false),
+            MessageContent::UserMessagePart {
+                digest,
+                index,
+                part_count,
+                payload,
+                msg_id,
+                ack,
+            } => match self
+                .reassembler
+                .add(src, dst, digest, index, part_count, payload)?
+            {
+                Some(content) => (content, msg_id, ack),
+                None => return Ok(()),
+            },
+            _ => return Err(RoutingError::InvalidMessage),
+        };
+
+        if !self.raise_user_message(msg_id, content, src, dst, None, delivery, outbox)? {
+            return Ok(());
+        }
+
+        if ack {
+            self.send_routing_message(RoutingMessage {
+                src: dst,
+                dst: src,
+                content: MessageContent::DeliveryAck(msg_id),
+            })?;
+        }
+        Ok(())
+    }
+
+    // Raises a user message, unless the filters of our dispatch table reject it. Returns whether it
+    // was raised.
+    #[allow(clippy::too_many_arguments)]
+    fn raise_user_message(
+        &mut self,
+        msg_id: MessageId,
+        content: Vec<u8>,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        client: Option<RelayedClientInfo>,
+        delivery: Delivery,
+        outbox: &mut dyn EventBox,
+    ) -> Result<bool, RoutingError> {
+        let content = match self.filter_user_message(msg_id, content, src, dst)? {
+            Some(content) => content,
+            None => return Ok(false),
+        };
+
+        outbox.send_event(Event::MessageReceived {
+            content,
+            src,
+            dst,
+            client,
+            trace_id: delivery.trace_id,
+            msg_id,
+            hop_count: delivery.hop_count,
+            secure: delivery.secure,
+        });
+        Ok(true)
+    }
+
+    // Passes the content of a user message, request or stream part through the filters of our
+    // dispatch table. Returns the content they accepted, or `None` after sending their reason back
+    // to the source if they rejected it.
+    fn filter_user_message(
+        &mut self,
+        msg_id: MessageId,
+        content: Vec<u8>,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+    ) -> Result<Option<Vec<u8>>, RoutingError> {
+        let filtered = self
+            .local_params()
+            .dispatch_table
+            .filter_user_message(&src, &dst, content);
+
+        match filtered {
+            Filtered::Accept(content) => Ok(Some(content)),
+            Filtered::Reject(reason) => {
+                debug!(
+                    "{} - {:?} from {:?} to {:?} rejected by a filter.",
+                    self, msg_id, src, dst
+                );
+                self.send_routing_message(RoutingMessage {
+                    src: dst,
+                    dst: src,
+                    content: MessageContent::MessageRejected { msg_id, reason },
+                })?;
+                Ok(None)
+            }
+        }
+    }
+
+    // Handles the acknowledgement of a message we sent, which only its destination can send.
+    fn handle_delivery_ack(
+        &mut self,
+        msg_id: MessageId,
+        src: Authority<XorName>,
+        outbox: &mut dyn EventBox,
+    ) {
+        let token = self
+            .pending_deliveries
+            .iter()
+            .find(|(_, pending)| pending.dst == src && pending.id == msg_id)
+            .map(|(token, _)| *token);
+
+        if let Some(token) = token {
+            let _ = self.pending_deliveries.remove(&token);
+            outbox.send_event(Event::MessageDelivered(msg_id));
+        }
+    }
+
+    fn handle_user_request(
+        &mut self,
+        token: MessageId,
+        content: Vec<u8>,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        if let Some(content) = self.filter_user_message(token, content, src, dst)? {
+            outbox.send_event(Event::RequestReceived {
+                content,
+                src,
+                dst,
+                token,
+            });
+        }
+        Ok(())
+    }
+
+    // Raises the response to our request with the given token, unless we gave up waiting for it.
+    // Only the destination of the request can respond to it: the token alone is no proof, as the
+    // nodes relaying the request see it.
+    fn handle_user_response(
+        &mut self,
+        token: MessageId,
+        content: Vec<u8>,
+        src: Authority<XorName>,
+        outbox: &mut dyn EventBox,
+    ) {
+        let request = self
+            .pending_requests
+            .iter()
+            .find(|(_, pending)| pending.id == token)
+            .map(|(timer_token, pending)| (*timer_token, pending.dst));
+
+        match request {
+            Some((timer_token, dst)) if dst == src => {
+                let _ = self.pending_requests.remove(&timer_token);
+                outbox.send_event(Event::NodeResponseReceived {
+                    token,
+                    content,
+                    src,
+                });
+            }
+            Some((_, dst)) => debug!(
+                "{} - Dropping response to request {:?} from {:?}, sent to {:?}.",
+                self, token, src, dst
+            ),
+            None => trace!(
+                "{} - Dropping response to unknown request {:?}.",
+                self,
+                token
+            ),
+        }
+    }
+
+    // Handles the report by `reporter` that our message with the given hash from `src` to `dst`
+    // couldn't be delivered. Each node of the destination section may report it, but we raise it
+    // only once. Reports of messages we didn't send, or by nodes outside the destination section,
+    // are ignored.
+    fn handle_undeliverable(
+        &mut self,
+        digest: Digest256,
+        reporter: XorName,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        outbox: &mut dyn EventBox,
+    ) {
+        if self.sent_user_messages.get(&digest) != Some(&dst) {
+            debug!(
+                "{} - Ignoring report by {} of a message to {:?} we didn't send.",
+                self, reporter, dst
+            );
+            return;
+        }
+        if !self.chain.is_in_section_of(&reporter, &dst) {
+            debug!(
+                "{} - Ignoring report by {} outside the section of {:?}.",
+                self, reporter, dst
+            );
+            return;
+        }
+        if self.undeliverable_reports.insert(digest, ()).is_some() {
+            return;
+        }
+
+        debug!(
+            "{} - Message from {:?} to {:?} undeliverable.",
+            self, src, dst
+        );
+        outbox.send_event(Event::MessageUndeliverable { src, dst });
+    }
+
+    // Handles a `StreamPart`, raising the parts of the stream which can now be delivered in order,
+    // and granting the sender credit for as many more.
+    fn handle_stream_part(
+        &mut self,
+        content: MessageContent,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        let (stream_id, progress) = match content {
+            MessageContent::StreamPart {
+                stream_id,
+                index,
+                part_count,
+                payload,
+            } => {
+                let progress = self
+                    .incoming_streams
+                    .add(src, dst, stream_id, index, part_count, payload)?;
+                (stream_id, progress)
+            }
+            _ => return Err(RoutingError::InvalidMessage),
+        };
+
+        for (index, content) in progress.parts {
+            if let Some(content) = self.filter_user_message(stream_id, content, src, dst)? {
+                outbox.send_event(Event::StreamPart {
+                    src,
+                    dst,
+                    stream_id,
+                    index,
+                    content,
+                });
+            }
+        }
+
+        if progress.complete {
+            outbox.send_event(Event::StreamComplete {
+                src,
+                dst,
+                stream_id,
+            });
+        }
+
+        if let Some(credit) = progress.credit {
+            self.send_routing_message(RoutingMessage {
+                src: dst,
+                dst: src,
+                content: MessageContent::StreamCredit { stream_id, credit },
+            })?;
+        }
+        Ok(())
+    }
+
+    fn handle_stream_credit(
+        &mut self,
+        stream_id: MessageId,
+        granter: Authority<XorName>,
+        credit: u32,
+    ) -> Result<(), RoutingError> {
+        let messages = self
+            .outgoing_streams
+            .add_credit(stream_id, &granter, credit);
+        self.send_stream_parts(messages)
+    }
+
+    // Sends the given parts of a stream, encrypted like the user messages.
+    fn send_stream_parts(&mut self, messages: Vec<RoutingMessage>) -> Result<(), RoutingError> {
+        for msg in messages {
+            self.send_user_message_parts(msg.src, msg.dst, &[msg.content], None)?;
+        }
+        Ok(())
+    }
+
     fn handle_merge(&mut self, digest: Digest256) -> Result<(), RoutingError> {
         self.vote_for_event(AccumulatingEvent::NeighbourMerge(digest));
         Ok(())
@@ -880,31 +1905,202 @@ impl Elder {
         Ok(())
     }
 
+    // Votes for the change to our elders leading to `info`, signing `info` itself.
+    fn vote_for_elders_change(
+        &mut self,
+        added: BTreeSet<PublicId>,
+        removed: BTreeSet<PublicId>,
+        info: EldersInfo,
+    ) -> Result<(), RoutingError> {
+        let signature_payload = SectionInfoSigPayload::new(&info, &self.full_id)?;
+        let event = AccumulatingEvent::EldersChanged {
+            added,
+            removed,
+            version: *info.version(),
+        };
+        self.vote_for_network_event(event.into_network_event_with(Some(signature_payload)));
+        Ok(())
+    }
+
     fn vote_for_network_event(&mut self, event: NetworkEvent) {
         trace!("{} Vote for Event {:?}", self, event);
         self.parsec_map.vote_for(event, &self.log_ident())
     }
 
+    fn vote_for_many(&mut self, events: Vec<NetworkEvent>) {
+        trace!("{} Vote for Events {:?}", self, events);
+        self.parsec_map.vote_for_many(events, &self.log_ident())
+    }
+
     // ----- Send Functions -----------------------------------------------------------------------
-    fn send_user_message(
+    fn relay_client_message(
         &mut self,
-        src: Authority<XorName>,
+        client_id: PublicId,
         dst: Authority<XorName>,
         content: Vec<u8>,
     ) -> Result<(), RoutingError> {
+        let client = RelayedClientInfo {
+            client_id,
+            proxy: *self.name(),
+            section_version: *self.chain.our_info().version(),
+        };
+
         self.send_routing_message(RoutingMessage {
-            src,
+            src: Authority::Node(*self.name()),
             dst,
-            content: MessageContent::UserMessage(content),
+            content: MessageContent::RelayedClientMessage {
+                client,
+                content,
+                msg_id: MessageId::new(),
+            },
         })
     }
 
+    // Verifies a signed message of a client connected to us and has our authoriser check the public
+    // id the client signed it with, which we then identify the client with. Relays the message
+    // into the network on the client's behalf if it is a request.
+    fn handle_client_direct_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        msg: SignedDirectMessage,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        let (msg, client_id, _) = msg.open(self.id())?;
+
+        match msg {
+            DirectMessage::ClientRequest {
+                ref proxy, nonce, ..
+            }
+            | DirectMessage::ClientIdentify { ref proxy, nonce } => {
+                self.check_client_request(client_id, proxy, nonce)?
+            }
+            _ => return Err(RoutingError::InvalidMessage),
+        }
+
+        if !self
+            .local_params()
+            .dispatch_table
+            .authorise_client(&peer_addr, &client_id)
+        {
+            debug!(
+                "{} - Client {} at {} not authorised - disconnecting.",
+                self, client_id, peer_addr
+            );
+            self.disconnect_client(peer_addr, outbox);
+            return Ok(());
+        }
+        self.peer_map.identify_client(peer_addr, client_id);
+
+        let dispatch = self
+            .local_params()
+            .dispatch_table
+            .dispatch_direct_message(&DirectMessageView::new(&msg, &client_id));
+        if dispatch == Dispatch::Consume {
+            trace!(
+                "{} {:?} from {} consumed by a handler.",
+                self,
+                msg.kind(),
+                client_id
+            );
+            return Ok(());
+        }
+
+        match msg {
+            DirectMessage::ClientRequest { dst, content, .. } => {
+                self.relay_client_message(client_id, dst, content)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Returns whether to pass on the messages of the client at `peer_addr` which aren't signed,
+    // having our authoriser check the public id the client identified itself with. Disconnects
+    // from the client if it isn't authorised.
+    fn authorise_client_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        outbox: &mut dyn EventBox,
+    ) -> bool {
+        let dispatch_table = &self.local_params().dispatch_table;
+        let authorised = match self.peer_map.client_id(&peer_addr) {
+            Some(client_id) => dispatch_table.authorise_client(&peer_addr, client_id),
+            None => {
+                let has_authoriser = dispatch_table.has_client_authoriser();
+                if has_authoriser {
+                    debug!(
+                        "{} - Dropping message of client {} which hasn't identified itself.",
+                        self, peer_addr
+                    );
+                }
+                return !has_authoriser;
+            }
+        };
+
+        if !authorised {
+            debug!(
+                "{} - Client at {} not authorised - disconnecting.",
+                self, peer_addr
+            );
+            self.disconnect_client(peer_addr, outbox);
+        }
+        authorised
+    }
+
+    // Rejects a `ClientRequest` or `ClientIdentify` meant for another proxy, or whose nonce is
+    // outside the window around our clock or not above the last one of the client, i.e. a replayed
+    // message.
+    fn check_client_request(
+        &mut self,
+        client_id: PublicId,
+        proxy: &ConnectionInfo,
+        nonce: u64,
+    ) -> Result<(), RoutingError> {
+        if *proxy != self.our_connection_info()? {
+            debug!(
+                "{} - Message of client {} meant for proxy {}.",
+                self, client_id, proxy.peer_addr
+            );
+            return Err(RoutingError::FilterCheckFailed);
+        }
+
+        let now = client_nonce_clock();
+        let last_nonce = self.client_nonces.get(&client_id).cloned().unwrap_or(0);
+        if nonce <= last_nonce
+            || nonce.saturating_add(CLIENT_NONCE_WINDOW_MS) < now
+            || nonce > now.saturating_add(CLIENT_NONCE_WINDOW_MS)
+        {
+            debug!(
+                "{} - Message of client {} with stale nonce {}.",
+                self, client_id, nonce
+            );
+            return Err(RoutingError::FilterCheckFailed);
+        }
+
+        let _ = self.client_nonces.insert(client_id, nonce);
+        Ok(())
+    }
+
     // Send signed_msg on route. Hop is the name of the peer we received this from, or our name if
-    // we are the first sender or the proxy for a client or joining node.
+    // we are the first sender or the proxy for a client or joining node. The recipients may relay
+    // the message `ttl` more times.
     fn send_signed_message(
         &mut self,
         signed_msg: &mut SignedRoutingMessage,
+        ttl: u8,
     ) -> Result<(), RoutingError> {
+        let (targets, dg_size) = self.next_hops(signed_msg, ttl)?;
+        let message = Message::Hop(HopMessage::with_ttl(signed_msg.clone(), ttl)?);
+        self.send_message_to_targets(&targets, dg_size, message);
+        Ok(())
+    }
+
+    // Returns the peers to send `signed_msg` to next, leaving out those it was already sent to,
+    // and marks it as seen so we don't handle it again if someone else sends it to us.
+    fn next_hops(
+        &mut self,
+        signed_msg: &SignedRoutingMessage,
+        ttl: u8,
+    ) -> Result<(Vec<PublicId>, usize), RoutingError> {
         let dst = signed_msg.routing_message().dst;
 
         // If the message is to a single node and we have the connection info for this node, don't
@@ -927,6 +2123,16 @@ impl Elder {
             signed_msg,
             target_pub_ids
         );
+        if let Some(trace_id) = signed_msg.trace_id() {
+            info!(
+                "{} - Trace {:?}: sending {:?} via {:?} with TTL {}.",
+                self,
+                trace_id,
+                signed_msg.routing_message(),
+                target_pub_ids,
+                ttl
+            );
+        }
 
         let targets: Vec<_> = target_pub_ids
             .into_iter()
@@ -937,15 +2143,11 @@ impl Elder {
             })
             .collect();
 
-        let message = self.to_hop_message(signed_msg.clone())?;
-        self.send_message_to_targets(&targets, dg_size, message);
-
         // we've seen this message - don't handle it again if someone else sends it to us
-        let _ = self
-            .routing_msg_filter
-            .filter_incoming(signed_msg.routing_message());
+        self.routing_msg_filter
+            .mark_incoming(signed_msg.routing_message());
 
-        Ok(())
+        Ok((targets, dg_size))
     }
 
     /// Vote for a user-defined event.
@@ -953,12 +2155,22 @@ impl Elder {
         self.vote_for_event(AccumulatingEvent::User(event));
     }
 
+    /// Vote for a user-defined event in the given namespace.
+    pub fn vote_for_namespaced_user_event(&mut self, namespace: String, payload: Vec<u8>) {
+        self.vote_for_event(AccumulatingEvent::NamespacedUser(NamespacedUserPayload {
+            namespace,
+            payload,
+        }));
+    }
+
     /// Returns the set of peers that are responsible for collecting signatures to verify a message;
     /// this may contain us or only other nodes. If our signature is not required, this returns
     /// `None`.
     fn get_signature_targets(&self, src: &Authority<XorName>) -> Option<BTreeSet<XorName>> {
         let list: Vec<XorName> = match *src {
-            Authority::Section(_) => self
+            // A close group can span sections, but its messages are signed with the key of the
+            // section closest to its name, whose shares only that section's elders hold.
+            Authority::Section(_) | Authority::CloseGroup { .. } => self
                 .chain
                 .our_elders()
                 .map(PublicId::name)
@@ -1023,6 +2235,57 @@ impl Elder {
 
     // Check whether we are connected to any elders. If this node loses all elder connections,
     // it must be restarted.
+    // Records a lost connection and returns whether it is part of a mass disconnection, that is
+    // whether within `MASS_DISCONNECT_WINDOW` we lost at least as many connections as we have left.
+    fn record_peer_loss(&mut self) -> bool {
+        while self
+            .recent_losses
+            .front()
+            .map_or(false, |time| time.elapsed() > MASS_DISCONNECT_WINDOW)
+        {
+            let _ = self.recent_losses.pop_front();
+        }
+        self.recent_losses.push_back(Instant::now());
+
+        self.recent_losses.len() > 1
+            && self.recent_losses.len() >= self.peer_map.connected_ids().count()
+    }
+
+    // Reconnect to the lost elder after a random delay instead of immediately, so that a mass
+    // disconnection does not result in dialing everyone at once. Elders of our own section are
+    // reconnected to before the elders of our neighbours.
+    fn schedule_reconnect(&mut self, pub_id: PublicId) {
+        let jitter = utils::rand_index(RECONNECT_JITTER_MS as usize) as u64;
+        let delay = if self.chain.is_peer_our_elder(&pub_id) {
+            jitter
+        } else {
+            RECONNECT_JITTER_MS + jitter
+        };
+
+        debug!(
+            "{} - Reconnecting to {} in {} ms due to mass disconnection.",
+            self, pub_id, delay
+        );
+
+        let token = self.timer.schedule(Duration::from_millis(delay));
+        let _ = self.delayed_reconnects.insert(token, pub_id);
+    }
+
+    fn reconnect(&mut self, pub_id: PublicId, outbox: &mut dyn EventBox) {
+        debug!(
+            "{} - Sending connection request to {} due to lost peer.",
+            self, pub_id
+        );
+
+        let our_name = *self.name();
+        let _ = self.send_connection_request(
+            pub_id,
+            Authority::Node(our_name),
+            Authority::Node(*pub_id.name()),
+            outbox,
+        );
+    }
+
     fn check_elder_connections(&mut self, outbox: &mut dyn EventBox) -> bool {
         if self
             .peer_map
@@ -1058,99 +2321,469 @@ impl Elder {
         }
     }
 
+    fn check_signed_message_freshness(
+        &self,
+        msg: &SignedRoutingMessage,
+    ) -> Result<(), RoutingError> {
+        // A replayed message is either still remembered by our filter, or too old to be accepted.
+        let max_skew = self.local_params().message_filter_limits.incoming_expiry / 2;
+        if msg.check_fresh(&self.chain, max_skew) {
+            Ok(())
+        } else {
+            debug!("{} Obsolete {:?} - dropping.", self, msg);
+            Err(RoutingError::ObsoleteMessage)
+        }
+    }
+
     fn our_prefix(&self) -> &Prefix<XorName> {
         self.chain.our_prefix()
     }
 
-    fn remove_member(&mut self, pub_id: PublicId, disconnect_time: DisconnectTime) {
-        self.chain.remove_member(&pub_id);
-
-        match disconnect_time {
-            DisconnectTime::Now => {
-                self.disconnect(&pub_id);
-            }
-            DisconnectTime::Later => {
-                let token = self.timer.schedule(RELOCATE_DISCONNECT_TIMEOUT);
-                let _ = self.delayed_disconnects.insert(token, pub_id);
-            }
+    // Flushes our outstanding messages once our section voted us offline or we gave up waiting
+    // for it, then terminates once they are all sent and acknowledged, or the timeout expires
+    // again.
+    fn finish_leave(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        if self.leave_draining {
+            debug!(
+                "{} - Timeout when waiting for our messages to be sent.",
+                self
+            );
+            return self.terminate_leave(outbox);
+        }
+
+        info!("{} - Leaving the network.", self);
+
+        // We are leaving anyway, so any transition the queued messages would cause is moot.
+        let _ = self.handle_routing_messages(outbox);
+
+        self.leave_draining = true;
+        self.leave_timer_token = Some(self.timer.schedule(self.timeouts().leave));
+        self.check_leave_drained(outbox)
+    }
+
+    // Terminates if we are leaving and none of our messages is still being sent or waiting for
+    // its acknowledgement.
+    fn check_leave_drained(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        if self.leave_draining
+            && self.pending_deliveries.is_empty()
+            && !self.network_service.has_pending_sends()
+        {
+            self.terminate_leave(outbox)
+        } else {
+            Transition::Stay
+        }
+    }
+
+    fn terminate_leave(&mut self, outbox: &mut dyn EventBox) -> Transition {
+        info!("{} - Left the network.", self);
+        self.leave_timer_token = None;
+        outbox.send_event(Event::Terminated);
+        Transition::Terminate
+    }
+
+    fn handle_leave(&mut self, pub_id: PublicId) {
+        if !self.chain.is_peer_our_member(&pub_id) {
+            debug!("{} - Ignoring Leave from non-member {}.", self, pub_id);
+            return;
+        }
+
+        info!("{} - {} is leaving the network.", self, pub_id);
+        self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+    }
+
+    fn handle_step_down_request(&mut self, pub_id: PublicId) {
+        if !self.chain.is_peer_our_elder(&pub_id) {
+            debug!("{} - Ignoring StepDown from non-elder {}.", self, pub_id);
+            return;
+        }
+
+        info!("{} - {} asked to step down.", self, pub_id);
+        self.vote_for_event(AccumulatingEvent::StepDown(pub_id));
+    }
+
+    fn handle_joined_already(&mut self, pub_id: PublicId) {
+        if !self.chain.is_peer_our_member(&pub_id) {
+            debug!(
+                "{} - Ignoring JoinedAlready from non-member {}.",
+                self, pub_id
+            );
+            return;
+        }
+
+        let _ = self.joined_already.insert(pub_id);
+    }
+
+    // Returns the number of our elders which are not stepping down.
+    fn remaining_elders_count(&self) -> usize {
+        self.chain
+            .our_elders()
+            .filter(|pub_id| !self.stepping_down.contains(pub_id))
+            .count()
+    }
+
+    fn remove_member(&mut self, pub_id: PublicId, disconnect_time: DisconnectTime) {
+        self.chain.remove_member(&pub_id);
+
+        match disconnect_time {
+            DisconnectTime::Now => {
+                self.disconnect(&pub_id);
+            }
+            DisconnectTime::Later => {
+                let token = self.timer.schedule(self.timeouts().relocate_disconnect);
+                let _ = self.delayed_disconnects.insert(token, pub_id);
+            }
+        }
+
+        if self.chain.is_peer_our_elder(&pub_id) {
+            self.vote_for_event(AccumulatingEvent::RemoveElder(pub_id));
+        }
+    }
+
+    fn send_relocate(
+        &mut self,
+        details: RelocateDetails,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        let pub_id = details.pub_id;
+        let destination = details.destination;
+
+        self.send_routing_message(RoutingMessage {
+            src: Authority::Section(self.our_prefix().name()),
+            dst: Authority::Node(*pub_id.name()),
+            content: MessageContent::Relocate(details),
+        })?;
+
+        // Delay the disconnect, to give the peer chance to receive the `Relocate` message.
+        self.remove_member(pub_id, DisconnectTime::Later);
+        outbox.send_event(Event::RelocationStarted {
+            name: *pub_id.name(),
+            destination,
+        });
+
+        Ok(())
+    }
+}
+
+impl Base for Elder {
+    fn network_service(&self) -> &NetworkService {
+        &self.network_service
+    }
+
+    fn authorise_client_connection(&self, peer_addr: &SocketAddr) -> ClientAuthorisation {
+        self.local_params()
+            .dispatch_table
+            .authorise_client_connection(peer_addr)
+    }
+
+    fn network_service_mut(&mut self) -> &mut NetworkService {
+        &mut self.network_service
+    }
+
+    fn full_id(&self) -> &FullId {
+        &self.full_id
+    }
+
+    fn in_authority(&self, auth: &Authority<XorName>) -> bool {
+        self.chain.in_authority(auth)
+    }
+
+    fn close_group(&self, name: XorName, count: usize) -> Option<Vec<XorName>> {
+        let conn_peers = self.connected_peers();
+        self.chain.closest_names(&name, count, &conn_peers)
+    }
+
+    fn min_section_size(&self) -> usize {
+        self.chain.min_sec_size()
+    }
+
+    fn peer_map(&self) -> &PeerMap {
+        &self.peer_map
+    }
+
+    fn peer_map_mut(&mut self) -> &mut PeerMap {
+        &mut self.peer_map
+    }
+
+    fn timer(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    fn handle_send_message(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        trace_id: Option<MessageId>,
+    ) -> Result<(), InterfaceError> {
+        match self.send_user_message(src, dst, content, None, trace_id) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
+        }
+    }
+
+    fn handle_send_message_to_many(
+        &mut self,
+        src: Authority<XorName>,
+        dsts: Vec<Authority<XorName>>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        match self.send_user_message_to_many(src, &dsts, content) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
+        }
+    }
+
+    fn handle_send_message_with_ack(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        match self.send_user_message(src, dst, content.clone(), Some(msg_id), None) {
+            Err(RoutingError::Interface(err)) => return Err(err),
+            Err(_) | Ok(()) => (),
+        }
+
+        let token = self
+            .timer
+            .schedule(self.local_params().delivery_ack_timeout);
+        let pending = PendingMessage {
+            src,
+            dst,
+            content,
+            id: msg_id,
+        };
+        let _ = self.pending_deliveries.insert(token, pending);
+        Ok(())
+    }
+
+    fn handle_send_stream(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        stream_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        let messages = self.outgoing_streams.start(stream_id, src, dst, content);
+        match self.send_stream_parts(messages) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
+        }
+    }
+
+    fn handle_send_user_request(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        token: MessageId,
+    ) -> Result<(), InterfaceError> {
+        let request = MessageContent::UserRequest {
+            token,
+            content: content.clone(),
+        };
+        match self.send_user_message_parts(src, dst, &[request], None) {
+            Ok(()) => (),
+            Err(RoutingError::Interface(err)) => return Err(err),
+            Err(err) => return Err(InterfaceError::SendFailed(Box::new(err))),
+        }
+
+        let timer_token = self.timer.schedule(self.local_params().request_timeout);
+        let pending = PendingMessage {
+            src,
+            dst,
+            content,
+            id: token,
+        };
+        let _ = self.pending_requests.insert(timer_token, pending);
+        Ok(())
+    }
+
+    fn handle_send_user_response(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+        token: MessageId,
+    ) -> Result<(), InterfaceError> {
+        let response = MessageContent::UserResponse { token, content };
+        match self.send_user_message_parts(src, dst, &[response], None) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
         }
+    }
 
-        if self.chain.is_peer_our_elder(&pub_id) {
-            self.vote_for_event(AccumulatingEvent::RemoveElder(pub_id));
+    fn handle_relay_client_message(
+        &mut self,
+        client_id: PublicId,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Result<(), InterfaceError> {
+        match self.relay_client_message(client_id, dst, content) {
+            Err(RoutingError::Interface(err)) => Err(err),
+            Err(_) | Ok(()) => Ok(()),
         }
     }
-}
 
-impl Base for Elder {
-    fn network_service(&self) -> &NetworkService {
-        &self.network_service
+    fn handle_request_proof_chain(&mut self, from_version: u64) -> Result<(), InterfaceError> {
+        self.send_proof_chain_request(from_version)
     }
 
-    fn network_service_mut(&mut self) -> &mut NetworkService {
-        &mut self.network_service
-    }
+    fn handle_initiate_leave(&mut self) -> Result<(), InterfaceError> {
+        if self.leave_timer_token.is_some() {
+            return Ok(());
+        }
 
-    fn full_id(&self) -> &FullId {
-        &self.full_id
-    }
+        info!("{} - Initiating leave.", self);
 
-    fn in_authority(&self, auth: &Authority<XorName>) -> bool {
-        self.chain.in_authority(auth)
-    }
+        let our_id = *self.id();
+        let other_elders: Vec<_> = self
+            .chain
+            .our_elders()
+            .filter(|pub_id| **pub_id != our_id)
+            .copied()
+            .collect();
+        for pub_id in &other_elders {
+            self.send_direct_message(pub_id, DirectMessage::Leave);
+        }
 
-    fn close_group(&self, name: XorName, count: usize) -> Option<Vec<XorName>> {
-        let conn_peers = self.connected_peers();
-        self.chain.closest_names(&name, count, &conn_peers)
+        self.vote_for_event(AccumulatingEvent::Offline(our_id));
+        self.leave_timer_token = Some(self.timer.schedule(self.timeouts().leave));
+        Ok(())
     }
 
-    fn min_section_size(&self) -> usize {
-        self.chain.min_sec_size()
-    }
+    fn handle_step_down(&mut self) -> Result<(), InterfaceError> {
+        // Removing an elder from a section at its minimum size would require a merge.
+        if self.remaining_elders_count() <= self.min_section_size() {
+            warn!(
+                "{} - Cannot step down - not enough elders to spare one.",
+                self
+            );
+            return Err(InterfaceError::InvalidState);
+        }
 
-    fn peer_map(&self) -> &PeerMap {
-        &self.peer_map
-    }
+        info!("{} - Requesting to step down.", self);
 
-    fn peer_map_mut(&mut self) -> &mut PeerMap {
-        &mut self.peer_map
-    }
+        let our_id = *self.id();
+        let other_elders: Vec<_> = self
+            .chain
+            .our_elders()
+            .filter(|pub_id| **pub_id != our_id)
+            .copied()
+            .collect();
+        for pub_id in &other_elders {
+            self.send_direct_message(pub_id, DirectMessage::StepDown);
+        }
 
-    fn timer(&mut self) -> &mut Timer {
-        &mut self.timer
+        self.vote_for_event(AccumulatingEvent::StepDown(our_id));
+        Ok(())
     }
 
-    fn handle_send_message(
+    fn handle_reconnect_to_neighbours(
         &mut self,
-        src: Authority<XorName>,
-        dst: Authority<XorName>,
-        content: Vec<u8>,
+        outbox: &mut dyn EventBox,
     ) -> Result<(), InterfaceError> {
-        match self.send_user_message(src, dst, content) {
-            Err(RoutingError::Interface(err)) => Err(err),
-            Err(_) | Ok(()) => Ok(()),
+        let disconnected: Vec<_> = self
+            .chain
+            .neighbour_elders()
+            .filter(|pub_id| !self.peer_map().has(pub_id))
+            .copied()
+            .collect();
+        info!(
+            "{} - Reconnecting to {} neighbour elders.",
+            self,
+            disconnected.len()
+        );
+
+        let src = Authority::Node(*self.name());
+        for pub_id in disconnected {
+            let dst = Authority::Node(*pub_id.name());
+            let _ = self.send_connection_request(pub_id, src, dst, outbox);
         }
+        Ok(())
+    }
+
+    fn handle_prune_parsec(&mut self) -> Result<(), InterfaceError> {
+        self.parsec_map.request_pruning();
+        self.maintain_parsec();
+        Ok(())
     }
 
     fn handle_timeout(&mut self, token: u64, outbox: &mut dyn EventBox) -> Transition {
         if self.tick_timer_token == token {
             // TODO: we no longer need tick for any internal purposes. Verify it is not needed by
             // the upper layers and remove it.
-            self.tick_timer_token = self.timer.schedule(TICK_TIMEOUT);
+            self.tick_timer_token = self.timer.schedule(self.timeouts().tick);
+            let _ = self.chain.purge_expired_votes(VOTE_TTL);
+            let now = client_nonce_clock();
+            self.client_nonces
+                .retain(|_, nonce| nonce.saturating_add(CLIENT_NONCE_WINDOW_MS) >= now);
+            for incomplete in self.reassembler.remove_expired() {
+                debug!("{} - Dropping incomplete message {:?}.", self, incomplete);
+                outbox.send_event(Event::MessageIncomplete {
+                    src: incomplete.src,
+                    dst: incomplete.dst,
+                    received_parts: incomplete.received_parts,
+                    part_count: incomplete.part_count,
+                });
+            }
+            for (src, dst, stream_id) in self.outgoing_streams.remove_expired() {
+                debug!(
+                    "{} - Giving up stream {:?}: no credit granted.",
+                    self, stream_id
+                );
+                outbox.send_event(Event::StreamFailed {
+                    src,
+                    dst,
+                    stream_id,
+                });
+            }
+            for (src, dst, stream_id) in self.incoming_streams.remove_expired() {
+                debug!(
+                    "{} - Dropping stalled stream {:?} from {:?} to {:?}.",
+                    self, stream_id, src, dst
+                );
+            }
             outbox.send_event(Event::TimerTicked);
         } else if self.gossip_timer_token == token {
-            self.gossip_timer_token = self.timer.schedule(GOSSIP_TIMEOUT);
+            self.gossip_timer_token = self.timer.schedule(self.timeouts().gossip);
 
             // If we're the only node then invoke parsec_poll directly
             if self.chain.our_info().members().len() == 1 {
                 let _ = self.parsec_poll(outbox);
             }
 
+            self.vote_for_pending_acks();
             self.send_parsec_gossip(None);
             self.maintain_parsec();
         } else if let Some(pub_id) = self.delayed_disconnects.remove(&token) {
             if !self.chain.is_peer_elder(&pub_id) && !self.chain.is_peer_our_member(&pub_id) {
                 self.disconnect(&pub_id);
             }
+            // The peer had its chance to receive the `Relocate` message, so the section can start
+            // the next relocation.
+            self.vote_for_event(AccumulatingEvent::RelocationComplete(pub_id));
+        } else if let Some(pub_id) = self.delayed_reconnects.remove(&token) {
+            if self.chain.is_peer_elder(&pub_id) {
+                self.reconnect(pub_id, outbox);
+            }
+        } else if let Some(pub_id) = self.delayed_offline_votes.remove(&token) {
+            if self.chain.is_peer_our_member(&pub_id) && !self.peer_map.has(&pub_id) {
+                self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+            }
+        } else if let Some(PendingMessage { id, .. }) = self.pending_deliveries.remove(&token) {
+            debug!("{} - Message {:?} not acknowledged in time.", self, id);
+            outbox.send_event(Event::MessageDeliveryFailed(id));
+            return self.check_leave_drained(outbox);
+        } else if let Some(PendingMessage { id, .. }) = self.pending_requests.remove(&token) {
+            debug!("{} - Request {:?} not responded to in time.", self, id);
+            outbox.send_event(Event::RequestTimedOut(id));
+        } else if self.leave_timer_token == Some(token) {
+            if !self.leave_draining {
+                debug!("{} - Timeout when waiting to be voted offline.", self);
+            }
+            return self.finish_leave(outbox);
         }
 
         Transition::Stay
@@ -1171,34 +2804,70 @@ impl Base for Elder {
     fn handle_peer_lost(&mut self, pub_id: PublicId, outbox: &mut dyn EventBox) -> Transition {
         debug!("{} - Lost peer {}", self, pub_id);
 
+        let _ = self.join_challenges.remove(&pub_id);
+        let _ = self.resource_challenges.remove(&pub_id);
+        let _ = self.joined_already.remove(&pub_id);
+
         if !self.check_elder_connections(outbox) {
             return Transition::Terminate;
         }
 
-        if self.chain.is_peer_our_member(&pub_id) {
-            self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+        if self.chain.is_peer_elder(&pub_id) {
+            if self.chain.is_peer_our_member(&pub_id) {
+                self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+            }
+        } else if self.chain.is_peer_our_member(&pub_id) {
+            // Give the adult a chance to restart and rejoin before removing it.
+            let token = self.timer.schedule(self.timeouts().rejoin_grace_period);
+            let _ = self.delayed_offline_votes.insert(token, pub_id);
         }
 
-        if self.chain.is_peer_elder(&pub_id) {
-            debug!(
-                "{} - Sending connection request to {} due to lost peer.",
-                self, pub_id
-            );
+        let mass_disconnect = self.record_peer_loss();
 
-            let our_name = *self.name();
-            let _ = self.send_connection_request(
-                pub_id,
-                Authority::Node(our_name),
-                Authority::Node(*pub_id.name()),
-                outbox,
-            );
+        if self.chain.is_peer_elder(&pub_id) {
+            if mass_disconnect {
+                self.schedule_reconnect(pub_id);
+            } else {
+                self.reconnect(pub_id, outbox);
+            }
         }
 
         Transition::Stay
     }
 
     fn finish_handle_network_event(&mut self, outbox: &mut dyn EventBox) -> Transition {
-        self.handle_routing_messages(outbox)
+        match self.handle_routing_messages(outbox) {
+            Transition::Stay => self.check_leave_drained(outbox),
+            transition => transition,
+        }
+    }
+
+    fn handle_client_message(
+        &mut self,
+        peer_addr: SocketAddr,
+        msg: NetworkBytes,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        // Signed messages identify the client or are requests relayed by us, anything else is
+        // passed on to the user if the client is authorised. Clients never compress their
+        // messages.
+        if let Ok(Message::Direct(signed_msg)) = from_network_bytes(msg.clone(), false) {
+            if let Err(error) = self.handle_client_direct_message(peer_addr, signed_msg, outbox) {
+                debug!(
+                    "{} - Dropping message of client {}: {:?}",
+                    self, peer_addr, error
+                );
+            }
+            return Transition::Stay;
+        }
+
+        if !self.authorise_client_message(peer_addr, outbox) {
+            return Transition::Stay;
+        }
+
+        let client_event = ClientEvent::NewMessageFromClient { peer_addr, msg };
+        outbox.send_event(client_event.into());
+        Transition::Stay
     }
 
     // Deconstruct a `DirectMessage` and handle or forward as appropriate.
@@ -1209,9 +2878,27 @@ impl Base for Elder {
         outbox: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
         use crate::messages::DirectMessage::*;
+
+        let dispatch = self
+            .local_params()
+            .dispatch_table
+            .dispatch_direct_message(&DirectMessageView::new(&msg, &pub_id));
+        if dispatch == Dispatch::Consume {
+            trace!(
+                "{} {:?} from {} consumed by a handler.",
+                self,
+                msg.kind(),
+                pub_id
+            );
+            return Ok(Transition::Stay);
+        }
+
         match msg {
             MessageSignature(msg) => self.handle_message_signature(msg, pub_id)?,
-            BootstrapRequest(name) => {
+            BootstrapRequest(name, capabilities) => {
+                if !self.negotiate_capabilities(&pub_id, capabilities) {
+                    return Ok(Transition::Stay);
+                }
                 if let Err(error) = self.handle_bootstrap_request(pub_id, name) {
                     warn!(
                         "{} Invalid BootstrapRequest received from {} ({:?}).",
@@ -1220,7 +2907,7 @@ impl Base for Elder {
                 }
             }
             ConnectionResponse => self.handle_connection_response(pub_id, outbox),
-            JoinRequest(payload) => self.handle_join_request(pub_id, payload),
+            JoinRequest(payload) => self.handle_join_request(pub_id, payload, outbox),
             ParsecPoke(version) => self.handle_parsec_poke(version, pub_id),
             ParsecRequest(version, par_request) => {
                 return self.handle_parsec_request(version, par_request, pub_id, outbox);
@@ -1228,7 +2915,24 @@ impl Base for Elder {
             ParsecResponse(version, par_response) => {
                 return self.handle_parsec_response(version, par_response, pub_id, outbox);
             }
-            BootstrapResponse(_) => {
+            ProofChainRequest(version) => self.handle_proof_chain_request(version, pub_id),
+            ProofChainResponse(proof_chain) => {
+                self.handle_proof_chain_response(proof_chain, pub_id, outbox)
+            }
+            ResourceProofResponse { data, solution } => {
+                self.handle_resource_proof_response(pub_id, data, solution)
+            }
+            Leave => self.handle_leave(pub_id),
+            StepDown => self.handle_step_down_request(pub_id),
+            JoinedAlready => self.handle_joined_already(pub_id),
+            JoinChallengeResponse(signature) => {
+                self.handle_join_challenge_response(pub_id, signature)
+            }
+            BootstrapResponse(_)
+            | ResourceChallenge { .. }
+            | JoinChallenge(_)
+            | ClientRequest { .. }
+            | ClientIdentify { .. } => {
                 debug!("{} Unhandled direct message: {:?}", self, msg);
             }
         }
@@ -1240,8 +2944,8 @@ impl Base for Elder {
         msg: HopMessage,
         _: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
-        let HopMessage { content, .. } = msg;
-        self.handle_signed_message(content)
+        let HopMessage { content, ttl } = msg;
+        self.handle_signed_message(content, ttl)
             .map(|()| Transition::Stay)
     }
 
@@ -1249,6 +2953,14 @@ impl Base for Elder {
     // these nodes a signature or tries to accumulate signatures for this message (on success, the
     // accumulator handles or forwards the message).
     fn send_routing_message(&mut self, routing_msg: RoutingMessage) -> Result<(), RoutingError> {
+        self.send_traced_routing_message(routing_msg, None)
+    }
+
+    fn send_traced_routing_message(
+        &mut self,
+        routing_msg: RoutingMessage,
+        trace_id: Option<MessageId>,
+    ) -> Result<(), RoutingError> {
         if !self.in_authority(&routing_msg.src) {
             log_or_panic!(
                 LogLevel::Error,
@@ -1259,20 +2971,24 @@ impl Base for Elder {
             return Ok(());
         }
 
+        self.record_sent_user_message(&routing_msg)?;
+
         // If the source is single, we don't even need to send signatures, so let's cut this short
         if !routing_msg.src.is_multiple() {
             let mut msg = SignedRoutingMessage::single_source(routing_msg, &self.full_id)?;
+            msg.set_trace_id(trace_id);
             if self.in_authority(&msg.routing_message().dst) {
-                self.handle_signed_message(msg)?;
+                self.handle_signed_message(msg, MAX_HOPS)?;
             } else {
-                self.send_signed_message(&mut msg)?;
+                self.send_signed_message(&mut msg, MAX_HOPS)?;
             }
             return Ok(());
         }
 
         let proof = self.chain.prove(&routing_msg.dst);
         let pk_set = self.public_key_set();
-        let signed_msg = SignedRoutingMessage::new(routing_msg, &self.full_id, pk_set, proof)?;
+        let mut signed_msg = SignedRoutingMessage::new(routing_msg, &self.full_id, pk_set, proof)?;
+        signed_msg.set_trace_id(trace_id);
 
         for target in Iterator::flatten(
             self.get_signature_targets(&signed_msg.routing_message().src)
@@ -1281,9 +2997,9 @@ impl Base for Elder {
             if target == *self.name() {
                 if let Some(mut msg) = self.sig_accumulator.add_proof(signed_msg.clone()) {
                     if self.in_authority(&msg.routing_message().dst) {
-                        self.handle_signed_message(msg)?;
+                        self.handle_signed_message(msg, MAX_HOPS)?;
                     } else {
-                        self.send_signed_message(&mut msg)?;
+                        self.send_signed_message(&mut msg, MAX_HOPS)?;
                     }
                 }
             } else if let Some(&pub_id) = self.peer_map.get_id(&target) {
@@ -1313,14 +3029,14 @@ impl Base for Elder {
 
 #[cfg(feature = "mock_base")]
 impl Elder {
-    pub fn chain(&self) -> &Chain {
-        &self.chain
-    }
-
     pub fn get_timed_out_tokens(&mut self) -> Vec<u64> {
         self.timer.get_timed_out_tokens()
     }
 
+    pub fn next_timer_deadline(&self) -> Option<Instant> {
+        self.timer.next_deadline()
+    }
+
     pub fn set_next_relocation_dst(&mut self, dst: Option<XorName>) {
         self.next_relocation_dst = dst;
     }
@@ -1333,6 +3049,18 @@ impl Elder {
         self.parsec_map.has_unpolled_observations()
     }
 
+    pub fn parsec_metrics(&self) -> parsec::ParsecMetrics {
+        self.parsec_map.metrics()
+    }
+
+    pub fn duplicate_counts(&self) -> &DuplicateCounts {
+        self.routing_msg_filter.duplicate_counts()
+    }
+
+    pub fn accumulator_metrics(&self) -> AccumulatorMetrics {
+        self.chain.accumulator_metrics()
+    }
+
     pub fn is_peer_our_elder(&self, pub_id: &PublicId) -> bool {
         self.chain.is_peer_our_elder(pub_id)
     }
@@ -1384,6 +3112,10 @@ impl Approved for Elder {
         &mut self.parsec_map
     }
 
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
     fn chain_mut(&mut self) -> &mut Chain {
         &mut self.chain
     }
@@ -1396,6 +3128,43 @@ impl Approved for Elder {
         self.pfx_is_successfully_polled
     }
 
+    fn send_signed_messages(
+        &mut self,
+        msgs: Vec<SignedRoutingMessage>,
+    ) -> Result<(), RoutingError> {
+        let mut batches: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut result = Ok(());
+
+        for msg in msgs {
+            self.record_sent_user_message(msg.routing_message())?;
+            if self.in_authority(&msg.routing_message().dst) {
+                if let Err(error) = self.handle_signed_message(msg, MAX_HOPS) {
+                    result = Err(error);
+                }
+                continue;
+            }
+
+            match self.next_hops(&msg, MAX_HOPS) {
+                Ok((targets, dg_size)) => batches
+                    .entry((targets, dg_size))
+                    .or_default()
+                    .push(HopMessage::new(msg)?),
+                Err(error) => result = Err(error),
+            }
+        }
+
+        for ((targets, dg_size), mut hop_msgs) in batches {
+            let message = if hop_msgs.len() == 1 {
+                Message::Hop(hop_msgs.remove(0))
+            } else {
+                Message::Hops(hop_msgs)
+            };
+            self.send_message_to_targets(&targets, dg_size, message);
+        }
+
+        result
+    }
+
     fn handle_add_elder_event(
         &mut self,
         pub_id: PublicId,
@@ -1403,11 +3172,21 @@ impl Approved for Elder {
     ) -> Result<(), RoutingError> {
         info!("{} - handle AddElder: {}.", self, pub_id);
 
-        let to_vote_infos = self.chain.add_elder(pub_id)?;
+        let mut to_vote_infos = self.chain.add_elder(pub_id)?;
 
         self.send_event(Event::NodeAdded(*pub_id.name()), outbox);
         self.print_rt_size();
 
+        // Unless we split, only vote for the change to our elders.
+        if to_vote_infos.len() == 1 && to_vote_infos[0].prefix() == self.our_prefix() {
+            let info = to_vote_infos.remove(0);
+            return self.vote_for_elders_change(
+                iter::once(pub_id).collect(),
+                BTreeSet::new(),
+                info,
+            );
+        }
+
         for info in to_vote_infos {
             self.vote_for_section_info(info)?;
         }
@@ -1419,19 +3198,34 @@ impl Approved for Elder {
         &mut self,
         pub_id: PublicId,
         outbox: &mut dyn EventBox,
-    ) -> Result<(), RoutingError> {
+    ) -> Result<Transition, RoutingError> {
         info!("{} - handle RemoveElder: {}.", self, pub_id);
 
+        let stepping_down = self.stepping_down.remove(&pub_id);
         let self_info = self.chain.remove_elder(pub_id)?;
-        self.vote_for_section_info(self_info)?;
 
-        if self.chain.is_peer_our_member(&pub_id) {
+        if stepping_down && pub_id == *self.id() {
+            // We are no longer a voter: the remaining elders agree on the change without us.
+            info!("{} - Stepping down to adult.", self);
+            return Ok(Transition::Demote);
+        }
+
+        self.vote_for_elders_change(BTreeSet::new(), iter::once(pub_id).collect(), self_info)?;
+
+        if !stepping_down && self.chain.is_peer_our_member(&pub_id) {
             self.vote_for_event(AccumulatingEvent::Offline(pub_id));
         }
 
+        // Replace the removed elder with the members the elder selection now picks, without it as
+        // it may still be a member. Votes for the ones already being added are dropped as
+        // duplicates.
+        for candidate in self.chain.elders_to_promote(Some(&pub_id)) {
+            self.vote_for_event(AccumulatingEvent::AddElder(candidate));
+        }
+
         self.send_event(Event::NodeLost(*pub_id.name()), outbox);
 
-        Ok(())
+        Ok(Transition::Stay)
     }
 
     fn handle_online_event(
@@ -1446,15 +3240,37 @@ impl Approved for Elder {
 
         // TODO: vote for StartDkg and only when that gets consensused, vote for AddElder.
 
-        self.vote_for_event(AccumulatingEvent::AddElder(payload.pub_id));
+        if self.chain.selected_elders().contains(&payload.pub_id) {
+            self.vote_for_event(AccumulatingEvent::AddElder(payload.pub_id));
+        } else {
+            info!("{} - {} joins as a non-elder member.", self, payload.pub_id);
+        }
+
+        // Demote the elders the new member displaces, so that only the selected ones remain.
+        for pub_id in self.chain.elders_to_demote() {
+            self.vote_for_event(AccumulatingEvent::StepDown(pub_id));
+        }
 
         Ok(())
     }
 
-    fn handle_offline_event(&mut self, pub_id: PublicId) -> Result<(), RoutingError> {
+    fn handle_offline_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<Transition, RoutingError> {
         info!("{} - handle Offline: {}.", self, pub_id);
+
+        if pub_id == *self.id() && self.leave_timer_token.is_some() {
+            self.chain.remove_member(&pub_id);
+            if self.leave_draining {
+                return Ok(Transition::Stay);
+            }
+            return Ok(self.finish_leave(outbox));
+        }
+
         self.remove_member(pub_id, DisconnectTime::Now);
-        Ok(())
+        Ok(Transition::Stay)
     }
 
     fn handle_our_merge_event(&mut self) -> Result<(), RoutingError> {
@@ -1491,13 +3307,11 @@ impl Approved for Elder {
         let self_sec_update = elders_info.prefix().matches(self.name());
 
         self.update_neighbour_connections(neighbour_change, outbox);
+        self.update_neighbour_conn_infos();
 
         if self_sec_update {
             // Vote to update our self messages proof
-            self.vote_send_section_info_ack(SendAckMessagePayload {
-                ack_prefix: *elders_info.prefix(),
-                ack_version: *elders_info.version(),
-            });
+            self.vote_send_section_info_ack(*elders_info.prefix(), *elders_info.version());
 
             self.send_neighbour_infos();
         }
@@ -1511,28 +3325,62 @@ impl Approved for Elder {
         &mut self,
         key_info: SectionKeyInfo,
     ) -> Result<(), RoutingError> {
-        self.vote_send_section_info_ack(SendAckMessagePayload {
-            ack_prefix: *key_info.prefix(),
-            ack_version: *key_info.version(),
-        });
+        self.vote_send_section_info_ack(*key_info.prefix(), *key_info.version());
         Ok(())
     }
 
-    fn handle_send_ack_message_event(
+    fn handle_ack_message_event(&mut self, ack_payload: &AckMessagePayload) {
+        self.pending_acks
+            .remove_acked(ack_payload.src_prefix, ack_payload.ack_version);
+    }
+
+    fn handle_send_ack_messages_event(
         &mut self,
-        ack_payload: SendAckMessagePayload,
+        ack_payload: SendAckMessagesPayload,
     ) -> Result<(), RoutingError> {
-        let src = Authority::Section(self.our_prefix().name());
-        let dst = Authority::Section(ack_payload.ack_prefix.name());
-        let content = MessageContent::AckMessage {
-            src_prefix: *self.our_prefix(),
-            ack_version: ack_payload.ack_version,
-        };
+        self.pending_send_acks.remove_batch(&ack_payload.acks);
+
+        let mut result = Ok(());
+        for (ack_prefix, ack_version) in ack_payload.acks {
+            let src = Authority::Section(self.our_prefix().name());
+            let dst = Authority::Section(ack_prefix.name());
+            let content = MessageContent::AckMessage {
+                src_prefix: *self.our_prefix(),
+                ack_version,
+            };
+
+            if let Err(error) = self.send_routing_message(RoutingMessage { src, dst, content }) {
+                result = Err(error);
+            }
+        }
 
-        self.send_routing_message(RoutingMessage { src, dst, content })
+        // The acks which arrived meanwhile can now be voted for.
+        self.vote_for_pending_send_acks();
+        result
     }
 
-    fn handle_relocate_event(&mut self, payload: RelocateDetails) -> Result<(), RoutingError> {
+    fn handle_conflicting_votes(&mut self, payload: ConflictingVotesPayload) {
+        self.vote_for_event(AccumulatingEvent::ConflictingVotes(payload));
+    }
+
+    fn handle_conflicting_votes_event(
+        &mut self,
+        payload: ConflictingVotesPayload,
+    ) -> Result<(), RoutingError> {
+        info!("{} - handle ConflictingVotes: {:?}.", self, payload);
+
+        // The section agrees the voter is faulty: treat it as gone.
+        if payload.voter != *self.id() && self.chain.is_peer_our_member(&payload.voter) {
+            self.vote_for_event(AccumulatingEvent::Offline(payload.voter));
+        }
+        Ok(())
+    }
+
+    fn handle_relocate_event(
+        &mut self,
+        payload: RelocateDetails,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
         info!("{} - handle Relocate: {:?}.", self, payload);
 
         if self.chain.our_prefix().matches(&payload.destination) {
@@ -1544,15 +3392,55 @@ impl Approved for Elder {
         }
 
         let pub_id = payload.pub_id;
+        if let Some(details) = self.chain.start_relocation(payload) {
+            self.send_relocate(details, outbox)
+        } else {
+            debug!(
+                "{} - Relocation of {} queued - too many relocations in flight.",
+                self, pub_id
+            );
+            Ok(())
+        }
+    }
 
-        self.send_routing_message(RoutingMessage {
-            src: Authority::Section(self.our_prefix().name()),
-            dst: Authority::Node(*payload.pub_id.name()),
-            content: MessageContent::Relocate(payload),
-        })?;
+    fn handle_relocation_complete_event(
+        &mut self,
+        pub_id: PublicId,
+        outbox: &mut dyn EventBox,
+    ) -> Result<(), RoutingError> {
+        info!("{} - handle RelocationComplete: {}.", self, pub_id);
 
-        // Delay the disconnect, to give the peer chance to receive the `Relocate` message.
-        self.remove_member(pub_id, DisconnectTime::Later);
+        if self.chain.is_relocating(&pub_id) {
+            outbox.send_event(Event::RelocationCompleted(*pub_id.name()));
+        }
+
+        if let Some(details) = self.chain.complete_relocation(&pub_id) {
+            self.send_relocate(details, outbox)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_step_down_event(&mut self, pub_id: PublicId) -> Result<(), RoutingError> {
+        info!("{} - handle StepDown: {}.", self, pub_id);
+
+        if self.stepping_down.contains(&pub_id)
+            || !self.chain.is_peer_our_elder(&pub_id)
+            || !self.chain.is_peer_our_member(&pub_id)
+        {
+            return Ok(());
+        }
+
+        if self.remaining_elders_count() <= self.min_section_size() {
+            info!(
+                "{} - Not removing {}: not enough elders to spare one.",
+                self, pub_id
+            );
+            return Ok(());
+        }
+
+        let _ = self.stepping_down.insert(pub_id);
+        self.vote_for_event(AccumulatingEvent::RemoveElder(pub_id));
 
         Ok(())
     }
@@ -1581,6 +3469,14 @@ fn create_first_elders_info(public_id: PublicId) -> Result<EldersInfo, RoutingEr
     })
 }
 
+// How a routing message carrying a user message reached us.
+#[derive(Clone, Copy)]
+struct Delivery {
+    trace_id: Option<MessageId>,
+    hop_count: u8,
+    secure: bool,
+}
+
 enum DisconnectTime {
     Now,
     Later,
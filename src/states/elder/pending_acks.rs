@@ -0,0 +1,138 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{routing_table::Prefix, xor_name::XorName};
+use std::collections::BTreeMap;
+
+/// Section versions waiting to be acknowledged, so that the acknowledgements of a burst of section
+/// updates are voted for together. Only the latest version of each prefix is kept, as
+/// acknowledging it implies acknowledging the older ones.
+///
+/// The versions are either voted for one event each, with `take_unvoted`, or all in one event,
+/// with `take_batch`.
+#[derive(Debug, Default)]
+pub struct PendingAcks {
+    versions: BTreeMap<Prefix<XorName>, u64>,
+    // The versions we already voted for, so we don't vote for the same one twice.
+    voted: BTreeMap<Prefix<XorName>, u64>,
+}
+
+impl PendingAcks {
+    /// Adds the `version` of `prefix` to acknowledge, unless a newer one is already pending.
+    pub fn insert(&mut self, prefix: Prefix<XorName>, version: u64) {
+        let pending = self.versions.entry(prefix).or_insert(version);
+        *pending = (*pending).max(version);
+    }
+
+    /// Returns the pending versions we haven't voted for yet, and marks them as voted for.
+    ///
+    /// They stay pending until they accumulate, so an older version voted for before a newer one
+    /// arrived doesn't need to accumulate: the newer one covers it.
+    pub fn take_unvoted(&mut self) -> Vec<(Prefix<XorName>, u64)> {
+        let unvoted: Vec<_> = self
+            .versions
+            .iter()
+            .filter(|(prefix, version)| self.voted.get(prefix) != Some(version))
+            .map(|(prefix, version)| (*prefix, *version))
+            .collect();
+        self.voted.extend(unvoted.iter().cloned());
+        unvoted
+    }
+
+    /// Returns all the pending versions to vote for in a single event, and marks them as voted for.
+    /// Returns `None` if there are none, or if the batch we voted for last hasn't accumulated yet:
+    /// the versions pending meanwhile wait for the next batch.
+    pub fn take_batch(&mut self) -> Option<BTreeMap<Prefix<XorName>, u64>> {
+        if self.versions.is_empty() || !self.voted.is_empty() {
+            return None;
+        }
+
+        self.voted = self.versions.clone();
+        Some(self.versions.clone())
+    }
+
+    /// Removes the acknowledgements the accumulated `batch` covers, and allows voting for the next
+    /// batch.
+    pub fn remove_batch(&mut self, batch: &BTreeMap<Prefix<XorName>, u64>) {
+        for (prefix, version) in batch {
+            self.remove_acked(*prefix, *version);
+        }
+        self.voted.clear();
+    }
+
+    /// Removes the acknowledgement of `prefix` if the accumulated `version` covers it.
+    pub fn remove_acked(&mut self, prefix: Prefix<XorName>, version: u64) {
+        if self
+            .versions
+            .get(&prefix)
+            .map_or(false, |pending| *pending <= version)
+        {
+            let _ = self.versions.remove(&prefix);
+            let _ = self.voted.remove(&prefix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    #[test]
+    fn votes_latest_version_per_prefix() {
+        let pfx_0 = Prefix::default().pushed(false);
+        let pfx_1 = Prefix::default().pushed(true);
+
+        let mut pending = PendingAcks::default();
+        pending.insert(pfx_0, 2);
+        pending.insert(pfx_0, 1);
+        pending.insert(pfx_1, 3);
+
+        assert_eq!(pending.take_unvoted(), vec![(pfx_0, 2), (pfx_1, 3)]);
+        // Nothing changed since we voted.
+        assert_eq!(pending.take_unvoted(), vec![]);
+
+        // Only the newer version is voted for, not the unchanged one.
+        pending.insert(pfx_0, 4);
+        assert_eq!(pending.take_unvoted(), vec![(pfx_0, 4)]);
+
+        // An older version accumulating doesn't cover the newer one.
+        pending.remove_acked(pfx_0, 2);
+        pending.remove_acked(pfx_1, 3);
+        assert_eq!(pending.take_unvoted(), vec![]);
+
+        pending.remove_acked(pfx_0, 4);
+        pending.insert(pfx_0, 4);
+        // Acked versions received again are voted for again.
+        assert_eq!(pending.take_unvoted(), vec![(pfx_0, 4)]);
+    }
+
+    #[test]
+    fn votes_one_batch_at_a_time() {
+        let pfx_0 = Prefix::default().pushed(false);
+        let pfx_1 = Prefix::default().pushed(true);
+
+        let mut pending = PendingAcks::default();
+        assert_eq!(pending.take_batch(), None);
+
+        pending.insert(pfx_0, 1);
+        let batch = pending.take_batch();
+        assert_eq!(batch, Some(vec![(pfx_0, 1)].into_iter().collect()));
+
+        // The versions arriving while the batch accumulates wait for the next one.
+        pending.insert(pfx_0, 2);
+        pending.insert(pfx_1, 3);
+        assert_eq!(pending.take_batch(), None);
+
+        pending.remove_batch(&unwrap!(batch));
+        assert_eq!(
+            pending.take_batch(),
+            Some(vec![(pfx_0, 2), (pfx_1, 3)].into_iter().collect())
+        );
+    }
+}
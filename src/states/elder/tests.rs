@@ -15,13 +15,15 @@
 
 use super::*;
 use crate::{
+    chain::SectionProofChain,
     messages::DirectMessage,
     mock::Network,
     outbox::{EventBox, EventBuf},
     state_machine::{State, StateMachine, Transition},
     utils::LogIdent,
-    NetworkConfig, NetworkService,
+    BlsPublicKeyShare, NetworkConfig, NetworkService,
 };
+use maidsafe_utilities::serialisation::serialise;
 use std::{iter, net::SocketAddr};
 use unwrap::unwrap;
 
@@ -69,6 +71,13 @@ impl ElderUnderTest {
     }
 
     fn with_min_section_size(min_section_size: usize) -> Self {
+        Self::with_network_params(NetworkParams {
+            min_section_size,
+            ..Default::default()
+        })
+    }
+
+    fn with_network_params(network_params: NetworkParams) -> Self {
         let full_ids = (0..NO_SINGLE_VETO_VOTE_COUNT)
             .map(|_| FullId::new())
             .collect_vec();
@@ -86,6 +95,7 @@ impl ElderUnderTest {
             .collect();
 
         let gen_pfx_info = GenesisPfxInfo {
+            format_version: GenesisPfxInfo::FORMAT_VERSION,
             first_info: elders_info.clone(),
             first_state_serialized: Vec::new(),
             first_ages,
@@ -93,12 +103,12 @@ impl ElderUnderTest {
         };
 
         let full_id = full_ids[0].clone();
-        let machine = make_state_machine(&full_id, &gen_pfx_info, min_section_size, &mut ev_buffer);
+        let machine = make_state_machine(&full_id, &gen_pfx_info, network_params, &mut ev_buffer);
 
         let other_full_ids = full_ids[1..].iter().cloned().collect_vec();
         let other_parsec_map = other_full_ids
             .iter()
-            .map(|full_id| ParsecMap::new(full_id.clone(), &gen_pfx_info))
+            .map(|full_id| ParsecMap::new(full_id.clone(), &gen_pfx_info, Default::default(), None))
             .collect_vec();
 
         let mut elder_test = Self {
@@ -154,11 +164,16 @@ impl ElderUnderTest {
     }
 
     fn accumulate_online(&mut self, pub_id: PublicId) {
+        self.accumulate_online_with_resource_proof(pub_id, true)
+    }
+
+    fn accumulate_online_with_resource_proof(&mut self, pub_id: PublicId, verified: bool) {
         let _ = self.n_vote_for_gossipped(
             ACCUMULATE_VOTE_COUNT,
             iter::once(AccumulatingEvent::Online(OnlinePayload {
                 pub_id,
                 age: MIN_AGE,
+                resource_proof_verified: verified,
             })),
         );
     }
@@ -170,11 +185,33 @@ impl ElderUnderTest {
         );
     }
 
-    fn accumulate_section_info_if_vote(&mut self, section_info_payload: EldersInfo) {
-        let _ = self.n_vote_for_gossipped(
-            NOT_ACCUMULATE_ALONE_VOTE_COUNT,
-            iter::once(AccumulatingEvent::SectionInfo(section_info_payload)),
-        );
+    fn accumulate_elders_changed_if_vote(&mut self, new_info: EldersInfo) {
+        let old_members = self.elder_state().chain().our_info().members().clone();
+        let event = AccumulatingEvent::EldersChanged {
+            added: new_info
+                .members()
+                .difference(&old_members)
+                .cloned()
+                .collect(),
+            removed: old_members
+                .difference(new_info.members())
+                .cloned()
+                .collect(),
+            version: *new_info.version(),
+        };
+
+        self.other_parsec_map
+            .iter_mut()
+            .zip(self.other_full_ids.iter())
+            .take(NOT_ACCUMULATE_ALONE_VOTE_COUNT)
+            .for_each(|(parsec, full_id)| {
+                let sig_event = unwrap!(SectionInfoSigPayload::new(&new_info, &full_id));
+                parsec.vote_for(
+                    event.clone().into_network_event_with(Some(sig_event)),
+                    &LogIdent::new(&0),
+                )
+            });
+        let _ = self.create_gossip();
     }
 
     fn accumulate_offline(&mut self, offline_payload: PublicId) {
@@ -281,44 +318,30 @@ impl ElderUnderTest {
 fn new_elder_state(
     full_id: &FullId,
     gen_pfx_info: &GenesisPfxInfo,
-    min_section_size: usize,
+    network_params: NetworkParams,
     network_service: NetworkService,
     timer: Timer,
     outbox: &mut dyn EventBox,
 ) -> State {
-    let public_id = *full_id.public_id();
-
-    let parsec_map = ParsecMap::new(full_id.clone(), gen_pfx_info);
-    let chain = Chain::new(min_section_size, public_id, gen_pfx_info.clone());
-    let peer_map = PeerMap::new();
-
-    let details = ElderDetails {
-        chain,
+    Elder::from_genesis(
         network_service,
-        event_backlog: Default::default(),
-        full_id: full_id.clone(),
-        gen_pfx_info: gen_pfx_info.clone(),
-        msg_queue: Default::default(),
-        parsec_map,
-        peer_map,
-        routing_msg_filter: RoutingMessageFilter::new(),
+        full_id.clone(),
+        gen_pfx_info.clone(),
+        network_params,
         timer,
-    };
-
-    let section_info = gen_pfx_info.first_info.clone();
-    let prefix = *section_info.prefix();
-    Elder::from_adult(details, section_info, prefix, outbox)
-        .map(State::Elder)
-        .unwrap_or(State::Terminated)
+        outbox,
+    )
+    .map(State::Elder)
+    .unwrap_or(State::Terminated)
 }
 
 fn make_state_machine(
     full_id: &FullId,
     gen_pfx_info: &GenesisPfxInfo,
-    min_section_size: usize,
+    network_params: NetworkParams,
     outbox: &mut dyn EventBox,
 ) -> StateMachine {
-    let network = Network::new(min_section_size, None);
+    let network = Network::new(network_params.min_section_size, None);
 
     let endpoint = network.gen_addr();
     let config = NetworkConfig::node().with_hard_coded_contact(endpoint);
@@ -328,7 +351,7 @@ fn make_state_machine(
             new_elder_state(
                 full_id,
                 gen_pfx_info,
-                min_section_size,
+                network_params,
                 network_service,
                 timer,
                 outbox2,
@@ -358,6 +381,15 @@ fn construct() {
     assert!(!elder_test.is_candidate_elder());
 }
 
+#[test]
+fn when_accumulate_online_with_failed_resource_proof_then_node_is_not_added() {
+    let mut elder_test = ElderUnderTest::new();
+    elder_test.accumulate_online_with_resource_proof(elder_test.candidate_id, false);
+
+    assert!(!elder_test.has_unpolled_observations());
+    assert!(!elder_test.is_candidate_member());
+}
+
 #[test]
 fn when_accumulate_online_then_node_is_added_to_our_members() {
     let mut elder_test = ElderUnderTest::new();
@@ -382,14 +414,14 @@ fn when_accumulate_online_and_accumulate_add_elder_then_node_is_promoted_to_elde
 }
 
 #[test]
-fn when_accumulate_online_and_accumulate_add_elder_and_accumulate_section_info_then_node_is_added_to_our_elders_info(
+fn when_accumulate_online_and_accumulate_add_elder_and_accumulate_elders_changed_then_node_is_added_to_our_elders_info(
 ) {
     let mut elder_test = ElderUnderTest::new();
     elder_test.accumulate_online(elder_test.candidate_id);
     elder_test.accumulate_add_elder_if_vote(elder_test.candidate_id);
 
     let new_elders_info = elder_test.new_elders_info_with_candidate();
-    elder_test.accumulate_section_info_if_vote(new_elders_info);
+    elder_test.accumulate_elders_changed_if_vote(new_elders_info);
 
     assert!(!elder_test.has_unpolled_observations());
     assert!(elder_test.is_candidate_member());
@@ -402,7 +434,7 @@ fn when_accumulate_offline_then_node_is_removed_from_our_members() {
     let mut elder_test = ElderUnderTest::new();
     elder_test.accumulate_online(elder_test.candidate_id);
     elder_test.accumulate_add_elder_if_vote(elder_test.candidate_id);
-    elder_test.accumulate_section_info_if_vote(elder_test.new_elders_info_with_candidate());
+    elder_test.accumulate_elders_changed_if_vote(elder_test.new_elders_info_with_candidate());
 
     elder_test.accumulate_offline(elder_test.candidate_id);
 
@@ -420,7 +452,7 @@ fn when_accumulate_offline_and_accumulate_remove_elder_then_node_is_not_yet_demo
     let mut elder_test = ElderUnderTest::new();
     elder_test.accumulate_online(elder_test.candidate_id);
     elder_test.accumulate_add_elder_if_vote(elder_test.candidate_id);
-    elder_test.accumulate_section_info_if_vote(elder_test.new_elders_info_with_candidate());
+    elder_test.accumulate_elders_changed_if_vote(elder_test.new_elders_info_with_candidate());
 
     elder_test.accumulate_offline(elder_test.candidate_id);
     elder_test.accumulate_remove_elder_if_vote(elder_test.candidate_id);
@@ -432,16 +464,16 @@ fn when_accumulate_offline_and_accumulate_remove_elder_then_node_is_not_yet_demo
 }
 
 #[test]
-fn when_accumulate_offline_and_accumulate_remove_elder_and_accumulate_section_info_then_node_is_removed_from_our_elders_info(
+fn when_accumulate_offline_and_accumulate_remove_elder_and_accumulate_elders_changed_then_node_is_removed_from_our_elders_info(
 ) {
     let mut elder_test = ElderUnderTest::new();
     elder_test.accumulate_online(elder_test.candidate_id);
     elder_test.accumulate_add_elder_if_vote(elder_test.candidate_id);
-    elder_test.accumulate_section_info_if_vote(elder_test.new_elders_info_with_candidate());
+    elder_test.accumulate_elders_changed_if_vote(elder_test.new_elders_info_with_candidate());
 
     elder_test.accumulate_offline(elder_test.candidate_id);
     elder_test.accumulate_remove_elder_if_vote(elder_test.candidate_id);
-    elder_test.accumulate_section_info_if_vote(elder_test.new_elders_info_without_candidate());
+    elder_test.accumulate_elders_changed_if_vote(elder_test.new_elders_info_without_candidate());
 
     assert!(!elder_test.has_unpolled_observations());
     assert!(!elder_test.is_candidate_member());
@@ -463,9 +495,401 @@ fn accept_previously_rejected_node_after_reaching_min_section_size() {
     // Add new section member to reach min_section_size.
     elder_test.accumulate_online(elder_test.candidate_id);
     elder_test.accumulate_add_elder_if_vote(elder_test.candidate_id);
-    elder_test.accumulate_section_info_if_vote(elder_test.new_elders_info_with_candidate());
+    elder_test.accumulate_elders_changed_if_vote(elder_test.new_elders_info_with_candidate());
 
     // Re-bootstrap now succeeds.
     elder_test.handle_bootstrap_request(*node.public_id(), node.connection_info());
     assert!(elder_test.is_connected(node.public_id()));
 }
+
+#[test]
+fn redirect_bootstrapping_nodes_when_overloaded() {
+    let mut elder_test = ElderUnderTest::with_network_params(NetworkParams {
+        min_section_size: DEFAULT_MIN_SECTION_SIZE,
+        max_proxy_load: 1,
+        ..Default::default()
+    });
+    let node_a = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let node_b = JoiningNodeInfo::with_addr("198.51.100.1:5000");
+
+    elder_test.handle_bootstrap_request(*node_a.public_id(), node_a.connection_info());
+    assert!(elder_test.is_connected(node_a.public_id()));
+
+    // We are already the proxy of node A, so node B is redirected.
+    elder_test.handle_bootstrap_request(*node_b.public_id(), node_b.connection_info());
+    assert!(!elder_test.is_connected(node_b.public_id()));
+}
+
+#[test]
+fn defer_bootstrapping_nodes_when_busy_with_candidates() {
+    let mut elder_test = ElderUnderTest::with_network_params(NetworkParams {
+        min_section_size: DEFAULT_MIN_SECTION_SIZE,
+        max_joining_candidates: 1,
+        ..Default::default()
+    });
+    let node_a = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let node_b = JoiningNodeInfo::with_addr("198.51.100.1:5000");
+
+    elder_test.handle_bootstrap_request(*node_a.public_id(), node_a.connection_info());
+    assert!(elder_test.is_connected(node_a.public_id()));
+    let join_request = (DirectMessage::JoinRequest(None), *node_a.public_id());
+    unwrap!(elder_test.handle_direct_message(join_request));
+
+    // We are already processing node A, so node B is told to try again later.
+    elder_test.handle_bootstrap_request(*node_b.public_id(), node_b.connection_info());
+    assert!(!elder_test.is_connected(node_b.public_id()));
+}
+
+#[test]
+fn attached_handler_consumes_messages_before_built_in_handling() {
+    use crate::dispatch::{
+        DirectMessageKind, DirectMessageView, Dispatch, DispatchTable, MessageHandler,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Default)]
+    struct DropJoinRequests(AtomicUsize);
+
+    impl MessageHandler for DropJoinRequests {
+        fn handle_direct_message(&self, _: &DirectMessageView) -> Dispatch {
+            let _ = self.0.fetch_add(1, Ordering::SeqCst);
+            Dispatch::Consume
+        }
+    }
+
+    let handler = Arc::new(DropJoinRequests::default());
+    let mut dispatch_table = DispatchTable::default();
+    dispatch_table.on_direct_message(DirectMessageKind::JoinRequest, handler.clone());
+
+    let mut network_params = NetworkParams {
+        min_section_size: DEFAULT_MIN_SECTION_SIZE,
+        max_joining_candidates: 1,
+        ..Default::default()
+    };
+    network_params.local.dispatch_table = dispatch_table;
+
+    let mut elder_test = ElderUnderTest::with_network_params(network_params);
+    let node_a = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let node_b = JoiningNodeInfo::with_addr("198.51.100.1:5000");
+
+    elder_test.handle_bootstrap_request(*node_a.public_id(), node_a.connection_info());
+    let join_request = (DirectMessage::JoinRequest(None), *node_a.public_id());
+    unwrap!(elder_test.handle_direct_message(join_request));
+    assert_eq!(handler.0.load(Ordering::SeqCst), 1);
+
+    // The join request of node A never reached the elder, so node B isn't deferred.
+    elder_test.handle_bootstrap_request(*node_b.public_id(), node_b.connection_info());
+    assert!(elder_test.is_connected(node_b.public_id()));
+}
+
+#[test]
+fn challenge_joining_nodes_to_sign_a_nonce() {
+    let mut elder_test = ElderUnderTest::new();
+    let node_a = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let node_b = JoiningNodeInfo::with_addr("198.51.100.1:5000");
+
+    let challenge = |elder_test: &mut ElderUnderTest, node: &JoiningNodeInfo| {
+        elder_test.handle_bootstrap_request(*node.public_id(), node.connection_info());
+        let join_request = (DirectMessage::JoinRequest(None), *node.public_id());
+        unwrap!(elder_test.handle_direct_message(join_request));
+        unwrap!(elder_test
+            .elder_state()
+            .join_challenges
+            .get(node.public_id()))
+        .0
+        .clone()
+    };
+
+    // Node A answers its challenge, so it gets a resource proof to solve.
+    let nonce = challenge(&mut elder_test, &node_a);
+    let signature = node_a.full_id.sign(&join_challenge_bytes(&nonce));
+    let response = (
+        DirectMessage::JoinChallengeResponse(signature),
+        *node_a.public_id(),
+    );
+    unwrap!(elder_test.handle_direct_message(response));
+    assert!(elder_test
+        .elder_state()
+        .resource_challenges
+        .contains_key(node_a.public_id()));
+
+    // Node B answers with a signature by another key, so it is dropped.
+    let nonce = challenge(&mut elder_test, &node_b);
+    let signature = FullId::new().sign(&join_challenge_bytes(&nonce));
+    let response = (
+        DirectMessage::JoinChallengeResponse(signature),
+        *node_b.public_id(),
+    );
+    unwrap!(elder_test.handle_direct_message(response));
+    assert!(!elder_test
+        .elder_state()
+        .resource_challenges
+        .contains_key(node_b.public_id()));
+    assert!(!elder_test.is_connected(node_b.public_id()));
+}
+
+#[test]
+fn reject_bootstrapping_nodes_with_incompatible_protocol_version() {
+    let mut elder_test = ElderUnderTest::new();
+    let node_a = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let node_b = JoiningNodeInfo::with_addr("198.51.100.1:5000");
+
+    let mut bootstrap = |node: &JoiningNodeInfo, version| {
+        let peer_addr = node.connection_info().peer_addr;
+        elder_test.handle_connected_to(node.connection_info());
+        elder_test
+            .machine
+            .elder_state_mut()
+            .identify_connection(*node.public_id(), peer_addr);
+
+        let capabilities = Capabilities {
+            version,
+            compression: true,
+        };
+        let request = DirectMessage::BootstrapRequest(*node.public_id().name(), capabilities);
+        unwrap!(elder_test.handle_direct_message((request, *node.public_id())));
+        elder_test.is_connected(node.public_id())
+    };
+
+    // An older minor version is fine.
+    let older = ProtocolVersion {
+        minor: ProtocolVersion::CURRENT.minor - 1,
+        ..ProtocolVersion::CURRENT
+    };
+    assert!(bootstrap(&node_a, older));
+
+    // Another major version isn't.
+    let newer = ProtocolVersion {
+        major: ProtocolVersion::CURRENT.major + 1,
+        minor: 0,
+    };
+    assert!(!bootstrap(&node_b, newer));
+}
+
+#[test]
+fn ignore_join_requests_from_nodes_that_joined_already() {
+    let mut elder_test = ElderUnderTest::new();
+    let node = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    let pub_id = *node.public_id();
+
+    elder_test.handle_bootstrap_request(pub_id, node.connection_info());
+    elder_test.accumulate_online(pub_id);
+    assert!(elder_test.elder_state().chain.is_peer_our_member(&pub_id));
+
+    // Sends a `JoinRequest` while the node has a pending offline vote and returns whether the
+    // request cancelled it, i.e. whether it was handled as a rejoin.
+    let rejoin = |elder_test: &mut ElderUnderTest| {
+        let _ = elder_test
+            .machine
+            .elder_state_mut()
+            .delayed_offline_votes
+            .insert(0, pub_id);
+        let join_request = (DirectMessage::JoinRequest(None), pub_id);
+        unwrap!(elder_test.handle_direct_message(join_request));
+        elder_test.elder_state().delayed_offline_votes.is_empty()
+    };
+
+    // A repeated request from a member is approved again.
+    assert!(rejoin(&mut elder_test));
+
+    // The node answers the repeated approval with `JoinedAlready`, so its next request is ignored.
+    unwrap!(elder_test.handle_direct_message((DirectMessage::JoinedAlready, pub_id)));
+    assert!(elder_test.elder_state().joined_already.contains(&pub_id));
+    assert!(!rejoin(&mut elder_test));
+}
+
+#[test]
+fn reject_replayed_client_requests() {
+    let mut elder_test = ElderUnderTest::new();
+    let client_id = *FullId::new().public_id();
+    let our_info = unwrap!(elder_test.machine.current_mut().our_connection_info());
+    let other_proxy = ConnectionInfo::from(unwrap!("198.51.100.0:5000".parse::<SocketAddr>()));
+    let nonce = client_nonce_clock();
+
+    let mut check = |proxy: &ConnectionInfo, nonce| {
+        elder_test
+            .machine
+            .elder_state_mut()
+            .check_client_request(client_id, proxy, nonce)
+            .is_ok()
+    };
+
+    // A request meant for another proxy is rejected.
+    assert!(!check(&other_proxy, nonce));
+    assert!(check(&our_info, nonce));
+    // The same request replayed, or an older one, is rejected.
+    assert!(!check(&our_info, nonce));
+    assert!(!check(&our_info, nonce - 1));
+    assert!(check(&our_info, nonce + 1));
+    // So is one whose nonce is too far from our clock.
+    assert!(!check(&our_info, nonce + 2 * CLIENT_NONCE_WINDOW_MS));
+}
+
+#[test]
+fn accept_delivery_acks_only_from_the_destination() {
+    let mut elder_test = ElderUnderTest::new();
+    let dst = Authority::Node(rand::random());
+    let msg_id = MessageId::new();
+    let _ = elder_test
+        .machine
+        .elder_state_mut()
+        .pending_deliveries
+        .insert(0, (dst, msg_id));
+
+    let ack = |elder_test: &mut ElderUnderTest, src| {
+        elder_test.machine.elder_state_mut().handle_delivery_ack(
+            msg_id,
+            src,
+            &mut elder_test.ev_buffer,
+        );
+        elder_test
+            .ev_buffer
+            .take_all()
+            .into_iter()
+            .any(|event| event == Event::MessageDelivered(msg_id))
+    };
+
+    assert!(!ack(&mut elder_test, Authority::Node(rand::random())));
+    assert!(ack(&mut elder_test, dst));
+    assert!(elder_test.elder_state().pending_deliveries.is_empty());
+}
+
+#[test]
+fn do_not_relay_messages_past_their_hop_limit() {
+    let mut elder_test = ElderUnderTest::new();
+    let node = JoiningNodeInfo::with_addr("198.51.100.0:5000");
+    elder_test.handle_connected_to(node.connection_info());
+    elder_test
+        .machine
+        .elder_state_mut()
+        .identify_connection(*node.public_id(), node.connection_info().peer_addr);
+
+    // Relays a message to the node, which may still be relayed `ttl` times, and returns whether
+    // it was sent.
+    let relay = |elder_test: &mut ElderUnderTest, ttl| {
+        let msg = RoutingMessage {
+            src: Authority::Node(rand::random()),
+            dst: Authority::Node(*node.public_id().name()),
+            content: MessageContent::UserMessage {
+                content: vec![ttl],
+                msg_id: MessageId::new(),
+            },
+        };
+        let mut signed_msg = SignedRoutingMessage::insecure(msg.clone());
+        let elder = elder_test.machine.elder_state_mut();
+        let _ = elder.relay_signed_message(&mut signed_msg, ttl);
+        !elder
+            .routing_msg_filter
+            .filter_outgoing(&msg, node.public_id())
+            .is_new()
+    };
+
+    assert!(!relay(&mut elder_test, 0));
+    assert!(relay(&mut elder_test, 1));
+}
+
+#[test]
+fn drop_messages_signed_with_obsolete_section_keys() {
+    let mut elder_test = ElderUnderTest::new();
+    let our_name = *elder_test.full_id.public_id().name();
+    let full_ids = iter::once(&elder_test.full_id)
+        .chain(&elder_test.other_full_ids)
+        .cloned()
+        .collect_vec();
+    let pk_set = BlsPublicKeySet::from_elders_info(elder_test.elders_info.clone());
+    let proof =
+        SectionProofChain::from_genesis(SectionKeyInfo::from_elders_info(&elder_test.elders_info));
+
+    // Returns our section's genesis elders' message to us, signed with their key.
+    let signed_msg = |content| {
+        let msg = RoutingMessage {
+            src: Authority::Section(our_name),
+            dst: Authority::Node(our_name),
+            content: MessageContent::UserMessage {
+                content,
+                msg_id: MessageId::new(),
+            },
+        };
+        let signed_bytes = unwrap!(serialise(&msg));
+        let mut signed_msg = unwrap!(SignedRoutingMessage::new(
+            msg,
+            &full_ids[0],
+            pk_set.clone(),
+            proof.clone(),
+        ));
+        for full_id in &full_ids[1..] {
+            let sig = full_id.sign(&signed_bytes);
+            signed_msg.add_signature_share(BlsPublicKeyShare(*full_id.public_id()), sig);
+        }
+        signed_msg.combine_signatures();
+        signed_msg
+    };
+
+    unwrap!(elder_test
+        .machine
+        .elder_state_mut()
+        .handle_signed_message(signed_msg(vec![0]), MAX_HOPS));
+
+    // Once we know of a key of a part of the section many versions newer, the genesis key is
+    // still trusted, but long obsolete.
+    let newer_info = unwrap!(EldersInfo::new_for_test(
+        full_ids
+            .iter()
+            .map(|full_id| *full_id.public_id())
+            .collect(),
+        Prefix::default().pushed(false),
+        20,
+    ));
+    let elder = elder_test.machine.elder_state_mut();
+    elder
+        .chain
+        .update_their_keys(&SectionKeyInfo::from_elders_info(&newer_info));
+    match elder.handle_signed_message(signed_msg(vec![1]), MAX_HOPS) {
+        Err(RoutingError::ObsoleteMessage) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn accept_undeliverable_reports_only_for_messages_we_sent() {
+    let mut elder_test = ElderUnderTest::new();
+    let src = Authority::Node(*elder_test.full_id.public_id().name());
+    let dst = Authority::Node(rand::random());
+    let reporter = *elder_test.other_full_ids[0].public_id().name();
+    let msg = RoutingMessage {
+        src,
+        dst,
+        content: MessageContent::UserMessage {
+            content: vec![0],
+            msg_id: MessageId::new(),
+        },
+    };
+    let digest = unwrap!(msg.hash());
+
+    let report = |elder_test: &mut ElderUnderTest| {
+        elder_test.machine.elder_state_mut().handle_undeliverable(
+            digest,
+            reporter,
+            src,
+            dst,
+            &mut elder_test.ev_buffer,
+        );
+        elder_test
+            .ev_buffer
+            .take_all()
+            .into_iter()
+            .any(|event| event == Event::MessageUndeliverable { src, dst })
+    };
+
+    assert!(!report(&mut elder_test));
+    let _ = elder_test
+        .machine
+        .elder_state_mut()
+        .send_routing_message(msg);
+    assert!(report(&mut elder_test));
+    // Each message is reported once.
+    assert!(!report(&mut elder_test));
+}
@@ -12,77 +12,162 @@ use super::{
     common::Base,
 };
 use crate::{
+    backlog::Backlog,
     chain::GenesisPfxInfo,
     error::{InterfaceError, RoutingError},
+    event::Event,
     id::{FullId, PublicId},
     messages::{
-        DirectMessage, HopMessage, MessageContent, RelocatePayload, RoutingMessage,
-        SignedRoutingMessage,
+        join_challenge_bytes, DirectMessage, HopMessage, MessageContent, RelocatePayload,
+        RoutingMessage, SignedRoutingMessage,
     },
     outbox::EventBox,
+    pause::{PausedCommon, PausedJoiningPeer, PausedStage, PausedState, RelocationState},
     peer_map::PeerMap,
-    routing_message_filter::RoutingMessageFilter,
+    routing_message_filter::{DuplicateCounts, RoutingMessageFilter},
     routing_table::Authority,
     state_machine::{State, Transition},
     timer::Timer,
+    types::MessageId,
+    utils,
     xor_name::XorName,
-    ConnectionInfo, NetworkService,
+    ConnectionInfo, NetworkParams, NetworkService,
 };
+use resource_proof::{ResourceProof, ResourceProver};
 use std::{
+    cmp,
+    collections::{HashMap, VecDeque},
     fmt::{self, Display, Formatter},
+    net::SocketAddr,
     time::Duration,
 };
 
-/// Time after which bootstrap is cancelled (and possibly retried).
+/// Upper bound of the time after which a join attempt is cancelled (and possibly retried), before
+/// jitter. See `JoinBackoff` for the timeout of each attempt.
 pub const JOIN_TIMEOUT: Duration = Duration::from_secs(120);
-/// How many times will the node try to join the same section before giving up and rebootstrapping.
-const MAX_JOIN_ATTEMPTS: u8 = 3;
+// Number of steps of a resource proof we take before yielding to the other events.
+const RESOURCE_PROOF_STEPS: usize = 1024;
+// Interval between the batches of steps of a resource proof.
+const RESOURCE_PROOF_STEP_INTERVAL: Duration = Duration::from_millis(1);
+
+// State of our `JoinRequest` to one of the elders of the section we are joining.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum JoinState {
+    // Sent, but the elder hasn't responded yet. Resent on each timeout.
+    Sent,
+    // The elder responded with a challenge, so it is processing our request.
+    Acked,
+    // Our connection to the elder dropped, so we no longer send to it.
+    Failed,
+}
+
+// Resource proof an elder challenged us with, solved a few steps at a time.
+struct PendingProof {
+    elder: PublicId,
+    data: Vec<u8>,
+    prover: ResourceProver,
+}
 
 // State of a node after bootstrapping, while joining a section
 pub struct JoiningPeer {
     network_service: NetworkService,
     routing_msg_filter: RoutingMessageFilter,
-    msg_backlog: Vec<SignedRoutingMessage>,
+    msg_backlog: Backlog<SignedRoutingMessage>,
     full_id: FullId,
-    min_section_size: usize,
+    network_params: NetworkParams,
     peer_map: PeerMap,
     timer: Timer,
     join_token: u64,
     join_attempts: u8,
     conn_infos: Vec<ConnectionInfo>,
+    join_states: HashMap<SocketAddr, JoinState>,
+    pending_proofs: VecDeque<PendingProof>,
+    proof_token: Option<u64>,
     relocate_payload: Option<RelocatePayload>,
+    relocation: Option<RelocationState>,
 }
 
 impl JoiningPeer {
     pub fn new(
         network_service: NetworkService,
         full_id: FullId,
-        min_section_size: usize,
+        network_params: NetworkParams,
         timer: Timer,
         peer_map: PeerMap,
         conn_infos: Vec<ConnectionInfo>,
         relocate_payload: Option<RelocatePayload>,
+        relocation: Option<RelocationState>,
     ) -> Self {
-        let join_token = timer.schedule(JOIN_TIMEOUT);
+        let join_states = conn_infos
+            .iter()
+            .map(|conn_info| (conn_info.peer_addr, JoinState::Sent))
+            .collect();
 
         let mut joining_peer = Self {
             network_service,
-            routing_msg_filter: RoutingMessageFilter::new(),
-            msg_backlog: vec![],
+            routing_msg_filter: RoutingMessageFilter::new(
+                network_params.local.message_filter_limits,
+            ),
+            msg_backlog: Backlog::new(network_params.backlog_limits),
             full_id,
-            min_section_size,
+            network_params,
             timer: timer,
             peer_map,
-            join_token,
+            join_token: 0,
             join_attempts: 0,
             conn_infos,
+            join_states,
+            pending_proofs: VecDeque::new(),
+            proof_token: None,
             relocate_payload,
+            relocation,
         };
 
+        joining_peer.schedule_join_timeout();
         joining_peer.send_join_requests();
         joining_peer
     }
 
+    pub fn pause(self) -> PausedState {
+        PausedState {
+            common: PausedCommon {
+                full_id: self.full_id,
+                msg_filter: self.routing_msg_filter,
+                msg_queue: self.msg_backlog.into_vec(),
+                network_service: self.network_service,
+                peer_map: self.peer_map,
+            },
+            network_rx: None,
+            stage: PausedStage::JoiningPeer(PausedJoiningPeer {
+                network_params: self.network_params,
+                conn_infos: self.conn_infos,
+                relocate_payload: self.relocate_payload,
+                relocation: self.relocation,
+            }),
+        }
+    }
+
+    /// Resumes joining, starting over with the first attempt.
+    pub fn resume(common: PausedCommon, state: PausedJoiningPeer, timer: Timer) -> Self {
+        let mut joining_peer = Self::new(
+            common.network_service,
+            common.full_id,
+            state.network_params,
+            timer,
+            common.peer_map,
+            state.conn_infos,
+            state.relocate_payload,
+            state.relocation,
+        );
+
+        joining_peer.routing_msg_filter = common.msg_filter;
+        for msg in common.msg_queue {
+            joining_peer.msg_backlog.push(msg);
+        }
+
+        joining_peer
+    }
+
     pub fn into_adult(
         self,
         gen_pfx_info: GenesisPfxInfo,
@@ -90,10 +175,10 @@ impl JoiningPeer {
     ) -> Result<State, RoutingError> {
         let details = AdultDetails {
             network_service: self.network_service,
-            event_backlog: vec![],
+            event_backlog: Backlog::new(self.network_params.backlog_limits),
             full_id: self.full_id,
             gen_pfx_info,
-            min_section_size: self.min_section_size,
+            network_params: self.network_params,
             msg_backlog: self.msg_backlog,
             peer_map: self.peer_map,
             routing_msg_filter: self.routing_msg_filter,
@@ -102,17 +187,56 @@ impl JoiningPeer {
         Adult::from_joining_peer(details, outbox).map(State::Adult)
     }
 
+    pub fn dropped_backlog_entries(&self) -> u64 {
+        self.msg_backlog.dropped()
+    }
+
+    pub fn duplicate_counts(&self) -> &DuplicateCounts {
+        self.routing_msg_filter.duplicate_counts()
+    }
+
+    pub fn relocation_state(&self) -> Option<RelocationState> {
+        self.relocation.clone()
+    }
+
     pub fn rebootstrap(self) -> Result<State, RoutingError> {
         Ok(State::BootstrappingPeer(BootstrappingPeer::new(
             self.network_service,
             FullId::new(),
-            self.min_section_size,
+            self.network_params,
             self.timer,
         )))
     }
 
+    // Schedules the timeout of the current join attempt, backing off exponentially with each
+    // failed one.
+    fn schedule_join_timeout(&mut self) {
+        let backoff = self.network_params.join_backoff;
+        let max_jitter_ms =
+            backoff.max_jitter.as_secs() * 1000 + u64::from(backoff.max_jitter.subsec_millis());
+        let jitter = Duration::from_millis(utils::rand_index(max_jitter_ms as usize + 1) as u64);
+        let timeout = backoff.timeout_with_jitter(self.join_attempts, jitter);
+
+        debug!(
+            "{} - Waiting {:?} for approval (attempt {}/{}).",
+            self,
+            timeout,
+            self.join_attempts + 1,
+            backoff.max_attempts
+        );
+        self.join_token = self.timer.schedule(timeout);
+    }
+
+    // Sends our `JoinRequest` to the elders which haven't responded to it yet.
     fn send_join_requests(&mut self) {
-        let conn_infos = self.conn_infos.clone();
+        let conn_infos: Vec<_> = self
+            .conn_infos
+            .iter()
+            .filter(|conn_info| {
+                self.join_states.get(&conn_info.peer_addr) == Some(&JoinState::Sent)
+            })
+            .cloned()
+            .collect();
         for dst in conn_infos {
             info!("{} - Sending JoinRequest to {:?}", self, dst);
             self.send_direct_message(
@@ -147,6 +271,86 @@ impl JoiningPeer {
         }
     }
 
+    fn handle_join_challenge(&mut self, pub_id: PublicId, nonce: &[u8]) {
+        debug!("{} - Signing join challenge from {}.", self, pub_id);
+
+        self.ack_join_request(&pub_id);
+
+        let signature = self.full_id.sign(&join_challenge_bytes(nonce));
+        self.send_direct_message(&pub_id, DirectMessage::JoinChallengeResponse(signature));
+    }
+
+    fn handle_resource_challenge(
+        &mut self,
+        pub_id: PublicId,
+        target_size: usize,
+        difficulty: u8,
+        seed: &[u8],
+    ) {
+        debug!(
+            "{} - Solving resource proof challenge from {}.",
+            self, pub_id
+        );
+
+        self.ack_join_request(&pub_id);
+
+        // The elders use the same parameters as us, so never solve a harder proof than ours.
+        let target_size = cmp::min(target_size, self.network_params.resource_proof_target_size);
+        let difficulty = cmp::min(difficulty, self.network_params.resource_proof_difficulty);
+
+        let resource_proof = ResourceProof::new(target_size, difficulty);
+        let data = resource_proof.create_proof_data(seed);
+        let prover = resource_proof.create_prover(data.clone());
+        self.pending_proofs.retain(|proof| proof.elder != pub_id);
+        self.pending_proofs.push_back(PendingProof {
+            elder: pub_id,
+            data,
+            prover,
+        });
+
+        if self.proof_token.is_none() {
+            self.solve_resource_proofs();
+        }
+    }
+
+    // Takes a batch of steps of the oldest pending resource proof, sending it once solved, and
+    // schedules the next batch if any proof is still pending. Solving them all at once would block
+    // the handling of the other events for as long as the proofs take.
+    fn solve_resource_proofs(&mut self) {
+        self.proof_token = None;
+
+        let mut solved = None;
+        if let Some(proof) = self.pending_proofs.front_mut() {
+            solved = (0..RESOURCE_PROOF_STEPS).find_map(|_| proof.prover.solve_step());
+        }
+
+        if let Some(solution) = solved {
+            if let Some(PendingProof { elder, data, .. }) = self.pending_proofs.pop_front() {
+                self.send_direct_message(
+                    &elder,
+                    DirectMessage::ResourceProofResponse { data, solution },
+                );
+            }
+        }
+
+        if !self.pending_proofs.is_empty() {
+            self.proof_token = Some(self.timer.schedule(RESOURCE_PROOF_STEP_INTERVAL));
+        }
+    }
+
+    // Stops resending our `JoinRequest` to the elder, as it challenged us.
+    fn ack_join_request(&mut self, pub_id: &PublicId) {
+        if let Some(peer_addr) = self
+            .peer_map
+            .get_connection_info(pub_id)
+            .map(|conn_info| conn_info.peer_addr)
+        {
+            if let Some(state) = self.join_states.get_mut(&peer_addr) {
+                *state = JoinState::Acked;
+            }
+        }
+    }
+
     fn handle_node_approval(&mut self, gen_pfx_info: GenesisPfxInfo) -> Transition {
         info!(
             "{} - This node has been approved to join the network!",
@@ -155,10 +359,30 @@ impl JoiningPeer {
         Transition::IntoAdult { gen_pfx_info }
     }
 
+    // Gives up joining this section and rebootstraps.
+    fn abandon_join(&mut self) -> Transition {
+        for peer_addr in self
+            .peer_map
+            .remove_all()
+            .map(|conn_info| conn_info.peer_addr)
+        {
+            self.network_service
+                .service_mut()
+                .disconnect_from(peer_addr);
+        }
+
+        Transition::Rebootstrap
+    }
+
     #[cfg(feature = "mock_base")]
     pub fn get_timed_out_tokens(&mut self) -> Vec<u64> {
         self.timer.get_timed_out_tokens()
     }
+
+    #[cfg(feature = "mock_base")]
+    pub fn next_timer_deadline(&self) -> Option<crate::time::Instant> {
+        self.timer.next_deadline()
+    }
 }
 
 impl Base for JoiningPeer {
@@ -179,7 +403,7 @@ impl Base for JoiningPeer {
     }
 
     fn min_section_size(&self) -> usize {
-        self.min_section_size
+        self.network_params.min_section_size
     }
 
     fn peer_map(&self) -> &PeerMap {
@@ -199,6 +423,7 @@ impl Base for JoiningPeer {
         _: Authority<XorName>,
         _: Authority<XorName>,
         _: Vec<u8>,
+        _: Option<MessageId>,
     ) -> Result<(), InterfaceError> {
         warn!("{} - Cannot handle SendMessage - not joined.", self);
         // TODO: return Err here eventually. Returning Ok for now to
@@ -206,42 +431,79 @@ impl Base for JoiningPeer {
         Ok(())
     }
 
-    fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
+    fn handle_timeout(&mut self, token: u64, outbox: &mut dyn EventBox) -> Transition {
+        if self.proof_token == Some(token) {
+            self.solve_resource_proofs();
+            return Transition::Stay;
+        }
+
         if self.join_token == token {
             self.join_attempts += 1;
+            let max_attempts = self.network_params.join_backoff.max_attempts;
             debug!(
                 "{} - Timeout when trying to join a section (attempt {}/{}).",
-                self, self.join_attempts, MAX_JOIN_ATTEMPTS
+                self, self.join_attempts, max_attempts
             );
+            outbox.send_event(Event::JoinAttemptFailed {
+                attempt: self.join_attempts,
+                max_attempts,
+            });
 
-            if self.join_attempts < MAX_JOIN_ATTEMPTS {
-                self.join_token = self.timer.schedule(JOIN_TIMEOUT);
+            if self.join_attempts < max_attempts {
+                self.schedule_join_timeout();
                 self.send_join_requests();
             } else {
-                for peer_addr in self
-                    .peer_map
-                    .remove_all()
-                    .map(|conn_info| conn_info.peer_addr)
-                {
-                    self.network_service
-                        .service_mut()
-                        .disconnect_from(peer_addr);
-                }
-
-                return Transition::Rebootstrap;
+                return self.abandon_join();
             }
         }
 
         Transition::Stay
     }
 
+    fn handle_connection_failure(
+        &mut self,
+        peer_addr: SocketAddr,
+        _: &mut dyn EventBox,
+    ) -> Transition {
+        let _ = self.peer_map.disconnect(peer_addr);
+
+        if !self.join_states.contains_key(&peer_addr) {
+            return Transition::Stay;
+        }
+
+        debug!(
+            "{} - Lost connection to {}, abandoning it.",
+            self, peer_addr
+        );
+        let _ = self.join_states.insert(peer_addr, JoinState::Failed);
+
+        if self
+            .join_states
+            .values()
+            .all(|state| *state == JoinState::Failed)
+        {
+            info!("{} - Lost connection to all the elders to join.", self);
+            return self.abandon_join();
+        }
+
+        Transition::Stay
+    }
+
     fn handle_direct_message(
         &mut self,
         msg: DirectMessage,
-        _pub_id: PublicId,
+        pub_id: PublicId,
         _outbox: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
-        debug!("{} Unhandled direct message: {:?}", self, msg);
+        match msg {
+            DirectMessage::ResourceChallenge {
+                target_size,
+                difficulty,
+                seed,
+            } => self.handle_resource_challenge(pub_id, target_size, difficulty, &seed),
+            DirectMessage::JoinChallenge(nonce) => self.handle_join_challenge(pub_id, &nonce),
+            _ => debug!("{} Unhandled direct message: {:?}", self, msg),
+        }
 
         Ok(Transition::Stay)
     }
@@ -8,17 +8,17 @@
 
 mod adult;
 mod bootstrapping_peer;
+mod client;
 pub mod common;
 mod elder;
 mod joining_peer;
 
 pub use self::{
-    adult::Adult, bootstrapping_peer::BootstrappingPeer, elder::Elder, joining_peer::JoiningPeer,
-};
-
-#[cfg(feature = "mock_base")]
-pub use self::{
-    adult::ADD_TIMEOUT, bootstrapping_peer::BOOTSTRAP_TIMEOUT, joining_peer::JOIN_TIMEOUT,
+    adult::{Adult, ADD_TIMEOUT},
+    bootstrapping_peer::{BootstrappingPeer, BOOTSTRAP_TIMEOUT},
+    client::Client,
+    elder::Elder,
+    joining_peer::{JoiningPeer, JOIN_TIMEOUT},
 };
 
 //
@@ -42,20 +42,29 @@ pub use self::{
 //              │                ┌───────┐
 //              └────────────────│ Adult │
 //                               └───────┘
-//                                 │
-//                                 │
-//                                 ▼
+//                                 │   ▲
+//                                 │   │
+//                                 ▼   │
 //                               ┌───────┐
 //                               │ Elder │
 //                               └───────┘
 //
 //
+//      START
+//        │
+//        ▼
+//   ┌────────┐
+//   │ Client │
+//   └────────┘
+//
+//
 // # Common traits
-//                              BootstrappingPeer
-//                              │   JoininigPeer
-//                              │   │   Adult
-//                              │   │   │   Elder
-//                              │   │   │   │
-// Base                         *   *   *   *
+//                          Client
+//                          │   BootstrappingPeer
+//                          │   │   JoininigPeer
+//                          │   │   │   Adult
+//                          │   │   │   │   Elder
+//                          │   │   │   │   │
+// Base                     *   *   *   *   *
 // Approved                             *   *
 //
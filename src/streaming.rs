@@ -0,0 +1,387 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Streams: user messages sent as an ordered sequence of parts which the recipient delivers as they
+//! arrive. The sender only sends the parts the recipient granted it credit for, and the recipient
+//! grants more as it delivers them.
+
+use crate::{
+    chunking::MAX_PART_LEN,
+    error::RoutingError,
+    messages::{MessageContent, RoutingMessage},
+    routing_table::Authority,
+    time::{Duration, Instant},
+    types::MessageId,
+    xor_name::XorName,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Number of parts of a stream the sender may send ahead of those the recipient delivered.
+pub const STREAM_WINDOW: u32 = 8;
+
+// Number of parts ahead of those delivered a recipient accepts. Larger than the window, as when the
+// destination is a section, the credit is that granted by a quorum of its elders, and the others
+// may lag behind.
+const MAX_PARTS_AHEAD: u32 = 2 * STREAM_WINDOW;
+
+// Maximum number of streams from the same source we receive at the same time. Parts of further
+// streams are rejected until some of them complete or expire.
+const MAX_STREAMS_PER_SRC: usize = 16;
+
+/// Time after which we give up a stream which made no progress.
+pub const STREAM_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+struct OutgoingStream {
+    src: Authority<XorName>,
+    dst: Authority<XorName>,
+    parts: Vec<Vec<u8>>,
+    // Number of parts sent so far.
+    sent: u32,
+    // Number of parts the recipient allows us to send.
+    credit: u32,
+    expires_at: Instant,
+}
+
+impl OutgoingStream {
+    // Returns the messages carrying the parts we have credit for and didn't send yet.
+    fn next_messages(&mut self, stream_id: MessageId) -> Vec<RoutingMessage> {
+        let part_count = self.parts.len() as u32;
+        let end = self.credit.min(part_count);
+        let messages = (self.sent..end)
+            .map(|index| RoutingMessage {
+                src: self.src,
+                dst: self.dst,
+                content: MessageContent::StreamPart {
+                    stream_id,
+                    index,
+                    part_count,
+                    payload: self.parts[index as usize].clone(),
+                },
+            })
+            .collect();
+        self.sent = self.sent.max(end);
+        messages
+    }
+}
+
+/// The streams we are sending, waiting for credit to send their remaining parts.
+#[derive(Default)]
+pub struct OutgoingStreams {
+    streams: HashMap<MessageId, OutgoingStream>,
+}
+
+impl OutgoingStreams {
+    /// Starts sending `content` as a stream, returning the messages which can be sent right away.
+    pub fn start(
+        &mut self,
+        stream_id: MessageId,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        content: Vec<u8>,
+    ) -> Vec<RoutingMessage> {
+        let mut parts: Vec<_> = content.chunks(MAX_PART_LEN).map(<[u8]>::to_vec).collect();
+        if parts.is_empty() {
+            parts.push(Vec::new());
+        }
+
+        let mut stream = OutgoingStream {
+            src,
+            dst,
+            parts,
+            sent: 0,
+            credit: STREAM_WINDOW,
+            expires_at: Instant::now() + STREAM_TIMEOUT,
+        };
+        let messages = stream.next_messages(stream_id);
+        if (stream.sent as usize) < stream.parts.len() {
+            let _ = self.streams.insert(stream_id, stream);
+        }
+        messages
+    }
+
+    /// Handles the credit granted by `granter` for the stream `stream_id`, returning the messages
+    /// it allows us to send.
+    pub fn add_credit(
+        &mut self,
+        stream_id: MessageId,
+        granter: &Authority<XorName>,
+        credit: u32,
+    ) -> Vec<RoutingMessage> {
+        let (messages, finished) = match self.streams.get_mut(&stream_id) {
+            Some(stream) if stream.dst == *granter => {
+                if credit > stream.credit {
+                    stream.credit = credit;
+                    stream.expires_at = Instant::now() + STREAM_TIMEOUT;
+                }
+                let messages = stream.next_messages(stream_id);
+                (messages, stream.sent as usize == stream.parts.len())
+            }
+            _ => return Vec::new(),
+        };
+
+        if finished {
+            let _ = self.streams.remove(&stream_id);
+        }
+        messages
+    }
+
+    /// Drops the streams the recipient didn't grant any credit for in time, and returns them.
+    pub fn remove_expired(&mut self) -> Vec<StreamKey> {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.expires_at <= now)
+            .map(|(stream_id, stream)| (stream.src, stream.dst, *stream_id))
+            .collect();
+        for (_, _, stream_id) in &expired {
+            let _ = self.streams.remove(stream_id);
+        }
+        expired
+    }
+}
+
+/// What to do after receiving a part of a stream.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct StreamProgress {
+    /// The parts which can now be delivered, in order, with their indices.
+    pub parts: Vec<(u32, Vec<u8>)>,
+    /// Whether all the parts of the stream have been delivered.
+    pub complete: bool,
+    /// The new credit to grant the sender, if it changed.
+    pub credit: Option<u32>,
+}
+
+type StreamKey = (Authority<XorName>, Authority<XorName>, MessageId);
+
+struct IncomingStream {
+    // Index of the next part to deliver.
+    next_index: u32,
+    part_count: u32,
+    // Parts received ahead of `next_index`.
+    pending: BTreeMap<u32, Vec<u8>>,
+    expires_at: Instant,
+}
+
+/// The streams we are receiving.
+#[derive(Default)]
+pub struct IncomingStreams {
+    streams: HashMap<StreamKey, IncomingStream>,
+}
+
+impl IncomingStreams {
+    /// Adds a received part of a stream. Parts delivered already are ignored, and parts too far
+    /// beyond the credit we granted, or of more than `MAX_STREAMS_PER_SRC` streams of the same
+    /// source at a time, are rejected.
+    pub fn add(
+        &mut self,
+        src: Authority<XorName>,
+        dst: Authority<XorName>,
+        stream_id: MessageId,
+        index: u32,
+        part_count: u32,
+        payload: Vec<u8>,
+    ) -> Result<StreamProgress, RoutingError> {
+        if index >= part_count {
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        let key = (src, dst, stream_id);
+        if !self.streams.contains_key(&key)
+            && self
+                .streams
+                .keys()
+                .filter(|(stream_src, _, _)| *stream_src == src)
+                .count()
+                >= MAX_STREAMS_PER_SRC
+        {
+            return Err(RoutingError::InvalidMessage);
+        }
+
+        let stream = self.streams.entry(key).or_insert_with(|| IncomingStream {
+            next_index: 0,
+            part_count,
+            pending: BTreeMap::new(),
+            expires_at: Instant::now() + STREAM_TIMEOUT,
+        });
+
+        if stream.part_count != part_count || index >= stream.next_index + MAX_PARTS_AHEAD {
+            return Err(RoutingError::InvalidMessage);
+        }
+        if index < stream.next_index {
+            return Ok(StreamProgress::default());
+        }
+
+        let _ = stream.pending.insert(index, payload);
+
+        let mut progress = StreamProgress::default();
+        while let Some(payload) = stream.pending.remove(&stream.next_index) {
+            progress.parts.push((stream.next_index, payload));
+            stream.next_index += 1;
+        }
+
+        if stream.next_index == stream.part_count {
+            progress.complete = true;
+            let _ = self.streams.remove(&key);
+        } else if !progress.parts.is_empty() {
+            progress.credit = Some(stream.next_index + STREAM_WINDOW);
+            stream.expires_at = Instant::now() + STREAM_TIMEOUT;
+        }
+
+        Ok(progress)
+    }
+
+    /// Drops the streams which made no progress in time, and returns them.
+    pub fn remove_expired(&mut self) -> Vec<StreamKey> {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.expires_at <= now)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &expired {
+            let _ = self.streams.remove(key);
+        }
+        expired
+    }
+}
+
+#[cfg(all(test, feature = "mock_base"))]
+mod tests {
+    use super::*;
+    use fake_clock::FakeClock;
+    use unwrap::unwrap;
+
+    fn part(message: &RoutingMessage) -> (MessageId, u32, u32, Vec<u8>) {
+        match message.content {
+            MessageContent::StreamPart {
+                stream_id,
+                index,
+                part_count,
+                ref payload,
+            } => (stream_id, index, part_count, payload.clone()),
+            ref content => panic!("Unexpected {:?}", content),
+        }
+    }
+
+    #[test]
+    fn stream_paced_by_credit() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Section(rand::random());
+        let stream_id = MessageId::new();
+        let part_count = 2 * STREAM_WINDOW + 1;
+        let content: Vec<u8> = (0..(part_count as usize * MAX_PART_LEN))
+            .map(|i| i as u8)
+            .collect();
+
+        let mut outgoing = OutgoingStreams::default();
+        let mut incoming = IncomingStreams::default();
+        let mut messages = outgoing.start(stream_id, src, dst, content.clone());
+        assert_eq!(messages.len(), STREAM_WINDOW as usize);
+
+        let mut received = Vec::new();
+        let mut complete = false;
+        while !messages.is_empty() {
+            let mut credits = Vec::new();
+            for message in &messages {
+                let (stream_id, index, part_count, payload) = part(message);
+                let progress =
+                    unwrap!(incoming.add(src, dst, stream_id, index, part_count, payload));
+                received.extend(progress.parts.into_iter().map(|(_, payload)| payload));
+                complete = progress.complete;
+                credits.extend(progress.credit);
+            }
+
+            messages = credits
+                .into_iter()
+                .flat_map(|credit| outgoing.add_credit(stream_id, &dst, credit))
+                .collect();
+            assert!(messages.len() <= STREAM_WINDOW as usize);
+        }
+
+        assert!(complete);
+        assert_eq!(received.concat(), content);
+        assert!(outgoing.remove_expired().is_empty());
+    }
+
+    #[test]
+    fn parts_delivered_in_order() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let stream_id = MessageId::new();
+        let mut incoming = IncomingStreams::default();
+
+        let progress = unwrap!(incoming.add(src, dst, stream_id, 1, 3, vec![1]));
+        assert_eq!(progress, StreamProgress::default());
+
+        let progress = unwrap!(incoming.add(src, dst, stream_id, 0, 3, vec![0]));
+        assert_eq!(progress.parts, vec![(0, vec![0]), (1, vec![1])]);
+        assert_eq!(progress.credit, Some(2 + STREAM_WINDOW));
+        assert!(!progress.complete);
+
+        let progress = unwrap!(incoming.add(src, dst, stream_id, 2, 3, vec![2]));
+        assert_eq!(progress.parts, vec![(2, vec![2])]);
+        assert!(progress.complete);
+    }
+
+    #[test]
+    fn parts_beyond_credit_rejected() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let stream_id = MessageId::new();
+        let part_count = 2 * MAX_PARTS_AHEAD;
+        let mut incoming = IncomingStreams::default();
+
+        assert!(incoming
+            .add(src, dst, stream_id, MAX_PARTS_AHEAD, part_count, vec![])
+            .is_err());
+        assert!(incoming
+            .add(src, dst, stream_id, MAX_PARTS_AHEAD - 1, part_count, vec![])
+            .is_ok());
+    }
+
+    #[test]
+    fn report_outgoing_streams_once_expired() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let stream_id = MessageId::new();
+        let content = vec![0; (STREAM_WINDOW as usize + 1) * MAX_PART_LEN];
+
+        let mut outgoing = OutgoingStreams::default();
+        let _ = outgoing.start(stream_id, src, dst, content);
+        assert!(outgoing.remove_expired().is_empty());
+
+        FakeClock::advance_time(STREAM_TIMEOUT.as_secs() * 1000 + 1);
+        assert_eq!(outgoing.remove_expired(), vec![(src, dst, stream_id)]);
+        assert!(outgoing
+            .add_credit(stream_id, &dst, 2 * STREAM_WINDOW)
+            .is_empty());
+    }
+
+    #[test]
+    fn limit_concurrent_streams_per_source() {
+        let src = Authority::Node(rand::random());
+        let dst = Authority::Node(rand::random());
+        let stream_ids: Vec<_> = (0..=MAX_STREAMS_PER_SRC)
+            .map(|_| MessageId::new())
+            .collect();
+        let mut incoming = IncomingStreams::default();
+
+        let last_id = stream_ids[MAX_STREAMS_PER_SRC];
+
+        for stream_id in &stream_ids[..MAX_STREAMS_PER_SRC] {
+            assert!(incoming.add(src, dst, *stream_id, 1, 2, vec![]).is_ok());
+        }
+        assert!(incoming.add(src, dst, last_id, 1, 2, vec![]).is_err());
+        // Parts of the streams already being received, and of other sources, are still accepted.
+        assert!(incoming.add(src, dst, stream_ids[0], 0, 2, vec![]).is_ok());
+        let other_src = Authority::Node(rand::random());
+        assert!(incoming.add(other_src, dst, last_id, 1, 2, vec![]).is_ok());
+    }
+}
@@ -11,10 +11,9 @@ pub use self::implementation::Timer;
 #[cfg(not(feature = "mock_base"))]
 mod implementation {
     use crate::{
-        action::Action,
+        action::{Action, ActionSender},
         time::{Duration, Instant},
     };
-    use crossbeam_channel as mpmc;
     use itertools::Itertools;
     use maidsafe_utilities::thread::{self, Joiner};
     use std::{cell::RefCell, collections::BTreeMap, rc::Rc, sync::mpsc};
@@ -38,7 +37,7 @@ mod implementation {
 
     impl Timer {
         /// Creates a new timer, passing a channel sender used to send `Timeout` events.
-        pub fn new(sender: mpmc::Sender<Action>) -> Self {
+        pub fn new(sender: ActionSender) -> Self {
             let (tx, rx) = mpsc::sync_channel(1);
 
             let worker = thread::named("Timer", move || Self::run(sender, rx));
@@ -72,7 +71,7 @@ mod implementation {
             })
         }
 
-        fn run(sender: mpmc::Sender<Action>, rx: mpsc::Receiver<Detail>) {
+        fn run(sender: ActionSender, rx: mpsc::Receiver<Detail>) {
             let mut deadlines: BTreeMap<Instant, Vec<u64>> = Default::default();
 
             loop {
@@ -120,13 +119,15 @@ mod implementation {
     #[cfg(test)]
     mod tests {
         use super::*;
-        use crate::action::Action;
+        use crate::{action::Action, event_stream::EventWaker};
+        use crossbeam_channel as mpmc;
         use std::thread;
         use std::time::{Duration, Instant};
 
         #[test]
         fn schedule() {
             let (action_tx, action_rx) = mpmc::unbounded();
+            let action_tx = ActionSender::new(action_tx, EventWaker::default());
             let interval = Duration::from_millis(500);
             let instant_when_added;
             let check_no_events_received = || {
@@ -185,7 +186,7 @@ mod implementation {
         #[test]
         fn heavy_duty_time_out() {
             let (action_tx, _) = mpmc::unbounded();
-            let timer = Timer::new(action_tx);
+            let timer = Timer::new(ActionSender::new(action_tx, EventWaker::default()));
             for _ in 0..1000 {
                 let _ = timer.schedule(Duration::new(0, 3000));
             }
@@ -196,10 +197,9 @@ mod implementation {
 #[cfg(feature = "mock_base")]
 mod implementation {
     use crate::{
-        action::Action,
+        action::ActionSender,
         time::{Duration, Instant},
     };
-    use crossbeam_channel as mpmc;
     use itertools::Itertools;
     use std::cell::RefCell;
     use std::collections::BTreeMap;
@@ -217,7 +217,7 @@ mod implementation {
     }
 
     impl Timer {
-        pub fn new(_action_sender: mpmc::Sender<Action>) -> Self {
+        pub fn new(_action_sender: ActionSender) -> Self {
             Timer {
                 inner: Rc::new(RefCell::new(Inner {
                     next_token: 0,
@@ -258,5 +258,9 @@ mod implementation {
             }
             expired_tokens
         }
+
+        pub fn next_deadline(&self) -> Option<Instant> {
+            self.inner.borrow().deadlines.keys().next().cloned()
+        }
     }
 }
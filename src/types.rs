@@ -6,13 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::xor_name::XorName;
+use crate::{crypto::Digest256, id::PublicId, xor_name::XorName};
 #[cfg(any(test, feature = "mock_base"))]
 use maidsafe_utilities::SeededRng;
 #[cfg(all(not(test), not(feature = "mock_base")))]
 use rand;
 #[cfg(any(test, feature = "mock_base"))]
 use rand::Rng;
+use std::collections::BTreeSet;
 
 /// Unique ID for messages
 ///
@@ -42,6 +43,11 @@ impl MessageId {
         MessageId(XorName([0; 32]))
     }
 
+    /// Generate the `MessageId` of the message with the given digest.
+    pub(crate) fn from_digest(digest: Digest256) -> MessageId {
+        MessageId(XorName(digest))
+    }
+
     /// Generate a new `MessageId` with contents extracted from lost node.
     pub fn from_lost_node(mut name: XorName) -> MessageId {
         name.0[0] = b'L';
@@ -82,6 +88,38 @@ impl Default for MessageId {
     }
 }
 
+/// Information about the client on whose behalf an elder relayed a message into the network.
+///
+/// It is part of the routing message signed by the relaying elder, so the recipient can trust it
+/// as much as it trusts that elder.
+#[derive(Ord, PartialOrd, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash)]
+pub struct RelayedClientInfo {
+    /// Public ID of the client which sent the message.
+    pub client_id: PublicId,
+    /// Name of the elder which relayed the message on behalf of the client.
+    pub proxy: XorName,
+    /// Version of the proxy's section info at the time the message was relayed.
+    pub section_version: u64,
+}
+
+/// Which elders of a neighbour section a node holds live connections to, as returned by
+/// `Node::neighbour_connectivity`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NeighbourConnectivity {
+    /// The elders we are connected to.
+    pub connected: BTreeSet<PublicId>,
+    /// The elders we are not connected to.
+    pub disconnected: BTreeSet<PublicId>,
+}
+
+impl NeighbourConnectivity {
+    /// Returns `true` if we are not connected to any elder of the section, so can't reach it
+    /// directly.
+    pub fn is_isolated(&self) -> bool {
+        self.connected.is_empty()
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::indexing_slicing)]
 mod tests {
@@ -6,14 +6,19 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::xor_name::XorName;
+use crate::{
+    routing_table::Prefix,
+    xor_name::{XorName, XOR_NAME_BITS},
+};
 #[cfg(any(test, feature = "mock_base"))]
 use maidsafe_utilities::SeededRng;
+use num_bigint::BigUint;
 use rand::{OsRng, Rng};
 use std::{
+    cmp,
     fmt::{self, Display, Formatter},
     ops::RangeInclusive,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 /// Display a "number" to the given number of decimal places
@@ -70,10 +75,102 @@ impl XorTargetInterval {
         Self(start, end)
     }
 
+    /// Create the XorTargetInterval of all the names matching `prefix`
+    pub fn from_prefix(prefix: &Prefix<XorName>) -> Self {
+        Self::new(prefix.range_inclusive())
+    }
+
     /// check if the inclusive range contains the value
     pub fn contains(&self, value: &XorName) -> bool {
         RangeInclusive::new(self.0, self.1).contains(value)
     }
+
+    /// Returns `true` if the interval contains no names, i.e. if its start is after its end.
+    pub fn is_empty(&self) -> bool {
+        self.0 > self.1
+    }
+
+    /// Returns a name drawn uniformly from the interval, or `None` if it is empty.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<XorName> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let start = BigUint::from_bytes_be(&(self.0).0);
+        let width = BigUint::from_bytes_be(&(self.1).0) - &start + BigUint::new(vec![1]);
+        let shift = XOR_NAME_BITS.saturating_sub(width.bits());
+        // Rejection sampling: draw offsets with as many bits as the width until one fits, which
+        // takes fewer than two draws on average.
+        loop {
+            let offset = BigUint::from_bytes_be(&rng.gen::<XorName>().0) >> shift;
+            if offset < width {
+                return Some(XorName::from_big_uint(start + offset));
+            }
+        }
+    }
+
+    /// Returns the part of the interval matching `prefix`, or `None` if they don't overlap.
+    pub fn intersection(&self, prefix: &Prefix<XorName>) -> Option<XorTargetInterval> {
+        let result = Self(
+            cmp::max(self.0, prefix.lower_bound()),
+            cmp::min(self.1, prefix.upper_bound()),
+        );
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Returns `true` if every name matching `prefix` is in the interval.
+    pub fn covers(&self, prefix: &Prefix<XorName>) -> bool {
+        self.0 <= prefix.lower_bound() && prefix.upper_bound() <= self.1
+    }
+
+    /// Returns `true` if every name in the interval matches one of the `prefixes`.
+    pub fn is_covered_by<'a, I>(&self, prefixes: I) -> bool
+    where
+        I: IntoIterator<Item = &'a Prefix<XorName>>,
+    {
+        if self.is_empty() {
+            return true;
+        }
+
+        let mut prefixes: Vec<_> = prefixes
+            .into_iter()
+            .filter(|prefix| self.intersection(prefix).is_some())
+            .collect();
+        prefixes.sort_by_key(|prefix| prefix.lower_bound());
+
+        // Walk the interval from its start, jumping to the end of each prefix containing the
+        // current name.
+        let mut next = self.0;
+        for prefix in prefixes {
+            if prefix.lower_bound() > next {
+                return false;
+            }
+            let upper_bound = prefix.upper_bound();
+            if upper_bound >= self.1 {
+                return true;
+            }
+            if upper_bound >= next {
+                next = successor(upper_bound);
+            }
+        }
+        false
+    }
+}
+
+// Returns the name following `name`. Must not be called with the largest name.
+fn successor(mut name: XorName) -> XorName {
+    for byte in name.0.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_add(1);
+        *byte = value;
+        if !overflow {
+            break;
+        }
+    }
+    name
 }
 
 impl Into<RangeInclusive<XorName>> for XorTargetInterval {
@@ -82,6 +179,19 @@ impl Into<RangeInclusive<XorName>> for XorTargetInterval {
     }
 }
 
+/// Returns the current time by the system clock, in milliseconds since the Unix epoch.
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(duration_ms)
+        .unwrap_or(0)
+}
+
+/// Returns `duration` in whole milliseconds.
+pub fn duration_ms(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
 #[cfg(any(test, feature = "mock_base"))]
 pub fn rand_index(exclusive_max: usize) -> usize {
     let mut rng = SeededRng::thread_rng();
@@ -141,8 +251,10 @@ pub fn new_rng() -> OsRng {
 
 #[cfg(test)]
 mod tests {
-    use super::DisplayDuration;
-    use std::time::Duration;
+    use super::{DisplayDuration, XorTargetInterval};
+    use crate::{routing_table::Prefix, xor_name::XorName};
+    use std::{str::FromStr, time::Duration};
+    use unwrap::unwrap;
 
     #[test]
     fn duration_formatting() {
@@ -159,4 +271,34 @@ mod tests {
             "1 seconds"
         );
     }
+
+    #[test]
+    fn xor_target_interval() {
+        let prefix = |bits| unwrap!(Prefix::<XorName>::from_str(bits));
+        let interval = XorTargetInterval(prefix("01").lower_bound(), prefix("10").upper_bound());
+        let mut rng = super::new_rng();
+
+        for _ in 0..100 {
+            let name = unwrap!(interval.sample(&mut rng));
+            assert!(interval.contains(&name));
+        }
+        let single = XorTargetInterval(prefix("1").lower_bound(), prefix("1").lower_bound());
+        assert_eq!(single.sample(&mut rng), Some(prefix("1").lower_bound()));
+        let empty = XorTargetInterval(prefix("1").lower_bound(), prefix("0").lower_bound());
+        assert!(empty.is_empty());
+        assert_eq!(empty.sample(&mut rng), None);
+
+        assert_eq!(
+            interval.intersection(&prefix("1")),
+            Some(XorTargetInterval::from_prefix(&prefix("10")))
+        );
+        assert_eq!(interval.intersection(&prefix("11")), None);
+        assert!(interval.covers(&prefix("011")));
+        assert!(!interval.covers(&prefix("0")));
+
+        assert!(interval.is_covered_by(&[prefix("01"), prefix("10")]));
+        assert!(interval.is_covered_by(&[prefix("0"), prefix("1")]));
+        assert!(!interval.is_covered_by(&[prefix("01"), prefix("101")]));
+        assert!(!interval.is_covered_by(&[prefix("10")]));
+    }
 }
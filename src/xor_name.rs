@@ -89,6 +89,39 @@ impl XorName {
         self.cmp_distance(lhs, rhs) != Ordering::Greater
     }
 
+    /// Compares the distance of the arguments to `self`. Returns `Less` if `lhs` is closer,
+    /// `Greater` if `rhs` is closer, and `Equal` if `lhs == rhs`. This is the order the routing
+    /// layer uses to determine closeness.
+    pub fn cmp_distance(&self, lhs: &XorName, rhs: &XorName) -> Ordering {
+        Xorable::cmp_distance(self, lhs, rhs)
+    }
+
+    /// Returns the XOR distance between `self` and `other`, as a name.
+    pub fn distance(&self, other: &XorName) -> XorName {
+        let mut result = XorName::default();
+        for (byte, (a, b)) in result.0.iter_mut().zip(self.0.iter().zip(other.0.iter())) {
+            *byte = a ^ b;
+        }
+        result
+    }
+
+    /// Returns the number of leading bits `self` has in common with `other`, i.e. the length of
+    /// their longest common prefix. This is `XOR_NAME_BITS` if they are equal.
+    pub fn common_leading_bits(&self, other: &XorName) -> usize {
+        self.common_prefix(other)
+    }
+
+    /// Returns the index of the bucket `other` belongs to from the point of view of `self`: the
+    /// index of the first bit in which they differ. Returns `None` if the names are equal.
+    pub fn bucket_index(&self, other: &XorName) -> Option<usize> {
+        let index = self.common_leading_bits(other);
+        if index < XOR_NAME_BITS {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
     /// Private function exposed in fmt Debug {:?} and Display {} traits.
     fn get_debug_id(&self) -> String {
         format!("{:02x}{:02x}{:02x}..", self.0[0], self.0[1], self.0[2])
@@ -97,7 +130,7 @@ impl XorName {
     /// Used to construct an XorName from a `BigUint`. `value` should not represent a number greater
     /// than or equal to `2^XOR_NAME_BITS`. If it does, the excessive most significant bits are
     /// ignored.
-    fn from_big_uint(value: BigUint) -> XorName {
+    pub(crate) fn from_big_uint(value: BigUint) -> XorName {
         let little_endian_value = value.to_bytes_le();
         if little_endian_value.len() > XOR_NAME_LEN {
             error!("This BigUint value exceeds the maximum capable of being held as an XorName.");
@@ -315,6 +348,24 @@ mod tests {
         assert!(!obj0.closer(&obj1, &obj0_clone));
     }
 
+    #[test]
+    fn distance_utilities() {
+        let name: XorName = rand::random();
+        let other = name.with_flipped_bit(9);
+        let further = name.with_flipped_bit(3);
+
+        assert_eq!(name.cmp_distance(&other, &further), Ordering::Less);
+        assert_eq!(name.cmp_distance(&further, &other), Ordering::Greater);
+        assert_eq!(name.cmp_distance(&other, &other), Ordering::Equal);
+        assert!(name.distance(&other) < name.distance(&further));
+        assert_eq!(name.distance(&name), XorName::default());
+
+        assert_eq!(name.common_leading_bits(&other), 9);
+        assert_eq!(name.common_leading_bits(&name), XOR_NAME_BITS);
+        assert_eq!(name.bucket_index(&further), Some(3));
+        assert_eq!(name.bucket_index(&name), None);
+    }
+
     #[test]
     fn format_random_nametype() {
         // test for Random XorName
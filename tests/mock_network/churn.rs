@@ -9,17 +9,19 @@
 use super::{
     clear_relocation_overrides, count_sections, create_connected_nodes,
     create_connected_nodes_until_split, current_sections, gen_range, gen_range_except,
-    poll_and_resend, verify_invariant_for_all_nodes, TestNode,
+    poll_and_resend, run_churn_scenario, set_relocation_overrides, verify_invariant_for_all_nodes,
+    ChurnStep, TestNode,
 };
 use itertools::Itertools;
 use rand::Rng;
 use routing::{
-    mock::Network, Authority, Event, EventStream, NetworkConfig, XorName, XorTargetInterval,
+    mock::Network, Authority, Event, EventStream, NetworkConfig, Prefix, XorName,
     QUORUM_DENOMINATOR, QUORUM_NUMERATOR,
 };
 use std::{
     cmp,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    iter,
 };
 
 /// Randomly removes some nodes, but <1/3 from each section and never node 0.
@@ -89,16 +91,8 @@ fn add_nodes<R: Rng>(
     nodes: &mut Vec<TestNode>,
     skip_some_prefixes: bool,
 ) -> BTreeSet<usize> {
-    let mut prefixes: BTreeSet<_> = nodes
-        .iter_mut()
-        .map(|node| {
-            let pfx = *node.our_prefix();
-            node.inner.set_next_relocation_dst(Some(pfx.lower_bound()));
-            node.inner
-                .set_next_relocation_interval(Some(XorTargetInterval::new(pfx.range_inclusive())));
-            pfx
-        })
-        .collect();
+    set_relocation_overrides(nodes, &[]);
+    let mut prefixes: BTreeSet<_> = nodes.iter().map(|node| *node.our_prefix()).collect();
 
     let mut added_nodes = Vec::new();
     while !prefixes.is_empty() {
@@ -153,7 +147,7 @@ fn check_added_indices(
         }
 
         loop {
-            match node.inner.try_next_ev() {
+            match node.try_next_ev() {
                 Err(_) => {
                     failed.push(index);
                     break;
@@ -325,7 +319,10 @@ impl Expectations {
         let mut section_msgs_received = HashMap::new(); // The count of received section messages.
         for node in nodes {
             while let Ok(event) = node.try_next_ev() {
-                if let Event::MessageReceived { content, src, dst } = event {
+                if let Event::MessageReceived {
+                    content, src, dst, ..
+                } = event
+                {
                     let key = MessageKey { content, src, dst };
 
                     if dst.is_multiple() {
@@ -502,6 +499,37 @@ fn aggressive_churn() {
     );
 }
 
+#[test]
+fn scripted_split_and_merge() {
+    let min_section_size = 3;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, 1);
+
+    let root = Prefix::default();
+    let first_half = *unwrap!([root.pushed(false), root.pushed(true)]
+        .iter()
+        .find(|prefix| prefix.matches(&nodes[0].name())));
+    let other_half = first_half.sibling();
+    let min_split_size = unwrap!(nodes[0].inner.min_split_size());
+
+    // Grow both halves until the section splits, then shrink the half without the first node
+    // until it merges back.
+    let steps: Vec<_> = iter::repeat(ChurnStep::AddNode(first_half))
+        .take(min_split_size)
+        .chain(iter::repeat(ChurnStep::AddNode(other_half)).take(min_split_size))
+        .chain(iter::once(ChurnStep::ExpectSplit(root)))
+        .chain(
+            iter::repeat(ChurnStep::RemoveNode(other_half))
+                .take(min_split_size - min_section_size + 1),
+        )
+        .chain(iter::once(ChurnStep::AdvanceTime(60)))
+        .chain(iter::once(ChurnStep::ExpectMerge(root)))
+        .collect();
+
+    run_churn_scenario(&mut rng, &network, &mut nodes, &steps);
+}
+
 #[test]
 fn messages_during_churn() {
     let min_section_size = 4;
@@ -0,0 +1,227 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{create_connected_nodes, poll_all, TestNode, MIN_SECTION_SIZE};
+use rand::Rng;
+use routing::{
+    mock::Network, Authority, Client, ClientAuthorisation, ClientAuthoriser, ClientEvent,
+    DispatchTable, Event, EventStream, InboundLimits, NetworkConfig, PublicId, QUORUM_DENOMINATOR,
+    QUORUM_NUMERATOR,
+};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+fn create_client(nodes: &mut [TestNode]) -> Client {
+    let config = NetworkConfig::client().with_hard_coded_contact(nodes[0].endpoint());
+    unwrap!(Client::builder().network_config(config).create())
+}
+
+// Polls the network, the nodes and the client until none of them has anything left to do.
+fn poll_with_client(nodes: &mut [TestNode], client: &mut Client) {
+    let network = nodes[0].network().clone();
+    loop {
+        network.poll();
+        let client_polled = client.poll();
+        let nodes_polled = poll_all(nodes);
+        if !client_polled && !nodes_polled {
+            break;
+        }
+    }
+}
+
+fn expect_client_event<F: Fn(&Event) -> bool>(client: &mut Client, predicate: F) -> Event {
+    loop {
+        match client.try_next_ev() {
+            Ok(event) => {
+                if predicate(&event) {
+                    return event;
+                }
+            }
+            Err(err) => panic!("Expected event not raised by the client: {:?}", err),
+        }
+    }
+}
+
+#[test]
+fn client_connects_to_proxy() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+    let mut client = create_client(&mut nodes);
+
+    poll_with_client(&mut nodes, &mut client);
+
+    let _ = expect_client_event(&mut client, |event| *event == Event::Connected);
+    expect_any_event!(
+        nodes[0],
+        Event::ClientEvent(ClientEvent::ConnectedToClient { .. })
+    );
+    // The client never joins the network.
+    assert!(nodes
+        .iter()
+        .all(|node| !node.close_names().contains(unwrap!(client.id()).name())));
+}
+
+#[test]
+#[cfg(not(feature = "mock_serialise"))]
+fn client_request_and_response() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+    let mut client = create_client(&mut nodes);
+    poll_with_client(&mut nodes, &mut client);
+    let _ = expect_client_event(&mut client, |event| *event == Event::Connected);
+
+    let request = bytes::Bytes::from(&b"request"[..]);
+    unwrap!(client.send_request(request.clone(), 0));
+    poll_with_client(&mut nodes, &mut client);
+
+    // The proxy echoes the request back as the response.
+    let (peer_addr, msg) = loop {
+        match nodes[0].try_next_ev() {
+            Ok(Event::ClientEvent(ClientEvent::NewMessageFromClient { peer_addr, msg })) => {
+                break (peer_addr, msg)
+            }
+            Ok(_) => (),
+            Err(err) => panic!("Request not received by the proxy: {:?}", err),
+        }
+    };
+    assert_eq!(msg, request);
+
+    unwrap!(nodes[0].inner.send_message_to_client(peer_addr, msg, 0));
+    poll_with_client(&mut nodes, &mut client);
+
+    match expect_client_event(&mut client, |event| match event {
+        Event::ResponseReceived { .. } => true,
+        _ => false,
+    }) {
+        Event::ResponseReceived { msg } => assert_eq!(msg, request),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn client_message_relayed_by_proxy() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+    let mut client = create_client(&mut nodes);
+    poll_with_client(&mut nodes, &mut client);
+    let _ = expect_client_event(&mut client, |event| *event == Event::Connected);
+
+    let client_id = unwrap!(client.id());
+    let proxy_name = nodes[0].name();
+    let dst = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(1024).collect();
+    unwrap!(client.send_message(dst, content.clone()));
+    poll_with_client(&mut nodes, &mut client);
+
+    // The recipients can attribute the message to the client which signed it.
+    let quorum = 1 + (MIN_SECTION_SIZE * QUORUM_NUMERATOR) / QUORUM_DENOMINATOR;
+    let mut message_received_count = 0;
+    for node in nodes.iter_mut().filter(|node| node.is_recipient(&dst)) {
+        loop {
+            match node.try_next_ev() {
+                Ok(Event::MessageReceived {
+                    content: ref received_content,
+                    src,
+                    client,
+                    ..
+                }) => {
+                    if content == *received_content {
+                        let client = unwrap!(client);
+                        assert_eq!(src, Authority::Node(proxy_name));
+                        assert_eq!(client.client_id, client_id);
+                        assert_eq!(client.proxy, proxy_name);
+                        message_received_count += 1;
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                _ => panic!("Event::MessageReceived not received"),
+            }
+        }
+    }
+
+    assert!(message_received_count >= quorum);
+}
+
+// Accepts every connection but no client.
+struct RejectClients;
+
+impl ClientAuthoriser for RejectClients {
+    fn authorise_connection(&self, _peer_addr: &SocketAddr) -> ClientAuthorisation {
+        ClientAuthorisation::Accept
+    }
+
+    fn authorise_client(&self, _peer_addr: &SocketAddr, _client_id: &PublicId) -> bool {
+        false
+    }
+}
+
+#[test]
+fn client_rejected_by_authoriser() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut rng = network.new_rng();
+    let mut dispatch_table = DispatchTable::default();
+    dispatch_table.set_client_authoriser(Arc::new(RejectClients));
+    let mut nodes = vec![TestNode::builder(&network)
+        .first()
+        .dispatch_table(dispatch_table)
+        .create()];
+    let mut client = create_client(&mut nodes);
+    poll_with_client(&mut nodes, &mut client);
+
+    // The proxy drops the client as soon as it identifies itself after bootstrapping, so it can't
+    // send anything through the proxy.
+    expect_any_event!(
+        nodes[0],
+        Event::ClientEvent(ClientEvent::ConnectionFailureToClient { .. })
+    );
+    let dst = Authority::Section(rng.gen());
+    assert!(client.send_message(dst, b"content".to_vec()).is_err());
+    assert!(client
+        .send_request(bytes::Bytes::from(&b"request"[..]), 0)
+        .is_err());
+}
+
+#[test]
+fn client_exceeding_inbound_limits_disconnected() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let limits = InboundLimits {
+        window: Duration::from_secs(60),
+        max_messages: 10,
+        max_bytes: usize::MAX,
+        blacklist_duration: Some(Duration::from_secs(60)),
+    };
+    let mut nodes = vec![TestNode::builder(&network)
+        .first()
+        .inbound_limits(limits)
+        .create()];
+    let mut client = create_client(&mut nodes);
+    poll_with_client(&mut nodes, &mut client);
+    let _ = expect_client_event(&mut client, |event| *event == Event::Connected);
+
+    let num_requests = 20;
+    for token in 0..num_requests {
+        unwrap!(client.send_request(bytes::Bytes::from(&b"request"[..]), token));
+    }
+    poll_with_client(&mut nodes, &mut client);
+
+    // The proxy stops passing on the requests once the client runs out of budget, and drops it.
+    let mut num_received = 0;
+    let mut disconnected = false;
+    while let Ok(event) = nodes[0].try_next_ev() {
+        match event {
+            Event::ClientEvent(ClientEvent::NewMessageFromClient { .. }) => num_received += 1,
+            Event::ClientEvent(ClientEvent::ConnectionFailureToClient { .. }) => {
+                disconnected = true
+            }
+            _ => (),
+        }
+    }
+    assert!(disconnected);
+    assert!(num_received < num_requests);
+}
@@ -7,7 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    create_connected_nodes, poll_all, poll_and_resend, verify_invariant_for_all_nodes, TestNode,
+    choose_node, create_connected_nodes, is_elder, poll_all, poll_and_resend,
+    verify_invariant_for_all_nodes, TestNode,
 };
 use rand::Rng;
 use routing::{mock::Network, Event, EventStream};
@@ -59,3 +60,24 @@ fn node_restart() {
 
     expect_next_event!(nodes[0], Event::RestartRequired);
 }
+
+#[test]
+fn node_loses_all_connections_at_once() {
+    // Idea of test: cut all the connections of an elder at once, as if its network interface went
+    // down. The elder should restart and the rest of the network should carry on without it.
+    let min_section_size = 5;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 2);
+
+    let index = unwrap!(choose_node(&mut rng, &nodes, is_elder));
+    let endpoint = nodes[index].endpoint();
+    network.disconnect_all(&endpoint);
+
+    let _ = poll_all(&mut nodes);
+    expect_any_event!(nodes[index], Event::RestartRequired);
+
+    let _ = nodes.remove(index);
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
@@ -55,7 +55,12 @@ fn merge(prefix_lengths: Vec<usize>) {
         for node in &mut *nodes {
             while let Ok(event) = node.try_next_ev() {
                 match event {
-                    Event::NodeAdded(..) | Event::NodeLost(..) | Event::TimerTicked => (),
+                    Event::NodeAdded(..)
+                    | Event::NodeLost(..)
+                    | Event::AgeIncreased(..)
+                    | Event::JoinAttemptFailed { .. }
+                    | Event::EldersChanged { .. }
+                    | Event::TimerTicked => (),
                     Event::SectionMerged(prefix) => {
                         if prefix.is_empty() {
                             merge_events_missing -= 1;
@@ -6,9 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{create_connected_nodes, poll_all};
+use super::{create_connected_nodes, is_elder, poll_all, poll_and_resend, TestNode};
+use fake_clock::FakeClock;
 use rand::Rng;
-use routing::{mock::Network, Authority, Event, EventStream, QUORUM_DENOMINATOR, QUORUM_NUMERATOR};
+use routing::{
+    mock::Network, test_consts, Authority, Event, EventStream, FullId, MessageId,
+    QUORUM_DENOMINATOR, QUORUM_NUMERATOR,
+};
+use std::collections::BTreeSet;
 
 #[test]
 fn send() {
@@ -51,6 +56,425 @@ fn send() {
     assert!(message_received_count >= quorum);
 }
 
+#[test]
+fn send_large_message() {
+    let min_section_size = 8;
+    let quorum = 1 + (min_section_size * QUORUM_NUMERATOR) / QUORUM_DENOMINATOR;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = rng.gen_range(0, nodes.len());
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    // Larger than the maximum size of a part under the mock network, so sent in several parts.
+    let content: Vec<_> = rng.gen_iter().take(3 * 1024 + 1).collect();
+    assert!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content.clone())
+        .is_ok());
+
+    let _ = poll_all(&mut nodes);
+
+    let mut message_received_count = 0;
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        while let Ok(event) = node.try_next_ev() {
+            match event {
+                Event::MessageReceived {
+                    content: ref received,
+                    ..
+                } if *received == content => message_received_count += 1,
+                Event::MessageIncomplete { .. } => panic!("Unexpected {:?}", event),
+                _ => (),
+            }
+        }
+    }
+
+    assert!(message_received_count >= quorum);
+}
+
+#[test]
+fn message_received_metadata() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    // A message signed by a single node reaches all its recipients with the same id, and the
+    // recipients other than the sender received it over the network. Sending the same content
+    // again makes a new message, with its own id.
+    let sender_index = rng.gen_range(0, nodes.len());
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(1024).collect();
+    for _ in 0..2 {
+        unwrap!(nodes[sender_index]
+            .inner
+            .send_message(src, dst, content.clone()));
+    }
+
+    let _ = poll_all(&mut nodes);
+
+    let mut msg_ids = BTreeSet::new();
+    for (index, node) in nodes.iter_mut().enumerate() {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::MessageReceived {
+                content: ref received,
+                msg_id,
+                hop_count,
+                secure,
+                ..
+            } = event
+            {
+                if *received == content {
+                    assert!(!secure);
+                    assert_eq!(hop_count == 0, index == sender_index);
+                    let _ = msg_ids.insert(msg_id);
+                }
+            }
+        }
+    }
+    assert_eq!(msg_ids.len(), 2);
+
+    // A message from our section is signed by the section.
+    let src = Authority::Section(rng.gen());
+    let dst = Authority::Node(nodes[0].name());
+    for node in &mut nodes {
+        unwrap!(node.inner.send_message(src, dst, content.clone()));
+    }
+
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(
+        nodes[0],
+        Event::MessageReceived {
+            content: ref received,
+            secure: true,
+            ..
+        } if *received == content
+    );
+}
+
+#[test]
+fn send_to_many() {
+    let min_section_size = 8;
+    let quorum = 1 + (min_section_size * QUORUM_NUMERATOR) / QUORUM_DENOMINATOR;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = rng.gen_range(0, nodes.len());
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst0 = Authority::Section(rng.gen());
+    let dst1 = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(3 * 1024 + 1).collect();
+    unwrap!(nodes[sender_index].inner.send_message_to_many(
+        src,
+        &[dst0, dst1, dst0],
+        content.clone()
+    ));
+
+    let _ = poll_all(&mut nodes);
+
+    let mut received_counts = [0; 2];
+    for node in nodes.iter_mut() {
+        let mut received = Vec::new();
+        while let Ok(event) = node.try_next_ev() {
+            match event {
+                Event::MessageReceived {
+                    content: ref received_content,
+                    dst,
+                    ..
+                } if *received_content == content => received.push(dst),
+                _ => (),
+            }
+        }
+
+        for (dst, count) in [dst0, dst1].iter().zip(received_counts.iter_mut()) {
+            // Listing a destination twice doesn't send it the message twice.
+            let copies = received.iter().filter(|received_dst| *received_dst == dst);
+            match copies.count() {
+                0 => (),
+                1 => *count += 1,
+                n => panic!("{:?} received the message {} times", dst, n),
+            }
+        }
+    }
+
+    assert!(received_counts.iter().all(|count| *count >= quorum));
+}
+
+#[test]
+fn send_with_ack() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(1024).collect();
+    let msg_id = MessageId::new();
+    unwrap!(nodes[sender_index]
+        .inner
+        .send_message_with_ack(src, dst, content, msg_id));
+
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(nodes[sender_index], Event::MessageDelivered(id) if id == msg_id);
+}
+
+#[test]
+fn send_with_ack_to_missing_node() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[sender_index].name());
+    // No node has this name, so nobody acknowledges the message.
+    let dst = Authority::Node(rng.gen());
+    let msg_id = MessageId::new();
+    unwrap!(nodes[sender_index]
+        .inner
+        .send_message_with_ack(src, dst, vec![1, 2, 3], msg_id));
+
+    let _ = poll_all(&mut nodes);
+    FakeClock::advance_time(test_consts::DELIVERY_ACK_TIMEOUT.as_secs() * 1000 + 1);
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(nodes[sender_index], Event::MessageDeliveryFailed(id) if id == msg_id);
+}
+
+#[test]
+fn send_traced() {
+    let min_section_size = 8;
+    let quorum = 1 + (min_section_size * QUORUM_NUMERATOR) / QUORUM_DENOMINATOR;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = rng.gen_range(0, nodes.len());
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    let trace_id = MessageId::new();
+    unwrap!(nodes[sender_index]
+        .inner
+        .send_traced_message(src, dst, vec![1, 2, 3], trace_id));
+
+    let _ = poll_all(&mut nodes);
+
+    let mut traced_count = 0;
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::MessageReceived {
+                trace_id: Some(id), ..
+            } = event
+            {
+                assert_eq!(id, trace_id);
+                traced_count += 1;
+            }
+        }
+    }
+
+    assert!(traced_count >= quorum);
+}
+
+#[test]
+fn send_to_missing_node() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[sender_index].name());
+    // No node has this name, so the section responsible for it reports the message undeliverable.
+    let dst = Authority::Node(rng.gen());
+    unwrap!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, vec![1, 2, 3]));
+
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(
+        nodes[sender_index],
+        Event::MessageUndeliverable { src: report_src, dst: report_dst }
+            if report_src == src && report_dst == dst
+    );
+}
+
+#[test]
+fn send_stream() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    // Enough parts for the sender to need more credit than it starts with.
+    let content: Vec<_> = rng.gen_iter().take(20 * 1024 + 1).collect();
+    let stream_id = MessageId::new();
+    unwrap!(nodes[sender_index]
+        .inner
+        .send_stream(src, dst, content.clone(), stream_id));
+
+    let _ = poll_all(&mut nodes);
+
+    for node in nodes
+        .iter_mut()
+        .filter(|node| is_elder(node) && node.is_recipient(&dst))
+    {
+        let mut received = Vec::new();
+        let mut complete = false;
+        while let Ok(event) = node.try_next_ev() {
+            match event {
+                Event::StreamPart {
+                    stream_id: id,
+                    index,
+                    content,
+                    ..
+                } if id == stream_id => {
+                    assert_eq!(index as usize, received.len());
+                    received.push(content);
+                }
+                Event::StreamComplete { stream_id: id, .. } if id == stream_id => complete = true,
+                _ => (),
+            }
+        }
+
+        assert!(complete);
+        assert_eq!(received.concat(), content);
+    }
+}
+
+#[test]
+fn send_request_and_response() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let elders: Vec<_> = nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| is_elder(node))
+        .map(|(index, _)| index)
+        .collect();
+    let (requester, responder, forger) = (elders[0], elders[1], elders[2]);
+    let src = Authority::Node(nodes[requester].name());
+    let dst = Authority::Node(nodes[responder].name());
+    let token = unwrap!(nodes[requester].inner.send_request(src, dst, vec![1, 2, 3]));
+
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(
+        nodes[responder],
+        Event::RequestReceived { content, token: id, .. } if content == vec![1, 2, 3] && id == token
+    );
+
+    // Only the destination of the request can respond to it.
+    let forger_src = Authority::Node(nodes[forger].name());
+    unwrap!(nodes[forger]
+        .inner
+        .send_response(forger_src, src, token, vec![0]));
+
+    let _ = poll_all(&mut nodes);
+
+    expect_no_event!(nodes[requester], Event::NodeResponseReceived { .. });
+    unwrap!(nodes[responder]
+        .inner
+        .send_response(dst, src, token, vec![4, 5, 6]));
+
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(
+        nodes[requester],
+        Event::NodeResponseReceived { token: id, content, src: response_src }
+            if content == vec![4, 5, 6] && id == token && response_src == dst
+    );
+
+    // A request not responded to times out.
+    let token = unwrap!(nodes[requester].inner.send_request(src, dst, vec![7, 8, 9]));
+
+    let _ = poll_all(&mut nodes);
+    FakeClock::advance_time(test_consts::REQUEST_TIMEOUT.as_secs() * 1000 + 1);
+    let _ = poll_all(&mut nodes);
+
+    expect_any_event!(nodes[requester], Event::RequestTimedOut(id) if id == token);
+}
+
+#[test]
+fn pending_request_survives_pause() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[sender_index].name());
+    // No node has this name, so the request is never responded to.
+    let dst = Authority::Node(rng.gen());
+    let token = unwrap!(nodes[sender_index]
+        .inner
+        .send_request(src, dst, vec![1, 2, 3]));
+
+    let state = unwrap!(nodes.remove(sender_index).inner.pause());
+    nodes.push(TestNode::resume(&network, state));
+    let _ = poll_all(&mut nodes);
+
+    FakeClock::advance_time(test_consts::REQUEST_TIMEOUT.as_secs() * 1000 + 1);
+    let _ = poll_all(&mut nodes);
+
+    let node = unwrap!(nodes.last_mut());
+    expect_any_event!(node, Event::RequestTimedOut(id) if id == token);
+}
+
+#[test]
+fn message_queued_at_crash_delivered_after_resume() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let sender_index = unwrap!(nodes.iter().position(is_elder));
+    let dst_index = (sender_index + 1) % nodes.len();
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst_name = nodes[dst_index].name();
+    let dst = Authority::Node(dst_name);
+
+    // Drop the sender's transport-level connection to the destination without the routing layer
+    // noticing, so the message waits for the connection to be re-established and is lost in the
+    // crash.
+    let sender_addr = nodes[sender_index].endpoint();
+    let dst_addr = nodes[dst_index].endpoint();
+    network.disconnect(&sender_addr, &dst_addr);
+
+    let content: Vec<_> = rng.gen_iter().take(1024).collect();
+    unwrap!(nodes[sender_index].inner.send_message_with_ack(
+        src,
+        dst,
+        content.clone(),
+        MessageId::new()
+    ));
+
+    let state = nodes.remove(sender_index).crash();
+    nodes.push(TestNode::resume(&network, state));
+    poll_and_resend(&mut nodes);
+
+    let node = unwrap!(nodes.iter_mut().find(|node| node.name() == dst_name));
+    expect_any_event!(
+        node,
+        Event::MessageReceived {
+            content: ref received,
+            ..
+        } if *received == content
+    );
+}
+
 #[test]
 fn send_and_receive() {
     let min_section_size = 8;
@@ -78,7 +502,9 @@ fn send_and_receive() {
     for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
         loop {
             match node.try_next_ev() {
-                Ok(Event::MessageReceived { content, src, dst }) => {
+                Ok(Event::MessageReceived {
+                    content, src, dst, ..
+                }) => {
                     request_received_count += 1;
                     if req_content == content {
                         if let Err(err) = node.inner.send_message(dst, src, res_content.clone()) {
@@ -100,7 +526,7 @@ fn send_and_receive() {
     let mut response_received_count = 0;
 
     loop {
-        match nodes[sender_index].inner.try_next_ev() {
+        match nodes[sender_index].try_next_ev() {
             Ok(Event::MessageReceived { content, .. }) => {
                 response_received_count += 1;
                 if res_content == content {
@@ -114,3 +540,81 @@ fn send_and_receive() {
 
     assert_eq!(response_received_count, 1);
 }
+
+#[test]
+fn relay_client_message() {
+    let min_section_size = 8;
+    let quorum = 1 + (min_section_size * QUORUM_NUMERATOR) / QUORUM_DENOMINATOR;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    let proxy_index = rng.gen_range(0, nodes.len());
+    let proxy_name = nodes[proxy_index].name();
+    let client_id = *FullId::new().public_id();
+    let dst = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(1024).collect();
+    assert!(nodes[proxy_index]
+        .inner
+        .relay_client_message(client_id, dst, content.clone())
+        .is_ok());
+
+    let _ = poll_all(&mut nodes);
+
+    let mut message_received_count = 0;
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        loop {
+            match node.try_next_ev() {
+                Ok(Event::MessageReceived {
+                    content: ref req_content,
+                    src,
+                    client,
+                    ..
+                }) => {
+                    if content == *req_content {
+                        // Recipients can tell the message came from a client and via which proxy.
+                        let client = unwrap!(client);
+                        assert_eq!(src, Authority::Node(proxy_name));
+                        assert_eq!(client.client_id, client_id);
+                        assert_eq!(client.proxy, proxy_name);
+                        message_received_count += 1;
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                _ => panic!("Event::MessageReceived not received"),
+            }
+        }
+    }
+
+    assert!(message_received_count >= quorum);
+}
+
+#[test]
+fn message_log() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+
+    network.set_message_log(true);
+
+    let sender_index = rng.gen_range(0, nodes.len());
+    let src = Authority::Node(nodes[sender_index].name());
+    let dst = Authority::Section(rng.gen());
+    let content: Vec<_> = rng.gen_iter().take(10).collect();
+    assert!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content)
+        .is_ok());
+
+    let _ = poll_all(&mut nodes);
+
+    let log = network.message_log();
+    let sender = nodes[sender_index].endpoint();
+    assert!(log
+        .messages()
+        .iter()
+        .any(|msg| msg.src == sender && msg.kind == "Hop(UserMessage)"));
+    assert!(log.to_mermaid().contains(": Hop(UserMessage)"));
+}
@@ -8,26 +8,42 @@
 
 mod accumulate;
 mod churn;
+mod client;
 mod drop;
 mod merge;
 mod messages;
 mod node_ageing;
+mod properties;
 mod secure_message_delivery;
+mod strategies;
+#[cfg(not(any(feature = "mock_parsec", feature = "mock_serialise")))]
+mod threaded;
 mod utils;
 
 pub use self::utils::{
     add_connected_nodes_until_one_away_from_split, add_connected_nodes_until_split,
-    clear_relocation_overrides, count_sections, create_connected_nodes,
-    create_connected_nodes_until_split, current_sections, gen_bytes, gen_range, gen_range_except,
-    poll_all, poll_and_resend, poll_and_resend_with_options, remove_nodes_which_failed_to_connect,
-    sort_nodes_by_distance_to, verify_invariant_for_all_nodes, Nodes, PollOptions, TestNode,
+    add_node_with_full_id, by_age, choose_node, choose_node_weighted, clear_relocation_overrides,
+    count_sections, create_connected_nodes, create_connected_nodes_until_split, current_sections,
+    current_topology, gen_bytes, gen_range, gen_range_except, in_prefix, is_adult, is_elder,
+    is_quiescent, poll_all, poll_and_resend, poll_and_resend_with_options,
+    remove_nodes_which_failed_to_connect, run_churn_scenario, set_relocation_overrides,
+    sort_nodes_by_distance_to, verify_invariant_for_all_nodes, with_relocation_overrides,
+    ChurnStep, Nodes, PollOptions, Slowness, TestNode,
 };
 use itertools::Itertools;
 use rand::Rng;
 use routing::{
-    mock::Network, Event, EventStream, NetworkConfig, Prefix, XorName, XorTargetInterval,
+    elders_info_for_test, gen_pfx_info_for_test, mock::Network, simulate_join, Authority, Event,
+    EventStream, FullId, InterfaceError, NetworkConfig, NetworkParams, Node, OldestElders, Prefix,
+    RoutingError, TransitionRecord, XorName, XorTargetInterval,
+};
+use std::{
+    cmp,
+    collections::BTreeSet,
+    iter,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use std::collections::BTreeSet;
 
 pub const MIN_SECTION_SIZE: usize = 3;
 
@@ -139,6 +155,124 @@ fn single_section() {
     verify_invariant_for_all_nodes(&network, &mut nodes);
 }
 
+#[test]
+fn section_elders_fully_meshed() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+    let endpoints = nodes.iter_mut().map(TestNode::endpoint).collect_vec();
+
+    let connections = network.connections();
+    for addr0 in &endpoints {
+        for addr1 in endpoints.iter().filter(|addr1| *addr1 != addr0) {
+            assert!(
+                connections.contains(&(*addr0, *addr1)),
+                "{} not connected to {}",
+                addr0,
+                addr1
+            );
+        }
+        assert_eq!(network.degree(addr0), endpoints.len() - 1);
+    }
+
+    assert_eq!(
+        network.connected_component(&endpoints[0]),
+        endpoints.iter().cloned().collect()
+    );
+}
+
+#[test]
+fn node_from_genesis() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let full_ids = (0..MIN_SECTION_SIZE).map(|_| FullId::new()).collect_vec();
+    let elder_ids: BTreeSet<_> = full_ids.iter().map(|id| *id.public_id()).collect();
+    let elders_info = unwrap!(elders_info_for_test(
+        elder_ids.clone(),
+        Prefix::default(),
+        0
+    ));
+
+    let mut node = TestNode::builder(&network)
+        .full_id(full_ids[0].clone())
+        .genesis(gen_pfx_info_for_test(elders_info))
+        .create();
+
+    expect_next_event!(node, Event::Connected);
+    assert!(node.inner.is_elder());
+    assert_eq!(*node.our_prefix(), Prefix::default());
+    assert_eq!(
+        node.inner.elders().cloned().collect::<BTreeSet<_>>(),
+        elder_ids
+    );
+}
+
+#[test]
+fn node_builder_validation() {
+    let _network = Network::new(MIN_SECTION_SIZE, None);
+    let is_invalid = |result: Result<Node, RoutingError>| match result {
+        Err(RoutingError::InvalidConfig(_)) => true,
+        _ => false,
+    };
+
+    assert!(is_invalid(
+        Node::builder().first(true).min_section_size(0).create()
+    ));
+
+    // A genesis node needs to be given the full id of one of the genesis elders.
+    let full_id = FullId::new();
+    let elders_info = unwrap!(elders_info_for_test(
+        iter::once(*full_id.public_id()).collect(),
+        Prefix::default(),
+        0
+    ));
+    assert!(is_invalid(
+        Node::builder()
+            .min_section_size(MIN_SECTION_SIZE)
+            .genesis(gen_pfx_info_for_test(elders_info))
+            .create()
+    ));
+}
+
+#[test]
+fn node_event_channel() {
+    let _network = Network::new(MIN_SECTION_SIZE, None);
+    let mut node = unwrap!(Node::builder()
+        .first(true)
+        .min_section_size(MIN_SECTION_SIZE)
+        .event_channel()
+        .create());
+    let event_rx = unwrap!(node.event_receiver());
+
+    // The node reads its events from the same channel, so each event is received only once.
+    assert_eq!(unwrap!(node.try_next_ev()), Event::Connected);
+    assert!(event_rx.try_recv().is_err());
+}
+
+#[test]
+fn ipv6_section() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    network.set_ipv6(true);
+
+    let mut nodes = create_connected_nodes(&network, 2 * MIN_SECTION_SIZE);
+    assert!(nodes.iter_mut().all(|node| node.endpoint().is_ipv6()));
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn poll_until_quiescent() {
+    let horizon = Duration::from_secs(1);
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, 2 * MIN_SECTION_SIZE);
+
+    let content = gen_bytes(&mut network.new_rng(), 64);
+    let src = Authority::Node(nodes[0].name());
+    let dst = Authority::Section(nodes[1].name());
+    unwrap!(nodes[0].inner.send_message(src, dst, content));
+
+    poll_and_resend_with_options(&mut nodes, PollOptions::default().until_quiescent(horizon));
+    assert!(is_quiescent(&nodes, horizon));
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
 #[test]
 fn less_than_section_size_nodes() {
     test_nodes(80)
@@ -253,7 +387,7 @@ fn simultaneous_joining_nodes(
         // Set the specified relocation interval on the nodes of the given prefixes
         let relocation_interval = setup
             .dst_relocation_interval_prefix
-            .map(|prefix| XorTargetInterval::new(prefix.range_inclusive()));
+            .map(|prefix| XorTargetInterval::from_prefix(&prefix));
         nodes_with_prefix_mut(&mut nodes, &setup.dst_section_prefix).for_each(|node| {
             node.inner
                 .set_next_relocation_interval(relocation_interval.clone())
@@ -330,7 +464,7 @@ fn simultaneous_joining_nodes(
 
     poll_and_resend_with_options(
         &mut nodes,
-        PollOptions::default().stop_if(all_new_nodes_are_elders),
+        PollOptions::default().stop_when("all new nodes are elders", all_new_nodes_are_elders),
     );
 
     //
@@ -499,6 +633,7 @@ fn vote_prune() {
 
     let network = Network::new(MIN_SECTION_SIZE, None);
     let mut nodes = create_connected_nodes(&network, max_section_size);
+    let mut gossip_sizes = vec![0; nodes.len()];
 
     for _ in 0..steps {
         if nodes.len() < max_section_size {
@@ -512,13 +647,402 @@ fn vote_prune() {
         }
 
         poll_and_resend(&mut nodes);
+
+        // Once the network settles, all our votes must have reached consensus and been polled.
+        for (node, gossip_size) in nodes.iter().zip(gossip_sizes.iter_mut()) {
+            let metrics = node.parsec_metrics();
+            assert_eq!(metrics.observation_backlog, 0);
+            *gossip_size = cmp::max(*gossip_size, metrics.gossip_size);
+        }
     }
 
+    // The graphs must have grown along the way.
+    assert!(gossip_sizes.iter().all(|&size| size > 0));
+
     assert!(nodes
         .iter()
         .all(|node| unwrap!(node.inner.parsec_prune_accumulated()) > 0));
 }
 
+#[test]
+fn explicit_parsec_prune() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    for node in &mut nodes {
+        unwrap!(node.inner.prune_parsec());
+    }
+    poll_and_resend(&mut nodes);
+
+    assert!(nodes
+        .iter()
+        .all(|node| unwrap!(node.inner.parsec_prune_accumulated()) > 0));
+}
+
+#[test]
+fn bootstrap_off_neighbour_joins_directly() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1]);
+    network.set_message_log(true);
+
+    let prefix_0 = Prefix::default().pushed(false);
+    let prefix_1 = Prefix::default().pushed(true);
+    let bootstrap_index = unwrap!(nodes
+        .iter()
+        .position(|node| is_elder(node) && prefix_0.matches(&node.name())));
+
+    let mut node = TestNode::builder(&network)
+        .network_config(
+            NetworkConfig::node().with_hard_coded_contact(nodes[bootstrap_index].endpoint()),
+        )
+        .full_id(FullId::within_range(&prefix_1.range_inclusive()))
+        .create();
+    let endpoint = node.endpoint();
+    nodes.push(node);
+
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default()
+            .continue_if(|nodes| !unwrap!(nodes.last()).inner.is_elder())
+            .fire_join_timeout(false),
+    );
+
+    let node = unwrap!(nodes.last());
+    assert!(prefix_1.matches(&node.name()));
+
+    // The neighbour elder sent us straight to our section instead of redirecting us to bootstrap
+    // off it again.
+    let bootstrap_requests = network
+        .message_log()
+        .messages()
+        .iter()
+        .filter(|msg| msg.src == endpoint && msg.kind == "Direct(BootstrapRequest)")
+        .count();
+    assert_eq!(bootstrap_requests, 1);
+}
+
+#[test]
+fn elder_leaves_gracefully() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE + 1);
+
+    let leaving_index = unwrap!(nodes.iter().rposition(is_elder));
+    let leaving_id = nodes[leaving_index].id();
+    unwrap!(nodes[leaving_index].inner.initiate_leave());
+
+    // Poll until the section voted the node offline and it terminated.
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default().continue_if(move |nodes| nodes[leaving_index].inner.id().is_ok()),
+    );
+
+    let mut leaving_node = nodes.remove(leaving_index);
+    expect_any_event!(leaving_node, Event::Terminated);
+
+    // The rest of the section removes us from both its members and its elders.
+    poll_and_resend(&mut nodes);
+    for node in &nodes {
+        assert!(!node.inner.member_ages().contains_key(&leaving_id));
+        assert!(node.inner.elders().all(|pub_id| *pub_id != leaving_id));
+    }
+}
+
+#[test]
+fn adult_sends_message() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    // Every member is eventually promoted, so send the message while the new node is still an
+    // adult.
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&network).network_config(config).create());
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default()
+            .continue_if(|nodes| !nodes.last().map_or(false, is_adult))
+            .fire_join_timeout(false),
+    );
+
+    let adult_index = nodes.len() - 1;
+    assert!(is_adult(&nodes[adult_index]));
+    let elder_index = unwrap!(nodes.iter().position(is_elder));
+    let src = Authority::Node(nodes[adult_index].name());
+    let dst = Authority::Node(nodes[elder_index].name());
+    let content = gen_bytes(&mut network.new_rng(), 32);
+
+    // Adults can't send on behalf of their section.
+    match nodes[adult_index].inner.send_message(
+        Authority::Section(nodes[adult_index].name()),
+        dst,
+        content.clone(),
+    ) {
+        Err(InterfaceError::InvalidSource) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    unwrap!(nodes[adult_index]
+        .inner
+        .send_message(src, dst, content.clone()));
+    poll_and_resend(&mut nodes);
+
+    expect_any_event!(
+        nodes[elder_index],
+        Event::MessageReceived {
+            content: ref received,
+            src: received_src,
+            ..
+        } if *received == content && received_src == src
+    );
+}
+
+#[test]
+fn elder_steps_down() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE + 1);
+
+    let index = unwrap!(nodes.iter().rposition(is_elder));
+    let id = nodes[index].id();
+    unwrap!(nodes[index].inner.step_down());
+    poll_and_resend(&mut nodes);
+
+    // It becomes an adult, but the section keeps it as a member.
+    assert!(is_adult(&nodes[index]));
+    assert_eq!(
+        nodes[index]
+            .inner
+            .transition_history()
+            .last()
+            .map(|record| record.transition),
+        Some("Demote")
+    );
+    for node in nodes.iter().filter(|node| node.id() != id) {
+        assert!(node.inner.member_ages().contains_key(&id));
+        assert!(node.inner.elders().all(|pub_id| *pub_id != id));
+    }
+
+    // The remaining elders can't spare another one.
+    let index = unwrap!(nodes.iter().position(is_elder));
+    match nodes[index].inner.step_down() {
+        Err(InterfaceError::InvalidState) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn adult_rejoins_after_restart() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    let full_id = FullId::new();
+    let id = *full_id.public_id();
+    add_node_with_full_id(&network, &mut nodes, full_id.clone());
+
+    // Make the node an adult, so that its section keeps it as a member while it restarts.
+    unwrap!(unwrap!(nodes.last_mut()).inner.step_down());
+    poll_and_resend(&mut nodes);
+    let _ = nodes.pop();
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(
+        TestNode::builder(&network)
+            .network_config(config)
+            .full_id(full_id)
+            .create(),
+    );
+    poll_and_resend(&mut nodes);
+
+    // It rejoined as the same member instead of being voted offline.
+    let node = unwrap!(nodes.last());
+    assert!(is_adult(node));
+    assert_eq!(node.id(), id);
+    for node in nodes.iter().filter(|node| is_elder(node)) {
+        assert!(node.inner.member_ages().contains_key(&id));
+    }
+}
+
+#[test]
+fn state_introspection() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    // The first node starts as an elder.
+    assert_eq!(nodes[0].inner.state_name(), "Elder");
+    assert_eq!(nodes[0].inner.transition_history().count(), 0);
+
+    for node in &nodes[1..] {
+        assert_eq!(node.inner.state_name(), "Elder");
+
+        let history: Vec<_> = node
+            .inner
+            .transition_history()
+            .map(|record| (record.from, record.transition, record.to))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                ("BootstrappingPeer", "IntoJoining", "JoiningPeer"),
+                ("JoiningPeer", "IntoAdult", "Adult"),
+                ("Adult", "IntoElder", "Elder"),
+            ]
+        );
+    }
+}
+
+#[test]
+fn transition_observer() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    let mut node = TestNode::builder(&network).network_config(config).create();
+    let observed_clone = Arc::clone(&observed);
+    node.inner
+        .set_transition_observer(move |record: &TransitionRecord| {
+            unwrap!(observed_clone.lock()).push(record.clone())
+        });
+    nodes.push(node);
+    poll_and_resend(&mut nodes);
+
+    let node = unwrap!(nodes.last());
+    assert!(is_elder(node));
+    let history: Vec<_> = node.inner.transition_history().cloned().collect();
+    assert_eq!(*unwrap!(observed.lock()), history);
+}
+
+#[test]
+fn accumulator_metrics() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    for node in &nodes {
+        let metrics = unwrap!(node.inner.accumulator_metrics());
+        assert_eq!(metrics.pending_ages.len(), metrics.pending_events);
+        assert_eq!(
+            metrics.pending_per_kind.values().sum::<usize>(),
+            metrics.pending_events
+        );
+        assert_eq!(
+            node.inner.elder_state_unchecked().accumulator_metrics(),
+            metrics
+        );
+    }
+}
+
+#[test]
+fn request_proof_chain() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    unwrap!(nodes[0].inner.request_proof_chain(0));
+    poll_and_resend(&mut nodes);
+
+    let proof_chain = loop {
+        match nodes[0].try_next_ev() {
+            Ok(Event::ProofChainReceived(proof_chain)) => break proof_chain,
+            Ok(_) => (),
+            Err(_) => panic!("Proof chain not received"),
+        }
+    };
+    assert!(proof_chain.validate());
+}
+
+#[test]
+fn namespaced_user_events() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    // The same payload voted for in two namespaces reaches consensus once in each.
+    for node in &mut nodes {
+        node.inner.vote_for_user_event("first", vec![1]);
+        node.inner.vote_for_user_event("second", vec![1]);
+    }
+    poll_and_resend(&mut nodes);
+
+    let expected_namespaces: BTreeSet<_> = vec!["first".to_string(), "second".to_string()]
+        .into_iter()
+        .collect();
+    for node in &mut nodes {
+        let mut namespaces = BTreeSet::new();
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::UserEventConsensused { namespace, payload } = event {
+                assert_eq!(payload, vec![1]);
+                assert!(namespaces.insert(namespace));
+            }
+        }
+        assert_eq!(namespaces, expected_namespaces);
+    }
+}
+
+#[test]
+fn chain_view() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    let expected_elders: BTreeSet<_> = nodes.iter().map(|node| node.name()).collect();
+    for node in &nodes {
+        let view = unwrap!(node.inner.chain_view());
+        assert_eq!(*view.our_prefix(), Prefix::default());
+        assert_eq!(view.our_info().prefix(), view.our_prefix());
+        assert_eq!(view.our_infos().last(), Some(view.our_info()));
+        assert_eq!(
+            view.our_elders()
+                .map(|pub_id| *pub_id.name())
+                .collect::<BTreeSet<_>>(),
+            expected_elders
+        );
+        assert_eq!(view.section_elders(&Prefix::default()), expected_elders);
+        assert!(view.neighbour_prefixes().is_empty());
+    }
+}
+
+#[test]
+fn neighbour_connectivity() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1]);
+
+    for node in nodes.iter().filter(|node| node.inner.is_elder()) {
+        let connectivity = node.inner.neighbour_connectivity();
+        assert_eq!(
+            connectivity.keys().cloned().collect::<BTreeSet<_>>(),
+            node.inner.neighbour_prefixes()
+        );
+        for status in connectivity.values() {
+            assert!(!status.is_isolated());
+            assert!(status.disconnected.is_empty());
+        }
+    }
+
+    // Cut the connection from the side of a neighbour elder, so that only we see it fail.
+    let index = unwrap!(nodes.iter().position(is_elder));
+    let prefix = *nodes[index].our_prefix();
+    let neighbour_index = unwrap!(nodes
+        .iter()
+        .position(|node| is_elder(node) && !prefix.matches(&node.name())));
+    let neighbour_id = nodes[neighbour_index].id();
+    let addr = nodes[index].endpoint();
+    let neighbour_addr = nodes[neighbour_index].endpoint();
+    network.disconnect(&neighbour_addr, &addr);
+    network.poll();
+    let _ = nodes[index].poll();
+
+    let connectivity = nodes[index].inner.neighbour_connectivity();
+    assert!(connectivity
+        .values()
+        .any(|status| status.disconnected.contains(&neighbour_id)));
+
+    for node in nodes.iter_mut().filter(|node| node.inner.is_elder()) {
+        unwrap!(node.inner.reconnect_to_neighbours());
+    }
+    poll_all(&mut nodes);
+
+    let connectivity = nodes[index].inner.neighbour_connectivity();
+    assert!(connectivity
+        .values()
+        .all(|status| status.disconnected.is_empty()));
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
 #[test]
 fn node_pause_and_resume() {
     let network = Network::new(MIN_SECTION_SIZE, None);
@@ -543,3 +1067,237 @@ fn node_pause_and_resume() {
     poll_and_resend(&mut nodes);
     verify_invariant_for_all_nodes(&network, &mut nodes);
 }
+
+#[test]
+fn adult_pause_and_resume() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    let full_id = FullId::new();
+    let id = *full_id.public_id();
+    add_node_with_full_id(&network, &mut nodes, full_id);
+    unwrap!(unwrap!(nodes.last_mut()).inner.step_down());
+    poll_and_resend(&mut nodes);
+    assert!(is_adult(unwrap!(nodes.last())));
+
+    let state = unwrap!(unwrap!(nodes.pop()).inner.pause());
+    poll_and_resend(&mut nodes);
+
+    nodes.push(TestNode::resume(&network, state));
+    poll_and_resend(&mut nodes);
+
+    let node = unwrap!(nodes.last());
+    assert!(is_adult(node));
+    assert_eq!(node.id(), id);
+    for node in nodes.iter().filter(|node| is_elder(node)) {
+        assert!(node.inner.member_ages().contains_key(&id));
+    }
+}
+
+#[test]
+fn joining_peer_pause_and_resume() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&network).network_config(config).create());
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default()
+            .continue_if(|nodes| unwrap!(nodes.last()).inner.state_name() != "JoiningPeer")
+            .fire_join_timeout(false),
+    );
+
+    let state = unwrap!(unwrap!(nodes.pop()).inner.pause());
+    nodes.push(TestNode::resume(&network, state));
+    poll_and_resend(&mut nodes);
+
+    assert!(is_elder(unwrap!(nodes.last())));
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn node_crash_and_restart() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, 8);
+
+    let index = network.new_rng().gen_range(0, nodes.len());
+    let node = nodes.remove(index);
+    let id = node.id();
+    let state = node.crash();
+
+    // Let the other nodes notice the lost connections and carry on without the crashed node.
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+
+    // Restart the node with the same identity and its persisted chain.
+    nodes.push(TestNode::resume(&network, state));
+    poll_and_resend(&mut nodes);
+
+    let node = unwrap!(nodes.pop());
+    assert_eq!(node.id(), id);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn elders_bounded_by_selection() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let elder_size = MIN_SECTION_SIZE + 1;
+    let params = NetworkParams {
+        elder_selection: Arc::new(OldestElders { elder_size }),
+        ..NetworkParams::default()
+    };
+
+    // Not enough nodes to split, but more than the elders selected.
+    let mut nodes = vec![TestNode::builder(&network)
+        .first()
+        .network_params(params.clone())
+        .create()];
+    let endpoint = nodes[0].endpoint();
+    for _ in 1..(2 * elder_size - 1) {
+        let config = NetworkConfig::node().with_hard_coded_contact(endpoint);
+        nodes.push(
+            TestNode::builder(&network)
+                .network_config(config)
+                .network_params(params.clone())
+                .create(),
+        );
+        poll_and_resend(&mut nodes);
+    }
+
+    // The elders displaced by the newcomers stepped down.
+    assert_eq!(
+        nodes.iter().filter(|node| is_elder(node)).count(),
+        elder_size
+    );
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+
+    // A lost elder is replaced by the member selected in its place.
+    let index = unwrap!(nodes.iter().position(is_elder));
+    drop(nodes.remove(index));
+    poll_and_resend(&mut nodes);
+
+    assert_eq!(
+        nodes.iter().filter(|node| is_elder(node)).count(),
+        elder_size
+    );
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn simulate_join_matches_actual_join() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+    // Keep adding nodes until one of them triggers a split, checking each prediction against what
+    // actually happened.
+    for _ in 0..50 {
+        let topology = current_topology(&network, &nodes);
+        let full_id = FullId::new();
+        let outcome = unwrap!(simulate_join(full_id.public_id().name(), &topology));
+
+        add_node_with_full_id(&network, &mut nodes, full_id);
+        poll_and_resend(&mut nodes);
+
+        let node = unwrap!(nodes.last());
+        assert_eq!(*node.our_prefix(), outcome.final_section);
+        assert_eq!(unwrap!(node.inner.our_age()), outcome.age);
+
+        if let Some((prefix_0, prefix_1)) = outcome.split {
+            let sections: BTreeSet<_> = current_sections(&nodes).collect();
+            assert!(sections.contains(&prefix_0));
+            assert!(sections.contains(&prefix_1));
+            assert!(!sections.contains(&outcome.section));
+            verify_invariant_for_all_nodes(&network, &mut nodes);
+            return;
+        }
+
+        assert_eq!(*node.our_prefix(), outcome.section);
+    }
+
+    panic!("No split happened after adding 50 nodes.");
+}
+
+#[test]
+fn duplicate_and_reordered_delivery() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    network.set_duplicate_delivery(Some(4));
+
+    // Every message handler must tolerate receiving the same message more than once and in
+    // arbitrary order, so the network should form and split just as it does otherwise.
+    let mut nodes = create_connected_nodes(&network, 8);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&network).network_config(config).create());
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn joining_node_event_history() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+    for node in nodes.iter_mut() {
+        node.start_recording();
+    }
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(
+        TestNode::builder(&network)
+            .network_config(config)
+            .record_events()
+            .create(),
+    );
+    poll_and_resend(&mut nodes);
+
+    let (new_node, old_nodes) = unwrap!(nodes.split_last());
+    expect_event_sequence!(new_node, Event::Connected, Event::NodeAdded(_));
+    expect_no_event_matching!(new_node, Event::RestartRequired);
+    expect_no_event_matching!(new_node, Event::Terminated);
+    assert!(new_node
+        .recorded_events()
+        .windows(2)
+        .all(|pair| pair[0].timestamp < pair[1].timestamp));
+
+    for node in old_nodes {
+        assert!(node
+            .recorded_events()
+            .iter()
+            .any(|recorded| recorded.event == Event::NodeAdded(new_node.name())));
+        expect_no_event_matching!(node, Event::NodeLost(_));
+    }
+}
+
+#[test]
+fn slow_node_bounded_processing() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, 2 * MIN_SECTION_SIZE);
+
+    let index = network.new_rng().gen_range(0, nodes.len());
+    nodes[index].set_slowness(Some(Slowness::MaxEventsPerPoll(1)));
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&network).network_config(config).create());
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
+
+#[test]
+fn slow_node_processing_delay() {
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut nodes = create_connected_nodes(&network, 2 * MIN_SECTION_SIZE);
+
+    // The slow node lags behind long enough for its peers to time out and resend.
+    let index = network.new_rng().gen_range(1, nodes.len());
+    nodes[index].set_slowness(Some(Slowness::ProcessingDelay(Duration::from_secs(5))));
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&network).network_config(config).create());
+    poll_and_resend(&mut nodes);
+
+    // Once it catches up, the network is consistent again.
+    nodes[index].set_slowness(None);
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&network, &mut nodes);
+}
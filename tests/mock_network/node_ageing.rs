@@ -7,11 +7,12 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    add_connected_nodes_until_one_away_from_split, create_connected_nodes_until_split,
-    current_sections, poll_and_resend_with_options, PollOptions, TestNode, MIN_SECTION_SIZE,
+    add_connected_nodes_until_one_away_from_split, choose_node, create_connected_nodes_until_split,
+    current_sections, in_prefix, poll_and_resend_with_options, PollOptions, TestNode,
+    MIN_SECTION_SIZE,
 };
 use rand::Rng;
-use routing::{mock::Network, FullId, NetworkConfig, Prefix, XorName};
+use routing::{mock::Network, Event, EventStream, FullId, NetworkConfig, Prefix, XorName};
 use std::{iter, slice};
 
 #[test]
@@ -26,7 +27,7 @@ fn relocate_without_split() {
     let source_prefix = *unwrap!(rng.choose(&prefixes));
     let target_prefix = *choose_other_prefix(&mut rng, &prefixes, &source_prefix);
 
-    let relocate_index = choose_node_from_section(&mut rng, &nodes, &source_prefix);
+    let relocate_index = unwrap!(choose_node(&mut rng, &nodes, in_prefix(source_prefix)));
     let relocate_id = nodes[relocate_index].id();
 
     let destination = gen_name_in_prefix(&mut rng, &target_prefix);
@@ -48,7 +49,20 @@ fn relocate_without_split() {
                 !relocation_complete(nodes, relocate_index, &source_prefix, &target_prefix)
             })
             .fire_join_timeout(false),
-    )
+    );
+
+    // The other members of the source section were told about the relocation.
+    for (index, node) in nodes.iter_mut().enumerate() {
+        if index != relocate_index && source_prefix.matches(&node.name()) {
+            expect_any_event!(
+                node,
+                Event::RelocationStarted {
+                    name,
+                    destination: relocated_to,
+                } if name == *relocate_id.name() && relocated_to == destination
+            );
+        }
+    }
 }
 
 #[test]
@@ -71,7 +85,7 @@ fn relocate_causing_split() {
         slice::from_ref(&target_prefix),
     );
 
-    let relocate_index = choose_node_from_section(&mut rng, &nodes, &source_prefix);
+    let relocate_index = unwrap!(choose_node(&mut rng, &nodes, in_prefix(source_prefix)));
     let relocate_id = nodes[relocate_index].id();
 
     let destination = gen_name_in_prefix(&mut rng, &target_prefix);
@@ -122,7 +136,7 @@ fn relocate_during_split() {
         slice::from_ref(&target_prefix),
     );
 
-    let relocate_index = choose_node_from_section(&mut rng, &nodes, &source_prefix);
+    let relocate_index = unwrap!(choose_node(&mut rng, &nodes, in_prefix(source_prefix)));
     let relocate_id = nodes[relocate_index].id();
 
     let destination = gen_name_in_prefix(&mut rng, &target_prefix);
@@ -163,6 +177,83 @@ fn relocate_during_split() {
     )
 }
 
+#[test]
+fn relocate_resumes_after_restart() {
+    // Trigger relocation of a node, then restart it before it joins the destination section and
+    // check it completes the relocation with the age it was granted.
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1]);
+
+    let prefixes: Vec<_> = current_sections(&nodes).collect();
+    let source_prefix = *unwrap!(rng.choose(&prefixes));
+    let target_prefix = *choose_other_prefix(&mut rng, &prefixes, &source_prefix);
+
+    let relocate_index = unwrap!(choose_node(&mut rng, &nodes, in_prefix(source_prefix)));
+    let relocate_id = nodes[relocate_index].id();
+
+    let destination = gen_name_in_prefix(&mut rng, &target_prefix);
+
+    for node in nodes
+        .iter_mut()
+        .filter(|node| source_prefix.matches(&node.name()))
+    {
+        node.inner.trigger_relocation(relocate_id, destination);
+    }
+
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default()
+            .continue_if(move |nodes| nodes[relocate_index].inner.relocation_state().is_none())
+            .fire_join_timeout(false),
+    );
+
+    let relocation = unwrap!(nodes[relocate_index].inner.relocation_state());
+    assert_eq!(*relocation.destination(), destination);
+    let age = relocation.age();
+
+    nodes[relocate_index] = TestNode::builder(&network)
+        .resume_relocation(relocation)
+        .create();
+
+    poll_and_resend_with_options(
+        &mut nodes,
+        PollOptions::default()
+            .continue_if(move |nodes| {
+                !relocation_complete(nodes, relocate_index, &source_prefix, &target_prefix)
+            })
+            .fire_join_timeout(false),
+    );
+
+    let relocated_id = nodes[relocate_index].id();
+    for node in nodes
+        .iter()
+        .filter(|node| target_prefix.matches(&node.name()))
+    {
+        assert_eq!(node.inner.member_ages().get(&relocated_id), Some(&age));
+    }
+}
+
+#[test]
+fn member_ages_agree_with_our_age() {
+    // Create a network of two sections and check that every node reports the same age for itself
+    // as the other members of its section report for it.
+    let network = Network::new(MIN_SECTION_SIZE, None);
+    let nodes = create_connected_nodes_until_split(&network, vec![1, 1]);
+
+    for node in &nodes {
+        let our_age = unwrap!(node.inner.our_age());
+        let our_prefix = *node.our_prefix();
+
+        for other in nodes
+            .iter()
+            .filter(|other| our_prefix.matches(&other.name()))
+        {
+            assert_eq!(other.inner.member_ages().get(&node.id()), Some(&our_age));
+        }
+    }
+}
+
 fn choose_other_prefix<'a, R: Rng>(
     rng: &mut R,
     prefixes: &'a [Prefix<XorName>],
@@ -179,16 +270,6 @@ fn gen_name_in_prefix<R: Rng>(rng: &mut R, prefix: &Prefix<XorName>) -> XorName
     unwrap!(rng.gen_iter().find(|name| prefix.matches(name)))
 }
 
-fn choose_node_from_section<R: Rng>(
-    rng: &mut R,
-    nodes: &[TestNode],
-    prefix: &Prefix<XorName>,
-) -> usize {
-    unwrap!(iter::repeat(())
-        .map(|_| rng.gen_range(0, nodes.len()))
-        .find(|index| prefix.matches(&nodes[*index].name())))
-}
-
 // Returns whether the relocation of node at `node_index` from `source_prefix` to `target_prefix`
 // is complete.
 fn relocation_complete(
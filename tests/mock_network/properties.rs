@@ -0,0 +1,58 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{
+    create_connected_nodes, run_churn_scenario,
+    strategies::{arb_churn_steps, arb_routing_message},
+    verify_invariant_for_all_nodes, MIN_SECTION_SIZE,
+};
+use proptest::{prop_assert, proptest, test_runner::Config as ProptestConfig};
+use routing::{mock::Network, Authority, FullId, RoutingMessage, SignedRoutingMessage};
+
+// Upper bound on the number of nodes in the churn tests, to keep their running time reasonable.
+const MAX_NODES: usize = 3 * MIN_SECTION_SIZE;
+const MAX_CHURN_STEPS: usize = 15;
+
+proptest! {
+    // Every case runs a whole network, so only try a few.
+    #![proptest_config(ProptestConfig::with_cases(8))]
+
+    #[test]
+    fn churn_preserves_invariants(
+        steps in arb_churn_steps(MIN_SECTION_SIZE, MIN_SECTION_SIZE, MAX_NODES, MAX_CHURN_STEPS)
+    ) {
+        let network = Network::new(MIN_SECTION_SIZE, None);
+        let mut rng = network.new_rng();
+        let mut nodes = create_connected_nodes(&network, MIN_SECTION_SIZE);
+
+        run_churn_scenario(&mut rng, &network, &mut nodes, &steps);
+        verify_invariant_for_all_nodes(&network, &mut nodes);
+    }
+}
+
+proptest! {
+    #[test]
+    fn single_source_message_integrity(msg in arb_routing_message()) {
+        let full_id = FullId::new();
+        let our_name = *full_id.public_id().name();
+
+        // Signed by its source node: accepted.
+        let own_msg = RoutingMessage {
+            src: Authority::Node(our_name),
+            ..msg.clone()
+        };
+        let signed_msg = unwrap!(SignedRoutingMessage::single_source(own_msg, &full_id));
+        prop_assert!(signed_msg.check_integrity().is_ok());
+
+        // Signed by anyone else: rejected.
+        if msg.src != Authority::Node(our_name) {
+            let signed_msg = unwrap!(SignedRoutingMessage::single_source(msg, &full_id));
+            prop_assert!(signed_msg.check_integrity().is_err());
+        }
+    }
+}
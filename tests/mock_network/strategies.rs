@@ -0,0 +1,105 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Proptest strategies generating random network operations and messages, for property-based
+//! tests checking that invariants hold for arbitrary histories.
+
+use super::ChurnStep;
+use proptest::{
+    collection,
+    prelude::{any, prop_oneof, Just, Strategy},
+};
+use routing::{Authority, MessageContent, Prefix, RoutingMessage, XorName, XOR_NAME_LEN};
+
+// Longest prefix generated. Longer ones are unlikely to match any section of a test network.
+const MAX_PREFIX_LEN: usize = 4;
+// Longest user message content generated, in bytes.
+const MAX_USER_CONTENT_LEN: usize = 1024;
+// Longest pause between two churn operations, in seconds.
+const MAX_ADVANCE_SECS: u64 = 60;
+
+pub fn arb_xor_name() -> impl Strategy<Value = XorName> {
+    any::<[u8; XOR_NAME_LEN]>().prop_map(XorName)
+}
+
+pub fn arb_prefix() -> impl Strategy<Value = Prefix<XorName>> {
+    (0..=MAX_PREFIX_LEN, arb_xor_name()).prop_map(|(bit_count, name)| Prefix::new(bit_count, name))
+}
+
+pub fn arb_authority() -> impl Strategy<Value = Authority<XorName>> {
+    prop_oneof![
+        arb_xor_name().prop_map(Authority::Node),
+        arb_xor_name().prop_map(Authority::Section),
+        arb_prefix().prop_map(Authority::PrefixSection),
+    ]
+}
+
+/// Generates the message contents which don't depend on the identities of real nodes.
+pub fn arb_message_content() -> impl Strategy<Value = MessageContent> {
+    prop_oneof![
+        collection::vec(any::<u8>(), 0..MAX_USER_CONTENT_LEN).prop_map(MessageContent::UserMessage),
+        any::<[u8; 32]>().prop_map(MessageContent::Merge),
+        (arb_prefix(), any::<u64>()).prop_map(|(src_prefix, ack_version)| {
+            MessageContent::AckMessage {
+                src_prefix,
+                ack_version,
+            }
+        }),
+    ]
+}
+
+pub fn arb_routing_message() -> impl Strategy<Value = RoutingMessage> {
+    (arb_authority(), arb_authority(), arb_message_content())
+        .prop_map(|(src, dst, content)| RoutingMessage { src, dst, content })
+}
+
+// A churn operation, before it's checked against the size of the network.
+#[derive(Clone, Copy, Debug)]
+enum ChurnOp {
+    // Add a node to the half of the name space given by the bit.
+    Add(bool),
+    Remove,
+    Wait(u64),
+}
+
+fn arb_churn_op() -> impl Strategy<Value = ChurnOp> {
+    prop_oneof![
+        any::<bool>().prop_map(ChurnOp::Add),
+        Just(ChurnOp::Remove),
+        (1..=MAX_ADVANCE_SECS).prop_map(ChurnOp::Wait),
+    ]
+}
+
+/// Generates sequences of up to `max_len` churn steps to be run by `run_churn_scenario` on a
+/// network of `initial_nodes` nodes. The steps are valid for any such network: nodes are only
+/// removed while there are more than `min_nodes` of them and only added while there are fewer than
+/// `max_nodes`. Operations violating this are dropped, so shrinking keeps the sequences valid.
+pub fn arb_churn_steps(
+    initial_nodes: usize,
+    min_nodes: usize,
+    max_nodes: usize,
+    max_len: usize,
+) -> impl Strategy<Value = Vec<ChurnStep>> {
+    collection::vec(arb_churn_op(), 0..=max_len).prop_map(move |ops| {
+        let mut count = initial_nodes;
+        ops.into_iter()
+            .filter_map(|op| match op {
+                ChurnOp::Add(bit) if count < max_nodes => {
+                    count += 1;
+                    Some(ChurnStep::AddNode(Prefix::default().pushed(bit)))
+                }
+                ChurnOp::Remove if count > min_nodes => {
+                    count -= 1;
+                    Some(ChurnStep::RemoveNode(Prefix::default()))
+                }
+                ChurnOp::Wait(secs) => Some(ChurnStep::AdvanceTime(secs)),
+                ChurnOp::Add(_) | ChurnOp::Remove => None,
+            })
+            .collect()
+    })
+}
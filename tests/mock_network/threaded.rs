@@ -0,0 +1,319 @@
+// Copyright 2019 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Semi-real test mode: every node runs its state machine on its own thread, driven by the real
+//! clock, while still talking over the mock transport. This surfaces concurrency bugs which the
+//! single-threaded, deterministic polling of the other tests hides.
+//!
+//! Requires the real parsec and real serialisation, as both the mock parsec and the mock
+//! serialised messages are confined to a single thread. Run with
+//! `cargo test --features=mock_base threaded`.
+
+use super::MIN_SECTION_SIZE;
+use crossbeam_channel as mpmc;
+use fake_clock::FakeClock;
+use routing::{mock::Network, Authority, Event, EventStream, NetworkConfig, Node, XorName};
+use std::{
+    collections::BTreeSet,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// Pause between two polls of a node or of the network.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+// How long to wait for an expected condition before failing the test.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+type Command = Box<dyn FnOnce(&mut Node) + Send>;
+
+/// Keeps the fake clock of the current thread in step with the real one, so the timers of the
+/// nodes created on the thread fire in real time. The fake clock is thread-local, so every thread
+/// needs its own instance.
+struct RealClock {
+    start: Instant,
+    advanced_ms: u64,
+}
+
+impl RealClock {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            advanced_ms: 0,
+        }
+    }
+
+    fn sync(&mut self) {
+        let elapsed = self.start.elapsed();
+        let elapsed_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+        FakeClock::advance_time(elapsed_ms - self.advanced_ms);
+        self.advanced_ms = elapsed_ms;
+    }
+}
+
+/// Delivers the messages of the mock network on a background thread until dropped.
+pub struct NetworkPoller {
+    network: Network,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NetworkPoller {
+    pub fn new(min_section_size: usize) -> Self {
+        let network = Network::new(min_section_size, None);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let network = network.clone();
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut clock = RealClock::new();
+                while !stop.load(Ordering::Relaxed) {
+                    clock.sync();
+                    network.poll();
+                    thread::sleep(POLL_INTERVAL);
+                }
+            })
+        };
+
+        Self {
+            network,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
+impl Drop for NetworkPoller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Handle to a node running on its own thread. The node is stopped when the handle is dropped.
+pub struct ThreadedNode {
+    commands: Option<mpmc::Sender<Command>>,
+    events: mpmc::Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ThreadedNode {
+    /// Starts a new node, bootstrapping off `contact`, or the first node of the network if `None`.
+    pub fn spawn(network: &Network, contact: Option<SocketAddr>) -> Self {
+        let (command_tx, command_rx) = mpmc::unbounded::<Command>();
+        let (event_tx, event_rx) = mpmc::unbounded();
+        let network = network.clone();
+
+        let thread = thread::spawn(move || {
+            network.make_current();
+
+            let builder = Node::builder().min_section_size(network.min_section_size());
+            let builder = if let Some(contact) = contact {
+                builder.network_config(NetworkConfig::node().with_hard_coded_contact(contact))
+            } else {
+                builder.first(true)
+            };
+            let mut node = unwrap!(builder.create());
+            let mut clock = RealClock::new();
+
+            loop {
+                clock.sync();
+
+                match command_rx.try_recv() {
+                    Ok(command) => command(&mut node),
+                    Err(mpmc::TryRecvError::Empty) => (),
+                    Err(mpmc::TryRecvError::Disconnected) => break,
+                }
+
+                let _ = node.poll();
+                while let Ok(event) = node.try_next_ev() {
+                    if event_tx.send(event).is_err() {
+                        return;
+                    }
+                }
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        Self {
+            commands: Some(command_tx),
+            events: event_rx,
+            thread: Some(thread),
+        }
+    }
+
+    /// Runs `f` on the node's own thread and returns its result.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Node) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (result_tx, result_rx) = mpmc::bounded(1);
+        let command: Command = Box::new(move |node| {
+            let _ = result_tx.send(f(node));
+        });
+        unwrap!(unwrap!(self.commands.as_ref()).send(command));
+        unwrap!(result_rx.recv_timeout(TIMEOUT))
+    }
+
+    pub fn endpoint(&self) -> SocketAddr {
+        self.with(|node| unwrap!(node.our_connection_info()).peer_addr)
+    }
+
+    /// Waits for an event matching the predicate, skipping all others. Panics on timeout.
+    pub fn wait_for_event<P>(&self, predicate: P) -> Event
+    where
+        P: Fn(&Event) -> bool,
+    {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let now = Instant::now();
+            assert!(now < deadline, "No expected event received in time.");
+            match self.events.recv_timeout(deadline - now) {
+                Ok(event) => {
+                    if predicate(&event) {
+                        return event;
+                    }
+                }
+                Err(mpmc::RecvTimeoutError::Timeout) => (),
+                Err(error) => panic!("Node stopped: {:?}", error),
+            }
+        }
+    }
+}
+
+impl Drop for ThreadedNode {
+    fn drop(&mut self) {
+        // Dropping the command sender makes the node's thread exit.
+        let _ = self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Waits until `condition` holds for all the nodes. Panics on timeout.
+fn wait_until<C>(nodes: &[ThreadedNode], condition: C)
+where
+    C: Fn(&ThreadedNode) -> bool,
+{
+    let deadline = Instant::now() + TIMEOUT;
+    while !nodes.iter().all(&condition) {
+        assert!(Instant::now() < deadline, "Condition not met in time.");
+        thread::sleep(POLL_INTERVAL * 100);
+    }
+}
+
+fn elder_names(node: &ThreadedNode) -> BTreeSet<XorName> {
+    node.with(|node| node.elders().map(|id| *id.name()).collect())
+}
+
+#[test]
+fn threaded_section_forms() {
+    let poller = NetworkPoller::new(MIN_SECTION_SIZE);
+    let network = poller.network();
+
+    let mut nodes = vec![ThreadedNode::spawn(network, None)];
+    let _ = nodes[0].wait_for_event(|event| *event == Event::Connected);
+    let contact = nodes[0].endpoint();
+
+    for _ in 1..MIN_SECTION_SIZE {
+        let node = ThreadedNode::spawn(network, Some(contact));
+        let _ = node.wait_for_event(|event| *event == Event::Connected);
+        nodes.push(node);
+    }
+
+    // All the nodes eventually agree they are all elders of the single section.
+    let names: BTreeSet<_> = nodes
+        .iter()
+        .map(|node| unwrap!(node.with(|node| node.our_name().cloned())))
+        .collect();
+    wait_until(&nodes, |node| elder_names(node) == names);
+}
+
+#[test]
+fn threaded_user_message() {
+    let poller = NetworkPoller::new(MIN_SECTION_SIZE);
+    let network = poller.network();
+
+    let src = ThreadedNode::spawn(network, None);
+    let _ = src.wait_for_event(|event| *event == Event::Connected);
+    let dst = ThreadedNode::spawn(network, Some(src.endpoint()));
+    let _ = dst.wait_for_event(|event| *event == Event::Connected);
+
+    let src_name = unwrap!(src.with(|node| node.our_name().cloned()));
+    let dst_name = unwrap!(dst.with(|node| node.our_name().cloned()));
+    let content = b"threaded".to_vec();
+
+    {
+        let content = content.clone();
+        src.with(move |node| {
+            unwrap!(node.send_message(
+                Authority::Node(src_name),
+                Authority::Node(dst_name),
+                content
+            ))
+        });
+    }
+
+    let _ = dst.wait_for_event(|event| match event {
+        Event::MessageReceived {
+            content: received, ..
+        } => *received == content,
+        _ => false,
+    });
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn threaded_async_node() {
+    use futures::executor;
+    use routing::{Client, ClientEvent};
+
+    let poller = NetworkPoller::new(MIN_SECTION_SIZE);
+    let network = poller.network().clone();
+    let (endpoint_tx, endpoint_rx) = mpmc::bounded(1);
+
+    // Nothing polls the node: it is driven only by awaiting its events.
+    let node_thread = thread::spawn(move || {
+        network.make_current();
+        let mut node = unwrap!(Node::builder()
+            .min_section_size(network.min_section_size())
+            .first(true)
+            .create());
+
+        executor::block_on(async {
+            assert_eq!(node.next_event().await, Some(Event::Connected));
+            unwrap!(endpoint_tx.send(unwrap!(node.our_connection_info()).peer_addr));
+
+            while let Some(event) = node.next_event().await {
+                if let Event::ClientEvent(ClientEvent::ConnectedToClient { .. }) = event {
+                    return;
+                }
+            }
+            panic!("Node terminated before the client connected.");
+        });
+    });
+
+    let contact = unwrap!(endpoint_rx.recv_timeout(TIMEOUT));
+    let config = NetworkConfig::client().with_hard_coded_contact(contact);
+    let _client = unwrap!(Client::builder().network_config(config).create());
+    unwrap!(node_thread.join());
+}
@@ -11,15 +11,19 @@ use fake_clock::FakeClock;
 use itertools::Itertools;
 use rand::Rng;
 use routing::{
-    mock::Network, test_consts, Authority, Event, EventStream, FullId, NetworkConfig, Node,
-    NodeBuilder, PausedState, Prefix, PublicId, XorName, Xorable,
+    check_invariant, mock::Network, test_consts, Authority, DispatchTable, Event, EventStream,
+    FullId, GenesisPfxInfo, InboundLimits, NetworkConfig, NetworkParams, Node, NodeBuilder,
+    ParsecMetrics, PausedState, Prefix, PublicId, RelocationState, Topology, XorName,
+    XorTargetInterval, Xorable,
 };
 use std::{
+    cell::Cell,
     cmp,
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     iter,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    time::{Duration, Instant},
 };
 
 // Maximum number of times to try and poll in a loop.  This is several orders higher than the
@@ -51,6 +55,60 @@ pub fn gen_range_except<T: Rng>(
     x
 }
 
+/// Pick a random node which satisfies the predicate and return its index. Returns `None` if there
+/// is no such node.
+pub fn choose_node<R, P>(rng: &mut R, nodes: &[TestNode], predicate: P) -> Option<usize>
+where
+    R: Rng,
+    P: Fn(&TestNode) -> bool,
+{
+    choose_node_weighted(rng, nodes, |node| if predicate(node) { 1 } else { 0 })
+}
+
+/// Pick a random node with probability proportional to its weight and return its index. Nodes with
+/// zero weight are never picked. Returns `None` if all the weights are zero.
+pub fn choose_node_weighted<R, W>(rng: &mut R, nodes: &[TestNode], weight: W) -> Option<usize>
+where
+    R: Rng,
+    W: Fn(&TestNode) -> u64,
+{
+    let weights: Vec<_> = nodes.iter().map(weight).collect();
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = rng.gen_range(0, total);
+    weights.iter().position(|weight| {
+        if point < *weight {
+            true
+        } else {
+            point -= *weight;
+            false
+        }
+    })
+}
+
+/// Node predicate: the node's name falls within `prefix`.
+pub fn in_prefix(prefix: Prefix<XorName>) -> impl Fn(&TestNode) -> bool {
+    move |node| prefix.matches(&node.name())
+}
+
+/// Node predicate: the node is an elder.
+pub fn is_elder(node: &TestNode) -> bool {
+    node.inner.is_elder()
+}
+
+/// Node predicate: the node is a member of a section, but not an elder.
+pub fn is_adult(node: &TestNode) -> bool {
+    !node.inner.is_elder() && node.inner.our_prefix().is_some()
+}
+
+/// Node weight: the node's age, or zero if it is not a member of a section.
+pub fn by_age(node: &TestNode) -> u64 {
+    node.inner.our_age().map_or(0, u64::from)
+}
+
 /// Wraps a `Vec<TestNode>`s and prints the nodes' routing tables when dropped in a panicking
 /// thread.
 pub struct Nodes(pub Vec<TestNode>);
@@ -75,21 +133,109 @@ impl EventStream for TestNode {
     type Item = Event;
 
     fn next_ev(&mut self) -> Result<Event, mpmc::RecvError> {
-        self.inner.next_ev()
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Some(event) = recorder.pending.pop_front() {
+                return Ok(event);
+            }
+
+            let event = self.inner.next_ev()?;
+            recorder.record(&event);
+            Ok(event)
+        } else {
+            self.inner.next_ev()
+        }
     }
 
     fn try_next_ev(&mut self) -> Result<Event, mpmc::TryRecvError> {
-        self.inner.try_next_ev()
+        if self.recorder.is_some() {
+            let _ = self.record_events();
+            let recorder = unwrap!(self.recorder.as_mut());
+            recorder
+                .pending
+                .pop_front()
+                .ok_or(mpmc::TryRecvError::Empty)
+        } else {
+            self.inner.try_next_ev()
+        }
     }
 
     fn poll(&mut self) -> bool {
-        self.inner.poll()
+        let result = match self.slowness {
+            None => self.inner.poll(),
+            Some(Slowness::MaxEventsPerPoll(max_events)) => self.inner.poll_bounded(max_events),
+            Some(Slowness::ProcessingDelay(delay)) => {
+                if self
+                    .last_processed
+                    .as_ref()
+                    .map_or(true, |last_processed| last_processed.elapsed() >= delay)
+                {
+                    self.last_processed = Some(FakeClock::now());
+                    self.inner.poll()
+                } else {
+                    false
+                }
+            }
+        };
+
+        if self.recorder.is_some() {
+            self.record_events() || result
+        } else {
+            result
+        }
     }
 }
 
+thread_local! {
+    // Source of the logical timestamps of the recorded events. Shared by all the nodes of a test
+    // (tests run each on its own thread) so the timestamps can be compared across nodes.
+    static NEXT_TIMESTAMP: Cell<u64> = Cell::new(0);
+}
+
+/// An event emitted by a node, with the logical time it was emitted at.
+#[derive(Clone, Debug)]
+pub struct RecordedEvent {
+    pub timestamp: u64,
+    pub event: Event,
+}
+
+// Collects every event emitted by a node, while still letting the test consume them as usual.
+#[derive(Default)]
+struct EventRecorder {
+    history: Vec<RecordedEvent>,
+    // Recorded events not yet consumed via `try_next_ev` / `next_ev`.
+    pending: VecDeque<Event>,
+}
+
+impl EventRecorder {
+    fn record(&mut self, event: &Event) {
+        let timestamp = NEXT_TIMESTAMP.with(|next| {
+            let timestamp = next.get();
+            next.set(timestamp + 1);
+            timestamp
+        });
+        self.history.push(RecordedEvent {
+            timestamp,
+            event: event.clone(),
+        });
+    }
+}
+
+/// How a slow node lags behind the rest of the network. Only affects `TestNode::poll`.
+#[derive(Clone, Copy, Debug)]
+pub enum Slowness {
+    /// Each poll processes at most this many pending incoming messages, actions and timeouts.
+    MaxEventsPerPoll(usize),
+    /// The node processes its pending events only once this much (fake) time has passed since it
+    /// last did.
+    ProcessingDelay(Duration),
+}
+
 pub struct TestNode {
     pub inner: Node,
     network: Network,
+    recorder: Option<EventRecorder>,
+    slowness: Option<Slowness>,
+    last_processed: Option<FakeClock>,
 }
 
 impl TestNode {
@@ -97,6 +243,7 @@ impl TestNode {
         TestNodeBuilder {
             inner: Node::builder(),
             network: network,
+            record_events: false,
         }
     }
 
@@ -104,9 +251,56 @@ impl TestNode {
         Self {
             inner: Node::resume(state),
             network: network.clone(),
+            recorder: None,
+            slowness: None,
+            last_processed: None,
+        }
+    }
+
+    /// Make this node slow (see `Slowness`), or back to normal with `None`.
+    pub fn set_slowness(&mut self, slowness: Option<Slowness>) {
+        self.slowness = slowness;
+        self.last_processed = None;
+    }
+
+    /// Start recording every event this node emits from now on. The events can still be consumed
+    /// as usual, the recorded history is available via `recorded_events`.
+    pub fn start_recording(&mut self) {
+        if self.recorder.is_none() {
+            self.recorder = Some(EventRecorder::default());
         }
     }
 
+    /// All the events recorded so far, in the order they were emitted. Panics if the node isn't
+    /// recording.
+    pub fn recorded_events(&self) -> &[RecordedEvent] {
+        &unwrap!(
+            self.recorder.as_ref(),
+            "{} is not recording events.",
+            self.name()
+        )
+        .history
+    }
+
+    // Move all the events emitted by the inner node into the recorder. Returns whether there were
+    // any.
+    fn record_events(&mut self) -> bool {
+        let recorder = unwrap!(self.recorder.as_mut());
+        let mut result = false;
+        while let Ok(event) = self.inner.try_next_ev() {
+            recorder.record(&event);
+            recorder.pending.push_back(event);
+            result = true;
+        }
+        result
+    }
+
+    /// Crash this node, dropping its connections abruptly. The returned state keeps the node's
+    /// `FullId` and chain, and can be passed to `TestNode::resume` to restart it.
+    pub fn crash(self) -> PausedState {
+        unwrap!(self.inner.crash())
+    }
+
     pub fn endpoint(&mut self) -> SocketAddr {
         unwrap!(self.inner.our_connection_info()).peer_addr
     }
@@ -131,6 +325,11 @@ impl TestNode {
         self.inner.in_authority(dst)
     }
 
+    /// Metrics of this node's current parsec instance. Panics if the node doesn't run parsec yet.
+    pub fn parsec_metrics(&self) -> ParsecMetrics {
+        unwrap!(self.inner.parsec_metrics())
+    }
+
     pub fn network(&self) -> &Network {
         &self.network
     }
@@ -144,9 +343,26 @@ pub fn current_sections<'a>(nodes: &'a [TestNode]) -> impl Iterator<Item = Prefi
     nodes.iter().flat_map(|n| n.inner.prefixes()).unique()
 }
 
+/// Returns the current topology of the network as seen by the given nodes, for use with
+/// `simulate_join`.
+pub fn current_topology(network: &Network, nodes: &[TestNode]) -> Topology {
+    let mut topology = Topology::new(network.min_section_size());
+    for prefix in nodes.iter().map(|node| *node.our_prefix()).unique() {
+        topology.insert_section(
+            prefix,
+            nodes
+                .iter()
+                .filter(|node| prefix.matches(&node.name()))
+                .map(TestNode::name),
+        );
+    }
+    topology
+}
+
 pub struct TestNodeBuilder<'a> {
     inner: NodeBuilder,
     network: &'a Network,
+    record_events: bool,
 }
 
 impl<'a> TestNodeBuilder<'a> {
@@ -171,6 +387,53 @@ impl<'a> TestNodeBuilder<'a> {
         }
     }
 
+    /// Use the given network parameters. The min section size is still taken from the network.
+    pub fn network_params(self, params: NetworkParams) -> Self {
+        Self {
+            inner: self.inner.network_params(params),
+            ..self
+        }
+    }
+
+    pub fn inbound_limits(self, limits: InboundLimits) -> Self {
+        Self {
+            inner: self.inner.inbound_limits(limits),
+            ..self
+        }
+    }
+
+    pub fn dispatch_table(self, dispatch_table: DispatchTable) -> Self {
+        Self {
+            inner: self.inner.dispatch_table(dispatch_table),
+            ..self
+        }
+    }
+
+    /// Start the node right away as an elder of the section described by `gen_pfx_info` (see
+    /// `gen_pfx_info_for_test`). Use together with `full_id`.
+    pub fn genesis(self, gen_pfx_info: GenesisPfxInfo) -> Self {
+        Self {
+            inner: self.inner.genesis(gen_pfx_info),
+            ..self
+        }
+    }
+
+    /// Resume the relocation the node was going through before it restarted.
+    pub fn resume_relocation(self, relocation: RelocationState) -> Self {
+        Self {
+            inner: self.inner.resume_relocation(relocation),
+            ..self
+        }
+    }
+
+    /// Record every event emitted by the node (see `TestNode::start_recording`).
+    pub fn record_events(self) -> Self {
+        Self {
+            record_events: true,
+            ..self
+        }
+    }
+
     pub fn create(self) -> TestNode {
         let inner = unwrap!(self
             .inner
@@ -180,6 +443,13 @@ impl<'a> TestNodeBuilder<'a> {
         TestNode {
             inner,
             network: self.network.clone(),
+            recorder: if self.record_events {
+                Some(EventRecorder::default())
+            } else {
+                None
+            },
+            slowness: None,
+            last_processed: None,
         }
     }
 }
@@ -216,27 +486,52 @@ pub fn poll_and_resend(nodes: &mut [TestNode]) {
     poll_and_resend_with_options(nodes, PollOptions::default())
 }
 
+/// A named condition which stops the polling once it is met.
+pub struct StopCondition {
+    /// Name of the condition, used in logs and panic messages.
+    pub name: &'static str,
+    /// Returns true once the condition is met.
+    pub predicate: Box<dyn Fn(&[TestNode]) -> bool>,
+    /// If set, the condition must be met within this many poll steps, otherwise polling panics.
+    pub max_steps: Option<usize>,
+}
+
 /// Options for polling nodes in the test network.
 pub struct PollOptions {
     /// If set, polling continues while this predicate returns true even if all nodes are idle.
     pub continue_predicate: Option<Box<dyn Fn(&[TestNode]) -> bool>>,
-    /// If this predicate returns true, the polling stops even if some nodes are still busy.
-    pub stop_predicate: Box<dyn Fn(&[TestNode]) -> bool>,
+    /// If any of these conditions is met, the polling stops even if some nodes are still busy.
+    pub stop_conditions: Vec<StopCondition>,
     /// If set and all nodes become idle, advances the time by this amount (in seconds) and polls
     /// again one more time.
     pub extra_advance: Option<u64>,
     /// If true and all nodes become idle, advances the time by the amount it takes for joining
     /// nodes to timeout and polls again one more time.
     pub fire_join_timeout: bool,
+    /// Label of the test phase this polling belongs to. Included in the panic message when the
+    /// budget is exhausted.
+    pub phase: Option<&'static str>,
+    /// Maximum number of poll steps before giving up.
+    pub max_steps: usize,
+    /// Maximum real (wall-clock) time before giving up, if any.
+    pub max_wall_time: Option<Duration>,
+    /// If set, polling stops only once the network is quiescent (see `is_quiescent`) with this
+    /// horizon, skipping time forward to the next timer whenever all nodes are idle. The
+    /// `extra_advance` and `fire_join_timeout` heuristics are not used then.
+    pub quiescence_horizon: Option<Duration>,
 }
 
 impl Default for PollOptions {
     fn default() -> Self {
         Self {
             continue_predicate: None,
-            stop_predicate: Box::new(|_| false),
+            stop_conditions: Vec::new(),
             extra_advance: None,
             fire_join_timeout: true,
+            phase: None,
+            max_steps: MAX_POLL_CALLS,
+            max_wall_time: None,
+            quiescence_horizon: None,
         }
     }
 }
@@ -252,19 +547,65 @@ impl PollOptions {
         }
     }
 
-    pub fn stop_if<F>(self, pred: F) -> Self
+    /// Poll until the network is quiescent with the given horizon. The horizon must be shorter
+    /// than the period of the timers which are always scheduled (e.g. parsec gossip), otherwise
+    /// quiescence is never reached.
+    pub fn until_quiescent(self, horizon: Duration) -> Self {
+        Self {
+            quiescence_horizon: Some(horizon),
+            ..self
+        }
+    }
+
+    /// Stop polling once `pred` returns true.
+    pub fn stop_when<F>(self, name: &'static str, pred: F) -> Self
+    where
+        F: Fn(&[TestNode]) -> bool + 'static,
+    {
+        self.stop_condition(name, None, pred)
+    }
+
+    /// Stop polling once `pred` returns true. Panics if that doesn't happen within `max_steps`.
+    pub fn stop_when_within<F>(self, name: &'static str, max_steps: usize, pred: F) -> Self
     where
         F: Fn(&[TestNode]) -> bool + 'static,
     {
+        self.stop_condition(name, Some(max_steps), pred)
+    }
+
+    fn stop_condition<F>(mut self, name: &'static str, max_steps: Option<usize>, pred: F) -> Self
+    where
+        F: Fn(&[TestNode]) -> bool + 'static,
+    {
+        self.stop_conditions.push(StopCondition {
+            name,
+            predicate: Box::new(pred),
+            max_steps,
+        });
+        self
+    }
+
+    pub fn fire_join_timeout(self, fire_join_timeout: bool) -> Self {
         Self {
-            stop_predicate: Box::new(pred),
+            fire_join_timeout,
             ..self
         }
     }
 
-    pub fn fire_join_timeout(self, fire_join_timeout: bool) -> Self {
+    pub fn phase(self, phase: &'static str) -> Self {
         Self {
-            fire_join_timeout,
+            phase: Some(phase),
+            ..self
+        }
+    }
+
+    pub fn max_steps(self, max_steps: usize) -> Self {
+        Self { max_steps, ..self }
+    }
+
+    pub fn max_wall_time(self, max_wall_time: Duration) -> Self {
+        Self {
+            max_wall_time: Some(max_wall_time),
             ..self
         }
     }
@@ -272,11 +613,46 @@ impl PollOptions {
 
 /// Polls and processes all events, until there are no unacknowledged messages left.
 pub fn poll_and_resend_with_options(nodes: &mut [TestNode], mut options: PollOptions) {
-    for _ in 0..MAX_POLL_CALLS {
-        if (options.stop_predicate)(nodes) {
+    let start = Instant::now();
+
+    for step in 0..options.max_steps {
+        if let Some(max_wall_time) = options.max_wall_time {
+            if start.elapsed() > max_wall_time {
+                budget_exceeded(
+                    nodes,
+                    &options,
+                    &format!("{:?} of wall time", max_wall_time),
+                );
+            }
+        }
+
+        if let Some(condition) = options
+            .stop_conditions
+            .iter()
+            .find(|condition| (condition.predicate)(nodes))
+        {
+            debug!(
+                "poll_and_resend in phase {:?} stopped after {} steps: {:?} met.",
+                options.phase.unwrap_or("<unnamed>"),
+                step,
+                condition.name
+            );
             return;
         }
 
+        if let Some((name, max_steps)) = options
+            .stop_conditions
+            .iter()
+            .filter_map(|condition| Some((condition.name, condition.max_steps?)))
+            .find(|(_, max_steps)| step >= *max_steps)
+        {
+            budget_exceeded(
+                nodes,
+                &options,
+                &format!("{} poll steps for stop condition {:?}", max_steps, name),
+            );
+        }
+
         let node_busy = |node: &TestNode| node.inner.has_unpolled_observations();
         if poll_all(nodes) || nodes.iter().any(node_busy) {
             // Advance time for next route/gossip iter.
@@ -284,6 +660,21 @@ pub fn poll_and_resend_with_options(nodes: &mut [TestNode], mut options: PollOpt
             continue;
         }
 
+        if let Some(horizon) = options.quiescence_horizon {
+            if is_quiescent(nodes, horizon) {
+                debug!(
+                    "poll_and_resend in phase {:?} reached quiescence after {} steps.",
+                    options.phase.unwrap_or("<unnamed>"),
+                    step
+                );
+                return;
+            }
+
+            // Nothing to do until the next timer fires, so skip to it.
+            advance_to_next_timer(nodes);
+            continue;
+        }
+
         if let Some(continue_predicate) = options.continue_predicate.as_ref() {
             if continue_predicate(nodes) {
                 continue;
@@ -312,7 +703,70 @@ pub fn poll_and_resend_with_options(nodes: &mut [TestNode], mut options: PollOpt
         return;
     }
 
-    panic!("poll_and_resend has been called {} times.", MAX_POLL_CALLS);
+    budget_exceeded(
+        nodes,
+        &options,
+        &format!("{} poll steps", options.max_steps),
+    );
+}
+
+/// Returns whether the network is quiescent: no messages are in flight, no node has unpolled parsec
+/// observations and no timer of any node is due within `horizon`.
+pub fn is_quiescent(nodes: &[TestNode], horizon: Duration) -> bool {
+    let deadline = FakeClock::now() + horizon;
+    !nodes[0].network().has_messages_in_flight()
+        && nodes.iter().all(|node| {
+            !node.inner.has_unpolled_observations()
+                && node
+                    .inner
+                    .next_timer_deadline()
+                    .map_or(true, |next| next > deadline)
+        })
+}
+
+// Advances the time just past the earliest deadline of the timers of all the nodes.
+fn advance_to_next_timer(nodes: &[TestNode]) {
+    let now = FakeClock::now();
+    let ms = nodes
+        .iter()
+        .filter_map(|node| node.inner.next_timer_deadline())
+        .min()
+        .filter(|next| *next > now)
+        .map_or(0, |next| {
+            let duration = next.duration_since(now);
+            duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+        });
+    FakeClock::advance_time(ms + 1);
+}
+
+// Panics with a message naming the phase whose budget was exceeded, the stop conditions which were
+// still unmet and the nodes which still had work pending, sorted by their distance to the first
+// node.
+fn budget_exceeded(nodes: &[TestNode], options: &PollOptions, budget: &str) -> ! {
+    let mut pending: Vec<_> = nodes
+        .iter()
+        .filter(|node| node.inner.has_unpolled_observations())
+        .map(TestNode::name)
+        .collect();
+    if let Some(first) = nodes.first() {
+        let first_name = first.name();
+        pending.sort_by(|lhs, rhs| first_name.cmp_distance(lhs, rhs));
+    }
+
+    let unmet: Vec<_> = options
+        .stop_conditions
+        .iter()
+        .map(|condition| condition.name)
+        .collect();
+
+    panic!(
+        "poll_and_resend exceeded the budget of {} in phase {:?}. Unmet stop conditions: {:?}. \
+         Nodes with pending work: {:?}",
+        budget,
+        options.phase.unwrap_or("<unnamed>"),
+        unmet,
+        pending
+    );
 }
 
 /// Checks each of the last `count` members of `nodes` for a `Connected` event, and removes those
@@ -369,8 +823,11 @@ pub fn create_connected_nodes(network: &Network, size: usize) -> Nodes {
                 Event::NodeAdded(..) => node_added_count += 1,
                 Event::NodeLost(..)
                 | Event::SectionSplit(..)
+                | Event::EldersChanged { .. }
                 | Event::RestartRequired
+                | Event::JoinAttemptFailed { .. }
                 | Event::ClientEvent(..)
+                | Event::AgeIncreased(..)
                 | Event::TimerTicked => (),
                 event => panic!("Got unexpected event: {:?}", event),
             }
@@ -460,6 +917,7 @@ pub fn add_connected_nodes_until_split(
             }
         }
         if let Some(prefix_to_split) = found_prefix {
+            set_relocation_overrides(nodes, &prefixes);
             add_node_to_section(network, nodes, &prefix_to_split);
         } else {
             break;
@@ -485,6 +943,9 @@ pub fn add_connected_nodes_until_split(
         | Event::NodeLost(..)
         | Event::TimerTicked
         | Event::ClientEvent(..)
+        | Event::AgeIncreased(..)
+        | Event::JoinAttemptFailed { .. }
+        | Event::EldersChanged { .. }
         | Event::SectionSplit(..) => (),
         event => panic!("Got unexpected event: {:?}", event),
     });
@@ -515,10 +976,15 @@ fn add_connected_nodes_until_sized(
 ) {
     clear_all_event_queues(nodes, |_| {});
 
-    add_nodes_to_prefixes(network, nodes, prefixes_new_count);
+    let target_prefixes = prefixes_new_count
+        .iter()
+        .map(|(prefix, _)| *prefix)
+        .collect_vec();
+    with_relocation_overrides(nodes, &target_prefixes, |nodes| {
+        add_nodes_to_prefixes(network, nodes, prefixes_new_count)
+    });
 
     clear_all_event_queues(nodes, |_| {});
-    clear_relocation_overrides(nodes);
 
     trace!(
         "Filled prefixes until ready to split {:?}",
@@ -532,6 +998,10 @@ fn add_nodes_to_prefixes(
     nodes: &mut Vec<TestNode>,
     prefixes_new_count: &[PrefixAndSize],
 ) {
+    let target_prefixes = prefixes_new_count
+        .iter()
+        .map(|(prefix, _)| *prefix)
+        .collect_vec();
     for (prefix, target_count) in prefixes_new_count {
         let num_in_section = nodes
             .iter()
@@ -549,6 +1019,7 @@ fn add_nodes_to_prefixes(
         );
         let to_add_count = target_count - num_in_section;
         for _ in 0..to_add_count {
+            set_relocation_overrides(nodes, &target_prefixes);
             add_node_to_section(network, nodes, prefix);
         }
     }
@@ -571,6 +1042,77 @@ pub fn clear_relocation_overrides(nodes: &mut Vec<TestNode>) {
     }
 }
 
+// Set the `next_relocation_dst` / `next_relocation_interval` values of all the elders so that any
+// node relocated while the network grows towards `target_prefixes` stays in the section it is
+// relocated from, and within the target prefix with the fewest nodes in that section. With no
+// matching target prefix, the section's own prefix is used.
+//
+// The overrides depend on the current sections, so this needs to be called again after any split.
+pub fn set_relocation_overrides(nodes: &mut [TestNode], target_prefixes: &[Prefix<XorName>]) {
+    let sections: BTreeSet<_> = nodes
+        .iter()
+        .filter(|node| node.inner.is_elder())
+        .map(|node| *node.our_prefix())
+        .collect();
+    let dst_prefixes: BTreeMap<_, _> = sections
+        .into_iter()
+        .map(|section| {
+            (
+                section,
+                relocation_dst_prefix(nodes, &section, target_prefixes),
+            )
+        })
+        .collect();
+
+    for node in nodes.iter_mut().filter(|node| node.inner.is_elder()) {
+        let dst_prefix = dst_prefixes[node.our_prefix()];
+        node.inner
+            .set_next_relocation_dst(Some(dst_prefix.lower_bound()));
+        node.inner
+            .set_next_relocation_interval(Some(XorTargetInterval::from_prefix(&dst_prefix)));
+    }
+}
+
+// Run `f` with the relocation overrides for `target_prefixes` set, then clear them.
+pub fn with_relocation_overrides<F, R>(
+    nodes: &mut Vec<TestNode>,
+    target_prefixes: &[Prefix<XorName>],
+    f: F,
+) -> R
+where
+    F: FnOnce(&mut Vec<TestNode>) -> R,
+{
+    set_relocation_overrides(nodes, target_prefixes);
+    let result = f(nodes);
+    clear_relocation_overrides(nodes);
+    result
+}
+
+// Returns the prefix nodes relocated from `section` should be relocated into: the most specific of
+// `section` and the compatible target prefix with the fewest nodes.
+fn relocation_dst_prefix(
+    nodes: &[TestNode],
+    section: &Prefix<XorName>,
+    target_prefixes: &[Prefix<XorName>],
+) -> Prefix<XorName> {
+    target_prefixes
+        .iter()
+        .filter(|target| target.is_compatible(section))
+        .min_by_key(|target| {
+            nodes
+                .iter()
+                .filter(|node| target.matches(&node.name()))
+                .count()
+        })
+        .map_or(*section, |target| {
+            if target.is_extension_of(section) {
+                *target
+            } else {
+                *section
+            }
+        })
+}
+
 // Returns sub-prefixes target size to reach so we would split with one extra node.
 // The second returned field contains the sub-prefixes to add the final node to trigger the splits.
 fn prefixes_and_count_to_split_with_only_one_extra_node(
@@ -606,200 +1148,112 @@ fn prefix_half_with_fewer_nodes(nodes: &[TestNode], prefix: &Prefix<XorName>) ->
     *unwrap!(smaller_prefix)
 }
 
-// -----  Small misc functions  -----
-
-/// Sorts the given nodes by their distance to `name`. Note that this will call the `name()`
-/// function on them which causes polling, so it calls `poll_all` to make sure that all other
-/// events have been processed before sorting.
-pub fn sort_nodes_by_distance_to(nodes: &mut [TestNode], name: &XorName) {
-    let _ = poll_all(nodes); // Poll
-    nodes.sort_by(|node0, node1| name.cmp_distance(&node0.name(), &node1.name()));
-}
-
-pub fn verify_section_invariants_for_node(node: &TestNode, min_section_size: usize) {
-    let our_prefix = unwrap!(node.inner.our_prefix());
-    let our_name = unwrap!(node.inner.our_name());
-    let our_section_elders = node.inner.section_elders(our_prefix);
-
-    assert!(
-        our_prefix.matches(our_name),
-        "Our prefix doesn't match our name: {:?}, {:?}",
-        our_prefix,
-        our_name,
-    );
-
-    if !our_prefix.is_empty() {
-        assert!(
-            our_section_elders.len() >= min_section_size,
-            "Our section {:?} is below the minimum size!",
-            our_prefix,
-        );
-    }
-
-    if let Some(name) = our_section_elders
-        .iter()
-        .find(|name| !our_prefix.matches(name))
-    {
-        panic!(
-            "A name in our section doesn't match its prefix! {:?}, {:?}",
-            name, our_prefix,
-        );
-    }
-
-    let neighbour_prefixes = node.inner.neighbour_prefixes();
-
-    if let Some(compatible_prefix) = neighbour_prefixes
-        .iter()
-        .find(|prefix| prefix.is_compatible(our_prefix))
-    {
-        panic!(
-            "Our prefix is compatible with one of the neighbour prefixes:\
-             us: {:?} / neighbour: {:?}",
-            our_prefix, compatible_prefix,
-        );
-    }
-
-    if let Some(prefix) = neighbour_prefixes
-        .iter()
-        .find(|prefix| node.inner.section_elders(prefix).len() < min_section_size)
-    {
-        panic!(
-            "A section is below the minimum size: size({:?}) = {}; For ({:?}: {:?})",
-            prefix,
-            node.inner.section_elders(prefix).len(),
-            our_name,
-            our_prefix,
-        );
-    }
-
-    for prefix in &neighbour_prefixes {
-        if let Some(name) = node
-            .inner
-            .section_elders(prefix)
-            .iter()
-            .find(|name| !prefix.matches(name))
-        {
-            panic!(
-                "A name in a section doesn't match its prefix! {:?}, {:?}",
-                name, prefix,
-            );
-        }
-    }
-
-    let all_are_neighbours = node
-        .inner
-        .neighbour_prefixes()
-        .iter()
-        .all(|prefix| our_prefix.is_neighbour(prefix));
-    let all_neighbours_covered = {
-        (0..our_prefix.bit_count()).all(|i| {
-            our_prefix
-                .with_flipped_bit(i)
-                .is_covered_by(&neighbour_prefixes)
-        })
-    };
-    if !all_are_neighbours {
-        panic!(
-            "Some sections in the chain aren't neighbours of our section: {:?}",
-            iter::once(*our_prefix)
-                .chain(neighbour_prefixes)
-                .collect::<Vec<_>>()
-        );
-    }
-    if !all_neighbours_covered {
-        panic!(
-            "Some neighbours aren't fully covered by the chain: {:?}",
-            iter::once(*our_prefix)
-                .chain(neighbour_prefixes)
-                .collect::<Vec<_>>()
-        );
-    }
-}
-
-pub fn verify_section_invariants_for_nodes(nodes: &[TestNode], min_section_size: usize) {
-    for node in nodes.iter() {
-        verify_section_invariants_for_node(node, min_section_size);
-    }
+// -----  Scripted churn scenarios  -----
+
+/// A single step of a scripted churn scenario.
+#[derive(Clone, Copy, Debug)]
+pub enum ChurnStep {
+    /// Add a new node to the section covering the given prefix and poll until it becomes an elder.
+    AddNode(Prefix<XorName>),
+    /// Remove a random node (never the first one) whose name matches the given prefix and poll.
+    RemoveNode(Prefix<XorName>),
+    /// Expect that the nodes matching the given prefix are in sections extending it.
+    ExpectSplit(Prefix<XorName>),
+    /// Expect that the nodes matching the given prefix are in the section with exactly that
+    /// prefix.
+    ExpectMerge(Prefix<XorName>),
+    /// Advance the mock time by the given number of seconds and poll.
+    AdvanceTime(u64),
 }
 
-pub fn verify_section_invariants_between_nodes(nodes: &[TestNode]) {
-    let mut sections: BTreeMap<Prefix<XorName>, (XorName, BTreeSet<XorName>)> = BTreeMap::new();
-
-    for node in nodes.iter() {
-        let our_prefix = unwrap!(node.inner.our_prefix());
-        let our_name = unwrap!(node.inner.our_name());
-        // NOTE: using neighbour_prefixes() here and not neighbour_infos().prefix().
-        // Is this a problem?
-        for prefix in iter::once(our_prefix).chain(node.inner.neighbour_prefixes().iter()) {
-            let our_view_section_elders = node.inner.section_elders(prefix);
-
-            if let Some(&(ref their_name, ref their_view_section_elders)) = sections.get(prefix) {
-                assert_eq!(
-                    &our_view_section_elders,
-                    their_view_section_elders,
-                    "Section with prefix {:?} doesn't agree between nodes {:?} and {:?}\n\
-                     {:?}: {:?},\n{:?}: {:?}",
-                    prefix,
-                    our_name,
-                    their_name,
-                    our_name,
-                    our_view_section_elders,
-                    their_name,
-                    their_view_section_elders,
+/// Execute the steps of a churn scenario in order. Panics, naming the failing step, if any of the
+/// expectations is not met.
+pub fn run_churn_scenario<R: Rng>(
+    rng: &mut R,
+    network: &Network,
+    nodes: &mut Nodes,
+    steps: &[ChurnStep],
+) {
+    for (index, step) in steps.iter().enumerate() {
+        trace!("Churn scenario step {}: {:?}", index, step);
+
+        match *step {
+            ChurnStep::AddNode(prefix) => add_node_to_section(network, nodes, &prefix),
+            ChurnStep::RemoveNode(prefix) => {
+                let candidates = nodes
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .filter(|(_, node)| prefix.matches(&node.name()))
+                    .map(|(index, _)| index)
+                    .collect_vec();
+                let index = *unwrap!(
+                    rng.choose(&candidates),
+                    "Step {} ({:?}): no node to remove",
+                    index,
+                    step
                 );
-                // NOTE: previous version of this also included an assertion that the section
-                // versions are the same. Removed since we don't expose the section version in the
-                // `Node` public API.
-                // Should we?
-                continue;
+                drop(nodes.remove(index));
+                poll_and_resend(nodes);
             }
-            let _ = sections.insert(*prefix, (*our_name, our_view_section_elders));
-        }
-    }
-
-    // check that prefixes are disjoint
-    for prefix1 in sections.keys() {
-        for prefix2 in sections.keys() {
-            if prefix1 == prefix2 {
-                continue;
+            ChurnStep::ExpectSplit(prefix) => {
+                for node in nodes.iter().filter(|node| prefix.matches(&node.name())) {
+                    let our_prefix = node.our_prefix();
+                    assert!(
+                        our_prefix.is_extension_of(&prefix),
+                        "Step {} ({:?}): {} is still in {:?}",
+                        index,
+                        step,
+                        node.name(),
+                        our_prefix
+                    );
+                }
+                verify_invariant_for_all_nodes(network, nodes);
             }
-            if prefix1.is_compatible(prefix2) {
-                panic!(
-                    "Section prefixes should be disjoint, but these are not:\n\
-                     Section {:?}, according to node {:?}: {:?}\n\
-                     Section {:?}, according to node {:?}: {:?}",
-                    prefix1,
-                    sections[prefix1].0,
-                    sections[prefix1].1,
-                    prefix2,
-                    sections[prefix2].0,
-                    sections[prefix2].1
-                );
+            ChurnStep::ExpectMerge(prefix) => {
+                for node in nodes.iter().filter(|node| prefix.matches(&node.name())) {
+                    let our_prefix = node.our_prefix();
+                    assert_eq!(
+                        *our_prefix,
+                        prefix,
+                        "Step {} ({:?}): {} is in {:?}",
+                        index,
+                        step,
+                        node.name(),
+                        our_prefix
+                    );
+                }
+                verify_invariant_for_all_nodes(network, nodes);
             }
-        }
-    }
-
-    // check that each section contains names agreeing with its prefix
-    for (prefix, (_, ref members)) in &sections {
-        for name in members {
-            if !prefix.matches(name) {
-                panic!(
-                    "Section members should match the prefix, but {:?} \
-                     does not match {:?}",
-                    name, prefix
-                );
+            ChurnStep::AdvanceTime(secs) => {
+                FakeClock::advance_time(secs * 1000);
+                poll_and_resend(nodes);
             }
         }
     }
+}
+
+// -----  Small misc functions  -----
 
-    // check that sections cover the whole namespace
-    assert!(Prefix::default().is_covered_by(sections.keys()));
+/// Sorts the given nodes by their distance to `name`. Note that this will call the `name()`
+/// function on them which causes polling, so it calls `poll_all` to make sure that all other
+/// events have been processed before sorting.
+pub fn sort_nodes_by_distance_to(nodes: &mut [TestNode], name: &XorName) {
+    let _ = poll_all(nodes); // Poll
+    nodes.sort_by(|node0, node1| name.cmp_distance(&node0.name(), &node1.name()));
 }
 
 pub fn verify_invariant_for_all_nodes(network: &Network, nodes: &mut [TestNode]) {
-    let min_section_size = network.min_section_size();
-    verify_section_invariants_for_nodes(nodes, min_section_size);
-    verify_section_invariants_between_nodes(nodes);
+    let snapshots: Vec<_> = nodes
+        .iter()
+        .map(|node| unwrap!(node.inner.chain_view()).snapshot())
+        .collect();
+    let violations = check_invariant(&snapshots, network.min_section_size());
+    assert!(
+        violations.is_empty(),
+        "verify_invariant_for_all_nodes - violations: {:?}",
+        violations
+    );
 
     let mut all_missing_peers = BTreeSet::<PublicId>::new();
     for node in nodes.iter_mut() {
@@ -882,8 +1336,19 @@ fn prefixes<T: Rng>(prefix_lengths: &[usize], rng: &mut T) -> Vec<Prefix<XorName
 }
 
 fn add_node_to_section(network: &Network, nodes: &mut Vec<TestNode>, prefix: &Prefix<XorName>) {
-    let config = NetworkConfig::node().with_hard_coded_contacts(iter::once(nodes[0].endpoint()));
     let full_id = FullId::within_range(&prefix.range_inclusive());
+    add_node_with_full_id(network, nodes, full_id);
+    assert!(
+        prefix.matches(&nodes[nodes.len() - 1].name()),
+        "Prefix {:?} doesn't match the name {}!",
+        prefix,
+        nodes[nodes.len() - 1].name()
+    );
+}
+
+/// Adds a node with the given id to the network and polls until it becomes an elder.
+pub fn add_node_with_full_id(network: &Network, nodes: &mut Vec<TestNode>, full_id: FullId) {
+    let config = NetworkConfig::node().with_hard_coded_contacts(iter::once(nodes[0].endpoint()));
     nodes.push(
         TestNode::builder(network)
             .network_config(config)
@@ -903,16 +1368,68 @@ fn add_node_to_section(network: &Network, nodes: &mut Vec<TestNode>, prefix: &Pr
             .fire_join_timeout(false),
     );
     expect_any_event!(unwrap!(nodes.last_mut()), Event::Connected);
-    assert!(
-        prefix.matches(&nodes[nodes.len() - 1].name()),
-        "Prefix {:?} doesn't match the name {}!",
-        prefix,
-        nodes[nodes.len() - 1].name()
-    );
 }
 
 mod tests {
-    use super::sanity_check;
+    use super::{
+        by_age, choose_node, choose_node_weighted, create_connected_nodes,
+        create_connected_nodes_until_split, in_prefix, is_adult, is_elder,
+        poll_and_resend_with_options, sanity_check, PollOptions,
+    };
+    use crate::mock_network::MIN_SECTION_SIZE;
+    use routing::mock::Network;
+    use std::time::Duration;
+
+    #[test]
+    fn choose_node_helpers() {
+        let network = Network::new(MIN_SECTION_SIZE, None);
+        let mut rng = network.new_rng();
+        let nodes = create_connected_nodes_until_split(&network, vec![1, 1]);
+
+        let index = unwrap!(choose_node(&mut rng, &nodes, is_elder));
+        assert!(nodes[index].inner.is_elder());
+
+        if let Some(index) = choose_node(&mut rng, &nodes, is_adult) {
+            assert!(!nodes[index].inner.is_elder());
+        }
+
+        let prefix = *nodes[0].our_prefix();
+        let index = unwrap!(choose_node(&mut rng, &nodes, in_prefix(prefix)));
+        assert!(prefix.matches(&nodes[index].name()));
+
+        let index = unwrap!(choose_node_weighted(&mut rng, &nodes, by_age));
+        assert!(nodes[index].inner.our_age().is_some());
+
+        assert_eq!(choose_node(&mut rng, &nodes, |_| false), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeded the budget of 10 poll steps in phase \"endless\"")]
+    fn poll_budget_exceeded() {
+        let network = Network::new(MIN_SECTION_SIZE, None);
+        let mut nodes = create_connected_nodes(&network, 2);
+        poll_and_resend_with_options(
+            &mut nodes,
+            PollOptions::default()
+                .continue_if(|_| true)
+                .phase("endless")
+                .max_steps(10)
+                .max_wall_time(Duration::from_secs(600)),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeded the budget of 5 poll steps for stop condition \"never\"")]
+    fn poll_stop_condition_budget_exceeded() {
+        let network = Network::new(MIN_SECTION_SIZE, None);
+        let mut nodes = create_connected_nodes(&network, 2);
+        poll_and_resend_with_options(
+            &mut nodes,
+            PollOptions::default()
+                .continue_if(|_| true)
+                .stop_when_within("never", 5, |_| false),
+        );
+    }
 
     #[test]
     fn sanity_check_valid() {
@@ -69,7 +69,7 @@ extern crate unwrap;
 macro_rules! expect_next_event {
     ($node:expr, $pattern:pat) => {
         loop {
-            match $node.inner.try_next_ev() {
+            match $node.try_next_ev() {
                 Ok($pattern) => break,
                 Ok(Event::TimerTicked) => (),
                 other => panic!(
@@ -92,7 +92,7 @@ macro_rules! expect_any_event {
     };
     ($node:expr, $pattern:pat if $guard:expr) => {
         loop {
-            match $node.inner.try_next_ev() {
+            match $node.try_next_ev() {
                 Ok($pattern) if $guard => break,
                 Ok(_) => (),
                 other => panic!(
@@ -110,7 +110,7 @@ macro_rules! expect_any_event {
 /// (ignores ticks). If no pattern given, expects that no event (except ticks) were raised.
 macro_rules! expect_no_event {
     ($node:expr) => {{
-        match $node.inner.try_next_ev() {
+        match $node.try_next_ev() {
             Ok(Event::TimerTicked) => (),
             Err(crossbeam_channel::TryRecvError::Empty) => (),
             other => panic!("Expected no event at {}, got {:?}", $node.name(), other),
@@ -119,7 +119,7 @@ macro_rules! expect_no_event {
 
     ($node:expr, $pattern:pat) => {
         loop {
-            match $node.inner.try_next_ev() {
+            match $node.try_next_ev() {
                 Ok(event @ $pattern) => panic!(
                     "Expected no event matching {} at {}, got {:?}",
                     stringify!($pattern),
@@ -134,4 +134,49 @@ macro_rules! expect_no_event {
     };
 }
 
+/// Expects that the events recorded by the node (see `TestNode::start_recording`) contain events
+/// matching the given patterns, in the given order, though not necessarily consecutive. Panics
+/// otherwise.
+macro_rules! expect_event_sequence {
+    ($node:expr, $($pattern:pat),+ $(,)*) => {{
+        let mut events = $node.recorded_events().iter();
+        $(
+            if !events.any(|recorded| match recorded.event {
+                $pattern => true,
+                _ => false,
+            }) {
+                panic!(
+                    "Expected an event matching {} in sequence at {}, recorded: {:?}",
+                    stringify!($pattern),
+                    $node.name(),
+                    $node.recorded_events()
+                );
+            }
+        )+
+    }};
+}
+
+/// Expects that none of the events recorded by the node (see `TestNode::start_recording`) matches
+/// the given pattern. Panics otherwise.
+macro_rules! expect_no_event_matching {
+    ($node:expr, $pattern:pat) => {
+        if let Some(recorded) =
+            $node
+                .recorded_events()
+                .iter()
+                .find(|recorded| match recorded.event {
+                    $pattern => true,
+                    _ => false,
+                })
+        {
+            panic!(
+                "Expected no event matching {} at {}, got {:?}",
+                stringify!($pattern),
+                $node.name(),
+                recorded
+            );
+        }
+    };
+}
+
 mod mock_network;